@@ -39,8 +39,8 @@ tb_modules = [
 # The first element indicates the name of the ShakeFlow module,
 # and the second element indicates the path of the module in Corundum.
 fpga_tb_module_pairs = [
-    ('rx_checksum_512', 'fpga/common/rtl/rx_checksum'),
-    ('rx_hash_512', 'fpga/common/rtl/rx_hash'),
+    ('rx_checksum', 'fpga/common/rtl/rx_checksum'),
+    ('rx_hash', 'fpga/common/rtl/rx_hash'),
     ('tx_checksum_512', 'fpga/common/rtl/tx_checksum'),
     ('event_mux', 'fpga/common/rtl/event_mux'),
     ('desc_fetch', 'fpga/common/rtl/desc_fetch'),
@@ -566,7 +566,7 @@ elif mode == 'program':
                 if len(x) == 0:
                     source_module = module
                 elif len(x) == 1:
-                    source_module = x[0][0] # e.g. 'rx_checksum_512'
+                    source_module = x[0][0] # e.g. 'tx_checksum_512'
                 else:
                     assert(False)
                 if (shakeflow_dir / f'scripts/rtl/{source_module}.v').is_file():
@@ -602,7 +602,7 @@ elif mode == 'program':
                 if len(x) == 0:
                     source_module = module
                 elif len(x) == 1:
-                    source_module, p = x[0] # e.g. 'rx_checksum_512'
+                    source_module, p = x[0] # e.g. 'tx_checksum_512'
                     run(['cp', shakeflow_dir / f'build/{source_module}_inner.v', corundum_dir / f'{p}_inner.v'])
                     run(['sed', '-i', 
                         f's/{source_module}_inner/{module}_inner/g',
@@ -635,7 +635,7 @@ elif mode == 'program':
                 if len(x) == 0:
                     source_module = module
                 elif len(x) == 1:
-                    source_module = x[0][0] # e.g. 'rx_checksum_512'
+                    source_module = x[0][0] # e.g. 'tx_checksum_512'
                 else:
                     assert(False)
                 if (shakeflow_dir / f'scripts/rtl/{source_module}.v').is_file():
@@ -671,7 +671,7 @@ elif mode == 'program':
                 if len(x) == 0:
                     source_module = module
                 elif len(x) == 1:
-                    source_module, p = x[0] # e.g. 'rx_checksum_512'
+                    source_module, p = x[0] # e.g. 'tx_checksum_512'
                     run(['cp', shakeflow_dir / f'build/{source_module}_inner.v', corundum_dir / f'{p}_inner.v'])
                     run(['sed', '-i', 
                         f's/{source_module}_inner/{module}_inner/g',
@@ -1064,7 +1064,7 @@ elif mode == 'program_per_module':
         if len(x) == 0:
             source_module = module
         elif len(x) == 1:
-            source_module = x[0][0] # e.g. 'rx_checksum_512'
+            source_module = x[0][0] # e.g. 'tx_checksum_512'
         else:
             assert(False)
         if (shakeflow_dir / f'scripts/rtl/{source_module}.v').is_file():
@@ -66,14 +66,12 @@ module rx_checksum #
     output wire                   m_axis_csum_valid
 );
 
-// check configuration
-initial begin
-    if (DATA_WIDTH != 512) begin $error; $finish; end
-    if (KEEP_WIDTH != 64) begin $error; $finish; end
-    if (START_OFFSET != 14) begin $error; $finish; end
-end
-
-rx_checksum_inner r_inner (
+rx_checksum_inner #(
+    .DATA_WIDTH(DATA_WIDTH),
+    .KEEP_WIDTH(KEEP_WIDTH),
+    .START_OFFSET(START_OFFSET)
+)
+r_inner (
     .clk(clk),
     .rst(rst),
 
@@ -1,90 +0,0 @@
-/*
-
-Copyright 2019, The Regents of the University of California.
-All rights reserved.
-
-Redistribution and use in source and binary forms, with or without
-modification, are permitted provided that the following conditions are met:
-
-   1. Redistributions of source code must retain the above copyright notice,
-      this list of conditions and the following disclaimer.
-
-   2. Redistributions in binary form must reproduce the above copyright notice,
-      this list of conditions and the following disclaimer in the documentation
-      and/or other materials provided with the distribution.
-
-THIS SOFTWARE IS PROVIDED BY THE REGENTS OF THE UNIVERSITY OF CALIFORNIA ''AS
-IS'' AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
-IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
-DISCLAIMED. IN NO EVENT SHALL THE REGENTS OF THE UNIVERSITY OF CALIFORNIA OR
-CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
-EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT
-OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS
-INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN
-CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING
-IN ANY WAY OUT OF THE USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY
-OF SUCH DAMAGE.
-
-The views and conclusions contained in the software and documentation are those
-of the authors and should not be interpreted as representing official policies,
-either expressed or implied, of The Regents of the University of California.
-
-*/
-
-// Language: Verilog 2001
-
-`timescale 1ns / 1ps
-
-/*
- * Receive checksum offload module
- */
-module rx_checksum #
-(
-    // Width of AXI stream interfaces in bits
-    parameter DATA_WIDTH = 256,
-    // AXI stream tkeep signal width (words per cycle)
-    parameter KEEP_WIDTH = (DATA_WIDTH/8),
-    // Checksum start offset
-    parameter START_OFFSET = 14
-)
-(
-    input  wire                   clk,
-    input  wire                   rst,
-
-    /*
-     * AXI input
-     */
-    input  wire [DATA_WIDTH-1:0]  s_axis_tdata,
-    input  wire [KEEP_WIDTH-1:0]  s_axis_tkeep,
-    input  wire                   s_axis_tvalid,
-    input  wire                   s_axis_tlast,
-
-    /*
-     * Checksum output
-     */
-    output wire [15:0]            m_axis_csum,
-    output wire                   m_axis_csum_valid
-);
-
-// check configuration
-initial begin
-    if (DATA_WIDTH != 512) begin $error; $finish; end
-    if (KEEP_WIDTH != 64) begin $error; $finish; end
-    if (START_OFFSET != 14) begin $error; $finish; end
-end
-
-rx_checksum_inner r_inner (
-    .clk(clk),
-    .rst(rst),
-
-    .s_axis_tvalid(s_axis_tvalid),
-    .s_axis_tready(s_axis_tready),
-    .s_axis_tdata(s_axis_tdata),
-    .s_axis_tkeep(s_axis_tkeep),
-    .s_axis_tlast(s_axis_tlast),
-
-    .m_axis_csum(m_axis_csum),
-    .m_axis_csum_valid(m_axis_csum_valid)
-);
-
-endmodule
@@ -70,12 +70,11 @@ module rx_hash #
     output wire                   m_axis_hash_valid
 );
 
-initial begin
-    if (DATA_WIDTH != 256) begin $error; $finish; end
-    if (KEEP_WIDTH != 32) begin $error; $finish; end
-end
-
-rx_hash_inner rx_hash_inst (
+rx_hash_inner #(
+    .DATA_WIDTH(DATA_WIDTH),
+    .KEEP_WIDTH(KEEP_WIDTH)
+)
+rx_hash_inst (
     .clk(clk),
     .rst(rst),
 
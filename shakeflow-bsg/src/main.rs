@@ -12,7 +12,8 @@ use shakeflow_std::*;
 pub mod bsg_dataflow;
 pub mod bsg_noc;
 
-fn main() -> Result<(), PackageError> {
+/// Returns the package of the modules to be generated.
+fn package() -> Package {
     let mut package = Package::default();
 
     package.add(bsg_dataflow::bsg_1_to_n_tagged::m::<bool, 10>());
@@ -87,5 +88,14 @@ fn main() -> Result<(), PackageError> {
     package.add(bsg_noc::bsg_wormhole_router_input_control::m::<10, 5>());
     package.add(bsg_noc::bsg_wormhole_router_output_control::m::<10, 4>());
 
+    package
+}
+
+fn main() -> Result<(), PackageError> {
+    // FIRRTL generation renames module instantiations in place, so it takes a package of its own.
+    package().gen_fir(Path::new("./build/fir"))?;
+
+    let package = package();
+    package.gen_dot(Path::new("./build/dot"))?;
     package.gen_vir(Path::new("./build"))
 }
@@ -397,3 +397,126 @@ pub fn m() -> Module<I, O> {
     .loop_feedback()
     .build()
 }
+
+#[cfg(test)]
+mod tests {
+    use shakeflow::sim::{Models, SimError};
+    use shakeflow_std::testbench::*;
+
+    use super::*;
+    use crate::testbench::*;
+
+    /// Model of `dma_client_axis_sink`, which reports each write once both its descriptor and its data
+    /// arrive, without writing to the RAM.
+    fn dma_client_axis_sink() -> Module<DmaClientAxisSinkI, DmaClientAxisSinkO> {
+        composite::<DmaClientAxisSinkI, DmaClientAxisSinkO, _>("dma_client_axis_sink", None, None, |input, k| {
+            let data = input.s_axis_write_data.into_vr(k);
+            let m_axis_write_desc_status = (input.s_axis_write_desc, data)
+                .fsm::<(), UniChannel<Valid<DmaWriteDescStatus<CL_DESC_TABLE_SIZE>>>, _>(
+                    k,
+                    None,
+                    ().into(),
+                    |fwd, _, state| {
+                        let (desc, data) = *fwd;
+                        let fire = desc.valid & data.valid;
+                        let status = DmaWriteDescStatusProj { tag: desc.inner.tag.resize(), error: 0.into() }.into();
+                        let ready = Expr::<Ready>::new(fire);
+                        (Expr::<Valid<_>>::new(fire, status), (ready, ready).into(), state)
+                    },
+                )
+                .buffer(k, Expr::invalid());
+            let ram_wr_cmd = range_map(|_| UniChannel::source(k, Expr::invalid()).into_vr(k));
+            DmaClientAxisSinkO { m_axis_write_desc_status, ram_wr_cmd }
+        })
+        .build()
+    }
+
+    /// Returns whether the queue manager reports the queue of the `i`-th request as full.
+    fn is_full(i: usize) -> bool { i % 4 == 3 }
+
+    /// Runs `cpl_write` on `count` requests with a queue manager and a DMA engine answering its requests,
+    /// and returns the transactions of its outputs.
+    fn simulate(count: usize, mut backpressure: Option<Backpressure>) -> Result<Vec<Vec<Value>>, SimError> {
+        let mut models = Models::default();
+        models.add("dma_psdpram", dma_psdpram::<SEG_COUNT>());
+        models.add("dma_client_axis_sink", dma_client_axis_sink());
+        models.add("priority_encoder", priority_encoder::<PORTS>());
+        let mut sim = Simulator::with_models(&m(), &models)?;
+        sim.reset()?;
+        let mut fork = || backpressure.as_mut().map(Backpressure::fork);
+
+        let mut enable = Constant::new(path("enable", None), true);
+        let mut requests = VrDriver::new(path("s_axis_req", None)).with_backpressure(fork());
+        requests.extend((0..count).map(|i| WReq { sel: bits(i % PORTS), queue: bits(i), tag: bits(i), data: bits(i) }));
+
+        // The queue manager answers each enqueue request with the address of the queue.
+        let mut queue_managers = (0..PORTS)
+            .map(|port| {
+                Responder::new(
+                    VrMonitor::<Req<QUEUE_INDEX_WIDTH, REQ_TAG_WIDTH>>::new(path("m_axis_cpl_enqueue_req", Some(port)))
+                        .with_backpressure(fork()),
+                    VrDriver::new(path("s_axis_cpl_enqueue_resp", Some(port))).with_backpressure(fork()),
+                    |req: &Value| {
+                        let (queue, tag) = (member(req, "queue"), member(req, "tag"));
+                        vec![CplEnqResp {
+                            addr: bits(queue << 12),
+                            tag: bits(tag),
+                            op_tag: bits(queue % 64),
+                            full: is_full(queue),
+                            error: false,
+                        }]
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // The DMA engine reports the completion of each write.
+        let mut dma = Responder::new(
+            VrMonitor::<DmaWriteDescResp>::new(path("m_axis_dma_write_desc", None)).with_backpressure(fork()),
+            UniDriver::new(path("s_axis_dma_write_desc_status", None)).with_backpressure(fork()),
+            |desc: &Value| vec![DmaWriteDescStatus::<DMA_TAG_WIDTH> { tag: bits(member(desc, "tag")), error: bits(0) }],
+        );
+
+        let mut commits = (0..PORTS)
+            .map(|port| {
+                let expected = (0..count).filter(|i| i % PORTS == port && !is_full(*i)).count();
+                VrMonitor::<CplEnqCommit>::new(path("m_axis_cpl_enqueue_commit", Some(port)))
+                    .with_backpressure(fork())
+                    .expect(expected)
+            })
+            .collect::<Vec<_>>();
+        let mut statuses = UniMonitor::<WReqStatus>::new(path("m_axis_req_status", None)).expect(count);
+
+        let mut components: Vec<&mut dyn Component> = vec![&mut enable, &mut requests, &mut dma, &mut statuses];
+        components.extend(queue_managers.iter_mut().map(|component| component as &mut dyn Component));
+        components.extend(commits.iter_mut().map(|component| component as &mut dyn Component));
+        let _ = run(&mut sim, &mut components, 10000)?;
+
+        // Requests to different ports may be answered in different orders, so the transactions of the
+        // shared channels are sorted by a member unique to each request.
+        let sorted = |mut values: Vec<Value>, key: &str| {
+            values.sort_by_key(|value| member(value, key));
+            values
+        };
+        let mut transactions = vec![sorted(statuses.into_values(), "tag"), sorted(dma.into_values(), "dma_addr")];
+        transactions.extend(queue_managers.into_iter().map(Responder::into_values));
+        transactions.extend(commits.into_iter().map(VrMonitor::into_values));
+        Ok(transactions)
+    }
+
+    #[test]
+    fn requests_complete() {
+        let transactions = simulate(48, None).unwrap();
+        let full = (0..48).filter(|i| is_full(*i)).count();
+        assert_eq!(transactions[0].len(), 48);
+        assert_eq!(transactions[0].iter().filter(|status| member(status, "full") == 1).count(), full);
+        assert_eq!(transactions[1].len(), 48 - full);
+        assert_eq!(transactions[2..2 + PORTS].iter().map(Vec::len).sum::<usize>(), 48);
+        assert_eq!(transactions[2 + PORTS..].iter().map(Vec::len).sum::<usize>(), 48 - full);
+    }
+
+    #[test]
+    fn latency_insensitive() {
+        check_latency_insensitive(&[1, 2, 3], 0.3, |backpressure| simulate(48, backpressure)).unwrap();
+    }
+}
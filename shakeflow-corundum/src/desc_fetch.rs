@@ -428,3 +428,159 @@ pub fn m() -> Module<I, O> {
     .loop_feedback()
     .build()
 }
+
+#[cfg(test)]
+mod tests {
+    use shakeflow::sim::{Models, SimError};
+    use shakeflow_std::testbench::*;
+
+    use super::*;
+    use crate::testbench::*;
+
+    /// Model of `dma_client_axis_source`, which sends a descriptor of don't-care data for each read in a
+    /// single beat, without reading from the RAM.
+    fn dma_client_axis_source() -> Module<DmaClientAxisSourceI, DmaClientAxisSourceO> {
+        composite::<DmaClientAxisSourceI, DmaClientAxisSourceO, _>("dma_client_axis_source", None, None, |input, k| {
+            let _ = input.ram_rd_resp.array_map(k, "sink", |ram_rd_resp, k| ram_rd_resp.sink(k));
+            let (m_axis_read_desc_status, m_axis_read_data) =
+                input.s_axis_read_desc.fsm::<(), (
+                    UniChannel<Valid<DmaReadDescStatus<CL_DESC_TABLE_SIZE>>>,
+                    AxisChannel<Desc<AXIS_DATA_WIDTH, AXIS_KEEP_WIDTH>>,
+                ), _>(k, None, ().into(), |fwd, bwd, state| {
+                    let tready = bwd.1.tready;
+                    let status = DmaReadDescStatusProj { tag: fwd.inner.tag.resize(), error: 0.into() }.into();
+                    let data = DescProj { data: Expr::x(), tid: fwd.inner.id, tuser: fwd.inner.user }.into();
+                    let data = AxisValidProj {
+                        inner: AxisValueProj { payload: data, tlast: true.into() }.into(),
+                        tvalid: fwd.valid,
+                    };
+                    let status = Expr::<Valid<_>>::new(fwd.valid & tready, status);
+                    ((status, data.into()).into(), Expr::<Ready>::new(tready), state)
+                });
+            let ram_rd_cmd = range_map(|_| UniChannel::source(k, Expr::invalid()).into_vr(k));
+            DmaClientAxisSourceO {
+                m_axis_read_desc_status: m_axis_read_desc_status.buffer(k, Expr::invalid()),
+                m_axis_read_data,
+                ram_rd_cmd,
+            }
+        })
+        .build()
+    }
+
+    /// Returns whether the queue manager reports the queue of the `i`-th request as empty.
+    fn is_empty(i: usize) -> bool { i % 4 == 3 }
+
+    /// Runs `desc_fetch` on `count` requests with a queue manager and a DMA engine answering its
+    /// requests, and returns the transactions of its outputs.
+    fn simulate(count: usize, mut backpressure: Option<Backpressure>) -> Result<Vec<Vec<Value>>, SimError> {
+        let mut models = Models::default();
+        models.add("dma_psdpram", dma_psdpram::<SEG_COUNT>());
+        models.add("dma_client_axis_source", dma_client_axis_source());
+        models.add("priority_encoder", priority_encoder::<PORTS>());
+        let mut sim = Simulator::with_models(&m(), &models)?;
+        sim.reset()?;
+        let mut fork = || backpressure.as_mut().map(Backpressure::fork);
+
+        let mut enable = Constant::new(path("enable", None), true);
+        let mut requests = VrDriver::new(path("s_axis_req", None)).with_backpressure(fork());
+        requests.extend((0..count).map(|i| RReq { sel: bits(i % PORTS), queue: bits(i), tag: bits(i) }));
+
+        // The queue manager answers each dequeue request with the address of the queue.
+        let mut queue_managers = (0..PORTS)
+            .map(|port| {
+                Responder::new(
+                    VrMonitor::<Req<QUEUE_INDEX_WIDTH, REQ_TAG_WIDTH>>::new(path(
+                        "m_axis_desc_dequeue_req",
+                        Some(port),
+                    ))
+                    .with_backpressure(fork()),
+                    VrDriver::new(path("s_axis_desc_dequeue_resp", Some(port))).with_backpressure(fork()),
+                    |req: &Value| {
+                        let (queue, tag) = (member(req, "queue"), member(req, "tag"));
+                        vec![DescDeqResp {
+                            queue: bits(queue),
+                            ptr: bits(tag),
+                            addr: bits(queue << 12),
+                            block_size: bits(0),
+                            cpl: bits(queue),
+                            tag: bits(tag),
+                            op_tag: bits(queue % 64),
+                            empty: is_empty(queue),
+                            error: false,
+                        }]
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // The DMA engine reports the completion of each read.
+        let mut dma = Responder::new(
+            VrMonitor::<DmaReadDescResp>::new(path("m_axis_dma_read_desc", None)).with_backpressure(fork()),
+            UniDriver::new(path("s_axis_dma_read_desc_status", None)).with_backpressure(fork()),
+            |desc: &Value| vec![DmaReadDescStatus::<DMA_TAG_WIDTH> { tag: bits(member(desc, "tag")), error: bits(0) }],
+        );
+
+        let fetched = (0..count).filter(|i| !is_empty(*i)).count();
+        let mut descs = VrMonitor::<AxisValue<Desc<AXIS_DATA_WIDTH, AXIS_KEEP_WIDTH>>>::new(path("m_axis_desc", None))
+            .with_backpressure(fork())
+            .expect(fetched);
+        let mut commits = (0..PORTS)
+            .map(|port| {
+                let expected = (0..count).filter(|i| i % PORTS == port && !is_empty(*i)).count();
+                VrMonitor::<DescDeqCommit>::new(path("m_axis_desc_dequeue_commit", Some(port)))
+                    .with_backpressure(fork())
+                    .expect(expected)
+            })
+            .collect::<Vec<_>>();
+        let mut statuses = UniMonitor::<RReqStatus>::new(path("m_axis_req_status", None)).expect(count);
+
+        let mut components: Vec<&mut dyn Component> =
+            vec![&mut enable, &mut requests, &mut dma, &mut descs, &mut statuses];
+        components.extend(queue_managers.iter_mut().map(|component| component as &mut dyn Component));
+        components.extend(commits.iter_mut().map(|component| component as &mut dyn Component));
+        let _ = run(&mut sim, &mut components, 10000)?;
+
+        // Requests to different ports may be answered in different orders, so the transactions of the
+        // shared channels are sorted by a member unique to each request.
+        let sorted = |mut values: Vec<Value>, key: fn(&Value) -> usize| {
+            values.sort_by_key(key);
+            values
+        };
+        // The RAM address and tag of a DMA read are the slot in the descriptor table, which depends on the
+        // stalls, so only the DMA address and length are compared.
+        let dma_reads = dma
+            .into_values()
+            .into_iter()
+            .map(|desc| {
+                Value::Struct(
+                    ["dma_addr", "len"].iter().map(|name| (name.to_string(), field(&desc, name).clone())).collect(),
+                )
+            })
+            .collect();
+        let mut transactions = vec![
+            sorted(statuses.into_values(), |status| member(status, "tag")),
+            sorted(dma_reads, |read| member(read, "dma_addr")),
+            sorted(descs.into_values(), |desc| member(field(desc, "payload"), "tid")),
+        ];
+        transactions.extend(queue_managers.into_iter().map(Responder::into_values));
+        transactions.extend(commits.into_iter().map(VrMonitor::into_values));
+        Ok(transactions)
+    }
+
+    #[test]
+    fn requests_complete() {
+        let transactions = simulate(48, None).unwrap();
+        let empty = (0..48).filter(|i| is_empty(*i)).count();
+        assert_eq!(transactions[0].len(), 48);
+        assert_eq!(transactions[0].iter().filter(|status| member(status, "empty") == 1).count(), empty);
+        assert_eq!(transactions[1].len(), 48 - empty);
+        assert_eq!(transactions[2].len(), 48 - empty);
+        assert_eq!(transactions[3..3 + PORTS].iter().map(Vec::len).sum::<usize>(), 48);
+        assert_eq!(transactions[3 + PORTS..].iter().map(Vec::len).sum::<usize>(), 48 - empty);
+    }
+
+    #[test]
+    fn latency_insensitive() {
+        check_latency_insensitive(&[1, 2, 3], 0.3, |backpressure| simulate(48, backpressure)).unwrap();
+    }
+}
@@ -7,7 +7,7 @@
 
 use std::path::Path;
 
-use shakeflow::{Package, PackageError};
+use shakeflow::{Package, PackageError, ParamModule};
 
 mod cmac_pad;
 mod constants;
@@ -23,17 +23,33 @@ mod queue_manager;
 mod rx_checksum;
 mod rx_engine;
 mod rx_hash;
+#[cfg(test)]
+mod testbench;
 mod tx_checksum;
 mod tx_engine;
 mod tx_scheduler_rr;
 mod types;
 
-fn main() -> Result<(), PackageError> {
+/// Returns the package of the modules to be generated.
+fn package() -> Package {
     let mut package = Package::default();
     package.add(cmac_pad::m());
-    package.add(rx_checksum::m("rx_checksum"));
+    package.add_param(ParamModule::new("rx_checksum", &["DATA_WIDTH", "KEEP_WIDTH", "START_OFFSET"]).specialize(
+        &[rx_checksum::DATA_WIDTH, rx_checksum::KEEP_WIDTH, rx_checksum::START_OFFSET],
+        rx_checksum::m("rx_checksum"),
+    ));
     package.add(event_mux::m());
-    package.add(rx_hash::m::<{ constants::rx_hash::DATA_WIDTH }, { constants::rx_hash::KEEP_WIDTH }>("rx_hash"));
+    package.add_param(
+        ParamModule::new("rx_hash", &["DATA_WIDTH", "KEEP_WIDTH"])
+            .specialize(
+                &[constants::rx_hash::DATA_WIDTH, constants::rx_hash::KEEP_WIDTH],
+                rx_hash::m::<{ constants::rx_hash::DATA_WIDTH }, { constants::rx_hash::KEEP_WIDTH }>("rx_hash"),
+            )
+            .specialize(
+                &[constants::rx_hash_512::DATA_WIDTH, constants::rx_hash_512::KEEP_WIDTH],
+                rx_hash::m::<{ constants::rx_hash_512::DATA_WIDTH }, { constants::rx_hash_512::KEEP_WIDTH }>("rx_hash"),
+            ),
+    );
     package.add(tx_checksum::m("tx_checksum"));
     package.add(cpl_op_mux::m::<
         { constants::cpl_op_mux::PORTS },
@@ -76,14 +92,10 @@ fn main() -> Result<(), PackageError> {
         { constants::tx_scheduler_rr::QUEUE_COUNT },
     >());
 
-    // TODO: Some module parameters differ between the tests for its own module and
-    // fpga_core. We currently don't support parameters, so we're destined to duplicate the logic
-    // and generate modules with different parameters for testing both. In the future, we need
-    // to support module parameters and merge that modules.
-    package.add(rx_checksum::m("rx_checksum_512"));
-    package.add(rx_hash::m::<{ constants::rx_hash_512::DATA_WIDTH }, { constants::rx_hash_512::KEEP_WIDTH }>(
-        "rx_hash_512",
-    ));
+    // Some module parameters differ between the tests for its own module and fpga_core, so we generate
+    // modules with different parameters for testing both. The wrapper of `tx_checksum_512` enables
+    // ports which that of `tx_checksum` does not, and `PIPELINE` of the queue managers changes their
+    // structure, so they are separate modules rather than specializations of a `ParamModule`.
     package.add(tx_checksum::m("tx_checksum_512"));
     package.add(queue_manager::m::<
         { constants::rx_queue_manager::PIPELINE },
@@ -176,5 +188,35 @@ fn main() -> Result<(), PackageError> {
         constants::rx_cpl_queue_manager_bitstream::M,
     >("rx_cpl_queue_manager_bitstream"));
 
+    package
+}
+
+fn main() -> Result<(), PackageError> {
+    // FIRRTL generation renames module instantiations in place, so it takes a package of its own.
+    package().gen_fir(Path::new("./build/fir"))?;
+
+    let package = package();
+    package.gen_dot(Path::new("./build/dot"))?;
     package.gen_vir(Path::new("./build"))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn readme_diagram_is_up_to_date() {
+        let path_dir = std::env::temp_dir().join("shakeflow-corundum-dot");
+        let mut package = Package::default();
+        package.add(tx_checksum::m("tx_checksum"));
+        package.gen_dot(&path_dir).unwrap();
+
+        let generated = fs::read_to_string(path_dir.join("tx_checksum.svg")).unwrap();
+        assert!(
+            generated == include_str!("../../img/tx_checksum.svg"),
+            "img/tx_checksum.svg is out of date; copy build/dot/tx_checksum.svg generated by `cargo run --bin shakeflow-corundum`"
+        );
+    }
+}
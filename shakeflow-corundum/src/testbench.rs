@@ -0,0 +1,61 @@
+//! Helpers and models of external modules for the tests of the modules.
+
+use shakeflow::lir::{EndpointNode, EndpointPath};
+use shakeflow::*;
+use shakeflow_std::*;
+
+use crate::types::dma_ram::*;
+
+/// Returns the path of the channel `name`, or of its `index`-th element if it is an array.
+pub fn path(name: &str, index: Option<usize>) -> EndpointPath {
+    std::iter::once(EndpointNode::Field(name.to_string(), None)).chain(index.map(EndpointNode::Index)).collect()
+}
+
+/// Returns the bits of the given width representing `value`.
+pub fn bits<N: Num>(value: usize) -> Bits<N> { Bits::new(usize_to_bitvec(N::WIDTH, value)) }
+
+/// Returns the member `name` of a struct value.
+pub fn field<'a>(value: &'a Value, name: &str) -> &'a Value {
+    let Value::Struct(members) = value else { panic!("{:?} is not a struct", value) };
+    let (_, member) = members.iter().find(|(member, _)| member == name).unwrap_or_else(|| panic!("no member {}", name));
+    member
+}
+
+/// Returns the integer of the member `name` of a struct value.
+pub fn member(value: &Value, name: &str) -> usize {
+    match field(value, name) {
+        Value::Bits(bits) => bits.iter().rev().fold(0, |value, bit| (value << 1) | *bit as usize),
+        _ => panic!("member {} is not bits", name),
+    }
+}
+
+/// Model of `dma_psdpram`, which accepts all writes and answers each read with don't-care data.
+pub fn dma_psdpram<const SEG_COUNT: usize>() -> Module<DmaPsdpramI<SEG_COUNT>, DmaPsdpramO<SEG_COUNT>> {
+    composite::<DmaPsdpramI<SEG_COUNT>, DmaPsdpramO<SEG_COUNT>, _>("dma_psdpram", None, None, |input, k| {
+        let wr_done = input.wr_cmd.array_map(k, "wr", |wr_cmd, k| {
+            let (wr_cmd, fire) = wr_cmd.fire(k);
+            wr_cmd.sink(k);
+            fire
+        });
+        let rd_resp = input.rd_cmd.array_map(k, "rd", |rd_cmd, k| rd_cmd.map(k, |_| Expr::x()).buffer_skid(k));
+        DmaPsdpramO { wr_done, rd_resp }
+    })
+    .build()
+}
+
+/// Model of `priority_encoder` with the priority for LSB.
+pub fn priority_encoder<const N: usize>() -> Module<priority_mux::IC<N>, priority_mux::OC<N>> {
+    composite::<priority_mux::IC<N>, priority_mux::OC<N>, _>(
+        "priority_encoder",
+        Some("input"),
+        Some("output"),
+        |input, k| {
+            input.map(k, |input| {
+                let unencoded = input.unencoded;
+                let encoded = (0..N).rev().fold(Expr::x(), |acc, i| unencoded[i].cond(i.into(), acc));
+                Expr::<Valid<_>>::new(unencoded.any(), priority_mux::OProj { encoded }.into())
+            })
+        },
+    )
+    .build()
+}
@@ -14,7 +14,7 @@ mod virtual_module;
 
 use std::path::Path;
 
-use shakeflow::{Package, PackageError};
+use shakeflow::{Package, PackageError, ParamModule};
 
 fn main() -> Result<(), PackageError> {
     let mut package = Package::default();
@@ -27,6 +27,11 @@ fn main() -> Result<(), PackageError> {
     package.add(set::m::<10>());
     package.add(split::m::<32, 80>("split_test"));
     package.add(split::m::<512, 112>("split_test_small_header"));
+    package.add_param(
+        ParamModule::new("split_param", &["INPUT_WIDTH", "SPLIT_WIDTH"])
+            .specialize(&[256, 112], split::m::<256, 112>("split_param"))
+            .specialize(&[512, 112], split::m::<512, 112>("split_param")),
+    );
     package.add(tree_fold::m());
     package.add(enum_signal::m());
     package.add(virtual_module::feedback_test_m());
@@ -34,5 +39,6 @@ fn main() -> Result<(), PackageError> {
     package.add(virtual_module::read_write_array_test_m());
     package.add(virtual_module::read_write_inline_test_m());
     package.add(::shakeflow_std::cuckoo_table::m());
+    package.gen_dot(Path::new("./build/dot"))?;
     package.gen_vir(Path::new("./build"))
 }
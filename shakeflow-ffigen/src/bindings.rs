@@ -0,0 +1,420 @@
+//! Generates bindings of modules, i.e., `impl_custom_inst!` invocations with the interface types of
+//! their ports.
+//!
+//! # Note
+//!
+//! Ports are grouped into channels as follows.
+//!
+//! - `clk` and `rst` (or `rst_n` for packages with active-low resets) are the clock and reset
+//!   signals of the instantiation.
+//! - `{name}_valid` and `{name}_ready` of the opposite directions, with the ports `{name}_{member}`
+//!   of the same direction as `{name}_valid`, are a `VrChannel` whose data is a struct of the
+//!   members. If `{name}_valid` is `N` bits wide and the other ports are multiples of `N`, they
+//!   are an array of `N` channels.
+//! - Each of the other ports is a `UniChannel`.
+//!
+//! The data and interface types are generic over the parameters in the widths of their ports, so
+//! that the same bindings serve every instantiation. Widths should be integers, parameters, or
+//! multiples of the number of channels by them; other expressions of parameters are rejected.
+
+use std::collections::{BTreeSet, HashMap};
+
+use thiserror::Error;
+
+use crate::verilog::*;
+
+#[allow(missing_docs)]
+#[derive(Debug, Error)]
+pub(crate) enum BindingsError {
+    #[error("width {width} of port {port} of module {module} is an expression of parameters")]
+    ParamExpr { module: String, port: String, width: String },
+}
+
+/// Keywords of Rust, which cannot be field names.
+const KEYWORDS: [&str; 51] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn",
+    "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+    "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "become",
+    "box", "do", "final", "macro", "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+
+/// Valid-ready channel.
+#[derive(Debug)]
+struct VrGroup {
+    /// Name, i.e., the common prefix of the ports.
+    name: String,
+
+    /// Direction of the forward signals.
+    direction: Direction,
+
+    /// Number of channels, if it is an array of channels.
+    count: Option<Expr>,
+
+    /// Members of the data with their widths.
+    members: Vec<(String, Expr)>,
+
+    /// Names of the ports.
+    ports: Vec<String>,
+}
+
+/// Generates bindings of the modules in the sources.
+pub(crate) fn gen_bindings(sources: &[String], modules: &[Module]) -> Result<String, BindingsError> {
+    let mut items = vec![
+        format!(
+            "// Generated by `shakeflow-ffigen` from {}.",
+            sources.iter().map(|s| format!("`{}`", s)).collect::<Vec<_>>().join(", ")
+        ),
+        "use shakeflow::*;\nuse shakeflow_std::*;".to_string(),
+    ];
+
+    for module in modules {
+        items.extend(gen_module(module)?);
+    }
+
+    Ok(items.join("\n\n") + "\n")
+}
+
+/// Generates the data types, interface types and `impl_custom_inst!` invocation of the module.
+fn gen_module(module: &Module) -> Result<Vec<String>, BindingsError> {
+    let has_clkrst = ["clk", "rst"].iter().all(|name| find_bit(module, name, Direction::Input))
+        || ["clk", "rst_n"].iter().all(|name| find_bit(module, name, Direction::Input));
+    let ports = module
+        .ports
+        .iter()
+        .filter(|port| !has_clkrst || !matches!(port.name.as_str(), "clk" | "rst" | "rst_n"))
+        .collect::<Vec<_>>();
+
+    let groups = gen_groups(&ports);
+    let mut items = Vec::new();
+    let mut fields = HashMap::new();
+
+    // Returns the parameter in the width of the port, or an error if the width is any other
+    // expression of parameters.
+    let width_param = |port: &str, width: &Expr| match width {
+        Expr::Param(name) => Ok(Some(name.to_uppercase())),
+        width if width.has_params() => Err(BindingsError::ParamExpr {
+            module: module.name.clone(),
+            port: port.to_string(),
+            width: width.to_rust(),
+        }),
+        _ => Ok(None),
+    };
+
+    for port in &ports {
+        match groups.iter().find(|group| group.ports.contains(&port.name)) {
+            Some(group) if group.ports[0] == port.name => {
+                let mut params = BTreeSet::new();
+                let data = if group.members.is_empty() {
+                    "()".to_string()
+                } else {
+                    let mut members = Vec::new();
+                    for (name, width) in &group.members {
+                        params.extend(width_param(&format!("{}_{}", group.name, name), width)?);
+                        members.push(gen_field(name, &gen_signal_typ(width)));
+                    }
+                    let (decl, args) = gen_generics(module, &params, "");
+                    let data = format!("{}{}", to_camel_case(&module.name), to_camel_case(&group.name));
+                    items.push(format!(
+                        "/// Data of `{}` of `{}`.{}\n#[derive(Debug, Clone, Signal)]\npub struct {}{} {{\n{}\n}}",
+                        group.name,
+                        module.name,
+                        gen_defaults_doc(module, &params),
+                        data,
+                        decl,
+                        members.join("\n")
+                    ));
+                    format!("{}{}", data, args)
+                };
+
+                let typ = match &group.count {
+                    Some(count) => {
+                        params.extend(width_param(&format!("{}_valid", group.name), count)?);
+                        format!("[VrChannel<{}>; {}]", data, count.to_rust())
+                    }
+                    None => format!("VrChannel<{}>", data),
+                };
+                let (direction_params, direction_fields) =
+                    fields.entry(group.direction).or_insert_with(|| (BTreeSet::new(), Vec::new()));
+                direction_params.extend(params);
+                direction_fields.push(gen_field(&group.name, &typ));
+            }
+            Some(_) => {}
+            None => {
+                let typ = format!("UniChannel<{}>", gen_signal_typ(&port.width));
+                let (direction_params, direction_fields) =
+                    fields.entry(port.direction).or_insert_with(|| (BTreeSet::new(), Vec::new()));
+                direction_params.extend(width_param(&port.name, &port.width)?);
+                direction_fields.push(gen_field(&port.name, &typ));
+            }
+        }
+    }
+
+    // Each interface is given in `impl_custom_inst!` with its generics renamed with a prefix, which
+    // should not shadow the parameters of the instantiation method.
+    let [input, output] = [(Direction::Input, "I", "Input", "IN_"), (Direction::Output, "O", "Output", "OUT_")].map(
+        |(direction, suffix, description, prefix)| match fields.remove(&direction) {
+            Some((params, fields)) => {
+                let name = format!("{}{}", to_camel_case(&module.name), suffix);
+                let (decl, _) = gen_generics(module, &params, "");
+                items.push(format!(
+                    "/// {} interface of `{}`.{}\n#[derive(Debug, Interface)]\npub struct {}{} {{\n{}\n}}",
+                    description,
+                    module.name,
+                    gen_defaults_doc(module, &params),
+                    name,
+                    decl,
+                    fields.join("\n")
+                ));
+                if params.is_empty() {
+                    name
+                } else {
+                    format!("{} <> & {}", name, gen_generics(module, &params, prefix).0)
+                }
+            }
+            None => "()".to_string(),
+        },
+    );
+
+    let params = module.params.iter().filter(|param| !param.local).map(|param| param.name.clone()).collect::<Vec<_>>();
+    items.push(format!(
+        "impl_custom_inst! {{\n    {},\n    {},\n    {},\n    <{}>,\n    {},\n}}",
+        input,
+        output,
+        module.name,
+        params.join(", "),
+        has_clkrst
+    ));
+
+    Ok(items)
+}
+
+/// Returns the declaration and the arguments of the const generics for the parameters, e.g.,
+/// `<const SEG_COUNT: usize>` and `<SEG_COUNT>`, in the order of the parameters of the module. The
+/// generics are declared with the prefix, e.g., `<const IN_SEG_COUNT: usize>` for `IN_`.
+fn gen_generics(module: &Module, params: &BTreeSet<String>, prefix: &str) -> (String, String) {
+    if params.is_empty() {
+        return (String::new(), String::new());
+    }
+
+    let mut params = params.iter().collect::<Vec<_>>();
+    params
+        .sort_by_key(|param| module.params.iter().position(|module_param| module_param.name.to_uppercase() == **param));
+    (
+        format!(
+            "<{}>",
+            params.iter().map(|param| format!("const {}{}: usize", prefix, param)).collect::<Vec<_>>().join(", ")
+        ),
+        format!("<{}>", params.iter().map(|param| param.as_str()).collect::<Vec<_>>().join(", ")),
+    )
+}
+
+/// Returns the doc comment lines of the default values of the parameters, if any.
+fn gen_defaults_doc(module: &Module, params: &BTreeSet<String>) -> String {
+    let defaults = module
+        .params
+        .iter()
+        .filter(|param| params.contains(&param.name.to_uppercase()))
+        .map(|param| {
+            format!(
+                "`{} = {}`",
+                param.name.to_uppercase(),
+                param.default.as_ref().map_or_else(|| param.value.clone(), Expr::to_rust)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if defaults.is_empty() {
+        return String::new();
+    }
+    format!("\n///\n/// In `{}`, the parameters default to {}.", module.name, defaults.join(", "))
+}
+
+/// Groups the ports into valid-ready channels. The ports of each channel start with the first port
+/// among them.
+fn gen_groups(ports: &[&Port]) -> Vec<VrGroup> {
+    let find = |name: &str| ports.iter().find(|port| port.name == name);
+
+    let candidates = ports
+        .iter()
+        .filter_map(|valid| {
+            let name = valid.name.strip_suffix("_valid")?;
+            let ready = find(&format!("{}_ready", name)).filter(|ready| ready.direction != valid.direction)?;
+            Some((name, *valid, *ready))
+        })
+        .collect::<Vec<_>>();
+    let is_handshake =
+        |port: &Port| candidates.iter().any(|(_, valid, ready)| valid.name == port.name || ready.name == port.name);
+
+    candidates
+        .iter()
+        .filter_map(|(name, valid, ready)| {
+            // Each data port belongs to the channel of the longest name.
+            let data = ports
+                .iter()
+                .filter(|port| !is_handshake(port))
+                .filter(|port| {
+                    candidates
+                        .iter()
+                        .filter(|(_, valid, _)| valid.direction == port.direction)
+                        .filter(|(name, ..)| port.name.starts_with(&format!("{}_", name)))
+                        .max_by_key(|(name, ..)| name.len())
+                        .map_or(false, |(longest, ..)| longest == name)
+                })
+                .collect::<Vec<_>>();
+
+            let count = Some(valid.width.clone()).filter(|width| *width != Expr::Int(1));
+            if ready.width != valid.width {
+                return None;
+            }
+
+            let members = data
+                .iter()
+                .map(|port| {
+                    let width = match &count {
+                        Some(count) => port.width.divide(count)?,
+                        None => port.width.clone(),
+                    };
+                    Some((port.name[name.len() + 1..].to_string(), width))
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            let group_ports = ports
+                .iter()
+                .filter(|port| {
+                    port.name == valid.name || port.name == ready.name || data.iter().any(|data| data.name == port.name)
+                })
+                .map(|port| port.name.clone())
+                .collect();
+
+            Some(VrGroup { name: name.to_string(), direction: valid.direction, count, members, ports: group_ports })
+        })
+        .collect()
+}
+
+/// Indicates that the module has the 1-bit port of the name and direction.
+fn find_bit(module: &Module, name: &str, direction: Direction) -> bool {
+    module.ports.iter().any(|port| port.name == name && port.direction == direction && port.width == Expr::Int(1))
+}
+
+/// Returns the signal type of the width.
+fn gen_signal_typ(width: &Expr) -> String {
+    match width {
+        Expr::Int(1) => "bool".to_string(),
+        Expr::Int(_) | Expr::Param(_) => format!("Bits<U<{}>>", width.to_rust()),
+        _ => format!("Bits<U<{{ {} }}>>", width.to_rust()),
+    }
+}
+
+/// Returns the field of the struct. Keywords are renamed, and the original name is given by the
+/// `member` attribute.
+fn gen_field(name: &str, typ: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        let field = if name == "type" { "typ".to_string() } else { format!("{}_", name) };
+        format!("    #[member(name = \"{}\")]\n    pub {}: {},", name, field, typ)
+    } else {
+        format!("    pub {}: {},", name, typ)
+    }
+}
+
+/// Converts the name in snake case to camel case, e.g., `DmaPsdpram` for `dma_psdpram`.
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verilog::tests::{DMA_CLIENT_AXIS_SINK, DMA_PSDPRAM};
+
+    fn bindings(src: &str) -> Result<String, BindingsError> {
+        gen_bindings(&["test.v".to_string()], &parse(src).unwrap())
+    }
+
+    /// Returns the `impl_custom_inst!` invocation of the module, with the parameters as given in
+    /// `shakeflow-corundum/src/ffis.rs`.
+    fn custom_inst(input: &str, output: &str, module: &str, params: &str) -> String {
+        format!(
+            "impl_custom_inst! {{\n    {},\n    {},\n    {},\n    <{}>,\n    true,\n}}",
+            input, output, module, params
+        )
+    }
+
+    #[test]
+    fn generates_dma_psdpram() {
+        let bindings = bindings(DMA_PSDPRAM).unwrap();
+
+        assert!(bindings.contains(
+            "pub struct DmaPsdpramWrCmd<const SEG_DATA_WIDTH: usize, const SEG_ADDR_WIDTH: usize, const SEG_BE_WIDTH: \
+             usize> {\n    pub be: Bits<U<SEG_BE_WIDTH>>,\n    pub addr: Bits<U<SEG_ADDR_WIDTH>>,\n    pub data: \
+             Bits<U<SEG_DATA_WIDTH>>,\n}"
+        ));
+        assert!(bindings.contains("pub struct DmaPsdpramRdCmd<const SEG_ADDR_WIDTH: usize> {"));
+        assert!(bindings.contains("pub struct DmaPsdpramRdResp<const SEG_DATA_WIDTH: usize> {"));
+        assert!(bindings.contains(
+            "    pub wr_cmd: [VrChannel<DmaPsdpramWrCmd<SEG_DATA_WIDTH, SEG_ADDR_WIDTH, SEG_BE_WIDTH>>; SEG_COUNT],"
+        ));
+        assert!(bindings.contains("    pub wr_done: UniChannel<Bits<U<SEG_COUNT>>>,"));
+        assert!(bindings.contains("    pub rd_resp: [VrChannel<DmaPsdpramRdResp<SEG_DATA_WIDTH>>; SEG_COUNT],"));
+        assert!(bindings.contains("the parameters default to `SEG_COUNT = 2`, `SEG_DATA_WIDTH = 128`."));
+        assert!(bindings.contains(&custom_inst(
+            "DmaPsdpramI <> & <const IN_SEG_COUNT: usize, const IN_SEG_DATA_WIDTH: usize, const IN_SEG_ADDR_WIDTH: \
+             usize, const IN_SEG_BE_WIDTH: usize>",
+            "DmaPsdpramO <> & <const OUT_SEG_COUNT: usize, const OUT_SEG_DATA_WIDTH: usize>",
+            "dma_psdpram",
+            "SIZE, SEG_COUNT, SEG_DATA_WIDTH, SEG_ADDR_WIDTH, SEG_BE_WIDTH, PIPELINE",
+        )));
+
+        // Clock and reset are not ports of the interfaces.
+        assert!(!bindings.contains("clk") && !bindings.contains("rst"));
+    }
+
+    #[test]
+    fn generates_dma_client_axis_sink() {
+        let bindings = bindings(DMA_CLIENT_AXIS_SINK).unwrap();
+
+        assert!(bindings.contains(
+            "pub struct DmaClientAxisSinkSAxisWriteDesc<const RAM_ADDR_WIDTH: usize, const LEN_WIDTH: usize, const \
+             TAG_WIDTH: usize> {\n    pub ram_addr: Bits<U<RAM_ADDR_WIDTH>>,\n    pub len: Bits<U<LEN_WIDTH>>,\n    pub \
+             tag: Bits<U<TAG_WIDTH>>,\n}"
+        ));
+        assert!(bindings.contains(
+            "    pub s_axis_write_desc: VrChannel<DmaClientAxisSinkSAxisWriteDesc<RAM_ADDR_WIDTH, LEN_WIDTH, TAG_WIDTH>>,"
+        ));
+        assert!(bindings.contains(
+            "    pub ram_wr_cmd: [VrChannel<DmaClientAxisSinkRamWrCmd<SEG_DATA_WIDTH, SEG_ADDR_WIDTH, SEG_BE_WIDTH>>; \
+             SEG_COUNT],"
+        ));
+
+        // The status has no ready signal, and the AXI4-Stream ports have no `_valid` suffix, so their
+        // ports are unidirectional channels.
+        assert!(bindings.contains("    pub m_axis_write_desc_status_valid: UniChannel<bool>,"));
+        assert!(bindings.contains("    pub m_axis_write_desc_status_error: UniChannel<Bits<U<4>>>,"));
+        assert!(bindings.contains("    pub s_axis_write_data_tdata: UniChannel<Bits<U<AXIS_DATA_WIDTH>>>,"));
+
+        let params = "SEG_COUNT, SEG_DATA_WIDTH, SEG_ADDR_WIDTH, SEG_BE_WIDTH, RAM_ADDR_WIDTH, AXIS_DATA_WIDTH, \
+                      AXIS_KEEP_ENABLE, AXIS_KEEP_WIDTH, AXIS_LAST_ENABLE, AXIS_ID_ENABLE, AXIS_DEST_ENABLE, \
+                      AXIS_USER_ENABLE, AXIS_USER_WIDTH, LEN_WIDTH, TAG_WIDTH";
+        assert!(bindings.contains(&format!("    dma_client_axis_sink,\n    <{}>,\n    true,\n}}", params)));
+    }
+
+    #[test]
+    fn rejects_expression_widths() {
+        let src = "module m #(parameter W = 8) (input [W/8-1:0] be, input [W-1:0] data);\nendmodule";
+        let Err(BindingsError::ParamExpr { module, port, width }) = bindings(src) else { panic!() };
+        assert_eq!((module.as_str(), port.as_str(), width.as_str()), ("m", "be", "W / 8"));
+
+        // Multiples of the number of channels are divided into the channels.
+        let src =
+            "module m #(parameter N = 2, parameter W = 8) (input [N*W-1:0] a_data, input [N-1:0] a_valid, output \
+                   [N-1:0] a_ready);\nendmodule";
+        assert!(bindings(src).unwrap().contains("    pub a: [VrChannel<MA<W>>; N],"));
+    }
+}
@@ -0,0 +1,98 @@
+//! Generates bindings of Verilog modules, which are instantiated in ShakeFlow modules by FFI.
+//!
+//! It parses the headers of the modules in Verilog or SystemVerilog sources, and prints the
+//! `impl_custom_inst!` invocations with the interface types of their ports. For example,
+//!
+//! ```text
+//! cargo run --bin shakeflow-ffigen -- --module dma_psdpram dma_psdpram.v > dma_psdpram.rs
+//! ```
+//!
+//! If no `--module` is given, the bindings of all modules in the sources are generated.
+
+// # Tries to deny all lints (`rustc -W help`).
+#![deny(absolute_paths_not_starting_with_crate)]
+#![deny(anonymous_parameters)]
+#![deny(deprecated_in_future)]
+#![deny(explicit_outlives_requirements)]
+#![deny(keyword_idents)]
+#![deny(macro_use_extern_crate)]
+#![deny(missing_debug_implementations)]
+#![deny(non_ascii_idents)]
+#![deny(pointer_structural_match)]
+#![deny(rust_2018_idioms)]
+#![deny(trivial_numeric_casts)]
+#![deny(unaligned_references)]
+#![deny(unsafe_op_in_unsafe_fn)]
+#![deny(unused_extern_crates)]
+#![deny(unused_import_braces)]
+#![deny(unused_qualifications)]
+#![deny(variant_size_differences)]
+#![deny(warnings)]
+//
+#![deny(missing_docs)]
+#![deny(unreachable_pub)]
+
+mod bindings;
+mod verilog;
+
+use std::{env, fs, io};
+
+use thiserror::Error;
+
+use crate::bindings::{gen_bindings, BindingsError};
+use crate::verilog::{parse, ParseError};
+
+#[allow(missing_docs)]
+#[allow(variant_size_differences)]
+#[derive(Debug, Error)]
+enum FfigenError {
+    #[error("usage: shakeflow-ffigen [--module <name>]... <source>...")]
+    Usage,
+
+    #[error("{path}: file system error: {error:?}")]
+    Fs { path: String, error: io::Error },
+
+    #[error("{path}: parse error: {error}")]
+    Parse { path: String, error: ParseError },
+
+    #[error("module {name} is not found")]
+    NoModule { name: String },
+
+    #[error("bindings error: {error}")]
+    Bindings { error: BindingsError },
+}
+
+fn main() -> Result<(), FfigenError> {
+    let mut args = env::args().skip(1);
+    let mut names = Vec::new();
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--module" => names.push(args.next().ok_or(FfigenError::Usage)?),
+            arg if arg.starts_with('-') => return Err(FfigenError::Usage),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        return Err(FfigenError::Usage);
+    }
+
+    let mut modules = Vec::new();
+    for path in &paths {
+        let src = fs::read_to_string(path).map_err(|error| FfigenError::Fs { path: path.clone(), error })?;
+        modules.extend(parse(&src).map_err(|error| FfigenError::Parse { path: path.clone(), error })?);
+    }
+
+    if !names.is_empty() {
+        for name in &names {
+            if !modules.iter().any(|module| module.name == *name) {
+                return Err(FfigenError::NoModule { name: name.clone() });
+            }
+        }
+        modules.retain(|module| names.contains(&module.name));
+    }
+
+    print!("{}", gen_bindings(&paths, &modules).map_err(|error| FfigenError::Bindings { error })?);
+    Ok(())
+}
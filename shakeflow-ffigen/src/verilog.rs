@@ -0,0 +1,1214 @@
+//! Parser of Verilog and SystemVerilog module headers.
+//!
+//! Only the parameters and ports of modules are parsed. Module bodies are skipped, except for the
+//! parameter and port declarations of non-ANSI style modules, e.g., `module m(a, b); input a; ...`.
+
+use std::fmt;
+
+use thiserror::Error;
+
+#[allow(missing_docs)]
+#[derive(Debug, Error)]
+pub(crate) enum ParseError {
+    #[error("line {line}: expected {expected}, found {found}")]
+    Unexpected { line: usize, expected: String, found: String },
+
+    #[error("unexpected end of file")]
+    Eof,
+
+    #[error("line {line}: {what} is not supported")]
+    Unsupported { line: usize, what: String },
+
+    #[error("port {name} of module {module} is not declared")]
+    UndeclaredPort { module: String, name: String },
+}
+
+/// Direction of port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Direction {
+    /// Input.
+    Input,
+
+    /// Output.
+    Output,
+}
+
+/// Port.
+#[derive(Debug, Clone)]
+pub(crate) struct Port {
+    /// Name.
+    pub(crate) name: String,
+
+    /// Direction.
+    pub(crate) direction: Direction,
+
+    /// Width.
+    pub(crate) width: Expr,
+}
+
+/// Parameter.
+#[derive(Debug, Clone)]
+pub(crate) struct Param {
+    /// Name.
+    pub(crate) name: String,
+
+    /// Default value as written in the source.
+    pub(crate) value: String,
+
+    /// Default value, or `None` if it is not an integer, e.g., a string.
+    pub(crate) default: Option<Expr>,
+
+    /// Indicates that it is a `localparam`, which cannot be overridden by instantiations.
+    pub(crate) local: bool,
+}
+
+/// Module header.
+#[derive(Debug, Clone)]
+pub(crate) struct Module {
+    /// Name.
+    pub(crate) name: String,
+
+    /// Parameters.
+    pub(crate) params: Vec<Param>,
+
+    /// Ports.
+    pub(crate) ports: Vec<Port>,
+}
+
+/// Unary operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnaryOp {
+    /// Logical negation: `!`
+    Not,
+
+    /// Bitwise negation: `~`
+    BitNot,
+}
+
+/// Binary operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Mod,
+    /// `**`
+    Pow,
+    /// `<<`
+    Shl,
+    /// `>>`
+    Shr,
+    /// `&`
+    And,
+    /// `|`
+    Or,
+    /// `^`
+    Xor,
+    /// `&&`
+    LogicAnd,
+    /// `||`
+    LogicOr,
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl BinaryOp {
+    /// Returns the operator and its precedence in Rust.
+    fn rust(self) -> (&'static str, u8) {
+        match self {
+            BinaryOp::Mul => ("*", PREC_MUL),
+            BinaryOp::Div => ("/", PREC_MUL),
+            BinaryOp::Mod => ("%", PREC_MUL),
+            BinaryOp::Add => ("+", PREC_ADD),
+            BinaryOp::Sub => ("-", PREC_ADD),
+            BinaryOp::Shl => ("<<", PREC_SHIFT),
+            BinaryOp::Shr => (">>", PREC_SHIFT),
+            BinaryOp::And => ("&", PREC_AND),
+            BinaryOp::Xor => ("^", PREC_XOR),
+            BinaryOp::Or => ("|", PREC_OR),
+            BinaryOp::Eq => ("==", PREC_CMP),
+            BinaryOp::Ne => ("!=", PREC_CMP),
+            BinaryOp::Lt => ("<", PREC_CMP),
+            BinaryOp::Le => ("<=", PREC_CMP),
+            BinaryOp::Gt => (">", PREC_CMP),
+            BinaryOp::Ge => (">=", PREC_CMP),
+            BinaryOp::LogicAnd => ("&&", PREC_LOGIC_AND),
+            BinaryOp::LogicOr => ("||", PREC_LOGIC_OR),
+            BinaryOp::Pow => ("pow", PREC_METHOD),
+        }
+    }
+
+    /// Indicates that the operator returns a boolean value in Rust.
+    fn is_bool(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Le
+                | BinaryOp::Gt
+                | BinaryOp::Ge
+                | BinaryOp::LogicAnd
+                | BinaryOp::LogicOr
+        )
+    }
+}
+
+// Precedences of Rust expressions.
+const PREC_COND: u8 = 0;
+const PREC_LOGIC_OR: u8 = 4;
+const PREC_LOGIC_AND: u8 = 5;
+const PREC_CMP: u8 = 6;
+const PREC_OR: u8 = 7;
+const PREC_XOR: u8 = 8;
+const PREC_AND: u8 = 9;
+const PREC_SHIFT: u8 = 10;
+const PREC_ADD: u8 = 11;
+const PREC_MUL: u8 = 12;
+const PREC_CAST: u8 = 13;
+const PREC_UNARY: u8 = 14;
+const PREC_METHOD: u8 = 15;
+const PREC_ATOM: u8 = 16;
+
+/// Constant expression, e.g., width of port or default value of parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Expr {
+    /// Integer.
+    Int(usize),
+
+    /// Parameter.
+    Param(String),
+
+    /// Unary operation.
+    Unary {
+        /// Operator
+        op: UnaryOp,
+
+        /// Operand
+        inner: Box<Expr>,
+    },
+
+    /// Binary operation.
+    Binary {
+        /// Operator
+        op: BinaryOp,
+
+        /// Lhs
+        lhs: Box<Expr>,
+
+        /// Rhs
+        rhs: Box<Expr>,
+    },
+
+    /// Conditional operator: `cond ? lhs : rhs`
+    Cond {
+        /// Condition
+        cond: Box<Expr>,
+
+        /// Output when the condition is true
+        lhs: Box<Expr>,
+
+        /// Output when the condition is false
+        rhs: Box<Expr>,
+    },
+
+    /// `$clog2(inner)`
+    Clog2(Box<Expr>),
+}
+
+impl Expr {
+    fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Self {
+        Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    }
+
+    /// Returns the width of the range `[msb:lsb]`, e.g., `WIDTH` for `[WIDTH-1:0]`.
+    fn range_width(msb: Expr, lsb: Expr) -> Self {
+        match (msb, lsb) {
+            (Expr::Int(msb), Expr::Int(lsb)) => Expr::Int(msb.abs_diff(lsb) + 1),
+            (Expr::Binary { op: BinaryOp::Sub, lhs, rhs }, Expr::Int(0))
+            | (Expr::Int(0), Expr::Binary { op: BinaryOp::Sub, lhs, rhs })
+                if *rhs == Expr::Int(1) =>
+            {
+                *lhs
+            }
+            (msb, Expr::Int(0)) => Expr::binary(BinaryOp::Add, msb, Expr::Int(1)),
+            (msb, lsb) => Expr::binary(BinaryOp::Add, Expr::binary(BinaryOp::Sub, msb, lsb), Expr::Int(1)),
+        }
+    }
+
+    /// Returns the product of the exprs.
+    fn product(exprs: Vec<Expr>) -> Self {
+        exprs
+            .into_iter()
+            .reduce(|lhs, rhs| match (lhs, rhs) {
+                (Expr::Int(1), expr) | (expr, Expr::Int(1)) => expr,
+                (lhs, rhs) => Expr::binary(BinaryOp::Mul, lhs, rhs),
+            })
+            .unwrap_or(Expr::Int(1))
+    }
+
+    /// Returns the quotient if the expr is a multiple of `divisor`, e.g., `DATA_WIDTH` for
+    /// `SEG_COUNT*DATA_WIDTH` divided by `SEG_COUNT`.
+    pub(crate) fn divide(&self, divisor: &Expr) -> Option<Expr> {
+        if self == divisor {
+            return Some(Expr::Int(1));
+        }
+
+        match (self, divisor) {
+            (Expr::Int(dividend), Expr::Int(divisor)) if *divisor != 0 && dividend % divisor == 0 => {
+                Some(Expr::Int(dividend / divisor))
+            }
+            (Expr::Binary { op: BinaryOp::Mul, lhs, rhs }, _) => {
+                if let Some(quotient) = lhs.divide(divisor) {
+                    Some(Expr::product(vec![quotient, (**rhs).clone()]))
+                } else {
+                    rhs.divide(divisor).map(|quotient| Expr::product(vec![(**lhs).clone(), quotient]))
+                }
+            }
+            (Expr::Binary { op: BinaryOp::Div, lhs, rhs }, _) => {
+                lhs.divide(divisor).map(|quotient| Expr::binary(BinaryOp::Div, quotient, (**rhs).clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Indicates that the expr refers to parameters.
+    pub(crate) fn has_params(&self) -> bool {
+        match self {
+            Expr::Int(_) => false,
+            Expr::Param(_) => true,
+            Expr::Unary { inner, .. } | Expr::Clog2(inner) => inner.has_params(),
+            Expr::Binary { lhs, rhs, .. } => lhs.has_params() || rhs.has_params(),
+            Expr::Cond { cond, lhs, rhs } => cond.has_params() || lhs.has_params() || rhs.has_params(),
+        }
+    }
+
+    /// Returns the expr in Rust, as a `usize` value. Parameters are referred to by their names in
+    /// upper case.
+    pub(crate) fn to_rust(&self) -> String { self.gen_int().0 }
+
+    /// Indicates that the expr is a boolean value in Rust.
+    fn is_bool(&self) -> bool {
+        match self {
+            Expr::Unary { op, .. } => *op == UnaryOp::Not,
+            Expr::Binary { op, .. } => op.is_bool(),
+            _ => false,
+        }
+    }
+
+    /// Returns the `usize` value in Rust with its precedence.
+    fn gen_int(&self) -> (String, u8) {
+        if self.is_bool() {
+            let (expr, prec) = self.gen_bool();
+            return (format!("{} as usize", paren(expr, prec, PREC_CAST)), PREC_CAST);
+        }
+
+        match self {
+            Expr::Int(value) => (value.to_string(), PREC_ATOM),
+            Expr::Param(name) => (name.to_uppercase(), PREC_ATOM),
+            Expr::Unary { inner, .. } => {
+                let (inner, prec) = inner.gen_int();
+                (format!("!{}", paren(inner, prec, PREC_UNARY)), PREC_UNARY)
+            }
+            Expr::Binary { op: BinaryOp::Pow, lhs, rhs } => {
+                let (lhs, lhs_prec) = lhs.gen_int();
+                let (rhs, rhs_prec) = rhs.gen_int();
+                (
+                    format!("{}.pow({} as u32)", paren(lhs, lhs_prec, PREC_ATOM), paren(rhs, rhs_prec, PREC_CAST)),
+                    PREC_METHOD,
+                )
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                let (op, prec) = op.rust();
+                let (lhs, lhs_prec) = lhs.gen_int();
+                let (rhs, rhs_prec) = rhs.gen_int();
+                (format!("{} {} {}", paren(lhs, lhs_prec, prec), op, paren(rhs, rhs_prec, prec + 1)), prec)
+            }
+            Expr::Cond { cond, lhs, rhs } => (
+                format!("if {} {{ {} }} else {{ {} }}", cond.gen_bool().0, lhs.gen_int().0, rhs.gen_int().0),
+                PREC_COND,
+            ),
+            Expr::Clog2(inner) => (format!("clog2({})", inner.gen_int().0), PREC_ATOM),
+        }
+    }
+
+    /// Returns the `bool` value in Rust with its precedence.
+    fn gen_bool(&self) -> (String, u8) {
+        match self {
+            Expr::Unary { op: UnaryOp::Not, inner } if inner.is_bool() => {
+                let (inner, prec) = inner.gen_bool();
+                (format!("!{}", paren(inner, prec, PREC_UNARY)), PREC_UNARY)
+            }
+            Expr::Unary { op: UnaryOp::Not, inner } => {
+                let (inner, prec) = inner.gen_int();
+                (format!("{} == 0", paren(inner, prec, PREC_CMP + 1)), PREC_CMP)
+            }
+            Expr::Binary { op: op @ (BinaryOp::LogicAnd | BinaryOp::LogicOr), lhs, rhs } => {
+                let (op, prec) = op.rust();
+                let (lhs, lhs_prec) = lhs.gen_bool();
+                let (rhs, rhs_prec) = rhs.gen_bool();
+                (format!("{} {} {}", paren(lhs, lhs_prec, prec), op, paren(rhs, rhs_prec, prec + 1)), prec)
+            }
+            Expr::Binary { op, lhs, rhs } if op.is_bool() => {
+                let (op, prec) = op.rust();
+                let (lhs, lhs_prec) = lhs.gen_int();
+                let (rhs, rhs_prec) = rhs.gen_int();
+                (format!("{} {} {}", paren(lhs, lhs_prec, prec + 1), op, paren(rhs, rhs_prec, prec + 1)), prec)
+            }
+            _ => {
+                let (expr, prec) = self.gen_int();
+                (format!("{} != 0", paren(expr, prec, PREC_CMP + 1)), PREC_CMP)
+            }
+        }
+    }
+}
+
+/// Parenthesizes the expr if its precedence is lower than `min`.
+fn paren(expr: String, prec: u8, min: u8) -> String {
+    if prec < min {
+        format!("({})", expr)
+    } else {
+        expr
+    }
+}
+
+/// Token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Identifier or keyword.
+    Ident(String),
+
+    /// System function, e.g., `$clog2`.
+    System(String),
+
+    /// Number, or `None` if it is not representable, e.g., it contains `x` or `z`.
+    Number(Option<usize>, String),
+
+    /// String.
+    Str(String),
+
+    /// Operator or punctuation.
+    Symbol(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Symbol(s) | Token::Number(_, s) => write!(f, "{}", s),
+            Token::System(s) => write!(f, "${}", s),
+            Token::Str(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+/// Operators and punctuations, longest first.
+const SYMBOLS: [&str; 43] = [
+    "<<<", ">>>", "===", "!==", "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "::", "+:", "-:", "#", "(", ")",
+    "[", "]", "{", "}", ",", ";", ":", "=", "+", "-", "*", "/", "%", "<", ">", "!", "~", "&", "|", "^", "?", ".", "@",
+    "'",
+];
+
+/// Splits the source into tokens with their line numbers. Comments, attributes and compiler
+/// directives are skipped.
+fn lex(src: &str) -> Vec<(Token, usize)> {
+    let chars = src.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    let starts_with = |i: usize, s: &str| s.chars().enumerate().all(|(j, c)| chars.get(i + j) == Some(&c));
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if starts_with(i, "//") || c == '`' {
+            while i < chars.len() && chars[i] != '\n' {
+                // Directives may be continued by backslashes.
+                if chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                    line += 1;
+                    i += 1;
+                }
+                i += 1;
+            }
+        } else if starts_with(i, "/*") || (starts_with(i, "(*") && chars.get(i + 2) != Some(&')')) {
+            let end = if c == '/' { "*/" } else { "*)" };
+            i += 2;
+            while i < chars.len() && !starts_with(i, end) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && is_ident(chars[i]) {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), line));
+        } else if c == '\\' {
+            while i < chars.len() && !chars[i].is_whitespace() {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start + 1..i].iter().collect()), line));
+        } else if c == '$' {
+            i += 1;
+            while i < chars.len() && is_ident(chars[i]) {
+                i += 1;
+            }
+            tokens.push((Token::System(chars[start + 1..i].iter().collect()), line));
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 1;
+            tokens.push((Token::Str(chars[start + 1..(i - 1).min(chars.len())].iter().collect()), line));
+        } else if c.is_ascii_digit() || (c == '\'' && chars.get(i + 1).map_or(false, |c| "sSdDhHoObB".contains(*c))) {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '_') {
+                i += 1;
+            }
+            let size_end = i;
+            let mut j = i;
+            while j < chars.len() && (chars[j] == ' ' || chars[j] == '\t') {
+                j += 1;
+            }
+
+            let value = if chars.get(j) == Some(&'\'') {
+                j += 1;
+                if chars.get(j).map_or(false, |c| *c == 's' || *c == 'S') {
+                    j += 1;
+                }
+                let radix = match chars.get(j).map(|c| c.to_ascii_lowercase()) {
+                    Some('d') => 10,
+                    Some('h') => 16,
+                    Some('o') => 8,
+                    Some('b') => 2,
+                    _ => 0,
+                };
+                if radix == 0 {
+                    parse_digits(&chars[start..size_end], 10)
+                } else {
+                    j += 1;
+                    while j < chars.len() && (chars[j] == ' ' || chars[j] == '\t') {
+                        j += 1;
+                    }
+                    let digits_start = j;
+                    while j < chars.len() && (chars[j].is_ascii_alphanumeric() || chars[j] == '_' || chars[j] == '?') {
+                        j += 1;
+                    }
+                    i = j;
+                    parse_digits(&chars[digits_start..j], radix)
+                }
+            } else {
+                parse_digits(&chars[start..size_end], 10)
+            };
+            tokens.push((Token::Number(value, chars[start..i].iter().collect()), line));
+        } else {
+            let symbol = SYMBOLS.iter().find(|symbol| starts_with(i, symbol)).map_or(c.to_string(), |s| s.to_string());
+            i += symbol.chars().count();
+            tokens.push((Token::Symbol(symbol), line));
+        }
+    }
+
+    tokens
+}
+
+/// Parses the digits in the radix, ignoring underscores.
+fn parse_digits(digits: &[char], radix: u32) -> Option<usize> {
+    let digits = digits.iter().filter(|c| **c != '_').collect::<String>();
+    usize::from_str_radix(&digits, radix).ok()
+}
+
+/// Parses the modules in the source.
+pub(crate) fn parse(src: &str) -> Result<Vec<Module>, ParseError> {
+    let mut parser = Parser { tokens: lex(src), pos: 0 };
+    let mut modules = Vec::new();
+
+    while let Some(token) = parser.next() {
+        if token == Token::Ident("module".to_string()) || token == Token::Ident("macromodule".to_string()) {
+            modules.push(parser.module()?);
+        }
+    }
+
+    Ok(modules)
+}
+
+/// Keywords of data types, which are skipped in declarations.
+const TYPE_KEYWORDS: [&str; 15] = [
+    "wire",
+    "reg",
+    "logic",
+    "var",
+    "tri",
+    "bit",
+    "signed",
+    "unsigned",
+    "integer",
+    "int",
+    "longint",
+    "shortint",
+    "byte",
+    "uwire",
+    "automatic",
+];
+
+#[derive(Debug)]
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos).map(|(token, _)| token) }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> { self.tokens.get(self.pos + n).map(|(token, _)| token) }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn line(&self) -> usize { self.tokens.get(self.pos).or_else(|| self.tokens.last()).map_or(0, |(_, line)| *line) }
+
+    fn is_symbol(&self, symbol: &str) -> bool { matches!(self.peek(), Some(Token::Symbol(s)) if s == symbol) }
+
+    fn is_keyword(&self, keyword: &str) -> bool { matches!(self.peek(), Some(Token::Ident(s)) if s == keyword) }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let is_symbol = self.is_symbol(symbol);
+        if is_symbol {
+            self.pos += 1;
+        }
+        is_symbol
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let is_keyword = self.is_keyword(keyword);
+        if is_keyword {
+            self.pos += 1;
+        }
+        is_keyword
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, ParseError> {
+        match self.peek() {
+            Some(token) => Err(ParseError::Unexpected {
+                line: self.line(),
+                expected: expected.to_string(),
+                found: token.to_string(),
+            }),
+            None => Err(ParseError::Eof),
+        }
+    }
+
+    fn unsupported<T>(&self, what: &str) -> Result<T, ParseError> {
+        Err(ParseError::Unsupported { line: self.line(), what: what.to_string() })
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", symbol))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("identifier"),
+        }
+    }
+
+    /// Skips tokens until the symbol at the top level of brackets, which is not consumed.
+    fn skip_until(&mut self, symbols: &[&str]) -> Vec<Token> {
+        let mut skipped = Vec::new();
+        let mut depth = 0usize;
+
+        while let Some(token) = self.peek() {
+            if let Token::Symbol(symbol) = token {
+                if depth == 0 && symbols.contains(&symbol.as_str()) {
+                    break;
+                }
+                match symbol.as_str() {
+                    "(" | "[" | "{" => depth += 1,
+                    ")" | "]" | "}" => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            skipped.push(self.next().unwrap());
+        }
+
+        skipped
+    }
+
+    /// Parses the module after the `module` keyword.
+    fn module(&mut self) -> Result<Module, ParseError> {
+        let _ = self.eat_keyword("automatic") || self.eat_keyword("static");
+        let name = self.ident()?;
+        let mut module = Module { name, params: Vec::new(), ports: Vec::new() };
+
+        while self.eat_keyword("import") {
+            let _ = self.skip_until(&[";"]);
+            self.expect_symbol(";")?;
+        }
+
+        if self.eat_symbol("#") {
+            self.expect_symbol("(")?;
+            let mut local = false;
+            while !self.eat_symbol(")") {
+                let param = self.param_decl(local, &[",", ")"])?;
+                local = param.local;
+                module.params.push(param);
+                let _ = self.eat_symbol(",");
+            }
+        }
+
+        // Parameters in the body are local if the module has a parameter list.
+        let has_param_list = !module.params.is_empty();
+
+        // Names of the ports in the non-ANSI style.
+        let mut port_names = Vec::new();
+
+        if self.eat_symbol("(") && !self.eat_symbol(")") {
+            if matches!(self.peek(), Some(Token::Ident(name)) if !is_direction(name)) {
+                loop {
+                    if self.is_symbol(".") {
+                        return self.unsupported("port expression");
+                    }
+                    port_names.push(self.ident()?);
+                    if self.eat_symbol(")") {
+                        break;
+                    }
+                    self.expect_symbol(",")?;
+                }
+            } else {
+                let mut last = None;
+                loop {
+                    let port = self.ansi_port(last.take())?;
+                    module.ports.push(port.clone());
+                    last = Some(port);
+                    if self.eat_symbol(")") {
+                        break;
+                    }
+                    self.expect_symbol(",")?;
+                }
+            }
+        }
+        self.expect_symbol(";")?;
+
+        // Parameters and ports declared in the body.
+        let mut ports = Vec::new();
+
+        loop {
+            match self.peek().cloned() {
+                None => return Err(ParseError::Eof),
+                Some(Token::Ident(keyword)) => match keyword.as_str() {
+                    "endmodule" => {
+                        self.pos += 1;
+                        break;
+                    }
+                    "function" | "task" => {
+                        let end = format!("end{}", keyword);
+                        while !self.eat_keyword(&end) {
+                            if self.next().is_none() {
+                                return Err(ParseError::Eof);
+                            }
+                        }
+                    }
+                    "parameter" | "localparam" if !port_names.is_empty() => {
+                        let local = keyword == "localparam" || has_param_list;
+                        self.pos += 1;
+                        loop {
+                            module.params.push(self.param_decl(local, &[",", ";"])?);
+                            if self.eat_symbol(";") {
+                                break;
+                            }
+                            self.expect_symbol(",")?;
+                        }
+                    }
+                    "input" | "output" | "inout" if !port_names.is_empty() => {
+                        let direction = self.direction()?;
+                        let width = self.data_type()?;
+                        loop {
+                            ports.push(Port { name: self.ident()?, direction, width: width.clone() });
+                            if self.eat_symbol(";") {
+                                break;
+                            }
+                            self.expect_symbol(",")?;
+                        }
+                    }
+                    _ => self.skip_statement(),
+                },
+                Some(_) => self.skip_statement(),
+            }
+        }
+
+        for name in port_names {
+            let port = ports.iter().find(|port| port.name == name).cloned();
+            let port = port.ok_or_else(|| ParseError::UndeclaredPort { module: module.name.clone(), name })?;
+            module.ports.push(port);
+        }
+
+        Ok(module)
+    }
+
+    /// Skips the statement in the module body until `;`, or `endmodule`.
+    fn skip_statement(&mut self) {
+        while let Some(token) = self.peek() {
+            if *token == Token::Ident("endmodule".to_string()) {
+                return;
+            }
+            if self.next() == Some(Token::Symbol(";".to_string())) {
+                return;
+            }
+        }
+    }
+
+    /// Parses the parameter declaration, e.g., `parameter integer WIDTH = 8`, which ends with one of
+    /// the `ends`.
+    fn param_decl(&mut self, mut local: bool, ends: &[&str]) -> Result<Param, ParseError> {
+        if self.eat_keyword("localparam") {
+            local = true;
+        } else if self.eat_keyword("parameter") {
+            local = false;
+        }
+        if self.is_keyword("type") {
+            return self.unsupported("type parameter");
+        }
+
+        // Skips the data type. The name is the last identifier before the default value.
+        let mut name = None;
+        loop {
+            match self.peek() {
+                Some(Token::Ident(ident)) => {
+                    name = Some(ident.clone());
+                    self.pos += 1;
+                }
+                Some(Token::Symbol(symbol)) if symbol == "[" => {
+                    if matches!(&name, Some(name) if !TYPE_KEYWORDS.contains(&name.as_str())) {
+                        return self.unsupported("unpacked array parameter");
+                    }
+                    let _ = self.skip_until(&["]"]);
+                    self.expect_symbol("]")?;
+                }
+                _ => break,
+            }
+        }
+        let name = match name {
+            Some(name) => name,
+            None => return self.unexpected("parameter name"),
+        };
+
+        if !self.eat_symbol("=") {
+            return Ok(Param { name, value: String::new(), default: None, local });
+        }
+
+        let value = self.skip_until(ends);
+        let mut parser = Parser { tokens: value.iter().map(|token| (token.clone(), self.line())).collect(), pos: 0 };
+        let default = parser.expr().ok().filter(|_| parser.peek().is_none());
+        let value = value.iter().map(|token| token.to_string()).collect::<Vec<_>>().join(" ");
+
+        Ok(Param { name, value, default, local })
+    }
+
+    fn direction(&mut self) -> Result<Direction, ParseError> {
+        if self.eat_keyword("input") {
+            Ok(Direction::Input)
+        } else if self.eat_keyword("output") {
+            Ok(Direction::Output)
+        } else if self.is_keyword("inout") {
+            self.unsupported("inout port")
+        } else {
+            self.unexpected("port direction")
+        }
+    }
+
+    /// Parses the data type of the port, and returns its width.
+    fn data_type(&mut self) -> Result<Expr, ParseError> {
+        while let Some(Token::Ident(keyword)) = self.peek() {
+            if !TYPE_KEYWORDS.contains(&keyword.as_str()) {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        // User-defined types and interfaces are followed by the port names.
+        let is_user_type = match self.peek_nth(1) {
+            Some(Token::Ident(_)) => true,
+            Some(Token::Symbol(symbol)) => symbol == "." || symbol == "::",
+            _ => false,
+        };
+        if matches!(self.peek(), Some(Token::Ident(_))) && is_user_type {
+            return self.unsupported("port of user-defined type or interface");
+        }
+
+        let mut widths = Vec::new();
+        while self.eat_symbol("[") {
+            let msb = self.expr()?;
+            self.expect_symbol(":")?;
+            let lsb = self.expr()?;
+            self.expect_symbol("]")?;
+            widths.push(Expr::range_width(msb, lsb));
+        }
+
+        Ok(Expr::product(widths))
+    }
+
+    /// Parses the port in the ANSI style. If the direction is omitted, the port has the same
+    /// direction and width as the `last` port.
+    fn ansi_port(&mut self, last: Option<Port>) -> Result<Port, ParseError> {
+        let (direction, width) = match (self.peek().cloned(), last) {
+            (Some(Token::Ident(name)), _) if is_direction(&name) => {
+                let direction = self.direction()?;
+                (direction, self.data_type()?)
+            }
+            (Some(Token::Ident(name)), Some(last)) if !TYPE_KEYWORDS.contains(&name.as_str()) => {
+                (last.direction, last.width)
+            }
+            (_, Some(last)) => (last.direction, self.data_type()?),
+            (_, None) => return self.unexpected("port direction"),
+        };
+
+        let name = self.ident()?;
+        if self.is_symbol("[") {
+            return self.unsupported("unpacked array port");
+        }
+        if self.eat_symbol("=") {
+            let _ = self.skip_until(&[",", ")"]);
+        }
+
+        Ok(Port { name, direction, width })
+    }
+
+    /// Parses the constant expr.
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let cond = self.binary_expr(0)?;
+        if !self.eat_symbol("?") {
+            return Ok(cond);
+        }
+
+        let lhs = self.expr()?;
+        self.expect_symbol(":")?;
+        let rhs = self.expr()?;
+        Ok(Expr::Cond { cond: Box::new(cond), lhs: Box::new(lhs), rhs: Box::new(rhs) })
+    }
+
+    /// Parses the binary operations whose precedences are at least `level`.
+    fn binary_expr(&mut self, level: usize) -> Result<Expr, ParseError> {
+        const LEVELS: [&[(&str, BinaryOp)]; 10] = [
+            &[("||", BinaryOp::LogicOr)],
+            &[("&&", BinaryOp::LogicAnd)],
+            &[("|", BinaryOp::Or)],
+            &[("^", BinaryOp::Xor)],
+            &[("&", BinaryOp::And)],
+            &[("==", BinaryOp::Eq), ("===", BinaryOp::Eq), ("!=", BinaryOp::Ne), ("!==", BinaryOp::Ne)],
+            &[("<", BinaryOp::Lt), ("<=", BinaryOp::Le), (">", BinaryOp::Gt), (">=", BinaryOp::Ge)],
+            &[("<<", BinaryOp::Shl), ("<<<", BinaryOp::Shl), (">>", BinaryOp::Shr), (">>>", BinaryOp::Shr)],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Mod)],
+        ];
+
+        if level == LEVELS.len() {
+            return self.pow_expr();
+        }
+
+        let mut lhs = self.binary_expr(level + 1)?;
+        while let Some((_, op)) = LEVELS[level].iter().find(|(symbol, _)| self.is_symbol(symbol)) {
+            self.pos += 1;
+            let rhs = self.binary_expr(level + 1)?;
+            lhs = Expr::binary(*op, lhs, rhs);
+        }
+
+        Ok(lhs)
+    }
+
+    fn pow_expr(&mut self) -> Result<Expr, ParseError> {
+        let lhs = self.unary_expr()?;
+        if self.eat_symbol("**") {
+            let rhs = self.pow_expr()?;
+            return Ok(Expr::binary(BinaryOp::Pow, lhs, rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr, ParseError> {
+        if self.eat_symbol("!") {
+            return Ok(Expr::Unary { op: UnaryOp::Not, inner: Box::new(self.unary_expr()?) });
+        }
+        if self.eat_symbol("~") {
+            return Ok(Expr::Unary { op: UnaryOp::BitNot, inner: Box::new(self.unary_expr()?) });
+        }
+        if self.eat_symbol("+") {
+            return self.unary_expr();
+        }
+        if self.is_symbol("-") {
+            return self.unsupported("negative value");
+        }
+
+        match self.next() {
+            Some(Token::Number(Some(value), _)) => Ok(Expr::Int(value)),
+            Some(Token::Ident(mut name)) => {
+                // Parameters in packages, e.g., `pkg::WIDTH`, are referred to by their names.
+                while self.eat_symbol("::") {
+                    name = self.ident()?;
+                }
+                Ok(Expr::Param(name))
+            }
+            Some(Token::System(name)) if name == "clog2" => {
+                self.expect_symbol("(")?;
+                let inner = self.expr()?;
+                self.expect_symbol(")")?;
+                Ok(Expr::Clog2(Box::new(inner)))
+            }
+            Some(Token::Symbol(symbol)) if symbol == "(" => {
+                let inner = self.expr()?;
+                self.expect_symbol(")")?;
+                Ok(inner)
+            }
+            _ => {
+                self.pos -= 1;
+                self.unexpected("constant expression")
+            }
+        }
+    }
+}
+
+fn is_direction(name: &str) -> bool { matches!(name, "input" | "output" | "inout") }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Header of `dma_psdpram` of Corundum.
+    pub(crate) const DMA_PSDPRAM: &str = r#"
+module dma_psdpram #
+(
+    parameter SIZE = 4096,
+    parameter SEG_COUNT = 2,
+    parameter SEG_DATA_WIDTH = 128,
+    parameter SEG_ADDR_WIDTH = $clog2(SIZE/(SEG_COUNT*SEG_DATA_WIDTH/8)),
+    parameter SEG_BE_WIDTH = SEG_DATA_WIDTH/8,
+    parameter PIPELINE = 2
+)
+(
+    input  wire                                clk,
+    input  wire                                rst,
+
+    /*
+     * Write port
+     */
+    input  wire [SEG_COUNT*SEG_BE_WIDTH-1:0]   wr_cmd_be,
+    input  wire [SEG_COUNT*SEG_ADDR_WIDTH-1:0] wr_cmd_addr,
+    input  wire [SEG_COUNT*SEG_DATA_WIDTH-1:0] wr_cmd_data,
+    input  wire [SEG_COUNT-1:0]                wr_cmd_valid,
+    output wire [SEG_COUNT-1:0]                wr_cmd_ready,
+    output wire [SEG_COUNT-1:0]                wr_done,
+
+    /*
+     * Read port
+     */
+    input  wire [SEG_COUNT*SEG_ADDR_WIDTH-1:0] rd_cmd_addr,
+    input  wire [SEG_COUNT-1:0]                rd_cmd_valid,
+    output wire [SEG_COUNT-1:0]                rd_cmd_ready,
+    output wire [SEG_COUNT*SEG_DATA_WIDTH-1:0] rd_resp_data,
+    output wire [SEG_COUNT-1:0]                rd_resp_valid,
+    input  wire [SEG_COUNT-1:0]                rd_resp_ready
+);
+endmodule
+"#;
+
+    /// Header of `dma_client_axis_sink` of Corundum, with the ID and destination widths local as
+    /// they are not given by the instantiations in `shakeflow-corundum`.
+    pub(crate) const DMA_CLIENT_AXIS_SINK: &str = r#"
+module dma_client_axis_sink #
+(
+    parameter SEG_COUNT = 2,
+    parameter SEG_DATA_WIDTH = 64,
+    parameter SEG_ADDR_WIDTH = 8,
+    parameter SEG_BE_WIDTH = SEG_DATA_WIDTH/8,
+    parameter RAM_ADDR_WIDTH = SEG_ADDR_WIDTH+$clog2(SEG_COUNT)+$clog2(SEG_BE_WIDTH),
+    parameter AXIS_DATA_WIDTH = SEG_DATA_WIDTH*SEG_COUNT/2,
+    parameter AXIS_KEEP_ENABLE = (AXIS_DATA_WIDTH>8),
+    parameter AXIS_KEEP_WIDTH = (AXIS_DATA_WIDTH/8),
+    parameter AXIS_LAST_ENABLE = 1,
+    parameter AXIS_ID_ENABLE = 0,
+    localparam AXIS_ID_WIDTH = 8,
+    parameter AXIS_DEST_ENABLE = 0,
+    localparam AXIS_DEST_WIDTH = 8,
+    parameter AXIS_USER_ENABLE = 1,
+    parameter AXIS_USER_WIDTH = 1,
+    parameter LEN_WIDTH = 16,
+    parameter TAG_WIDTH = 8
+)
+(
+    input  wire                                clk,
+    input  wire                                rst,
+
+    /*
+     * AXI write descriptor input
+     */
+    input  wire [RAM_ADDR_WIDTH-1:0]           s_axis_write_desc_ram_addr,
+    input  wire [LEN_WIDTH-1:0]                s_axis_write_desc_len,
+    input  wire [TAG_WIDTH-1:0]                s_axis_write_desc_tag,
+    input  wire                                s_axis_write_desc_valid,
+    output wire                                s_axis_write_desc_ready,
+
+    /*
+     * AXI write descriptor status output
+     */
+    output wire [LEN_WIDTH-1:0]                m_axis_write_desc_status_len,
+    output wire [TAG_WIDTH-1:0]                m_axis_write_desc_status_tag,
+    output wire [AXIS_ID_WIDTH-1:0]            m_axis_write_desc_status_id,
+    output wire [AXIS_DEST_WIDTH-1:0]          m_axis_write_desc_status_dest,
+    output wire [AXIS_USER_WIDTH-1:0]          m_axis_write_desc_status_user,
+    output wire [3:0]                          m_axis_write_desc_status_error,
+    output wire                                m_axis_write_desc_status_valid,
+
+    /*
+     * AXI stream write data input
+     */
+    input  wire [AXIS_DATA_WIDTH-1:0]          s_axis_write_data_tdata,
+    input  wire [AXIS_KEEP_WIDTH-1:0]          s_axis_write_data_tkeep,
+    input  wire                                s_axis_write_data_tvalid,
+    output wire                                s_axis_write_data_tready,
+    input  wire                                s_axis_write_data_tlast,
+    input  wire [AXIS_ID_WIDTH-1:0]            s_axis_write_data_tid,
+    input  wire [AXIS_DEST_WIDTH-1:0]          s_axis_write_data_tdest,
+    input  wire [AXIS_USER_WIDTH-1:0]          s_axis_write_data_tuser,
+
+    /*
+     * RAM interface
+     */
+    output wire [SEG_COUNT*SEG_BE_WIDTH-1:0]   ram_wr_cmd_be,
+    output wire [SEG_COUNT*SEG_ADDR_WIDTH-1:0] ram_wr_cmd_addr,
+    output wire [SEG_COUNT*SEG_DATA_WIDTH-1:0] ram_wr_cmd_data,
+    output wire [SEG_COUNT-1:0]                ram_wr_cmd_valid,
+    input  wire [SEG_COUNT-1:0]                ram_wr_cmd_ready,
+    input  wire [SEG_COUNT-1:0]                ram_wr_done,
+
+    /*
+     * Configuration
+     */
+    input  wire                                enable,
+    input  wire                                abort
+);
+endmodule
+"#;
+
+    fn param_names(module: &Module, local: bool) -> Vec<&str> {
+        module.params.iter().filter(|param| param.local == local).map(|param| param.name.as_str()).collect()
+    }
+
+    fn port<'a>(module: &'a Module, name: &str) -> &'a Port {
+        module.ports.iter().find(|port| port.name == name).unwrap()
+    }
+
+    #[test]
+    fn parses_dma_psdpram() {
+        let modules = parse(DMA_PSDPRAM).unwrap();
+        assert_eq!(modules.len(), 1);
+        let module = &modules[0];
+
+        assert_eq!(module.name, "dma_psdpram");
+        assert_eq!(param_names(module, false), [
+            "SIZE",
+            "SEG_COUNT",
+            "SEG_DATA_WIDTH",
+            "SEG_ADDR_WIDTH",
+            "SEG_BE_WIDTH",
+            "PIPELINE"
+        ]);
+        assert_eq!(module.params[0].default, Some(Expr::Int(4096)));
+        assert_eq!(module.params[4].value, "SEG_DATA_WIDTH / 8");
+        assert_eq!(module.params[4].default.as_ref().map(Expr::to_rust).as_deref(), Some("SEG_DATA_WIDTH / 8"));
+
+        assert_eq!(module.ports.len(), 14);
+        let wr_cmd_be = port(module, "wr_cmd_be");
+        assert_eq!(wr_cmd_be.direction, Direction::Input);
+        assert_eq!(wr_cmd_be.width.to_rust(), "SEG_COUNT * SEG_BE_WIDTH");
+        assert_eq!(
+            wr_cmd_be.width.divide(&Expr::Param("SEG_COUNT".to_string())),
+            Some(Expr::Param("SEG_BE_WIDTH".to_string()))
+        );
+        assert_eq!(port(module, "rd_resp_ready").direction, Direction::Input);
+        assert_eq!(port(module, "rd_resp_valid").direction, Direction::Output);
+        assert_eq!(port(module, "clk").width, Expr::Int(1));
+    }
+
+    #[test]
+    fn parses_dma_client_axis_sink() {
+        let modules = parse(DMA_CLIENT_AXIS_SINK).unwrap();
+        let module = &modules[0];
+
+        assert_eq!(module.name, "dma_client_axis_sink");
+        assert_eq!(param_names(module, false), [
+            "SEG_COUNT",
+            "SEG_DATA_WIDTH",
+            "SEG_ADDR_WIDTH",
+            "SEG_BE_WIDTH",
+            "RAM_ADDR_WIDTH",
+            "AXIS_DATA_WIDTH",
+            "AXIS_KEEP_ENABLE",
+            "AXIS_KEEP_WIDTH",
+            "AXIS_LAST_ENABLE",
+            "AXIS_ID_ENABLE",
+            "AXIS_DEST_ENABLE",
+            "AXIS_USER_ENABLE",
+            "AXIS_USER_WIDTH",
+            "LEN_WIDTH",
+            "TAG_WIDTH",
+        ]);
+        assert_eq!(param_names(module, true), ["AXIS_ID_WIDTH", "AXIS_DEST_WIDTH"]);
+
+        assert_eq!(module.ports.len(), 30);
+        assert_eq!(port(module, "m_axis_write_desc_status_error").width, Expr::Int(4));
+        assert_eq!(port(module, "s_axis_write_desc_ram_addr").width, Expr::Param("RAM_ADDR_WIDTH".to_string()));
+        assert_eq!(port(module, "ram_wr_done").direction, Direction::Input);
+    }
+
+    #[test]
+    fn parses_non_ansi_ports() {
+        let src =
+            "module m(a, b);\n  parameter W = 4;\n  input [W-1:0] a;\n  output reg b;\n  assign b = &a;\nendmodule";
+        let module = &parse(src).unwrap()[0];
+        assert_eq!(param_names(module, false), ["W"]);
+        assert_eq!(port(module, "a").width.to_rust(), "W");
+        assert_eq!(port(module, "b").direction, Direction::Output);
+    }
+
+    #[test]
+    fn rejects_unsupported_headers() {
+        assert!(matches!(parse("module m(a); input a;"), Err(ParseError::Eof)));
+        assert!(matches!(parse("module m(a, b); input a; endmodule"), Err(ParseError::UndeclaredPort { .. })));
+        assert!(matches!(parse("module m #(parameter type T = logic) ();"), Err(ParseError::Unsupported { .. })));
+    }
+}
@@ -62,7 +62,17 @@ pub fn derive(input: TokenStream) -> TokenStream {
         let name = f.ident.as_ref().unwrap();
         let ty = &f.ty;
         let symbol = get_member_symbol(&f.attrs, name).unwrap();
-        quote! { let #name = <#ty>::try_from_inner(_inner.remove(#symbol).unwrap().1)? }
+        quote! {
+            let (__sep, #name) = _inner.remove(#symbol).unwrap();
+            let #name = <#ty>::try_from_inner(#name)
+                .map_err(|error| error.nested(lir::EndpointNode::Field(#symbol.to_string(), __sep)))?
+        }
+    });
+
+    // symbols of fields, which should be the keys of the struct interface in `try_from_inner`.
+    let symbols = fields.iter().map(|f| {
+        let name = f.ident.as_ref().unwrap();
+        get_member_symbol(&f.attrs, name).unwrap()
     });
 
     // fields for `try_into_inner`.
@@ -79,6 +89,12 @@ pub fn derive(input: TokenStream) -> TokenStream {
         }
     });
 
+    // fields for `check_protocol`.
+    let check_protocol_fields = fields.iter().map(|f| {
+        let name = f.ident.as_ref().unwrap();
+        quote! { #name: self.#name.check_protocol(k) }
+    });
+
     let expanded = quote! {
         #[allow(unused_braces, missing_docs)]
         #[derive(Debug, Clone, Signal)]
@@ -100,13 +116,15 @@ pub fn derive(input: TokenStream) -> TokenStream {
                 lir::InterfaceTyp::Struct(_inner)
             }
             fn try_from_inner(interface: lir::Interface) -> Result<Self, shakeflow::hir::InterfaceError> {
+                const SYMBOLS: &[&str] = &[#(#symbols,)*];
                 match interface {
-                    lir::Interface::Struct(mut _inner) => {
+                    lir::Interface::Struct(mut _inner)
+                        if _inner.len() == SYMBOLS.len() && SYMBOLS.iter().all(|symbol| _inner.contains_key(*symbol)) =>
+                    {
                         #(#try_from_inner_fields;)*
-                        assert!(_inner.is_empty(), "internal compiler error");
                         Ok(Self { #(#names,)* })
                     }
-                    _ => todo!(),
+                    interface => Err(shakeflow::hir::typ_mismatch::<Self>(&interface)),
                 }
             }
             fn try_into_inner(self) -> Result<lir::Interface, shakeflow::hir::InterfaceError> {
@@ -114,6 +132,9 @@ pub fn derive(input: TokenStream) -> TokenStream {
                 #(#try_into_inner_fields;)*
                 Ok(lir::Interface::Struct(_inner))
             }
+            fn check_protocol(self, k: &mut ::shakeflow::hir::CompositeModuleContext) -> Self {
+                Self { #(#check_protocol_fields,)* }
+            }
         }
     };
     expanded.into()
@@ -37,6 +37,35 @@ pub fn derive(input: TokenStream) -> TokenStream {
                 }
             });
 
+            // fields for `signal_typ`.
+            let signal_typ_fields = fields.iter().map(|f| {
+                let name = f.ident.as_ref().unwrap();
+                let field_name = name.to_string();
+                let ty = &f.ty;
+                let symbol = get_member_symbol(&f.attrs, name);
+
+                match symbol {
+                    None => quote! { (#field_name.to_string(), None, <#ty>::signal_typ()) },
+                    Some(symbol) => {
+                        quote! { (#field_name.to_string(), Some(#symbol.to_string()), <#ty>::signal_typ()) }
+                    }
+                }
+            });
+
+            // fields for `decode`. Offset of each field is the sum of widths of the preceding fields.
+            let decode_fields = fields.iter().enumerate().map(|(i, f)| {
+                let name = f.ident.as_ref().unwrap().to_string();
+                let ty = &f.ty;
+                let prev_tys = fields.iter().take(i).map(|f| &f.ty);
+
+                quote! {
+                    {
+                        let offset = 0 #(+ <#prev_tys>::WIDTH)*;
+                        (#name.to_string(), <#ty>::decode(&bits[offset..(offset + <#ty>::WIDTH)]))
+                    }
+                }
+            });
+
             let expanded = quote! {
                 impl #impl_generics Signal for #name #ty_generics #where_clause {
                     const WIDTH: usize = #(#ty_widths)+*;
@@ -48,6 +77,20 @@ pub fn derive(input: TokenStream) -> TokenStream {
                             #(#port_decls_fields,)*
                         ])
                     }
+                    fn signal_typ() -> lir::SignalTyp {
+                        lir::SignalTyp::Struct {
+                            name: Some(stringify!(#name).to_string()),
+                            members: vec![
+                                #(#signal_typ_fields,)*
+                            ],
+                        }
+                    }
+                    fn decode(bits: &[bool]) -> Value {
+                        assert_eq!(bits.len(), Self::WIDTH);
+                        Value::Struct(vec![
+                            #(#decode_fields,)*
+                        ])
+                    }
                 }
             };
 
@@ -73,31 +116,53 @@ pub fn derive(input: TokenStream) -> TokenStream {
 
             let typ_width = quote! {#width};
 
-            let into_variants = variants.iter().enumerate().map(|(i, f)| {
-                let variant_name = &f.ident;
-                assert!(
-                    matches!(f.fields, syn::Fields::Unit),
-                    "{name}::{variant_name}: Only Unit Variant is allowed to be derived as Shakeflow Signal"
-                );
-
-                let encode_value = if let Some(encode_value_lit) = get_enum_encode_value(&f.attrs) {
-                    let encode_value = encode_value_lit
-                        .base10_parse::<usize>()
-                        .unwrap_or_else(|_| panic!("encoding value of {name}::{variant_name} should be usize"));
+            let encode_values = variants
+                .iter()
+                .enumerate()
+                .map(|(i, f)| {
+                    let variant_name = &f.ident;
                     assert!(
-                        encode_value < (1 << width),
-                        "{encode_value}(encoding of {name}::{variant_name}) exceeds maximum for {width} bits",
+                        matches!(f.fields, syn::Fields::Unit),
+                        "{name}::{variant_name}: Only Unit Variant is allowed to be derived as Shakeflow Signal"
                     );
-                    encode_value
-                } else {
-                    i
-                };
+
+                    if let Some(encode_value_lit) = get_enum_encode_value(&f.attrs) {
+                        let encode_value = encode_value_lit
+                            .base10_parse::<usize>()
+                            .unwrap_or_else(|_| panic!("encoding value of {name}::{variant_name} should be usize"));
+                        assert!(
+                            encode_value < (1 << width),
+                            "{encode_value}(encoding of {name}::{variant_name}) exceeds maximum for {width} bits",
+                        );
+                        encode_value
+                    } else {
+                        i
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let into_variants = variants.iter().zip(encode_values.iter()).map(|(f, encode_value)| {
+                let variant_name = &f.ident;
 
                 quote! { Self::#variant_name => (0..#width).map(|idx| {
                     ((#encode_value >> idx) & 1) != 0
                 }).collect::<Vec<bool>>(), }
             });
 
+            // variants for `decode`. Bits that encode no variant are decoded as bits.
+            let decode_variants = variants.iter().zip(encode_values.iter()).map(|(f, encode_value)| {
+                let variant_name = f.ident.to_string();
+
+                quote! { #encode_value => Value::Enum { variant: #variant_name.to_string(), bits: bits.to_vec() }, }
+            });
+
+            // variants for `signal_typ`.
+            let signal_typ_variants = variants.iter().zip(encode_values.iter()).map(|(f, encode_value)| {
+                let variant_name = f.ident.to_string();
+
+                quote! { (#variant_name.to_string(), #encode_value), }
+            });
+
             let expanded = quote! {
                 impl #impl_generics Signal for #name #ty_generics #where_clause {
                     const WIDTH: usize = #typ_width;
@@ -109,6 +174,22 @@ pub fn derive(input: TokenStream) -> TokenStream {
                     fn port_decls() -> lir::PortDecls {
                         lir::PortDecls::Bits(lir::Shape::new([Self::WIDTH]))
                     }
+                    fn signal_typ() -> lir::SignalTyp {
+                        lir::SignalTyp::Enum {
+                            name: stringify!(#name).to_string(),
+                            width: Self::WIDTH,
+                            variants: vec![#(#signal_typ_variants)*],
+                        }
+                    }
+                    fn decode(bits: &[bool]) -> Value {
+                        assert_eq!(bits.len(), Self::WIDTH);
+                        let value = bits.iter().rev().fold(0usize, |acc, b| (acc << 1) | usize::from(*b));
+                        #[allow(unreachable_patterns)]
+                        match value {
+                            #(#decode_variants)*
+                            _ => Value::Bits(bits.to_vec()),
+                        }
+                    }
                 }
 
                 impl EnumValue for #name {}
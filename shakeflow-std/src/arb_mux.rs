@@ -68,6 +68,7 @@ fn logic<'id, V: Signal, const N: usize>(
     (fwd_o, bwd_i)
 }
 
+#[track_caller]
 fn m<V: Signal, const N: usize>(
     arb_type_round_robin: usize, arb_lsb_high_priority: usize,
 ) -> Module<IC<V, N>, OC<V, N>> {
@@ -120,6 +121,7 @@ where Self: Interface
 impl<V: Signal, const N: usize> ArbMuxExt for [VrChannel<V>; N] {
     type O = OC<V, N>;
 
+    #[track_caller]
     fn arb_mux(
         self, k: &mut CompositeModuleContext, arb_type_round_robin: usize, arb_lsb_high_priority: usize,
     ) -> Self::O {
@@ -27,6 +27,7 @@ pub trait ArrayMap<I: Interface, O: Interface> {
 impl<const N: usize, I: Interface, O: Interface> ArrayMap<I, O> for [I; N] {
     type Target = [O; N];
 
+    #[track_caller]
     fn array_map(
         self, k: &mut CompositeModuleContext, name: &str, f: fn(I, &mut CompositeModuleContext) -> O,
     ) -> Self::Target {
@@ -34,6 +35,7 @@ impl<const N: usize, I: Interface, O: Interface> ArrayMap<I, O> for [I; N] {
         self.comb_inline(k, module)
     }
 
+    #[track_caller]
     fn array_map_feedback<V: Signal>(
         self, k: &mut CompositeModuleContext, feedback: UniChannel<V>, name: &str,
         f: fn((I, UniChannel<V>), &mut CompositeModuleContext) -> O,
@@ -2,6 +2,7 @@
 
 use super::*;
 
+#[track_caller]
 fn chunk_m<WIDTH: Num, V: Signal + Serialize>() -> Module<AxisVrChannel<V>, AxisChannel<Keep<WIDTH, Quot<WIDTH, U<8>>>>>
 where [(); V::WIDTH + 1]: {
     composite::<AxisVrChannel<V>, AxisChannel<Keep<WIDTH, Quot<WIDTH, U<8>>>>, _>(
@@ -56,6 +57,7 @@ where [(); V::WIDTH + 1]: {
 
 impl<V: Signal + Serialize> AxisVrChannel<V> {
     /// Splice a packet to units of WIDTH bits, and write them to egress one at a time.
+    #[track_caller]
     pub fn chunk<WIDTH: Num>(self, k: &mut CompositeModuleContext) -> AxisChannel<Keep<WIDTH, Quot<WIDTH, U<8>>>>
     // Thankfully, the calling function doesn't need to assert the following constraint if it calls this with a specified `V` type.
     where [(); V::WIDTH + 1]: {
@@ -29,6 +29,7 @@ pub trait AxisFifoExt: Interface {
 impl<V: Signal, const P: Protocol> AxisFifoExt for AxisVrChannel<V, P> {
     type Out = AxisVrChannel<V>;
 
+    #[track_caller]
     fn axis_fifo<
         Depth: Num,
         const DATA_WIDTH: usize,
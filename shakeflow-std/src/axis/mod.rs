@@ -87,18 +87,17 @@ impl<V: Signal, const P: Protocol> Interface for AxisVrChannel<V, P> {
     type Fwd = AxisValid<V>;
 
     fn interface_typ() -> lir::InterfaceTyp {
-        lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+        lir::InterfaceTyp::Channel(channel_typ::<Self::Fwd, Self::Bwd>("AxisVrChannel"))
     }
 
     fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
-        assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-        let channel = some_or!(interface.get_channel(), panic!());
+        let channel = channel_of::<Self>(interface)?;
         Ok(Self { endpoint: channel.endpoint(), _marker: PhantomData })
     }
 
     fn try_into_inner(self) -> Result<lir::Interface, InterfaceError> {
         Ok(lir::Interface::Channel(lir::Channel {
-            typ: lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+            typ: channel_typ::<Self::Fwd, Self::Bwd>("AxisVrChannel"),
             endpoint: self.endpoint,
         }))
     }
@@ -122,6 +121,7 @@ impl<'id, V: Signal> AxisValueExt<'id, V> for Expr<'id, AxisValue<V>> {
 
 impl<V: Signal, const P: Protocol> VrChannel<V, P> {
     /// Converts into axis valid/ready channel.
+    #[track_caller]
     pub fn into_axis_vr(self, k: &mut CompositeModuleContext) -> AxisVrChannel<V, P> {
         self.fsm::<(), AxisVrChannel<V, P>, _>(k, Some("into_axis_vr"), Expr::x(), move |fwd, bwd, s| {
             let ValidProj { inner, valid } = *fwd;
@@ -134,6 +134,7 @@ impl<V: Signal, const P: Protocol> VrChannel<V, P> {
 
 impl<V: Signal, const P: Protocol> AxisVrChannel<V, P> {
     /// Converts into valid/ready channel.
+    #[track_caller]
     pub fn into_vr(self, k: &mut CompositeModuleContext) -> VrChannel<V, P> {
         self.fsm::<(), VrChannel<V, P>, _>(k, Some("into_vr"), Expr::x(), move |fwd, bwd, s| {
             let AxisValidProj { inner, tvalid } = *fwd;
@@ -147,6 +148,7 @@ impl<V: Signal, const P: Protocol> AxisVrChannel<V, P> {
     ///
     /// The ready signal for input channel is high when both ready signals for output channels are high.
     // TODO: support duplicate to N channels
+    #[track_caller]
     pub fn duplicate<const P1: Protocol, const P2: Protocol>(
         self, k: &mut CompositeModuleContext,
     ) -> (AxisVrChannel<V, P1>, AxisVrChannel<V, P2>) {
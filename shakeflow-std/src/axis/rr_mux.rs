@@ -12,6 +12,7 @@ struct State {
 }
 
 #[allow(clippy::type_complexity)]
+#[track_caller]
 fn m<D1: Signal, D2: Signal, const P: Protocol>(
 ) -> Module<(AxisChannel<D1, P>, AxisChannel<D2, P>), AxisChannel<(bool, D1, D2), P>> {
     composite::<(AxisChannel<D1, P>, AxisChannel<D2, P>), AxisChannel<(bool, D1, D2), P>, _>(
@@ -74,5 +75,6 @@ where Self: Interface
 impl<D1: Signal, D2: Signal, const P: Protocol> AxisRrMuxExt for (AxisChannel<D1, P>, AxisChannel<D2, P>) {
     type O = AxisChannel<(bool, D1, D2), P>;
 
+    #[track_caller]
     fn axis_rr_mux(self, k: &mut CompositeModuleContext) -> Self::O { self.comb_inline(k, m()) }
 }
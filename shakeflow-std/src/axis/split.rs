@@ -43,6 +43,7 @@ impl<WIDTH: Num, M: Num> Default for State<WIDTH, M> {
     }
 }
 
+#[track_caller]
 fn split_m<const P: Protocol, WIDTH: Num, M: Num>() -> Module<
     AxisChannel<Keep<WIDTH, Quot<WIDTH, U<8>>>, P>,
     (VrChannel<Keep<M, Quot<M, U<8>>>, P>, AxisChannel<Keep<WIDTH, Quot<WIDTH, U<8>>>, P>),
@@ -146,6 +147,7 @@ fn split_m<const P: Protocol, WIDTH: Num, M: Num>() -> Module<
 impl<const P: Protocol, WIDTH: Num> AxisChannel<Keep<WIDTH, Quot<WIDTH, U<8>>>, P> {
     /// Split the first M bytes out of the ingress and egress it to a seperate channel.
     /// Output the remaining bytes to another channel.
+    #[track_caller]
     pub fn split<M: Num>(
         self, k: &mut CompositeModuleContext,
     ) -> (VrChannel<Keep<M, Quot<M, U<8>>>, P>, AxisChannel<Keep<WIDTH, Quot<WIDTH, U<8>>>, P>) {
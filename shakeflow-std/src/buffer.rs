@@ -14,6 +14,7 @@ struct State<V: Signal> {
 }
 
 /// Creates a buffer module.
+#[track_caller]
 fn m<V: Signal>(init: Expr<'static, V>) -> Module<UniChannel<V>, UniChannel<V>> {
     composite::<UniChannel<V>, UniChannel<V>, _>("buffer", Some("in"), Some("out"), |value, k| {
         value.fsm_map(k, None, StateProj { inner: init }.into(), |value, state| {
@@ -24,6 +25,7 @@ fn m<V: Signal>(init: Expr<'static, V>) -> Module<UniChannel<V>, UniChannel<V>>
 }
 
 /// Creates a buffer module with enable signal.
+#[track_caller]
 fn m_en<V: Signal>(init: Expr<'static, V>) -> Module<UniChannel<(V, bool)>, UniChannel<V>> {
     composite::<UniChannel<(V, bool)>, UniChannel<V>, _>("buffer_en", Some("in"), Some("out"), |value, k| {
         value.fsm_map(k, None, StateProj { inner: init }.into(), move |value, state| {
@@ -35,6 +37,7 @@ fn m_en<V: Signal>(init: Expr<'static, V>) -> Module<UniChannel<(V, bool)>, UniC
 }
 
 /// Creates a buffer module with delay
+#[track_caller]
 fn m_buffer_cycles<V: Signal, const STAGES: usize>(
     init: Expr<'static, V>, delay: usize,
 ) -> Module<UniChannel<V>, UniChannel<V>> {
@@ -55,6 +58,7 @@ fn m_buffer_cycles<V: Signal, const STAGES: usize>(
 ///
 /// Note: In this implementation, set signal overrides clear signal.
 #[allow(clippy::type_complexity)]
+#[track_caller]
 fn m_set_clear<const N: usize>() -> Module<UniChannel<(Bits<U<N>>, Bits<U<N>>)>, UniChannel<Bits<U<N>>>> {
     composite::<UniChannel<(Bits<U<N>>, Bits<U<N>>)>, UniChannel<Bits<U<N>>>, _>(
         "buffer_set_clear",
@@ -71,6 +75,7 @@ fn m_set_clear<const N: usize>() -> Module<UniChannel<(Bits<U<N>>, Bits<U<N>>)>,
 }
 
 /// Creates a buffer module with valid-ready signals.
+#[track_caller]
 fn m_vr<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, VrChannel<V>> {
     composite::<VrChannel<V, P>, VrChannel<V>, _>("buffer_vr", Some("in"), Some("out"), |value, k| {
         value.fsm::<Valid<V>, VrChannel<V>, _>(k, None, Expr::invalid(), move |ingress_fwd, egress_bwd, state| {
@@ -96,6 +101,7 @@ fn m_vr<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, VrChannel<V>>
 }
 
 /// Creates a buffer module with valid-ready signals.
+#[track_caller]
 fn m_vr_always<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, VrChannel<V>> {
     composite::<VrChannel<V, P>, VrChannel<V>, _>("buffer_vr_always", Some("in"), Some("out"), |value, k| {
         value.fsm::<Valid<V>, VrChannel<V>, _>(k, None, Expr::invalid(), move |ingress_fwd, egress_bwd, state| {
@@ -111,6 +117,7 @@ fn m_vr_always<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, VrChan
 /// Creates a FIFO module with N-elements queue.
 ///
 /// TODO: Current implementation is not actually producing helpful protocol. We need to fix it
+#[track_caller]
 fn m_fifo<V: Signal, const N: usize, const P: Protocol>() -> Module<VrChannel<V, P>, VrChannel<V>> {
     composite::<VrChannel<V, P>, VrChannel<V>, _>("fifo_vr", Some("in"), Some("out"), |value, k| {
         value.fsm::<(Array<V, U<N>>, Bits<U<N>>, Bits<U<N>>, Bits<U<N>>), VrChannel<V>, _>(
@@ -149,6 +156,7 @@ fn m_fifo<V: Signal, const N: usize, const P: Protocol>() -> Module<VrChannel<V,
 
 impl<I: Signal> UniChannel<Valid<I>> {
     /// Creates a buffer module with valid/ready channel as an input, which updates when valid.
+    #[track_caller]
     pub fn buffer_valid(self, k: &mut CompositeModuleContext, init: Expr<'static, I>) -> UniChannel<I> {
         let valid = self.clone().map(k, |input| input.valid);
         self.map(k, |input| input.inner).buffer_en(k, init, valid)
@@ -158,14 +166,17 @@ impl<I: Signal> UniChannel<Valid<I>> {
 impl<I: Signal> UniChannel<I> {
     /// Adds a buffer.
     #[must_use]
+    #[track_caller]
     pub fn buffer(self, k: &mut CompositeModuleContext, init: Expr<'static, I>) -> Self { self.comb_inline(k, m(init)) }
 
     /// Adds a buffer with enable signal.
+    #[track_caller]
     pub fn buffer_en(self, k: &mut CompositeModuleContext, init: Expr<'static, I>, en: UniChannel<bool>) -> Self {
         self.zip(k, en).comb_inline(k, m_en(init))
     }
 
     /// Adds a buffer with n-cycle delays.
+    #[track_caller]
     pub fn buffer_with_delay<const STAGES: usize>(
         self, k: &mut CompositeModuleContext, init: Expr<'static, I>, delay: usize,
     ) -> Self {
@@ -175,6 +186,7 @@ impl<I: Signal> UniChannel<I> {
 
 impl<const N: usize> UniChannel<Bits<U<N>>> {
     /// Adds a buffer with set and clear signals.
+    #[track_caller]
     pub fn buffer_set_clear(
         k: &mut CompositeModuleContext, set: UniChannel<Bits<U<N>>>, clear: UniChannel<Bits<U<N>>>,
     ) -> Self {
@@ -186,15 +198,94 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// Adds a buffer.
     ///
     /// It can receive data when the internal buffer is empty.
+    #[track_caller]
     pub fn buffer(self, k: &mut CompositeModuleContext) -> VrChannel<I> { self.comb_inline(k, m_vr()) }
 
     /// Adds a buffer.
     ///
     /// If the data can be received from the egress side, the data can also be received from the ingress side.
+    #[track_caller]
     pub fn buffer_always(self, k: &mut CompositeModuleContext) -> VrChannel<I> { self.comb_inline(k, m_vr_always()) }
 
     /// Adds a fifo.
+    #[track_caller]
     pub fn fifo<const N: usize>(self, k: &mut CompositeModuleContext) -> VrChannel<I> {
         self.comb_inline(k, m_fifo::<I, N, P>())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the forward bits of a valid-ready channel of 8-bit values.
+    fn fwd(value: Option<usize>) -> Vec<bool> {
+        let mut bits = usize_to_bitvec(8, value.unwrap_or(0));
+        bits.push(value.is_some());
+        bits
+    }
+
+    /// Returns the value of the forward bits of a valid-ready channel of 8-bit values.
+    fn value(bits: &[bool]) -> Option<usize> {
+        bits[8].then(|| bits[..8].iter().rev().fold(0, |value, bit| (value << 1) | *bit as usize))
+    }
+
+    /// Pushes `count` values into `module` while the output is ready in the cycles given by `ready`, and
+    /// returns the values accepted and transferred in each cycle.
+    fn simulate(
+        module: Module<VrChannel<Bits<U<8>>>, VrChannel<Bits<U<8>>>>, count: usize, ready: impl Fn(usize) -> bool,
+        cycles: usize,
+    ) -> Vec<(Option<usize>, Option<usize>)> {
+        let mut sim = Simulator::new(&module).unwrap();
+        let input = sim.input_channels()[0].0.clone();
+        let output = sim.output_channels()[0].0.clone();
+        sim.reset().unwrap();
+
+        let mut next = 0;
+        let mut trace = Vec::new();
+        for cycle in 0..cycles {
+            sim.set_input_fwd(&input, fwd((next < count).then_some(next)));
+            sim.set_output_bwd(&output, vec![ready(cycle)]);
+            sim.eval().unwrap();
+
+            let accepted = (next < count && sim.input_bwd(&input)[0]).then_some(next);
+            let transferred = value(sim.output_fwd(&output)).filter(|_| ready(cycle));
+            next += accepted.is_some() as usize;
+            trace.push((accepted, transferred));
+            sim.step().unwrap();
+        }
+        trace
+    }
+
+    #[test]
+    fn fifo_streams_values() {
+        let trace = simulate(m_fifo::<_, 4, { Protocol::Helpful }>(), 8, |_| true, 10);
+
+        // Each value is accepted in its own cycle and comes out in the next cycle.
+        for (cycle, (accepted, transferred)) in trace.iter().enumerate() {
+            assert_eq!(*accepted, (cycle < 8).then_some(cycle));
+            assert_eq!(*transferred, cycle.checked_sub(1).filter(|value| *value < 8));
+        }
+    }
+
+    #[test]
+    fn fifo_fills_up_while_blocked() {
+        let trace = simulate(m_fifo::<_, 4, { Protocol::Helpful }>(), 8, |cycle| cycle >= 6, 16);
+        let accepted = trace[..6].iter().filter(|(accepted, _)| accepted.is_some()).count();
+        assert_eq!(accepted, 4);
+
+        let transferred = trace.iter().filter_map(|(_, transferred)| *transferred).collect::<Vec<_>>();
+        assert_eq!(transferred, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn buffer_alternates_between_accepting_and_sending() {
+        // The buffer accepts a value only when it is empty.
+        let trace = simulate(m_vr::<_, { Protocol::Helpful }>(), 3, |_| true, 8);
+        let accepted = trace.iter().map(|(accepted, _)| *accepted).collect::<Vec<_>>();
+        assert_eq!(&accepted[..6], &[Some(0), None, Some(1), None, Some(2), None]);
+
+        let transferred = trace.iter().filter_map(|(_, transferred)| *transferred).collect::<Vec<_>>();
+        assert_eq!(transferred, vec![0, 1, 2]);
+    }
+}
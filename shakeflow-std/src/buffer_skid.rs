@@ -66,6 +66,7 @@ fn logic<'id, V: Signal>(
 }
 
 /// Creates a skid buffer module.
+#[track_caller]
 fn m<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, VrChannel<V>> {
     composite::<VrChannel<V, P>, VrChannel<V>, _>("buffer_skid", Some("in"), Some("out"), |value, k| {
         value.fsm(
@@ -85,5 +86,69 @@ fn m<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, VrChannel<V>> {
 
 impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// Adds a skid buffer.
+    #[track_caller]
     pub fn buffer_skid(self, k: &mut CompositeModuleContext) -> VrChannel<I> { self.comb_inline(k, m()) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the forward bits of a valid-ready channel of 8-bit values.
+    fn fwd(value: Option<usize>) -> Vec<bool> {
+        let mut bits = usize_to_bitvec(8, value.unwrap_or(0));
+        bits.push(value.is_some());
+        bits
+    }
+
+    /// Returns the value of the forward bits of a valid-ready channel of 8-bit values.
+    fn value(bits: &[bool]) -> Option<usize> {
+        bits[8].then(|| bits[..8].iter().rev().fold(0, |value, bit| (value << 1) | *bit as usize))
+    }
+
+    /// Pushes `count` values while the output is ready in the cycles given by `ready`, and returns the
+    /// values accepted and transferred in each cycle.
+    fn simulate(count: usize, ready: impl Fn(usize) -> bool, cycles: usize) -> Vec<(Option<usize>, Option<usize>)> {
+        let mut sim = Simulator::new(&m::<Bits<U<8>>, { Protocol::Helpful }>()).unwrap();
+        let input = sim.input_channels()[0].0.clone();
+        let output = sim.output_channels()[0].0.clone();
+        sim.reset().unwrap();
+
+        let mut next = 0;
+        let mut trace = Vec::new();
+        for cycle in 0..cycles {
+            sim.set_input_fwd(&input, fwd((next < count).then_some(next)));
+            sim.set_output_bwd(&output, vec![ready(cycle)]);
+            sim.eval().unwrap();
+
+            let accepted = (next < count && sim.input_bwd(&input)[0]).then_some(next);
+            let transferred = value(sim.output_fwd(&output)).filter(|_| ready(cycle));
+            next += accepted.is_some() as usize;
+            trace.push((accepted, transferred));
+            sim.step().unwrap();
+        }
+        trace
+    }
+
+    #[test]
+    fn values_pass_in_order() {
+        let trace = simulate(4, |_| true, 8);
+        let transferred = trace.iter().filter_map(|(_, transferred)| *transferred).collect::<Vec<_>>();
+        assert_eq!(transferred, vec![0, 1, 2, 3]);
+
+        // Once the buffer is out of reset, it accepts a value in every cycle.
+        let accepted = trace.iter().map(|(accepted, _)| *accepted).collect::<Vec<_>>();
+        assert_eq!(&accepted[..5], &[None, Some(0), Some(1), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn blocked_output_fills_skid_register() {
+        // While the output is blocked, the output register and the skid register take a value each.
+        let trace = simulate(4, |cycle| cycle >= 6, 12);
+        let accepted = trace[..6].iter().filter(|(accepted, _)| accepted.is_some()).count();
+        assert_eq!(accepted, 2);
+
+        let transferred = trace.iter().filter_map(|(_, transferred)| *transferred).collect::<Vec<_>>();
+        assert_eq!(transferred, vec![0, 1, 2, 3]);
+    }
+}
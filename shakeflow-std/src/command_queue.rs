@@ -39,6 +39,7 @@ pub trait Command: Signal {
 /// # Note
 ///
 /// `allow_duplicate` argument represents whether there can be multiple same type commands in the queue.
+#[track_caller]
 pub fn m_command_queue<V: Command, const N: usize, const PIPELINE: usize>(
     allow_duplicate: [bool; N],
 ) -> Module<([VrChannel<V>; N], UniChannel<Bits<U<N>>>), ((UniChannel<(V, Bits<U<N>>)>, UniChannel<(V, Bits<U<N>>)>), ())>
@@ -119,6 +120,7 @@ pub trait CommandQueueExt<I: Interface, const N: usize> {
 impl<V: Command, const N: usize> CommandQueueExt<VrChannel<V>, N> for [VrChannel<V>; N] {
     type O<const PIPELINE: usize> = (UniChannel<(V, Bits<U<N>>)>, UniChannel<(V, Bits<U<N>>)>);
 
+    #[track_caller]
     fn command_queue<const PIPELINE: usize>(
         self, k: &mut CompositeModuleContext, allow_duplicate: [bool; N],
     ) -> (Self::O<PIPELINE>, Module<UniChannel<Bits<U<N>>>, ()>) {
@@ -17,6 +17,7 @@ pub trait ConcentrateExt {
 impl<V: Signal, N: Num> ConcentrateExt for UniChannel<Array<V, N>> {
     type Output<M: Num> = UniChannel<Array<V, M>>;
 
+    #[track_caller]
     fn concentrate<M: Num>(self, k: &mut CompositeModuleContext, pattern: Vec<bool>) -> UniChannel<Array<V, M>> {
         // Assertion: Pattern has same length as input width and it is longer than output width.
         assert!(pattern.len() == N::WIDTH && N::WIDTH >= M::WIDTH);
@@ -7,6 +7,7 @@ impl UniChannel<bool> {
     /// Circular pointer.
     ///
     /// It returns the pair of current counter value and next counter value.
+    #[track_caller]
     pub fn counter<N: Num>(self, k: &mut CompositeModuleContext) -> UniChannel<(Bits<Log2<N>>, Bits<Log2<N>>)> {
         self.fsm_map::<Bits<Log2<N>>, (Bits<Log2<N>>, Bits<Log2<N>>), _>(k, None, 0.into(), |input, state| {
             let state_next = state + input.repr().resize();
@@ -18,6 +19,7 @@ impl UniChannel<bool> {
 
 impl<V: Signal> VrChannel<V> {
     /// Counts the number of transfer.
+    #[track_caller]
     pub fn counter_transfer<N: Num>(self, k: &mut CompositeModuleContext) -> (VrChannel<V>, UniChannel<Bits<Log2<N>>>) {
         let (this, fire) = self.fire(k);
         let counter = fire.counter::<N>(k).map(k, |input| input.0);
@@ -32,6 +34,7 @@ pub trait CounterUpDownExt {
 }
 
 impl CounterUpDownExt for (UniChannel<bool>, UniChannel<bool>) {
+    #[track_caller]
     fn counter_up_down<N: Num>(self, k: &mut CompositeModuleContext) -> UniChannel<Bits<N>> {
         let (up, down) = self;
         up.zip(k, down).fsm_map::<Bits<N>, Bits<N>, _>(k, None, 0.into(), |input, count| {
@@ -49,6 +52,7 @@ pub trait CounterClearUpOneHotExt {
 }
 
 impl CounterClearUpOneHotExt for (UniChannel<bool>, UniChannel<bool>) {
+    #[track_caller]
     fn counter_clear_up_one_hot<N: Num>(self, k: &mut CompositeModuleContext) -> UniChannel<Bits<N>> {
         let (clear, up) = self;
         clear.zip(k, up).fsm_map::<Bits<N>, Bits<N>, _>(k, None, 0.into(), |input, count| {
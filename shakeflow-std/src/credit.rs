@@ -4,6 +4,7 @@ use shakeflow::*;
 
 use crate::*;
 
+#[track_caller]
 fn m_into_fifo<V: Signal>(fifo: Module<VrChannel<V>, VrChannel<V>>) -> Module<VrChannel<V>, VrChannel<V>> {
     composite::<(VrChannel<V>, UniChannel<bool>), (VrChannel<V>, UniChannel<bool>), _>(
         "into_fifo",
@@ -30,6 +31,7 @@ fn m_into_fifo<V: Signal>(fifo: Module<VrChannel<V>, VrChannel<V>>) -> Module<Vr
 
 impl<V: Signal> VrChannel<V> {
     /// Converts the valid-ready protocol into valid-credit protocol, by keeping the count of available credits.
+    #[track_caller]
     pub fn into_credit_flow<const CREDIT_MAX_VAL: usize, const DECIMATION: usize>(
         self, k: &mut CompositeModuleContext,
     ) -> VrChannel<V> {
@@ -53,6 +55,7 @@ impl<V: Signal> VrChannel<V> {
     }
 
     /// Converts valid-credit protocol into valid-ready protocol, by using a FIFO to keep the data.
+    #[track_caller]
     pub fn into_fifo(self, k: &mut CompositeModuleContext, fifo: Module<VrChannel<V>, VrChannel<V>>) -> VrChannel<V> {
         self.comb_inline(k, m_into_fifo(fifo))
     }
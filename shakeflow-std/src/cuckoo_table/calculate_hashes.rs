@@ -7,6 +7,7 @@ use super::{HashType, KeyType};
 use crate::*;
 
 /// Calculate NUM_TABLES hashes given a key.
+#[track_caller]
 pub(super) fn m() -> Module<VrChannel<KeyType>, [VrChannel<HashType, { Protocol::Demanding }>; NUM_TABLES]> {
     composite::<VrChannel<KeyType>, [VrChannel<HashType, { Protocol::Demanding }>; NUM_TABLES], _>(
         "calculate_hashes",
@@ -165,6 +165,7 @@ struct Feedback {
 /// Ported from: <https://github.com/fpgasystems/Vitis_with_100Gbps_TCP-IP/tree/vitis_2020_1/fpga-network-stack/hls/hash_table>
 ///
 /// TODO: Make Hash Table to be configurable using constant generic (to what extent?)
+#[track_caller]
 pub fn m() -> Module<HI, HO> {
     composite::<HI, HO, _>("hash_table", Some("in"), Some("out"), |input, k| {
         let (feedback_source, feedback_sink) = k.feedback::<Feedback>();
@@ -312,6 +313,7 @@ type OpModuleIC = VrChannel<OpModuleInput>;
 type LookupOC = VrChannel<LookupResp>;
 
 /// Lookup Module
+#[track_caller]
 fn m_lookup() -> Module<OpModuleIC, LookupOC> {
     composite::<OpModuleIC, LookupOC, _>("lookup", Some("i"), Some("o"), |input, k| {
         input.map(k, |inner| {
@@ -335,6 +337,7 @@ fn m_lookup() -> Module<OpModuleIC, LookupOC> {
 type DeleteOC = (VrChannel<UpdateResp>, VrChannel<Array<HashEntryPair, U<NUM_TABLES>>>);
 
 /// Delete Module
+#[track_caller]
 fn m_delete() -> Module<OpModuleIC, DeleteOC> {
     composite::<OpModuleIC, DeleteOC, _>("delete", Some("i"), Some("o"), |input, k| {
         let (input, input_dup) = input.duplicate::<{ Protocol::Demanding }, { Protocol::Demanding }>(k);
@@ -389,6 +392,7 @@ struct InsertState {
 }
 
 /// Insert Module
+#[track_caller]
 fn m_insert() -> Module<OpModuleIC, InsertOC> {
     composite::<OpModuleIC, InsertOC, _>("insert", Some("i"), Some("o"), |input, k| {
         let [dup1, dup2, dup3]: [VrChannel<
@@ -5,6 +5,7 @@ use crate::num::*;
 use crate::*;
 
 #[allow(clippy::type_complexity)]
+#[track_caller]
 fn m<V: Signal, CL: Num, const N: usize, const P: Protocol>(
 ) -> Module<VrChannel<(V, Bits<CL>), P>, [VrChannel<V, P>; N]> {
     composite::<VrChannel<(V, Bits<CL>), P>, [VrChannel<V, P>; N], _>("demux", Some("in"), Some("out"), |value, k| {
@@ -23,6 +24,7 @@ fn m<V: Signal, CL: Num, const N: usize, const P: Protocol>(
 }
 
 #[allow(clippy::type_complexity)]
+#[track_caller]
 fn m_axi<V: Signal, CL: Num, const N: usize, const P: Protocol>(
 ) -> Module<AxisVrChannel<(V, Bits<CL>), P>, [AxisVrChannel<V, P>; N]> {
     composite::<AxisVrChannel<(V, Bits<CL>), P>, [AxisVrChannel<V, P>; N], _>(
@@ -36,6 +38,7 @@ fn m_axi<V: Signal, CL: Num, const N: usize, const P: Protocol>(
 
 impl<V: Signal, CL: Num, const P: Protocol> VrChannel<(V, Bits<CL>), P> {
     /// Demuxs valid-ready channel.
+    #[track_caller]
     pub fn demux<const N: usize>(self, k: &mut CompositeModuleContext) -> [VrChannel<V, P>; N] {
         self.comb_inline(k, m::<V, CL, N, P>())
     }
@@ -43,6 +46,7 @@ impl<V: Signal, CL: Num, const P: Protocol> VrChannel<(V, Bits<CL>), P> {
 
 impl<V: Signal, CL: Num, const P: Protocol> AxisVrChannel<(V, Bits<CL>), P> {
     /// Demuxs axis valid-ready channel.
+    #[track_caller]
     pub fn demux<const N: usize>(self, k: &mut CompositeModuleContext) -> [AxisVrChannel<V, P>; N] {
         self.comb_inline(k, m_axi::<V, CL, N, P>())
     }
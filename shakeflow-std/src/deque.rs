@@ -31,6 +31,7 @@ impl<V: Signal> DeqChannel<V> {
     /// Transforms into valid-ready channel.
     ///
     /// Since all packets are valid in deque channel, the valid signal of valid-ready signal is always high.
+    #[track_caller]
     pub fn into_vr(self, k: &mut CompositeModuleContext) -> VrChannel<V> {
         self.fsm::<(), VrChannel<V>, _>(k, Some("into_vr"), ().into(), |data, bwd_o, s| {
             let fwd_o = Expr::valid(data);
@@ -41,6 +42,7 @@ impl<V: Signal> DeqChannel<V> {
     }
 
     /// Returns deque signal.
+    #[track_caller]
     pub fn deque(self, k: &mut CompositeModuleContext) -> (Self, UniChannel<bool>) {
         let (fire, this) = self.fsm::<_, _, _>(k, Some("fire"), ().into(), move |fwd, bwd: Expr<((), Deque)>, s| {
             let bwd = bwd.1;
@@ -58,6 +60,7 @@ impl<V: Signal> DeqChannel<V> {
 
 impl<I: Signal> DeqChannel<I> {
     /// Fsm for deque channel.
+    #[track_caller]
     pub fn fsm_map<
         S: Signal,
         O: Signal,
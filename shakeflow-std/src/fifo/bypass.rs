@@ -4,6 +4,7 @@ use shakeflow::*;
 
 use crate::*;
 
+#[track_caller]
 fn m_fifo_bypass<V: Signal>(fifo: Module<VrChannel<V>, VrChannel<V>>) -> Module<VrChannel<V>, VrChannel<V>> {
     composite::<(VrChannel<V>, VrChannel<V>), (VrChannel<V>, VrChannel<V>), _>(
         "fifo_bypass",
@@ -39,6 +40,7 @@ fn m_fifo_bypass<V: Signal>(fifo: Module<VrChannel<V>, VrChannel<V>>) -> Module<
 
 impl<V: Signal> VrChannel<V> {
     /// FIFO bypass.
+    #[track_caller]
     pub fn fifo_bypass(self, k: &mut CompositeModuleContext, fifo: Module<VrChannel<V>, VrChannel<V>>) -> VrChannel<V> {
         self.comb_inline(k, m_fifo_bypass(fifo))
     }
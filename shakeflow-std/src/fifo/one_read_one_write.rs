@@ -13,6 +13,7 @@ pub struct FifoI<V: Signal, const SLOTS: usize> {
     read_n: Bits<Log2<U<SLOTS>>>,
 }
 
+#[track_caller]
 fn m_fifo_1r1w<V: Signal, const SLOTS: usize>(
     logic: fn(UniChannel<FifoI<V, SLOTS>>, &mut CompositeModuleContext) -> UniChannel<V>,
 ) -> Module<VrChannel<V>, VrChannel<V>> {
@@ -65,6 +66,7 @@ fn m_fifo_1r1w<V: Signal, const SLOTS: usize>(
     .build()
 }
 
+#[track_caller]
 fn m_fifo_1r1w_from_1rw<V: Signal>(
     fifo_1rw: Module<UniChannel<one_read_write::I<V>>, UniChannel<one_read_write::E<V>>>,
 ) -> Module<VrChannel<V>, VrChannel<V>> {
@@ -103,6 +105,7 @@ fn m_fifo_1r1w_from_1rw<V: Signal>(
 
 impl<V: Signal> VrChannel<V> {
     /// FIFO with 1 read and 1 write.
+    #[track_caller]
     pub fn fifo_1r1w<const SLOTS: usize>(
         self, k: &mut CompositeModuleContext,
         logic: fn(UniChannel<FifoI<V, SLOTS>>, &mut CompositeModuleContext) -> UniChannel<V>,
@@ -111,6 +114,7 @@ impl<V: Signal> VrChannel<V> {
     }
 
     /// Constructs FIFO 1R1W from FIFO 1RW.
+    #[track_caller]
     pub fn fifo_1r1w_from_1rw(
         self, k: &mut CompositeModuleContext,
         fifo_1rw: Module<UniChannel<one_read_write::I<V>>, UniChannel<one_read_write::E<V>>>,
@@ -32,6 +32,7 @@ impl<V: Signal, const ELS: usize> S<V, ELS> {
     }
 }
 
+#[track_caller]
 fn m_fifo_1rw<V: Signal, const ELS: usize>() -> Module<UniChannel<I<V>>, UniChannel<E<V>>> {
     composite::<UniChannel<I<V>>, UniChannel<E<V>>, _>("fifo_1rw_large", Some("i"), Some("o"), |input, k| {
         let enque = input.clone().map(k, |input| input.data.valid & input.enq_not_deq);
@@ -68,6 +69,7 @@ fn m_fifo_1rw<V: Signal, const ELS: usize>() -> Module<UniChannel<I<V>>, UniChan
 
 impl<V: Signal> UniChannel<I<V>> {
     /// FIFO with 1 read or write.
+    #[track_caller]
     pub fn fifo_1rw<const ELS: usize>(self, k: &mut CompositeModuleContext) -> UniChannel<E<V>> {
         self.comb_inline(k, m_fifo_1rw::<V, ELS>())
     }
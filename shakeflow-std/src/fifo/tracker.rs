@@ -27,6 +27,7 @@ pub type IC = UniChannel<I>;
 pub type EC<PtrWidth: Num> = UniChannel<E<PtrWidth>>;
 
 /// Inner logic of FIFO tracker.
+#[track_caller]
 pub fn m<const SLOTS: usize, PtrWidth: Num>() -> Module<IC, EC<PtrWidth>> {
     composite::<IC, EC<PtrWidth>, _>("bsg_fifo_tracker", Some("i"), Some("o"), |input, k| {
         let wptr = input.clone().map(k, |input| input.enq).counter::<U<SLOTS>>(k);
@@ -15,6 +15,7 @@ pub trait FsmExt<I: Signal>: Sized {
     /// The FSM is described by `F`, which generates the circuit for (1) the current-cycle output
     /// for all cycles and (2) the next-cycle state. The state is updated only after transfer
     /// cycles.
+    #[track_caller]
     fn fsm_map<
         S: Signal,
         O: Signal,
@@ -24,6 +25,7 @@ pub trait FsmExt<I: Signal>: Sized {
     ) -> Self::Out<O>;
 
     /// Maps the output.
+    #[track_caller]
     fn map<O: Signal, F: 'static + for<'id> Fn(Expr<'id, I>) -> Expr<'id, O>>(
         self, k: &mut CompositeModuleContext, f: F,
     ) -> Self::Out<O> {
@@ -31,6 +33,7 @@ pub trait FsmExt<I: Signal>: Sized {
     }
 
     /// Feeds `self` to a new `N`-windowing FSM.
+    #[track_caller]
     fn window<const N: usize>(
         self, default: Expr<'static, I>, k: &mut CompositeModuleContext,
     ) -> Self::Out<Array<I, U<N>>> {
@@ -51,6 +54,7 @@ pub trait FsmExt<I: Signal>: Sized {
 impl<I: Signal> FsmExt<I> for UniChannel<I> {
     type Out<O: Signal> = UniChannel<O>;
 
+    #[track_caller]
     fn fsm_map<
         S: Signal,
         O: Signal,
@@ -68,6 +72,7 @@ impl<I: Signal> FsmExt<I> for UniChannel<I> {
 impl<I: Signal, const P: Protocol> FsmExt<I> for VrChannel<I, P> {
     type Out<O: Signal> = VrChannel<O, P>;
 
+    #[track_caller]
     fn fsm_map<
         S: Signal,
         O: Signal,
@@ -86,3 +91,34 @@ impl<I: Signal, const P: Protocol> FsmExt<I> for VrChannel<I, P> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn source_is_the_caller_of_the_builder() {
+        let mut line = 0;
+        let module = composite::<UniChannel<Bits<U<4>>>, UniChannel<Array<Bits<U<4>>, U<2>>>, _>(
+            "windows",
+            Some("in"),
+            Some("out"),
+            |input, k| {
+                line = line!() + 1;
+                input.window::<2>(0.into(), k)
+            },
+        )
+        .build();
+
+        let dir = std::env::temp_dir().join(format!("shakeflow-std-fsm-{}", std::process::id()));
+        let mut package = Package::default();
+        package.add(module);
+        package.gen_vir(&dir).unwrap();
+        let verilog = fs::read_to_string(dir.join("windows_inner.v")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(verilog.contains(&format!("Begin FSM window\n    src = {}:{}\n", file!(), line)), "{}", verilog);
+    }
+}
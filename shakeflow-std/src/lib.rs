@@ -68,6 +68,7 @@ pub mod priority_mux;
 mod register_slice;
 pub mod rr_mux;
 mod scatter_gather;
+pub mod testbench;
 pub mod transpose;
 pub mod unconcentrate;
 pub mod unidir;
@@ -21,6 +21,7 @@ type KeyType<const MEM_SIZE: usize> = Bits<Log2<U<MEM_SIZE>>>;
 ///# NOTE
 ///
 /// When valid read and valid write request arrive at the same time, both will be consumed
+#[track_caller]
 pub fn m<ENTRY: Signal, const PIPELINE: usize, const NUM_ENTRIES: usize>(
     init_entry: Expr<'static, ENTRY>,
 ) -> Module<MemI<KeyType<NUM_ENTRIES>, ENTRY>, MemO<KeyType<NUM_ENTRIES>, ENTRY>> {
@@ -34,28 +35,25 @@ pub fn m<ENTRY: Signal, const PIPELINE: usize, const NUM_ENTRIES: usize>(
         Some("in"),
         Some("out"),
         |input, k| {
-            let (selector, input): (UniChannel<Bits<U<2>>>, VrChannel<(KeyType<NUM_ENTRIES>, ENTRY)>) =
-                [input.0.map(k, |input| (input, Expr::x()).into()), input.1].mux(k);
+            // Each request is tagged as a read or a write, instead of using the selector of the mux, which
+            // is gated by the ready of the demux and thus cannot select the output of the demux.
+            let (_, input): (UniChannel<Bits<U<2>>>, VrChannel<((KeyType<NUM_ENTRIES>, ENTRY), bool)>) = [
+                input.0.map(k, |input| {
+                    let request: Expr<(KeyType<NUM_ENTRIES>, ENTRY)> = (input, Expr::x()).into();
+                    (request, Expr::from(false)).into()
+                }),
+                input.1.map(k, |input| (input, Expr::from(true)).into()),
+            ]
+            .mux(k);
 
             let [read_out, write_out] = input
-                .zip_uni(k, selector)
                 .fsm_map(k, Some("memory"), init_entry.repeat(), |input, state| {
-                    let (input, selector) = *input;
+                    let (input, is_write) = *input;
                     let (key, value) = *input;
 
                     let read_entry = state[key];
-
-                    let state_next = select! {
-                        selector.is_eq(1.into()) => state,
-                        selector.is_eq(2.into()) => state.set(key, value),
-                        default => state,
-                    };
-
-                    let selector: Expr<Bits<U<1>>> = select! {
-                        selector.is_eq(1.into()) => 0.into(),
-                        selector.is_eq(2.into()) => 1.into(),
-                        default => 0.into(),
-                    };
+                    let state_next = is_write.cond(state.set(key, value), state);
+                    let selector: Expr<Bits<U<1>>> = is_write.repr();
 
                     let output: Expr<_> = (key, read_entry).into();
                     ((output, selector).into(), state_next)
@@ -67,3 +65,43 @@ pub fn m<ENTRY: Signal, const PIPELINE: usize, const NUM_ENTRIES: usize>(
     )
     .build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the forward bits of a valid-ready channel of `(key, entry)`.
+    fn request(valid: bool, key: usize, entry: usize) -> Vec<bool> {
+        let mut bits = usize_to_bitvec(2, key);
+        bits.extend(usize_to_bitvec(8, entry));
+        bits.push(valid);
+        bits
+    }
+
+    #[test]
+    fn write_with_blocked_write_out_settles() {
+        let mut sim = Simulator::new(&m::<Bits<U<8>>, 0, 4>(0.into())).unwrap();
+        let inputs = sim.input_channels().into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+        let outputs = sim.output_channels().into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+        sim.reset().unwrap();
+
+        // A write request while only `read_out` is ready must not be accepted, and must not make the
+        // selector of the input mux depend on the ready of its own output.
+        sim.set_input_fwd(&inputs[0], vec![false; 3]);
+        sim.set_input_fwd(&inputs[1], request(true, 1, 5));
+        sim.set_output_bwd(&outputs[0], vec![true]);
+        sim.set_output_bwd(&outputs[1], vec![false]);
+        sim.eval().unwrap();
+        assert_eq!(sim.input_bwd(&inputs[1]), &[false]);
+
+        sim.set_output_bwd(&outputs[1], vec![true]);
+        sim.eval().unwrap();
+        assert_eq!(sim.input_bwd(&inputs[1]), &[true]);
+        sim.step().unwrap();
+
+        sim.set_input_fwd(&inputs[0], usize_to_bitvec(2, 1).into_iter().chain([true]).collect());
+        sim.set_input_fwd(&inputs[1], request(false, 0, 0));
+        sim.eval().unwrap();
+        assert_eq!(sim.output_fwd(&outputs[0]), &request(true, 1, 5)[..]);
+    }
+}
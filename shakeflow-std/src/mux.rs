@@ -16,6 +16,7 @@ pub trait MuxExt {
 impl<V: Signal, const CL: usize, const N: usize> MuxExt for (UniChannel<Bits<U<CL>>>, [UniChannel<V>; N]) {
     type Output = UniChannel<V>;
 
+    #[track_caller]
     fn mux(self, k: &mut CompositeModuleContext) -> UniChannel<V> {
         self.fsm::<(), UniChannel<V>, _>(k, Some("mux"), ().into(), |fwd_i, bwd_o, s| {
             let (sel, fwd_i) = *fwd_i;
@@ -32,6 +33,7 @@ impl<V: Signal, const CL: usize, const N: usize> MuxExt for (UniChannel<Bits<U<C
 impl<V: Signal, CL: Num, const N: usize, const P: Protocol> MuxExt for (UniChannel<Bits<CL>>, [VrChannel<V, P>; N]) {
     type Output = VrChannel<V, P>;
 
+    #[track_caller]
     fn mux(self, k: &mut CompositeModuleContext) -> VrChannel<V, P> {
         self.fsm::<(), VrChannel<V, P>, _>(k, Some("mux"), ().into(), |fwd_i, bwd_o, s| {
             let (sel, fwd_i) = *fwd_i;
@@ -44,6 +46,7 @@ impl<V: Signal, CL: Num, const N: usize, const P: Protocol> MuxExt for (UniChann
     }
 }
 
+#[track_caller]
 fn m_vr_array<V: Signal, const N: usize>() -> Module<[VrChannel<V>; N], (UniChannel<Bits<U<N>>>, VrChannel<V>)> {
     composite::<[VrChannel<V>; N], (UniChannel<Bits<U<N>>>, VrChannel<V>), _>(
         "mux",
@@ -78,5 +81,6 @@ fn m_vr_array<V: Signal, const N: usize>() -> Module<[VrChannel<V>; N], (UniChan
 impl<V: Signal, const N: usize> MuxExt for [VrChannel<V>; N] {
     type Output = (UniChannel<Bits<U<N>>>, VrChannel<V>);
 
+    #[track_caller]
     fn mux(self, k: &mut CompositeModuleContext) -> Self::Output { self.comb_inline(k, m_vr_array()) }
 }
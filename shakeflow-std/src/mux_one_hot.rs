@@ -15,6 +15,7 @@ pub trait MuxOneHotExt {
 impl<N: Num, M: Num> MuxOneHotExt for (UniChannel<Array<Bits<N>, M>>, UniChannel<Bits<M>>) {
     type Output = UniChannel<Bits<N>>;
 
+    #[track_caller]
     fn mux_one_hot(self, k: &mut CompositeModuleContext) -> UniChannel<Bits<N>> {
         self.0.zip(k, self.1).map(k, |i| {
             let (data, sel_one_hot) = *i;
@@ -28,6 +29,7 @@ impl<N: Num, M: Num> MuxOneHotExt for (UniChannel<Array<Bits<N>, M>>, UniChannel
 impl<N: Num, M: Num, const P: Protocol> MuxOneHotExt for (VrChannel<Array<Bits<N>, M>, P>, UniChannel<Bits<M>>) {
     type Output = VrChannel<Bits<N>, P>;
 
+    #[track_caller]
     fn mux_one_hot(self, k: &mut CompositeModuleContext) -> VrChannel<Bits<N>, P> {
         self.0.zip_uni(k, self.1).map(k, |i| {
             let (data, sel_one_hot) = *i;
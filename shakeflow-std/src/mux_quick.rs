@@ -15,6 +15,7 @@ pub trait MuxQuickExt {
     fn mux_quick(self, k: &mut CompositeModuleContext) -> (UniChannel<Self::Sel>, Self::O);
 }
 
+#[track_caller]
 fn m<V: Signal, const N: usize>() -> Module<[VrChannel<V>; N], (UniChannel<Bits<U<N>>>, UniChannel<V>)> {
     composite::<[VrChannel<V>; N], (UniChannel<Bits<U<N>>>, UniChannel<V>), _>(
         "mux_quick",
@@ -43,5 +44,6 @@ impl<V: Signal, const N: usize> MuxQuickExt for [VrChannel<V>; N] {
     type O = UniChannel<V>;
     type Sel = Bits<U<N>>;
 
+    #[track_caller]
     fn mux_quick(self, k: &mut CompositeModuleContext) -> (UniChannel<Self::Sel>, Self::O) { self.comb_inline(k, m()) }
 }
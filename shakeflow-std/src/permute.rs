@@ -17,6 +17,7 @@ pub trait PermuteExt<const N: usize> {
 impl<V: Signal, const N: usize> PermuteExt<N> for [UniChannel<V>; N] {
     type Selector = UniChannel<Array<Bits<Log2<U<N>>>, U<N>>>;
 
+    #[track_caller]
     fn permute(self, k: &mut CompositeModuleContext, selector: Self::Selector) -> Self {
         (self, selector).fsm::<(), Self, _>(k, Some("permute"), ().into(), |fwd_i, _, s| {
             let (fwd_i, select) = *fwd_i;
@@ -29,6 +30,7 @@ impl<V: Signal, const N: usize> PermuteExt<N> for [UniChannel<V>; N] {
 impl<V: Signal, const N: usize, const P: Protocol> PermuteExt<N> for [VrChannel<V, P>; N] {
     type Selector = (UniChannel<Array<Bits<Log2<U<N>>>, U<N>>>, UniChannel<Array<Bits<Log2<U<N>>>, U<N>>>);
 
+    #[track_caller]
     fn permute(self, k: &mut CompositeModuleContext, selector: Self::Selector) -> Self {
         // TODO: Calculate `select_inv` from `select`
         let (select, select_inv) = selector;
@@ -24,6 +24,7 @@ pub type IC<const N: usize> = UniChannel<I<N>>;
 pub type OC<const N: usize> = UniChannel<Valid<O<N>>>;
 
 // TODO: Is this correct?
+#[track_caller]
 fn m<V: Signal, const N: usize>() -> Module<[VrChannel<V>; N], VrChannel<(Bits<Log2<U<N>>>, V)>> {
     composite::<([VrChannel<V>; N], IC<N>), (VrChannel<(Bits<Log2<U<N>>>, V)>, IC<N>), _>(
         "priority_mux",
@@ -83,6 +84,7 @@ where Self: Interface
 impl<const N: usize> PriorityMuxExt for IC<N> {
     type O = OC<N>;
 
+    #[track_caller]
     fn priority_mux(self, k: &mut CompositeModuleContext) -> Self::O {
         self.module_inst::<OC<N>>(
             k,
@@ -99,5 +101,6 @@ impl<const N: usize> PriorityMuxExt for IC<N> {
 impl<V: Signal, const N: usize> PriorityMuxExt for [VrChannel<V>; N] {
     type O = VrChannel<(Bits<Log2<U<N>>>, V)>;
 
+    #[track_caller]
     fn priority_mux(self, k: &mut CompositeModuleContext) -> Self::O { self.comb_inline(k, m::<_, N>()) }
 }
@@ -5,6 +5,7 @@
 use crate::*;
 
 /// Creates a forward register slice module.
+#[track_caller]
 fn m_fwd<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, (VrChannel<V>, UniChannel<bool>)> {
     composite::<VrChannel<V, P>, (VrChannel<V>, UniChannel<bool>), _>(
         "register_slice_fwd",
@@ -32,6 +33,7 @@ fn m_fwd<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, (VrChannel<V
 }
 
 /// Creates a backward register slice module.
+#[track_caller]
 fn m_bwd<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, VrChannel<V>> {
     composite::<VrChannel<V, P>, VrChannel<V>, _>("register_slice_bwd", Some("in"), Some("out"), |value, k| {
         value.fsm::<Ready, VrChannel<_>, _>(k, None, Expr::<Ready>::new(false.into()), |fwd, bwd, s| (fwd, s, bwd))
@@ -46,6 +48,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     ///
     /// The second `UniChannel<bool>` indicates whether the registered value is occupied and remaining in
     /// the next cycle.
+    #[track_caller]
     pub fn register_slice_fwd(self, k: &mut CompositeModuleContext) -> (VrChannel<I>, UniChannel<bool>) {
         self.comb_inline(k, m_fwd())
     }
@@ -56,5 +59,6 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// register slice will malfunction. Reason: we didn't install data/valid registers for the case
     /// that (1) ready_reg is true; and (2) ready_in is false.
     #[must_use]
+    #[track_caller]
     pub fn register_slice_bwd(self, k: &mut CompositeModuleContext) -> VrChannel<I> { self.comb_inline(k, m_bwd()) }
 }
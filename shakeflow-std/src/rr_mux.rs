@@ -10,6 +10,7 @@ pub trait RrMuxExt<I: Interface, const N: usize> {
     fn rr_mux(self, k: &mut CompositeModuleContext) -> (UniChannel<Bits<Log2<U<N>>>>, I);
 }
 
+#[track_caller]
 fn m_rr_mux_vr<V: Signal, const N: usize>() -> Module<[VrChannel<V>; N], (UniChannel<Bits<Log2<U<N>>>>, VrChannel<V>)> {
     composite::<(UniChannel<Bits<Log2<U<N>>>>, [VrChannel<V>; N]), (UniChannel<Bits<Log2<U<N>>>>, VrChannel<V>), _>(
         "rr_mux",
@@ -27,6 +28,7 @@ fn m_rr_mux_vr<V: Signal, const N: usize>() -> Module<[VrChannel<V>; N], (UniCha
 }
 
 impl<V: Signal, const N: usize> RrMuxExt<VrChannel<V>, N> for [VrChannel<V>; N] {
+    #[track_caller]
     fn rr_mux(self, k: &mut CompositeModuleContext) -> (UniChannel<Bits<Log2<U<N>>>>, VrChannel<V>) {
         self.comb_inline(k, m_rr_mux_vr::<V, N>())
     }
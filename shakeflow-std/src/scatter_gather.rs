@@ -24,6 +24,7 @@ pub trait GatherExt<const N: usize> {
 impl<V: Signal, const N: usize, const P: Protocol> ScatterExt<N> for VrChannel<Array<V, U<N>>, P> {
     type Out = [VrChannel<V, P>; N];
 
+    #[track_caller]
     fn scatter(self, k: &mut CompositeModuleContext) -> Self::Out {
         self.fsm::<(), Self::Out, _>(k, None, ().into(), |fwd_i, bwd_o, s| {
             let fwd_o = Expr::<Valid<_>>::new_arr(fwd_i.valid.repeat::<U<N>>(), fwd_i.inner);
@@ -37,6 +38,7 @@ impl<V: Signal, const N: usize, const P: Protocol> ScatterExt<N> for VrChannel<A
 impl<V: Signal, const N: usize, const P: Protocol> GatherExt<N> for [VrChannel<V, P>; N] {
     type Out = VrChannel<Array<V, U<N>>, P>;
 
+    #[track_caller]
     fn gather(self, k: &mut CompositeModuleContext) -> Self::Out {
         self.fsm::<(), Self::Out, _>(k, None, ().into(), |fwd_i, bwd_o, s| {
             let v_o = fwd_i.map(|fwd| fwd.valid).all();
@@ -0,0 +1,1115 @@
+//! Transaction-level testbench components for simulated modules.
+//!
+//! Each component is attached to a channel of a [`Simulator`]. In each cycle, call `drive` on all
+//! components, [`Simulator::eval`], `sample` on all components, and then [`Simulator::step`]. The
+//! [`run`] function does exactly this.
+//!
+//! Drivers and monitors can randomly withhold `valid` and drop `ready` with a [`Backpressure`], and
+//! [`check_latency_insensitive`] checks that such stalls do not change the transactions.
+//!
+//! A [`GoldenModel`] feeds transactions to both a module and a Rust reference function, and
+//! reports the first transaction of the module that differs from the reference.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::marker::PhantomData;
+
+use shakeflow::lir::EndpointPath;
+use shakeflow::sim::SimError;
+
+use crate::axis::*;
+use crate::*;
+
+/// Testbench component attached to channels of a simulator.
+pub trait Component {
+    /// Drives the signals of the current cycle. Called before evaluating the combinational logic.
+    fn drive(&mut self, sim: &mut Simulator);
+
+    /// Samples the settled signals of the current cycle. Called before advancing the clock.
+    fn sample(&mut self, sim: &Simulator);
+
+    /// Returns `true` if the component has nothing left to do.
+    fn is_done(&self) -> bool { true }
+}
+
+/// Component which pushes transactions into an input channel.
+pub trait Source: Component {
+    /// Transaction.
+    type Transaction: Clone;
+
+    /// Queues a transaction.
+    fn send(&mut self, transaction: Self::Transaction);
+}
+
+/// Component which collects transactions from an output channel.
+pub trait Sink: Component {
+    /// Transaction.
+    type Transaction: Clone + PartialEq;
+
+    /// Returns the collected transactions.
+    fn received(&self) -> &[Self::Transaction];
+}
+
+/// Runs the simulation until all components are done, for at most `max_cycles` cycles.
+///
+/// Returns the number of simulated cycles.
+pub fn run(sim: &mut Simulator, components: &mut [&mut dyn Component], max_cycles: usize) -> Result<usize, SimError> {
+    for cycle in 0..max_cycles {
+        if components.iter().all(|component| component.is_done()) {
+            return Ok(cycle);
+        }
+
+        for component in components.iter_mut() {
+            component.drive(sim);
+        }
+        sim.eval()?;
+        for component in components.iter_mut() {
+            component.sample(sim);
+        }
+        sim.step()?;
+    }
+
+    Ok(max_cycles)
+}
+
+/// Random stall generator.
+#[derive(Debug, Clone)]
+pub struct Backpressure {
+    /// State of the xorshift generator.
+    state: u64,
+
+    /// Probability of stalling in each cycle.
+    probability: f64,
+}
+
+impl Backpressure {
+    /// Creates a new generator which stalls with the given probability in each cycle.
+    pub fn new(seed: u64, probability: f64) -> Self {
+        assert!((0.0..=1.0).contains(&probability), "probability should be in [0, 1]");
+        // The state of xorshift should not be zero.
+        Self { state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1, probability }
+    }
+
+    /// Creates a new generator with the same probability, seeded from this generator.
+    ///
+    /// Used to give each component its own stream of stalls.
+    #[must_use]
+    pub fn fork(&mut self) -> Self { Self::new(self.next_u64(), self.probability) }
+
+    /// Returns `true` if the current cycle should stall.
+    pub fn stall(&mut self) -> bool { ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < self.probability }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+/// Result of `check_latency_insensitive`.
+#[derive(Debug)]
+pub enum LatencyCheckError<T> {
+    /// Simulation with the given seed (`None` for the run without stalls) failed.
+    Sim(Option<u64>, SimError),
+
+    /// Transactions with stalls of the given seed differ from those without stalls.
+    Mismatch {
+        /// Seed of the stalls.
+        seed: u64,
+
+        /// Transactions without stalls.
+        expected: T,
+
+        /// Transactions with stalls.
+        actual: T,
+    },
+}
+
+/// Checks that random stalls do not change the transactions of a module.
+///
+/// `testbench` builds a simulator and components, runs them, and returns the observed
+/// transactions. It is called once without stalls, and then once with a `Backpressure` for each
+/// seed, which should be forked for each driver and monitor with [`Backpressure::fork`].
+pub fn check_latency_insensitive<T: PartialEq, F: FnMut(Option<Backpressure>) -> Result<T, SimError>>(
+    seeds: &[u64], probability: f64, mut testbench: F,
+) -> Result<(), LatencyCheckError<T>> {
+    let expected = testbench(None).map_err(|e| LatencyCheckError::Sim(None, e))?;
+
+    for seed in seeds {
+        let actual = testbench(Some(Backpressure::new(*seed, probability)))
+            .map_err(|e| LatencyCheckError::Sim(Some(*seed), e))?;
+        if actual != expected {
+            return Err(LatencyCheckError::Mismatch { seed: *seed, expected, actual });
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if the component should stall in the current cycle.
+fn stall(backpressure: &mut Option<Backpressure>) -> bool { backpressure.as_mut().map_or(false, Backpressure::stall) }
+
+/// Pushes a queue of values into a valid-ready input channel.
+#[derive(Debug)]
+pub struct VrDriver<V: Signal> {
+    path: EndpointPath,
+    queue: VecDeque<V>,
+    backpressure: Option<Backpressure>,
+
+    /// Whether the front of the queue is offered. Once offered, it is offered until transferred.
+    offered: bool,
+}
+
+impl<V: Signal> VrDriver<V> {
+    /// Creates a new driver for the input channel at `path`.
+    pub fn new(path: EndpointPath) -> Self { Self { path, queue: VecDeque::new(), backpressure: None, offered: false } }
+
+    /// Makes the driver randomly withhold `valid` before offering each value.
+    #[must_use]
+    pub fn with_backpressure(mut self, backpressure: Option<Backpressure>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Queues a value.
+    pub fn push(&mut self, value: V) { self.queue.push_back(value); }
+
+    /// Returns the number of values not yet transferred.
+    pub fn len(&self) -> usize { self.queue.len() }
+
+    /// Returns `true` if all values are transferred.
+    pub fn is_empty(&self) -> bool { self.queue.is_empty() }
+}
+
+impl<V: Signal> Extend<V> for VrDriver<V> {
+    fn extend<T: IntoIterator<Item = V>>(&mut self, iter: T) { self.queue.extend(iter) }
+}
+
+impl<V: Signal> Source for VrDriver<V> {
+    type Transaction = V;
+
+    fn send(&mut self, transaction: V) { self.push(transaction) }
+}
+
+impl<V: Signal> Component for VrDriver<V> {
+    fn drive(&mut self, sim: &mut Simulator) {
+        if !self.offered && !self.queue.is_empty() {
+            self.offered = !stall(&mut self.backpressure);
+        }
+
+        let fwd = match self.queue.front() {
+            Some(inner) if self.offered => Valid { inner: inner.clone(), valid: true }.transl(),
+            _ => vec![false; Valid::<V>::WIDTH],
+        };
+        sim.set_input_fwd(&self.path, fwd);
+    }
+
+    fn sample(&mut self, sim: &Simulator) {
+        if self.offered && sim.input_bwd(&self.path)[0] {
+            self.queue.pop_front();
+            self.offered = false;
+        }
+    }
+
+    fn is_done(&self) -> bool { self.is_empty() }
+}
+
+/// Collects values from a valid-ready output channel.
+#[derive(Debug)]
+pub struct VrMonitor<V: Signal> {
+    path: EndpointPath,
+    expected: Option<usize>,
+    values: Vec<Value>,
+    backpressure: Option<Backpressure>,
+    ready: bool,
+    _marker: PhantomData<V>,
+}
+
+impl<V: Signal> VrMonitor<V> {
+    /// Creates a new monitor for the output channel at `path`, which is always ready.
+    pub fn new(path: EndpointPath) -> Self {
+        Self { path, expected: None, values: Vec::new(), backpressure: None, ready: true, _marker: PhantomData }
+    }
+
+    /// Makes the monitor randomly drop `ready`.
+    #[must_use]
+    pub fn with_backpressure(mut self, backpressure: Option<Backpressure>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Makes the monitor not done until `count` values are collected.
+    #[must_use]
+    pub fn expect(mut self, count: usize) -> Self {
+        self.expected = Some(count);
+        self
+    }
+
+    /// Returns the collected values.
+    pub fn values(&self) -> &[Value] { &self.values }
+
+    /// Consumes the monitor, returning the collected values.
+    pub fn into_values(self) -> Vec<Value> { self.values }
+}
+
+impl<V: Signal> Sink for VrMonitor<V> {
+    type Transaction = Value;
+
+    fn received(&self) -> &[Value] { self.values() }
+}
+
+impl<V: Signal> Component for VrMonitor<V> {
+    fn drive(&mut self, sim: &mut Simulator) {
+        self.ready = !stall(&mut self.backpressure);
+        sim.set_output_bwd(&self.path, vec![self.ready]);
+    }
+
+    fn sample(&mut self, sim: &Simulator) {
+        let fwd = sim.output_fwd(&self.path);
+        if self.ready && fwd[V::WIDTH] {
+            self.values.push(V::decode(&fwd[..V::WIDTH]));
+        }
+    }
+
+    fn is_done(&self) -> bool { self.expected.map_or(true, |count| self.values.len() >= count) }
+}
+
+/// Drives a constant value into a unidirectional input channel.
+#[derive(Debug)]
+pub struct Constant {
+    path: EndpointPath,
+    bits: Vec<bool>,
+}
+
+impl Constant {
+    /// Creates a new driver of `value` for the input channel at `path`.
+    pub fn new<V: Signal>(path: EndpointPath, value: V) -> Self { Self { path, bits: value.transl() } }
+}
+
+impl Component for Constant {
+    fn drive(&mut self, sim: &mut Simulator) { sim.set_input_fwd(&self.path, self.bits.clone()); }
+
+    fn sample(&mut self, _sim: &Simulator) {}
+}
+
+/// Pushes a queue of values into a unidirectional input channel of `Valid<V>`, each for one cycle.
+#[derive(Debug)]
+pub struct UniDriver<V: Signal> {
+    path: EndpointPath,
+    queue: VecDeque<V>,
+    backpressure: Option<Backpressure>,
+
+    /// Whether the front of the queue is driven in the current cycle.
+    offered: bool,
+}
+
+impl<V: Signal> UniDriver<V> {
+    /// Creates a new driver for the input channel at `path`.
+    pub fn new(path: EndpointPath) -> Self { Self { path, queue: VecDeque::new(), backpressure: None, offered: false } }
+
+    /// Makes the driver randomly delay each value.
+    #[must_use]
+    pub fn with_backpressure(mut self, backpressure: Option<Backpressure>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+}
+
+impl<V: Signal> Source for UniDriver<V> {
+    type Transaction = V;
+
+    fn send(&mut self, transaction: V) { self.queue.push_back(transaction); }
+}
+
+impl<V: Signal> Component for UniDriver<V> {
+    fn drive(&mut self, sim: &mut Simulator) {
+        self.offered = !self.queue.is_empty() && !stall(&mut self.backpressure);
+        let fwd = match self.queue.front() {
+            Some(inner) if self.offered => Valid { inner: inner.clone(), valid: true }.transl(),
+            _ => vec![false; Valid::<V>::WIDTH],
+        };
+        sim.set_input_fwd(&self.path, fwd);
+    }
+
+    fn sample(&mut self, _sim: &Simulator) {
+        // Unidirectional channels take the values whenever they are valid.
+        if self.offered {
+            self.queue.pop_front();
+        }
+    }
+
+    fn is_done(&self) -> bool { self.queue.is_empty() }
+}
+
+/// Collects values from a unidirectional output channel of `Valid<V>`.
+#[derive(Debug)]
+pub struct UniMonitor<V: Signal> {
+    path: EndpointPath,
+    expected: Option<usize>,
+    values: Vec<Value>,
+    _marker: PhantomData<V>,
+}
+
+impl<V: Signal> UniMonitor<V> {
+    /// Creates a new monitor for the output channel at `path`.
+    pub fn new(path: EndpointPath) -> Self { Self { path, expected: None, values: Vec::new(), _marker: PhantomData } }
+
+    /// Makes the monitor not done until `count` values are collected.
+    #[must_use]
+    pub fn expect(mut self, count: usize) -> Self {
+        self.expected = Some(count);
+        self
+    }
+
+    /// Returns the collected values.
+    pub fn values(&self) -> &[Value] { &self.values }
+
+    /// Consumes the monitor, returning the collected values.
+    pub fn into_values(self) -> Vec<Value> { self.values }
+}
+
+impl<V: Signal> Sink for UniMonitor<V> {
+    type Transaction = Value;
+
+    fn received(&self) -> &[Value] { self.values() }
+}
+
+impl<V: Signal> Component for UniMonitor<V> {
+    fn drive(&mut self, _sim: &mut Simulator) {}
+
+    fn sample(&mut self, sim: &Simulator) {
+        let fwd = sim.output_fwd(&self.path);
+        if fwd[V::WIDTH] {
+            self.values.push(V::decode(&fwd[..V::WIDTH]));
+        }
+    }
+
+    fn is_done(&self) -> bool { self.expected.map_or(true, |count| self.values.len() >= count) }
+}
+
+/// Answers each value collected from a valid-ready output channel with the transactions given by
+/// `respond`, sent to an input channel. It is done when the monitor and the source are done.
+pub struct Responder<V: Signal, S: Source, F> {
+    monitor: VrMonitor<V>,
+    source: S,
+    respond: F,
+
+    /// Number of the collected values answered.
+    answered: usize,
+}
+
+impl<V: Signal, S: Source + fmt::Debug, F> fmt::Debug for Responder<V, S, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Responder")
+            .field("monitor", &self.monitor)
+            .field("source", &self.source)
+            .field("answered", &self.answered)
+            .finish()
+    }
+}
+
+impl<V: Signal, S: Source, F: FnMut(&Value) -> Vec<S::Transaction>> Responder<V, S, F> {
+    /// Creates a new responder from `monitor` to `source`.
+    pub fn new(monitor: VrMonitor<V>, source: S, respond: F) -> Self { Self { monitor, source, respond, answered: 0 } }
+
+    /// Consumes the responder, returning the collected values.
+    pub fn into_values(self) -> Vec<Value> { self.monitor.into_values() }
+}
+
+impl<V: Signal, S: Source, F: FnMut(&Value) -> Vec<S::Transaction>> Component for Responder<V, S, F> {
+    fn drive(&mut self, sim: &mut Simulator) {
+        self.monitor.drive(sim);
+        self.source.drive(sim);
+    }
+
+    fn sample(&mut self, sim: &Simulator) {
+        self.monitor.sample(sim);
+        self.source.sample(sim);
+
+        while let Some(value) = self.monitor.values().get(self.answered) {
+            for transaction in (self.respond)(value) {
+                self.source.send(transaction);
+            }
+            self.answered += 1;
+        }
+    }
+
+    fn is_done(&self) -> bool { self.monitor.is_done() && self.source.is_done() }
+}
+
+/// Pushes packets of bytes into an AXI4-Stream input channel of `Keep<WIDTH, KWIDTH>` data.
+///
+/// Each packet is split into beats of `KWIDTH` bytes, and `tkeep` of the last beat masks out the
+/// unused bytes. `tlast` is asserted on the last beat.
+#[derive(Debug)]
+pub struct AxisDriver<WIDTH: Num, KWIDTH: Num> {
+    path: EndpointPath,
+    beats: VecDeque<(Vec<u8>, bool)>,
+    backpressure: Option<Backpressure>,
+
+    /// Whether the front beat is offered. Once offered, it is offered until transferred.
+    offered: bool,
+    _marker: PhantomData<(WIDTH, KWIDTH)>,
+}
+
+impl<WIDTH: Num, KWIDTH: Num> AxisDriver<WIDTH, KWIDTH> {
+    /// Creates a new driver for the input channel at `path`.
+    pub fn new(path: EndpointPath) -> Self {
+        assert_eq!(WIDTH::WIDTH, KWIDTH::WIDTH * 8, "TDATA should consist of TKEEP-many bytes");
+        Self { path, beats: VecDeque::new(), backpressure: None, offered: false, _marker: PhantomData }
+    }
+
+    /// Makes the driver randomly withhold `tvalid` before offering each beat.
+    #[must_use]
+    pub fn with_backpressure(mut self, backpressure: Option<Backpressure>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Queues a packet. Empty packets are ignored.
+    pub fn push(&mut self, packet: &[u8]) {
+        let count = packet.chunks(KWIDTH::WIDTH).count();
+        for (i, beat) in packet.chunks(KWIDTH::WIDTH).enumerate() {
+            self.beats.push_back((beat.to_vec(), i + 1 == count));
+        }
+    }
+
+    /// Returns `true` if all packets are transferred.
+    pub fn is_empty(&self) -> bool { self.beats.is_empty() }
+}
+
+impl<WIDTH: Num, KWIDTH: Num> Source for AxisDriver<WIDTH, KWIDTH> {
+    type Transaction = Vec<u8>;
+
+    fn send(&mut self, transaction: Vec<u8>) { self.push(&transaction) }
+}
+
+impl<WIDTH: Num, KWIDTH: Num> Component for AxisDriver<WIDTH, KWIDTH> {
+    fn drive(&mut self, sim: &mut Simulator) {
+        if !self.offered && !self.beats.is_empty() {
+            self.offered = !stall(&mut self.backpressure);
+        }
+
+        let fwd = match self.beats.front() {
+            Some((beat, tlast)) if self.offered => {
+                let tdata = (0..KWIDTH::WIDTH)
+                    .flat_map(|i| {
+                        let byte = beat.get(i).copied().unwrap_or(0);
+                        (0..8).map(move |j| (byte >> j) & 1 == 1)
+                    })
+                    .collect();
+                let tkeep = (0..KWIDTH::WIDTH).map(|i| i < beat.len()).collect();
+                AxisValid {
+                    inner: AxisValue {
+                        payload: Keep::<WIDTH, KWIDTH> { tdata: Bits::new(tdata), tkeep: Bits::new(tkeep) },
+                        tlast: *tlast,
+                    },
+                    tvalid: true,
+                }
+                .transl()
+            }
+            _ => vec![false; AxisValid::<AxisValue<Keep<WIDTH, KWIDTH>>>::WIDTH],
+        };
+        sim.set_input_fwd(&self.path, fwd);
+    }
+
+    fn sample(&mut self, sim: &Simulator) {
+        if self.offered && sim.input_bwd(&self.path)[0] {
+            self.beats.pop_front();
+            self.offered = false;
+        }
+    }
+
+    fn is_done(&self) -> bool { self.is_empty() }
+}
+
+/// Collects packets of bytes from an AXI4-Stream output channel of `Keep<WIDTH, KWIDTH>` data.
+///
+/// Bytes whose `tkeep` bits are deasserted are dropped, and a packet ends at the beat with `tlast`.
+#[derive(Debug)]
+pub struct AxisMonitor<WIDTH: Num, KWIDTH: Num> {
+    path: EndpointPath,
+    expected: Option<usize>,
+    current: Vec<u8>,
+    packets: Vec<Vec<u8>>,
+    backpressure: Option<Backpressure>,
+    tready: bool,
+    _marker: PhantomData<(WIDTH, KWIDTH)>,
+}
+
+impl<WIDTH: Num, KWIDTH: Num> AxisMonitor<WIDTH, KWIDTH> {
+    /// Creates a new monitor for the output channel at `path`, which is always ready.
+    pub fn new(path: EndpointPath) -> Self {
+        assert_eq!(WIDTH::WIDTH, KWIDTH::WIDTH * 8, "TDATA should consist of TKEEP-many bytes");
+        Self {
+            path,
+            expected: None,
+            current: Vec::new(),
+            packets: Vec::new(),
+            backpressure: None,
+            tready: true,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes the monitor randomly drop `tready`.
+    #[must_use]
+    pub fn with_backpressure(mut self, backpressure: Option<Backpressure>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Makes the monitor not done until `count` packets are collected.
+    #[must_use]
+    pub fn expect(mut self, count: usize) -> Self {
+        self.expected = Some(count);
+        self
+    }
+
+    /// Returns the collected packets.
+    pub fn packets(&self) -> &[Vec<u8>] { &self.packets }
+
+    /// Consumes the monitor, returning the collected packets.
+    pub fn into_packets(self) -> Vec<Vec<u8>> { self.packets }
+}
+
+impl<WIDTH: Num, KWIDTH: Num> Sink for AxisMonitor<WIDTH, KWIDTH> {
+    type Transaction = Vec<u8>;
+
+    fn received(&self) -> &[Vec<u8>] { self.packets() }
+}
+
+impl<WIDTH: Num, KWIDTH: Num> Component for AxisMonitor<WIDTH, KWIDTH> {
+    fn drive(&mut self, sim: &mut Simulator) {
+        self.tready = !stall(&mut self.backpressure);
+        sim.set_output_bwd(&self.path, vec![self.tready]);
+    }
+
+    fn sample(&mut self, sim: &Simulator) {
+        // Layout: `tdata`, `tkeep`, `tlast`, `tvalid`.
+        let fwd = sim.output_fwd(&self.path);
+        let (tdata, rest) = fwd.split_at(WIDTH::WIDTH);
+        let (tkeep, rest) = rest.split_at(KWIDTH::WIDTH);
+        let (tlast, tvalid) = (rest[0], rest[1]);
+
+        if !(self.tready && tvalid) {
+            return;
+        }
+
+        for (byte, keep) in tdata.chunks(8).zip(tkeep) {
+            if *keep {
+                self.current.push(byte.iter().rev().fold(0, |acc, b| (acc << 1) | u8::from(*b)));
+            }
+        }
+        if tlast {
+            self.packets.push(std::mem::take(&mut self.current));
+        }
+    }
+
+    fn is_done(&self) -> bool { self.expected.map_or(true, |count| self.packets.len() >= count) }
+}
+
+/// Result of `GoldenModel::check`.
+#[derive(Debug)]
+pub enum GoldenModelError<I, O> {
+    /// Simulation failed.
+    Sim(SimError),
+
+    /// The module produced a transaction different from the reference.
+    Mismatch {
+        /// Index of the output transaction.
+        index: usize,
+
+        /// Input transaction from which the reference produced the expected transaction.
+        input: I,
+
+        /// Transaction produced by the reference.
+        expected: O,
+
+        /// Transaction produced by the module.
+        actual: O,
+    },
+
+    /// The module did not produce a transaction the reference produced.
+    Missing {
+        /// Index of the output transaction.
+        index: usize,
+
+        /// Input transaction from which the reference produced the expected transaction.
+        input: I,
+
+        /// Transaction produced by the reference.
+        expected: O,
+    },
+
+    /// The module produced more transactions than the reference.
+    Unexpected {
+        /// Index of the output transaction.
+        index: usize,
+
+        /// Transaction produced by the module.
+        actual: O,
+    },
+}
+
+/// Co-simulates a module with a reference function.
+///
+/// Each transaction sent is pushed into `source` and given to `reference`, which returns the
+/// transactions expected on `sink` in order. Transactions collected by `sink` are compared as they
+/// arrive, and the component is done at the first mismatch or when all expected transactions are
+/// collected. For example, the reference of a valid-ready module may be
+/// `|x: &Bits<U<8>>| vec![x.clone().to_value()]`.
+pub struct GoldenModel<S: Source, K: Sink, F> {
+    source: S,
+    sink: K,
+    reference: F,
+
+    /// Sent transactions.
+    inputs: Vec<S::Transaction>,
+
+    /// Expected transactions with the indices of the input transactions producing them.
+    expected: Vec<(usize, K::Transaction)>,
+
+    /// Number of compared transactions.
+    compared: usize,
+
+    /// First mismatch.
+    error: Option<GoldenModelError<S::Transaction, K::Transaction>>,
+}
+
+impl<S: Source + fmt::Debug, K: Sink + fmt::Debug, F> fmt::Debug for GoldenModel<S, K, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GoldenModel")
+            .field("source", &self.source)
+            .field("sink", &self.sink)
+            .field("inputs", &self.inputs.len())
+            .field("expected", &self.expected.len())
+            .field("compared", &self.compared)
+            .finish()
+    }
+}
+
+impl<S: Source, K: Sink, F: FnMut(&S::Transaction) -> Vec<K::Transaction>> GoldenModel<S, K, F> {
+    /// Creates a new co-simulation of the module between `source` and `sink` with `reference`.
+    pub fn new(source: S, sink: K, reference: F) -> Self {
+        Self { source, sink, reference, inputs: Vec::new(), expected: Vec::new(), compared: 0, error: None }
+    }
+
+    /// Sends a transaction to both the module and the reference.
+    pub fn send(&mut self, transaction: S::Transaction) {
+        let index = self.inputs.len();
+        let expected = (self.reference)(&transaction);
+        self.expected.extend(expected.into_iter().map(|output| (index, output)));
+        self.source.send(transaction.clone());
+        self.inputs.push(transaction);
+    }
+
+    /// Returns the source.
+    pub fn source(&self) -> &S { &self.source }
+
+    /// Returns the sink.
+    pub fn sink(&self) -> &K { &self.sink }
+
+    /// Returns the first mismatch, or a missing transaction if the module has not produced all
+    /// expected transactions.
+    pub fn check(self) -> Result<(), GoldenModelError<S::Transaction, K::Transaction>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        let index = self.sink.received().len();
+        if let Some((input, expected)) = self.expected.get(index) {
+            return Err(GoldenModelError::Missing {
+                index,
+                input: self.inputs[*input].clone(),
+                expected: expected.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Runs the simulation until done, for at most `max_cycles` cycles, and checks the result.
+    pub fn run(
+        mut self, sim: &mut Simulator, max_cycles: usize,
+    ) -> Result<(), GoldenModelError<S::Transaction, K::Transaction>> {
+        run(sim, &mut [&mut self], max_cycles).map_err(GoldenModelError::Sim)?;
+        self.check()
+    }
+}
+
+impl<S: Source, K: Sink, F: FnMut(&S::Transaction) -> Vec<K::Transaction>> Extend<S::Transaction>
+    for GoldenModel<S, K, F>
+{
+    fn extend<T: IntoIterator<Item = S::Transaction>>(&mut self, iter: T) {
+        for transaction in iter {
+            self.send(transaction);
+        }
+    }
+}
+
+impl<S: Source, K: Sink, F> Component for GoldenModel<S, K, F> {
+    fn drive(&mut self, sim: &mut Simulator) {
+        self.source.drive(sim);
+        self.sink.drive(sim);
+    }
+
+    fn sample(&mut self, sim: &Simulator) {
+        self.source.sample(sim);
+        self.sink.sample(sim);
+
+        let received = self.sink.received();
+        while self.error.is_none() && self.compared < received.len() {
+            let index = self.compared;
+            let actual = received[index].clone();
+            self.error = match self.expected.get(index) {
+                Some((_, expected)) if *expected == actual => None,
+                Some((input, expected)) => Some(GoldenModelError::Mismatch {
+                    index,
+                    input: self.inputs[*input].clone(),
+                    expected: expected.clone(),
+                    actual,
+                }),
+                None => Some(GoldenModelError::Unexpected { index, actual }),
+            };
+            self.compared += 1;
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.error.is_some() || (self.source.is_done() && self.sink.received().len() >= self.expected.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Byte = Bits<U<8>>;
+    type Data = Keep<U<32>, U<4>>;
+
+    /// Returns a skid buffer of bytes.
+    fn skid_buffer() -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        composite::<VrChannel<Byte>, VrChannel<Byte>, _>("skid", Some("in"), Some("out"), |input, k| {
+            input.buffer_skid(k)
+        })
+        .build()
+    }
+
+    /// Returns a skid buffer of AXI4-Stream beats.
+    fn axis_skid_buffer() -> Module<AxisChannel<Data>, AxisChannel<Data>> {
+        composite::<AxisChannel<Data>, AxisChannel<Data>, _>("axis_skid", Some("s_axis"), Some("m_axis"), |input, k| {
+            input.into_vr(k).buffer_skid(k).into_axis_vr(k)
+        })
+        .build()
+    }
+
+    /// Returns the simulator of the module after reset, with the paths of its input and output channels.
+    fn simulator<I: Interface, O: Interface>(module: &Module<I, O>) -> (Simulator, EndpointPath, EndpointPath) {
+        let mut sim = Simulator::new(module).unwrap();
+        let input = sim.input_channels()[0].0.clone();
+        let output = sim.output_channels()[0].0.clone();
+        sim.reset().unwrap();
+        (sim, input, output)
+    }
+
+    fn byte(value: usize) -> Byte { Bits::new(usize_to_bitvec(8, value)) }
+
+    #[test]
+    fn vr_driver_and_monitor_transfer_values() {
+        let (mut sim, input, output) = simulator(&skid_buffer());
+        let mut driver = VrDriver::new(input);
+        let mut monitor = VrMonitor::<Byte>::new(output).expect(5);
+        driver.extend((0..5).map(byte));
+
+        let cycles = run(&mut sim, &mut [&mut driver, &mut monitor], 100).unwrap();
+        assert!(cycles < 100);
+        assert!(driver.is_empty());
+        assert_eq!(monitor.into_values(), (0..5).map(|value| byte(value).to_value()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn vr_monitor_is_done_after_expected_values() {
+        let (mut sim, input, output) = simulator(&skid_buffer());
+        let mut driver = VrDriver::new(input);
+        let mut monitor = VrMonitor::<Byte>::new(output).expect(2);
+        driver.extend((0..2).map(byte));
+
+        // The monitor is not done until the values arrive, and the simulation stops once they do.
+        assert!(!monitor.is_done());
+        let cycles = run(&mut sim, &mut [&mut driver, &mut monitor], 100).unwrap();
+        assert!(monitor.is_done());
+        assert_eq!(sim.cycle(), cycles);
+        assert_eq!(monitor.values().len(), 2);
+    }
+
+    /// Returns a module registering valid bytes.
+    fn uni_register() -> Module<UniChannel<Valid<Byte>>, UniChannel<Valid<Byte>>> {
+        composite::<UniChannel<Valid<Byte>>, UniChannel<Valid<Byte>>, _>("reg", Some("in"), Some("out"), |input, k| {
+            input.buffer(k, Expr::invalid())
+        })
+        .build()
+    }
+
+    #[test]
+    fn uni_driver_and_monitor_transfer_values() {
+        let (mut sim, input, output) = simulator(&uni_register());
+        let mut driver = UniDriver::new(input).with_backpressure(Some(Backpressure::new(1, 0.5)));
+        let mut monitor = UniMonitor::<Byte>::new(output).expect(5);
+        for value in 0..5 {
+            driver.send(byte(value));
+        }
+
+        let cycles = run(&mut sim, &mut [&mut driver, &mut monitor], 100).unwrap();
+        assert!(cycles < 100);
+        assert!(driver.is_done());
+        assert_eq!(monitor.into_values(), (0..5).map(|value| byte(value).to_value()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn responder_answers_collected_values() {
+        let (mut sim, input, output) = simulator(&skid_buffer());
+        let mut driver = VrDriver::new(input);
+        driver.push(byte(0));
+
+        // Each value comes back incremented, until it reaches 4.
+        let mut responder = Responder::new(VrMonitor::<Byte>::new(output).expect(5), driver, |value: &Value| {
+            let Value::Bits(bits) = value else { panic!() };
+            let value = bits.iter().rev().fold(0, |value, bit| (value << 1) | *bit as usize);
+            if value < 4 {
+                vec![byte(value + 1)]
+            } else {
+                vec![]
+            }
+        });
+
+        let cycles = run(&mut sim, &mut [&mut responder], 100).unwrap();
+        assert!(cycles < 100);
+        assert_eq!(responder.into_values(), (0..5).map(|value| byte(value).to_value()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn axis_driver_and_monitor_transfer_packets() {
+        let (mut sim, input, output) = simulator(&axis_skid_buffer());
+        let packets = vec![vec![1], (2..6).collect(), (6..11).collect(), (11..20).collect::<Vec<u8>>()];
+        let mut driver = AxisDriver::<U<32>, U<4>>::new(input);
+        let mut monitor = AxisMonitor::<U<32>, U<4>>::new(output).expect(packets.len());
+        for packet in &packets {
+            driver.push(packet);
+        }
+
+        let cycles = run(&mut sim, &mut [&mut driver, &mut monitor], 100).unwrap();
+        assert!(cycles < 100);
+        assert!(driver.is_empty());
+        assert_eq!(monitor.into_packets(), packets);
+    }
+
+    #[test]
+    fn axis_driver_ignores_empty_packets() {
+        let (_, input, _) = simulator(&axis_skid_buffer());
+        let mut driver = AxisDriver::<U<32>, U<4>>::new(input);
+        driver.push(&[]);
+        assert!(driver.is_empty());
+    }
+
+    /// Returns a module of bytes built by `f`.
+    fn vr_module(
+        f: fn(VrChannel<Byte>, &mut CompositeModuleContext) -> VrChannel<Byte>,
+    ) -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        composite::<VrChannel<Byte>, VrChannel<Byte>, _>("dut", Some("in"), Some("out"), f).build()
+    }
+
+    /// Returns the values collected by monitors of the outputs, after pushing `count` bytes into each input.
+    fn collect<I: Interface, O: Interface>(
+        module: &Module<I, O>, count: usize, expected: usize, mut backpressure: Option<Backpressure>,
+    ) -> Result<Vec<Vec<Value>>, SimError> {
+        let mut sim = Simulator::new(module)?;
+        sim.reset()?;
+        let mut drivers = sim
+            .input_channels()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (path, _))| {
+                let mut driver = VrDriver::new(path).with_backpressure(backpressure.as_mut().map(Backpressure::fork));
+                driver.extend((0..count).map(|value| byte(i * 128 + value)));
+                driver
+            })
+            .collect::<Vec<_>>();
+        let mut monitors = sim
+            .output_channels()
+            .into_iter()
+            .map(|(path, _)| {
+                VrMonitor::<Byte>::new(path)
+                    .expect(expected)
+                    .with_backpressure(backpressure.as_mut().map(Backpressure::fork))
+            })
+            .collect::<Vec<_>>();
+
+        let mut components = drivers
+            .iter_mut()
+            .map(|driver| driver as &mut dyn Component)
+            .chain(monitors.iter_mut().map(|monitor| monitor as &mut dyn Component))
+            .collect::<Vec<_>>();
+        let _ = run(&mut sim, &mut components, 1000)?;
+        Ok(monitors.into_iter().map(VrMonitor::into_values).collect())
+    }
+
+    /// Checks that random stalls do not change the bytes passing through the module.
+    fn check<I: Interface, O: Interface>(module: Module<I, O>, count: usize, expected: usize) {
+        check_latency_insensitive(&[1, 2, 3, 4, 5], 0.5, |backpressure| {
+            collect(&module, count, expected, backpressure)
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn buffers_are_latency_insensitive() {
+        check(vr_module(|input, k| input.buffer(k)), 16, 16);
+        check(vr_module(|input, k| input.buffer_always(k)), 16, 16);
+        check(vr_module(|input, k| input.buffer_skid(k)), 16, 16);
+        check(vr_module(|input, k| input.fifo::<4>(k)), 16, 16);
+        check(
+            vr_module(|input, k| {
+                let (output, _) = input.register_slice_fwd(k);
+                output
+            }),
+            16,
+            16,
+        );
+    }
+
+    #[test]
+    fn maps_are_latency_insensitive() {
+        check(vr_module(|input, k| input.map(k, |value| (value + 1.into()).resize())), 16, 16);
+        check(vr_module(|input, k| input.filter(k, |value| value.clip_const::<U<1>>(0).is_eq(0.into()))), 16, 8);
+    }
+
+    #[test]
+    fn demux_is_latency_insensitive() {
+        let module =
+            composite::<VrChannel<Byte>, [VrChannel<Byte>; 2], _>("dut", Some("in"), Some("out"), |input, k| {
+                input.map(k, |value| (value, value.clip_const::<U<1>>(0)).into()).demux(k)
+            })
+            .build();
+        check(module, 16, 8);
+    }
+
+    #[test]
+    fn duplicate_and_zip_are_latency_insensitive() {
+        let module = composite::<VrChannel<Byte>, VrChannel<Byte>, _>("dut", Some("in"), Some("out"), |input, k| {
+            let (lhs, rhs) = input.duplicate::<{ Protocol::Helpful }, { Protocol::Demanding }>(k);
+            let rhs = rhs.fifo::<2>(k);
+            lhs.buffer_skid(k).zip_vr(k, rhs).map(k, |input| {
+                let (lhs, rhs) = *input;
+                (lhs + rhs).resize()
+            })
+        })
+        .build();
+        check(module, 16, 16);
+    }
+
+    #[test]
+    fn rr_mux_keeps_order_of_each_input() {
+        let module =
+            composite::<[VrChannel<Byte>; 2], VrChannel<Byte>, _>("dut", Some("in"), Some("out"), |input, k| {
+                let (_, output) = input.rr_mux(k);
+                output
+            })
+            .build();
+
+        // The interleaving of the inputs depends on the stalls, but the order of each input does not.
+        check_latency_insensitive(&[1, 2, 3, 4, 5], 0.5, |backpressure| {
+            let values = collect(&module, 8, 16, backpressure)?.remove(0);
+            let inputs = (0..2).map(|i| (0..8).map(|value| byte(i * 128 + value).to_value()).collect::<Vec<_>>());
+            Ok(inputs
+                .map(|input| values.iter().filter(|value| input.contains(value)).cloned().collect::<Vec<_>>())
+                .collect::<Vec<_>>())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn dropping_values_is_detected() {
+        // Accepts the values regardless of the output being ready.
+        let module = vr_module(|input, k| {
+            input.fsm::<(), VrChannel<Byte>, _>(k, None, ().into(), |fwd, _, state| {
+                (fwd, Expr::<Ready>::new(true.into()), state)
+            })
+        });
+        let result =
+            check_latency_insensitive(&[1, 2, 3, 4, 5], 0.5, |backpressure| collect(&module, 16, 16, backpressure));
+        assert!(matches!(result, Err(LatencyCheckError::Mismatch { .. })));
+    }
+
+    #[test]
+    fn backpressure_is_deterministic() {
+        let stalls = |seed| {
+            let mut backpressure = Backpressure::new(seed, 0.5);
+            (0..64).map(|_| backpressure.stall()).collect::<Vec<_>>()
+        };
+        assert_eq!(stalls(1), stalls(1));
+        assert_ne!(stalls(1), stalls(2));
+
+        let mut never = Backpressure::new(1, 0.0);
+        let mut always = Backpressure::new(1, 1.0);
+        assert!((0..64).all(|_| !never.stall() && always.stall()));
+    }
+
+    /// Returns the integer represented by the byte.
+    fn value(byte: &Byte) -> usize {
+        let Value::Bits(bits) = byte.clone().to_value() else { unreachable!() };
+        bits.iter().rev().fold(0, |value, bit| (value << 1) | usize::from(*bit))
+    }
+
+    /// Co-simulates a module incrementing bytes with `reference` on the bytes `0..count`.
+    fn golden_model<F: FnMut(&Byte) -> Vec<Value>>(
+        count: usize, reference: F, backpressure: Option<Backpressure>,
+    ) -> Result<(), GoldenModelError<Byte, Value>> {
+        let module = vr_module(|input, k| input.map(k, |value| (value + 1.into()).resize()).buffer_skid(k));
+        let (mut sim, input, output) = simulator(&module);
+        let mut golden_model = GoldenModel::new(
+            VrDriver::new(input),
+            VrMonitor::<Byte>::new(output).with_backpressure(backpressure),
+            reference,
+        );
+        golden_model.extend((0..count).map(byte));
+        golden_model.run(&mut sim, 1000)
+    }
+
+    #[test]
+    fn golden_model_accepts_matching_reference() {
+        let reference = |x: &Byte| vec![byte(value(x) + 1).to_value()];
+        golden_model(16, reference, None).unwrap();
+        golden_model(16, reference, Some(Backpressure::new(1, 0.5))).unwrap();
+    }
+
+    #[test]
+    fn golden_model_reports_mismatch() {
+        let result = golden_model(16, |x| vec![byte(value(x) + (value(x) == 3) as usize + 1).to_value()], None);
+        let Err(GoldenModelError::Mismatch { index, input, expected, actual }) = result else { panic!("{:?}", result) };
+        assert_eq!(index, 3);
+        assert_eq!(value(&input), 3);
+        assert_eq!(expected, byte(5).to_value());
+        assert_eq!(actual, byte(4).to_value());
+    }
+
+    #[test]
+    fn golden_model_reports_missing_transaction() {
+        // The reference expects the last byte twice.
+        let result = golden_model(8, |x| vec![byte(value(x) + 1).to_value(); 1 + (value(x) == 7) as usize], None);
+        let Err(GoldenModelError::Missing { index, input, expected }) = result else { panic!("{:?}", result) };
+        assert_eq!(index, 8);
+        assert_eq!(value(&input), 7);
+        assert_eq!(expected, byte(8).to_value());
+    }
+
+    #[test]
+    fn golden_model_reports_unexpected_transaction() {
+        // The reference expects nothing, so the first byte out of the module is unexpected.
+        let result = golden_model(8, |_| vec![], None);
+        let Err(GoldenModelError::Unexpected { index, actual }) = result else { panic!("{:?}", result) };
+        assert_eq!(index, 0);
+        assert_eq!(actual, byte(1).to_value());
+    }
+}
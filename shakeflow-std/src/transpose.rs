@@ -17,6 +17,7 @@ pub trait TransposeExt {
 impl<V: Signal, const N: usize, const M: usize> TransposeExt for [UniChannel<Array<V, U<M>>>; N] {
     type Output = [UniChannel<Array<V, U<N>>>; M];
 
+    #[track_caller]
     fn transpose(self, k: &mut CompositeModuleContext) -> [UniChannel<Array<V, U<N>>>; M] {
         self.fsm::<(), [UniChannel<Array<V, U<N>>>; M], _>(k, Some("transpose"), ().into(), move |fwd_i, _, s| {
             let mut fwd_o = Expr::<Array<Array<V, U<N>>, U<M>>>::x();
@@ -41,6 +42,7 @@ pub trait ReverseExt {
 }
 
 impl<V: Signal, const N: usize, const P: Protocol> ReverseExt for [VrChannel<V, P>; N] {
+    #[track_caller]
     fn reverse(self, _k: &mut CompositeModuleContext) -> [VrChannel<V, P>; N] {
         self.into_iter().rev().collect::<ArrayVec<_, N>>().into_inner().unwrap()
     }
@@ -16,6 +16,7 @@ pub trait UnconcentrateExt {
 impl<V: Signal, N: Num> UnconcentrateExt for UniChannel<Array<V, N>> {
     type Output<M: Num> = UniChannel<Array<V, M>>;
 
+    #[track_caller]
     fn unconcentrate<M: Num>(self, k: &mut CompositeModuleContext, pattern: Vec<bool>) -> UniChannel<Array<V, M>> {
         // Assertion: Pattern has same length as output width and it is longer than input width.
         assert!(pattern.len() == M::WIDTH && M::WIDTH >= N::WIDTH);
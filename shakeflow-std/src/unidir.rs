@@ -22,6 +22,7 @@ pub trait SliceExt<V: Signal, const N: usize> {
 impl<V: Signal, const N: usize> SliceExt<V, N> for UniChannel<Array<V, U<N>>> {
     type Out = [UniChannel<V>; N];
 
+    #[track_caller]
     fn slice(self, k: &mut CompositeModuleContext) -> Self::Out {
         self.fsm(k, None, ().into(), |input, _, _| (input, ().into(), ().into()))
     }
@@ -39,6 +40,7 @@ pub trait ConcatExt<V: Signal, const N: usize> {
 impl<V: Signal, const N: usize> ConcatExt<V, N> for [UniChannel<V>; N] {
     type Out = UniChannel<Array<V, U<N>>>;
 
+    #[track_caller]
     fn concat(self, k: &mut CompositeModuleContext) -> Self::Out {
         self.fsm::<(), UniChannel<Array<V, U<N>>>, _>(k, None, ().into(), |i_fwd, _o_bwd, s| {
             (i_fwd, Array::default().into(), s)
@@ -48,16 +50,19 @@ impl<V: Signal, const N: usize> ConcatExt<V, N> for [UniChannel<V>; N] {
 
 impl<V: Signal> UniChannel<V> {
     /// Generates the value indefinitely.
+    #[track_caller]
     pub fn source(k: &mut CompositeModuleContext, value: Expr<'static, V>) -> Self {
         ().fsm(k, Some("source"), ().into(), move |_fwd, _bwd, state| (value, ().into(), state))
     }
 
     /// Zips a unidirectional channel with another unidirectional channel.
+    #[track_caller]
     pub fn zip<W: Signal>(self, k: &mut CompositeModuleContext, other: UniChannel<W>) -> UniChannel<(V, W)> {
         (self, other).fsm(k, Some("zip"), Expr::x(), |fwd, _, _| (fwd, ((), ()).into(), Expr::from(())))
     }
 
     /// Zip3
+    #[track_caller]
     pub fn zip3<V1: Signal, V2: Signal>(
         self, k: &mut CompositeModuleContext, other1: UniChannel<V1>, other2: UniChannel<V2>,
     ) -> UniChannel<(V, V1, V2)> {
@@ -65,6 +70,7 @@ impl<V: Signal> UniChannel<V> {
     }
 
     /// Zip4
+    #[track_caller]
     pub fn zip4<V1: Signal, V2: Signal, V3: Signal>(
         self, k: &mut CompositeModuleContext, other1: UniChannel<V1>, other2: UniChannel<V2>, other3: UniChannel<V3>,
     ) -> UniChannel<(V, V1, V2, V3)> {
@@ -73,6 +79,7 @@ impl<V: Signal> UniChannel<V> {
     }
 
     /// Zip5
+    #[track_caller]
     pub fn zip5<V1: Signal, V2: Signal, V3: Signal, V4: Signal>(
         self, k: &mut CompositeModuleContext, other1: UniChannel<V1>, other2: UniChannel<V2>, other3: UniChannel<V3>,
         other4: UniChannel<V4>,
@@ -82,6 +89,7 @@ impl<V: Signal> UniChannel<V> {
     }
 
     /// Zip6
+    #[track_caller]
     pub fn zip6<V1: Signal, V2: Signal, V3: Signal, V4: Signal, V5: Signal>(
         self, k: &mut CompositeModuleContext, other1: UniChannel<V1>, other2: UniChannel<V2>, other3: UniChannel<V3>,
         other4: UniChannel<V4>, other5: UniChannel<V5>,
@@ -97,6 +105,7 @@ impl<V: Signal> UniChannel<Valid<V>> {
     /// Once `cond` is asserted, it must remain so until `self` is transferred. This is necessary to
     /// satisfy `zip_uni`'s requirement.
     #[must_use]
+    #[track_caller]
     pub fn filter(self, k: &mut CompositeModuleContext, cond: UniChannel<bool>) -> Self {
         // TODO: maybe we should set ready = false if cond = false?
         self.zip(k, cond).map(k, move |input| {
@@ -106,6 +115,7 @@ impl<V: Signal> UniChannel<Valid<V>> {
     }
 
     /// Maps the inner value.
+    #[track_caller]
     pub fn map_inner<W: Signal, F: 'static + for<'id> Fn(Expr<'id, V>) -> Expr<'id, W>>(
         self, k: &mut CompositeModuleContext, f: F,
     ) -> UniChannel<Valid<W>> {
@@ -118,6 +128,7 @@ impl<V: Signal> UniChannel<Valid<V>> {
     }
 
     /// Zips the inner value with other value.
+    #[track_caller]
     pub fn zip_inner<W: Signal>(
         self, k: &mut CompositeModuleContext, other: UniChannel<W>,
     ) -> UniChannel<Valid<(V, W)>> {
@@ -134,6 +145,7 @@ impl<V: Signal> UniChannel<Valid<V>> {
 
 impl<V: Signal> UniChannel<Valid<V>> {
     /// Transforms into a valid-ready channel.
+    #[track_caller]
     pub fn into_vr(self, k: &mut CompositeModuleContext) -> VrChannel<V> {
         self.fsm::<(), VrChannel<V>, _>(k, Some("into_vr"), Expr::from(()), |fwd, _, s| (fwd, Expr::from(()), s))
     }
@@ -141,6 +153,7 @@ impl<V: Signal> UniChannel<Valid<V>> {
 
 impl<V: Signal> UniChannel<V> {
     /// Transforms into a deque channel.
+    #[track_caller]
     pub fn into_deq(self, k: &mut CompositeModuleContext) -> DeqChannel<V> {
         self.fsm::<(), DeqChannel<V>, _>(k, Some("into_deq"), ().into(), |fwd, _, s| (fwd, ().into(), s))
     }
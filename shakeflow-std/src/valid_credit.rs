@@ -36,18 +36,17 @@ impl<V: Signal, const P: Protocol> Interface for VcChannel<V, P> {
     type Fwd = Valid<V>;
 
     fn interface_typ() -> lir::InterfaceTyp {
-        lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+        lir::InterfaceTyp::Channel(channel_typ::<Self::Fwd, Self::Bwd>("VcChannel"))
     }
 
     fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
-        assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-        let channel = some_or!(interface.get_channel(), panic!());
+        let channel = channel_of::<Self>(interface)?;
         Ok(Self { endpoint: channel.endpoint(), _marker: PhantomData })
     }
 
     fn try_into_inner(self) -> Result<lir::Interface, InterfaceError> {
         Ok(lir::Interface::Channel(lir::Channel {
-            typ: lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+            typ: channel_typ::<Self::Fwd, Self::Bwd>("VcChannel"),
             endpoint: self.endpoint,
         }))
     }
@@ -57,6 +56,7 @@ impl<I: Signal, const P: Protocol> FsmExt<I> for VcChannel<I, P> {
     type Out<O: Signal> = VcChannel<O, P>;
 
     // TODO: Define more precise semantic of Valid-credit Channel.
+    #[track_caller]
     fn fsm_map<
         S: Signal,
         O: Signal,
@@ -78,6 +78,7 @@ impl<I: Signal, const P: Protocol> FsmExt<I> for VcChannel<I, P> {
     }
 }
 
+#[track_caller]
 fn m_into_uni<V: Signal, const P: Protocol>(consuming: bool) -> Module<VcChannel<V, P>, UniChannel<Valid<V>>> {
     composite::<VcChannel<V, P>, UniChannel<Valid<V>>, _>("into_uni", Some("in"), Some("out"), |value, k| {
         value.fsm::<(), _, _>(k, None, ().into(), move |i_fwd, _o_bwd, state| {
@@ -87,6 +88,7 @@ fn m_into_uni<V: Signal, const P: Protocol>(consuming: bool) -> Module<VcChannel
     .build()
 }
 
+#[track_caller]
 fn m_into_vr<V: Signal, const P: Protocol>() -> Module<VcChannel<V, P>, VrChannel<V, P>> {
     composite::<VcChannel<V, P>, VrChannel<V, P>, _>("into_vr", Some("in"), Some("out"), |value, k| {
         value.fsm::<(), _, _>(k, None, ().into(), move |i_fwd, o_bwd, state| {
@@ -103,10 +105,12 @@ fn m_into_vr<V: Signal, const P: Protocol>() -> Module<VcChannel<V, P>, VrChanne
 
 impl<I: Signal, const P: Protocol> VcChannel<I, P> {
     /// Transforms into unidirectional channel.
+    #[track_caller]
     pub fn into_uni(self, k: &mut CompositeModuleContext) -> UniChannel<Valid<I>> {
         self.comb_inline(k, m_into_uni(true))
     }
 
     /// Transforms into valid/ready chanel.
+    #[track_caller]
     pub fn into_vr(self, k: &mut CompositeModuleContext) -> VrChannel<I, P> { self.comb_inline(k, m_into_vr()) }
 }
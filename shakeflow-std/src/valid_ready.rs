@@ -44,21 +44,39 @@ impl<V: Signal, const P: Protocol> Interface for VrChannel<V, P> {
     type Fwd = Valid<V>;
 
     fn interface_typ() -> lir::InterfaceTyp {
-        lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+        lir::InterfaceTyp::Channel(channel_typ::<Self::Fwd, Self::Bwd>("VrChannel"))
     }
 
     fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
-        assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-        let channel = some_or!(interface.get_channel(), panic!());
+        let channel = channel_of::<Self>(interface)?;
         Ok(Self { endpoint: channel.endpoint(), _marker: PhantomData })
     }
 
     fn try_into_inner(self) -> Result<lir::Interface, InterfaceError> {
         Ok(lir::Interface::Channel(lir::Channel {
-            typ: lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+            typ: channel_typ::<Self::Fwd, Self::Bwd>("VrChannel"),
             endpoint: self.endpoint,
         }))
     }
+
+    /// Inserts a checker of the valid/ready handshake.
+    ///
+    /// Once valid is asserted, it should stay asserted with the same payload until ready is seen. For
+    /// `Protocol::Demanding` channels, ready should not depend combinationally on valid, which is
+    /// checked only in the simulator.
+    fn check_protocol(self, k: &mut CompositeModuleContext) -> Self {
+        self.fsm::<(bool, V), Self, _>(k, Some("vr_checker"), (false.into(), Expr::x()).into(), |fwd, bwd, s| {
+            let (pending, payload) = *s;
+
+            (!pending | fwd.valid).assert("valid is deasserted before ready");
+            (!pending | fwd.inner.is_bits_eq(payload)).assert("payload is changed before ready");
+            if P == Protocol::Demanding {
+                assert_bwd_independent(V::WIDTH, "ready depends combinationally on valid");
+            }
+
+            (fwd, bwd, (fwd.valid & !bwd.ready, fwd.inner).into())
+        })
+    }
 }
 
 /// Valid/ready channel's forward exprs.
@@ -146,6 +164,7 @@ impl<'id> ReadyExt<'id> for Expr<'id, Ready> {
     }
 }
 
+#[track_caller]
 fn m_split_map<
     I: Signal,
     O1: Signal,
@@ -168,6 +187,7 @@ fn m_split_map<
     .build()
 }
 
+#[track_caller]
 fn m_duplicate<I: Signal, const P: Protocol, const P1: Protocol, const P2: Protocol>(
 ) -> Module<VrChannel<I, P>, (VrChannel<I, P1>, VrChannel<I, P2>)> {
     // TODO: Check this in compile time
@@ -193,6 +213,7 @@ fn m_duplicate<I: Signal, const P: Protocol, const P1: Protocol, const P2: Proto
     .build()
 }
 
+#[track_caller]
 fn m_duplicate_any<I: Signal, const N: usize, const P: Protocol>() -> Module<VrChannel<I, P>, [VrChannel<I, P>; N]> {
     composite::<VrChannel<I, P>, [VrChannel<I, P>; N], _>("duplicate_any", Some("in"), Some("out"), |input, k| {
         input.fsm::<(), [VrChannel<I, P>; N], _>(k, None, ().into(), |ingress_fwd, egress_bwd, state| {
@@ -204,6 +225,7 @@ fn m_duplicate_any<I: Signal, const N: usize, const P: Protocol>() -> Module<VrC
     .build()
 }
 
+#[track_caller]
 fn m_duplicate_n<I: Signal, const N: usize, const P: Protocol>(
 ) -> Module<VrChannel<I, P>, [VrChannel<I, { Protocol::Demanding }>; N]> {
     composite::<VrChannel<I, P>, [VrChannel<I, { Protocol::Demanding }>; N], _>(
@@ -225,6 +247,7 @@ fn m_duplicate_n<I: Signal, const N: usize, const P: Protocol>(
     .build()
 }
 
+#[track_caller]
 fn m_into_uni<V: Signal, const P: Protocol>(consuming: bool) -> Module<VrChannel<V, P>, UniChannel<Valid<V>>> {
     composite::<VrChannel<V, P>, UniChannel<Valid<V>>, _>("into_uni", Some("s_axis"), Some("m_axi"), |value, k| {
         value.fsm::<(), _, _>(k, None, ().into(), move |i_fwd, _o_bwd, state| {
@@ -235,6 +258,7 @@ fn m_into_uni<V: Signal, const P: Protocol>(consuming: bool) -> Module<VrChannel
 }
 
 #[allow(clippy::type_complexity)]
+#[track_caller]
 fn m_clone_uni<V: Signal, const P: Protocol>() -> Module<VrChannel<V, P>, (VrChannel<V, P>, UniChannel<Valid<V>>)> {
     composite::<VrChannel<V, P>, (VrChannel<V, P>, UniChannel<Valid<V>>), _>(
         "clone_uni",
@@ -267,6 +291,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// definition. But if we allow both egress channels to be Helpful, combinational loop will
     /// appear if both consumers of the egress has Demanding ingress channel. To prevent such case,
     /// we assert that there can be at most 1 Helpful channel as egress.
+    #[track_caller]
     pub fn duplicate<const P1: Protocol, const P2: Protocol>(
         self, k: &mut CompositeModuleContext,
     ) -> (VrChannel<I, P1>, VrChannel<I, P2>) {
@@ -280,6 +305,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// Duplicates the inner signal.
     ///
     /// TODO: Documentation
+    #[track_caller]
     pub fn duplicate_any<const N: usize>(self, k: &mut CompositeModuleContext) -> [VrChannel<I, P>; N] {
         self.comb_inline(k, m_duplicate_any())
     }
@@ -295,6 +321,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// - If we change the implementation of `duplicate_n` module to calculate valid signal of each
     ///   egress channel without looking at the valid signal of itself, we can allow at most one
     ///   Helpful channel. But to do this, the return type would become complicated.
+    #[track_caller]
     pub fn duplicate_n<const N: usize>(
         self, k: &mut CompositeModuleContext,
     ) -> [VrChannel<I, { Protocol::Demanding }>; N] {
@@ -304,6 +331,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// Returns fire signal and itself. (fire signal: valid & ready)
     ///
     /// TODO: Consider helpful/demanding for Unichannel.
+    #[track_caller]
     pub fn fire(self, k: &mut CompositeModuleContext) -> (Self, UniChannel<bool>) {
         let (fire, this) = self.fsm::<_, _, _>(k, Some("fire"), ().into(), move |fwd, bwd: Expr<((), Ready)>, s| {
             let bwd = bwd.1;
@@ -319,6 +347,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     }
 
     /// TODO: Documentation
+    #[track_caller]
     pub fn transfer(self, k: &mut CompositeModuleContext, ready_next: UniChannel<bool>) -> UniChannel<Valid<I>> {
         self.zip_uni(k, ready_next).fsm::<_, UniChannel<Valid<I>>, _>(
             k,
@@ -379,6 +408,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// - P3 again, and onwards: `done` is false, and the fsm keeps receiving packets
     ///                          until `f()` computes `done` to be TRUE again.
     ///
+    #[track_caller]
     pub fn fsm_ingress<
         S: Signal,
         F: 'static + for<'id> Fn(Expr<'id, I>, Expr<'id, S>) -> (Expr<'id, S>, Expr<'id, bool>),
@@ -405,6 +435,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     }
 
     /// TODO: Remove this
+    #[track_caller]
     pub fn fsm_fwd<
         S: Signal,
         O: Signal,
@@ -425,6 +456,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     ///
     /// Once the resulting channel's output becomes valid, it must remain the same until it's
     /// transferred.
+    #[track_caller]
     pub fn map_fwd<O: Signal, F: 'static + for<'id> Fn(Expr<'id, Valid<I>>) -> Expr<'id, Valid<O>>>(
         self, k: &mut CompositeModuleContext, module_name: Option<&str>, f: F,
     ) -> VrChannel<O, P> {
@@ -432,6 +464,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     }
 
     /// TODO: documentation
+    #[track_caller]
     pub fn fsm_and_then<
         S: Signal,
         O: Signal,
@@ -457,6 +490,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     }
 
     /// TODO: Documentation
+    #[track_caller]
     pub fn and_then<O: Signal, F: 'static + for<'id> Fn(Expr<'id, I>) -> Expr<'id, Valid<O>>>(
         self, k: &mut CompositeModuleContext, module_name: Option<&str>, f: F,
     ) -> VrChannel<O, { Protocol::Demanding }> {
@@ -468,6 +502,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// Once `cond` is asserted, it must remain so until `self` is transferred. This is necessary to
     /// satisfy `zip_uni`'s requirement.
     #[must_use]
+    #[track_caller]
     pub fn filter_bwd(self, k: &mut CompositeModuleContext, cond: UniChannel<bool>) -> VrChannel<I, P> {
         self.zip_uni(k, cond).fsm::<(), Self, _>(k, Some("filter_bwd"), Expr::from(()), move |i_fwd, o_bwd, s| {
             let i_valid = i_fwd.valid;
@@ -480,6 +515,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
 
     /// TODO: Documentation
     #[must_use]
+    #[track_caller]
     pub fn filter_fwd_ready(self, k: &mut CompositeModuleContext) -> VrChannel<I, { Protocol::Demanding }> {
         self.fsm::<(), VrChannel<I, { Protocol::Demanding }>, _>(
             k,
@@ -495,6 +531,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
 
     /// TODO: Documentation
     #[must_use]
+    #[track_caller]
     pub fn filter_fwd_ready_neg(self, k: &mut CompositeModuleContext) -> VrChannel<I, { Protocol::Demanding }> {
         self.fsm::<(), VrChannel<I, { Protocol::Demanding }>, _>(
             k,
@@ -512,6 +549,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     ///
     /// Once the given channel's output becomes valid, the `other` channel must remain the same until it's
     /// transferred. It's required by the valid-ready protocol.
+    #[track_caller]
     pub fn zip_uni<J: Signal>(self, k: &mut CompositeModuleContext, other: UniChannel<J>) -> VrChannel<(I, J), P> {
         (self, other).fsm::<(), VrChannel<(I, J), P>, _>(k, Some("zip_uni"), ().into(), |fwd, bwd, state| {
             let (fwd_i, fwd_j) = *fwd;
@@ -522,6 +560,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     }
 
     /// Transforms into a unidirectional channel.
+    #[track_caller]
     pub fn into_uni(self, k: &mut CompositeModuleContext, consuming: bool) -> UniChannel<Valid<I>> {
         self.comb_inline(k, m_into_uni(consuming))
     }
@@ -529,6 +568,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     /// Transforms into data-deque channel.
     ///
     /// TODO: Consider helpful/demanding for deque channel.
+    #[track_caller]
     pub fn into_deq(self, k: &mut CompositeModuleContext) -> DeqChannel<I> {
         self.fsm::<(), DeqChannel<I>, _>(k, Some("into_deq"), ().into(), |fwd_i, bwd_o, s| {
             let fwd_o = fwd_i.inner;
@@ -539,18 +579,22 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
     }
 
     /// Clones into a unidirectional channel with no `Bwd` expr.
+    #[track_caller]
     pub fn clone_uni(self, k: &mut CompositeModuleContext) -> (Self, UniChannel<Valid<I>>) {
         let (this, this_cloned) = self.comb_inline(k, m_clone_uni());
         (this, this_cloned)
     }
 
     /// Sinks the valid-ready channel by consuming.
+    #[track_caller]
     pub fn sink(self, k: &mut CompositeModuleContext) { let _ = self.into_uni(k, true); }
 
     /// Blocks the valid-ready channel without consuming.
+    #[track_caller]
     pub fn block(self, k: &mut CompositeModuleContext) { let _ = self.comb_inline(k, m_into_uni(false)); }
 
     /// Receives until `cond` is low. If `cond` becomes high, then turn on valid signal.
+    #[track_caller]
     pub fn receive_until(self, k: &mut CompositeModuleContext, cond: UniChannel<bool>) -> VrChannel<I> {
         (self, cond).fsm::<(), VrChannel<I>, _>(k, None, ().into(), |fwd_i, bwd_o, s| {
             let (fwd_i, cond) = *fwd_i;
@@ -565,6 +609,7 @@ impl<I: Signal, const P: Protocol> VrChannel<I, P> {
 
 impl<I: Signal> VrChannel<I> {
     /// Generates unit values indefinitely.
+    #[track_caller]
     pub fn source(k: &mut CompositeModuleContext, value: Expr<'static, I>) -> Self {
         ().fsm(k, Some("source"), ().into(), move |_fwd, _bwd, state| {
             (ValidProj { inner: value, valid: true.into() }.into(), ().into(), state)
@@ -572,6 +617,7 @@ impl<I: Signal> VrChannel<I> {
     }
 
     /// TODO: documentation
+    #[track_caller]
     pub fn split_map<O1: Signal, O2: Signal, F: 'static + for<'id> Fn(Expr<'id, I>) -> Expr<'id, (bool, O1, O2)>>(
         self, k: &mut CompositeModuleContext, f: F,
     ) -> (VrChannel<O1>, VrChannel<O2>) {
@@ -579,6 +625,7 @@ impl<I: Signal> VrChannel<I> {
     }
 
     /// TODO: documentation
+    #[track_caller]
     pub fn filter_map<O: Signal, F: 'static + for<'id> Fn(Expr<'id, I>) -> Expr<'id, (bool, O)>>(
         self, k: &mut CompositeModuleContext, f: F,
     ) -> VrChannel<O> {
@@ -590,20 +637,28 @@ impl<I: Signal> VrChannel<I> {
         take
     }
 
-    /// TODO: documentation
+    /// Maps the payload with `f`, asserting that it is valid whenever the channel is valid.
+    ///
+    /// `f` returns whether the payload is valid and the mapped payload. Invalid payloads are never
+    /// transferred, and the assertion is checked in the simulator and the generated Verilog.
+    #[track_caller]
     pub fn assert_map<O: Signal, F: 'static + for<'id> Fn(Expr<'id, I>) -> Expr<'id, (bool, O)>>(
         self, k: &mut CompositeModuleContext, f: F,
     ) -> VrChannel<O> {
-        let (take, drop) = self.split_map(k, move |i| {
-            let (is_valid, output) = *f(i);
-            (is_valid, output, ().into()).into()
-        });
-        drop.block(k);
-        take
+        self.fsm::<(), VrChannel<O>, _>(k, Some("assert_map"), ().into(), move |fwd, bwd, s| {
+            let (is_valid, output) = *f(fwd.inner);
+            (!fwd.valid | is_valid).assert("assert_map: the payload is invalid");
+
+            let fwd = Expr::<Valid<_>>::new(fwd.valid & is_valid, output);
+            let bwd = Expr::<Ready>::new(is_valid & bwd.ready);
+
+            (fwd, bwd, s)
+        })
     }
 
     /// TODO: documentation
     #[must_use]
+    #[track_caller]
     pub fn filter<F: 'static + for<'id> Fn(Expr<'id, I>) -> Expr<'id, bool>>(
         self, k: &mut CompositeModuleContext, f: F,
     ) -> VrChannel<I> {
@@ -612,6 +667,7 @@ impl<I: Signal> VrChannel<I> {
 
     /// TODO: Documentation
     #[must_use]
+    #[track_caller]
     pub fn filter_bwd_valid(self, k: &mut CompositeModuleContext) -> VrChannel<I> {
         self.fsm::<(), Self, _>(k, Some("filter_bwd_valid"), Expr::from(()), |ingress_fwd, egress_bwd, s| {
             let ingress_bwd = ReadyProj { ready: (ingress_fwd.valid & egress_bwd.ready) }.into();
@@ -620,6 +676,7 @@ impl<I: Signal> VrChannel<I> {
     }
 
     /// Zips a valid-ready channel with another valid-ready channel.
+    #[track_caller]
     pub fn zip_vr<J: Signal, const P: Protocol>(
         self, k: &mut CompositeModuleContext, other: VrChannel<J, P>,
     ) -> VrChannel<(I, J), P> {
@@ -635,6 +692,7 @@ impl<I: Signal> VrChannel<I> {
     }
 
     /// Runs an FSM from the egress valid/ready channel.
+    #[track_caller]
     pub fn fsm_egress<
         S: Signal,
         O: Signal,
@@ -0,0 +1,231 @@
+//! BTOR2 nodes.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::lir::ModuleError;
+
+/// Bit of a bit vector.
+#[allow(variant_size_differences)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Bit {
+    /// Constant bit.
+    Const(bool),
+
+    /// The given bit of the node, counting from the LSB.
+    Node(usize, usize),
+}
+
+impl Default for Bit {
+    fn default() -> Self { Bit::Const(false) }
+}
+
+impl Bit {
+    /// Returns the value if the bit is constant.
+    pub(super) fn as_const(self) -> Option<bool> {
+        match self {
+            Bit::Const(b) => Some(b),
+            Bit::Node(..) => None,
+        }
+    }
+}
+
+/// Returns the values of the bits if they are all constant.
+pub(super) fn as_consts(bits: &[Bit]) -> Option<Vec<bool>> { bits.iter().map(|bit| bit.as_const()).collect() }
+
+/// Returns the constant bits.
+pub(super) fn consts(bits: &[bool]) -> Vec<Bit> { bits.iter().map(|b| Bit::Const(*b)).collect() }
+
+/// BTOR2 transition system under construction.
+///
+/// Nodes are numbered from 1 in the order they are added. Bit vectors of width 0 are not
+/// representable in BTOR2, so they never become nodes.
+#[derive(Debug)]
+pub(super) struct Btor {
+    /// Name of the module, for errors.
+    module: String,
+
+    /// Lines.
+    lines: Vec<String>,
+
+    /// Widths of nodes, or `None` if a node is not a bit vector.
+    widths: Vec<Option<usize>>,
+
+    /// Sorts by width.
+    sorts: HashMap<usize, usize>,
+
+    /// Constant nodes by value.
+    consts: HashMap<Vec<bool>, usize>,
+}
+
+impl Btor {
+    pub(super) fn new(module: String) -> Self {
+        Self { module, lines: Vec::new(), widths: Vec::new(), sorts: HashMap::new(), consts: HashMap::new() }
+    }
+
+    fn add(&mut self, line: String, width: Option<usize>) -> usize {
+        self.widths.push(width);
+        let node = self.widths.len();
+        self.lines.push(format!("{} {}", node, line));
+        node
+    }
+
+    /// Returns the error that a bit vector of width 0 is given.
+    fn zero_width(&self) -> ModuleError {
+        ModuleError::Unsupported { module: self.module.clone(), what: "bit vector of width 0".to_string() }
+    }
+
+    /// Returns the bitvector sort of the given width.
+    fn sort(&mut self, width: usize) -> Result<usize, ModuleError> {
+        if width == 0 {
+            return Err(self.zero_width());
+        }
+        if let Some(sort) = self.sorts.get(&width) {
+            return Ok(*sort);
+        }
+        let sort = self.add(format!("sort bitvec {}", width), None);
+        self.sorts.insert(width, sort);
+        Ok(sort)
+    }
+
+    /// Adds a comment.
+    pub(super) fn comment(&mut self, comment: &str) {
+        for line in comment.lines() {
+            self.lines.push(format!("; {}", line));
+        }
+    }
+
+    /// Adds a node of the given width with an operator and arguments, and returns its bits.
+    pub(super) fn op(&mut self, op: &str, width: usize, args: &[usize]) -> Result<Vec<Bit>, ModuleError> {
+        let sort = self.sort(width)?;
+        let line = [op.to_string(), sort.to_string()]
+            .into_iter()
+            .chain(args.iter().map(|arg| arg.to_string()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let node = self.add(line, Some(width));
+        Ok((0..width).map(|i| Bit::Node(node, i)).collect())
+    }
+
+    /// Adds an input and returns its bits.
+    pub(super) fn input(&mut self, width: usize, name: &str) -> Result<Vec<Bit>, ModuleError> {
+        if width == 0 {
+            return Ok(Vec::new());
+        }
+        let sort = self.sort(width)?;
+        let node = self.add(format!("input {} {}", sort, symbol(name)), Some(width));
+        Ok((0..width).map(|i| Bit::Node(node, i)).collect())
+    }
+
+    /// Adds a state with the initial value, and returns its node and bits.
+    pub(super) fn state(&mut self, init: &[bool], name: &str) -> Result<Option<(usize, Vec<Bit>)>, ModuleError> {
+        if init.is_empty() {
+            return Ok(None);
+        }
+        let sort = self.sort(init.len())?;
+        let node = self.add(format!("state {} {}", sort, symbol(name)), Some(init.len()));
+        let init_node = self.node(&consts(init))?;
+        self.add(format!("init {} {} {}", sort, node, init_node), None);
+        Ok(Some((node, (0..init.len()).map(|i| Bit::Node(node, i)).collect())))
+    }
+
+    /// Sets the next value of the state.
+    pub(super) fn next(&mut self, state: usize, bits: &[Bit]) -> Result<(), ModuleError> {
+        let sort = self.sort(bits.len())?;
+        let next = self.node(bits)?;
+        self.add(format!("next {} {} {}", sort, state, next), None);
+        Ok(())
+    }
+
+    /// Adds an output.
+    pub(super) fn output(&mut self, bits: &[Bit], name: &str) -> Result<(), ModuleError> {
+        if bits.is_empty() {
+            return Ok(());
+        }
+        let node = self.node(bits)?;
+        self.add(format!("output {} {}", node, symbol(name)), None);
+        Ok(())
+    }
+
+    /// Adds a bad state property, which holds if the bit is set.
+    pub(super) fn bad(&mut self, bit: Bit, name: &str) -> Result<(), ModuleError> {
+        let node = self.node(&[bit])?;
+        self.add(format!("bad {} {}", node, symbol(name)), None);
+        Ok(())
+    }
+
+    /// Adds a constraint, which is assumed to hold if the bit is set.
+    pub(super) fn constraint(&mut self, bit: Bit, name: &str) -> Result<(), ModuleError> {
+        let node = self.node(&[bit])?;
+        self.add(format!("constraint {} {}", node, symbol(name)), None);
+        Ok(())
+    }
+
+    /// Returns a node with the given bits, adding slices and concatenations if needed.
+    ///
+    /// Fails if `bits` is empty, since bit vectors of width 0 are not representable.
+    pub(super) fn node(&mut self, bits: &[Bit]) -> Result<usize, ModuleError> {
+        if bits.is_empty() {
+            return Err(self.zero_width());
+        }
+
+        if let Some(value) = as_consts(bits) {
+            if let Some(node) = self.consts.get(&value) {
+                return Ok(*node);
+            }
+            let sort = self.sort(value.len())?;
+            let digits = value.iter().rev().map(|b| if *b { '1' } else { '0' }).collect::<String>();
+            let node = self.add(format!("const {} {}", sort, digits), Some(value.len()));
+            self.consts.insert(value, node);
+            return Ok(node);
+        }
+
+        // Splits the bits into runs of constants and of consecutive bits of a node, LSB first.
+        let mut runs: Vec<(Bit, usize)> = Vec::new();
+        for bit in bits {
+            match (runs.last_mut(), bit) {
+                (Some((Bit::Const(_), len)), Bit::Const(_)) => *len += 1,
+                (Some((Bit::Node(node, lsb), len)), Bit::Node(bit_node, index))
+                    if node == bit_node && *lsb + *len == *index =>
+                {
+                    *len += 1
+                }
+                _ => runs.push((*bit, 1)),
+            }
+        }
+
+        let mut offset = 0;
+        let mut nodes = Vec::new();
+        for (bit, len) in runs {
+            let node = match bit {
+                Bit::Const(_) => self.node(&bits[offset..(offset + len)])?,
+                Bit::Node(node, lsb) if lsb == 0 && self.widths[node - 1] == Some(len) => node,
+                Bit::Node(node, lsb) => {
+                    let sort = self.sort(len)?;
+                    self.add(format!("slice {} {} {} {}", sort, node, lsb + len - 1, lsb), Some(len))
+                }
+            };
+            offset += len;
+            nodes.push((node, offset));
+        }
+
+        let (mut acc, _) = nodes[0];
+        for (node, width) in nodes.into_iter().skip(1) {
+            let sort = self.sort(width)?;
+            acc = self.add(format!("concat {} {} {}", sort, node, acc), Some(width));
+        }
+        Ok(acc)
+    }
+}
+
+impl fmt::Display for Btor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts the name into a BTOR2 symbol, which cannot contain whitespaces.
+fn symbol(name: &str) -> String { name.split_whitespace().collect::<Vec<_>>().join("_") }
@@ -0,0 +1,362 @@
+//! Translation of exprs into BTOR2 nodes.
+//!
+//! Exprs are translated into bit vectors in the same layout as the simulator, and operations follow
+//! the semantics of the simulator: don't-care values are zeros, and out-of-range indices read zeros
+//! and write nothing.
+
+use super::btor::*;
+use super::Btorgen;
+use crate::lir::bits::*;
+use crate::lir::*;
+use crate::utils::{clog2, usize_to_bitvec};
+
+/// Environment of an expr, binding its inputs.
+#[derive(Debug)]
+pub(super) enum Env {
+    /// Exprs of the FSM with the given index, with inputs "in", "out" and "st".
+    Fsm(usize),
+
+    /// Function exprs such as those of `Expr::Map`, with the given key and input values.
+    Local(usize, Vec<(Option<&'static str>, Vec<Bit>)>),
+}
+
+impl Env {
+    /// Returns the key of the environment, unique among environments.
+    fn key(&self) -> usize {
+        match self {
+            Env::Fsm(index) => *index,
+            Env::Local(key, _) => *key,
+        }
+    }
+}
+
+impl Btorgen {
+    /// Translates the expr under the environment.
+    pub(super) fn expr(&mut self, env: &Env, id: ExprId) -> Result<Vec<Bit>, ModuleError> {
+        if let Some(value) = self.cache.get(&(env.key(), id)) {
+            return Ok(value.clone());
+        }
+
+        let value = self.expr_inner(env, &id.into_expr())?;
+        self.cache.insert((env.key(), id), value.clone());
+        Ok(value)
+    }
+
+    /// Creates a new local environment.
+    fn local(&mut self, inputs: Vec<(Option<&'static str>, Vec<Bit>)>) -> Env {
+        self.envs += 1;
+        Env::Local(self.envs, inputs)
+    }
+
+    fn expr_inner(&mut self, env: &Env, expr: &Expr) -> Result<Vec<Bit>, ModuleError> {
+        let value = match expr {
+            Expr::X { typ } => vec![Bit::default(); typ.width()],
+            Expr::Constant { bits, .. } => consts(bits),
+            Expr::Repeat { inner, count } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.expr(env, *inner)?;
+                split_leaves(&value, &leaf_widths(&typ)).into_iter().flat_map(|leaf| leaf.repeat(*count)).collect()
+            }
+            Expr::Input { name, typ } => {
+                let value = match env {
+                    Env::Fsm(index) => self.fsm_input(*index, name.as_deref())?,
+                    Env::Local(_, inputs) => inputs
+                        .iter()
+                        .find(|(input, _)| *input == name.as_deref())
+                        .map(|(_, value)| value.clone())
+                        .ok_or_else(|| ModuleError::Misc(format!("input {:?} is not bound", name)))?,
+                };
+                if value.len() != typ.width() {
+                    return Err(ModuleError::TypMismatch(format!(
+                        "input {:?} has width {}, but {} is given",
+                        name,
+                        typ.width(),
+                        value.len()
+                    )));
+                }
+                value
+            }
+            Expr::Member { inner, index } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.expr(env, *inner)?;
+                match typ {
+                    PortDecls::Struct(members) => {
+                        let offset = members.iter().take(*index).map(|(_, member)| member.width()).sum::<usize>();
+                        value[offset..(offset + members[*index].1.width())].to_vec()
+                    }
+                    PortDecls::Bits(_) => panic!("Cannot index a `PortDecls::Bits`."),
+                }
+            }
+            Expr::Struct { inner } => {
+                let mut value = Vec::new();
+                for (_, member) in inner {
+                    value.extend(self.expr(env, *member)?);
+                }
+                value
+            }
+            Expr::Resize { inner, typ_elt, count } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.expr(env, *inner)?;
+                let widths = leaf_widths(&typ_elt.multiple(*count));
+                split_leaves(&value, &leaf_widths(&typ))
+                    .into_iter()
+                    .zip(widths)
+                    .flat_map(|(leaf, width)| resize(leaf, width))
+                    .collect()
+            }
+            Expr::LeftShift { inner, rhs } => self.binary_op(env, BinaryOp::ShiftLeft, *inner, *rhs)?,
+            Expr::RightShift { inner, rhs } => self.binary_op(env, BinaryOp::ShiftRight, *inner, *rhs)?,
+            Expr::Not { inner } => {
+                let value = self.expr(env, *inner)?;
+                self.not(&value)?
+            }
+            Expr::BinaryOp { op, lhs, rhs } => self.binary_op(env, *op, *lhs, *rhs)?,
+            Expr::Fold { inner, typ_elt, func, init, .. } => {
+                let value = self.expr(env, *inner)?;
+                let mut acc = self.expr(env, *init)?;
+                for index in 0..array_len(&value, typ_elt) {
+                    let inner_slice = get_elts(&value, typ_elt, index, 1);
+                    let local = self.local(vec![(Some("acc"), acc), (Some("inner_slice"), inner_slice)]);
+                    acc = self.expr(&local, *func)?;
+                }
+                acc
+            }
+            Expr::TreeFold { inner, op, lhs, .. } => {
+                let typ_elt = lhs.into_expr().port_decls();
+                let value = self.expr(env, *inner)?;
+                let mut elts = (0..array_len(&value, &typ_elt))
+                    .map(|index| get_elts(&value, &typ_elt, index, 1))
+                    .collect::<Vec<_>>();
+                while elts.len() > 1 {
+                    let mut next = Vec::new();
+                    for pair in elts.chunks(2) {
+                        match pair {
+                            [lhs, rhs] => {
+                                let local = self.local(vec![(Some("lhs"), lhs.clone()), (Some("rhs"), rhs.clone())]);
+                                next.push(self.expr(&local, *op)?);
+                            }
+                            [elt] => next.push(elt.clone()),
+                            _ => unreachable!(),
+                        }
+                    }
+                    elts = next;
+                }
+                elts.pop().unwrap_or_else(|| vec![Bit::default(); typ_elt.width()])
+            }
+            Expr::Map { inner, typ_elt, func } => {
+                let typ_func = func.into_expr().port_decls();
+                let value = self.expr(env, *inner)?;
+                let mut elts = Vec::new();
+                for index in 0..array_len(&value, typ_elt) {
+                    let local = self.local(vec![(None, get_elts(&value, typ_elt, index, 1))]);
+                    elts.push(self.expr(&local, *func)?);
+                }
+                join_elts(&elts, &typ_func)
+            }
+            Expr::Get { inner, typ_elt, index } | Expr::GetVarArray { inner, typ_elt, index } => {
+                let value = self.expr(env, *inner)?;
+                let index = self.expr(env, *index)?;
+                let default = vec![Bit::default(); typ_elt.width()];
+                self.select(&index, array_len(&value, typ_elt), default, |index| get_elts(&value, typ_elt, index, 1))?
+            }
+            Expr::Clip { inner, typ_elt, from, size } => {
+                let value = self.expr(env, *inner)?;
+                let from = self.expr(env, *from)?;
+                let default = vec![Bit::default(); typ_elt.width() * size];
+                self.select(&from, array_len(&value, typ_elt), default, |from| get_elts(&value, typ_elt, from, *size))?
+            }
+            Expr::Append { lhs, rhs, .. } => {
+                let (typ_lhs, typ_rhs) = (lhs.into_expr().port_decls(), rhs.into_expr().port_decls());
+                let (lhs, rhs) = (self.expr(env, *lhs)?, self.expr(env, *rhs)?);
+                append_leaves(&lhs, &typ_lhs, &rhs, &typ_rhs)
+            }
+            Expr::Zip { inner, .. } => {
+                let mut value = Vec::new();
+                for member in inner {
+                    value.extend(self.expr(env, *member)?);
+                }
+                value
+            }
+            Expr::Concat { inner, .. } | Expr::Chunk { inner, .. } | Expr::Repr { inner } => self.expr(env, *inner)?,
+            Expr::Sum { inner, width_elt } => {
+                let value = self.expr(env, *inner)?;
+                let typ_elt = PortDecls::Bits(Shape::new([*width_elt]));
+                let mut acc = vec![Bit::default(); *width_elt];
+                for index in 0..array_len(&value, &typ_elt) {
+                    let elt = get_elts(&value, &typ_elt, index, 1);
+                    acc = self.op("add", *width_elt, &acc, &elt)?;
+                }
+                acc
+            }
+            Expr::Cond { cond, lhs, rhs } => {
+                let cond = self.expr(env, *cond)?[0];
+                let (lhs, rhs) = (self.expr(env, *lhs)?, self.expr(env, *rhs)?);
+                self.ite(cond, &lhs, &rhs)?
+            }
+            Expr::Set { inner, index, elt } | Expr::SetVarArray { inner, index, elt } => {
+                let typ_elt = elt.into_expr().port_decls();
+                let value = self.expr(env, *inner)?;
+                let index = self.expr(env, *index)?;
+                let elt = self.expr(env, *elt)?;
+                let len = array_len(&value, &typ_elt);
+                self.select(&index, len, value.clone(), |index| set_elts(&value, &typ_elt, index, &elt))?
+            }
+            Expr::SetRange { inner, typ_elt, index, elts } => {
+                let value = self.expr(env, *inner)?;
+                let index = self.expr(env, *index)?;
+                let elts = self.expr(env, *elts)?;
+                let len = array_len(&value, typ_elt);
+                self.select(&index, len, value.clone(), |index| set_elts(&value, typ_elt, index, &elts))?
+            }
+            Expr::Case { case_expr, case_items, default } => {
+                let value = self.expr(env, *case_expr)?;
+                let mut output = match default {
+                    Some(default) => self.expr(env, *default)?,
+                    None => vec![Bit::default(); expr.width()],
+                };
+                for (case, item) in case_items.iter().rev() {
+                    let case = self.expr(env, *case)?;
+                    let item = self.expr(env, *item)?;
+                    let cond = self.compare("eq", &value, &case)?;
+                    output = self.ite(cond, &item, &output)?;
+                }
+                output
+            }
+            // Signedness is not modeled, so `$signed` is not supported either.
+            Expr::Call { func_name, .. } => {
+                return Err(ModuleError::Unsupported {
+                    module: self.module.clone(),
+                    what: format!("function call `{}`", func_name),
+                });
+            }
+            Expr::ConcatArray { inner, elt_typ } => {
+                let elts = inner.iter().map(|elt| self.expr(env, *elt)).collect::<Result<Vec<_>, _>>()?;
+                join_elts(&elts, elt_typ)
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn binary_op(&mut self, env: &Env, op: BinaryOp, lhs: ExprId, rhs: ExprId) -> Result<Vec<Bit>, ModuleError> {
+        let lhs = self.expr(env, lhs)?;
+        let rhs = self.expr(env, rhs)?;
+        let (width_lhs, width_rhs) = (lhs.len(), rhs.len());
+        let width_max = width_lhs.max(width_rhs);
+        let width_min = width_lhs.min(width_rhs);
+
+        let value = match op {
+            BinaryOp::Add => self.op("add", width_lhs + 1, &lhs, &rhs)?,
+            BinaryOp::Sub => self.op("sub", width_lhs, &lhs, &rhs)?,
+            BinaryOp::Mul => self.op("mul", width_lhs + width_rhs, &lhs, &rhs)?,
+            BinaryOp::Div | BinaryOp::Mod => {
+                let (name, width) = if op == BinaryOp::Div { ("udiv", width_lhs) } else { ("urem", width_rhs) };
+                let result = self.op(name, width_max, &lhs, &rhs)?;
+                let is_zero = self.compare("eq", &rhs, &[])?;
+                let result = self.ite(is_zero, &[], &result)?;
+                resize(&result, width)
+            }
+            BinaryOp::Or => self.op("or", width_min, &lhs, &rhs)?,
+            BinaryOp::And => self.op("and", width_min, &lhs, &rhs)?,
+            BinaryOp::Xor => self.op("xor", width_min, &lhs, &rhs)?,
+            BinaryOp::Eq => self.op("xnor", width_min, &lhs, &rhs)?,
+            BinaryOp::EqArithmetic => vec![self.compare("eq", &lhs, &rhs)?],
+            BinaryOp::Less => vec![self.compare("ult", &lhs, &rhs)?],
+            BinaryOp::Greater => vec![self.compare("ugt", &lhs, &rhs)?],
+            BinaryOp::LessEq => vec![self.compare("ulte", &lhs, &rhs)?],
+            BinaryOp::GreaterEq => vec![self.compare("ugte", &lhs, &rhs)?],
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                let left = op == BinaryOp::ShiftLeft;
+                match as_consts(&rhs).map(|rhs| to_usize(&rhs).unwrap_or(usize::MAX)) {
+                    Some(amount) => (0..width_lhs)
+                        .map(|i| {
+                            if left {
+                                i.checked_sub(amount).map_or(Bit::default(), |i| lhs[i])
+                            } else {
+                                lhs.get(i.saturating_add(amount)).copied().unwrap_or_default()
+                            }
+                        })
+                        .collect(),
+                    None => {
+                        let result = self.op(if left { "sll" } else { "srl" }, width_max, &lhs, &rhs)?;
+                        resize(&result, width_lhs)
+                    }
+                }
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Applies the operator to the operands zero-extended or truncated to the width.
+    fn op(&mut self, op: &str, width: usize, lhs: &[Bit], rhs: &[Bit]) -> Result<Vec<Bit>, ModuleError> {
+        if width == 0 {
+            return Ok(Vec::new());
+        }
+        let lhs = self.btor.node(&resize(lhs, width))?;
+        let rhs = self.btor.node(&resize(rhs, width))?;
+        self.btor.op(op, width, &[lhs, rhs])
+    }
+
+    /// Compares the operands as unsigned integers.
+    fn compare(&mut self, op: &str, lhs: &[Bit], rhs: &[Bit]) -> Result<Bit, ModuleError> {
+        let width = lhs.len().max(rhs.len());
+        if width == 0 {
+            return Ok(Bit::Const(matches!(op, "eq" | "ulte" | "ugte")));
+        }
+        let lhs = self.btor.node(&resize(lhs, width))?;
+        let rhs = self.btor.node(&resize(rhs, width))?;
+        Ok(self.btor.op(op, 1, &[lhs, rhs])?[0])
+    }
+
+    /// Negates the bits.
+    pub(super) fn not(&mut self, bits: &[Bit]) -> Result<Vec<Bit>, ModuleError> {
+        if let Some(value) = as_consts(bits) {
+            return Ok(value.into_iter().map(|b| Bit::Const(!b)).collect());
+        }
+        let node = self.btor.node(bits)?;
+        self.btor.op("not", bits.len(), &[node])
+    }
+
+    /// Returns `lhs` if `cond` is set, and `rhs` otherwise. Operands of different widths are
+    /// zero-extended.
+    fn ite(&mut self, cond: Bit, lhs: &[Bit], rhs: &[Bit]) -> Result<Vec<Bit>, ModuleError> {
+        let width = lhs.len().max(rhs.len());
+        let (lhs, rhs) = (resize(lhs, width), resize(rhs, width));
+        match cond.as_const() {
+            Some(true) => Ok(lhs),
+            Some(false) => Ok(rhs),
+            None if width == 0 || lhs == rhs => Ok(lhs),
+            None => {
+                let cond = self.btor.node(&[cond])?;
+                let lhs = self.btor.node(&lhs)?;
+                let rhs = self.btor.node(&rhs)?;
+                self.btor.op("ite", width, &[cond, lhs, rhs])
+            }
+        }
+    }
+
+    /// Returns `f(index)` if `index` is less than `count`, and `default` otherwise.
+    fn select<F: FnMut(usize) -> Vec<Bit>>(
+        &mut self, index: &[Bit], count: usize, default: Vec<Bit>, mut f: F,
+    ) -> Result<Vec<Bit>, ModuleError> {
+        if let Some(index) = as_consts(index) {
+            return Ok(match to_usize(&index) {
+                Some(index) if index < count => f(index),
+                _ => default,
+            });
+        }
+
+        let mut output = default;
+        for i in (0..count).rev() {
+            // Indices that do not fit in the width of `index` are never selected.
+            if index.len() < clog2(i + 1) {
+                continue;
+            }
+            let cond = self.compare("eq", index, &consts(&usize_to_bitvec(index.len(), i)))?;
+            let value = f(i);
+            output = self.ite(cond, &value, &output)?;
+        }
+        Ok(output)
+    }
+}
@@ -0,0 +1,348 @@
+//! Generates BTOR2 transition systems for bounded model checking.
+//!
+//! The module is flattened into FSMs connected by channels as in the simulator. States of FSMs
+//! become states of the system, forward values of input channels and backward values of output
+//! channels become inputs, and assertions of FSMs become bad state properties. Assumptions become
+//! constraints, and covers are not exported.
+//!
+//! Reset is not modeled: the system starts from the initial states of FSMs.
+
+mod btor;
+mod expr;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use btor::*;
+use expr::Env;
+
+use crate::lir::*;
+use crate::sim::netlist::*;
+use crate::sim::SimError;
+use crate::utils::join_options;
+use crate::*;
+
+impl Package {
+    /// Generates BTOR2 transition systems of top-level modules at the given directory path. Each
+    /// specialization of a parameterized module is generated in a file named after its parameter
+    /// values.
+    pub fn gen_btor<P: AsRef<Path>>(self, path_dir: P) -> Result<(), PackageError> {
+        fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
+        self.check_construction()?;
+
+        let top_modules = self.modules.iter().map(|module| (module.get_module_name(), module));
+        let specializations = self.param_modules.iter().flat_map(|module| module.named_specializations());
+
+        for (name, module) in top_modules.chain(specializations) {
+            let path = path_dir.as_ref().join(format!("{}.btor2", name));
+            let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+
+            let btor = gen_btor(module).map_err(|error| PackageError::Module { error })?;
+
+            write!(file, "{}", btor).map_err(|error| PackageError::Fs { error })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Generates a BTOR2 transition system of the module.
+pub fn gen_btor(module: &lir::Module) -> Result<String, ModuleError> {
+    let mut netlist = Netlist::default();
+    let inputs = netlist.alloc_ports(&module.inner.input_interface_typ());
+    let outputs = netlist.alloc_ports(&module.inner.output_interface_typ());
+    netlist.add_module(module, &[], &inputs, &outputs).map_err(|error| match error {
+        SimError::Module(error) => *error,
+        error => ModuleError::Misc(error.to_string()),
+    })?;
+    netlist.finish();
+
+    let inputs = inputs.into_iter().map(|(path, id)| (path, netlist.find(id))).collect::<Ports>();
+    let outputs = outputs.into_iter().map(|(path, id)| (path, netlist.find(id))).collect::<Ports>();
+
+    let mut btorgen = Btorgen::new(module.get_module_name(), netlist);
+    btorgen.btor.comment(&format!("Generated from module {}", module.get_module_name()));
+
+    // Inputs of the system.
+    let input_prefix = module.inner.input_prefix();
+    let output_prefix = module.inner.output_prefix();
+    for (path, net) in inputs.iter() {
+        let value = btorgen.port_input(&input_prefix, path, *net, Dir::Fwd)?;
+        btorgen.values.insert((*net, Dir::Fwd), value);
+    }
+    for (path, net) in outputs.iter() {
+        let value = btorgen.port_input(&output_prefix, path, *net, Dir::Bwd)?;
+        btorgen.values.insert((*net, Dir::Bwd), value);
+    }
+
+    // States of FSMs.
+    let mut states = Vec::new();
+    for fsm in btorgen.netlist.fsms.iter() {
+        let state = btorgen.btor.state(&fsm.init, &format!("{}_st", fsm.name))?;
+        states.push(state);
+    }
+    btorgen.states =
+        states.iter().map(|state| state.as_ref().map(|(_, bits)| bits.clone()).unwrap_or_default()).collect();
+
+    // Outputs of the system.
+    for (path, net) in outputs.iter() {
+        btorgen.port_output(&output_prefix, path, *net, Dir::Fwd)?;
+    }
+    for (path, net) in inputs.iter() {
+        btorgen.port_output(&input_prefix, path, *net, Dir::Bwd)?;
+    }
+
+    // Next states of FSMs.
+    for (index, state) in states.into_iter().enumerate() {
+        if let Some((node, _)) = state {
+            let next = btorgen.expr(&Env::Fsm(index), btorgen.netlist.fsms[index].state)?;
+            btorgen.btor.next(node, &next)?;
+        }
+    }
+
+    // Assertions of FSMs.
+    for index in 0..btorgen.netlist.fsms.len() {
+        let name = btorgen.netlist.fsms[index].name.clone();
+        for assertion in btorgen.netlist.fsms[index].assertions.clone() {
+            let cond = btorgen.expr(&Env::Fsm(index), assertion.cond)?[0];
+            let symbol = format!("{}: {}", name, assertion.message);
+            match assertion.kind {
+                AssertionKind::Assert => {
+                    btorgen.btor.comment(&format!("assert {}", symbol));
+                    let bad = btorgen.not(&[cond])?[0];
+                    btorgen.btor.bad(bad, &symbol)?;
+                }
+                AssertionKind::Assume => {
+                    btorgen.btor.comment(&format!("assume {}", symbol));
+                    btorgen.btor.constraint(cond, &symbol)?;
+                }
+                AssertionKind::Cover => {}
+            }
+        }
+    }
+
+    Ok(btorgen.btor.to_string())
+}
+
+/// BTOR2 generator for a flattened module.
+#[derive(Debug)]
+struct Btorgen {
+    /// Name of the flattened module.
+    module: String,
+
+    /// Transition system under construction.
+    btor: Btor,
+
+    /// Flattened module.
+    netlist: Netlist,
+
+    /// FSMs and indices of their channels driving the forward and backward values of nets.
+    drivers: HashMap<(usize, Dir), (usize, usize)>,
+
+    /// Translated values of nets.
+    values: HashMap<(usize, Dir), Vec<Bit>>,
+
+    /// Values of nets being translated, to detect combinational loops.
+    visiting: HashSet<(usize, Dir)>,
+
+    /// States of FSMs.
+    states: Vec<Vec<Bit>>,
+
+    /// Translated exprs, by the keys of their environments.
+    cache: HashMap<(usize, ExprId), Vec<Bit>>,
+
+    /// Number of environments.
+    envs: usize,
+}
+
+impl Btorgen {
+    fn new(module: String, netlist: Netlist) -> Self {
+        let mut drivers = HashMap::new();
+        for (index, fsm) in netlist.fsms.iter().enumerate() {
+            for (channel, net) in fsm.outputs.iter().enumerate() {
+                drivers.insert((*net, Dir::Fwd), (index, channel));
+            }
+            for (channel, net) in fsm.inputs.iter().enumerate() {
+                drivers.insert((*net, Dir::Bwd), (index, channel));
+            }
+        }
+
+        let envs = netlist.fsms.len();
+        Self {
+            btor: Btor::new(module.clone()),
+            module,
+            netlist,
+            drivers,
+            values: HashMap::new(),
+            visiting: HashSet::new(),
+            states: Vec::new(),
+            cache: HashMap::new(),
+            envs,
+        }
+    }
+
+    /// Returns the translated value of the net.
+    ///
+    /// Values of nets without drivers are zeros.
+    fn net(&mut self, net: usize, dir: Dir) -> Result<Vec<Bit>, ModuleError> {
+        if let Some(value) = self.values.get(&(net, dir)) {
+            return Ok(value.clone());
+        }
+
+        if !self.visiting.insert((net, dir)) {
+            let fsms = self
+                .visiting
+                .iter()
+                .filter_map(|key| self.drivers.get(key).map(|(index, _)| self.netlist.fsms[*index].name.clone()))
+                .collect::<HashSet<_>>();
+            return Err(ModuleError::Misc(format!("combinational loop through {:?}", fsms)));
+        }
+
+        let typ = self.netlist.nets[net].typ.clone();
+        let value = match self.drivers.get(&(net, dir)).copied() {
+            Some((index, channel)) => {
+                let fsm = &self.netlist.fsms[index];
+                let (expr, interface_typ) = match dir {
+                    Dir::Fwd => (fsm.output_fwd, fsm.output_interface_typ.clone()),
+                    Dir::Bwd => (fsm.input_bwd, fsm.input_interface_typ.clone()),
+                };
+                let value = self.expr(&Env::Fsm(index), expr)?;
+                split_interface(&interface_typ, dir, &value).swap_remove(channel)
+            }
+            None => {
+                let width = match dir {
+                    Dir::Fwd => typ.fwd.width(),
+                    Dir::Bwd => typ.bwd.width(),
+                };
+                vec![Bit::default(); width]
+            }
+        };
+
+        self.visiting.remove(&(net, dir));
+        self.values.insert((net, dir), value.clone());
+        Ok(value)
+    }
+
+    /// Returns the translated value of the input of the FSM.
+    fn fsm_input(&mut self, index: usize, name: Option<&str>) -> Result<Vec<Bit>, ModuleError> {
+        let fsm = &self.netlist.fsms[index];
+        let (interface_typ, nets, dir) = match name {
+            Some("in") => (fsm.input_interface_typ.clone(), fsm.inputs.clone(), Dir::Fwd),
+            Some("out") => (fsm.output_interface_typ.clone(), fsm.outputs.clone(), Dir::Bwd),
+            Some("st") => return Ok(self.states[index].clone()),
+            _ => return Err(ModuleError::Misc(format!("input {:?} is not bound", name))),
+        };
+
+        let module = fsm.name.clone();
+
+        let values = nets.into_iter().map(|net| self.net(net, dir)).collect::<Result<Vec<_>, _>>()?;
+        join_interface(&interface_typ, dir, &mut values.iter().map(|value| &value[..]))
+            .map_err(|error| ModuleError::Interface { module, error })
+    }
+
+    /// Adds inputs for the ports of the channel, and returns the value of the channel.
+    fn port_input(
+        &mut self, prefix: &Option<String>, path: &EndpointPath, net: usize, dir: Dir,
+    ) -> Result<Vec<Bit>, ModuleError> {
+        let typ = self.netlist.nets[net].typ.clone();
+        let decls = match dir {
+            Dir::Fwd => &typ.fwd,
+            Dir::Bwd => &typ.bwd,
+        };
+        let base = port_name(prefix, path);
+        let values = decls
+            .iter()
+            .map(|(name, shape)| self.btor.input(shape.width(), &port_member(&base, name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(values.concat())
+    }
+
+    /// Adds outputs for the ports of the channel.
+    fn port_output(
+        &mut self, prefix: &Option<String>, path: &EndpointPath, net: usize, dir: Dir,
+    ) -> Result<(), ModuleError> {
+        let typ = self.netlist.nets[net].typ.clone();
+        let decls = match dir {
+            Dir::Fwd => &typ.fwd,
+            Dir::Bwd => &typ.bwd,
+        };
+        let base = port_name(prefix, path);
+        let value = self.net(net, dir)?;
+        let mut offset = 0;
+        for (name, shape) in decls.iter() {
+            self.btor.output(&value[offset..(offset + shape.width())], &port_member(&base, name))?;
+            offset += shape.width();
+        }
+        Ok(())
+    }
+}
+
+/// Returns the name of the channel at the path of an interface with the prefix.
+fn port_name(prefix: &Option<String>, path: &EndpointPath) -> Option<String> {
+    let nodes = path.inner.iter().map(|node| match node {
+        EndpointNode::Index(index) | EndpointNode::ExpansiveIndex(index) => Some(index.to_string()),
+        EndpointNode::Field(name, _) => Some(name.clone()),
+    });
+    join_options("_", [prefix.clone()].into_iter().chain(nodes))
+}
+
+/// Returns the name of the port of the member of the channel.
+fn port_member(base: &Option<String>, name: Option<String>) -> String {
+    join_options("_", [base.clone(), name]).unwrap_or_else(|| "_".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    type Count = Bits<U<3>>;
+
+    /// Returns an FSM counting from 0 to 4 repeatedly, asserting that the count does not exceed 4.
+    fn counter() -> Module<(), UniChannel<Count>> {
+        hir::Fsm::<(), UniChannel<Count>, Count, _>::new(
+            "counter",
+            |_, bwd, state| {
+                state.is_le(4.into()).assert("count exceeds 4");
+                let next = state.is_eq(4.into()).cond(0.into(), (state + 1.into()).resize());
+                (state, bwd, next)
+            },
+            0.into(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn counter_golden() {
+        let files = generate(package(counter()), |package, dir| package.gen_btor(dir)).unwrap();
+        assert_eq!(
+            files["counter.btor2"],
+            r#"; Generated from module counter
+1 sort bitvec 3
+2 state 1 counter_st
+3 const 1 000
+4 init 1 2 3
+5 output 2 out
+6 const 1 100
+7 sort bitvec 1
+8 eq 7 2 6
+9 const 7 0
+10 sort bitvec 4
+11 concat 10 9 2
+12 const 10 0001
+13 add 10 11 12
+14 slice 1 13 2 0
+15 ite 1 8 3 14
+16 next 1 2 15
+17 ulte 7 2 6
+; assert counter: count exceeds 4
+18 not 7 17
+19 bad 18 counter:_count_exceeds_4
+"#
+        );
+    }
+}
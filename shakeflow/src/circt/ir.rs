@@ -0,0 +1,501 @@
+//! CIRCT IR.
+//!
+//! Textual MLIR in the `hw`, `comb` and `seq` dialects of CIRCT. Only the operations used by the
+//! generator are modeled, and the bodies of modules are graph regions, so values may be used
+//! before their definitions.
+
+use crate::codegen::*;
+use crate::utils::indent;
+
+const INDENT: usize = 2;
+
+/// Type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    /// Clock type.
+    Clock,
+    /// Integer type of the given width.
+    Int(usize),
+}
+
+impl ToString for Type {
+    fn to_string(&self) -> String {
+        match self {
+            Type::Clock => "!seq.clock".to_string(),
+            Type::Int(width) => format!("i{}", width),
+        }
+    }
+}
+
+/// SSA value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Value {
+    /// Name of the value
+    pub name: String,
+    /// Type of the value
+    pub tpe: Type,
+}
+
+impl ToString for Value {
+    fn to_string(&self) -> String { format!("%{}", self.name) }
+}
+
+impl Value {
+    /// Creates new clock value.
+    #[inline]
+    pub fn clock(name: String) -> Self { Value { name, tpe: Type::Clock } }
+
+    /// Creates new integer value.
+    #[inline]
+    pub fn int(name: String, width: usize) -> Self { Value { name, tpe: Type::Int(width) } }
+
+    /// Returns the width of the value. Clocks are 1-bit wide.
+    pub fn width(&self) -> usize {
+        match self.tpe {
+            Type::Clock => 1,
+            Type::Int(width) => width,
+        }
+    }
+}
+
+/// Operators of `comb` whose operands and result are of the same type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombOp {
+    /// Addition
+    Add,
+    /// Subtraction
+    Sub,
+    /// Multiplication
+    Mul,
+    /// Unsigned division
+    DivU,
+    /// Unsigned remainder
+    ModU,
+    /// Bitwise and
+    And,
+    /// Bitwise or
+    Or,
+    /// Bitwise exclusive or
+    Xor,
+    /// Shift left
+    Shl,
+    /// Logical shift right
+    ShrU,
+}
+
+impl ToString for CombOp {
+    fn to_string(&self) -> String {
+        match self {
+            CombOp::Add => "comb.add",
+            CombOp::Sub => "comb.sub",
+            CombOp::Mul => "comb.mul",
+            CombOp::DivU => "comb.divu",
+            CombOp::ModU => "comb.modu",
+            CombOp::And => "comb.and",
+            CombOp::Or => "comb.or",
+            CombOp::Xor => "comb.xor",
+            CombOp::Shl => "comb.shl",
+            CombOp::ShrU => "comb.shru",
+        }
+        .to_string()
+    }
+}
+
+/// Predicates of `comb.icmp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ICmpPredicate {
+    /// Equal
+    Eq,
+    /// Not equal
+    Ne,
+    /// Unsigned less than
+    Ult,
+    /// Unsigned less than or equal
+    Ule,
+    /// Unsigned greater than
+    Ugt,
+    /// Unsigned greater than or equal
+    Uge,
+}
+
+impl ToString for ICmpPredicate {
+    fn to_string(&self) -> String {
+        match self {
+            ICmpPredicate::Eq => "eq",
+            ICmpPredicate::Ne => "ne",
+            ICmpPredicate::Ult => "ult",
+            ICmpPredicate::Ule => "ule",
+            ICmpPredicate::Ugt => "ugt",
+            ICmpPredicate::Uge => "uge",
+        }
+        .to_string()
+    }
+}
+
+/// Reset of register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reset {
+    /// Active-high reset signal
+    pub signal: Value,
+    /// Whether the reset is asynchronous
+    pub is_async: bool,
+    /// Reset value, which is a constant
+    pub value: Value,
+}
+
+/// Operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Constant, `hw.constant`.
+    Constant {
+        /// Value, whose first element is the most significant bit
+        value: LogicValues,
+    },
+    /// Named copy of the value, `hw.wire`.
+    Wire {
+        /// Input value
+        input: Value,
+    },
+    /// Operation of `comb` whose operands and result are of the same type.
+    Comb {
+        /// Operator
+        op: CombOp,
+        /// Operands
+        operands: Vec<Value>,
+    },
+    /// Comparison, `comb.icmp`.
+    ICmp {
+        /// Predicate
+        predicate: ICmpPredicate,
+        /// Left operand
+        lhs: Value,
+        /// Right operand
+        rhs: Value,
+    },
+    /// Multiplexer, `comb.mux`.
+    Mux {
+        /// Condition
+        cond: Value,
+        /// Value if the condition is true
+        tval: Value,
+        /// Value if the condition is false
+        fval: Value,
+    },
+    /// Bit extraction, `comb.extract`.
+    Extract {
+        /// Input value
+        input: Value,
+        /// Offset of the least significant bit
+        low: usize,
+        /// Width of the result
+        width: usize,
+    },
+    /// Concatenation, `comb.concat`.
+    Concat {
+        /// Operands, whose first element is placed at the most significant bits
+        operands: Vec<Value>,
+    },
+    /// Replication, `comb.replicate`.
+    Replicate {
+        /// Input value
+        input: Value,
+        /// Number of repetitions
+        count: usize,
+    },
+    /// Register, `seq.firreg`.
+    Register {
+        /// Next value
+        next: Value,
+        /// Clock signal
+        clock: Value,
+        /// Reset, or `None` for registers without reset
+        reset: Option<Reset>,
+    },
+    /// Module instantiation, `hw.instance`.
+    Instance {
+        /// Name of the instance
+        name: String,
+        /// Name of the module
+        module: String,
+        /// Parameters of the module
+        params: Vec<(String, usize)>,
+        /// Input ports and their values
+        inputs: Vec<(String, Value)>,
+        /// Output ports, whose values are the results of the operation
+        outputs: Vec<(String, Type)>,
+    },
+}
+
+impl ToString for Operation {
+    fn to_string(&self) -> String {
+        match self {
+            Operation::Constant { value } => {
+                format!("hw.constant {} : {}", gen_constant_literal(value), Type::Int(value.len()).to_string())
+            }
+            Operation::Wire { input } => format!("hw.wire {} : {}", input.to_string(), input.tpe.to_string()),
+            Operation::Comb { op, operands } => format!(
+                "{} {} : {}",
+                op.to_string(),
+                operands.iter().map(|operand| operand.to_string()).collect::<Vec<_>>().join(", "),
+                operands[0].tpe.to_string()
+            ),
+            Operation::ICmp { predicate, lhs, rhs } => format!(
+                "comb.icmp {} {}, {} : {}",
+                predicate.to_string(),
+                lhs.to_string(),
+                rhs.to_string(),
+                lhs.tpe.to_string()
+            ),
+            Operation::Mux { cond, tval, fval } => format!(
+                "comb.mux {}, {}, {} : {}",
+                cond.to_string(),
+                tval.to_string(),
+                fval.to_string(),
+                tval.tpe.to_string()
+            ),
+            Operation::Extract { input, low, width } => format!(
+                "comb.extract {} from {} : ({}) -> {}",
+                input.to_string(),
+                low,
+                input.tpe.to_string(),
+                Type::Int(*width).to_string()
+            ),
+            Operation::Concat { operands } => format!(
+                "comb.concat {} : {}",
+                operands.iter().map(|operand| operand.to_string()).collect::<Vec<_>>().join(", "),
+                operands.iter().map(|operand| operand.tpe.to_string()).collect::<Vec<_>>().join(", ")
+            ),
+            Operation::Replicate { input, count } => format!(
+                "comb.replicate {} : ({}) -> {}",
+                input.to_string(),
+                input.tpe.to_string(),
+                Type::Int(input.width() * count).to_string()
+            ),
+            Operation::Register { next, clock, reset } => format!(
+                "seq.firreg {} clock {}{} : {}",
+                next.to_string(),
+                clock.to_string(),
+                match reset {
+                    Some(Reset { signal, is_async, value }) => format!(
+                        " reset {} {}, {}",
+                        if *is_async { "async" } else { "sync" },
+                        signal.to_string(),
+                        value.to_string()
+                    ),
+                    None => "".to_string(),
+                },
+                next.tpe.to_string()
+            ),
+            Operation::Instance { name, module, params, inputs, outputs } => format!(
+                "hw.instance \"{}\" @{}{}({}) -> ({})",
+                name,
+                module,
+                gen_params(params),
+                inputs
+                    .iter()
+                    .map(|(port, value)| format!("{}: {}: {}", port, value.to_string(), value.tpe.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                outputs
+                    .iter()
+                    .map(|(port, tpe)| format!("{}: {}", port, tpe.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// Operation whose results are bound to the values.
+    Operation {
+        /// Results of the operation
+        results: Vec<Value>,
+        /// Operation
+        op: Operation,
+    },
+    /// Block of statements.
+    Block {
+        /// Statements
+        stmts: Vec<Statement>,
+    },
+    /// Statement with comment.
+    Commented {
+        /// Comment
+        comment: String,
+        /// Statement
+        stmt: Box<Statement>,
+    },
+}
+
+impl ToString for Statement {
+    fn to_string(&self) -> String {
+        match self {
+            Statement::Operation { results, op } => {
+                if results.is_empty() {
+                    op.to_string()
+                } else {
+                    format!(
+                        "{} = {}",
+                        results.iter().map(|result| result.to_string()).collect::<Vec<_>>().join(", "),
+                        op.to_string()
+                    )
+                }
+            }
+            Statement::Block { stmts } => {
+                stmts.iter().map(|stmt| stmt.to_string()).filter(|stmt| !stmt.is_empty()).collect::<Vec<_>>().join("\n")
+            }
+            Statement::Commented { comment, stmt } => {
+                let comment = comment.lines().map(|line| format!("// {}", line)).collect::<Vec<_>>().join("\n");
+                format!("{}\n{}", comment, stmt.to_string())
+            }
+        }
+    }
+}
+
+impl Statement {
+    /// Creates new operation whose result is bound to `result`.
+    #[inline]
+    pub fn op(result: Value, op: Operation) -> Self { Statement::Operation { results: vec![result], op } }
+
+    /// Creates new block statement.
+    #[inline]
+    pub fn block(stmts: Vec<Statement>) -> Self { Statement::Block { stmts } }
+}
+
+/// Port of module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Port {
+    /// Name of the port
+    pub name: String,
+    /// Direction of the port
+    pub direction: Direction,
+    /// Type of the port
+    pub tpe: Type,
+}
+
+impl ToString for Port {
+    fn to_string(&self) -> String {
+        match self.direction {
+            Direction::Input => format!("in %{} : {}", self.name, self.tpe.to_string()),
+            Direction::Output => format!("out {} : {}", self.name, self.tpe.to_string()),
+        }
+    }
+}
+
+/// Module, `hw.module`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    /// Name of the module
+    pub name: String,
+    /// Ports of the module
+    pub ports: Vec<Port>,
+    /// Body of the module
+    pub body: Statement,
+}
+
+impl ToString for Module {
+    fn to_string(&self) -> String {
+        let outputs = self.ports.iter().filter(|port| port.direction == Direction::Output).collect::<Vec<_>>();
+        let output = if outputs.is_empty() {
+            "hw.output".to_string()
+        } else {
+            format!(
+                "hw.output {} : {}",
+                outputs.iter().map(|port| format!("%{}", port.name)).collect::<Vec<_>>().join(", "),
+                outputs.iter().map(|port| port.tpe.to_string()).collect::<Vec<_>>().join(", ")
+            )
+        };
+        let body = self.body.to_string();
+
+        format!(
+            "hw.module @{}({}) {{\n{}\n}}",
+            self.name,
+            self.ports.iter().map(|port| port.to_string()).collect::<Vec<_>>().join(", "),
+            indent(if body.is_empty() { output } else { format!("{}\n{}", body, output) }, INDENT)
+        )
+    }
+}
+
+/// Externally defined module, e.g., a Verilog module, `hw.module.extern`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtModule {
+    /// Name of the module
+    pub name: String,
+    /// Ports of the module
+    pub ports: Vec<Port>,
+    /// Name of the module in the generated Verilog
+    pub verilog_name: String,
+    /// Parameters of the module
+    pub params: Vec<String>,
+}
+
+impl ToString for ExtModule {
+    fn to_string(&self) -> String {
+        format!(
+            "hw.module.extern @{}{}({}) attributes {{verilogName = \"{}\"}}",
+            self.name,
+            if self.params.is_empty() {
+                "".to_string()
+            } else {
+                format!("<{}>", self.params.iter().map(|name| format!("{}: i32", name)).collect::<Vec<_>>().join(", "))
+            },
+            self.ports.iter().map(|port| port.to_string()).collect::<Vec<_>>().join(", "),
+            self.verilog_name
+        )
+    }
+}
+
+/// Design, which consists of modules and the external modules they instantiate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Design {
+    /// External modules
+    pub ext_modules: Vec<ExtModule>,
+    /// Modules, where instantiated modules precede the modules instantiating them
+    pub modules: Vec<Module>,
+}
+
+impl ToString for Design {
+    fn to_string(&self) -> String {
+        ::std::iter::empty()
+            .chain(self.ext_modules.iter().map(|module| module.to_string()))
+            .chain(self.modules.iter().map(|module| module.to_string()))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Returns the parameter list of the instance, e.g., `<WIDTH: i32 = 4>`.
+fn gen_params(params: &[(String, usize)]) -> String {
+    if params.is_empty() {
+        return "".to_string();
+    }
+
+    format!(
+        "<{}>",
+        params.iter().map(|(name, value)| format!("{}: i32 = {}", name, value)).collect::<Vec<_>>().join(", ")
+    )
+}
+
+/// Returns the integer literal of the value. Unknown bits are zero, and values wider than 64 bits
+/// are written in hexadecimal.
+fn gen_constant_literal(value: &LogicValues) -> String {
+    let bits = value.iter().map(|bit| *bit == LogicValue::True).collect::<Vec<_>>();
+
+    if bits.len() <= 64 {
+        return bits.iter().fold(0u64, |acc, bit| (acc << 1) | u64::from(*bit)).to_string();
+    }
+
+    let digits = bits
+        .rchunks(4)
+        .rev()
+        .map(|chunk| {
+            let digit = chunk.iter().fold(0u32, |acc, bit| (acc << 1) | u32::from(*bit));
+            char::from_digit(digit, 16).unwrap()
+        })
+        .collect::<String>();
+    let digits = digits.trim_start_matches('0');
+    format!("0x{}", if digits.is_empty() { "0" } else { digits })
+}
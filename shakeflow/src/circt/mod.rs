@@ -0,0 +1,5 @@
+//! CIRCT.
+
+mod ir;
+
+pub use ir::*;
@@ -0,0 +1,1321 @@
+//! Generates CIRCT code in the `hw`, `comb` and `seq` dialects.
+//!
+//! # Note
+//!
+//! Signals are flattened into integers, and loops in expressions and arrays of modules are
+//! unrolled as in FIRRTL. Wires and output ports are bound to the values connected to them by
+//! `lower_connects`. Names of the values introduced by the generator contain `.`, so they do not
+//! conflict with the names of signals.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::panic::Location;
+use std::path::Path;
+
+use hashcons::merkle::Merkle;
+use itertools::izip;
+
+use crate::circt::{Value, *};
+use crate::codegen::*;
+use crate::*;
+
+impl Package {
+    /// Generates CIRCT design of the top-level module, which contains the modules it instantiates.
+    /// Modules instantiated by FFIs are declared as external modules.
+    fn gen_circt_design(&self, name: String, module: &lir::Module) -> Result<Design, PackageError> {
+        let module_insts = scan_module_insts(module);
+
+        let mut design = Design { ext_modules: Vec::new(), modules: Vec::new() };
+
+        for (name, module_inst) in module_insts {
+            match &module_inst.module {
+                Some(submodule) => design.modules.push(
+                    gen_module::<Circtgen>(name, submodule, self.reset_style)
+                        .map_err(|error| PackageError::Module { error })?
+                        .into(),
+                ),
+                None => design.ext_modules.push(
+                    gen_ext_module(name, &module_inst, self.reset_style)
+                        .map_err(|error| PackageError::Module { error })?,
+                ),
+            }
+        }
+
+        design.modules.push(
+            gen_module::<Circtgen>(name, module, self.reset_style)
+                .map_err(|error| PackageError::Module { error })?
+                .into(),
+        );
+
+        Ok(design)
+    }
+
+    /// Generates CIRCT code at the given directory path.
+    ///
+    /// Each top-level module is generated in a file with the modules it instantiates, as is each
+    /// specialization of a parameterized module, named after its parameter values.
+    pub fn gen_circt<P: AsRef<Path>>(mut self, path_dir: P) -> Result<(), PackageError> {
+        fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
+
+        let (_, top_modules) = self.scan_modules()?;
+
+        let top_modules = top_modules.iter().map(|module| (module.get_module_name(), module));
+        let specializations = self.param_modules.iter().flat_map(|module| module.named_specializations());
+
+        for (name, module) in top_modules.chain(specializations) {
+            let path = path_dir.as_ref().join(format!("{}_inner.mlir", name));
+            let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+
+            let design = self.gen_circt_design(name, module)?;
+
+            writeln!(file, "{}", design.to_string()).map_err(|error| PackageError::Fs { error })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<codegen::Module<Circtgen>> for circt::Module {
+    fn from(module: codegen::Module<Circtgen>) -> Self {
+        let body = lower_connects(&module.ports, module.body);
+        circt::Module { name: module.name, ports: module.ports, body }
+    }
+}
+
+/// Generates external module instantiated by the module instantiation.
+fn gen_ext_module(
+    name: String, module_inst: &lir::ModuleInst, reset_style: lir::ResetStyle,
+) -> Result<ExtModule, lir::ModuleError> {
+    let clocks = gen_module_inst_clocks(module_inst, reset_style.polarity);
+
+    let ports = gen_connections(module_inst, &mut Context::with_reset_style(reset_style))?
+        .into_iter()
+        .map(|(direction, name, width, ..)| {
+            let tpe = if clocks.contains(&name) { Type::Clock } else { Type::Int(width) };
+            Port { name, direction, tpe }
+        })
+        .collect();
+
+    Ok(ExtModule {
+        name,
+        ports,
+        verilog_name: module_inst.module_name.clone(),
+        params: module_inst.params.iter().map(|(name, _)| name.clone()).collect(),
+    })
+}
+
+/// Module body, whose wires and connections are lowered into operations by `lower_connects`.
+#[derive(Debug, Clone)]
+pub enum Body {
+    /// Statement.
+    Stmt(Statement),
+
+    /// Wire declaration.
+    Wire {
+        /// Name of the wire
+        name: String,
+        /// Width of the wire
+        width: usize,
+    },
+
+    /// Connection to a wire or an output port, where ranges are the offset and width of the sliced
+    /// signals.
+    Connect {
+        /// L-value
+        lvalue: String,
+        /// Range of l-value
+        lvalue_range: Option<(usize, usize)>,
+        /// R-value
+        rvalue: String,
+        /// Range of r-value
+        rvalue_range: Option<(usize, usize)>,
+    },
+
+    /// Block of bodies.
+    Block(Vec<Body>),
+
+    /// Body with comment.
+    Commented {
+        /// Comment
+        comment: String,
+        /// Body
+        body: Box<Body>,
+    },
+}
+
+/// CIRCT Generator
+#[derive(Default, Debug)]
+pub struct Circtgen;
+
+impl Codegen for Circtgen {
+    type Body = Body;
+    type Ports = Vec<Port>;
+
+    fn gen_port_decls(&self, module: &lir::Module, ctx: &Context) -> Result<Vec<Port>, lir::ModuleError> {
+        let clocks = gen_module_clocks(module, ctx.reset_style().polarity);
+
+        Ok(gen_port_decls(module, ctx)?
+            .into_iter()
+            .map(|(direction, width, name)| {
+                let tpe = if clocks.contains(&name) { Type::Clock } else { Type::Int(width) };
+                Port { name, direction, tpe }
+            })
+            .collect())
+    }
+
+    fn gen_module_composite(&self, module: &lir::CompositeModule, ctx: &mut Context) -> Result<Body, lir::ModuleError> {
+        let clock_domain = ctx.enter_clock_domain(module.clock_domain.clone());
+        let mut bodies = vec![];
+
+        match module.module_typ {
+            lir::CompositeModuleTyp::OneToOne => {
+                for (name, shape) in gen_submodule_wires(module, ctx)? {
+                    bodies.push(Body::Wire { name, width: shape.width() });
+                }
+
+                bodies.append(&mut self.gen_module_wiring(module, ctx.get_prefix())?);
+                bodies.append(&mut self.gen_submodules(module, ctx)?);
+            }
+            lir::CompositeModuleTyp::NToN(n) => {
+                let genvar_id = ctx.alloc_genvar_id();
+
+                let mut module = module.clone();
+                module.module_typ = lir::CompositeModuleTyp::OneToOne;
+
+                // Arrays of modules are unrolled. Signals in each iteration are prefixed by the
+                // genvar and the iteration index.
+                for i in 0..n {
+                    let prefix = ctx.get_prefix();
+                    ctx.enter_scope(format!("{}_{}", genvar_id, i));
+
+                    for (name, shape) in gen_submodule_wires(&module, ctx)? {
+                        bodies.push(Body::Wire { name, width: shape.width() });
+                    }
+
+                    bodies.append(&mut self.gen_module_wiring_array(&module, prefix, ctx.get_prefix(), i)?);
+                    bodies.append(&mut self.gen_submodules(&module, ctx)?);
+
+                    ctx.leave_scope();
+                }
+            }
+        }
+
+        ctx.leave_clock_domain(clock_domain);
+        Ok(gen_commented(module.source, Body::Block(bodies)))
+    }
+
+    /// Generates target code for FSM.
+    ///
+    /// # Note
+    ///
+    /// Each field of the state is a register named after the field, whose next value is connected
+    /// to the wire suffixed with `.next` by the state update logic.
+    fn gen_module_fsm(&self, module: &lir::Fsm, ctx: &mut Context) -> Result<Body, lir::ModuleError> {
+        let mut bodies = Vec::new();
+
+        let state_init = gen_module_fsm_state_init(&module.state.into_expr(), &module.init.into_expr(), ctx)?;
+
+        // (1) state registers
+        {
+            let mut stmts = Vec::new();
+            let (clk, rst) = ctx.clock_signals();
+            let reset = match ctx.reset_kind(module) {
+                lir::ResetKind::None => None,
+                reset_kind => {
+                    let signal = match ctx.reset_style().polarity {
+                        lir::ResetPolarity::ActiveHigh => Value::int(rst, 1),
+                        lir::ResetPolarity::ActiveLow => gen_not(&mut stmts, ctx, Value::int(rst, 1)),
+                    };
+                    Some((signal, reset_kind == lir::ResetKind::Async))
+                }
+            };
+
+            // Zero-width fields of the state are not declared.
+            for (shape, net_name, init_value) in state_init.into_iter().filter(|(shape, ..)| shape.width() > 0) {
+                let next = format!("{}.next", net_name);
+                bodies.push(Body::Wire { name: next.clone(), width: shape.width() });
+
+                let reset = reset.clone().map(|(signal, is_async)| Reset {
+                    signal,
+                    is_async,
+                    value: gen_constant(&mut stmts, ctx, init_value),
+                });
+                stmts.push(Statement::op(Value::int(net_name, shape.width()), Operation::Register {
+                    next: Value::int(next, shape.width()),
+                    clock: Value::clock(clk.clone()),
+                    reset,
+                }));
+            }
+
+            bodies.push(Body::Stmt(Statement::block(stmts)));
+        }
+
+        // (2) input, output logic
+        bodies.push(self.gen_module_fsm_output("out".to_string(), module.output_fwd.into_expr(), ctx)?);
+        bodies.push(self.gen_module_fsm_output("in".to_string(), module.input_bwd.into_expr(), ctx)?);
+
+        // (3) state update logic
+        bodies.push(self.gen_module_fsm_state("st".to_string(), module.state.into_expr(), ctx)?);
+
+        Ok(gen_commented(module.source, Body::Block(bodies)))
+    }
+
+    fn gen_module_inst(&self, module: &lir::ModuleInst, ctx: &mut Context) -> Result<Body, lir::ModuleError> {
+        let inst_name = join_options("_", [ctx.get_prefix(), Some(module.inst_name.clone())]).unwrap();
+        let clocks = gen_module_inst_clocks(module, ctx.reset_style().polarity);
+
+        let (mut inputs, mut outputs, mut results, mut conts) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let mut stmts = Vec::new();
+        for (direction, port, width, expr, inverted) in gen_connections(module, ctx)? {
+            match direction {
+                Direction::Input => {
+                    let value = if clocks.contains(&port) { Value::clock(expr) } else { Value::int(expr, width) };
+                    let value = if inverted { gen_not(&mut stmts, ctx, value) } else { value };
+                    inputs.push((port, value));
+                }
+                Direction::Output => {
+                    let result = format!("{}.{}", inst_name, port);
+                    conts.push(gen_connect(expr, None, result.clone(), None));
+                    results.push(Value::int(result, width));
+                    outputs.push((port, Type::Int(width)));
+                }
+            }
+        }
+
+        // Parameterized modules are specialized, except for the external modules.
+        let params = if module.module.is_some() { Vec::new() } else { module.params.clone() };
+
+        let module_inst = Statement::Operation {
+            results,
+            op: Operation::Instance { name: inst_name, module: gen_module_inst_name(module), params, inputs, outputs },
+        };
+
+        let bodies = [stmts.into_iter().map(Body::Stmt).collect(), vec![Body::Stmt(module_inst)], conts].concat();
+        Ok(gen_commented(module.source, Body::Block(bodies)))
+    }
+
+    fn gen_module_virtual(
+        &self, module: &lir::VirtualModule, composite_context_prefix: Option<String>, ctx: &mut Context,
+    ) -> Result<Body, lir::ModuleError> {
+        let conts = gen_virtual_wirings(module, composite_context_prefix, ctx.get_prefix())?
+            .into_iter()
+            .map(|(lvalue, lvalue_range, rvalue, rvalue_range)| {
+                gen_connect(
+                    lvalue,
+                    lvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                    rvalue,
+                    rvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                )
+            })
+            .collect();
+
+        Ok(Body::Block(conts))
+    }
+}
+
+impl Circtgen {
+    /// Generates CIRCT code for registered modules and submodules in the module.
+    fn gen_submodules(&self, module: &lir::CompositeModule, ctx: &mut Context) -> Result<Vec<Body>, lir::ModuleError> {
+        let mut bodies = vec![];
+
+        for (index, submodule) in module.registered_modules.iter().enumerate() {
+            let comp_name = submodule.get_module_name();
+            ctx.enter_scope(format!("registered_{}_{}", comp_name, index));
+            match &*submodule.inner {
+                lir::ModuleInner::ModuleInst(module) => {
+                    bodies.push(self.gen_module_inst(module, ctx)?);
+                }
+                lir::ModuleInner::Composite(_, module) => {
+                    bodies.push(self.gen_module_composite(module, ctx)?);
+                }
+                _ => {
+                    return Err(lir::ModuleError::RegisteredModule {
+                        module: module.name.clone(),
+                        submodule: comp_name,
+                    })
+                }
+            }
+            ctx.leave_scope();
+        }
+
+        let composite_context_prefix = ctx.get_prefix();
+
+        for (index, (submodule, _)) in module.submodules.iter().enumerate() {
+            let comp_name = submodule.get_module_name();
+            ctx.enter_scope(format!("{}_{}", comp_name, index));
+            match &*submodule.inner {
+                lir::ModuleInner::Composite(_, module) => {
+                    bodies.push(self.gen_module_composite(module, ctx)?);
+                }
+                lir::ModuleInner::Fsm(module) => {
+                    bodies.push(self.gen_module_fsm(module, ctx)?);
+                }
+                lir::ModuleInner::ModuleInst(module) => {
+                    bodies.push(self.gen_module_inst(module, ctx)?);
+                }
+                lir::ModuleInner::VirtualModule(module) => {
+                    bodies.push(self.gen_module_virtual(module, composite_context_prefix.clone(), ctx)?);
+                }
+            }
+            ctx.leave_scope();
+        }
+
+        Ok(bodies)
+    }
+
+    /// Generates CIRCT code for wirings in the module.
+    fn gen_module_wiring(
+        &self, module: &lir::CompositeModule, prefix: Option<String>,
+    ) -> Result<Vec<Body>, lir::ModuleError> {
+        Ok(gen_wiring(module, prefix)?
+            .into_iter()
+            .map(|(lvalue, lvalue_range, rvalue, rvalue_range)| {
+                gen_connect(
+                    lvalue,
+                    lvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                    rvalue,
+                    rvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                )
+            })
+            .collect())
+    }
+
+    /// Generates CIRCT code for wirings in the `iteration`-th element of the array module.
+    ///
+    /// Signals of the array module are prefixed by `prefix`, and signals in the iteration are
+    /// prefixed by `iteration_prefix`.
+    fn gen_module_wiring_array(
+        &self, module: &lir::CompositeModule, prefix: Option<String>, iteration_prefix: Option<String>,
+        iteration: usize,
+    ) -> Result<Vec<Body>, lir::ModuleError> {
+        let range = |generate: Option<usize>, range: Option<(usize, usize)>| match (generate, range) {
+            (Some(gen_size), Some((index, elt_size))) => Some((iteration * gen_size + index * elt_size, elt_size)),
+            (Some(gen_size), None) => Some((iteration * gen_size, gen_size)),
+            (None, Some((index, elt_size))) => Some((index * elt_size, elt_size)),
+            (None, None) => None,
+        };
+
+        Ok(izip!(gen_wiring_array(module, prefix)?, gen_wiring_array(module, iteration_prefix)?)
+            .map(
+                |(
+                    (lvalue, lvalue_generate, lvalue_range, rvalue, rvalue_generate, rvalue_range),
+                    (lvalue_inner, _, _, rvalue_inner, ..),
+                )| {
+                    gen_connect(
+                        if lvalue_generate.is_some() { lvalue } else { lvalue_inner },
+                        range(lvalue_generate, lvalue_range),
+                        if rvalue_generate.is_some() { rvalue } else { rvalue_inner },
+                        range(rvalue_generate, rvalue_range),
+                    )
+                },
+            )
+            .collect())
+    }
+
+    /// Generates FSM output.
+    ///
+    /// Returns `Err` if types of `typ` and `output` are mismatched.
+    fn gen_module_fsm_output(
+        &self, target: String, output: Merkle<lir::Expr>, ctx: &mut Context,
+    ) -> Result<Body, lir::ModuleError> {
+        let mut stmts = Vec::new();
+        let exprs = self.gen_expr(&output, ctx, &mut HashMap::new(), &mut stmts)?;
+
+        let assignments = match_value_typ_exprs(
+            join_options("_", [ctx.get_prefix(), Some(target)]),
+            flatten_typ(output.port_decls()),
+            exprs,
+        )
+        .ok_or_else(|| ctx.expr_structure_error(&output.port_decls()))?;
+
+        Ok(Body::Block(
+            ::std::iter::once(Body::Stmt(Statement::block(stmts)))
+                .chain(
+                    assignments.into_iter().map(|(_, var_name, value)| gen_connect(var_name, None, value.name, None)),
+                )
+                .collect(),
+        ))
+    }
+
+    /// Generates FSM state.
+    ///
+    /// Returns `Err` if types of `typ` and `state` are mismatched.
+    fn gen_module_fsm_state(
+        &self, target: String, state: Merkle<lir::Expr>, ctx: &mut Context,
+    ) -> Result<Body, lir::ModuleError> {
+        let mut stmts = Vec::new();
+        let exprs = self.gen_expr(&state, ctx, &mut HashMap::new(), &mut stmts)?;
+
+        let assignments = match_value_typ_exprs(
+            join_options("_", [ctx.get_prefix(), Some(target)]),
+            flatten_typ(state.port_decls()),
+            exprs,
+        )
+        .ok_or_else(|| ctx.expr_structure_error(&state.port_decls()))?;
+
+        Ok(Body::Block(
+            ::std::iter::once(Body::Stmt(Statement::block(stmts)))
+                .chain(
+                    assignments
+                        .into_iter()
+                        .map(|(_, var_name, value)| gen_connect(format!("{}.next", var_name), None, value.name, None)),
+                )
+                .collect(),
+        ))
+    }
+
+    /// Generates operations for Expr, which are appended to `stmts`. Returns the values of the
+    /// fields of the expr.
+    ///
+    /// Values are cached, so each expr is generated only once.
+    fn gen_expr(
+        &self, expr: &lir::Expr, ctx: &mut Context, cache: &mut HashMap<lir::Expr, CompositeExpr<Value>>,
+        stmts: &mut Vec<Statement>,
+    ) -> Result<CompositeExpr<Value>, lir::ModuleError> {
+        if let Some(values) = cache.get(expr) {
+            return Ok(values.clone());
+        }
+
+        let values = match expr {
+            lir::Expr::X { .. } | lir::Expr::Constant { .. } => {
+                let literal = gen_expr_literal(expr, ctx)?;
+                literal.map(|value| gen_constant(stmts, ctx, value))
+            }
+            lir::Expr::Repeat { inner, count } => {
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                values_for_inner.map(|value| match count {
+                    1 => value,
+                    _ if value.width() == 0 || *count == 0 => gen_uint(stmts, ctx, 0, 0),
+                    _ => {
+                        let width = value.width() * count;
+                        gen_op(stmts, ctx, Operation::Replicate { input: value, count: *count }, width)
+                    }
+                })
+            }
+            // Zero-width fields are not declared.
+            lir::Expr::Input { name, .. } => {
+                CompositeExpr::from_typ(expr.port_decls(), join_options("_", [ctx.get_prefix(), name.clone()]).unwrap())
+                    .map(
+                        |(ident, shape)| {
+                            if shape.width() == 0 {
+                                gen_uint(stmts, ctx, 0, 0)
+                            } else {
+                                Value::int(ident, shape.width())
+                            }
+                        },
+                    )
+            }
+            lir::Expr::Member { inner, index } => match self.gen_expr(&inner.into_expr(), ctx, cache, stmts)? {
+                CompositeExpr::Struct(inner) => inner[*index].clone(),
+                CompositeExpr::Bits(_) => return Err(ctx.expr_structure_error(&inner.into_expr().port_decls())),
+            },
+            lir::Expr::Struct { inner } => CompositeExpr::Struct(
+                inner
+                    .iter()
+                    .map(|(_, inner)| self.gen_expr(&inner.into_expr(), ctx, cache, stmts))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            lir::Expr::Resize { inner, .. } => {
+                let typ_inner = inner.into_expr().port_decls();
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let shapes = CompositeExpr::from(typ_inner.clone()).try_zip(
+                    CompositeExpr::from(expr.port_decls()),
+                    &typ_inner,
+                    ctx,
+                )?;
+                values_for_inner
+                    .try_zip(shapes, &typ_inner, ctx)?
+                    .map(|(value, (_, (_, to)))| gen_resize(stmts, ctx, value, to.width()))
+            }
+            lir::Expr::LeftShift { inner, rhs } => self.gen_expr_binary_op(
+                lir::BinaryOp::ShiftLeft,
+                &inner.into_expr(),
+                &rhs.into_expr(),
+                ctx,
+                cache,
+                stmts,
+            )?,
+            lir::Expr::RightShift { inner, rhs } => self.gen_expr_binary_op(
+                lir::BinaryOp::ShiftRight,
+                &inner.into_expr(),
+                &rhs.into_expr(),
+                ctx,
+                cache,
+                stmts,
+            )?,
+            lir::Expr::Not { inner } => {
+                let value = self
+                    .gen_expr(&inner.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&inner.into_expr().port_decls(), ctx)?;
+                CompositeExpr::Bits(gen_not(stmts, ctx, value))
+            }
+            lir::Expr::BinaryOp { op, lhs, rhs } => {
+                self.gen_expr_binary_op(*op, &lhs.into_expr(), &rhs.into_expr(), ctx, cache, stmts)?
+            }
+            lir::Expr::Fold { inner, typ_elt, func, init, acc, inner_slice } => {
+                let values_for_init = self.gen_expr(&init.into_expr(), ctx, cache, stmts)?;
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let loop_count = if typ_elt.width() == 0 { 0 } else { inner.into_expr().width() / typ_elt.width() };
+                let mut values_for_acc = values_for_init;
+
+                // The loop is unrolled. Each iteration binds the accumulator and the element to the
+                // inputs of `func`.
+                for i in 0..loop_count {
+                    let fold_body_input_prefix = ctx.alloc_temp_id();
+
+                    let mut ctx = Context::new();
+                    ctx.enter_scope(fold_body_input_prefix);
+                    let mut cache = HashMap::new();
+
+                    let idents_for_acc = self.gen_expr(&acc.into_expr(), &mut ctx, &mut cache, stmts)?;
+                    let idents_for_inner_slice =
+                        self.gen_expr(&inner_slice.into_expr(), &mut ctx, &mut cache, stmts)?;
+
+                    let values_for_elt = element_values(values_for_inner.clone(), i, typ_elt, &mut ctx, stmts)?;
+                    bind_values(idents_for_acc, values_for_acc, &acc.into_expr().port_decls(), &ctx, stmts)?;
+                    bind_values(idents_for_inner_slice, values_for_elt, typ_elt, &ctx, stmts)?;
+
+                    values_for_acc = self.gen_expr(&func.into_expr(), &mut ctx, &mut cache, stmts)?;
+                }
+
+                values_for_acc
+            }
+            lir::Expr::TreeFold { inner, op, lhs, rhs, .. } => {
+                let typ_elt = lhs.into_expr().port_decls();
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let num_elts = if typ_elt.width() == 0 { 0 } else { inner.into_expr().width() / typ_elt.width() };
+                let mut values_for_elts = (0..num_elts)
+                    .map(|i| element_values(values_for_inner.clone(), i, &typ_elt, ctx, stmts))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Levels of the tree are unrolled. An element without its pair is carried to the
+                // next level.
+                while values_for_elts.len() > 1 {
+                    let mut values_for_level = Vec::new();
+
+                    for pair in values_for_elts.chunks(2) {
+                        let (values_for_lhs, values_for_rhs) = match pair {
+                            [values_for_lhs, values_for_rhs] => (values_for_lhs.clone(), values_for_rhs.clone()),
+                            _ => {
+                                values_for_level.push(pair[0].clone());
+                                continue;
+                            }
+                        };
+
+                        let tree_fold_prefix = ctx.alloc_temp_id();
+
+                        let mut ctx = Context::new();
+                        ctx.enter_scope(tree_fold_prefix);
+                        let mut cache = HashMap::new();
+
+                        let idents_for_lhs = self.gen_expr(&lhs.into_expr(), &mut ctx, &mut cache, stmts)?;
+                        let idents_for_rhs = self.gen_expr(&rhs.into_expr(), &mut ctx, &mut cache, stmts)?;
+
+                        bind_values(idents_for_lhs, values_for_lhs, &typ_elt, &ctx, stmts)?;
+                        bind_values(idents_for_rhs, values_for_rhs, &typ_elt, &ctx, stmts)?;
+
+                        values_for_level.push(self.gen_expr(&op.into_expr(), &mut ctx, &mut cache, stmts)?);
+                    }
+
+                    values_for_elts = values_for_level;
+                }
+
+                match values_for_elts.pop() {
+                    Some(values) => values,
+                    None => CompositeExpr::from(typ_elt).map(|(_, shape)| gen_uint(stmts, ctx, 0, shape.width())),
+                }
+            }
+            lir::Expr::Map { inner, typ_elt, func } => {
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let loop_count = if typ_elt.width() == 0 { 0 } else { inner.into_expr().width() / typ_elt.width() };
+                let mut values_for_output = Vec::new();
+
+                for i in 0..loop_count {
+                    let loop_body_input_prefix = ctx.alloc_temp_id();
+
+                    let idents = CompositeExpr::from_typ(typ_elt.clone(), loop_body_input_prefix.clone())
+                        .map(|(ident, shape)| Value::int(ident, shape.width()));
+                    let values_for_elt = element_values(values_for_inner.clone(), i, typ_elt, ctx, stmts)?;
+                    bind_values(idents, values_for_elt, typ_elt, ctx, stmts)?;
+
+                    let mut ctx = Context::new();
+                    ctx.enter_scope(loop_body_input_prefix);
+
+                    values_for_output.push(self.gen_expr(&func.into_expr(), &mut ctx, &mut HashMap::new(), stmts)?);
+                }
+
+                concat_values(values_for_output, &func.into_expr().port_decls(), ctx, stmts)?
+            }
+            lir::Expr::Get { inner, typ_elt, index } | lir::Expr::GetVarArray { inner, typ_elt, index } => {
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let value_for_index = self
+                    .gen_expr(&index.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&index.into_expr().port_decls(), ctx)?;
+
+                range_indexing_values(values_for_inner, value_for_index, 1, typ_elt, ctx, stmts)?
+            }
+            lir::Expr::Clip { inner, typ_elt, from, size } => {
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let value_for_from = self
+                    .gen_expr(&from.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&from.into_expr().port_decls(), ctx)?;
+
+                range_indexing_values(values_for_inner, value_for_from, *size, typ_elt, ctx, stmts)?
+            }
+            lir::Expr::Append { lhs, rhs, .. } => {
+                let values_for_lhs = self.gen_expr(&lhs.into_expr(), ctx, cache, stmts)?;
+                let values_for_rhs = self.gen_expr(&rhs.into_expr(), ctx, cache, stmts)?;
+
+                values_for_lhs
+                    .try_zip(values_for_rhs, &lhs.into_expr().port_decls(), ctx)?
+                    .map(|(lhs, rhs)| gen_cat(stmts, ctx, vec![lhs, rhs]))
+            }
+            lir::Expr::Zip { inner, .. } => CompositeExpr::Struct(
+                inner
+                    .iter()
+                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache, stmts))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            lir::Expr::Concat { inner, .. } => self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?,
+            lir::Expr::Chunk { inner, .. } => self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?,
+            lir::Expr::Repr { inner } => self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?,
+            lir::Expr::Sum { inner, width_elt } => {
+                let value_for_inner = self
+                    .gen_expr(&inner.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&inner.into_expr().port_decls(), ctx)?;
+
+                let mut value_for_acc = gen_uint(stmts, ctx, 0, *width_elt);
+
+                if *width_elt > 0 {
+                    for i in 0..(inner.into_expr().width() / width_elt) {
+                        let value_for_elt = gen_slice(stmts, ctx, value_for_inner.clone(), i * width_elt, *width_elt);
+                        value_for_acc = gen_comb(stmts, ctx, CombOp::Add, value_for_acc, value_for_elt);
+                    }
+                }
+
+                CompositeExpr::Bits(value_for_acc)
+            }
+            lir::Expr::Cond { cond, lhs, rhs } => {
+                let value_for_cond = self
+                    .gen_expr(&cond.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&cond.into_expr().port_decls(), ctx)?;
+                let values_for_lhs = self.gen_expr(&lhs.into_expr(), ctx, cache, stmts)?;
+                let values_for_rhs = self.gen_expr(&rhs.into_expr(), ctx, cache, stmts)?;
+
+                values_for_lhs
+                    .try_zip(values_for_rhs, &lhs.into_expr().port_decls(), ctx)?
+                    .map(|(lhs, rhs)| gen_mux(stmts, ctx, value_for_cond.clone(), lhs, rhs))
+            }
+            lir::Expr::Set { inner, index, elt } | lir::Expr::SetVarArray { inner, index, elt } => {
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let value_for_index = self
+                    .gen_expr(&index.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&index.into_expr().port_decls(), ctx)?;
+                let values_for_elt = self.gen_expr(&elt.into_expr(), ctx, cache, stmts)?;
+
+                set_range_values(
+                    values_for_inner,
+                    inner.into_expr().width(),
+                    value_for_index,
+                    values_for_elt,
+                    1,
+                    &elt.into_expr().port_decls(),
+                    ctx,
+                    stmts,
+                )?
+            }
+            lir::Expr::SetRange { inner, typ_elt, index, elts } => {
+                let values_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let value_for_index = self
+                    .gen_expr(&index.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&index.into_expr().port_decls(), ctx)?;
+                let values_for_elts = self.gen_expr(&elts.into_expr(), ctx, cache, stmts)?;
+
+                set_range_values(
+                    values_for_inner,
+                    inner.into_expr().width(),
+                    value_for_index,
+                    values_for_elts,
+                    if typ_elt.width() == 0 { 0 } else { elts.into_expr().width() / typ_elt.width() },
+                    typ_elt,
+                    ctx,
+                    stmts,
+                )?
+            }
+            lir::Expr::Case { case_expr, case_items, default } => {
+                let typ = expr.port_decls();
+                let typ_case_expr = case_expr.into_expr().port_decls();
+
+                let value_for_case_expr =
+                    self.gen_expr(&case_expr.into_expr(), ctx, cache, stmts)?.try_into_expr(&typ_case_expr, ctx)?;
+
+                // Case items are prioritized in order, so the mux chain is built from the last one.
+                let mut values_for_output = match default {
+                    Some(default) => self.gen_expr(&default.into_expr(), ctx, cache, stmts)?,
+                    None => CompositeExpr::from(typ.clone()).map(|(_, shape)| gen_uint(stmts, ctx, 0, shape.width())),
+                };
+
+                for (cond, item) in case_items.iter().rev() {
+                    let value_for_cond =
+                        self.gen_expr(&cond.into_expr(), ctx, cache, stmts)?.try_into_expr(&typ_case_expr, ctx)?;
+                    let values_for_item = self.gen_expr(&item.into_expr(), ctx, cache, stmts)?;
+
+                    let value_for_cond =
+                        gen_icmp(stmts, ctx, ICmpPredicate::Eq, value_for_case_expr.clone(), value_for_cond);
+                    values_for_output = values_for_item
+                        .try_zip(values_for_output, &typ, ctx)?
+                        .map(|(item, acc)| gen_mux(stmts, ctx, value_for_cond.clone(), item, acc));
+                }
+
+                values_for_output
+            }
+            // Signedness is not modeled, so `$signed` is not supported either.
+            lir::Expr::Call { func_name, .. } => {
+                return Err(lir::ModuleError::Unsupported {
+                    module: ctx.scope_name(),
+                    what: format!("function call `{}`", func_name),
+                })
+            }
+            lir::Expr::ConcatArray { inner, elt_typ } => {
+                let values = inner
+                    .iter()
+                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache, stmts))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                concat_values(values, elt_typ, ctx, stmts)?
+            }
+        };
+
+        cache.insert(expr.clone(), values.clone());
+
+        Ok(values)
+    }
+
+    /// Generates binary operation.
+    ///
+    /// # Note
+    ///
+    /// Operands of `comb` operations are of the same width as the result, so they are zero-extended
+    /// or truncated to the widths of the operations in ShakeFlow.
+    fn gen_expr_binary_op(
+        &self, op: lir::BinaryOp, lhs: &lir::Expr, rhs: &lir::Expr, ctx: &mut Context,
+        cache: &mut HashMap<lir::Expr, CompositeExpr<Value>>, stmts: &mut Vec<Statement>,
+    ) -> Result<CompositeExpr<Value>, lir::ModuleError> {
+        let value_for_lhs = self.gen_expr(lhs, ctx, cache, stmts)?.try_into_expr(&lhs.port_decls(), ctx)?;
+        let value_for_rhs = self.gen_expr(rhs, ctx, cache, stmts)?.try_into_expr(&rhs.port_decls(), ctx)?;
+
+        let (width_lhs, width_rhs) = (lhs.width(), rhs.width());
+        let mut gen_arith = |op: CombOp, width: usize, width_output: usize| {
+            let lhs = gen_resize(stmts, ctx, value_for_lhs.clone(), width);
+            let rhs = gen_resize(stmts, ctx, value_for_rhs.clone(), width);
+            let output = gen_comb(stmts, ctx, op, lhs, rhs);
+            gen_resize(stmts, ctx, output, width_output)
+        };
+
+        let value = match op {
+            lir::BinaryOp::Add => gen_arith(CombOp::Add, width_lhs + 1, width_lhs + 1),
+            lir::BinaryOp::Sub => gen_arith(CombOp::Sub, width_lhs, width_lhs),
+            lir::BinaryOp::Mul => gen_arith(CombOp::Mul, width_lhs + width_rhs, width_lhs + width_rhs),
+            lir::BinaryOp::Div => gen_arith(CombOp::DivU, width_lhs.max(width_rhs), width_lhs),
+            lir::BinaryOp::Mod => gen_arith(CombOp::ModU, width_lhs.max(width_rhs), width_rhs),
+            lir::BinaryOp::And => gen_comb(stmts, ctx, CombOp::And, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::Or => gen_comb(stmts, ctx, CombOp::Or, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::Xor => gen_comb(stmts, ctx, CombOp::Xor, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::Eq => {
+                let value = gen_comb(stmts, ctx, CombOp::Xor, value_for_lhs, value_for_rhs);
+                gen_not(stmts, ctx, value)
+            }
+            lir::BinaryOp::EqArithmetic => gen_icmp(stmts, ctx, ICmpPredicate::Eq, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::Less => gen_icmp(stmts, ctx, ICmpPredicate::Ult, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::Greater => gen_icmp(stmts, ctx, ICmpPredicate::Ugt, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::LessEq => gen_icmp(stmts, ctx, ICmpPredicate::Ule, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::GreaterEq => gen_icmp(stmts, ctx, ICmpPredicate::Uge, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::ShiftLeft => gen_shift(stmts, ctx, CombOp::Shl, value_for_lhs, value_for_rhs),
+            lir::BinaryOp::ShiftRight => gen_shift(stmts, ctx, CombOp::ShrU, value_for_lhs, value_for_rhs),
+        };
+
+        Ok(CompositeExpr::Bits(value))
+    }
+}
+
+/// Returns the `index`-th element of `values`, whose elements are of type `typ_elt`.
+fn element_values(
+    values: CompositeExpr<Value>, index: usize, typ_elt: &lir::PortDecls, ctx: &mut Context, stmts: &mut Vec<Statement>,
+) -> Result<CompositeExpr<Value>, lir::ModuleError> {
+    let values = values.try_zip(typ_elt.clone().into(), typ_elt, ctx)?;
+    Ok(values.map(|(value, (_, shape))| gen_slice(stmts, ctx, value, index * shape.width(), shape.width())))
+}
+
+/// Returns `count` elements of `values` from `index`, whose elements are of type `typ_elt`.
+fn range_indexing_values(
+    values: CompositeExpr<Value>, index: Value, count: usize, typ_elt: &lir::PortDecls, ctx: &mut Context,
+    stmts: &mut Vec<Statement>,
+) -> Result<CompositeExpr<Value>, lir::ModuleError> {
+    let values = values.try_zip(typ_elt.clone().into(), typ_elt, ctx)?;
+    Ok(values.map(|(value, (_, shape))| {
+        let width = shape.width();
+        if width == 0 {
+            return gen_uint(stmts, ctx, 0, 0);
+        }
+
+        // The shift amount is wide enough not to overflow.
+        let width_amount = index.width() + (usize::BITS - width.leading_zeros()) as usize;
+        let amount = gen_resize(stmts, ctx, index.clone(), width_amount);
+        let amount = if width == 1 {
+            amount
+        } else {
+            let width = gen_uint(stmts, ctx, width, width_amount);
+            gen_comb(stmts, ctx, CombOp::Mul, amount, width)
+        };
+
+        let value = gen_shift(stmts, ctx, CombOp::ShrU, value, amount);
+        gen_slice(stmts, ctx, value, 0, width * count)
+    }))
+}
+
+/// Returns `values` whose `count` elements from `index` are replaced by `elts`. The elements are of
+/// type `typ_elt`, and `width` is the width of `values`.
+#[allow(clippy::too_many_arguments)]
+fn set_range_values(
+    values: CompositeExpr<Value>, width: usize, index: Value, elts: CompositeExpr<Value>, count: usize,
+    typ_elt: &lir::PortDecls, ctx: &mut Context, stmts: &mut Vec<Statement>,
+) -> Result<CompositeExpr<Value>, lir::ModuleError> {
+    if typ_elt.width() == 0 {
+        return Ok(values);
+    }
+
+    let num_elts = width / typ_elt.width();
+
+    // Conditions that the index is equal to each position. Positions that the index cannot
+    // represent are never selected.
+    let conds = (0..num_elts)
+        .map(|i| {
+            (index.width() >= usize::BITS as usize || i >> index.width() == 0).then(|| {
+                let position = gen_uint(stmts, ctx, i, index.width());
+                gen_icmp(stmts, ctx, ICmpPredicate::Eq, index.clone(), position)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let values = values.try_zip(elts, typ_elt, ctx)?.try_zip(typ_elt.clone().into(), typ_elt, ctx)?;
+    Ok(values.map(|((value, elts), (_, shape))| {
+        let width = shape.width();
+        let values_for_elts = (0..num_elts)
+            .map(|i| {
+                (0..count.min(i + 1)).fold(
+                    gen_slice(stmts, ctx, value.clone(), i * width, width),
+                    |acc, j| match &conds[i - j] {
+                        Some(cond) => {
+                            let elt = gen_slice(stmts, ctx, elts.clone(), j * width, width);
+                            gen_mux(stmts, ctx, cond.clone(), elt, acc)
+                        }
+                        None => acc,
+                    },
+                )
+            })
+            .collect();
+
+        gen_cat(stmts, ctx, values_for_elts)
+    }))
+}
+
+/// Concatenates `values`, whose elements are of type `typ_elt`.
+fn concat_values(
+    values: Vec<CompositeExpr<Value>>, typ_elt: &lir::PortDecls, ctx: &mut Context, stmts: &mut Vec<Statement>,
+) -> Result<CompositeExpr<Value>, lir::ModuleError> {
+    let mut values = values.into_iter();
+
+    let values_for_fields = match values.next() {
+        Some(first) => values.try_fold(first.map(|value| vec![value]), |acc, values| {
+            Ok::<_, lir::ModuleError>(acc.try_zip(values, typ_elt, ctx)?.map(|(mut acc, value)| {
+                acc.push(value);
+                acc
+            }))
+        })?,
+        None => CompositeExpr::from(typ_elt.clone()).map(|_| Vec::new()),
+    };
+
+    Ok(values_for_fields.map(|values| gen_cat(stmts, ctx, values)))
+}
+
+/// Binds `values` to the values `idents`, e.g., inputs of the loop body.
+fn bind_values(
+    idents: CompositeExpr<Value>, values: CompositeExpr<Value>, typ: &lir::PortDecls, ctx: &Context,
+    stmts: &mut Vec<Statement>,
+) -> Result<(), lir::ModuleError> {
+    // Zero-width inputs are constants.
+    for (ident, value) in idents.try_zip(values, typ, ctx)?.iter().filter(|(ident, _)| ident.width() > 0) {
+        stmts.push(Statement::op(ident, Operation::Wire { input: value }));
+    }
+
+    Ok(())
+}
+
+/// Appends the operation to `stmts`. Returns its result of the given width, which is named by a
+/// temporary identifier.
+fn gen_op(stmts: &mut Vec<Statement>, ctx: &mut Context, op: Operation, width: usize) -> Value {
+    let result = Value::int(ctx.alloc_temp_id(), width);
+    stmts.push(Statement::op(result.clone(), op));
+    result
+}
+
+/// Generates constant.
+fn gen_constant(stmts: &mut Vec<Statement>, ctx: &mut Context, value: LogicValues) -> Value {
+    let width = value.len();
+    gen_op(stmts, ctx, Operation::Constant { value }, width)
+}
+
+/// Generates unsigned integer constant of the given width.
+fn gen_uint(stmts: &mut Vec<Statement>, ctx: &mut Context, value: usize, width: usize) -> Value {
+    let value =
+        LogicValues::new(
+            (0..width)
+                .rev()
+                .map(|i| {
+                    if i < usize::BITS as usize && (value >> i) & 1 == 1 {
+                        LogicValue::True
+                    } else {
+                        LogicValue::False
+                    }
+                })
+                .collect(),
+        );
+    gen_constant(stmts, ctx, value)
+}
+
+/// Returns `width` bits of `value` from `lo`.
+fn gen_slice(stmts: &mut Vec<Statement>, ctx: &mut Context, value: Value, lo: usize, width: usize) -> Value {
+    if width == 0 {
+        gen_uint(stmts, ctx, 0, 0)
+    } else if lo == 0 && width == value.width() {
+        value
+    } else {
+        gen_op(stmts, ctx, Operation::Extract { input: value, low: lo, width }, width)
+    }
+}
+
+/// Concatenates `values`, whose first element is placed at the least significant bits.
+fn gen_cat(stmts: &mut Vec<Statement>, ctx: &mut Context, values: Vec<Value>) -> Value {
+    let mut operands = values.into_iter().filter(|value| value.width() > 0).rev().collect::<Vec<_>>();
+
+    match operands.len() {
+        0 => gen_uint(stmts, ctx, 0, 0),
+        1 => operands.pop().unwrap(),
+        _ => {
+            let width = operands.iter().map(Value::width).sum();
+            gen_op(stmts, ctx, Operation::Concat { operands }, width)
+        }
+    }
+}
+
+/// Zero-extends or truncates `value` to width `to`.
+fn gen_resize(stmts: &mut Vec<Statement>, ctx: &mut Context, value: Value, to: usize) -> Value {
+    let from = value.width();
+    match from.cmp(&to) {
+        ::std::cmp::Ordering::Less => {
+            let zeros = gen_uint(stmts, ctx, 0, to - from);
+            gen_cat(stmts, ctx, vec![value, zeros])
+        }
+        ::std::cmp::Ordering::Equal => value,
+        ::std::cmp::Ordering::Greater => gen_slice(stmts, ctx, value, 0, to),
+    }
+}
+
+/// Generates operation of `comb` whose operands and result are of the same width.
+fn gen_comb(stmts: &mut Vec<Statement>, ctx: &mut Context, op: CombOp, lhs: Value, rhs: Value) -> Value {
+    let width = lhs.width();
+    if width == 0 {
+        return gen_uint(stmts, ctx, 0, 0);
+    }
+
+    gen_op(stmts, ctx, Operation::Comb { op, operands: vec![lhs, rhs] }, width)
+}
+
+/// Generates bitwise complement.
+fn gen_not(stmts: &mut Vec<Statement>, ctx: &mut Context, value: Value) -> Value {
+    let ones = gen_constant(stmts, ctx, LogicValues::new(vec![LogicValue::True; value.width()]));
+    gen_comb(stmts, ctx, CombOp::Xor, value, ones)
+}
+
+/// Generates comparison. Zero-width operands are equal.
+fn gen_icmp(stmts: &mut Vec<Statement>, ctx: &mut Context, predicate: ICmpPredicate, lhs: Value, rhs: Value) -> Value {
+    if lhs.width() == 0 {
+        let value = matches!(predicate, ICmpPredicate::Eq | ICmpPredicate::Ule | ICmpPredicate::Uge);
+        return gen_uint(stmts, ctx, value.into(), 1);
+    }
+
+    gen_op(stmts, ctx, Operation::ICmp { predicate, lhs, rhs }, 1)
+}
+
+/// Generates multiplexer.
+fn gen_mux(stmts: &mut Vec<Statement>, ctx: &mut Context, cond: Value, tval: Value, fval: Value) -> Value {
+    let width = tval.width();
+    if width == 0 {
+        return gen_uint(stmts, ctx, 0, 0);
+    }
+
+    gen_op(stmts, ctx, Operation::Mux { cond, tval, fval }, width)
+}
+
+/// Shifts `value` by `amount`, where `op` is `Shl` or `ShrU`.
+///
+/// The amount of `comb` shifts is as wide as the value, so upper bits of the amount are checked
+/// separately.
+fn gen_shift(stmts: &mut Vec<Statement>, ctx: &mut Context, op: CombOp, value: Value, amount: Value) -> Value {
+    let (width, width_amount) = (value.width(), amount.width());
+    if width == 0 || width_amount == 0 {
+        return value;
+    }
+
+    if width_amount <= width {
+        let amount = gen_resize(stmts, ctx, amount, width);
+        return gen_comb(stmts, ctx, op, value, amount);
+    }
+
+    let amount_hi = gen_slice(stmts, ctx, amount.clone(), width, width_amount - width);
+    let zero_hi = gen_uint(stmts, ctx, 0, width_amount - width);
+    let overflow = gen_icmp(stmts, ctx, ICmpPredicate::Ne, amount_hi, zero_hi);
+
+    let amount_lo = gen_slice(stmts, ctx, amount, 0, width);
+    let shifted = gen_comb(stmts, ctx, op, value, amount_lo);
+    let zero = gen_uint(stmts, ctx, 0, width);
+    gen_mux(stmts, ctx, overflow, zero, shifted)
+}
+
+/// Generates connection, where ranges are the offset and width of the sliced signals.
+fn gen_connect(
+    lvalue: String, lvalue_range: Option<(usize, usize)>, rvalue: String, rvalue_range: Option<(usize, usize)>,
+) -> Body {
+    Body::Connect { lvalue, lvalue_range, rvalue, rvalue_range }
+}
+
+/// Lowers wires and connections into operations. Each wire or output port is bound to the value
+/// connected to it, or the concatenation of the values connected to its slices. Unconnected bits
+/// are zero.
+fn lower_connects(ports: &[Port], body: Body) -> Statement {
+    let mut widths = HashMap::new();
+    let mut sinks = Vec::new();
+    for port in ports {
+        if let Type::Int(width) = port.tpe {
+            let _ = widths.insert(port.name.clone(), width);
+            if port.direction == Direction::Output {
+                sinks.push((port.name.clone(), width));
+            }
+        }
+    }
+    collect_values(&body, &mut widths, &mut sinks);
+
+    let mut slices = HashMap::new();
+    let mut connected = HashSet::new();
+    let mut stmts = vec![lower_body(body, &widths, &mut slices, &mut connected)];
+
+    for (name, width) in sinks {
+        match slices.remove(&name) {
+            Some(mut slices) => {
+                slices.sort_by_key(|(lo, _)| *lo);
+
+                let mut operands = Vec::new();
+                let mut offset = 0;
+                for (lo, value) in slices {
+                    if lo > offset {
+                        operands.push(gen_zero(&mut stmts, format!("{}.{}", name, offset), lo - offset));
+                    }
+                    offset = lo + value.width();
+                    operands.push(value);
+                }
+                if width > offset {
+                    operands.push(gen_zero(&mut stmts, format!("{}.{}", name, offset), width - offset));
+                }
+
+                let op = if operands.len() == 1 {
+                    Operation::Wire { input: operands.pop().unwrap() }
+                } else {
+                    Operation::Concat { operands: operands.into_iter().rev().collect() }
+                };
+                stmts.push(Statement::op(Value::int(name, width), op));
+            }
+            None if !connected.contains(&name) => {
+                let _ = gen_zero(&mut stmts, name, width);
+            }
+            None => {}
+        }
+    }
+
+    Statement::block(stmts)
+}
+
+/// Collects widths of the values defined in the body, and names and widths of the wires.
+fn collect_values(body: &Body, widths: &mut HashMap<String, usize>, wires: &mut Vec<(String, usize)>) {
+    match body {
+        Body::Stmt(stmt) => collect_results(stmt, widths),
+        Body::Wire { name, width } => {
+            let _ = widths.insert(name.clone(), *width);
+            wires.push((name.clone(), *width));
+        }
+        Body::Connect { .. } => {}
+        Body::Block(bodies) => bodies.iter().for_each(|body| collect_values(body, widths, wires)),
+        Body::Commented { body, .. } => collect_values(body, widths, wires),
+    }
+}
+
+/// Collects widths of the results of the operations in the statement.
+fn collect_results(stmt: &Statement, widths: &mut HashMap<String, usize>) {
+    match stmt {
+        Statement::Operation { results, .. } => {
+            for result in results {
+                let _ = widths.insert(result.name.clone(), result.width());
+            }
+        }
+        Statement::Block { stmts } => stmts.iter().for_each(|stmt| collect_results(stmt, widths)),
+        Statement::Commented { stmt, .. } => collect_results(stmt, widths),
+    }
+}
+
+/// Lowers the body into statements. Connections to whole signals are bound in place, and
+/// connections to slices are recorded in `slices` by the offsets of the slices. Names of the other
+/// connected signals are recorded in `connected`.
+fn lower_body(
+    body: Body, widths: &HashMap<String, usize>, slices: &mut HashMap<String, Vec<(usize, Value)>>,
+    connected: &mut HashSet<String>,
+) -> Statement {
+    match body {
+        Body::Stmt(stmt) => stmt,
+        Body::Wire { .. } => Statement::block(Vec::new()),
+        Body::Connect { lvalue_range: Some((_, 0)), .. } => Statement::block(Vec::new()),
+        Body::Connect { lvalue, lvalue_range, rvalue, rvalue_range } => {
+            let width = widths.get(&rvalue).or_else(|| widths.get(&lvalue)).copied().unwrap_or_default();
+            let input = Value::int(rvalue, width);
+
+            match lvalue_range {
+                Some((lo, width)) => {
+                    let (stmt, value) = match rvalue_range {
+                        Some((rvalue_lo, _)) => {
+                            let value = Value::int(format!("{}.{}", lvalue, lo), width);
+                            let op = Operation::Extract { input, low: rvalue_lo, width };
+                            (Statement::op(value.clone(), op), value)
+                        }
+                        None => (Statement::block(Vec::new()), input),
+                    };
+                    slices.entry(lvalue).or_default().push((lo, value));
+                    stmt
+                }
+                None => {
+                    let _ = connected.insert(lvalue.clone());
+                    match rvalue_range {
+                        Some((lo, width)) => {
+                            Statement::op(Value::int(lvalue, width), Operation::Extract { input, low: lo, width })
+                        }
+                        None => Statement::op(Value::int(lvalue, width), Operation::Wire { input }),
+                    }
+                }
+            }
+        }
+        Body::Block(bodies) => {
+            Statement::block(bodies.into_iter().map(|body| lower_body(body, widths, slices, connected)).collect())
+        }
+        Body::Commented { comment, body } => {
+            Statement::Commented { comment, stmt: Box::new(lower_body(*body, widths, slices, connected)) }
+        }
+    }
+}
+
+/// Appends zero constant named `name` to `stmts`.
+fn gen_zero(stmts: &mut Vec<Statement>, name: String, width: usize) -> Value {
+    let value = Value::int(name, width);
+    let op = Operation::Constant { value: LogicValues::new(vec![LogicValue::False; width]) };
+    stmts.push(Statement::op(value.clone(), op));
+    value
+}
+
+/// Annotates the body with the source location.
+fn gen_commented(source: Option<&Location<'_>>, body: Body) -> Body {
+    match source {
+        Some(source) => Body::Commented { comment: gen_source_annotation(source), body: Box::new(body) },
+        None => body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    /// Returns an FSM accumulating its input.
+    fn accumulate() -> Module<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>> {
+        hir::Fsm::<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>, Bits<U<4>>, _>::new(
+            "accumulate",
+            |fwd, bwd, state| (state, bwd, (state + fwd).resize()),
+            0.into(),
+        )
+        .into()
+    }
+
+    /// Returns a composite module of two accumulators.
+    fn sum() -> Module<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>> {
+        composite::<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>, _>("sum", Some("in"), Some("out"), |input, k| {
+            input.comb_inline(k, accumulate()).comb_inline(k, accumulate())
+        })
+        .build()
+    }
+
+    #[test]
+    fn composite_module() {
+        let files = generate(package(sum()), |package, dir| package.gen_circt(dir)).unwrap();
+        let mlir = files["sum_inner.mlir"]
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("// src = "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            mlir,
+            r#"hw.module @sum(in %clk : !seq.clock, in %rst : i1, in %in : i4, out out : i4) {
+  %accumulate_0_in = hw.wire %in : i4
+  %accumulate_1_in = hw.wire %accumulate_0_out : i4
+  %out = hw.wire %accumulate_1_out : i4
+  %accumulate_0_t0 = hw.constant 0 : i4
+  %accumulate_0_st = seq.firreg %accumulate_0_st.next clock %clk reset sync %rst, %accumulate_0_t0 : i4
+  %accumulate_0_out = hw.wire %accumulate_0_st : i4
+  %accumulate_0_t1 = hw.constant 0 : i0
+  %accumulate_0_t2 = hw.constant 0 : i1
+  %accumulate_0_t3 = comb.concat %accumulate_0_t2, %accumulate_0_st : i1, i4
+  %accumulate_0_t4 = hw.constant 0 : i1
+  %accumulate_0_t5 = comb.concat %accumulate_0_t4, %accumulate_0_in : i1, i4
+  %accumulate_0_t6 = comb.add %accumulate_0_t3, %accumulate_0_t5 : i5
+  %accumulate_0_t7 = hw.constant 0 : i3
+  %accumulate_0_t8 = hw.constant 0 : i1
+  %accumulate_0_t9 = comb.concat %accumulate_0_t8, %accumulate_0_t7 : i1, i3
+  %accumulate_0_t10 = hw.constant 0 : i1
+  %accumulate_0_t11 = comb.concat %accumulate_0_t10, %accumulate_0_t9 : i1, i4
+  %accumulate_0_t12 = comb.shru %accumulate_0_t6, %accumulate_0_t11 : i5
+  %accumulate_0_t13 = comb.extract %accumulate_0_t12 from 0 : (i5) -> i4
+  %accumulate_0_st.next = hw.wire %accumulate_0_t13 : i4
+  %accumulate_1_t0 = hw.constant 0 : i4
+  %accumulate_1_st = seq.firreg %accumulate_1_st.next clock %clk reset sync %rst, %accumulate_1_t0 : i4
+  %accumulate_1_out = hw.wire %accumulate_1_st : i4
+  %accumulate_1_t1 = hw.constant 0 : i0
+  %accumulate_1_t2 = hw.constant 0 : i1
+  %accumulate_1_t3 = comb.concat %accumulate_1_t2, %accumulate_1_st : i1, i4
+  %accumulate_1_t4 = hw.constant 0 : i1
+  %accumulate_1_t5 = comb.concat %accumulate_1_t4, %accumulate_1_in : i1, i4
+  %accumulate_1_t6 = comb.add %accumulate_1_t3, %accumulate_1_t5 : i5
+  %accumulate_1_t7 = hw.constant 0 : i3
+  %accumulate_1_t8 = hw.constant 0 : i1
+  %accumulate_1_t9 = comb.concat %accumulate_1_t8, %accumulate_1_t7 : i1, i3
+  %accumulate_1_t10 = hw.constant 0 : i1
+  %accumulate_1_t11 = comb.concat %accumulate_1_t10, %accumulate_1_t9 : i1, i4
+  %accumulate_1_t12 = comb.shru %accumulate_1_t6, %accumulate_1_t11 : i5
+  %accumulate_1_t13 = comb.extract %accumulate_1_t12 from 0 : (i5) -> i4
+  %accumulate_1_st.next = hw.wire %accumulate_1_t13 : i4
+  hw.output %out : i4
+}"#
+        );
+    }
+}
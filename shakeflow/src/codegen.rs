@@ -2,8 +2,10 @@
 
 use std::collections::VecDeque;
 use std::ops::*;
+use std::panic::Location;
 
 use itertools::*;
+use linked_hash_map::LinkedHashMap;
 
 use crate::*;
 
@@ -34,7 +36,7 @@ pub trait Codegen: Default {
     type Body;
 
     /// Generates target code for port declarations.
-    fn gen_port_decls(&self, module: &lir::Module) -> Result<Self::Ports, lir::ModuleError>;
+    fn gen_port_decls(&self, module: &lir::Module, ctx: &Context) -> Result<Self::Ports, lir::ModuleError>;
 
     /// Generates target code for composite module.
     fn gen_module_composite(
@@ -53,22 +55,32 @@ pub trait Codegen: Default {
     ) -> Result<Self::Body, lir::ModuleError>;
 }
 
-/// Generates target code for module with given compiler.
-pub fn gen_module<C: Codegen>(name: String, module: &lir::Module) -> Result<Module<C>, lir::ModuleError> {
+/// Generates target code for module with given compiler and reset style.
+///
+/// Composite modules, FSMs and module instantiations can be top-level modules. Virtual modules
+/// cannot, since they only refer to registered modules of the enclosing composite module.
+pub fn gen_module<C: Codegen>(
+    name: String, module: &lir::Module, reset_style: lir::ResetStyle,
+) -> Result<Module<C>, lir::ModuleError> {
     let compiler = C::default();
+    let mut ctx = Context::with_reset_style(reset_style);
 
-    match &*module.inner {
+    let body = match &*module.inner {
         lir::ModuleInner::Composite(_, composite_module) => {
-            let module = Module::new(
-                name,
-                compiler.gen_port_decls(module)?,
-                compiler.gen_module_composite(composite_module, &mut Context::new())?,
-            );
-
-            Ok(module)
+            compiler.gen_module_composite(composite_module, &mut ctx)?
         }
-        _ => unimplemented!("not supported module type"),
-    }
+        lir::ModuleInner::Fsm(fsm) => compiler.gen_module_fsm(fsm, &mut ctx)?,
+        lir::ModuleInner::ModuleInst(module_inst) => compiler.gen_module_inst(module_inst, &mut ctx)?,
+        lir::ModuleInner::VirtualModule(_) => {
+            return Err(lir::ModuleError::Misc(format!(
+                "virtual module {} cannot be a top-level module",
+                module.get_module_name()
+            )))
+        }
+    };
+
+    let ports = compiler.gen_port_decls(module, &ctx)?;
+    Ok(Module::new(name, ports, body))
 }
 
 /// Composite of expressions.
@@ -82,11 +94,11 @@ pub enum CompositeExpr<V: Clone> {
 }
 
 impl<V: Clone + std::fmt::Debug> CompositeExpr<V> {
-    /// Converts into expression.
-    pub fn into_expr(self) -> V {
+    /// Converts into expression. Returns `None` if it is a struct of expressions.
+    pub fn into_expr(self) -> Option<V> {
         match self {
-            Self::Struct(_) => panic!("Cannot convert struct of expressions into expression."),
-            Self::Bits(expr) => expr,
+            Self::Struct(_) => None,
+            Self::Bits(expr) => Some(expr),
         }
     }
 
@@ -98,21 +110,40 @@ impl<V: Clone + std::fmt::Debug> CompositeExpr<V> {
         CompositeExprMap { inner: self, f: &mut f }.collect()
     }
 
-    /// Zips with other composite expr. Structures of the two compositions should be same.
-    pub fn zip<W: Clone + std::fmt::Debug>(self, other: CompositeExpr<W>) -> CompositeExpr<(V, W)> {
+    /// Zips with other composite expr. Returns `None` if structures of the two compositions are
+    /// different.
+    pub fn zip<W: Clone + std::fmt::Debug>(self, other: CompositeExpr<W>) -> Option<CompositeExpr<(V, W)>> {
         match (self, other) {
-            (CompositeExpr::Struct(exprs_self), CompositeExpr::Struct(exprs_other)) => CompositeExpr::Struct(
-                izip!(exprs_self.into_iter(), exprs_other.into_iter())
-                    .map(|(expr_lhs, expr_rhs)| expr_lhs.zip(expr_rhs))
-                    .collect(),
-            ),
+            (CompositeExpr::Struct(exprs_self), CompositeExpr::Struct(exprs_other)) => {
+                if exprs_self.len() != exprs_other.len() {
+                    return None;
+                }
+                Some(CompositeExpr::Struct(
+                    izip!(exprs_self.into_iter(), exprs_other.into_iter())
+                        .map(|(expr_lhs, expr_rhs)| expr_lhs.zip(expr_rhs))
+                        .collect::<Option<_>>()?,
+                ))
+            }
             (CompositeExpr::Bits(expr_self), CompositeExpr::Bits(expr_other)) => {
-                CompositeExpr::Bits((expr_self, expr_other))
+                Some(CompositeExpr::Bits((expr_self, expr_other)))
             }
-            (CompositeExpr::Struct(exprs_self), CompositeExpr::Bits(expr_other)) => panic!("zip: two compositions CompositeExpr::Struct(\n{:#?})\nand CompositeExpr::Bits(\n{:#?})\nhave different structure", exprs_self, expr_other),
-            (CompositeExpr::Bits(exprs_self), CompositeExpr::Struct(expr_other)) => panic!("zip: two compositions CompositeExpr::Bits(\n{:#?})\nand CompositeExpr::Struct(\n{:#?})\nhave different structure", exprs_self, expr_other),
+            _ => None,
         }
     }
+
+    /// Converts into expression of type `typ`. Returns `Err` located at the inner scope of `ctx` if
+    /// it is a struct of expressions.
+    pub fn try_into_expr(self, typ: &lir::PortDecls, ctx: &Context) -> Result<V, lir::ModuleError> {
+        self.into_expr().ok_or_else(|| ctx.expr_structure_error(typ))
+    }
+
+    /// Zips with other composite expr of type `typ`. Returns `Err` located at the inner scope of
+    /// `ctx` if structures of the two compositions are different.
+    pub fn try_zip<W: Clone + std::fmt::Debug>(
+        self, other: CompositeExpr<W>, typ: &lir::PortDecls, ctx: &Context,
+    ) -> Result<CompositeExpr<(V, W)>, lir::ModuleError> {
+        self.zip(other).ok_or_else(|| ctx.expr_structure_error(typ))
+    }
 }
 
 #[derive(Debug)]
@@ -205,14 +236,40 @@ pub struct Context {
     /// Scopes in the context
     scopes: Vec<Scope>,
 
+    /// Scope of the top-level module, whose names are not prefixed
+    root: Scope,
+
     /// Genvar index
     genvar_id: usize,
+
+    /// Clock domain of the inner module, or `None` for the default clock domain
+    clock_domain: Option<String>,
+
+    /// Reset style of state registers
+    reset_style: lir::ResetStyle,
 }
 
 impl Context {
     /// Creates new context.
     pub fn new() -> Self { Self::default() }
 
+    /// Creates new context with given reset style.
+    pub fn with_reset_style(reset_style: lir::ResetStyle) -> Self { Self { reset_style, ..Self::default() } }
+
+    /// Returns the reset style of state registers.
+    pub fn reset_style(&self) -> lir::ResetStyle { self.reset_style }
+
+    /// Returns the kind of reset of the state registers of the FSM.
+    pub fn reset_kind(&self, fsm: &lir::Fsm) -> lir::ResetKind { fsm.reset.unwrap_or(self.reset_style.kind) }
+
+    /// Returns the name of the inner scope, which is used to locate errors.
+    pub fn scope_name(&self) -> String { self.get_prefix().unwrap_or_else(|| "top-level module".to_string()) }
+
+    /// Returns the error that an expression does not have the structure of its type.
+    pub fn expr_structure_error(&self, typ: &lir::PortDecls) -> lir::ModuleError {
+        lir::ModuleError::ExprStructure { module: self.scope_name(), typ: typ.to_string() }
+    }
+
     /// Enters scope with given scope name.
     pub fn enter_scope(&mut self, scope_name: String) { self.scopes.push(Scope::new(scope_name)); }
 
@@ -228,12 +285,30 @@ impl Context {
         }
     }
 
+    /// Returns the inner scope.
+    fn scope_mut(&mut self) -> &mut Scope { self.scopes.last_mut().unwrap_or(&mut self.root) }
+
+    /// Enters the clock domain if it is given, and returns the clock domain before entering.
+    pub fn enter_clock_domain(&mut self, clock_domain: Option<String>) -> Option<String> {
+        match clock_domain {
+            Some(clock_domain) => self.clock_domain.replace(clock_domain),
+            None => self.clock_domain.clone(),
+        }
+    }
+
+    /// Leaves to the clock domain returned by `enter_clock_domain`.
+    pub fn leave_clock_domain(&mut self, clock_domain: Option<String>) { self.clock_domain = clock_domain; }
+
+    /// Returns the names of the clock and reset signals of the inner clock domain.
+    pub fn clock_signals(&self) -> (String, String) {
+        clock_signals(self.clock_domain.as_deref(), self.reset_style.polarity)
+    }
+
     /// Allocates integer.
     pub fn alloc_int_id(&mut self) -> String {
-        let count = self.scopes.len();
-        assert!(count > 0, "There is no scope in context");
-        let int_id = self.scopes[count - 1].int_id;
-        self.scopes[count - 1].int_id += 1;
+        let scope = self.scope_mut();
+        let int_id = scope.int_id;
+        scope.int_id += 1;
         join_options("_", [self.get_prefix(), Some(format!("i{}", int_id))]).unwrap()
     }
 
@@ -246,16 +321,15 @@ impl Context {
 
     /// Allocates net or reg.
     pub fn alloc_temp_id(&mut self) -> String {
-        let count = self.scopes.len();
-        assert!(count > 0, "There is no scope in context");
-        let temp_id = self.scopes[count - 1].temp_id;
-        self.scopes[count - 1].temp_id += 1;
+        let scope = self.scope_mut();
+        let temp_id = scope.temp_id;
+        scope.temp_id += 1;
         join_options("_", [self.get_prefix(), Some(format!("t{}", temp_id))]).unwrap()
     }
 }
 
 /// Scope.
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct Scope {
     /// Prefix of the scope
     prefix: String,
@@ -272,6 +346,23 @@ impl Scope {
     pub fn new(prefix: String) -> Self { Self { prefix, int_id: 0, temp_id: 0 } }
 }
 
+/// Returns the annotation of the source location, `src = file.rs:line`.
+pub fn gen_source_annotation(source: &Location<'_>) -> String { format!("src = {}:{}", source.file(), source.line()) }
+
+/// Returns the names of the clock and reset signals of the clock domain, which are `clk` and `rst`
+/// for the default clock domain and `{clock_domain}_clk` and `{clock_domain}_rst` otherwise. Names
+/// of active-low reset signals are suffixed with `_n`.
+pub fn clock_signals(clock_domain: Option<&str>, polarity: lir::ResetPolarity) -> (String, String) {
+    let suffix = match polarity {
+        lir::ResetPolarity::ActiveHigh => "",
+        lir::ResetPolarity::ActiveLow => "_n",
+    };
+    match clock_domain {
+        Some(clock_domain) => (format!("{}_clk", clock_domain), format!("{}_rst{}", clock_domain, suffix)),
+        None => ("clk".to_string(), format!("rst{}", suffix)),
+    }
+}
+
 /// Represents port in target language.
 #[derive(Debug, Clone)]
 struct Port {
@@ -367,15 +458,20 @@ impl LogicValues {
     pub fn into_inner(self) -> Vec<LogicValue> { self.0 }
 }
 
-/// Generates bitarrays representing Expr. Panics if it cannot be converted into bitarrays.
+/// Generates bitarrays representing Expr. Returns `Err` if it cannot be converted into bitarrays.
 ///
 /// Returned string contains "0", "1" and "x".
-pub(super) fn gen_expr_literal(expr: &lir::Expr) -> CompositeExpr<LogicValues> {
-    match expr {
+pub(super) fn gen_expr_literal(
+    expr: &lir::Expr, ctx: &Context,
+) -> Result<CompositeExpr<LogicValues>, lir::ModuleError> {
+    Ok(match expr {
         lir::Expr::X { typ } => match typ {
             lir::PortDecls::Bits(shape) => CompositeExpr::Bits(LogicValues(vec![LogicValue::X; shape.width()])),
             lir::PortDecls::Struct(inner) => CompositeExpr::Struct(
-                inner.iter().map(|(_, typ)| gen_expr_literal(&lir::Expr::X { typ: typ.clone() })).collect(),
+                inner
+                    .iter()
+                    .map(|(_, typ)| gen_expr_literal(&lir::Expr::X { typ: typ.clone() }, ctx))
+                    .collect::<Result<_, _>>()?,
             ),
         },
         lir::Expr::Constant { bits, typ } => match typ {
@@ -388,29 +484,34 @@ pub(super) fn gen_expr_literal(expr: &lir::Expr) -> CompositeExpr<LogicValues> {
 
                 for (_, typ) in inner {
                     let width = typ.width();
-                    member_exprs.push(gen_expr_literal(&lir::Expr::Constant {
-                        bits: bits[offset..(offset + width)].to_vec(),
-                        typ: typ.clone(),
-                    }));
+                    member_exprs.push(gen_expr_literal(
+                        &lir::Expr::Constant { bits: bits[offset..(offset + width)].to_vec(), typ: typ.clone() },
+                        ctx,
+                    )?);
                     offset += width;
                 }
 
                 CompositeExpr::Struct(member_exprs)
             }
         },
-        lir::Expr::Struct { inner } => {
-            CompositeExpr::Struct(inner.iter().map(|(_, s)| gen_expr_literal(&s.into_expr())).collect())
-        }
-        lir::Expr::Repeat { inner, count } => gen_expr_literal(&inner.into_expr()).repeat(*count),
+        lir::Expr::Struct { inner } => CompositeExpr::Struct(
+            inner.iter().map(|(_, s)| gen_expr_literal(&s.into_expr(), ctx)).collect::<Result<_, _>>()?,
+        ),
+        lir::Expr::Repeat { inner, count } => gen_expr_literal(&inner.into_expr(), ctx)?.repeat(*count),
         lir::Expr::Member { inner, index } => {
-            let inner = gen_expr_literal(&inner.into_expr());
-            match inner {
-                CompositeExpr::Struct(inner) => inner[*index].clone(),
-                _ => todo!(),
+            let inner = inner.into_expr();
+            match gen_expr_literal(&inner, ctx)? {
+                CompositeExpr::Struct(members) => members[*index].clone(),
+                CompositeExpr::Bits(_) => return Err(ctx.expr_structure_error(&inner.port_decls())),
             }
         }
-        _ => todo!("not yet implemented: {:?}", expr),
-    }
+        _ => {
+            return Err(lir::ModuleError::Unsupported {
+                module: ctx.scope_name(),
+                what: format!("non-constant initial value of type {}", expr.port_decls().to_string()),
+            })
+        }
+    })
 }
 
 /// Checks `values` and `exprs` have same structure and returns its matched elements.
@@ -420,27 +521,33 @@ pub(super) fn gen_expr_literal(expr: &lir::Expr) -> CompositeExpr<LogicValues> {
 /// - `lir::Shape`: Shape of the element
 /// - `String`: Name of the element
 /// - `vir::Expression`: Expression of the element
+///
+/// Returns `None` if they have different structures.
 pub(super) fn match_value_typ_exprs<V: Clone>(
     prefix: Option<String>, values: lir::PortDecls, exprs: CompositeExpr<V>,
-) -> Vec<(lir::Shape, String, V)> {
+) -> Option<Vec<(lir::Shape, String, V)>> {
     match (values, exprs) {
         (lir::PortDecls::Bits(shape), CompositeExpr::Bits(expr)) => {
             if shape.dim() > 1 || shape.width() == 0 {
-                vec![]
+                Some(vec![])
             } else {
-                vec![(shape, prefix.unwrap(), expr)]
+                Some(vec![(shape, prefix?, expr)])
             }
         }
         (lir::PortDecls::Struct(values), CompositeExpr::Struct(exprs)) => {
-            assert_eq!(values.len(), exprs.len());
-            izip!(values, exprs)
-                .map(|((name, values), exprs)| {
-                    match_value_typ_exprs(join_options("_", [prefix.clone(), name]), values, exprs)
-                })
-                .collect::<Vec<_>>()
-                .concat()
+            if values.len() != exprs.len() {
+                return None;
+            }
+            Some(
+                izip!(values, exprs)
+                    .map(|((name, values), exprs)| {
+                        match_value_typ_exprs(join_options("_", [prefix.clone(), name]), values, exprs)
+                    })
+                    .collect::<Option<Vec<_>>>()?
+                    .concat(),
+            )
         }
-        _ => panic!("internal compiler error"),
+        _ => None,
     }
 }
 
@@ -454,16 +561,80 @@ pub(super) fn match_value_typ_exprs<V: Clone>(
 pub(super) fn gen_module_fsm_state_init(
     state: &lir::Expr, init: &lir::Expr, ctx: &Context,
 ) -> Result<Vec<(lir::Shape, String, LogicValues)>, lir::ModuleError> {
-    let state_init_value = gen_expr_literal(init);
+    let state_init_value = gen_expr_literal(init, ctx)?;
 
     Ok(izip!(state.port_decls().iter(), state_init_value.iter())
         .map(|((name, shape), init_value)| {
-            let net_name = join_options("_", [Some(format!("{}_st", ctx.get_prefix().unwrap())), name]).unwrap();
+            let net_name = join_options("_", [ctx.get_prefix(), Some("st".to_string()), name]).unwrap();
             (shape, net_name, init_value)
         })
         .collect())
 }
 
+/// Returns value type whose fields are flattened to 1-dimensional, e.g., arrays of registers are
+/// represented as a single register.
+pub(super) fn flatten_typ(typ: lir::PortDecls) -> lir::PortDecls {
+    match typ {
+        lir::PortDecls::Struct(inner) => {
+            lir::PortDecls::Struct(inner.into_iter().map(|(name, typ)| (name, flatten_typ(typ))).collect())
+        }
+        lir::PortDecls::Bits(shape) => lir::PortDecls::Bits(lir::Shape::new([shape.width()])),
+    }
+}
+
+/// Returns the names of the clock signals of the module.
+pub(super) fn gen_module_clocks(module: &lir::Module, polarity: lir::ResetPolarity) -> Vec<String> {
+    [None]
+        .into_iter()
+        .chain(module.clock_domains().into_iter().map(Some))
+        .map(|clock_domain| clock_signals(clock_domain.as_deref(), polarity).0)
+        .collect()
+}
+
+/// Returns the polarity of the reset ports of the module instantiation, given the polarity of the
+/// package. Shakeflow modules have the polarity of the package, and external modules have their own.
+pub(super) fn gen_module_inst_polarity(
+    module_inst: &lir::ModuleInst, polarity: lir::ResetPolarity,
+) -> lir::ResetPolarity {
+    if module_inst.module.is_some() {
+        polarity
+    } else {
+        module_inst.reset_polarity
+    }
+}
+
+/// Returns the names of the clock signals of the module instantiation.
+pub(super) fn gen_module_inst_clocks(module_inst: &lir::ModuleInst, polarity: lir::ResetPolarity) -> Vec<String> {
+    let polarity = gen_module_inst_polarity(module_inst, polarity);
+    module_inst
+        .has_clkrst
+        .then_some(None)
+        .into_iter()
+        .chain(module_inst.clock_ports.iter().map(|(prefix, _)| Some(prefix.as_str())))
+        .map(|clock_domain| clock_signals(clock_domain, polarity).0)
+        .collect()
+}
+
+/// Returns the name of the module instantiated by the module instantiation. Specializations of
+/// parameterized modules are suffixed with the parameter values, for targets whose modules do not
+/// have parameters.
+pub(super) fn gen_module_inst_name(module_inst: &lir::ModuleInst) -> String {
+    ::std::iter::once(module_inst.module_name.clone())
+        .chain(module_inst.params.iter().map(|(_, value)| value.to_string()))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Scans module instantiations in the module by the names given by `gen_module_inst_name`, in
+/// topological order.
+pub(super) fn scan_module_insts(module: &lir::Module) -> LinkedHashMap<String, lir::ModuleInst> {
+    let mut module_insts = LinkedHashMap::new();
+    for module_inst in module.scan_module_insts() {
+        let _ = module_insts.entry(gen_module_inst_name(&module_inst)).or_insert(module_inst);
+    }
+    module_insts
+}
+
 /// Returns a set of ports to represent given interface type.
 fn gen_ports(interface_typ: &lir::InterfaceTyp) -> Vec<(Port, Accessor)> {
     match interface_typ {
@@ -529,13 +700,15 @@ pub(super) fn gen_virtual_wirings(
     for (ityp, path) in virtual_module.input_interface_typ().into_primitives() {
         let channel_typ = some_or!(ityp.clone().get_channel_typ(), continue);
 
-        let mut ingress_accessor = gen_channel_accessor(&virtual_module.input_interface_typ(), path.clone());
+        let mut ingress_accessor =
+            gen_channel_accessor(&virtual_module.module_name, &virtual_module.input_interface_typ(), path.clone())?;
         ingress_accessor.prefix = join_options("_", [Some("in".to_string()), ingress_accessor.prefix]);
 
         let mut registered_ingress_accessor = gen_channel_accessor(
+            &virtual_module.module_name,
             &virtual_module.input_interface_typ,
             virtual_module.input_endpoint().inner.into_iter().chain(path.inner.into_iter()).collect(),
-        );
+        )?;
         registered_ingress_accessor.prefix =
             join_options("_", [Some(virtual_module.input_prefix.clone()), registered_ingress_accessor.prefix]);
 
@@ -578,13 +751,15 @@ pub(super) fn gen_virtual_wirings(
     for (ityp, path) in virtual_module.output_interface_typ().into_primitives() {
         let channel_typ = some_or!(ityp.clone().get_channel_typ(), continue);
 
-        let mut egress_accessor = gen_channel_accessor(&virtual_module.output_interface_typ(), path.clone());
+        let mut egress_accessor =
+            gen_channel_accessor(&virtual_module.module_name, &virtual_module.output_interface_typ(), path.clone())?;
         egress_accessor.prefix = join_options("_", [Some("out".to_string()), egress_accessor.prefix]);
 
         let mut registered_egress_accessor = gen_channel_accessor(
+            &virtual_module.module_name,
             &virtual_module.output_interface_typ,
             virtual_module.output_endpoint().inner.into_iter().chain(path.inner.into_iter()).collect(),
-        );
+        )?;
         registered_egress_accessor.prefix =
             join_options("_", [Some(virtual_module.output_prefix.clone()), registered_egress_accessor.prefix]);
 
@@ -633,36 +808,70 @@ pub(super) fn gen_virtual_wirings(
 ///
 /// - `Direction`: Direction of the port
 /// - `String`: Name of the port
+/// - `usize`: Bitwidth of the port
 /// - `String`: Name of the expression
+/// - `bool`: Whether the expression should be inverted, for reset ports whose polarity is opposite
+///   to the reset signals
+#[allow(clippy::type_complexity)]
 pub(super) fn gen_connections(
     module: &lir::ModuleInst, ctx: &mut Context,
-) -> Result<Vec<(Direction, String, String)>, lir::ModuleError> {
+) -> Result<Vec<(Direction, String, usize, String, bool)>, lir::ModuleError> {
     let mut connections = Vec::new();
 
+    let polarity = gen_module_inst_polarity(module, ctx.reset_style().polarity);
+    let inverted = polarity != ctx.reset_style().polarity;
+
     if module.has_clkrst {
-        connections.push((Direction::Input, "clk".to_string(), "clk".to_string()));
-        connections.push((Direction::Input, "rst".to_string(), "rst".to_string()));
+        let (clk_port, rst_port) = clock_signals(None, polarity);
+        let (clk, rst) = ctx.clock_signals();
+        connections.push((Direction::Input, clk_port, 1, clk, false));
+        connections.push((Direction::Input, rst_port, 1, rst, inverted));
+
+        // Clock domains of the shakeflow module are driven by the clock domains of the same names.
+        for clock_domain in module.module.iter().flat_map(|module| module.clock_domains()) {
+            let (clk, rst) = clock_signals(Some(&clock_domain), polarity);
+            connections.push((Direction::Input, clk.clone(), 1, clk, false));
+            connections.push((Direction::Input, rst.clone(), 1, rst, false));
+        }
+    }
+
+    for (prefix, clock_domain) in module.clock_ports.iter() {
+        let (clk_port, rst_port) = clock_signals(Some(prefix), polarity);
+        let (clk, rst) = match clock_domain {
+            Some(clock_domain) => clock_signals(Some(clock_domain), ctx.reset_style().polarity),
+            None => ctx.clock_signals(),
+        };
+        connections.push((Direction::Input, clk_port, 1, clk, false));
+        connections.push((Direction::Input, rst_port, 1, rst, inverted));
     }
 
     for (port, accessor) in gen_ports(&module.input_interface_typ()) {
         let (path_prefix, path_sep) = (accessor.prefix, accessor.sep);
         let path_sep = path_sep.unwrap_or_else(|| "_".to_string());
         let lvalue_prefix = join_options("_", [module.input_prefix.clone(), path_prefix.clone()]);
-        let rvalue_prefix = join_options("_", [ctx.get_prefix(), Some("in".to_string()), path_prefix]);
+        // At the top level, the ports of the module are named as those of the instance.
+        let rvalue_prefix = match ctx.get_prefix() {
+            Some(prefix) => join_options("_", [Some(prefix), Some("in".to_string()), path_prefix]),
+            None => lvalue_prefix.clone(),
+        };
 
-        for (name, _) in port.channel_typ.fwd.iter() {
+        for (name, shape) in port.channel_typ.fwd.iter() {
             connections.push((
                 Direction::Input,
                 join_options(&path_sep, [lvalue_prefix.clone(), name.clone()]).unwrap(),
+                shape.width() * port.size,
                 join_options(&path_sep, [rvalue_prefix.clone(), name]).unwrap(),
+                false,
             ));
         }
 
-        for (name, _) in port.channel_typ.bwd.iter() {
+        for (name, shape) in port.channel_typ.bwd.iter() {
             connections.push((
                 Direction::Output,
                 join_options(&path_sep, [lvalue_prefix.clone(), name.clone()]).unwrap(),
+                shape.width() * port.size,
                 join_options(&path_sep, [rvalue_prefix.clone(), name.clone()]).unwrap(),
+                false,
             ));
         }
     }
@@ -671,21 +880,29 @@ pub(super) fn gen_connections(
         let (path_prefix, path_sep) = (accessor.prefix, accessor.sep);
         let path_sep = path_sep.unwrap_or_else(|| "_".to_string());
         let lvalue_prefix = join_options("_", [module.output_prefix.clone(), path_prefix.clone()]);
-        let rvalue_prefix = join_options("_", [ctx.get_prefix(), Some("out".to_string()), path_prefix]);
+        // At the top level, the ports of the module are named as those of the instance.
+        let rvalue_prefix = match ctx.get_prefix() {
+            Some(prefix) => join_options("_", [Some(prefix), Some("out".to_string()), path_prefix]),
+            None => lvalue_prefix.clone(),
+        };
 
-        for (name, _) in port.channel_typ.fwd.iter() {
+        for (name, shape) in port.channel_typ.fwd.iter() {
             connections.push((
                 Direction::Output,
                 join_options(&path_sep, [lvalue_prefix.clone(), name.clone()]).unwrap(),
+                shape.width() * port.size,
                 join_options(&path_sep, [rvalue_prefix.clone(), name]).unwrap(),
+                false,
             ));
         }
 
-        for (name, _) in port.channel_typ.bwd.iter() {
+        for (name, shape) in port.channel_typ.bwd.iter() {
             connections.push((
                 Direction::Input,
                 join_options(&path_sep, [lvalue_prefix.clone(), name.clone()]).unwrap(),
+                shape.width() * port.size,
                 join_options(&path_sep, [rvalue_prefix.clone(), name]).unwrap(),
+                false,
             ));
         }
     }
@@ -693,6 +910,29 @@ pub(super) fn gen_connections(
     Ok(connections)
 }
 
+/// Returns channel ports in the module.
+///
+/// # Returns
+///
+/// - `Direction`: Direction of the forward value (input for the input interface)
+/// - `lir::ChannelTyp`: Type of the channel
+/// - `usize`: Array size of the channel
+/// - `Option<String>`: Prefix of the port names
+/// - `String`: Separator between the prefix and the names of the values
+pub(super) fn gen_channel_ports(
+    module: &lir::Module,
+) -> Vec<(Direction, lir::ChannelTyp, usize, Option<String>, String)> {
+    let input_ports = gen_ports(&module.inner.input_interface_typ()).into_iter().map(|(port, accessor)| {
+        let prefix = join_options("_", [module.inner.input_prefix(), accessor.prefix]);
+        (Direction::Input, port.channel_typ, port.size, prefix, accessor.sep.unwrap_or_else(|| "_".to_string()))
+    });
+    let output_ports = gen_ports(&module.inner.output_interface_typ()).into_iter().map(|(port, accessor)| {
+        let prefix = join_options("_", [module.inner.output_prefix(), accessor.prefix]);
+        (Direction::Output, port.channel_typ, port.size, prefix, accessor.sep.unwrap_or_else(|| "_".to_string()))
+    });
+    input_ports.chain(output_ports).collect()
+}
+
 /// Returns port declarations in the module.
 ///
 /// # Returns
@@ -700,56 +940,33 @@ pub(super) fn gen_connections(
 /// - `Direction`: Direction of the port (input or output)
 /// - `usize`: Bitwidth of the port
 /// - `String`: Name of the port
-pub(super) fn gen_port_decls(module: &lir::Module) -> Result<Vec<(Direction, usize, String)>, lir::ModuleError> {
-    let mut port_decls = vec![(Direction::Input, 1, "clk".to_string()), (Direction::Input, 1, "rst".to_string())];
-
-    // Port declarations for input interface
-    for (port, accessor) in gen_ports(&module.inner.input_interface_typ()) {
-        let (path_prefix, path_sep) = (accessor.prefix, accessor.sep);
-        let path_sep = path_sep.unwrap_or_else(|| "_".to_string());
-        let input_prefix = join_options("_", [module.inner.input_prefix(), path_prefix]);
-
-        for (name, shape) in port.channel_typ.fwd.iter() {
-            assert_eq!(shape.dim(), 1, "Port of module should be 1-dimensional.");
-            port_decls.push((
-                Direction::Input,
-                shape.width() * port.size,
-                join_options(&path_sep, [input_prefix.clone(), name]).unwrap(),
-            ));
-        }
-
-        for (name, shape) in port.channel_typ.bwd.iter() {
-            assert_eq!(shape.dim(), 1, "Port of module should be 1-dimensional.");
-            port_decls.push((
-                Direction::Output,
-                shape.width() * port.size,
-                join_options(&path_sep, [input_prefix.clone(), name]).unwrap(),
-            ));
-        }
+pub(super) fn gen_port_decls(
+    module: &lir::Module, ctx: &Context,
+) -> Result<Vec<(Direction, usize, String)>, lir::ModuleError> {
+    let polarity = ctx.reset_style().polarity;
+    let (clk, rst) = clock_signals(None, polarity);
+    let mut port_decls = vec![(Direction::Input, 1, clk), (Direction::Input, 1, rst)];
+
+    // Clock and reset ports for the other clock domains
+    for clock_domain in module.clock_domains() {
+        let (clk, rst) = clock_signals(Some(&clock_domain), polarity);
+        port_decls.push((Direction::Input, 1, clk));
+        port_decls.push((Direction::Input, 1, rst));
     }
 
-    // Port declarations for output interface
-    for (port, accessor) in gen_ports(&module.inner.output_interface_typ()) {
-        let (path_prefix, path_sep) = (accessor.prefix, accessor.sep);
-        let path_sep = path_sep.unwrap_or_else(|| "_".to_string());
-        let output_prefix = join_options("_", [module.inner.output_prefix(), path_prefix]);
-
-        for (name, shape) in port.channel_typ.fwd.iter() {
-            assert_eq!(shape.dim(), 1, "Port of module should be 1-dimensional.");
-            port_decls.push((
-                Direction::Output,
-                shape.width() * port.size,
-                join_options(&path_sep, [output_prefix.clone(), name]).unwrap(),
-            ));
-        }
-
-        for (name, shape) in port.channel_typ.bwd.iter() {
+    // Port declarations for input and output interfaces
+    for (dir, channel_typ, size, prefix, sep) in gen_channel_ports(module) {
+        let bwd_dir = match dir {
+            Direction::Input => Direction::Output,
+            Direction::Output => Direction::Input,
+        };
+
+        for (dir, (name, shape)) in ::std::iter::empty()
+            .chain(channel_typ.fwd.iter().map(|port| (dir.clone(), port)))
+            .chain(channel_typ.bwd.iter().map(|port| (bwd_dir.clone(), port)))
+        {
             assert_eq!(shape.dim(), 1, "Port of module should be 1-dimensional.");
-            port_decls.push((
-                Direction::Input,
-                shape.width() * port.size,
-                join_options(&path_sep, [output_prefix.clone(), name]).unwrap(),
-            ));
+            port_decls.push((dir, shape.width() * size, join_options(&sep, [prefix.clone(), name]).unwrap()));
         }
     }
 
@@ -776,12 +993,12 @@ pub(super) fn gen_submodule_wires(
             let path_sep = path_sep.unwrap_or_else(|| "_".to_string());
             let input_prefix = join_options("_", [
                 ctx.get_prefix(),
-                Some(format!(
-                    "{}_{}_{}",
-                    comp_name,
-                    index,
-                    registered_module.inner.input_prefix().unwrap_or_else(|| "in".to_string())
-                )),
+                Some(format!("{}_{}_{}", comp_name, index, match &*registered_module.inner {
+                    lir::ModuleInner::Composite(..) => {
+                        registered_module.inner.input_prefix().unwrap_or_else(|| "in".to_string())
+                    }
+                    _ => "in".to_string(),
+                })),
                 path_prefix,
             ]);
 
@@ -798,12 +1015,12 @@ pub(super) fn gen_submodule_wires(
             let path_sep = path_sep.unwrap_or_else(|| "_".to_string());
             let output_prefix = join_options("_", [
                 ctx.get_prefix(),
-                Some(format!(
-                    "{}_{}_{}",
-                    comp_name,
-                    index,
-                    registered_module.inner.output_prefix().unwrap_or_else(|| "out".to_string())
-                )),
+                Some(format!("{}_{}_{}", comp_name, index, match &*registered_module.inner {
+                    lir::ModuleInner::Composite(..) => {
+                        registered_module.inner.output_prefix().unwrap_or_else(|| "out".to_string())
+                    }
+                    _ => "out".to_string(),
+                })),
                 path_prefix,
             ]);
 
@@ -981,17 +1198,25 @@ pub(super) fn gen_submodule_wires(
     Ok(submodule_wires)
 }
 
-/// Returns accessor to channel in interface.
-fn gen_channel_accessor(interface_typ: &lir::InterfaceTyp, mut path: lir::EndpointPath) -> Accessor {
-    if path.is_empty() {
-        assert!(matches!(interface_typ, lir::InterfaceTyp::Channel(_)));
-        return Accessor::default();
-    }
+/// Returns accessor to channel in interface of the module.
+fn gen_channel_accessor(
+    module: &str, interface_typ: &lir::InterfaceTyp, path: lir::EndpointPath,
+) -> Result<Accessor, lir::ModuleError> {
+    gen_channel_accessor_inner(interface_typ, path.clone()).ok_or_else(|| lir::ModuleError::EndpointPath {
+        module: module.to_string(),
+        path: path.to_string(),
+        typ: format!("{:?}", interface_typ),
+    })
+}
 
-    let front = path.pop_front().unwrap();
-    match (&front, interface_typ) {
+/// Returns accessor to channel in interface, or `None` if the path does not match the interface.
+fn gen_channel_accessor_inner(interface_typ: &lir::InterfaceTyp, mut path: lir::EndpointPath) -> Option<Accessor> {
+    let front = some_or!(path.pop_front(), {
+        return matches!(interface_typ, lir::InterfaceTyp::Channel(_)).then(Accessor::default);
+    });
+    Some(match (&front, interface_typ) {
         (lir::EndpointNode::Index(i), lir::InterfaceTyp::Array(interface_typ_elt, count)) => {
-            let mut accessor = gen_channel_accessor(interface_typ_elt, path);
+            let mut accessor = gen_channel_accessor_inner(interface_typ_elt, path)?;
             accessor.index = match accessor.index {
                 Some((index, total)) => Some((total * i + index, total * count)),
                 None => Some((*i, *count)),
@@ -999,13 +1224,13 @@ fn gen_channel_accessor(interface_typ: &lir::InterfaceTyp, mut path: lir::Endpoi
             accessor
         }
         (lir::EndpointNode::ExpansiveIndex(i), lir::InterfaceTyp::ExpansiveArray(interface_typ_elt, _)) => {
-            let mut accessor = gen_channel_accessor(interface_typ_elt, path);
+            let mut accessor = gen_channel_accessor_inner(interface_typ_elt, path)?;
             accessor.prefix = join_options("_", [Some(i.to_string()), accessor.prefix]);
             accessor
         }
         (lir::EndpointNode::Field(name, _), lir::InterfaceTyp::Struct(inner)) => {
-            let (sep, interface_typ_field) = inner.get(name).unwrap();
-            let mut accessor = gen_channel_accessor(interface_typ_field, path);
+            let (sep, interface_typ_field) = inner.get(name)?;
+            let mut accessor = gen_channel_accessor_inner(interface_typ_field, path)?;
             match accessor.prefix {
                 Some(prefix) => {
                     accessor.prefix = join_options(&sep.clone().unwrap_or_else(|| "_".to_string()), [
@@ -1020,8 +1245,8 @@ fn gen_channel_accessor(interface_typ: &lir::InterfaceTyp, mut path: lir::Endpoi
             }
             accessor
         }
-        _ => panic!("unmatched endpoint node and interface type: {:#?} and {:#?}", front, interface_typ),
-    }
+        _ => return None,
+    })
 }
 
 /// Returns wirings in the module.
@@ -1046,7 +1271,7 @@ pub(super) fn gen_wiring(
         for (interface, path) in from.clone().into_primitives() {
             let channel = some_or!(interface.get_channel(), continue);
 
-            let mut comp_accessor = gen_channel_accessor(&from.typ(), path);
+            let mut comp_accessor = gen_channel_accessor(&module.name, &from.typ(), path.clone())?;
             comp_accessor.prefix = join_options("_", [
                 Some(format!("{}_{}", submodule.get_module_name(), submodule_index)),
                 match &*submodule.inner {
@@ -1060,14 +1285,17 @@ pub(super) fn gen_wiring(
 
             match channel.endpoint() {
                 lir::Endpoint::Input { path } => {
-                    let mut from_accessor = gen_channel_accessor(&module.input_interface_typ(), path);
+                    let mut from_accessor = gen_channel_accessor(&module.name, &module.input_interface_typ(), path)?;
                     from_accessor.prefix = join_options("_", [module.input_prefix.clone(), from_accessor.prefix]);
 
                     input_connections.push((from_accessor, comp_accessor, channel.typ()));
                 }
                 lir::Endpoint::Submodule { submodule_index, path } => {
-                    let mut from_accessor =
-                        gen_channel_accessor(&module.submodules[submodule_index].0.inner.output_interface_typ(), path);
+                    let mut from_accessor = gen_channel_accessor(
+                        &module.name,
+                        &module.submodules[submodule_index].0.inner.output_interface_typ(),
+                        path,
+                    )?;
                     from_accessor.prefix = join_options("_", [
                         Some(format!("{}_{}", module.submodules[submodule_index].0.get_module_name(), submodule_index)),
                         match &*module.submodules[submodule_index].0.inner {
@@ -1083,7 +1311,13 @@ pub(super) fn gen_wiring(
 
                     comp_connections[submodule_index].push((from_accessor, comp_accessor, channel.typ()));
                 }
-                _ => panic!("internal compiler error"),
+                endpoint => {
+                    return Err(lir::ModuleError::Endpoint {
+                        module: module.name.clone(),
+                        path: path.to_string(),
+                        endpoint: format!("{:?}", endpoint),
+                    })
+                }
             }
         }
     }
@@ -1091,19 +1325,22 @@ pub(super) fn gen_wiring(
     for (interface, path) in module.output_interface.clone().into_primitives() {
         let channel = some_or!(interface.get_channel(), continue);
 
-        let mut output_accessor = gen_channel_accessor(&module.output_interface_typ(), path);
+        let mut output_accessor = gen_channel_accessor(&module.name, &module.output_interface_typ(), path.clone())?;
         output_accessor.prefix = join_options("_", [module.output_prefix.clone(), output_accessor.prefix]);
 
         match channel.endpoint() {
             lir::Endpoint::Input { path } => {
-                let mut from_accessor = gen_channel_accessor(&module.input_interface_typ(), path);
+                let mut from_accessor = gen_channel_accessor(&module.name, &module.input_interface_typ(), path)?;
                 from_accessor.prefix = join_options("_", [module.input_prefix.clone(), from_accessor.prefix]);
 
                 input_connections.push((from_accessor, output_accessor, channel.typ()));
             }
             lir::Endpoint::Submodule { submodule_index, path } => {
-                let mut from_accessor =
-                    gen_channel_accessor(&module.submodules[submodule_index].0.inner.output_interface_typ(), path);
+                let mut from_accessor = gen_channel_accessor(
+                    &module.name,
+                    &module.submodules[submodule_index].0.inner.output_interface_typ(),
+                    path,
+                )?;
                 from_accessor.prefix = join_options("_", [
                     Some(format!("{}_{}", module.submodules[submodule_index].0.get_module_name(), submodule_index,)),
                     match &*module.submodules[submodule_index].0.inner {
@@ -1117,7 +1354,13 @@ pub(super) fn gen_wiring(
 
                 comp_connections[submodule_index].push((from_accessor, output_accessor, channel.typ()));
             }
-            _ => panic!("internal compiler error"),
+            endpoint => {
+                return Err(lir::ModuleError::Endpoint {
+                    module: module.name.clone(),
+                    path: path.to_string(),
+                    endpoint: format!("{:?}", endpoint),
+                })
+            }
         }
     }
 
@@ -1186,7 +1429,7 @@ pub(super) fn gen_wiring_array(
         for (interface, path) in from.clone().into_primitives() {
             let channel = some_or!(interface.get_channel(), continue);
 
-            let mut comp_accessor = gen_channel_accessor(&from.typ(), path);
+            let mut comp_accessor = gen_channel_accessor(&module.name, &from.typ(), path.clone())?;
             comp_accessor.prefix = join_options("_", [
                 Some(format!("{}_{}", submodule.get_module_name(), submodule_index)),
                 match &*submodule.inner {
@@ -1200,14 +1443,17 @@ pub(super) fn gen_wiring_array(
 
             match channel.endpoint() {
                 lir::Endpoint::Input { path } => {
-                    let mut from_accessor = gen_channel_accessor(&module.input_interface_typ(), path);
+                    let mut from_accessor = gen_channel_accessor(&module.name, &module.input_interface_typ(), path)?;
                     from_accessor.prefix = join_options("_", [module.input_prefix.clone(), from_accessor.prefix]);
 
                     input_connections.push((from_accessor, true, comp_accessor, false, channel.typ()));
                 }
                 lir::Endpoint::Submodule { submodule_index, path } => {
-                    let mut from_accessor =
-                        gen_channel_accessor(&module.submodules[submodule_index].0.inner.output_interface_typ(), path);
+                    let mut from_accessor = gen_channel_accessor(
+                        &module.name,
+                        &module.submodules[submodule_index].0.inner.output_interface_typ(),
+                        path,
+                    )?;
                     from_accessor.prefix = join_options("_", [
                         Some(format!("{}_{}", module.submodules[submodule_index].0.get_module_name(), submodule_index)),
                         match &*module.submodules[submodule_index].0.inner {
@@ -1223,7 +1469,13 @@ pub(super) fn gen_wiring_array(
 
                     comp_connections[submodule_index].push((from_accessor, false, comp_accessor, false, channel.typ()));
                 }
-                _ => panic!("internal compiler error"),
+                endpoint => {
+                    return Err(lir::ModuleError::Endpoint {
+                        module: module.name.clone(),
+                        path: path.to_string(),
+                        endpoint: format!("{:?}", endpoint),
+                    })
+                }
             }
         }
     }
@@ -1231,19 +1483,22 @@ pub(super) fn gen_wiring_array(
     for (interface, path) in module.output_interface.clone().into_primitives() {
         let channel = some_or!(interface.get_channel(), continue);
 
-        let mut output_accessor = gen_channel_accessor(&module.output_interface_typ(), path);
+        let mut output_accessor = gen_channel_accessor(&module.name, &module.output_interface_typ(), path.clone())?;
         output_accessor.prefix = join_options("_", [module.output_prefix.clone(), output_accessor.prefix]);
 
         match channel.endpoint() {
             lir::Endpoint::Input { path } => {
-                let mut from_accessor = gen_channel_accessor(&module.input_interface_typ(), path);
+                let mut from_accessor = gen_channel_accessor(&module.name, &module.input_interface_typ(), path)?;
                 from_accessor.prefix = join_options("_", [module.input_prefix.clone(), from_accessor.prefix]);
 
                 input_connections.push((from_accessor, true, output_accessor, true, channel.typ()));
             }
             lir::Endpoint::Submodule { submodule_index, path } => {
-                let mut from_accessor =
-                    gen_channel_accessor(&module.submodules[submodule_index].0.inner.output_interface_typ(), path);
+                let mut from_accessor = gen_channel_accessor(
+                    &module.name,
+                    &module.submodules[submodule_index].0.inner.output_interface_typ(),
+                    path,
+                )?;
                 from_accessor.prefix = join_options("_", [
                     Some(format!("{}_{}", module.submodules[submodule_index].0.get_module_name(), submodule_index,)),
                     match &*module.submodules[submodule_index].0.inner {
@@ -1257,7 +1512,13 @@ pub(super) fn gen_wiring_array(
 
                 comp_connections[submodule_index].push((from_accessor, false, output_accessor, true, channel.typ()));
             }
-            _ => panic!("internal compiler error"),
+            endpoint => {
+                return Err(lir::ModuleError::Endpoint {
+                    module: module.name.clone(),
+                    path: path.to_string(),
+                    endpoint: format!("{:?}", endpoint),
+                })
+            }
         }
     }
 
@@ -1334,3 +1595,179 @@ pub(super) fn gen_wiring_array(
 
     Ok(conts)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    type Byte = Bits<U<8>>;
+
+    fn fsm() -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        hir::Fsm::<VrChannel<Byte>, VrChannel<Byte>, (), _>::new("pass", |fwd, bwd, state| (fwd, bwd, state), ().into())
+            .into()
+    }
+
+    fn module_inst() -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        hir::ModuleInst::new(
+            "ext".to_string(),
+            "ext_inst".to_string(),
+            vec![],
+            true,
+            Some("s_axis".to_string()),
+            Some("m_axis".to_string()),
+            None,
+        )
+        .into()
+    }
+
+    /// Returns the names of the ports of the module `name` in the Verilog or FIRRTL code.
+    fn ports(code: &str, name: &str) -> Vec<String> {
+        code.lines()
+            .map(str::trim)
+            .skip_while(|line| *line != format!("module {}", name) && *line != format!("module {} :", name))
+            .skip(1)
+            .take_while(|line| !line.is_empty() && *line != ");")
+            .filter_map(|line| {
+                let words = line.trim_end_matches(',').split_whitespace().collect::<Vec<_>>();
+                match words[..] {
+                    ["input" | "output", "wire", .., name] | ["input" | "output", name, ":", _] => {
+                        Some(name.to_string())
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn top_level_fsm_ports_are_prefixed_with_in_and_out() {
+        let expected = ["clk", "rst", "in", "in_valid", "in_ready", "out", "out_valid", "out_ready"];
+
+        let files = generate(package(fsm()), |package, dir| package.gen_vir(dir)).unwrap();
+        assert_eq!(ports(&files["pass_inner.v"], "pass_inner"), expected);
+
+        let files = generate(package(fsm()), |package, dir| package.gen_fir(dir)).unwrap();
+        assert_eq!(ports(&files["pass_inner.fir"], "pass"), expected);
+    }
+
+    #[test]
+    fn top_level_module_inst_ports_are_named_as_the_instance() {
+        let expected =
+            ["clk", "rst", "s_axis", "s_axis_valid", "s_axis_ready", "m_axis", "m_axis_valid", "m_axis_ready"];
+
+        let files = generate(package(module_inst()), |package, dir| package.gen_vir(dir)).unwrap();
+        let code = &files["ext_inner.v"];
+        assert_eq!(ports(code, "ext_inner"), expected);
+        for port in expected {
+            assert!(code.contains(&format!(".{}({})", port, port)), "{} is not connected in\n{}", port, code);
+        }
+
+        let files = generate(package(module_inst()), |package, dir| package.gen_fir(dir)).unwrap();
+        let code = &files["ext_inner.fir"];
+        assert!(code.starts_with("circuit ext_inner :"));
+        assert_eq!(ports(code, "ext_inner"), expected);
+        assert!(code.contains("ext_inst.s_axis <= s_axis"));
+        assert!(code.contains("m_axis <= ext_inst.m_axis"));
+    }
+
+    /// Returns an FSM counting up, with the given reset overriding the reset style of the package.
+    fn counter(reset: Option<lir::ResetKind>) -> Module<UniChannel<()>, UniChannel<Bits<U<2>>>> {
+        let fsm = hir::Fsm::<UniChannel<()>, UniChannel<Bits<U<2>>>, Bits<U<2>>, _>::new(
+            "counter",
+            |_, bwd, state| (state, bwd, (state + 1.into()).resize()),
+            1.into(),
+        );
+        match reset {
+            Some(reset) => fsm.with_reset(reset),
+            None => fsm,
+        }
+        .into()
+    }
+
+    /// Returns the Verilog and FIRRTL code of the counter in the given reset style.
+    fn counter_code(reset_style: lir::ResetStyle, reset: Option<lir::ResetKind>) -> (String, String) {
+        let gen = |f: fn(Package, &std::path::Path) -> Result<(), PackageError>| {
+            let mut package = package(counter(reset));
+            package.set_reset_style(reset_style);
+            generate(package, f).unwrap().into_values().next().unwrap()
+        };
+        (gen(|package, dir| package.gen_vir(dir)), gen(|package, dir| package.gen_fir(dir)))
+    }
+
+    #[test]
+    fn async_active_low_reset() {
+        let (verilog, firrtl) = counter_code(
+            lir::ResetStyle { kind: lir::ResetKind::Async, polarity: lir::ResetPolarity::ActiveLow },
+            None,
+        );
+
+        assert_eq!(ports(&verilog, "counter_inner"), ["clk", "rst_n", "out"]);
+        assert!(verilog.contains(
+            r#"always @(posedge clk or negedge rst_n) begin
+    if (~rst_n) begin
+        st_reg <= 2'b01;
+    end else begin
+        t0 = st_reg + 2'b01;
+        t1 = t0[2'b0 * 1 +: 2 * 1];
+        st_reg <= t1;
+    end
+end"#
+        ));
+
+        assert_eq!(ports(&firrtl, "counter"), ["clk", "rst_n", "out"]);
+        assert!(firrtl.contains(
+            r#"    reg st_reg : UInt<2>, clk with :
+      reset => (asAsyncReset(not(rst_n)), UInt<2>("b01"))"#
+        ));
+    }
+
+    #[test]
+    fn sync_active_high_reset() {
+        let (verilog, firrtl) = counter_code(
+            lir::ResetStyle { kind: lir::ResetKind::Sync, polarity: lir::ResetPolarity::ActiveHigh },
+            None,
+        );
+
+        assert_eq!(ports(&verilog, "counter_inner"), ["clk", "rst", "out"]);
+        assert!(verilog.contains(
+            r#"always @(posedge clk) begin
+    t0 = st_reg + 2'b01;
+    t1 = t0[2'b0 * 1 +: 2 * 1];
+    st_reg <= t1;
+    if (rst) begin
+        st_reg <= 2'b01;
+    end
+end"#
+        ));
+
+        assert_eq!(ports(&firrtl, "counter"), ["clk", "rst", "out"]);
+        assert!(firrtl.contains(
+            r#"    reg st_reg : UInt<2>, clk with :
+      reset => (rst, UInt<2>("b01"))"#
+        ));
+    }
+
+    #[test]
+    fn reset_less_registers_are_initialized() {
+        let (verilog, firrtl) = counter_code(lir::ResetStyle::default(), Some(lir::ResetKind::None));
+
+        assert!(verilog.contains(
+            r#"initial begin
+    st_reg = 2'b01;
+end
+
+always @(posedge clk) begin
+    t0 = st_reg + 2'b01;
+    t1 = t0[2'b0 * 1 +: 2 * 1];
+    st_reg <= t1;
+end"#
+        ));
+
+        assert!(firrtl.contains(
+            r#"    reg st_reg : UInt<2>, clk with :
+      reset => (UInt<1>(0), UInt<2>("b01"))"#
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+//! DOT IR.
+//!
+//! Directed graphs in the DOT language of Graphviz. Identifiers and attribute values are always
+//! quoted, so they may contain any characters.
+
+use crate::utils::indent;
+
+const INDENT: usize = 2;
+
+/// Attributes of graph, node or edge.
+pub type Attrs = Vec<(String, String)>;
+
+/// Node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// Identifier of the node
+    pub id: String,
+    /// Attributes
+    pub attrs: Attrs,
+}
+
+impl ToString for Node {
+    fn to_string(&self) -> String { format!("{}{};", quote(&self.id), gen_attrs(&self.attrs)) }
+}
+
+/// Edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    /// Identifier of the tail node
+    pub from: String,
+    /// Identifier of the head node
+    pub to: String,
+    /// Attributes
+    pub attrs: Attrs,
+}
+
+impl ToString for Edge {
+    fn to_string(&self) -> String { format!("{} -> {}{};", quote(&self.from), quote(&self.to), gen_attrs(&self.attrs)) }
+}
+
+/// Statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// Node.
+    Node(Node),
+    /// Edge.
+    Edge(Edge),
+    /// Subgraph, which is drawn as a cluster if its identifier starts with `cluster`.
+    Subgraph {
+        /// Identifier of the subgraph
+        id: String,
+        /// Attributes of the subgraph
+        attrs: Attrs,
+        /// Statements
+        stmts: Vec<Statement>,
+    },
+}
+
+impl ToString for Statement {
+    fn to_string(&self) -> String {
+        match self {
+            Statement::Node(node) => node.to_string(),
+            Statement::Edge(edge) => edge.to_string(),
+            Statement::Subgraph { id, attrs, stmts } => {
+                format!("subgraph {} {{\n{}\n}}", quote(id), indent(gen_body(attrs, stmts), INDENT))
+            }
+        }
+    }
+}
+
+/// Directed graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Graph {
+    /// Name of the graph
+    pub name: String,
+    /// Attributes of the graph
+    pub attrs: Attrs,
+    /// Statements
+    pub stmts: Vec<Statement>,
+}
+
+impl ToString for Graph {
+    fn to_string(&self) -> String {
+        format!("digraph {} {{\n{}\n}}", quote(&self.name), indent(gen_body(&self.attrs, &self.stmts), INDENT))
+    }
+}
+
+/// Quotes the string. Newlines are written as line breaks of labels.
+fn quote(s: &str) -> String { format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")) }
+
+/// Returns the attribute list, e.g., ` [shape="box"]`.
+fn gen_attrs(attrs: &Attrs) -> String {
+    if attrs.is_empty() {
+        return "".to_string();
+    }
+
+    format!(
+        " [{}]",
+        attrs.iter().map(|(name, value)| format!("{}={}", name, quote(value))).collect::<Vec<_>>().join(", ")
+    )
+}
+
+/// Returns the body of the graph or subgraph.
+fn gen_body(attrs: &Attrs, stmts: &[Statement]) -> String {
+    ::std::iter::empty()
+        .chain(attrs.iter().map(|(name, value)| format!("{}={};", name, quote(value))))
+        .chain(stmts.iter().map(|stmt| stmt.to_string()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,6 @@
+//! Graphviz DOT.
+
+mod ir;
+mod svg;
+
+pub use ir::*;
@@ -0,0 +1,292 @@
+//! SVG rendering of DOT graphs.
+//!
+//! Renders the top level of a graph without Graphviz, so that diagrams can be generated and checked
+//! by plain `cargo` runs. Clusters are drawn as single nodes, and nodes are laid out in columns from
+//! left to right by the longest path from the sources, ignoring the edges that close cycles.
+
+use std::collections::{HashMap, HashSet};
+
+use super::*;
+use crate::some_or;
+
+/// Width of a character of the labels.
+const CHAR_WIDTH: f64 = 7.0;
+
+/// Height of a line of the labels.
+const LINE_HEIGHT: f64 = 14.0;
+
+/// Padding inside the nodes.
+const PADDING: f64 = 10.0;
+
+/// Gap between columns.
+const COLUMN_GAP: f64 = 60.0;
+
+/// Gap between nodes in a column.
+const ROW_GAP: f64 = 20.0;
+
+/// Node to be drawn.
+#[derive(Debug)]
+struct SvgNode {
+    label: Vec<String>,
+    shape: String,
+    style: String,
+    width: f64,
+    height: f64,
+    x: f64,
+    y: f64,
+}
+
+impl SvgNode {
+    fn new(attrs: &Attrs, default_label: &str) -> Self {
+        let attr = |name: &str| attrs.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+        let label = attr("label").unwrap_or_else(|| default_label.to_string());
+        let label = label.lines().map(String::from).collect::<Vec<_>>();
+        let width = label.iter().map(|line| line.chars().count()).max().unwrap_or(0) as f64 * CHAR_WIDTH;
+
+        Self {
+            shape: attr("shape").unwrap_or_else(|| "box".to_string()),
+            style: attr("style").unwrap_or_default(),
+            width: width.max(2.0 * CHAR_WIDTH) + 2.0 * PADDING,
+            height: label.len() as f64 * LINE_HEIGHT + 2.0 * PADDING,
+            label,
+            x: 0.0,
+            y: 0.0,
+        }
+    }
+
+    /// Returns the SVG elements of the shape of the node.
+    fn shape(&self) -> String {
+        let (x, y, w, h) = (self.x, self.y, self.width, self.height);
+        let dash = if self.style.contains("dashed") { " stroke-dasharray=\"5,3\"" } else { "" };
+        let rx = if self.style.contains("rounded") { 6.0 } else { 0.0 };
+
+        let mut shape = match self.shape.as_str() {
+            "cds" => format!(
+                "<polygon points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\"{}/>",
+                x,
+                y,
+                x + w - h / 2.0,
+                y,
+                x + w,
+                y + h / 2.0,
+                x + w - h / 2.0,
+                y + h,
+                x,
+                y + h,
+                dash
+            ),
+            _ => format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"{:.1}\"{}/>",
+                x, y, w, h, rx, dash
+            ),
+        };
+        match self.shape.as_str() {
+            "box3d" => shape.push_str(&format!(
+                "<polyline points=\"{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\"/>",
+                x + 4.0,
+                y,
+                x + 4.0,
+                y - 4.0,
+                x + w + 4.0,
+                y - 4.0
+            )),
+            "component" => {
+                for dy in [h / 3.0 - 4.0, 2.0 * h / 3.0 - 4.0] {
+                    shape.push_str(&format!(
+                        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"8.0\" height=\"8.0\"/>",
+                        x - 4.0,
+                        y + dy
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        shape
+    }
+
+    /// Returns the SVG elements of the label of the node.
+    fn text(&self) -> String {
+        self.label
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+                    self.x + self.width / 2.0,
+                    self.y + PADDING + (i as f64 + 0.8) * LINE_HEIGHT,
+                    escape(line)
+                )
+            })
+            .collect()
+    }
+}
+
+impl Graph {
+    /// Renders the top level of the graph in SVG.
+    pub fn to_svg(&self) -> String {
+        // Collects the nodes, where the nodes in a cluster are represented by the cluster.
+        let mut nodes = Vec::new();
+        let mut representatives = HashMap::new();
+        let mut edges = Vec::new();
+        for stmt in &self.stmts {
+            match stmt {
+                Statement::Node(node) => {
+                    let _ = representatives.insert(node.id.clone(), nodes.len());
+                    nodes.push(SvgNode::new(&node.attrs, &node.id));
+                }
+                Statement::Edge(edge) => edges.push(edge),
+                Statement::Subgraph { id, attrs, stmts } => {
+                    collect_subgraph(stmts, nodes.len(), &mut representatives, &mut edges);
+                    nodes.push(SvgNode::new(attrs, id));
+                }
+            }
+        }
+
+        let mut links = Vec::new();
+        for edge in edges {
+            let (from, to) = some_or!(representatives.get(&edge.from).zip(representatives.get(&edge.to)), continue);
+            let dashed = edge.attrs.iter().any(|(name, value)| name == "style" && value == "dashed");
+            let directed = !edge.attrs.iter().any(|(name, value)| name == "dir" && value == "none");
+            if from != to && !links.contains(&(*from, *to, dashed, directed)) {
+                links.push((*from, *to, dashed, directed));
+            }
+        }
+
+        // Places the nodes in columns by the longest path from the sources.
+        let forward = forward_edges(nodes.len(), &links.iter().map(|(from, to, ..)| (*from, *to)).collect::<Vec<_>>());
+        let mut columns = vec![0; nodes.len()];
+        for _ in 0..nodes.len() {
+            for (from, to) in &forward {
+                columns[*to] = columns[*to].max(columns[*from] + 1);
+            }
+        }
+        let column_count = columns.iter().max().map_or(0, |column| column + 1);
+
+        let mut x = COLUMN_GAP / 2.0;
+        let mut heights = vec![0.0; column_count];
+        let mut xs = vec![0.0; column_count];
+        for (column, column_x) in xs.iter_mut().enumerate() {
+            let members = (0..nodes.len()).filter(|node| columns[*node] == column).collect::<Vec<_>>();
+            *column_x = x;
+            x += members.iter().map(|node| nodes[*node].width).fold(0.0, f64::max) + COLUMN_GAP;
+            heights[column] = members.iter().map(|node| nodes[*node].height + ROW_GAP).sum::<f64>();
+        }
+        let height = heights.iter().cloned().fold(0.0, f64::max) + ROW_GAP;
+        for (column, column_height) in heights.iter().enumerate() {
+            let mut y = (height - column_height + ROW_GAP) / 2.0 + 4.0;
+            for node in (0..nodes.len()).filter(|node| columns[*node] == column) {
+                nodes[node].x = xs[column];
+                nodes[node].y = y;
+                y += nodes[node].height + ROW_GAP;
+            }
+        }
+        let width = x - COLUMN_GAP / 2.0;
+
+        let paths = links
+            .iter()
+            .map(|(from, to, dashed, directed)| {
+                let span = columns[*to] as isize - columns[*from] as isize;
+                let (from, to) = (&nodes[*from], &nodes[*to]);
+                let (x1, y1) = (from.x + from.width, from.y + from.height / 2.0);
+                let (x2, y2) = (to.x, to.y + to.height / 2.0);
+                let dx = ((x2 - x1).abs() / 2.0).max(COLUMN_GAP / 2.0);
+
+                // Edges to the next column are drawn directly. Edges skipping columns are drawn above
+                // the nodes in between, and edges closing cycles below the nodes.
+                let (cy1, cy2) = match span {
+                    1 => (y1, y2),
+                    span if span > 1 => (0.0, 0.0),
+                    _ => (height, height),
+                };
+                format!(
+                    "<path d=\"M{:.1},{:.1} C{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}\"{}{}/>",
+                    x1,
+                    y1,
+                    x1 + dx,
+                    cy1,
+                    x2 - dx,
+                    cy2,
+                    x2,
+                    y2,
+                    if *dashed { " stroke-dasharray=\"5,3\"" } else { "" },
+                    if *directed { " marker-end=\"url(#arrow)\"" } else { "" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<svg version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\" width=\"{width:.1}\" height=\"{height:.1}\" viewBox=\"0 0 {width:.1} {height:.1}\">
+<title>{}</title>
+<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\"/></marker></defs>
+<rect width=\"100%\" height=\"100%\" fill=\"white\"/>
+<g fill=\"none\" stroke=\"black\">
+{}
+</g>
+<g fill=\"white\" stroke=\"black\">
+{}
+</g>
+<g font-family=\"Helvetica, Arial, sans-serif\" font-size=\"12\" text-anchor=\"middle\">
+{}
+</g>
+</svg>",
+            escape(&self.name),
+            paths,
+            nodes.iter().map(SvgNode::shape).collect::<Vec<_>>().join("\n"),
+            nodes.iter().map(SvgNode::text).collect::<Vec<_>>().join("\n"),
+        )
+    }
+}
+
+/// Collects the nodes in the subgraph as represented by `representative`, and the edges in it.
+fn collect_subgraph<'a>(
+    stmts: &'a [Statement], representative: usize, representatives: &mut HashMap<String, usize>,
+    edges: &mut Vec<&'a Edge>,
+) {
+    for stmt in stmts {
+        match stmt {
+            Statement::Node(node) => {
+                let _ = representatives.insert(node.id.clone(), representative);
+            }
+            Statement::Edge(edge) => edges.push(edge),
+            Statement::Subgraph { stmts, .. } => collect_subgraph(stmts, representative, representatives, edges),
+        }
+    }
+}
+
+/// Returns the edges except those closing cycles, found by depth-first search from the nodes in order.
+fn forward_edges(count: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    fn visit(
+        node: usize, edges: &[(usize, usize)], visited: &mut HashSet<usize>, stack: &mut Vec<usize>,
+        forward: &mut Vec<(usize, usize)>,
+    ) {
+        let _ = visited.insert(node);
+        stack.push(node);
+        for (from, to) in edges.iter().filter(|(from, _)| *from == node) {
+            if stack.contains(to) {
+                continue;
+            }
+            forward.push((*from, *to));
+            if !visited.contains(to) {
+                visit(*to, edges, visited, stack, forward);
+            }
+        }
+        let _ = stack.pop();
+    }
+
+    let mut visited = HashSet::new();
+    let mut forward = Vec::new();
+    for node in 0..count {
+        if !visited.contains(&node) {
+            visit(node, edges, &mut visited, &mut Vec::new(), &mut forward);
+        }
+    }
+    forward
+}
+
+/// Escapes the text for XML.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
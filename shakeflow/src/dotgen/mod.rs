@@ -0,0 +1,249 @@
+//! Generates Graphviz DOT diagrams of composite modules.
+//!
+//! # Note
+//!
+//! Each composite module is drawn as a graph whose nodes are the submodules and whose edges are
+//! the channels between them. Composite submodules are drawn as clusters containing their own
+//! submodules, FSMs as rounded boxes, FFI module instantiations as components, and virtual modules
+//! as dashed boxes linked to their registered modules.
+
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::panic::Location;
+use std::path::Path;
+
+use crate::codegen::{gen_module_inst_name, gen_source_annotation};
+use crate::dot::*;
+use crate::*;
+
+impl Package {
+    /// Generates DOT diagrams at the given directory path.
+    ///
+    /// Each composite module in the package, including the instantiated ones, is generated in a
+    /// file named after the module. The top level of the diagram is also rendered in SVG, which does
+    /// not require Graphviz. Each specialization of a parameterized module is named after its
+    /// parameter values.
+    pub fn gen_dot<P: AsRef<Path>>(&self, path_dir: P) -> Result<(), PackageError> {
+        fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
+        self.check_construction()?;
+
+        let mut names = HashSet::new();
+
+        let submodules = self.scan_submodule_inst();
+        let modules = submodules.iter().chain(self.modules.iter()).map(|module| (None, module));
+        let specializations = self
+            .param_modules
+            .iter()
+            .flat_map(|module| module.named_specializations())
+            .map(|(name, module)| (Some(name), module));
+
+        for (specialization, module) in modules.chain(specializations) {
+            let (name, module) = match &*module.inner {
+                lir::ModuleInner::Composite(name, module) => (specialization.unwrap_or_else(|| name.clone()), module),
+                _ => continue,
+            };
+            if !names.insert(name.clone()) {
+                continue;
+            }
+
+            let graph = gen_graph(name.clone(), module);
+
+            let path = path_dir.as_ref().join(format!("{}.dot", name));
+            let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+            writeln!(file, "{}", graph.to_string()).map_err(|error| PackageError::Fs { error })?;
+
+            let path = path_dir.as_ref().join(format!("{}.svg", name));
+            let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+            writeln!(file, "{}", graph.to_svg()).map_err(|error| PackageError::Fs { error })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Generates DOT graph of the composite module.
+pub fn gen_graph(name: String, module: &lir::CompositeModule) -> Graph {
+    let mut stmts = Vec::new();
+    gen_composite(module, None, &mut stmts);
+
+    Graph {
+        name,
+        attrs: vec![("rankdir".to_string(), "LR".to_string()), ("fontname".to_string(), "Helvetica".to_string())],
+        stmts,
+    }
+}
+
+/// Generates the input and output nodes, submodules and channels of the composite module, whose
+/// nodes are prefixed by `prefix`.
+fn gen_composite(module: &lir::CompositeModule, prefix: Option<String>, stmts: &mut Vec<Statement>) {
+    let (input, output) = composite_endpoints(&prefix);
+    stmts.push(gen_node(input.clone(), vec![("shape", "cds"), ("label", "in")], None));
+    stmts.push(gen_node(output.clone(), vec![("shape", "cds"), ("label", "out")], None));
+
+    let registered = module
+        .registered_modules
+        .iter()
+        .enumerate()
+        .map(|(index, submodule)| {
+            let id = join_options("_", [
+                prefix.clone(),
+                Some(format!("registered_{}_{}", submodule.get_module_name(), index)),
+            ])
+            .unwrap();
+            gen_submodule(submodule, id, &[], stmts)
+        })
+        .collect::<Vec<_>>();
+
+    let submodules = module
+        .submodules
+        .iter()
+        .enumerate()
+        .map(|(index, (submodule, _))| {
+            let id = join_options("_", [prefix.clone(), Some(format!("{}_{}", submodule.get_module_name(), index))])
+                .unwrap();
+            gen_submodule(submodule, id, &registered, stmts)
+        })
+        .collect::<Vec<_>>();
+
+    // Tail of the channel from the endpoint. Temporary endpoints do not appear in the final module.
+    let tail = |endpoint: &lir::Endpoint| match endpoint {
+        lir::Endpoint::Input { .. } => Some(input.clone()),
+        lir::Endpoint::Submodule { submodule_index, .. } => Some(submodules[*submodule_index].1.clone()),
+        lir::Endpoint::Temp { .. } => None,
+    };
+
+    for (index, (_, from)) in module.submodules.iter().enumerate() {
+        for (interface, path) in from.clone().into_primitives() {
+            let channel = some_or!(interface.get_channel(), continue);
+            let from = some_or!(tail(&channel.endpoint), continue);
+            stmts.push(gen_channel(from, submodules[index].0.clone(), &channel, &path));
+        }
+    }
+
+    for (interface, path) in module.output_interface.clone().into_primitives() {
+        let channel = some_or!(interface.get_channel(), continue);
+        let from = some_or!(tail(&channel.endpoint), continue);
+        stmts.push(gen_channel(from, output.clone(), &channel, &path));
+    }
+}
+
+/// Generates the submodule whose nodes are prefixed by `id`. `registered` are the input and output
+/// nodes of the registered modules of the enclosing module.
+///
+/// Returns the nodes where the input and output channels of the submodule are connected.
+fn gen_submodule(
+    module: &lir::Module, id: String, registered: &[(String, String)], stmts: &mut Vec<Statement>,
+) -> (String, String) {
+    match &*module.inner {
+        lir::ModuleInner::Composite(name, module) => {
+            let label = match module.module_typ {
+                lir::CompositeModuleTyp::OneToOne => name.clone(),
+                lir::CompositeModuleTyp::NToN(n) => format!("{} \u{d7} {}", name, n),
+            };
+            let mut attrs = vec![("label".to_string(), label), ("style".to_string(), "rounded".to_string())];
+            attrs.extend(gen_tooltip(module.source));
+
+            let mut inner = Vec::new();
+            gen_composite(module, Some(id.clone()), &mut inner);
+            stmts.push(Statement::Subgraph { id: format!("cluster_{}", id), attrs, stmts: inner });
+
+            composite_endpoints(&Some(id))
+        }
+        lir::ModuleInner::Fsm(module) => {
+            stmts.push(gen_node(
+                id.clone(),
+                vec![("shape", "box"), ("style", "rounded"), ("label", &module.get_module_name())],
+                module.source,
+            ));
+
+            (id.clone(), id)
+        }
+        lir::ModuleInner::ModuleInst(module) => {
+            // Instantiations of modules written in ShakeFlow are generated as separate modules.
+            let (shape, module_name) = match module.module {
+                Some(_) => ("box3d", gen_module_inst_name(module)),
+                None => ("component", module.module_name.clone()),
+            };
+            let label = format!("{}\n{}", module.inst_name, module_name);
+            stmts.push(gen_node(id.clone(), vec![("shape", shape), ("label", &label)], module.source));
+
+            (id.clone(), id)
+        }
+        lir::ModuleInner::VirtualModule(module) => {
+            stmts.push(gen_node(
+                id.clone(),
+                vec![("shape", "box"), ("style", "dashed"), ("label", &module.get_module_name())],
+                None,
+            ));
+            if let Some((input, _)) = registered.get(module.registered_index) {
+                stmts.push(Statement::Edge(Edge {
+                    from: id.clone(),
+                    to: input.clone(),
+                    attrs: vec![
+                        ("style".to_string(), "dashed".to_string()),
+                        ("dir".to_string(), "none".to_string()),
+                        ("constraint".to_string(), "false".to_string()),
+                    ],
+                }));
+            }
+
+            (id.clone(), id)
+        }
+    }
+}
+
+/// Returns the input and output nodes of the composite module whose nodes are prefixed by
+/// `prefix`.
+fn composite_endpoints(prefix: &Option<String>) -> (String, String) {
+    (
+        join_options("_", [prefix.clone(), Some("in".to_string())]).unwrap(),
+        join_options("_", [prefix.clone(), Some("out".to_string())]).unwrap(),
+    )
+}
+
+/// Generates edge of the channel, which is labelled with the channel type and the widths of the
+/// forward and backward signals. `path` is the endpoint path of the head.
+fn gen_channel(from: String, to: String, channel: &lir::Channel, path: &lir::EndpointPath) -> Statement {
+    let decl = channel.typ.decl();
+    let mut label = format!(
+        "{}<{}>\n{} / {} bits",
+        decl.name,
+        gen_signal_typ_name(&decl.fwd),
+        channel.typ.fwd.width(),
+        channel.typ.bwd.width()
+    );
+
+    let paths = [channel.endpoint.path(), path];
+    if paths.iter().any(|path| !path.is_empty()) {
+        label = format!("{} \u{2192} {}\n{}", paths[0].to_string(), paths[1].to_string(), label);
+    }
+
+    Statement::Edge(Edge { from, to, attrs: vec![("label".to_string(), label)] })
+}
+
+/// Returns the name of the signal type, or its structure if it is anonymous.
+fn gen_signal_typ_name(typ: &lir::SignalTyp) -> String {
+    match typ {
+        lir::SignalTyp::Struct { name: Some(name), .. } | lir::SignalTyp::Enum { name, .. } => name.clone(),
+        typ => typ.port_decls().to_string(),
+    }
+}
+
+/// Generates node with the attributes. The source location is shown as its tooltip.
+fn gen_node(id: String, attrs: Vec<(&str, &str)>, source: Option<&Location<'_>>) -> Statement {
+    Statement::Node(Node {
+        id,
+        attrs: attrs
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .chain(gen_tooltip(source))
+            .collect(),
+    })
+}
+
+/// Returns the tooltip attribute of the source location.
+fn gen_tooltip(source: Option<&Location<'_>>) -> Option<(String, String)> {
+    source.map(|source| ("tooltip".to_string(), gen_source_annotation(source)))
+}
@@ -1,6 +1,6 @@
 //! FIRRTL.
 //!
-//! For simplicity, we omitted some items such as `Info`, `StringLit`, `ExtModule`, ..
+//! For simplicity, we omitted some items such as `Info`, `StringLit`, ..
 
 use crate::codegen::*;
 use crate::lir;
@@ -91,6 +91,9 @@ pub enum PrimOp {
 
     /// Tail
     Tail,
+
+    /// Interpret As AsyncReset
+    AsAsyncReset,
 }
 
 impl ToString for PrimOp {
@@ -123,6 +126,7 @@ impl ToString for PrimOp {
             PrimOp::Bits => "bits",
             PrimOp::Head => "head",
             PrimOp::Tail => "tail",
+            PrimOp::AsAsyncReset => "asAsyncReset",
         }
         .to_string()
     }
@@ -136,29 +140,6 @@ impl From<lir::UnaryOp> for PrimOp {
     }
 }
 
-impl From<lir::BinaryOp> for PrimOp {
-    fn from(op: lir::BinaryOp) -> Self {
-        match op {
-            lir::BinaryOp::Add => PrimOp::Add,
-            lir::BinaryOp::Sub => PrimOp::Sub,
-            lir::BinaryOp::Mul => PrimOp::Mul,
-            lir::BinaryOp::Div => PrimOp::Div,
-            lir::BinaryOp::Mod => PrimOp::Rem,
-            lir::BinaryOp::Or => PrimOp::Or,
-            lir::BinaryOp::And => PrimOp::And,
-            lir::BinaryOp::Xor => PrimOp::Xor,
-            lir::BinaryOp::Eq => todo!(),
-            lir::BinaryOp::EqArithmetic => PrimOp::Eq,
-            lir::BinaryOp::Less => PrimOp::Lt,
-            lir::BinaryOp::Greater => PrimOp::Gt,
-            lir::BinaryOp::LessEq => PrimOp::Leq,
-            lir::BinaryOp::GreaterEq => PrimOp::Geq,
-            lir::BinaryOp::ShiftLeft => PrimOp::Shl,
-            lir::BinaryOp::ShiftRight => PrimOp::Shr,
-        }
-    }
-}
-
 impl PrimOp {
     /// Returns true if `self` is comparison operator.
     #[inline]
@@ -330,10 +311,17 @@ impl Expression {
 
     /// Literal expression.
     ///
-    /// Value should be represented in binary format. (e.g. "01001")
+    /// Value should be represented in binary format. (e.g. "01001") Don't-care bits are zero.
     #[inline]
     pub fn literal(value: LogicValues, width: Option<usize>) -> Self {
-        Expression::Literal { value: format!("\"b{}\"", value.to_string()), width }
+        let value = value.iter().map(|b| if *b == LogicValue::True { '1' } else { '0' }).collect::<String>();
+        Expression::Literal { value: format!("\"b{}\"", value), width }
+    }
+
+    /// Unsigned integer literal of the given width.
+    #[inline]
+    pub fn uint(value: usize, width: usize) -> Self {
+        Expression::Literal { value: value.to_string(), width: Some(width) }
     }
 
     /// Primitive operation.
@@ -478,10 +466,8 @@ pub enum Statement {
         tpe: Type,
         /// Clock signal
         clock: Expression,
-        /// Reset signal
-        reset: Expression,
-        /// Initialization signal
-        init: Expression,
+        /// Reset signal and initialization signal, or `None` for registers without reset
+        reset: Option<(Expression, Expression)>,
     },
     /// Module instantiation.
     DefInstance {
@@ -539,6 +525,13 @@ pub enum Statement {
     },
     /// Empty statement.
     EmptyStmt,
+    /// Statement with comment.
+    Commented {
+        /// Comment
+        comment: String,
+        /// Statement
+        stmt: Box<Statement>,
+    },
 }
 
 impl ToString for Statement {
@@ -547,15 +540,16 @@ impl ToString for Statement {
             Statement::DefWire { name, tpe } => {
                 format!("wire {} : {}", name, tpe.to_string())
             }
-            Statement::DefRegister { name, tpe, clock, reset, init } => {
-                format!(
+            Statement::DefRegister { name, tpe, clock, reset } => match reset {
+                Some((reset, init)) => format!(
                     "reg {} : {}, {} with :\n{}",
                     name,
                     tpe.to_string(),
                     clock.to_string(),
                     indent(format!("reset => ({}, {})", reset.to_string(), init.to_string()), INDENT)
-                )
-            }
+                ),
+                None => format!("reg {} : {}, {}", name, tpe.to_string(), clock.to_string()),
+            },
             Statement::DefInstance { name, module } => {
                 format!("inst {} of {}", name, module)
             }
@@ -593,6 +587,10 @@ impl ToString for Statement {
                 format!("{} is invalid", expr.to_string())
             }
             Statement::EmptyStmt => "skip".to_string(),
+            Statement::Commented { comment, stmt } => {
+                let comment = comment.lines().map(|line| format!("; {}", line)).collect::<Vec<_>>().join("\n");
+                format!("{}\n{}", comment, stmt.to_string())
+            }
         }
     }
 }
@@ -605,7 +603,7 @@ impl Statement {
     /// Creates new reg definition.
     #[inline]
     pub fn def_reg(name: String, tpe: Type, init: Expression) -> Self {
-        Statement::DefRegister { name, tpe, clock: Expression::clk(), reset: Expression::rst(), init }
+        Statement::DefRegister { name, tpe, clock: Expression::clk(), reset: Some((Expression::rst(), init)) }
     }
 
     /// Creates new module instantiation.
@@ -712,9 +710,42 @@ impl ToString for Module {
     }
 }
 
+/// Externally defined module, e.g., a Verilog module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtModule {
+    /// Name of the module
+    pub name: String,
+    /// Ports of the module
+    pub ports: Vec<Port>,
+    /// Name of the module in the generated Verilog
+    pub defname: String,
+    /// Parameters of the module
+    pub params: Vec<(String, usize)>,
+}
+
+impl ToString for ExtModule {
+    fn to_string(&self) -> String {
+        format!(
+            "extmodule {} :\n{}",
+            self.name,
+            indent(
+                ::std::iter::empty()
+                    .chain(self.ports.iter().map(|port| port.to_string()))
+                    .chain(Some(format!("defname = {}", self.defname)))
+                    .chain(self.params.iter().map(|(name, value)| format!("parameter {} = {}", name, value)))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                INDENT
+            )
+        )
+    }
+}
+
 /// Circuit.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Circuit {
+    /// External modules
+    pub ext_modules: Vec<ExtModule>,
     /// Inner modules
     pub modules: Vec<Module>,
     /// Name of the circuit
@@ -726,7 +757,12 @@ impl ToString for Circuit {
         format!(
             "circuit {} :\n{}\n",
             self.main,
-            self.modules.iter().map(|s| s.to_string()).map(|s| indent(s, INDENT)).collect::<Vec<_>>().join("\n")
+            ::std::iter::empty()
+                .chain(self.ext_modules.iter().map(|s| s.to_string()))
+                .chain(self.modules.iter().map(|s| s.to_string()))
+                .map(|s| indent(s, INDENT))
+                .collect::<Vec<_>>()
+                .join("\n")
         )
     }
 }
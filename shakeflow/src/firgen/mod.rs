@@ -2,33 +2,77 @@
 //!
 //! # Note
 //!
-//! In FIRRTL generation, only 1-dimensional signals are supported.
+//! Signals are flattened into unsigned integers, e.g., multi-dimensional signals are represented
+//! by the concatenation of their elements. Loops in expressions and arrays of modules are unrolled.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::fs::File;
 use std::io::Write;
+use std::panic::Location;
 use std::path::Path;
 
 use hashcons::merkle::Merkle;
+use itertools::izip;
 
 use crate::codegen::*;
 use crate::fir::*;
 use crate::*;
 
 impl Package {
+    /// Generates FIRRTL circuit of the top-level module named `main`, which contains the modules it
+    /// instantiates. Modules instantiated by FFIs are declared as external modules.
+    fn gen_fir_circuit(&self, main: String, module: &lir::Module) -> Result<Circuit, PackageError> {
+        let module_insts = scan_module_insts(module);
+
+        let mut circuit = Circuit { ext_modules: Vec::new(), modules: Vec::new(), main: main.clone() };
+
+        for (name, module_inst) in module_insts {
+            match &module_inst.module {
+                Some(submodule) => circuit.modules.push(
+                    gen_module::<Firgen>(name, submodule, self.reset_style)
+                        .map_err(|error| PackageError::Module { error })?
+                        .into(),
+                ),
+                None => circuit.ext_modules.push(
+                    gen_ext_module(name, &module_inst, self.reset_style)
+                        .map_err(|error| PackageError::Module { error })?,
+                ),
+            }
+        }
+
+        circuit.modules.push(
+            gen_module::<Firgen>(main, module, self.reset_style)
+                .map_err(|error| PackageError::Module { error })?
+                .into(),
+        );
+
+        Ok(circuit)
+    }
+
     /// Generates FIRRTL code at the given directory path.
-    pub fn gen_fir<P: AsRef<Path>>(self, path_dir: P) -> Result<(), PackageError> {
+    ///
+    /// Each top-level module is generated as a circuit with the modules it instantiates. FIRRTL has
+    /// no module parameters, so each specialization of a parameterized module is generated as a
+    /// circuit named after its parameter values.
+    pub fn gen_fir<P: AsRef<Path>>(mut self, path_dir: P) -> Result<(), PackageError> {
         fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
 
-        for module in self.modules.into_iter() {
-            let path = path_dir.as_ref().join(format!("{}_inner.fir", module.get_module_name()));
+        let (_, top_modules) = self.scan_modules()?;
+
+        let top_modules = top_modules.iter().map(|module| (module.get_module_name(), module));
+        let specializations = self.param_modules.iter().flat_map(|module| module.named_specializations());
+
+        for (name, module) in top_modules.chain(specializations) {
+            let path = path_dir.as_ref().join(format!("{}_inner.fir", name));
             let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
 
-            let name = module.get_module_name();
-            let module =
-                gen_module::<Firgen>(name.clone(), &module).map_err(|error| PackageError::Module { error })?.into();
-            let circuit = Circuit { modules: vec![module], main: name };
+            // A top-level module instantiation is named as in Virgen, not to collide with the module it instantiates.
+            let main = match &*module.inner {
+                lir::ModuleInner::ModuleInst(_) => format!("{}_inner", name),
+                _ => name,
+            };
+            let circuit = self.gen_fir_circuit(main, module)?;
 
             writeln!(file, "{}", circuit.to_string()).map_err(|error| PackageError::Fs { error })?;
         }
@@ -39,10 +83,31 @@ impl Package {
 
 impl From<codegen::Module<Firgen>> for fir::Module {
     fn from(module: codegen::Module<Firgen>) -> Self {
-        fir::Module { name: module.name, ports: module.ports, body: module.body }
+        let body = lower_range_connects(&module.ports, module.body);
+        fir::Module { name: module.name, ports: module.ports, body }
     }
 }
 
+/// Generates external module instantiated by the module instantiation.
+fn gen_ext_module(
+    name: String, module_inst: &lir::ModuleInst, reset_style: lir::ResetStyle,
+) -> Result<ExtModule, lir::ModuleError> {
+    let clocks = gen_module_inst_clocks(module_inst, reset_style.polarity);
+
+    let ports = gen_connections(module_inst, &mut Context::with_reset_style(reset_style))?
+        .into_iter()
+        .map(|(direction, name, width, ..)| {
+            if clocks.contains(&name) {
+                Port::input(name, Type::clock())
+            } else {
+                Port { name, direction, tpe: Type::uint(width) }
+            }
+        })
+        .collect();
+
+    Ok(ExtModule { name, ports, defname: module_inst.module_name.clone(), params: module_inst.params.clone() })
+}
+
 /// FIRRTL Generator
 #[derive(Default, Debug)]
 pub struct Firgen;
@@ -51,12 +116,14 @@ impl Codegen for Firgen {
     type Body = Statement;
     type Ports = Vec<Port>;
 
-    fn gen_port_decls(&self, module: &lir::Module) -> Result<Vec<Port>, lir::ModuleError> {
-        Ok(gen_port_decls(module)?
+    fn gen_port_decls(&self, module: &lir::Module, ctx: &Context) -> Result<Vec<Port>, lir::ModuleError> {
+        let clocks = gen_module_clocks(module, ctx.reset_style().polarity);
+
+        Ok(gen_port_decls(module, ctx)?
             .into_iter()
             .map(|(direction, width, name)| {
-                if name == "clk" {
-                    Port::input("clk".to_string(), Type::clock())
+                if clocks.contains(&name) {
+                    Port::input(name, Type::clock())
                 } else {
                     Port { name, direction, tpe: Type::uint(width) }
                 }
@@ -67,37 +134,44 @@ impl Codegen for Firgen {
     fn gen_module_composite(
         &self, module: &lir::CompositeModule, ctx: &mut Context,
     ) -> Result<Statement, lir::ModuleError> {
-        assert!(matches!(module.module_typ, lir::CompositeModuleTyp::OneToOne), "unsupported composite module type");
-
+        let clock_domain = ctx.enter_clock_domain(module.clock_domain.clone());
         let mut stmts = vec![];
 
-        for (name, shape) in gen_submodule_wires(module, ctx)? {
-            stmts.push(Statement::def_wire(name, Type::uint(shape.width())));
-        }
+        match module.module_typ {
+            lir::CompositeModuleTyp::OneToOne => {
+                for (name, shape) in gen_submodule_wires(module, ctx)? {
+                    stmts.push(Statement::def_wire(name, Type::uint(shape.width())));
+                }
 
-        let mut conts = self.gen_module_wiring(module, ctx.get_prefix())?;
-        stmts.append(&mut conts);
+                stmts.append(&mut self.gen_module_wiring(module, ctx.get_prefix())?);
+                stmts.append(&mut self.gen_submodules(module, ctx)?);
+            }
+            lir::CompositeModuleTyp::NToN(n) => {
+                let genvar_id = ctx.alloc_genvar_id();
 
-        // TODO: Add inner submodule's logic.
-        for (index, (submodule, _)) in module.submodules.iter().enumerate() {
-            let comp_name = submodule.get_module_name();
-            ctx.enter_scope(format!("{}_{}", comp_name, index));
-            match &*submodule.inner {
-                lir::ModuleInner::Composite(_, module) => {
-                    stmts.push(self.gen_module_composite(module, ctx)?);
-                }
-                lir::ModuleInner::Fsm(module) => {
-                    stmts.push(self.gen_module_fsm(module, ctx)?);
-                }
-                lir::ModuleInner::ModuleInst(module) => {
-                    stmts.push(self.gen_module_inst(module, ctx)?);
+                let mut module = module.clone();
+                module.module_typ = lir::CompositeModuleTyp::OneToOne;
+
+                // Arrays of modules are unrolled. Signals in each iteration are prefixed by the
+                // genvar and the iteration index.
+                for i in 0..n {
+                    let prefix = ctx.get_prefix();
+                    ctx.enter_scope(format!("{}_{}", genvar_id, i));
+
+                    for (name, shape) in gen_submodule_wires(&module, ctx)? {
+                        stmts.push(Statement::def_wire(name, Type::uint(shape.width())));
+                    }
+
+                    stmts.append(&mut self.gen_module_wiring_array(&module, prefix, ctx.get_prefix(), i)?);
+                    stmts.append(&mut self.gen_submodules(&module, ctx)?);
+
+                    ctx.leave_scope();
                 }
-                _ => todo!(),
             }
-            ctx.leave_scope();
         }
 
-        Ok(Statement::block(stmts))
+        ctx.leave_clock_domain(clock_domain);
+        Ok(gen_commented(module.source, Statement::block(stmts)))
     }
 
     /// Generates target code for FSM.
@@ -125,12 +199,11 @@ impl Codegen for Firgen {
         let state_init = gen_module_fsm_state_init(&module.state.into_expr(), &module.init.into_expr(), ctx)?
             .into_iter()
             .map(|(shape, name, init_value)| {
-                let init_value = LogicValues::new(
-                    init_value
-                        .iter()
-                        .map(|b| if *b == LogicValue::X { LogicValue::False } else { *b })
-                        .collect::<Vec<_>>(),
-                );
+                let init_value = if shape.width() == 0 {
+                    Expression::uint(0, 0)
+                } else {
+                    Expression::literal(init_value, Some(shape.width()))
+                };
 
                 (shape, name, init_value)
             })
@@ -139,16 +212,29 @@ impl Codegen for Firgen {
         // (1) state reg, wire (decls, conts)
         {
             let (mut decls, mut conts) = (Vec::new(), Vec::new());
+            let (clk, rst) = ctx.clock_signals();
+            let reset_style = ctx.reset_style();
+            let reset = match reset_style.polarity {
+                lir::ResetPolarity::ActiveHigh => Expression::reference(rst),
+                lir::ResetPolarity::ActiveLow => Expression::not(Expression::reference(rst)),
+            };
+            let reset = match ctx.reset_kind(module) {
+                lir::ResetKind::Sync => reset,
+                lir::ResetKind::Async => Expression::do_prim(PrimOp::AsAsyncReset, vec![reset], Vec::new()),
+                // Registers without reset are initialized by a reset which is never asserted.
+                lir::ResetKind::None => Expression::uint(0, 1),
+            };
 
             state_init.iter().for_each(|(shape, net_name, init_value)| {
                 let reg_name = format!("{}_reg", net_name);
 
                 // Add decls
-                decls.push(Statement::def_reg(
-                    reg_name.clone(),
-                    Type::uint(shape.width()),
-                    Expression::literal(init_value.clone(), Some(shape.width())),
-                ));
+                decls.push(Statement::DefRegister {
+                    name: reg_name.clone(),
+                    tpe: Type::uint(shape.width()),
+                    clock: Expression::reference(clk.clone()),
+                    reset: Some((reset.clone(), init_value.clone())),
+                });
                 decls.push(Statement::def_wire(net_name.clone(), Type::uint(shape.width())));
 
                 // Add conts
@@ -172,9 +258,6 @@ impl Codegen for Firgen {
 
         // (2) input, output logic
         {
-            assert_eq!(output_fwd.into_expr().port_decls().max_dim(), 1);
-            assert_eq!(input_bwd.into_expr().port_decls().max_dim(), 1);
-
             // input, output logic for output forward exprs
             stmts.push(self.gen_module_fsm_output(
                 "out".to_string(),
@@ -195,37 +278,102 @@ impl Codegen for Firgen {
         // (3) state update logic
         stmts.push(self.gen_module_fsm_state("st".to_string(), state.into_expr(), ctx, &mut HashMap::new())?);
 
-        Ok(Statement::block(stmts))
+        Ok(gen_commented(module.source, Statement::block(stmts)))
     }
 
     fn gen_module_inst(&self, module: &lir::ModuleInst, ctx: &mut Context) -> Result<Statement, lir::ModuleError> {
+        let inst_name = join_options("_", [ctx.get_prefix(), Some(module.inst_name.clone())]).unwrap();
+
         let connections = gen_connections(module, ctx)?
             .into_iter()
-            .map(|(dir, port, expr)| match dir {
-                Direction::Input => Statement::PartialConnect {
-                    loc: Expression::sub_field(Expression::reference(module.inst_name.clone()), port),
-                    expr: Expression::reference(expr),
-                },
-                Direction::Output => Statement::PartialConnect {
-                    loc: Expression::reference(expr),
-                    expr: Expression::sub_field(Expression::reference(module.inst_name.clone()), port),
-                },
+            .map(|(dir, port, _, expr, inverted)| {
+                let port = Expression::sub_field(Expression::reference(inst_name.clone()), port);
+                match dir {
+                    Direction::Input if inverted => {
+                        Statement::connect(port, Expression::not(Expression::reference(expr)))
+                    }
+                    Direction::Input => Statement::connect(port, Expression::reference(expr)),
+                    Direction::Output => Statement::connect(Expression::reference(expr), port),
+                }
             })
             .collect::<Vec<_>>();
 
-        let module_inst = Statement::def_inst(module.inst_name.clone(), module.get_module_name());
+        let module_inst = Statement::def_inst(inst_name, gen_module_inst_name(module));
 
-        Ok(Statement::block(vec![vec![module_inst], connections].concat()))
+        Ok(gen_commented(module.source, Statement::block(vec![vec![module_inst], connections].concat())))
     }
 
     fn gen_module_virtual(
-        &self, _module: &lir::VirtualModule, _composite_context_prefix: Option<String>, _ctx: &mut Context,
+        &self, module: &lir::VirtualModule, composite_context_prefix: Option<String>, ctx: &mut Context,
     ) -> Result<Self::Body, lir::ModuleError> {
-        todo!()
+        let conts = gen_virtual_wirings(module, composite_context_prefix, ctx.get_prefix())?
+            .into_iter()
+            .map(|(lvalue, lvalue_range, rvalue, rvalue_range)| {
+                gen_connect(
+                    lvalue,
+                    lvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                    rvalue,
+                    rvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                )
+            })
+            .collect();
+
+        Ok(Statement::block(conts))
     }
 }
 
 impl Firgen {
+    /// Generates FIRRTL code for registered modules and submodules in the module.
+    fn gen_submodules(
+        &self, module: &lir::CompositeModule, ctx: &mut Context,
+    ) -> Result<Vec<Statement>, lir::ModuleError> {
+        let mut stmts = vec![];
+
+        for (index, submodule) in module.registered_modules.iter().enumerate() {
+            let comp_name = submodule.get_module_name();
+            ctx.enter_scope(format!("registered_{}_{}", comp_name, index));
+            match &*submodule.inner {
+                lir::ModuleInner::ModuleInst(module) => {
+                    stmts.push(self.gen_module_inst(module, ctx)?);
+                }
+                lir::ModuleInner::Composite(_, module) => {
+                    stmts.push(self.gen_module_composite(module, ctx)?);
+                }
+                _ => {
+                    return Err(lir::ModuleError::RegisteredModule {
+                        module: module.name.clone(),
+                        submodule: comp_name,
+                    })
+                }
+            }
+            ctx.leave_scope();
+        }
+
+        let composite_context_prefix = ctx.get_prefix();
+
+        for (index, (submodule, _)) in module.submodules.iter().enumerate() {
+            let comp_name = submodule.get_module_name();
+            ctx.enter_scope(format!("{}_{}", comp_name, index));
+            match &*submodule.inner {
+                lir::ModuleInner::Composite(_, module) => {
+                    stmts.push(self.gen_module_composite(module, ctx)?);
+                }
+                lir::ModuleInner::Fsm(module) => {
+                    stmts.push(self.gen_module_fsm(module, ctx)?);
+                }
+                lir::ModuleInner::ModuleInst(module) => {
+                    stmts.push(self.gen_module_inst(module, ctx)?);
+                }
+                lir::ModuleInner::VirtualModule(module) => {
+                    stmts.push(self.gen_module_virtual(module, composite_context_prefix.clone(), ctx)?);
+                }
+            }
+            ctx.leave_scope();
+        }
+
+        Ok(stmts)
+    }
+
     /// Generates FIRRTL code for wirings in the module.
     fn gen_module_wiring(
         &self, module: &lir::CompositeModule, prefix: Option<String>,
@@ -233,21 +381,48 @@ impl Firgen {
         Ok(gen_wiring(module, prefix)?
             .into_iter()
             .map(|(lvalue, lvalue_range, rvalue, rvalue_range)| {
-                if lvalue_range.is_some() {
-                    todo!("FIRRTL does not support indexing in L-value expression");
-                }
-
-                match rvalue_range {
-                    Some((index, size)) => Statement::connect(
-                        Expression::reference(lvalue),
-                        Expression::bits(Expression::reference(rvalue), size * index + index - 1, size * index),
-                    ),
-                    None => Statement::connect(Expression::reference(lvalue), Expression::reference(rvalue)),
-                }
+                gen_connect(
+                    lvalue,
+                    lvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                    rvalue,
+                    rvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                )
             })
             .collect())
     }
 
+    /// Generates FIRRTL code for wirings in the `iteration`-th element of the array module.
+    ///
+    /// Signals of the array module are prefixed by `prefix`, and signals in the iteration are
+    /// prefixed by `iteration_prefix`.
+    fn gen_module_wiring_array(
+        &self, module: &lir::CompositeModule, prefix: Option<String>, iteration_prefix: Option<String>,
+        iteration: usize,
+    ) -> Result<Vec<Statement>, lir::ModuleError> {
+        let range = |generate: Option<usize>, range: Option<(usize, usize)>| match (generate, range) {
+            (Some(gen_size), Some((index, elt_size))) => Some((iteration * gen_size + index * elt_size, elt_size)),
+            (Some(gen_size), None) => Some((iteration * gen_size, gen_size)),
+            (None, Some((index, elt_size))) => Some((index * elt_size, elt_size)),
+            (None, None) => None,
+        };
+
+        Ok(izip!(gen_wiring_array(module, prefix)?, gen_wiring_array(module, iteration_prefix)?)
+            .map(
+                |(
+                    (lvalue, lvalue_generate, lvalue_range, rvalue, rvalue_generate, rvalue_range),
+                    (lvalue_inner, _, _, rvalue_inner, ..),
+                )| {
+                    gen_connect(
+                        if lvalue_generate.is_some() { lvalue } else { lvalue_inner },
+                        range(lvalue_generate, lvalue_range),
+                        if rvalue_generate.is_some() { rvalue } else { rvalue_inner },
+                        range(rvalue_generate, rvalue_range),
+                    )
+                },
+            )
+            .collect())
+    }
+
     /// Generates FSM output.
     ///
     /// Returns `Err` if types of `typ` and `output` are mismatched.
@@ -256,8 +431,12 @@ impl Firgen {
     ) -> Result<Statement, lir::ModuleError> {
         let (mut stmts, expr) = self.gen_expr(&output, ctx, cache)?;
 
-        let assignments =
-            match_value_typ_exprs(Some(format!("{}_{}", ctx.get_prefix().unwrap(), target)), output.port_decls(), expr);
+        let assignments = match_value_typ_exprs(
+            join_options("_", [ctx.get_prefix(), Some(target)]),
+            flatten_typ(output.port_decls()),
+            expr,
+        )
+        .ok_or_else(|| ctx.expr_structure_error(&output.port_decls()))?;
 
         let mut conts = assignments
             .into_iter()
@@ -279,8 +458,12 @@ impl Firgen {
     ) -> Result<Statement, lir::ModuleError> {
         let (mut stmts, exprs) = self.gen_expr(&state, ctx, cache)?;
 
-        let assignments =
-            match_value_typ_exprs(Some(format!("{}_{}", ctx.get_prefix().unwrap(), target)), state.port_decls(), exprs);
+        let assignments = match_value_typ_exprs(
+            join_options("_", [ctx.get_prefix(), Some(target)]),
+            flatten_typ(state.port_decls()),
+            exprs,
+        )
+        .ok_or_else(|| ctx.expr_structure_error(&state.port_decls()))?;
 
         let mut conts = assignments
             .into_iter()
@@ -310,9 +493,9 @@ impl Firgen {
 
         match expr {
             lir::Expr::X { .. } | lir::Expr::Constant { .. } => {
-                let literal = gen_expr_literal(expr).map(|s| {
+                let literal = gen_expr_literal(expr, ctx)?.map(|s| {
                     if s.is_empty() {
-                        0.into()
+                        Expression::uint(0, 0)
                     } else {
                         Expression::literal(s.clone(), Some(s.len()))
                     }
@@ -329,26 +512,35 @@ impl Firgen {
 
                 Ok((stmts, exprs_for_concat))
             }
+            // Zero-width fields are not declared.
             lir::Expr::Input { name, .. } => Ok((
                 Vec::new(),
                 CompositeExpr::from_typ(
                     expr.port_decls(),
                     join_options("_", [ctx.get_prefix(), name.clone()]).unwrap(),
                 )
-                .map(|(ident, _)| Expression::reference(ident)),
+                .map(|(ident, shape)| {
+                    if shape.width() == 0 {
+                        Expression::uint(0, 0)
+                    } else {
+                        Expression::reference(ident)
+                    }
+                }),
             )),
             lir::Expr::Member { inner, index } => {
                 let (stmts, exprs) = self.gen_expr(&inner.into_expr(), ctx, cache)?;
 
                 match exprs {
                     CompositeExpr::Struct(inner) => Ok((stmts, inner[*index].clone())),
-                    _ => panic!("cannot index bits"),
+                    CompositeExpr::Bits(_) => Err(ctx.expr_structure_error(&inner.into_expr().port_decls())),
                 }
             }
             lir::Expr::Struct { inner } => {
                 let (stmts, exprs) = inner
                     .iter()
-                    .map(|(_, inner)| self.gen_expr(&inner.into_expr(), ctx, cache).unwrap())
+                    .map(|(_, inner)| self.gen_expr(&inner.into_expr(), ctx, cache))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
                     .fold((Vec::new(), Vec::new()), |mut acc, mut x| {
                         acc.0.append(&mut x.0);
                         acc.1.push(x.1);
@@ -357,6 +549,21 @@ impl Firgen {
 
                 Ok((stmts, CompositeExpr::Struct(exprs)))
             }
+            lir::Expr::Resize { inner, .. } => {
+                let typ_inner = inner.into_expr().port_decls();
+                let (stmts, exprs_for_inner) = self.gen_expr(&inner.into_expr(), ctx, cache)?;
+
+                let shapes = CompositeExpr::from(typ_inner.clone()).try_zip(
+                    CompositeExpr::from(expr.port_decls()),
+                    &typ_inner,
+                    ctx,
+                )?;
+                let exprs = exprs_for_inner
+                    .try_zip(shapes, &typ_inner, ctx)?
+                    .map(|(expr, ((_, from), (_, to)))| resize(expr, from.width(), to.width()));
+
+                Ok((stmts, exprs))
+            }
             lir::Expr::LeftShift { inner, rhs } => {
                 self.gen_expr_binary_op(lir::BinaryOp::ShiftLeft, &inner.into_expr(), &rhs.into_expr(), ctx, cache)
             }
@@ -367,15 +574,39 @@ impl Firgen {
             lir::Expr::BinaryOp { op, lhs, rhs } => {
                 self.gen_expr_binary_op(*op, &lhs.into_expr(), &rhs.into_expr(), ctx, cache)
             }
+            lir::Expr::Fold { inner, typ_elt, func, init, acc, inner_slice } => self.gen_expr_fold(
+                expr,
+                &inner.into_expr(),
+                typ_elt,
+                &init.into_expr(),
+                &acc.into_expr(),
+                &inner_slice.into_expr(),
+                &func.into_expr(),
+                ctx,
+                cache,
+            ),
+            lir::Expr::TreeFold { inner, op, lhs, rhs, .. } => self.gen_expr_tree_fold(
+                expr,
+                &inner.into_expr(),
+                &op.into_expr(),
+                &lhs.into_expr(),
+                &rhs.into_expr(),
+                ctx,
+                cache,
+            ),
             lir::Expr::Map { inner, typ_elt, func } => {
                 self.gen_expr_map(&inner.into_expr(), typ_elt, &func.into_expr(), ctx, cache)
             }
-            lir::Expr::Get { inner, typ_elt, index } => {
+            lir::Expr::Get { inner, typ_elt, index } | lir::Expr::GetVarArray { inner, typ_elt, index } => {
                 let (stmts_for_inner, exprs_for_inner) = self.gen_expr_to_idents(&inner.into_expr(), ctx, cache)?;
                 let (stmts_for_index, exprs_for_index) = self.gen_expr(&index.into_expr(), ctx, cache)?;
 
-                let exprs_for_elt =
-                    self.indexing_exprs(exprs_for_inner, exprs_for_index.into_expr(), typ_elt.clone())?;
+                let exprs_for_elt = self.indexing_exprs(
+                    exprs_for_inner,
+                    exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?,
+                    typ_elt.clone(),
+                    ctx,
+                )?;
                 let (stmts_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), exprs_for_elt, ctx, cache)?;
 
                 let stmts = [stmts_for_inner, stmts_for_index, stmts_for_output].concat();
@@ -386,8 +617,13 @@ impl Firgen {
                 let (stmts_for_inner, exprs_for_inner) = self.gen_expr_to_idents(&inner.into_expr(), ctx, cache)?;
                 let (stmts_for_from, exprs_for_from) = self.gen_expr(&from.into_expr(), ctx, cache)?;
 
-                let exprs_for_elts =
-                    self.range_indexing_exprs(exprs_for_inner, exprs_for_from.into_expr(), *size, typ_elt.clone())?;
+                let exprs_for_elts = self.range_indexing_exprs(
+                    exprs_for_inner,
+                    exprs_for_from.try_into_expr(&from.into_expr().port_decls(), ctx)?,
+                    *size,
+                    typ_elt.clone(),
+                    ctx,
+                )?;
                 let (stmts_for_output, exprs_for_output) =
                     self.alloc_exprs(expr.clone(), exprs_for_elts, ctx, cache)?;
 
@@ -400,14 +636,18 @@ impl Firgen {
                 let (stmts_for_rhs, exprs_for_rhs) = self.gen_expr(&rhs.into_expr(), ctx, cache)?;
 
                 let stmts = [stmts_for_lhs, stmts_for_rhs].concat();
-                let exprs = exprs_for_lhs.zip(exprs_for_rhs).map(|(lhs, rhs)| Expression::cat(rhs, lhs));
+                let exprs = exprs_for_lhs
+                    .try_zip(exprs_for_rhs, &lhs.into_expr().port_decls(), ctx)?
+                    .map(|(lhs, rhs)| Expression::cat(rhs, lhs));
 
                 Ok((stmts, exprs))
             }
             lir::Expr::Zip { inner, .. } => {
                 let (stmts_for_inner, exprs_for_inner) = inner
                     .iter()
-                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache).expect("gen_expr: zip"))
+                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
                     .fold((Vec::new(), Vec::new()), |(mut acc_stmts, mut acc_exprs), (stmts, exprs)| {
                         acc_stmts.push(stmts);
                         acc_exprs.push(exprs);
@@ -425,14 +665,38 @@ impl Firgen {
             lir::Expr::Concat { inner, .. } => self.gen_expr(&inner.into_expr(), ctx, cache),
             lir::Expr::Chunk { inner, .. } => self.gen_expr(&inner.into_expr(), ctx, cache),
             lir::Expr::Repr { inner } => self.gen_expr(&inner.into_expr(), ctx, cache),
+            lir::Expr::Sum { inner, width_elt } => {
+                let (stmts_for_inner, exprs_for_inner) = self.gen_expr_to_idents(&inner.into_expr(), ctx, cache)?;
+                let expr_for_inner = exprs_for_inner.try_into_expr(&inner.into_expr().port_decls(), ctx)?;
+
+                let mut stmts = stmts_for_inner;
+                let mut expr_for_acc = Expression::uint(0, *width_elt);
+
+                if *width_elt > 0 {
+                    for i in 0..(inner.into_expr().width() / width_elt) {
+                        let ident = ctx.alloc_temp_id();
+                        stmts.push(Statement::def_node(
+                            ident.clone(),
+                            Expression::tail(
+                                Expression::add(expr_for_acc, slice(expr_for_inner.clone(), i * width_elt, *width_elt)),
+                                1,
+                            ),
+                        ));
+                        expr_for_acc = Expression::reference(ident);
+                    }
+                }
+
+                Ok((stmts, CompositeExpr::Bits(expr_for_acc)))
+            }
             lir::Expr::Cond { cond, lhs, rhs } => {
                 let (stmts_for_cond, exprs_for_cond) = self.gen_expr(&cond.into_expr(), ctx, cache)?;
                 let (stmts_for_lhs, exprs_for_lhs) = self.gen_expr(&lhs.into_expr(), ctx, cache)?;
                 let (stmts_for_rhs, exprs_for_rhs) = self.gen_expr(&rhs.into_expr(), ctx, cache)?;
 
+                let expr_for_cond = exprs_for_cond.try_into_expr(&cond.into_expr().port_decls(), ctx)?;
                 let exprs_for_mux = exprs_for_lhs
-                    .zip(exprs_for_rhs)
-                    .map(|(lhs, rhs)| Expression::mux(exprs_for_cond.clone().into_expr(), lhs, rhs));
+                    .try_zip(exprs_for_rhs, &lhs.into_expr().port_decls(), ctx)?
+                    .map(|(lhs, rhs)| Expression::mux(expr_for_cond.clone(), lhs, rhs));
 
                 let (stmts_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), exprs_for_mux, ctx, cache)?;
 
@@ -440,7 +704,110 @@ impl Firgen {
 
                 Ok((stmts, exprs_for_output))
             }
-            _ => unimplemented!("{:?}", expr),
+            lir::Expr::Set { inner, index, elt } | lir::Expr::SetVarArray { inner, index, elt } => {
+                let typ_elt = elt.into_expr().port_decls();
+
+                let (stmts_for_inner, exprs_for_inner) = self.gen_expr_to_idents(&inner.into_expr(), ctx, cache)?;
+                let (stmts_for_index, exprs_for_index) = self.gen_expr(&index.into_expr(), ctx, cache)?;
+                let (stmts_for_elt, exprs_for_elt) = self.gen_expr_to_idents(&elt.into_expr(), ctx, cache)?;
+
+                let exprs_for_elts = self.set_range_exprs(
+                    exprs_for_inner,
+                    inner.into_expr().width(),
+                    exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?,
+                    exprs_for_elt,
+                    1,
+                    typ_elt,
+                    ctx,
+                )?;
+                let (stmts_for_output, exprs_for_output) =
+                    self.alloc_exprs(expr.clone(), exprs_for_elts, ctx, cache)?;
+
+                let stmts = [stmts_for_inner, stmts_for_index, stmts_for_elt, stmts_for_output].concat();
+
+                Ok((stmts, exprs_for_output))
+            }
+            lir::Expr::SetRange { inner, typ_elt, index, elts } => {
+                let (stmts_for_inner, exprs_for_inner) = self.gen_expr_to_idents(&inner.into_expr(), ctx, cache)?;
+                let (stmts_for_index, exprs_for_index) = self.gen_expr(&index.into_expr(), ctx, cache)?;
+                let (stmts_for_elts, exprs_for_elts) = self.gen_expr_to_idents(&elts.into_expr(), ctx, cache)?;
+
+                let exprs_for_output = self.set_range_exprs(
+                    exprs_for_inner,
+                    inner.into_expr().width(),
+                    exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?,
+                    exprs_for_elts,
+                    if typ_elt.width() == 0 { 0 } else { elts.into_expr().width() / typ_elt.width() },
+                    typ_elt.clone(),
+                    ctx,
+                )?;
+                let (stmts_for_output, exprs_for_output) =
+                    self.alloc_exprs(expr.clone(), exprs_for_output, ctx, cache)?;
+
+                let stmts = [stmts_for_inner, stmts_for_index, stmts_for_elts, stmts_for_output].concat();
+
+                Ok((stmts, exprs_for_output))
+            }
+            lir::Expr::Case { case_expr, case_items, default } => {
+                let typ = expr.port_decls();
+                let typ_case_expr = case_expr.into_expr().port_decls();
+
+                let (mut stmts, exprs_for_case_expr) = self.gen_expr(&case_expr.into_expr(), ctx, cache)?;
+                let expr_for_case_expr = exprs_for_case_expr.try_into_expr(&typ_case_expr, ctx)?;
+
+                // Case items are prioritized in order, so the mux chain is built from the last one.
+                let mut exprs_for_output = match default {
+                    Some(default) => {
+                        let (mut stmts_for_default, exprs_for_default) =
+                            self.gen_expr(&default.into_expr(), ctx, cache)?;
+                        stmts.append(&mut stmts_for_default);
+                        exprs_for_default
+                    }
+                    None => CompositeExpr::from(typ.clone()).map(|(_, shape)| Expression::uint(0, shape.width())),
+                };
+
+                for (cond, item) in case_items.iter().rev() {
+                    let (mut stmts_for_cond, exprs_for_cond) = self.gen_expr(&cond.into_expr(), ctx, cache)?;
+                    let (mut stmts_for_item, exprs_for_item) = self.gen_expr(&item.into_expr(), ctx, cache)?;
+
+                    let expr_for_cond = Expression::cmp(
+                        PrimOp::Eq,
+                        expr_for_case_expr.clone(),
+                        exprs_for_cond.try_into_expr(&typ_case_expr, ctx)?,
+                    );
+
+                    stmts.append(&mut stmts_for_cond);
+                    stmts.append(&mut stmts_for_item);
+                    exprs_for_output = exprs_for_item
+                        .try_zip(exprs_for_output, &typ, ctx)?
+                        .map(|(item, acc)| Expression::mux(expr_for_cond.clone(), item, acc));
+                }
+
+                let (mut stmts_for_output, exprs_for_output) =
+                    self.alloc_exprs(expr.clone(), exprs_for_output, ctx, cache)?;
+                stmts.append(&mut stmts_for_output);
+
+                Ok((stmts, exprs_for_output))
+            }
+            // Signedness is not modeled, so `$signed` is not supported either.
+            lir::Expr::Call { func_name, .. } => Err(lir::ModuleError::Unsupported {
+                module: ctx.scope_name(),
+                what: format!("function call `{}`", func_name),
+            }),
+            lir::Expr::ConcatArray { inner, elt_typ } => {
+                let (stmts, exprs) = inner
+                    .iter()
+                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .fold((Vec::new(), Vec::new()), |mut acc, mut x| {
+                        acc.0.append(&mut x.0);
+                        acc.1.push(x.1);
+                        acc
+                    });
+
+                Ok((stmts, concat_exprs(exprs, elt_typ, ctx)?))
+            }
         }
     }
 
@@ -449,13 +816,20 @@ impl Firgen {
     ) -> Result<(Vec<Statement>, CompositeExpr<Expression>), lir::ModuleError> {
         let (stmts_for_inner, exprs_for_inner) = self.gen_expr(inner, ctx, cache)?;
 
-        let expr = Expression::do_prim(op.into(), vec![exprs_for_inner.into_expr()], Vec::new());
+        let expr =
+            Expression::do_prim(op.into(), vec![exprs_for_inner.try_into_expr(&inner.port_decls(), ctx)?], Vec::new());
 
         let exprs = CompositeExpr::Bits(expr);
 
         Ok((stmts_for_inner, exprs))
     }
 
+    /// Generates binary operation.
+    ///
+    /// # Note
+    ///
+    /// Result widths of FIRRTL primitive operations are adjusted to the widths of the operations in
+    /// ShakeFlow, e.g., subtraction is truncated to the width of the operands.
     fn gen_expr_binary_op(
         &self, op: lir::BinaryOp, lhs: &lir::Expr, rhs: &lir::Expr, ctx: &mut Context,
         cache: &mut HashMap<lir::Expr, String>,
@@ -463,13 +837,26 @@ impl Firgen {
         let (stmts_for_lhs, exprs_for_lhs) = self.gen_expr(lhs, ctx, cache)?;
         let (stmts_for_rhs, exprs_for_rhs) = self.gen_expr(rhs, ctx, cache)?;
 
+        let expr_for_lhs = exprs_for_lhs.try_into_expr(&lhs.port_decls(), ctx)?;
+        let expr_for_rhs = exprs_for_rhs.try_into_expr(&rhs.port_decls(), ctx)?;
+
         let expr = match op {
-            lir::BinaryOp::Eq => {
-                let expr =
-                    Expression::binary_bitwise(PrimOp::Xor, exprs_for_lhs.into_expr(), exprs_for_rhs.into_expr());
-                Expression::not(expr)
-            }
-            _ => Expression::do_prim(op.into(), vec![exprs_for_lhs.into_expr(), exprs_for_rhs.into_expr()], Vec::new()),
+            lir::BinaryOp::Add => Expression::add(expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Sub => Expression::tail(Expression::sub(expr_for_lhs, expr_for_rhs), 1),
+            lir::BinaryOp::Mul => Expression::mul(expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Div => Expression::div(expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Mod => Expression::pad(Expression::rem(expr_for_lhs, expr_for_rhs), rhs.width()),
+            lir::BinaryOp::And => Expression::binary_bitwise(PrimOp::And, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Or => Expression::binary_bitwise(PrimOp::Or, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Xor => Expression::binary_bitwise(PrimOp::Xor, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Eq => Expression::not(Expression::binary_bitwise(PrimOp::Xor, expr_for_lhs, expr_for_rhs)),
+            lir::BinaryOp::EqArithmetic => Expression::cmp(PrimOp::Eq, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Less => Expression::cmp(PrimOp::Lt, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::Greater => Expression::cmp(PrimOp::Gt, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::LessEq => Expression::cmp(PrimOp::Leq, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::GreaterEq => Expression::cmp(PrimOp::Geq, expr_for_lhs, expr_for_rhs),
+            lir::BinaryOp::ShiftLeft => shift_left(expr_for_lhs, lhs.width(), expr_for_rhs, rhs.width()),
+            lir::BinaryOp::ShiftRight => Expression::dshr(expr_for_lhs, expr_for_rhs),
         };
 
         let stmts = [stmts_for_lhs, stmts_for_rhs].concat();
@@ -494,11 +881,11 @@ impl Firgen {
             let stmts_for_loop_input = {
                 let exprs = CompositeExpr::from_typ(typ_elt.clone(), loop_body_input_prefix.clone());
 
-                let exprs_for_elt = self.indexing_exprs(exprs_for_inner.clone(), i.into(), typ_elt.clone())?;
+                let exprs_for_elt = self.element_exprs(exprs_for_inner.clone(), i, typ_elt.clone(), ctx)?;
 
                 exprs
                     .clone()
-                    .zip(exprs_for_elt)
+                    .try_zip(exprs_for_elt, typ_elt, ctx)?
                     .iter()
                     .map(|((ident, _), expr_for_elt)| Statement::def_node(ident, expr_for_elt))
                     .collect::<Vec<_>>()
@@ -516,11 +903,116 @@ impl Firgen {
         }
 
         let stmts = [stmts_for_inner, stmts_for_output.concat()].concat();
-        let exprs = self.concat_exprs(exprs_for_output)?;
+        let exprs = concat_exprs(exprs_for_output, &func.port_decls(), ctx)?;
 
         Ok((stmts, exprs))
     }
 
+    /// Generates fold by unrolling the loop. Each iteration binds the accumulator and the element to
+    /// the inputs of `func`.
+    #[allow(clippy::too_many_arguments)]
+    fn gen_expr_fold(
+        &self, expr: &lir::Expr, inner: &lir::Expr, typ_elt: &lir::PortDecls, init: &lir::Expr, acc: &lir::Expr,
+        inner_slice: &lir::Expr, func: &lir::Expr, ctx: &mut Context, cache: &mut HashMap<lir::Expr, String>,
+    ) -> Result<(Vec<Statement>, CompositeExpr<Expression>), lir::ModuleError> {
+        let (stmts_for_init, exprs_for_init) = self.gen_expr(init, ctx, cache)?;
+        let (stmts_for_inner, exprs_for_inner) = self.gen_expr_to_idents(inner, ctx, cache)?;
+
+        let loop_count = if typ_elt.width() == 0 { 0 } else { inner.width() / typ_elt.width() };
+
+        let mut stmts = [stmts_for_init, stmts_for_inner].concat();
+        let mut exprs_for_acc = exprs_for_init;
+
+        for i in 0..loop_count {
+            let fold_body_input_prefix = ctx.alloc_temp_id();
+
+            let mut ctx = Context::new();
+            ctx.enter_scope(fold_body_input_prefix);
+            let mut cache = HashMap::new();
+
+            let (_, idents_for_acc) = self.gen_expr(acc, &mut ctx, &mut cache)?;
+            let (_, idents_for_inner_slice) = self.gen_expr(inner_slice, &mut ctx, &mut cache)?;
+
+            let mut stmts_for_acc = self.bind_exprs(idents_for_acc, exprs_for_acc, &acc.port_decls(), &ctx)?;
+            let mut stmts_for_inner_slice = self.bind_exprs(
+                idents_for_inner_slice,
+                self.element_exprs(exprs_for_inner.clone(), i, typ_elt.clone(), &ctx)?,
+                typ_elt,
+                &ctx,
+            )?;
+            let (mut stmts_for_loop_body, exprs_for_loop_body) = self.gen_expr(func, &mut ctx, &mut cache)?;
+
+            stmts.append(&mut stmts_for_acc);
+            stmts.append(&mut stmts_for_inner_slice);
+            stmts.append(&mut stmts_for_loop_body);
+            exprs_for_acc = exprs_for_loop_body;
+        }
+
+        let (mut stmts_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), exprs_for_acc, ctx, cache)?;
+        stmts.append(&mut stmts_for_output);
+
+        Ok((stmts, exprs_for_output))
+    }
+
+    /// Generates tree fold by unrolling the levels of the tree. An element without its pair is
+    /// carried to the next level.
+    #[allow(clippy::too_many_arguments)]
+    fn gen_expr_tree_fold(
+        &self, expr: &lir::Expr, inner: &lir::Expr, op: &lir::Expr, lhs: &lir::Expr, rhs: &lir::Expr,
+        ctx: &mut Context, cache: &mut HashMap<lir::Expr, String>,
+    ) -> Result<(Vec<Statement>, CompositeExpr<Expression>), lir::ModuleError> {
+        let typ_elt = lhs.port_decls();
+
+        let (mut stmts, exprs_for_inner) = self.gen_expr_to_idents(inner, ctx, cache)?;
+
+        let num_elts = if typ_elt.width() == 0 { 0 } else { inner.width() / typ_elt.width() };
+        let mut exprs_for_elts = (0..num_elts)
+            .map(|i| self.element_exprs(exprs_for_inner.clone(), i, typ_elt.clone(), ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        while exprs_for_elts.len() > 1 {
+            let mut exprs_for_level = Vec::new();
+
+            for pair in exprs_for_elts.chunks(2) {
+                let (exprs_for_lhs, exprs_for_rhs) = match pair {
+                    [exprs_for_lhs, exprs_for_rhs] => (exprs_for_lhs.clone(), exprs_for_rhs.clone()),
+                    _ => {
+                        exprs_for_level.push(pair[0].clone());
+                        continue;
+                    }
+                };
+
+                let tree_fold_prefix = ctx.alloc_temp_id();
+
+                let mut ctx = Context::new();
+                ctx.enter_scope(tree_fold_prefix);
+                let mut cache = HashMap::new();
+
+                let (_, idents_for_lhs) = self.gen_expr(lhs, &mut ctx, &mut cache)?;
+                let (_, idents_for_rhs) = self.gen_expr(rhs, &mut ctx, &mut cache)?;
+
+                let mut stmts_for_lhs = self.bind_exprs(idents_for_lhs, exprs_for_lhs, &typ_elt, &ctx)?;
+                let mut stmts_for_rhs = self.bind_exprs(idents_for_rhs, exprs_for_rhs, &typ_elt, &ctx)?;
+                let (mut stmts_for_op, exprs_for_op) = self.gen_expr(op, &mut ctx, &mut cache)?;
+
+                stmts.append(&mut stmts_for_lhs);
+                stmts.append(&mut stmts_for_rhs);
+                stmts.append(&mut stmts_for_op);
+                exprs_for_level.push(exprs_for_op);
+            }
+
+            exprs_for_elts = exprs_for_level;
+        }
+
+        let exprs_for_fold = exprs_for_elts.pop().unwrap_or_else(|| {
+            CompositeExpr::from(typ_elt.clone()).map(|(_, shape)| Expression::uint(0, shape.width()))
+        });
+        let (mut stmts_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), exprs_for_fold, ctx, cache)?;
+        stmts.append(&mut stmts_for_output);
+
+        Ok((stmts, exprs_for_output))
+    }
+
     fn gen_expr_multiple_concat(
         &self, expr_for_elt: &lir::Expr, exprs_for_elt: CompositeExpr<Expression>, count: usize, ctx: &mut Context,
         cache: &mut HashMap<lir::Expr, String>,
@@ -537,11 +1029,11 @@ impl Firgen {
 
         let (stmts_for_output, exprs_for_output) = self.alloc_exprs(
             lir::Expr::Repeat { inner: lir::ExprId::alloc_expr(Merkle::new(expr_for_elt.clone())), count },
-            self.concat_exprs(if count % 2 != 0 {
-                vec![exprs.clone(), exprs, exprs_for_elt]
-            } else {
-                vec![exprs.clone(), exprs]
-            })?,
+            concat_exprs(
+                if count % 2 != 0 { vec![exprs.clone(), exprs, exprs_for_elt] } else { vec![exprs.clone(), exprs] },
+                &expr_for_elt.port_decls(),
+                ctx,
+            )?,
             ctx,
             cache,
         )?;
@@ -567,13 +1059,24 @@ impl Firgen {
         Ok((stmts, new_exprs))
     }
 
+    /// Returns the `index`-th element of `exprs`, whose elements are of type `typ_elt`.
+    fn element_exprs(
+        &self, exprs: CompositeExpr<Expression>, index: usize, typ_elt: lir::PortDecls, ctx: &Context,
+    ) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
+        let exprs_for_elt = exprs
+            .try_zip(typ_elt.clone().into(), &typ_elt, ctx)?
+            .map(|(expr, (_, shape))| slice(expr, index * shape.width(), shape.width()));
+
+        Ok(exprs_for_elt)
+    }
+
     fn indexing_exprs(
-        &self, exprs: CompositeExpr<Expression>, index: Expression, typ_elt: lir::PortDecls,
+        &self, exprs: CompositeExpr<Expression>, index: Expression, typ_elt: lir::PortDecls, ctx: &Context,
     ) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
-        let exprs_for_elt = exprs.zip(typ_elt.into()).map(|(expr, (_, shape))| {
+        let exprs_for_elt = exprs.try_zip(typ_elt.clone().into(), &typ_elt, ctx)?.map(|(expr, (_, shape))| {
             let shift_amount = Expression::mul(index.clone(), shape.width().into());
 
-            Expression::bits(Expression::dshr(expr, shift_amount), shape.width() - 1, 0)
+            slice(Expression::dshr(expr, shift_amount), 0, shape.width())
         });
 
         Ok(exprs_for_elt)
@@ -581,27 +1084,62 @@ impl Firgen {
 
     fn range_indexing_exprs(
         &self, exprs: CompositeExpr<Expression>, base: Expression, offset: usize, typ_elt: lir::PortDecls,
+        ctx: &Context,
     ) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
-        let exprs_for_elts = exprs.zip(typ_elt.into()).map(|(expr, (_, shape))| {
+        let exprs_for_elts = exprs.try_zip(typ_elt.clone().into(), &typ_elt, ctx)?.map(|(expr, (_, shape))| {
             let shift_amount = Expression::mul(base.clone(), shape.width().into());
 
-            Expression::bits(Expression::dshr(expr, shift_amount), shape.width() * offset - 1, 0)
+            slice(Expression::dshr(expr, shift_amount), 0, shape.width() * offset)
         });
 
         Ok(exprs_for_elts)
     }
 
+    /// Returns `exprs` whose `count` elements from `index` are replaced by `elts`. The elements are
+    /// of type `typ_elt`, and `width` is the width of `exprs`.
+    #[allow(clippy::too_many_arguments)]
+    fn set_range_exprs(
+        &self, exprs: CompositeExpr<Expression>, width: usize, index: Expression, elts: CompositeExpr<Expression>,
+        count: usize, typ_elt: lir::PortDecls, ctx: &Context,
+    ) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
+        if typ_elt.width() == 0 {
+            return Ok(exprs);
+        }
+
+        let num_elts = width / typ_elt.width();
+        let exprs_for_output = exprs.try_zip(elts, &typ_elt, ctx)?.try_zip(typ_elt.clone().into(), &typ_elt, ctx)?.map(
+            |((expr, elts), (_, shape))| {
+                let width = shape.width();
+                cat_exprs(
+                    (0..num_elts)
+                        .map(|i| {
+                            (0..count.min(i + 1)).fold(slice(expr.clone(), i * width, width), |acc, j| {
+                                Expression::mux(
+                                    Expression::cmp(PrimOp::Eq, index.clone(), (i - j).into()),
+                                    slice(elts.clone(), j * width, width),
+                                    acc,
+                                )
+                            })
+                        })
+                        .collect(),
+                )
+            },
+        );
+
+        Ok(exprs_for_output)
+    }
+
     fn alloc_exprs(
         &self, expr: lir::Expr, value: CompositeExpr<Expression>, ctx: &mut Context,
         cache: &mut HashMap<lir::Expr, String>,
     ) -> Result<(Vec<Statement>, CompositeExpr<Expression>), lir::ModuleError> {
         let typ = expr.port_decls();
         let prefix = ctx.alloc_temp_id();
-        let exprs = CompositeExpr::from_typ(typ, prefix.clone());
+        let exprs = CompositeExpr::from_typ(typ.clone(), prefix.clone());
 
         let stmts = exprs
             .clone()
-            .zip(value)
+            .try_zip(value, &typ, ctx)?
             .iter()
             .map(|((ident, _), rhs)| Statement::def_node(ident, rhs))
             .collect::<Vec<_>>();
@@ -612,12 +1150,318 @@ impl Firgen {
         Ok((stmts, exprs))
     }
 
-    fn concat_exprs(
-        &self, exprs: Vec<CompositeExpr<Expression>>,
-    ) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
-        Ok(exprs
-            .into_iter()
-            .reduce(|acc, expr| expr.zip(acc).map(|(expr, acc)| Expression::cat(expr, acc)))
-            .expect("concat_exprs: no element"))
+    /// Binds `value` to the references `idents`, e.g., inputs of the loop body.
+    fn bind_exprs(
+        &self, idents: CompositeExpr<Expression>, value: CompositeExpr<Expression>, typ: &lir::PortDecls, ctx: &Context,
+    ) -> Result<Vec<Statement>, lir::ModuleError> {
+        idents
+            .try_zip(value, typ, ctx)?
+            .iter()
+            .filter_map(|(ident, value)| match ident {
+                Expression::Reference { name } => Some(Ok(Statement::def_node(name, value))),
+                // Zero-width inputs are literals.
+                Expression::Literal { .. } => None,
+                _ => Some(Err(ctx.expr_structure_error(typ))),
+            })
+            .collect()
+    }
+}
+
+/// Concatenates `exprs`, whose elements are of type `typ_elt`.
+fn concat_exprs(
+    exprs: Vec<CompositeExpr<Expression>>, typ_elt: &lir::PortDecls, ctx: &Context,
+) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
+    // Halves are concatenated separately to keep the nesting of expressions shallow.
+    match exprs.len() {
+        0 => Ok(CompositeExpr::from(typ_elt.clone()).map(|_| Expression::uint(0, 0))),
+        1 => Ok(exprs.into_iter().next().unwrap()),
+        len => {
+            let mut exprs = exprs;
+            let exprs_hi = exprs.split_off(len / 2);
+            let exprs_lo = concat_exprs(exprs, typ_elt, ctx)?;
+            let exprs_hi = concat_exprs(exprs_hi, typ_elt, ctx)?;
+
+            Ok(exprs_hi.try_zip(exprs_lo, typ_elt, ctx)?.map(|(hi, lo)| Expression::cat(hi, lo)))
+        }
+    }
+}
+
+/// Returns `width` bits of `expr` from `lo`.
+fn slice(expr: Expression, lo: usize, width: usize) -> Expression {
+    if width == 0 {
+        Expression::uint(0, 0)
+    } else {
+        Expression::bits(expr, lo + width - 1, lo)
+    }
+}
+
+/// Zero-extends or truncates `expr` of width `from` to width `to`.
+fn resize(expr: Expression, from: usize, to: usize) -> Expression {
+    match from.cmp(&to) {
+        ::std::cmp::Ordering::Less => Expression::pad(expr, to),
+        ::std::cmp::Ordering::Equal => expr,
+        ::std::cmp::Ordering::Greater => slice(expr, 0, to),
+    }
+}
+
+/// Concatenates `exprs`, whose first element is placed at the least significant bits.
+fn cat_exprs(mut exprs: Vec<Expression>) -> Expression {
+    match exprs.len() {
+        0 => Expression::uint(0, 0),
+        1 => exprs.pop().unwrap(),
+        len => {
+            let exprs_hi = exprs.split_off(len / 2);
+            Expression::cat(cat_exprs(exprs_hi), cat_exprs(exprs))
+        }
+    }
+}
+
+/// Shifts `expr` of width `width` to the left by `amount` of width `width_amount`.
+///
+/// The result of `dshl` is as wide as `width + 2^width_amount - 1`, so upper bits of the amount are
+/// checked separately.
+fn shift_left(expr: Expression, width: usize, amount: Expression, width_amount: usize) -> Expression {
+    if width == 0 {
+        return Expression::uint(0, 0);
+    }
+
+    let width_shift = clog2(width);
+    if width_amount <= width_shift {
+        return slice(Expression::dshl(expr, amount), 0, width);
+    }
+
+    Expression::mux(
+        Expression::bitwise_reduction(PrimOp::Orr, slice(amount.clone(), width_shift, width_amount - width_shift)),
+        Expression::uint(0, width),
+        slice(Expression::dshl(expr, slice(amount, 0, width_shift)), 0, width),
+    )
+}
+
+/// Generates connection, where ranges are the offset and width of the sliced signals.
+///
+/// Connections to slices are lowered by `lower_range_connects`.
+fn gen_connect(
+    lvalue: String, lvalue_range: Option<(usize, usize)>, rvalue: String, rvalue_range: Option<(usize, usize)>,
+) -> Statement {
+    let loc = match lvalue_range {
+        Some((_, 0)) => return Statement::EmptyStmt,
+        Some((lo, width)) => slice(Expression::reference(lvalue), lo, width),
+        None => Expression::reference(lvalue),
+    };
+    let expr = match rvalue_range {
+        Some((lo, width)) => slice(Expression::reference(rvalue), lo, width),
+        None => Expression::reference(rvalue),
+    };
+
+    Statement::connect(loc, expr)
+}
+
+/// Lowers connections to slices of wires and output ports, which are not allowed in FIRRTL, into
+/// a connection of the concatenated value. Unconnected bits are zero, and wires and output ports
+/// that are never connected are marked as invalid.
+fn lower_range_connects(ports: &[Port], body: Statement) -> Statement {
+    let mut sinks = ports
+        .iter()
+        .filter_map(|port| match (&port.direction, &port.tpe) {
+            (Direction::Output, Type::UIntType(width)) => Some((port.name.clone(), *width)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    collect_wires(&body, &mut sinks);
+
+    let mut slices = HashMap::new();
+    let mut connected = HashSet::new();
+    let body = split_range_connects(body, &mut slices, &mut connected);
+
+    let mut stmts = vec![body];
+    for (name, width) in sinks {
+        match slices.remove(&name) {
+            Some(mut slices) => {
+                slices.sort_by_key(|(lo, ..)| *lo);
+
+                let mut exprs = Vec::new();
+                let mut offset = 0;
+                for (lo, hi, expr) in slices {
+                    if lo > offset {
+                        exprs.push(Expression::uint(0, lo - offset));
+                    }
+                    exprs.push(expr);
+                    offset = hi + 1;
+                }
+                if width > offset {
+                    exprs.push(Expression::uint(0, width - offset));
+                }
+
+                stmts.push(Statement::connect(Expression::reference(name), cat_exprs(exprs)));
+            }
+            None if !connected.contains(&name) => {
+                stmts.push(Statement::IsInvalid { expr: Expression::reference(name) });
+            }
+            None => {}
+        }
+    }
+
+    Statement::block(stmts)
+}
+
+/// Collects names and widths of wires in the statement.
+fn collect_wires(stmt: &Statement, wires: &mut Vec<(String, usize)>) {
+    match stmt {
+        Statement::DefWire { name, tpe: Type::UIntType(width) } => wires.push((name.clone(), *width)),
+        Statement::Block { stmts } => stmts.iter().for_each(|stmt| collect_wires(stmt, wires)),
+        Statement::Commented { stmt, .. } => collect_wires(stmt, wires),
+        _ => {}
+    }
+}
+
+/// Removes connections to slices from the statement. Returns the statement, and records the
+/// removed connections in `slices` and the names of the other connected signals in `connected`.
+fn split_range_connects(
+    stmt: Statement, slices: &mut HashMap<String, Vec<(usize, usize, Expression)>>, connected: &mut HashSet<String>,
+) -> Statement {
+    match stmt {
+        Statement::Connect { loc: Expression::DoPrim { op: PrimOp::Bits, args, consts }, expr } => {
+            match (args.as_slice(), consts.as_slice()) {
+                ([Expression::Reference { name }], [hi, lo]) => {
+                    slices.entry(name.clone()).or_default().push((*lo, *hi, expr));
+                    Statement::EmptyStmt
+                }
+                _ => Statement::Connect { loc: Expression::DoPrim { op: PrimOp::Bits, args, consts }, expr },
+            }
+        }
+        Statement::Connect { loc, expr } => {
+            if let Expression::Reference { name } = &loc {
+                let _ = connected.insert(name.clone());
+            }
+            Statement::Connect { loc, expr }
+        }
+        Statement::Block { stmts } => Statement::block(
+            stmts
+                .into_iter()
+                .map(|stmt| split_range_connects(stmt, slices, connected))
+                .filter(|stmt| !matches!(stmt, Statement::EmptyStmt))
+                .collect(),
+        ),
+        Statement::Commented { comment, stmt } => {
+            Statement::Commented { comment, stmt: Box::new(split_range_connects(*stmt, slices, connected)) }
+        }
+        stmt => stmt,
+    }
+}
+
+/// Annotates the statement with the source location.
+fn gen_commented(source: Option<&Location<'_>>, stmt: Statement) -> Statement {
+    match source {
+        Some(source) => Statement::Commented { comment: gen_source_annotation(source), stmt: Box::new(stmt) },
+        None => stmt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    /// Two rows of three 4-bit values.
+    type Matrix = Array<Array<Bits<U<4>>, U<3>>, U<2>>;
+
+    /// Returns an FSM swapping the rows of a matrix, registering the result.
+    fn swap() -> Module<UniChannel<Matrix>, UniChannel<Matrix>> {
+        hir::Fsm::<UniChannel<Matrix>, UniChannel<Matrix>, Matrix, _>::new(
+            "swap",
+            |fwd, bwd, state| (state, bwd, fwd.set(0.into(), fwd[1]).set(1.into(), fwd[0])),
+            Expr::x(),
+        )
+        .into()
+    }
+
+    /// Returns a composite module instantiating the `stage` module twice.
+    fn pipeline() -> Module<UniChannel<Matrix>, UniChannel<Matrix>> {
+        let stage =
+            composite::<UniChannel<Matrix>, UniChannel<Matrix>, _>("stage", Some("in"), Some("out"), |input, k| {
+                input.comb_inline(k, swap())
+            })
+            .build();
+
+        composite::<UniChannel<Matrix>, UniChannel<Matrix>, _>("pipeline", Some("in"), Some("out"), |input, k| {
+            input.comb(k, Some("first"), stage.clone()).comb(k, Some("second"), stage)
+        })
+        .build()
+    }
+
+    /// Returns the FIRRTL module `name` in the generated circuit, without source locations.
+    fn fir_module(name: &str) -> String {
+        let files = generate(package(pipeline()), |package, dir| package.gen_fir(dir)).unwrap();
+        let header = format!("  module {} :", name);
+        files["pipeline_inner.fir"]
+            .lines()
+            .skip_while(|line| *line != header)
+            .enumerate()
+            .take_while(|(i, line)| *i == 0 || !line.starts_with("  module "))
+            .map(|(_, line)| line)
+            .filter(|line| !line.trim_start().starts_with("; src = "))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn submodules_are_instantiated() {
+        assert_eq!(
+            fir_module("pipeline"),
+            r#"  module pipeline :
+    input clk : Clock
+    input rst : UInt<1>
+    input in : UInt<24>
+    output out : UInt<24>
+  
+    wire stage_inner_0_in : UInt<24>
+    wire stage_inner_0_out : UInt<24>
+    wire stage_inner_1_in : UInt<24>
+    wire stage_inner_1_out : UInt<24>
+    stage_inner_0_in <= in
+    stage_inner_1_in <= stage_inner_0_out
+    out <= stage_inner_1_out
+    inst stage_inner_0_stage_inner_inst_first of stage_inner
+    stage_inner_0_stage_inner_inst_first.clk <= clk
+    stage_inner_0_stage_inner_inst_first.rst <= rst
+    stage_inner_0_stage_inner_inst_first.in <= stage_inner_0_in
+    stage_inner_0_out <= stage_inner_0_stage_inner_inst_first.out
+    inst stage_inner_1_stage_inner_inst_second of stage_inner
+    stage_inner_1_stage_inner_inst_second.clk <= clk
+    stage_inner_1_stage_inner_inst_second.rst <= rst
+    stage_inner_1_stage_inner_inst_second.in <= stage_inner_1_in
+    stage_inner_1_out <= stage_inner_1_stage_inner_inst_second.out"#
+        );
+    }
+
+    #[test]
+    fn multi_dimensional_ports_are_flattened() {
+        assert_eq!(
+            fir_module("stage_inner"),
+            r#"  module stage_inner :
+    input clk : Clock
+    input rst : UInt<1>
+    input in : UInt<24>
+    output out : UInt<24>
+  
+    wire swap_0_in : UInt<24>
+    wire swap_0_out : UInt<24>
+    swap_0_in <= in
+    out <= swap_0_out
+    reg swap_0_st_reg : UInt<24>, clk with :
+      reset => (rst, UInt<24>("b000000000000000000000000"))
+    wire swap_0_st : UInt<24>
+    swap_0_st <= swap_0_st_reg
+    swap_0_out <= swap_0_st
+    skip
+    node swap_0_t0 = bits(dshr(swap_0_in, mul(UInt<1>("b1"), UInt(12))), 11, 0)
+    node swap_0_t1 = cat(mux(eq(UInt<1>("b0"), UInt(1)), bits(swap_0_t0, 11, 0), bits(swap_0_in, 23, 12)), mux(eq(UInt<1>("b0"), UInt(0)), bits(swap_0_t0, 11, 0), bits(swap_0_in, 11, 0)))
+    node swap_0_t2 = bits(dshr(swap_0_in, mul(UInt<1>("b0"), UInt(12))), 11, 0)
+    node swap_0_t3 = cat(mux(eq(UInt<1>("b1"), UInt(1)), bits(swap_0_t2, 11, 0), bits(swap_0_t1, 23, 12)), mux(eq(UInt<1>("b1"), UInt(0)), bits(swap_0_t2, 11, 0), bits(swap_0_t1, 11, 0)))
+    swap_0_st_reg <= swap_0_t3"#
+        );
     }
 }
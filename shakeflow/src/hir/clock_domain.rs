@@ -0,0 +1,229 @@
+//! Clock domains.
+//!
+//! Each clock domain other than the default one has its own clock and reset ports, `{name}_clk` and
+//! `{name}_rst`, in the generated modules. Interfaces in a clock domain are typed as `Clocked`, so
+//! connecting interfaces in different clock domains is a type error. `Clocked` is not an `Interface`,
+//! so it only has combinators that preserve the clock domain, and interfaces in different clock
+//! domains can only be connected by explicit clock-domain crossings with `Clocked::cdc`.
+//!
+//! The simulator and the model checker regard all clock domains as one clock.
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use crate::*;
+
+/// Clock domain.
+pub trait ClockDomain: 'static + Debug {
+    /// Name of the clock domain, or `None` for the default clock domain.
+    const NAME: Option<&'static str>;
+}
+
+/// Default clock domain, which is driven by `clk` and `rst` of the enclosing module.
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultDomain;
+
+impl ClockDomain for DefaultDomain {
+    const NAME: Option<&'static str> = None;
+}
+
+/// Declares a clock domain with the given name.
+///
+/// For example, `clock_domain!(pub Mac = "mac");` declares the clock domain `Mac` whose clock and
+/// reset ports are `mac_clk` and `mac_rst`.
+#[macro_export]
+macro_rules! clock_domain {
+    ($(#[$attr:meta])* $vis:vis $domain:ident = $name:literal) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy)]
+        $vis struct $domain;
+
+        impl $crate::ClockDomain for $domain {
+            const NAME: Option<&'static str> = Some($name);
+        }
+    };
+}
+
+/// Interface in the clock domain `D`.
+#[derive(Debug)]
+pub struct Clocked<I: Interface, D: ClockDomain> {
+    inner: I,
+    _marker: PhantomData<D>,
+}
+
+impl<I: Interface, D: ClockDomain> Clocked<I, D> {
+    fn new(inner: I) -> Self { Self { inner, _marker: PhantomData } }
+
+    /// Feeds `self` to the logic described by `f` in the clock domain.
+    #[track_caller]
+    pub fn domain<O: Interface, F: FnOnce(I, &mut CompositeModuleContext) -> O>(
+        self, k: &mut CompositeModuleContext, f: F,
+    ) -> Clocked<O, D> {
+        let module = clocked_composite::<D, I, O, _>("clock_domain", Some("in"), Some("out"), |i, k| {
+            Clocked::new(f(i.inner, k))
+        });
+        self.comb_inline(k, module)
+    }
+
+    /// Feeds `self` to the given module in the same clock domain, inlining the module.
+    pub fn comb_inline<O: Interface>(
+        self, k: &mut CompositeModuleContext, module: ClockedModule<I, O, D>,
+    ) -> Clocked<O, D> {
+        Clocked::new(self.inner.comb_inline(k, module.inner))
+    }
+
+    /// Feeds `self` to the given module in the same clock domain, instantiating the module.
+    #[track_caller]
+    pub fn comb<O: Interface>(
+        self, k: &mut CompositeModuleContext, inst_postfix: Option<&str>, module: ClockedModule<I, O, D>,
+    ) -> Clocked<O, D> {
+        Clocked::new(self.inner.comb(k, inst_postfix, module.inner))
+    }
+
+    /// Crosses `self` to the clock domain `E` by instantiating the given Verilog module, e.g., an
+    /// asynchronous FIFO.
+    ///
+    /// `clock_prefixes` are the prefixes of the clock and reset ports of the module for the input
+    /// and output sides, which are driven by `D` and `E`, respectively. For example, `("s", "m")`
+    /// means `s_clk` and `s_rst` are driven by `D`, and `m_clk` and `m_rst` by `E`.
+    #[allow(clippy::too_many_arguments)]
+    #[track_caller]
+    pub fn cdc<E: ClockDomain, O: Interface>(
+        self, k: &mut CompositeModuleContext, module_name: &str, inst_name: &str, params: Vec<(&str, usize)>,
+        clock_prefixes: (&str, &str), input_prefix: Option<&str>, output_prefix: Option<&str>,
+    ) -> Clocked<O, E> {
+        let mut module_inst = ModuleInst::<I, O>::new(
+            module_name.to_string(),
+            inst_name.to_string(),
+            params.into_iter().map(|(s, w)| (s.to_string(), w)).collect(),
+            false,
+            input_prefix.map(String::from),
+            output_prefix.map(String::from),
+            None,
+        );
+        module_inst.clock_ports = vec![
+            (clock_prefixes.0.to_string(), D::NAME.map(String::from)),
+            (clock_prefixes.1.to_string(), E::NAME.map(String::from)),
+        ];
+
+        Clocked::new(self.inner.comb_inline(k, module_inst.into()))
+    }
+}
+
+/// Module whose input and output interfaces are in the clock domain `D`.
+///
+/// It can only be fed with interfaces in the same clock domain, or added to a package with
+/// `Package::add_clocked`.
+#[derive(Debug)]
+pub struct ClockedModule<I: Interface, O: Interface, D: ClockDomain> {
+    pub(crate) inner: Module<I, O>,
+    _marker: PhantomData<D>,
+}
+
+/// Creates a new module in the clock domain `D` with given prefix for input and output channels.
+///
+/// The logic of `f` may cross to other clock domains with `Clocked::cdc`, but its input and output
+/// interfaces are in `D`.
+#[track_caller]
+pub fn clocked_composite<
+    D: ClockDomain,
+    I: Interface,
+    O: Interface,
+    F: FnOnce(Clocked<I, D>, &mut CompositeModuleContext) -> Clocked<O, D>,
+>(
+    name: &str, input_prefix: Option<&str>, output_prefix: Option<&str>, f: F,
+) -> ClockedModule<I, O, D> {
+    let mut module = composite::<I, O, _>(name, input_prefix, output_prefix, |i, k| f(Clocked::new(i), k).inner);
+    module.inner.clock_domain = D::NAME.map(String::from);
+    ClockedModule { inner: module.build(), _marker: PhantomData }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    type Nibble = Bits<U<4>>;
+
+    clock_domain!(Mac = "mac");
+
+    /// Returns an FSM delaying values by a cycle.
+    fn delay() -> Module<UniChannel<Nibble>, UniChannel<Nibble>> {
+        hir::Fsm::<UniChannel<Nibble>, UniChannel<Nibble>, Nibble, _>::new(
+            "delay",
+            |fwd, bwd, state| (state, bwd, fwd),
+            0.into(),
+        )
+        .into()
+    }
+
+    /// Returns a module delaying values in the default clock domain and then in `Mac`.
+    fn two_domains() -> ClockedModule<UniChannel<Nibble>, UniChannel<Nibble>, DefaultDomain> {
+        clocked_composite::<DefaultDomain, UniChannel<Nibble>, UniChannel<Nibble>, _>(
+            "two_domains",
+            Some("in"),
+            Some("out"),
+            |input, k| {
+                input
+                    .domain(k, |input, k| input.comb_inline(k, delay()))
+                    .cdc::<Mac, UniChannel<Nibble>>(k, "async_fifo", "to_mac", vec![], ("s", "m"), Some("s"), Some("m"))
+                    .domain(k, |input, k| input.comb_inline(k, delay()))
+                    .cdc::<DefaultDomain, UniChannel<Nibble>>(
+                        k,
+                        "async_fifo",
+                        "from_mac",
+                        vec![],
+                        ("s", "m"),
+                        Some("s"),
+                        Some("m"),
+                    )
+            },
+        )
+    }
+
+    #[test]
+    fn clock_domains_are_crossed() {
+        let mut package = Package::default();
+        package.add_clocked(two_domains());
+        let files = generate(package, |package, dir| package.gen_vir(dir)).unwrap();
+        let verilog = &files["two_domains_inner.v"];
+
+        assert!(verilog.contains(
+            r#"module two_domains_inner
+(
+    input wire clk,
+    input wire rst,
+    input wire mac_clk,
+    input wire mac_rst,
+    input wire [4-1:0] in,
+    output wire [4-1:0] out
+);"#
+        ));
+        assert!(verilog.contains(
+            r#"to_mac (
+    .s_clk(clk),
+    .s_rst(rst),
+    .m_clk(mac_clk),
+    .m_rst(mac_rst),
+    .s(clock_domain_0_delay_0_st_reg),
+    .m(async_fifo_1_out)
+);"#
+        ));
+        assert!(verilog.contains(
+            r#"always @(posedge mac_clk) begin
+    clock_domain_2_delay_0_st_reg <= async_fifo_1_out;
+    if (mac_rst) begin"#
+        ));
+        assert!(verilog.contains(
+            r#"from_mac (
+    .s_clk(mac_clk),
+    .s_rst(mac_rst),
+    .m_clk(clk),
+    .m_rst(rst),
+    .s(clock_domain_2_delay_0_st_reg),
+    .m(async_fifo_3_out)
+);"#
+        ));
+    }
+}
@@ -15,11 +15,16 @@ pub struct ExpansiveArrayValue<V: Signal, const N: usize> {
 impl<V: Signal, const N: usize> Signal for ExpansiveArrayValue<V, N> {
     const WIDTH: usize = V::WIDTH * N;
 
-    fn transl(self) -> Vec<bool> { self.inner.transl() }
+    fn transl(self) -> Vec<bool> { self.inner.inner.into_iter().flat_map(|v| v.transl()).collect() }
 
     fn port_decls() -> lir::PortDecls {
         lir::PortDecls::Struct((0..N).map(|i| (Some(i.to_string()), V::port_decls())).collect())
     }
+
+    fn decode(bits: &[bool]) -> Value {
+        assert_eq!(bits.len(), Self::WIDTH);
+        Value::Struct((0..N).map(|i| (i.to_string(), V::decode(&bits[(i * V::WIDTH)..((i + 1) * V::WIDTH)]))).collect())
+    }
 }
 
 /// Expansive array interface.
@@ -36,25 +41,40 @@ impl<I: Interface, const N: usize> Interface for ExpansiveArray<I, N> {
     fn interface_typ() -> lir::InterfaceTyp { lir::InterfaceTyp::ExpansiveArray(Box::new(I::interface_typ()), N) }
 
     fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
-        if let lir::Interface::ExpansiveArray(inner) = interface {
-            Ok(ExpansiveArray {
+        match interface {
+            lir::Interface::ExpansiveArray(inner) if inner.len() == N => Ok(ExpansiveArray {
                 inner: inner
                     .into_iter()
-                    .map(|interface_elt| I::try_from_inner(interface_elt).unwrap())
-                    .collect::<ArrayVec<I, N>>()
+                    .enumerate()
+                    .map(|(i, interface_elt)| {
+                        I::try_from_inner(interface_elt)
+                            .map_err(|error| error.nested(lir::EndpointNode::ExpansiveIndex(i)))
+                    })
+                    .collect::<Result<ArrayVec<I, N>, _>>()?
                     .into_inner()
-                    .unwrap(),
-            })
-        } else {
-            panic!("internal compiler error")
+                    .unwrap(), // this should be successful because there are `N` elements.
+            }),
+            interface => Err(typ_mismatch::<Self>(&interface)),
         }
     }
 
     fn try_into_inner(self) -> Result<lir::Interface, InterfaceError> {
         Ok(lir::Interface::ExpansiveArray(
-            self.inner.into_iter().map(|interface| interface.try_into_inner().unwrap()).collect(),
+            self.inner.into_iter().map(|interface| interface.try_into_inner()).collect::<Result<_, _>>()?,
         ))
     }
+
+    fn check_protocol(self, k: &mut CompositeModuleContext) -> Self {
+        ExpansiveArray {
+            inner: self
+                .inner
+                .into_iter()
+                .map(|interface| interface.check_protocol(k))
+                .collect::<ArrayVec<I, N>>()
+                .into_inner()
+                .unwrap(),
+        }
+    }
 }
 
 impl<'id, V: Signal, const N: usize> From<Expr<'id, ExpansiveArrayValue<V, N>>> for Expr<'id, Array<V, U<N>>> {
@@ -74,6 +94,7 @@ impl<'id, V: Signal, const N: usize> From<Expr<'id, Array<V, U<N>>>> for Expr<'i
 
 impl<I: Interface, const N: usize> ExpansiveArray<I, N> {
     /// Transforms into `[I; N]`.
+    #[track_caller]
     pub fn into_array(self, k: &mut CompositeModuleContext) -> [I; N] {
         self.fsm(k, None, ().into(), |i_fwd, o_bwd, s| {
             let o_fwd = Expr::<Array<I::Fwd, U<N>>>::from(i_fwd);
@@ -84,6 +105,7 @@ impl<I: Interface, const N: usize> ExpansiveArray<I, N> {
     }
 
     /// Transforms from `[I; N]`.
+    #[track_caller]
     pub fn from_array(array: [I; N], k: &mut CompositeModuleContext) -> Self {
         array.fsm(k, None, ().into(), |i_fwd, o_bwd, s| {
             let o_fwd = Expr::<ExpansiveArrayValue<I::Fwd, N>>::from(i_fwd);
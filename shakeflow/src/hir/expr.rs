@@ -409,6 +409,11 @@ impl<'id, V: Signal> Expr<'id, V> {
         lir::Expr::Repr { inner: self.into_inner() }.into()
     }
 
+    /// Checks two exprs have the same bit representation.
+    pub fn is_bits_eq(&self, other: Expr<'id, V>) -> Expr<'id, bool> {
+        Expr { id: is_bits_eq(self.into_inner(), other.into_inner(), &V::port_decls()), _marker: PhantomData }
+    }
+
     /// Repeats expr.
     pub fn repeat<N: Num>(&self) -> Expr<'id, Array<V, N>> {
         assert_eq!(V::port_decls().max_dim(), 1);
@@ -418,6 +423,8 @@ impl<'id, V: Signal> Expr<'id, V> {
 
 impl<'id, N: Num> Expr<'id, Bits<N>> {
     /// $signed() system function
+    ///
+    /// Signedness is only supported by the Verilog backends; the simulator and other backends reject it.
     pub fn signed(&self) -> Self {
         lir::Expr::Call {
             func_name: "$signed".to_string(),
@@ -738,6 +745,22 @@ impl<'id> Expr<'id, bool> {
     pub fn cond<V: Signal>(&self, lhs: Expr<'id, V>, rhs: Expr<'id, V>) -> Expr<'id, V> {
         lir::Expr::Cond { cond: self.into_inner(), lhs: lhs.into_inner(), rhs: rhs.into_inner() }.into()
     }
+
+    /// Asserts that the expr holds at each rising edge of the clock out of reset.
+    ///
+    /// Should be called inside the function of an FSM (including `map` and alike), and should not be
+    /// called inside closures of exprs such as `Expr::map` and `Expr::fold`.
+    pub fn assert(self, message: &str) { add_assertion(lir::AssertionKind::Assert, self.into_inner(), message) }
+
+    /// Assumes that the expr holds at each rising edge of the clock out of reset.
+    ///
+    /// See `assert` for where it can be called.
+    pub fn assume(self, message: &str) { add_assertion(lir::AssertionKind::Assume, self.into_inner(), message) }
+
+    /// Covers the case where the expr holds at a rising edge of the clock out of reset.
+    ///
+    /// See `assert` for where it can be called.
+    pub fn cover(self, message: &str) { add_assertion(lir::AssertionKind::Cover, self.into_inner(), message) }
 }
 
 impl<'id, V: Signal, N: Num> Index<Expr<'id, Bits<Log2<N>>>> for Expr<'id, VarArray<V, N>> {
@@ -1010,3 +1033,44 @@ macro_rules! if_then_set_var_arr {
         $id.set_var_arr($idx, $cond.cond($elt, $id[$idx]))
     };
 }
+
+/// Checks two exprs of the type have the same bit representation.
+///
+/// Structs and multi-dimensional arrays cannot be compared as a whole in every backend, so their
+/// members and elements are compared one by one.
+fn is_bits_eq(lhs: lir::ExprId, rhs: lir::ExprId, typ: &lir::PortDecls) -> lir::ExprId {
+    let alloc = |expr: lir::Expr| lir::ExprId::alloc_expr(Merkle::new(expr));
+    let and = |lhs, rhs| alloc(lir::Expr::BinaryOp { op: lir::BinaryOp::And, lhs, rhs });
+
+    match typ {
+        _ if typ.width() == 0 => alloc(true.to_lir()),
+        lir::PortDecls::Bits(shape) if shape.dim() == 1 => {
+            alloc(lir::Expr::BinaryOp { op: lir::BinaryOp::EqArithmetic, lhs, rhs })
+        }
+        lir::PortDecls::Struct(inner) => inner
+            .iter()
+            .enumerate()
+            .map(|(index, (_, member))| {
+                let lhs = alloc(lir::Expr::Member { inner: lhs, index });
+                let rhs = alloc(lir::Expr::Member { inner: rhs, index });
+                is_bits_eq(lhs, rhs, member)
+            })
+            .reduce(and)
+            .unwrap_or_else(|| alloc(true.to_lir())),
+        lir::PortDecls::Bits(shape) => {
+            let typ_elt = lir::PortDecls::Bits(lir::Shape::new((1..shape.dim()).map(|i| shape.get(i))));
+            (0..shape.get(0))
+                .map(|i| {
+                    let index = alloc(lir::Expr::Constant {
+                        bits: usize_to_bitvec(clog2(shape.get(0)), i),
+                        typ: lir::PortDecls::Bits(lir::Shape::new([clog2(shape.get(0))])),
+                    });
+                    let lhs = alloc(lir::Expr::GetVarArray { inner: lhs, typ_elt: typ_elt.clone(), index });
+                    let rhs = alloc(lir::Expr::GetVarArray { inner: rhs, typ_elt: typ_elt.clone(), index });
+                    is_bits_eq(lhs, rhs, &typ_elt)
+                })
+                .reduce(and)
+                .unwrap_or_else(|| alloc(true.to_lir()))
+        }
+    }
+}
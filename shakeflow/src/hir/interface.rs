@@ -1,21 +1,69 @@
+use std::cell::Cell;
 use std::fmt::Debug;
 use std::ops::*;
 
 use arrayvec::ArrayVec;
 use linked_hash_map::LinkedHashMap;
-use thiserror::Error;
 use tuple_utils::*;
 
 use crate::hir::*;
 use crate::lir;
 
-#[allow(missing_docs)]
-#[derive(Debug, Error)]
-pub enum InterfaceError {
-    #[error("more channels are required")]
-    NoChannels,
-    #[error("there are too many channels")]
-    TooManyChannels,
+thread_local! {
+    /// Whether protocol checkers are inserted on the inputs of submodules and the outputs of
+    /// composite modules.
+    static PROTOCOL_CHECKS: Cell<bool> = Cell::new(false);
+}
+
+/// Enables or disables protocol checks for the modules built afterwards on this thread.
+///
+/// If enabled, `Interface::check_protocol` is called on the input of each submodule and the output
+/// of each composite module, e.g. to check the valid/ready handshake of channels crossing module
+/// boundaries. Hence the outputs of top-level modules are checked as well, while their inputs are
+/// assumed to be driven correctly.
+pub fn set_protocol_checks(enabled: bool) { PROTOCOL_CHECKS.with(|checks| checks.set(enabled)) }
+
+/// Inserts protocol checkers on the interface if protocol checks are enabled, disabling protocol
+/// checks on the checkers themselves.
+pub(crate) fn insert_protocol_checks<I: Interface>(interface: I, k: &mut CompositeModuleContext) -> I {
+    if PROTOCOL_CHECKS.with(|checks| checks.replace(false)) {
+        let interface = interface.check_protocol(k);
+        PROTOCOL_CHECKS.with(|checks| checks.set(true));
+        interface
+    } else {
+        interface
+    }
+}
+
+/// Returns the type of the channel named `name` whose forward and backward signals are `Fwd` and
+/// `Bwd`, respectively.
+pub fn channel_typ<Fwd: Signal, Bwd: Signal>(name: &str) -> lir::ChannelTyp {
+    lir::ChannelTyp::new(Fwd::port_decls(), Bwd::port_decls()).with_decl(lir::ChannelTypDecl {
+        name: name.to_string(),
+        fwd: Fwd::signal_typ(),
+        bwd: Bwd::signal_typ(),
+    })
+}
+
+pub use crate::lir::InterfaceError;
+
+/// Returns the error that `interface` does not have the interface type of `I`.
+pub fn typ_mismatch<I: Interface>(interface: &lir::Interface) -> InterfaceError {
+    InterfaceError::TypMismatch {
+        path: lir::EndpointPath::default(),
+        expected: std::any::type_name::<I>().to_string(),
+        found: interface.typ().describe(),
+    }
+}
+
+/// Returns the channel of `interface` if it has the interface type of the channel `I`.
+pub fn channel_of<I: Interface>(interface: lir::Interface) -> Result<lir::Channel, InterfaceError> {
+    match interface {
+        lir::Interface::Channel(channel) if lir::InterfaceTyp::Channel(channel.typ()) == I::interface_typ() => {
+            Ok(channel)
+        }
+        interface => Err(typ_mismatch::<I>(&interface)),
+    }
 }
 
 /// Interface of channels.
@@ -35,19 +83,40 @@ pub trait Interface: 'static + Sized + Debug {
     /// Tries to convert `self` to `lir::Interface`.
     fn try_into_inner(self) -> Result<lir::Interface, InterfaceError>;
 
+    /// Inserts protocol checkers on the channels of `self`, and returns the checked interface.
+    ///
+    /// Called on the input of each submodule and the output of each composite module if protocol
+    /// checks are enabled by `set_protocol_checks`. Checkers are not checked again.
+    fn check_protocol(self, _k: &mut CompositeModuleContext) -> Self { self }
+
     /// Chains `self` as input to a new module, and returns the module's output.
+    ///
+    /// If the interfaces do not match their types, the construction of the enclosing module fails,
+    /// which is reported when the package is generated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `O` cannot be converted from its own interface type, i.e., `O` does not implement
+    /// `Interface` consistently.
     fn comb_inline<O: Interface>(self, k: &mut CompositeModuleContext, module: Module<Self, O>) -> O {
-        // Adds submodule.
-        let input_interface = self.try_into_inner().expect("internal compiler error");
-        let output_interface = k.inner.add_submodule(module.inner, input_interface);
-
-        // Converts output interface.
-        O::try_from_inner(output_interface).expect("internal compiler error")
+        let this = insert_protocol_checks(self, k);
+
+        // Adds submodule, and converts output interface.
+        let module_name = module.inner.get_module_name();
+        let output = this
+            .try_into_inner()
+            .and_then(|input_interface| O::try_from_inner(k.inner.add_submodule(module.inner, input_interface)));
+
+        output.unwrap_or_else(|error| {
+            k.fail(lir::ModuleError::Interface { module: module_name, error });
+            O::try_from_inner(temp_interface::<O>()).expect("interface type is inconsistent")
+        })
     }
 
     /// Feeds `self` to a new FSM.
     ///
     /// The FSM is described by `F`, which generates the circuit for (1) the current-cycle output; and (2) the next-cycle state.
+    #[track_caller]
     fn fsm<
         S: Signal,
         O: 'static + Interface,
@@ -66,6 +135,7 @@ pub trait Interface: 'static + Sized + Debug {
     /// Feeds `self` to a new Module instantiation.
     // TODO: Remove this
     #[allow(clippy::too_many_arguments)]
+    #[track_caller]
     fn module_inst<O: Interface>(
         self, k: &mut CompositeModuleContext, module_name: &str, inst_name: &str, params: Vec<(&str, usize)>,
         has_clk: bool, input_prefix: Option<&str>, output_prefix: Option<&str>,
@@ -83,6 +153,7 @@ pub trait Interface: 'static + Sized + Debug {
     }
 
     /// Module instantiation api for shakeflow modules
+    #[track_caller]
     fn comb<O: Interface>(
         self, k: &mut CompositeModuleContext, inst_postfix: Option<&str>, shakeflow_module: Module<Self, O>,
     ) -> O {
@@ -101,8 +172,10 @@ impl Interface for () {
     fn interface_typ() -> lir::InterfaceTyp { lir::InterfaceTyp::Unit }
 
     fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
-        assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-        Ok(())
+        match interface {
+            lir::Interface::Unit => Ok(()),
+            interface => Err(typ_mismatch::<Self>(&interface)),
+        }
     }
 
     fn try_into_inner(self) -> Result<lir::Interface, InterfaceError> { Ok(lir::Interface::Unit) }
@@ -127,18 +200,17 @@ macro_rules! channel {
             type Fwd = $fwd;
 
             fn interface_typ() -> ::shakeflow::lir::InterfaceTyp {
-                lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+                lir::InterfaceTyp::Channel(::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)))
             }
 
             fn try_from_inner(interface: lir::Interface) -> Result<Self, ::shakeflow::InterfaceError> {
-                assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-                let channel = some_or!(interface.get_channel(), panic!());
+                let channel = ::shakeflow::channel_of::<Self>(interface)?;
                 Ok(Self { endpoint: channel.endpoint() })
             }
 
             fn try_into_inner(self) -> Result<::shakeflow::lir::Interface, ::shakeflow::InterfaceError> {
                 Ok(::shakeflow::lir::Interface::Channel(::shakeflow::lir::Channel {
-                    typ: ::shakeflow::lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+                    typ: ::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)),
                     endpoint: self.endpoint,
                 }))
             }
@@ -160,18 +232,17 @@ macro_rules! channel {
             type Fwd = $fwd;
 
             fn interface_typ() -> ::shakeflow::lir::InterfaceTyp {
-                lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+                lir::InterfaceTyp::Channel(::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)))
             }
 
             fn try_from_inner(interface: lir::Interface) -> Result<Self, ::shakeflow::InterfaceError> {
-                assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-                let channel = some_or!(interface.get_channel(), panic!());
+                let channel = ::shakeflow::channel_of::<Self>(interface)?;
                 Ok(Self { endpoint: channel.endpoint(), _marker: ::std::marker::PhantomData })
             }
 
             fn try_into_inner(self) -> Result<::shakeflow::lir::Interface, ::shakeflow::InterfaceError> {
                 Ok(::shakeflow::lir::Interface::Channel(::shakeflow::lir::Channel {
-                    typ: ::shakeflow::lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+                    typ: ::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)),
                     endpoint: self.endpoint,
                 }))
             }
@@ -192,18 +263,17 @@ macro_rules! channel {
             type Fwd = $fwd;
 
             fn interface_typ() -> ::shakeflow::lir::InterfaceTyp {
-                lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+                lir::InterfaceTyp::Channel(::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)))
             }
 
             fn try_from_inner(interface: lir::Interface) -> Result<Self, ::shakeflow::InterfaceError> {
-                assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-                let channel = some_or!(interface.get_channel(), panic!());
+                let channel = ::shakeflow::channel_of::<Self>(interface)?;
                 Ok(Self { endpoint: channel.endpoint(), _marker: ::std::marker::PhantomData })
             }
 
             fn try_into_inner(self) -> Result<::shakeflow::lir::Interface, ::shakeflow::InterfaceError> {
                 Ok(::shakeflow::lir::Interface::Channel(::shakeflow::lir::Channel {
-                    typ: ::shakeflow::lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+                    typ: ::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)),
                     endpoint: self.endpoint,
                 }))
             }
@@ -224,18 +294,17 @@ macro_rules! channel {
             type Fwd = $fwd<$fwd_generic>;
 
             fn interface_typ() -> ::shakeflow::lir::InterfaceTyp {
-                lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+                lir::InterfaceTyp::Channel(::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)))
             }
 
             fn try_from_inner(interface: lir::Interface) -> Result<Self, ::shakeflow::InterfaceError> {
-                assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-                let channel = some_or!(interface.get_channel(), panic!());
+                let channel = ::shakeflow::channel_of::<Self>(interface)?;
                 Ok(Self { endpoint: channel.endpoint(), _marker: ::std::marker::PhantomData })
             }
 
             fn try_into_inner(self) -> Result<::shakeflow::lir::Interface, ::shakeflow::InterfaceError> {
                 Ok(::shakeflow::lir::Interface::Channel(::shakeflow::lir::Channel {
-                    typ: ::shakeflow::lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+                    typ: ::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)),
                     endpoint: self.endpoint,
                 }))
             }
@@ -256,18 +325,17 @@ macro_rules! channel {
             type Fwd = $fwd<$fwd_generic>;
 
             fn interface_typ() -> ::shakeflow::lir::InterfaceTyp {
-                lir::InterfaceTyp::Channel(lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()))
+                lir::InterfaceTyp::Channel(::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)))
             }
 
             fn try_from_inner(interface: lir::Interface) -> Result<Self, ::shakeflow::InterfaceError> {
-                assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-                let channel = some_or!(interface.get_channel(), panic!());
+                let channel = ::shakeflow::channel_of::<Self>(interface)?;
                 Ok(Self { endpoint: channel.endpoint(), _marker: ::std::marker::PhantomData })
             }
 
             fn try_into_inner(self) -> Result<::shakeflow::lir::Interface, ::shakeflow::InterfaceError> {
                 Ok(::shakeflow::lir::Interface::Channel(::shakeflow::lir::Channel {
-                    typ: ::shakeflow::lir::ChannelTyp::new(Self::Fwd::port_decls(), Self::Bwd::port_decls()),
+                    typ: ::shakeflow::channel_typ::<Self::Fwd, Self::Bwd>(stringify!($channel_name)),
                     endpoint: self.endpoint,
                 }))
             }
@@ -291,12 +359,11 @@ macro_rules! impl_interface_tuple {
 
             fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
                 match interface {
-                    lir::Interface::Struct(mut inner) => {
-                        let b = inner.remove("0").unwrap().1;
-                        assert!(inner.is_empty(), "internal compiler error");
-                        Ok(($a::try_from_inner(b)?,))
+                    lir::Interface::Struct(mut inner) if inner.len() == 1 && inner.contains_key("0") => {
+                        let (sep, b) = inner.remove("0").unwrap();
+                        Ok(($a::try_from_inner(b).map_err(|error| error.nested(lir::EndpointNode::Field("0".to_string(), sep)))?,))
                     }
-                    _ => panic!("internal compiler error"),
+                    interface => Err(typ_mismatch::<Self>(&interface)),
                 }
             }
 
@@ -305,6 +372,8 @@ macro_rules! impl_interface_tuple {
                 inner.insert("0".to_string(), (None, self.0.try_into_inner()?));
                 Ok(lir::Interface::Struct(inner))
             }
+
+            fn check_protocol(self, k: &mut CompositeModuleContext) -> Self { (self.0.check_protocol(k),) }
         }
     };
     ($($a:ident)+) => {
@@ -313,29 +382,26 @@ macro_rules! impl_interface_tuple {
             type Bwd = ($($a::Bwd,)+);
 
             fn interface_typ() -> lir::InterfaceTyp {
-                match <<Self as SplitLast>::Left as Interface>::interface_typ() {
-                    lir::InterfaceTyp::Struct(mut inner) => {
-                        inner.insert(
-                            (Self::arity() - 1).to_string(),
-                            (None, <<Self as SplitLast>::Right as Interface>::interface_typ()),
-                        );
-                        lir::InterfaceTyp::Struct(inner)
-                    }
-                    _ => panic!("internal compiler error"),
-                }
+                lir::InterfaceTyp::Struct(
+                    [$($a::interface_typ(),)+]
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, interface_typ)| (i.to_string(), (None, interface_typ)))
+                        .collect(),
+                )
             }
 
             fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
+                let last = (Self::arity() - 1).to_string();
                 match interface {
-                    lir::Interface::Struct(mut inner) => {
-                        let right = inner.remove(&(Self::arity() - 1).to_string()).unwrap().1;
-
-                        Ok(
-                            <<Self as SplitLast>::Left as Interface>::try_from_inner(lir::Interface::Struct(inner))?
-                                .push_back(<<Self as SplitLast>::Right as Interface>::try_from_inner(right)?),
-                        )
+                    lir::Interface::Struct(mut inner) if inner.len() == Self::arity() && inner.contains_key(&last) => {
+                        let (sep, right) = inner.remove(&last).unwrap();
+                        let left = <<Self as SplitLast>::Left as Interface>::try_from_inner(lir::Interface::Struct(inner))?;
+                        let right = <<Self as SplitLast>::Right as Interface>::try_from_inner(right)
+                            .map_err(|error| error.nested(lir::EndpointNode::Field(last, sep)))?;
+                        Ok(left.push_back(right))
                     }
-                    _ => panic!("internal compiler error"),
+                    interface => Err(typ_mismatch::<Self>(&interface)),
                 }
             }
 
@@ -347,9 +413,14 @@ macro_rules! impl_interface_tuple {
                         inner.insert((Self::arity() - 1).to_string(), (None, right.try_into_inner()?));
                         Ok(lir::Interface::Struct(inner))
                     }
-                    _ => panic!("internal compiler error"),
+                    interface => Err(typ_mismatch::<<Self as SplitLast>::Left>(&interface)),
                 }
             }
+
+            fn check_protocol(self, k: &mut CompositeModuleContext) -> Self {
+                let (left, right) = SplitLast::split_last(self);
+                left.check_protocol(k).push_back(right.check_protocol(k))
+            }
         }
     }
 }
@@ -374,20 +445,27 @@ impl<B: Interface, const N: usize> Interface for [B; N] {
     fn interface_typ() -> lir::InterfaceTyp { lir::InterfaceTyp::Array(Box::new(B::interface_typ()), N) }
 
     fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
-        assert_eq!(interface.typ(), Self::interface_typ(), "internal compiler error");
-        if let lir::Interface::Array(interfaces) = interface {
-            Ok(interfaces
+        match interface {
+            lir::Interface::Array(interfaces) if interfaces.len() == N => Ok(interfaces
                 .into_iter()
-                .map(|interface| B::try_from_inner(interface).unwrap())
-                .collect::<ArrayVec<B, N>>()
+                .enumerate()
+                .map(|(i, interface)| {
+                    B::try_from_inner(interface).map_err(|error| error.nested(lir::EndpointNode::Index(i)))
+                })
+                .collect::<Result<ArrayVec<B, N>, _>>()?
                 .into_inner()
-                .unwrap()) // this should be successful because there should be `N` elements.
-        } else {
-            panic!("internal compiler error");
+                .unwrap()), // this should be successful because there are `N` elements.
+            interface => Err(typ_mismatch::<Self>(&interface)),
         }
     }
 
     fn try_into_inner(self) -> Result<lir::Interface, InterfaceError> {
-        Ok(lir::Interface::Array(self.into_iter().map(|interface| interface.try_into_inner().unwrap()).collect()))
+        Ok(lir::Interface::Array(
+            self.into_iter().map(|interface| interface.try_into_inner()).collect::<Result<_, _>>()?,
+        ))
+    }
+
+    fn check_protocol(self, k: &mut CompositeModuleContext) -> Self {
+        self.into_iter().map(|interface| interface.check_protocol(k)).collect::<ArrayVec<B, N>>().into_inner().unwrap()
     }
 }
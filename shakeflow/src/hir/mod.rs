@@ -1,9 +1,11 @@
 //! High-level IR.
 
+mod clock_domain;
 mod interface;
 mod module;
 mod module_fsm;
 mod module_inst;
+mod module_param;
 mod package;
 #[macro_use]
 mod expr;
@@ -12,7 +14,9 @@ mod fpu;
 mod module_composite;
 pub mod num;
 mod signal;
+mod value;
 
+pub use clock_domain::*;
 pub use expansive_array::*;
 pub use expr::*;
 pub use fpu::FP32;
@@ -21,6 +25,8 @@ pub use module::*;
 pub use module_composite::*;
 pub use module_fsm::*;
 pub use module_inst::*;
+pub use module_param::*;
 pub use num::*;
 pub use package::*;
 pub use signal::*;
+pub use value::*;
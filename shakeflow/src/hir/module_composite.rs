@@ -2,12 +2,62 @@
 //!
 //! For more details, see "Section 2.3 Composite Module" in the paper.
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::mem;
+use std::panic::Location;
 
 use crate::*;
 
+/// Maximum number of composite modules created at the same location that are constructed inside one
+/// another. Recursive generators nest more deeply only if they do not terminate.
+const MAX_CONSTRUCTION_DEPTH: usize = 64;
+
+thread_local! {
+    /// Names and source locations of the composite modules being constructed, from the outermost one.
+    static CONSTRUCTING: RefCell<Vec<(String, &'static Location<'static>)>> = RefCell::new(Vec::new());
+}
+
+/// Marks a composite module as being constructed while it is alive.
+///
+/// On drop, the module is unmarked, even if the construction panics.
+#[derive(Debug)]
+struct ConstructionGuard;
+
+impl ConstructionGuard {
+    /// Marks the module `name` created at `source` as being constructed.
+    ///
+    /// Returns the names of the modules in the instantiation cycle, from the module created at
+    /// `source` to itself, if `MAX_CONSTRUCTION_DEPTH` modules created at `source` are already being
+    /// constructed. Otherwise the construction would recurse until the stack overflows.
+    fn new(name: &str, source: &'static Location<'static>) -> Result<Self, Vec<String>> {
+        CONSTRUCTING.with(|constructing| {
+            let mut constructing = constructing.borrow_mut();
+            let mut indices =
+                constructing.iter().enumerate().filter(|(_, (_, module_source))| *module_source == source);
+            if indices.clone().count() >= MAX_CONSTRUCTION_DEPTH {
+                let (index, _) = indices.next_back().unwrap();
+                return Err(constructing[index..]
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .chain(std::iter::once(name.to_string()))
+                    .collect());
+            }
+            constructing.push((name.to_string(), source));
+            Ok(Self)
+        })
+    }
+}
+
+impl Drop for ConstructionGuard {
+    fn drop(&mut self) {
+        CONSTRUCTING.with(|constructing| {
+            let _ = constructing.borrow_mut().pop();
+        });
+    }
+}
+
 /// Composite module.
 #[derive(Debug, Clone)]
 pub struct CompositeModule<I: Interface, O: Interface> {
@@ -24,6 +74,7 @@ pub struct CompositeModuleContext {
 
 impl CompositeModuleContext {
     /// Register given module to the context, and return virtual module with same I/O interfaces
+    #[track_caller]
     pub fn register<I: Interface, O: Interface>(
         &mut self, inst_postfix: Option<&str>, module: Module<I, O>,
     ) -> Module<I, O> {
@@ -69,7 +120,11 @@ impl CompositeModuleContext {
         Module::new(virtual_module.into())
     }
 
+    /// Fails the construction of the module, keeping the first error.
+    pub(crate) fn fail(&mut self, error: lir::ModuleError) { let _ = self.inner.error.get_or_insert(error); }
+
     /// register inline
+    #[track_caller]
     pub fn feedback<F: Interface>(&mut self) -> (F, Module<F, ()>) {
         let (source, sink) = self
             .register_inline(
@@ -100,11 +155,33 @@ impl<B: Interface> Default for CompositeModule<B, B> {
 }
 
 /// Creates a new composite module with given prefix for input and output channels.
+#[track_caller]
 pub fn composite<I: Interface, O: Interface, F: FnOnce(I, &mut CompositeModuleContext) -> O>(
     name: &str, input_prefix: Option<&str>, output_prefix: Option<&str>, f: F,
 ) -> CompositeModule<I, O> {
-    CompositeModule::<I, I>::new(name.to_string(), input_prefix.map(String::from), output_prefix.map(String::from))
-        .and_then(f)
+    let source = Location::caller();
+    let _guard = match ConstructionGuard::new(name, source) {
+        Ok(guard) => guard,
+        Err(cycle) => {
+            // The construction stops at the cycle, which is reported when the package is generated.
+            let mut module = CompositeModule::placeholder(
+                name.to_string(),
+                input_prefix.map(String::from),
+                output_prefix.map(String::from),
+            );
+            module.inner.source = Some(source);
+            module.inner.instantiation_cycle = Some(cycle);
+            return module;
+        }
+    };
+    let mut module =
+        CompositeModule::<I, I>::new(name.to_string(), input_prefix.map(String::from), output_prefix.map(String::from))
+            .and_then(|i, k| {
+                let o = f(i, k);
+                insert_protocol_checks(o, k)
+            });
+    module.inner.source = Some(source);
+    module
 }
 
 /// Creates new input interface from given interface type.
@@ -150,12 +227,56 @@ pub fn temp_interface<O: Interface>() -> lir::Interface {
 }
 
 impl<I: Interface, O: Interface> CompositeModule<I, O> {
+    /// Creates a module named `name` that instantiates the external module `name` in place of a module
+    /// whose construction failed, so that the enclosing modules can still be constructed.
+    fn placeholder(name: String, input_prefix: Option<String>, output_prefix: Option<String>) -> Self {
+        let external: Module<I, O> = ModuleInst::new(
+            name.clone(),
+            format!("{}_inst", name),
+            Vec::new(),
+            true,
+            input_prefix.clone(),
+            output_prefix.clone(),
+            None,
+        )
+        .into();
+        let mut inner = lir::CompositeModule::new(name, input_prefix, output_prefix);
+        inner.input_interface = input_interface::<I>();
+        inner.output_interface = inner.add_submodule(external.inner, input_interface::<I>());
+        Self { inner, _marker: PhantomData }
+    }
+
     /// Wraps the module with new input/output interface.
-    // TODO: add branded id to `F` to mimic pure functions.
+    ///
+    /// If `try_wrap` fails, the module is replaced by a placeholder and the error is reported when the
+    /// package is generated.
     pub fn wrap<Iw: Interface, Ow: Interface, F: FnOnce(&mut CompositeModuleContext, Iw, O) -> (I, Ow)>(
         self, f: F,
     ) -> CompositeModule<Iw, Ow> {
+        let (name, input_prefix, output_prefix) =
+            (self.inner.name.clone(), self.inner.input_prefix.clone(), self.inner.output_prefix.clone());
+        let (clock_domain, source) = (self.inner.clock_domain.clone(), self.inner.source);
+        self.try_wrap(f).unwrap_or_else(|error| {
+            let mut module = CompositeModule::placeholder(name, input_prefix, output_prefix);
+            module.inner.clock_domain = clock_domain;
+            module.inner.source = source;
+            module.inner.error = Some(error);
+            module
+        })
+    }
+
+    /// Wraps the module with new input/output interface.
+    ///
+    /// Fails if the interfaces returned by `f` do not match their types, if an output channel of `f` is
+    /// assigned to itself, e.g., when `f` feeds the output of a wire back to its input, or if the
+    /// construction of the module fails in `f`.
+    // TODO: add branded id to `F` to mimic pure functions.
+    pub fn try_wrap<Iw: Interface, Ow: Interface, F: FnOnce(&mut CompositeModuleContext, Iw, O) -> (I, Ow)>(
+        self, f: F,
+    ) -> Result<CompositeModule<Iw, Ow>, lir::ModuleError> {
         let mut ctx = CompositeModuleContext { inner: self.inner };
+        let name = ctx.inner.name.clone();
+        let interface_error = |error: InterfaceError| lir::ModuleError::Interface { module: name.clone(), error };
 
         // Takes old input/output interface.
         let old_output_interface = mem::take(&mut ctx.inner.output_interface);
@@ -163,12 +284,15 @@ impl<I: Interface, O: Interface> CompositeModule<I, O> {
 
         // Creates old input interface and new output interface.
         let (old_input_interface, new_output_interface) = {
-            let new_input_interface = Iw::try_from_inner(input_interface::<Iw>()).expect("internal compiler error");
-            let output_interface = O::try_from_inner(temp_interface::<O>()).expect("internal compiler error");
+            let new_input_interface = Iw::try_from_inner(input_interface::<Iw>()).map_err(interface_error)?;
+            let output_interface = O::try_from_inner(temp_interface::<O>()).map_err(interface_error)?;
             f(&mut ctx, new_input_interface, output_interface)
         };
+        if let Some(error) = ctx.inner.error.take() {
+            return Err(error);
+        }
 
-        let old_input_interface = old_input_interface.try_into_inner().expect("internal compiler error");
+        let old_input_interface = old_input_interface.try_into_inner().map_err(interface_error)?;
         let update_input = {
             let primitives = old_input_interface.into_primitives();
             primitives
@@ -176,102 +300,90 @@ impl<I: Interface, O: Interface> CompositeModule<I, O> {
                 .filter_map(|(interface, path)| interface.get_channel().map(|channel| (path, channel)))
                 .collect::<HashMap<_, _>>()
         };
-        let update_output = {
-            let primitives = old_output_interface.into_primitives();
-            primitives
-                .into_iter()
-                .filter_map(|(interface, path)| {
-                    interface.get_channel().map(|channel| {
-                        (path, match channel.endpoint() {
-                            lir::Endpoint::Input { path } => update_input.get(&path).unwrap().clone(),
-                            _ => channel,
-                        })
-                    })
+        let get_input = |path: &lir::EndpointPath| {
+            update_input.get(path).cloned().ok_or_else(|| lir::ModuleError::EndpointPath {
+                module: name.clone(),
+                path: path.to_string(),
+                typ: I::interface_typ().describe(),
+            })
+        };
+        let update_output = old_output_interface
+            .into_primitives()
+            .into_iter()
+            .filter_map(|(interface, path)| {
+                interface.get_channel().map(|channel| {
+                    Ok((path, match channel.endpoint() {
+                        lir::Endpoint::Input { path } => get_input(&path)?,
+                        _ => channel,
+                    }))
                 })
-                .collect::<HashMap<_, _>>()
+            })
+            .collect::<Result<HashMap<_, _>, lir::ModuleError>>()?;
+
+        // Resolves the channel assigned to the output of `f` at `path`, following the output channels
+        // that are assigned from other output channels.
+        let get_output = |path: &lir::EndpointPath| {
+            let mut visited = HashSet::new();
+            let mut path = path.clone();
+            loop {
+                let channel = some_or!(
+                    update_output.get(&path),
+                    return Err(lir::ModuleError::EndpointPath {
+                        module: name.clone(),
+                        path: path.to_string(),
+                        typ: O::interface_typ().describe(),
+                    })
+                );
+                match channel.endpoint() {
+                    lir::Endpoint::Temp { path: next } if visited.insert(path.clone()) => path = next,
+                    lir::Endpoint::Temp { .. } => {
+                        return Err(lir::ModuleError::CyclicAssignment { module: name.clone(), path: path.to_string() })
+                    }
+                    _ => return Ok(channel.clone()),
+                }
+            }
         };
 
-        // Updates old submodules' interfaces.
-        for (_, ref mut interface) in ctx.inner.submodules.iter_mut().take(old_submodules_len) {
-            *interface = interface
-                .clone()
+        // Updates the channels of the interface to the resolved input and output channels.
+        let update = |interface: lir::Interface, update_temp: bool| {
+            interface
                 .into_primitives()
                 .into_iter()
                 .map(|(interface, path)| {
-                    (
+                    Ok((
                         match interface {
                             lir::Interface::Unit => lir::Interface::Unit,
                             lir::Interface::Channel(channel) => lir::Interface::Channel(match channel.endpoint() {
-                                lir::Endpoint::Input { path } => update_input.get(&path).unwrap().clone(),
+                                lir::Endpoint::Input { path } if !update_temp => get_input(&path)?,
+                                lir::Endpoint::Temp { path } if update_temp => get_output(&path)?,
                                 _ => channel,
                             }),
-                            _ => panic!("internal compiler error"),
+                            _ => return Err(interface_error(lir::InterfaceError::PrimitivePath { path })),
                         },
                         path,
-                    )
+                    ))
                 })
-                .collect();
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|primitives| lir::Interface::try_from_primitives(primitives).map_err(interface_error))
+        };
+
+        // Updates old submodules' interfaces.
+        for (_, ref mut interface) in ctx.inner.submodules.iter_mut().take(old_submodules_len) {
+            *interface = update(interface.clone(), false)?;
         }
         for (_, ref mut interface) in ctx.inner.submodules.iter_mut() {
-            *interface = interface
-                .clone()
-                .into_primitives()
-                .into_iter()
-                .map(|(interface, path)| {
-                    (
-                        match interface {
-                            lir::Interface::Unit => lir::Interface::Unit,
-                            lir::Interface::Channel(channel) => lir::Interface::Channel(match channel.endpoint() {
-                                lir::Endpoint::Temp { path } => {
-                                    let channel = update_output.get(&path).unwrap().clone();
-                                    if matches!(channel.endpoint(), lir::Endpoint::Temp { .. }) {
-                                        // TODO: Analyze cyclic assignment and handle it
-                                        todo!()
-                                    }
-                                    channel
-                                }
-                                _ => channel,
-                            }),
-                            _ => panic!("internal compiler error"),
-                        },
-                        path,
-                    )
-                })
-                .collect();
+            *interface = update(interface.clone(), true)?;
         }
 
         // Updates new output channels.
-        let new_output_interface = new_output_interface.try_into_inner().expect("internal compiler error");
-        let new_output_interface = new_output_interface
-            .into_primitives()
-            .into_iter()
-            .map(|(interface, path)| {
-                (
-                    match interface {
-                        lir::Interface::Unit => lir::Interface::Unit,
-                        lir::Interface::Channel(channel) => lir::Interface::Channel(match channel.endpoint() {
-                            lir::Endpoint::Temp { path } => {
-                                let channel = update_output.get(&path).unwrap().clone();
-                                if matches!(channel.endpoint(), lir::Endpoint::Temp { .. }) {
-                                    // TODO: Analyze cyclic assignment and handle it
-                                    todo!()
-                                }
-                                channel
-                            }
-                            _ => channel,
-                        }),
-                        _ => panic!("internal compiler error"),
-                    },
-                    path,
-                )
-            })
-            .collect();
+        let new_output_interface = new_output_interface.try_into_inner().map_err(interface_error)?;
+        let new_output_interface = update(new_output_interface, true)?;
 
         // Wires new input/output channels.
         ctx.inner.input_interface = input_interface::<Iw>();
         ctx.inner.output_interface = new_output_interface;
 
-        CompositeModule { inner: ctx.inner, _marker: PhantomData }
+        Ok(CompositeModule { inner: ctx.inner, _marker: PhantomData })
     }
 
     /// Transforms the output interface.
@@ -302,3 +414,78 @@ impl<I: Interface, O: Interface, F: Interface> CompositeModule<(I, F), (O, F)> {
         self.wrap(|_, input, (output, feedback)| ((input, feedback), output))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    type Byte = Bits<U<8>>;
+
+    fn ping() -> Module<UniChannel<Byte>, UniChannel<Byte>> {
+        composite::<UniChannel<Byte>, UniChannel<Byte>, _>("ping", None, None, |input, k| input.comb_inline(k, pong()))
+            .build()
+    }
+
+    fn pong() -> Module<UniChannel<Byte>, UniChannel<Byte>> {
+        composite::<UniChannel<Byte>, UniChannel<Byte>, _>("pong", None, None, |input, k| input.comb_inline(k, ping()))
+            .build()
+    }
+
+    fn stage() -> Module<UniChannel<Byte>, UniChannel<Byte>> {
+        hir::Fsm::<UniChannel<Byte>, UniChannel<Byte>, Byte, _>::new(
+            "stage",
+            |fwd, bwd, state| (state, bwd, fwd),
+            0.into(),
+        )
+        .into()
+    }
+
+    fn chain(depth: usize) -> Module<UniChannel<Byte>, UniChannel<Byte>> {
+        composite::<UniChannel<Byte>, UniChannel<Byte>, _>("chain", Some("in"), Some("out"), |input, k| {
+            let input = input.comb_inline(k, stage());
+            if depth == 0 {
+                input
+            } else {
+                input.comb_inline(k, chain(depth - 1))
+            }
+        })
+        .build()
+    }
+
+    #[test]
+    fn instantiation_cycle_is_a_package_error() {
+        let error = generate(package(ping()), |package, dir| package.gen_vir(dir)).unwrap_err();
+        assert!(
+            matches!(&error, PackageError::InstantiationCycle { cycle } if cycle == &["ping", "pong", "ping"]),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn recursive_generator_at_one_location_terminates() {
+        let files = generate(package(chain(3)), |package, dir| package.gen_vir(dir)).unwrap();
+        assert_eq!(files["chain_inner.v"].matches("Begin FSM stage").count(), 4, "{}", files["chain_inner.v"]);
+    }
+
+    #[test]
+    fn feedback_source_is_the_caller() {
+        let mut line = 0;
+        let module =
+            composite::<UniChannel<Byte>, UniChannel<Byte>, _>("loopback", Some("in"), Some("out"), |input, k| {
+                line = line!() + 1;
+                let (source, sink) = k.feedback::<UniChannel<Byte>>();
+                input.comb_inline(k, stage()).comb_inline(k, sink);
+                source
+            })
+            .build();
+
+        let files = generate(package(module), |package, dir| package.gen_dot(dir)).unwrap();
+        let dot = &files["loopback.dot"];
+        let cluster =
+            format!("label=\"feedback\";\n    style=\"rounded\";\n    tooltip=\"src = {}:{}\";", file!(), line);
+        assert!(dot.contains(&cluster), "{}", dot);
+    }
+}
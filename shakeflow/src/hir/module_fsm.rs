@@ -1,12 +1,80 @@
 //! Finite state machine (Mealy machine).
 
+use std::cell::RefCell;
 use std::fmt;
 use std::marker::PhantomData;
 use std::ops::*;
+use std::panic::Location;
 
 use crate::hir::*;
 use crate::*;
 
+/// Assertions and independences of an FSM.
+type Assertions = (Vec<lir::Assertion>, Vec<lir::Independence>);
+
+thread_local! {
+    /// Assertions and independences made while the function of an FSM is being called.
+    static ASSERTIONS: RefCell<Option<Assertions>> = RefCell::new(None);
+}
+
+/// Collects the assertions made while it is alive.
+///
+/// On drop, the assertions being collected before are restored, even if the function of the FSM
+/// panics.
+#[derive(Debug)]
+struct AssertionsGuard {
+    prev: Option<Assertions>,
+}
+
+impl AssertionsGuard {
+    fn new() -> Self { Self { prev: ASSERTIONS.with(|assertions| assertions.replace(Some((Vec::new(), Vec::new())))) } }
+
+    /// Returns the collected assertions.
+    fn finish(self) -> Assertions { ASSERTIONS.with(|assertions| assertions.borrow_mut().take()).unwrap_or_default() }
+}
+
+impl Drop for AssertionsGuard {
+    fn drop(&mut self) { ASSERTIONS.with(|assertions| *assertions.borrow_mut() = self.prev.take()); }
+}
+
+/// Adds an assertion to the FSM whose function is being called.
+///
+/// # Panics
+///
+/// Panics if no function of an FSM is being called.
+pub(crate) fn add_assertion(kind: lir::AssertionKind, cond: lir::ExprId, message: &str) {
+    ASSERTIONS.with(|assertions| {
+        assertions
+            .borrow_mut()
+            .as_mut()
+            .unwrap_or_else(|| panic!("{}: assertions can only be made inside FSM functions", message))
+            .0
+            .push(lir::Assertion { kind, cond, message: message.to_string() })
+    })
+}
+
+/// Asserts that the backward value of the output interface of the FSM whose function is being
+/// called does not depend combinationally on the `fwd_bit`-th bit of its forward value.
+///
+/// It is checked only in the simulator, by flipping the bit after every evaluation, and the
+/// simulation fails with `SimError::CombinationalDependency` if the backward value changes. The
+/// backends do not emit it, since it is not a property of the values of a cycle that an assertion
+/// can state.
+///
+/// # Panics
+///
+/// Panics if no function of an FSM is being called.
+pub fn assert_bwd_independent(fwd_bit: usize, message: &str) {
+    ASSERTIONS.with(|assertions| {
+        assertions
+            .borrow_mut()
+            .as_mut()
+            .unwrap_or_else(|| panic!("{}: assertions can only be made inside FSM functions", message))
+            .1
+            .push(lir::Independence { fwd_bit, message: message.to_string() })
+    })
+}
+
 /// Finite state machine (Mealy machine).
 #[derive(Clone)]
 pub struct Fsm<
@@ -25,6 +93,10 @@ pub struct Fsm<
     pub(crate) f: F,
     /// Initial value of registers in the FSM.
     pub(crate) init: Expr<'static, S>,
+    /// Reset of registers in the FSM, overriding the reset style of the package.
+    reset: Option<lir::ResetKind>,
+    /// Source location where the FSM is created.
+    source: &'static Location<'static>,
     _marker: PhantomData<(I, O)>,
 }
 
@@ -40,9 +112,21 @@ impl<
     > Fsm<I, O, S, F>
 {
     /// Creates a new FSM.
+    #[track_caller]
     pub fn new(module_name: &str, f: F, init: Expr<'static, S>) -> Self {
-        Self { module_name: module_name.to_string(), f, init, _marker: PhantomData }
+        Self {
+            module_name: module_name.to_string(),
+            f,
+            init,
+            reset: None,
+            source: Location::caller(),
+            _marker: PhantomData,
+        }
     }
+
+    /// Sets the kind of reset of registers in the FSM, overriding the reset style of the package.
+    #[must_use]
+    pub fn with_reset(self, reset: lir::ResetKind) -> Self { Self { reset: Some(reset), ..self } }
 }
 
 impl<
@@ -57,7 +141,11 @@ impl<
     > fmt::Debug for Fsm<I, O, S, F>
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Fsm").field("init", &self.init).finish()
+        f.debug_struct("Fsm")
+            .field("init", &self.init)
+            .field("reset", &self.reset)
+            .field("source", &self.source)
+            .finish()
     }
 }
 
@@ -77,7 +165,10 @@ impl<
         let i_fwd = Expr::input(Some("in".to_string()));
         let o_bwd = Expr::input(Some("out".to_string()));
         let s = Expr::input(Some("st".to_string()));
+
+        let guard = AssertionsGuard::new();
         let (o_fwd, i_bwd, s) = (module.f)(i_fwd, o_bwd, s);
+        let (assertions, independences) = guard.finish();
 
         lir::Fsm {
             input_interface_typ: I::interface_typ(),
@@ -87,6 +178,133 @@ impl<
             input_bwd: i_bwd.into_inner(),
             state: s.into_inner(),
             init: module.init.into_inner(),
+            assertions,
+            independences,
+            reset: module.reset,
+            source: Some(module.source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::sim::{SimError, Simulator};
+    use crate::testing::*;
+    use crate::*;
+
+    type Byte = Bits<U<8>>;
+
+    /// Returns an FSM passing values through, asserting that the ready of its output does not depend
+    /// on the valid.
+    fn checker() -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        hir::Fsm::<VrChannel<Byte>, VrChannel<Byte>, (), _>::new(
+            "checker",
+            |fwd, bwd, state| {
+                assert_bwd_independent(8, "ready depends combinationally on valid");
+                (fwd, bwd, state)
+            },
+            ().into(),
+        )
+        .into()
+    }
+
+    /// Returns an FSM whose ready is its valid.
+    fn echo() -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        hir::Fsm::<VrChannel<Byte>, VrChannel<Byte>, (), _>::new(
+            "echo",
+            |fwd, _, state| (fwd, ReadyProj { ready: fwd.valid }.into(), state),
+            ().into(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn bwd_dependency_fails_simulation() {
+        let module = composite::<VrChannel<Byte>, VrChannel<Byte>, _>("top", Some("in"), Some("out"), |input, k| {
+            input.comb_inline(k, checker()).comb_inline(k, echo())
+        })
+        .build();
+        assert!(Simulator::new(&checker()).is_ok());
+
+        let error = Simulator::new(&module).unwrap_err();
+        assert!(
+            matches!(&error, SimError::CombinationalDependency { fsm, message }
+                if fsm == "checker_0" && message == "ready depends combinationally on valid"),
+            "{:?}",
+            error
+        );
+    }
+
+    /// Returns an FSM counting the cycles in which its input is set, asserting that the count stays
+    /// below 3.
+    fn counter() -> Module<UniChannel<bool>, UniChannel<Bits<U<2>>>> {
+        hir::Fsm::<UniChannel<bool>, UniChannel<Bits<U<2>>>, Bits<U<2>>, _>::new(
+            "counter",
+            |fwd, bwd, state| {
+                state.is_lt(3.into()).assert("count reaches 3");
+                fwd.assume("input is set");
+                state.is_eq(2.into()).cover("count reaches 2");
+                (state, bwd, fwd.cond((state + 1.into()).resize(), state))
+            },
+            0.into(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn assertions_are_emitted_as_sva() {
+        let files = generate(package(counter()), |package, dir| package.gen_vir(dir)).unwrap();
+        assert!(files["counter_inner.v"].contains(
+            r#"wire assert_0;
+
+assign assert_0 = st_reg < 2'b11;
+
+`ifdef FORMAL
+assert property (@(posedge clk) disable iff (rst) assert_0)
+else $error("count reaches 3");
+`endif
+
+`ifdef FORMAL
+assume property (@(posedge clk) disable iff (rst) in)
+else $error("input is set");
+`endif
+
+wire assert_2;
+
+assign assert_2 = st_reg == 2'b10;
+
+`ifdef FORMAL
+cover property (@(posedge clk) disable iff (rst) assert_2); // count reaches 2
+`endif"#
+        ));
+    }
+
+    #[test]
+    fn failed_assertion_fails_simulation() {
+        let mut sim = Simulator::new(&counter()).unwrap();
+        let (input, _) = sim.input_channels().remove(0);
+        sim.set_input_fwd(&input, vec![true]);
+
+        for _ in 0..3 {
+            sim.step().unwrap();
         }
+        assert_eq!(sim.cover_counts(), vec![("counter".to_string(), "count reaches 2".to_string(), 1)]);
+
+        let error = sim.step().unwrap_err();
+        assert!(
+            matches!(&error, SimError::AssertionFailed { fsm, kind: lir::AssertionKind::Assert, message }
+                if fsm == "counter" && message == "count reaches 3"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "assertions can only be made inside FSM functions")]
+    fn assertions_outside_fsms_panic() {
+        // The guard of the FSM restores the previous state, where no assertions are collected.
+        let _ = counter();
+        Expr::<bool>::from(true).assert("outside");
     }
 }
@@ -2,6 +2,7 @@
 
 use std::fmt;
 use std::marker::PhantomData;
+use std::panic::Location;
 
 use crate::hir::*;
 use crate::*;
@@ -23,11 +24,18 @@ pub struct ModuleInst<I: Interface, O: Interface> {
     pub(crate) output_prefix: Option<String>,
     /// Shakeflow Module
     pub(crate) shakeflow_module: Option<Module<I, O>>,
+    /// Additional clock and reset ports, with the clock domains driving them.
+    pub(crate) clock_ports: Vec<(String, Option<String>)>,
+    /// Polarity of the reset ports of the external module.
+    pub(crate) reset_polarity: lir::ResetPolarity,
+    /// Source location where the module instantiation is created.
+    source: &'static Location<'static>,
     _marker: PhantomData<(I, O)>,
 }
 
 impl<I: Interface, O: Interface> ModuleInst<I, O> {
     /// Creates a new Module instantiation.
+    #[track_caller]
     pub fn new(
         module_name: String, inst_name: String, params: Vec<(String, usize)>, has_clkrst: bool,
         input_prefix: Option<String>, output_prefix: Option<String>, shakeflow_module: Option<Module<I, O>>,
@@ -40,9 +48,17 @@ impl<I: Interface, O: Interface> ModuleInst<I, O> {
             input_prefix,
             output_prefix,
             shakeflow_module,
+            clock_ports: Vec::new(),
+            reset_polarity: lir::ResetPolarity::ActiveHigh,
+            source: Location::caller(),
             _marker: PhantomData,
         }
     }
+
+    /// Sets the polarity of the reset ports of the external module, which is active-high by default.
+    ///
+    /// Reset ports of shakeflow modules have the polarity of the package regardless of this.
+    pub fn with_reset_polarity(self, reset_polarity: lir::ResetPolarity) -> Self { Self { reset_polarity, ..self } }
 }
 
 impl<I: Interface, O: Interface> fmt::Debug for ModuleInst<I, O> {
@@ -54,12 +70,16 @@ impl<I: Interface, O: Interface> fmt::Debug for ModuleInst<I, O> {
             .field("has_clkrst", &self.has_clkrst)
             .field("input_prefix", &self.input_prefix)
             .field("output_prefix", &self.output_prefix)
+            .field("clock_ports", &self.clock_ports)
+            .field("reset_polarity", &self.reset_polarity)
+            .field("source", &self.source)
             .finish()
     }
 }
 
 impl<I: Interface, O: Interface> ModuleInst<I, O> {
     /// generates module_inst fron shakeflow module
+    #[track_caller]
     pub(crate) fn from_module(inst_postfix: Option<&str>, module: Module<I, O>) -> Self {
         let module_name = format!("{}_inner", module.inner.get_module_name());
         let inst_name = join_options("_", [
@@ -92,6 +112,9 @@ impl<I: Interface, O: Interface> From<ModuleInst<I, O>> for lir::ModuleInst {
             input_prefix: module.input_prefix,
             output_prefix: module.output_prefix,
             module: module.shakeflow_module.map(|module| module.inner),
+            clock_ports: module.clock_ports,
+            reset_polarity: module.reset_polarity,
+            source: Some(module.source),
         }
     }
 }
@@ -114,6 +137,7 @@ macro_rules! impl_custom_inst {
             for $in {
                 type Out = $out;
 
+                #[track_caller]
                 fn $module_name<$(const [<$params:snake:upper>]: usize,)*>(self, k: &mut CompositeModuleContext, inst_name: &str, input_prefix: Option<&str>, output_prefix: Option<&str>) -> Self::Out {
                     let params = vec![$((stringify!($params), [<$params:snake:upper>]),)*];
                     self.module_inst::<Self::Out>(k, stringify!($module_name), inst_name, params, $use_clk, input_prefix, output_prefix)
@@ -141,6 +165,7 @@ macro_rules! impl_custom_inst {
             > [<$module_name:camel Ext>]<$($out_generics,)*$($out_const_generics,)*>
             for $in<$($in_generics,)*$($in_const_generics,)*> {
                 type Out = $out<$($out_generics,)*$($out_const_generics,)*>;
+                #[track_caller]
                 fn $module_name<$(const [<$params:snake:upper>]: usize,)*>(self, k: &mut CompositeModuleContext, inst_name: &str, input_prefix: Option<&str>, output_prefix: Option<&str>) -> Self::Out {
                     let params = vec![$((stringify!($params), [<$params:snake:upper>]),)*];
                     self.module_inst::<Self::Out>(k, stringify!($module_name), inst_name, params, $use_clk, input_prefix, output_prefix)
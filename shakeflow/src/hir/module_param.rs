@@ -0,0 +1,134 @@
+//! Parameterized module.
+
+use crate::hir::*;
+use crate::*;
+
+/// Module parameterized by Verilog parameters.
+///
+/// Each specialization is a module generated with const generics for one set of parameter values,
+/// so their interface types may differ. In Verilog, they are emitted as one module with
+/// `parameter`s, whose widths and numbers are expressions of the parameters, and instances override
+/// the parameters with `#(...)`. The specializations should have the same structure other than
+/// widths and numbers.
+#[derive(Debug, Clone)]
+pub struct ParamModule {
+    pub(crate) inner: lir::ParamModule,
+}
+
+impl ParamModule {
+    /// Creates a new parameterized module with the given parameter names.
+    pub fn new(name: &str, params: &[&str]) -> Self {
+        Self {
+            inner: lir::ParamModule {
+                name: name.to_string(),
+                params: params.iter().map(|param| param.to_string()).collect(),
+                specializations: Vec::new(),
+            },
+        }
+    }
+
+    /// Adds the specialization for the parameter values. The first specialization gives the
+    /// default values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of values differs from that of parameters, the values are already
+    /// specialized, or the module is not named after the parameterized module.
+    #[must_use]
+    pub fn specialize<I: Interface, O: Interface>(mut self, values: &[usize], module: Module<I, O>) -> Self {
+        assert_eq!(values.len(), self.inner.params.len(), "number of parameter values should match parameters");
+        assert!(
+            self.inner.specializations.iter().all(|(specialized, _)| specialized != values),
+            "parameter values {:?} of {} are already specialized",
+            values,
+            self.inner.name
+        );
+        assert_eq!(
+            module.inner.get_module_name(),
+            self.inner.name,
+            "specialization should be named after the parameterized module"
+        );
+        self.inner.specializations.push((values.to_vec(), module.inner));
+        self
+    }
+
+    /// Returns an instantiation of the specialization for the parameter values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values are not specialized, or the specialization has a different interface.
+    #[track_caller]
+    pub fn inst<I: Interface, O: Interface>(&self, inst_postfix: Option<&str>, values: &[usize]) -> Module<I, O> {
+        let (_, module) = self
+            .inner
+            .specializations
+            .iter()
+            .find(|(specialized, _)| specialized == values)
+            .unwrap_or_else(|| panic!("parameter values {:?} of {} are not specialized", values, self.inner.name));
+        assert!(
+            module.inner.input_interface_typ() == I::interface_typ()
+                && module.inner.output_interface_typ() == O::interface_typ(),
+            "specialization for parameter values {:?} of {} has a different interface",
+            values,
+            self.inner.name
+        );
+
+        let module_name = format!("{}_inner", self.inner.name);
+        let inst_name =
+            join_options("_", [Some(module_name.clone()), Some("inst".to_string()), inst_postfix.map(String::from)])
+                .unwrap();
+        ModuleInst::new(
+            module_name,
+            inst_name,
+            self.inner.params.iter().cloned().zip(values.iter().copied()).collect(),
+            true,
+            module.inner.input_prefix(),
+            module.inner.output_prefix(),
+            Some(Module::new(module.clone())),
+        )
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    fn pass<const N: usize>() -> Module<VrChannel<Bits<U<N>>>, VrChannel<Bits<U<N>>>> {
+        hir::Fsm::<VrChannel<Bits<U<N>>>, VrChannel<Bits<U<N>>>, (), _>::new(
+            "pass",
+            |fwd, bwd, state| (fwd, bwd, state),
+            ().into(),
+        )
+        .into()
+    }
+
+    fn param_package() -> Package {
+        let mut package = Package::default();
+        package.add_param(
+            ParamModule::new("pass", &["WIDTH"]).specialize(&[8], pass::<8>()).specialize(&[16], pass::<16>()),
+        );
+        package
+    }
+
+    #[test]
+    fn specializations_are_unified_in_verilog() {
+        let files = generate(param_package(), |package, dir| package.gen_vir(dir)).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["pass_inner.v"]);
+
+        let code = &files["pass_inner.v"];
+        assert!(code.contains("parameter WIDTH = 8"), "{}", code);
+        assert!(code.contains("input wire [WIDTH-1:0] in,"), "{}", code);
+        assert!(code.contains("(WIDTH == 8) | (WIDTH == 16)"), "{}", code);
+    }
+
+    #[test]
+    fn specializations_are_generated_separately_without_parameters() {
+        let files = generate(param_package(), |package, dir| package.gen_fir(dir)).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["pass_16_inner.fir", "pass_8_inner.fir"]);
+        assert!(files["pass_8_inner.fir"].contains("input in : UInt<8>"), "{}", files["pass_8_inner.fir"]);
+        assert!(files["pass_16_inner.fir"].contains("input in : UInt<16>"), "{}", files["pass_16_inner.fir"]);
+    }
+}
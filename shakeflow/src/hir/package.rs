@@ -14,6 +14,16 @@ pub enum PackageError {
 
     #[error("module error: {error:?}")]
     Module { error: lir::ModuleError },
+
+    #[error("modules named {name} have different logic")]
+    NameConflict { name: String },
+
+    #[error("module instantiation cycle through {}", .cycle.join(" -> "))]
+    InstantiationCycle { cycle: Vec<String> },
+
+    #[cfg(feature = "serde")]
+    #[error("json error: {error:?}")]
+    Json { error: serde_json::Error },
 }
 
 /// Package.
@@ -21,15 +31,55 @@ pub enum PackageError {
 pub struct Package {
     /// Modules.
     pub modules: Vec<lir::Module>,
+
+    /// Parameterized modules.
+    pub param_modules: Vec<lir::ParamModule>,
+
+    /// Reset style of state registers, which can be overridden by each FSM.
+    pub reset_style: lir::ResetStyle,
 }
 
 impl Package {
     /// Adds the given module to package.
     pub fn add<I: Interface, O: Interface>(&mut self, module: Module<I, O>) { self.modules.push(module.inner); }
 
-    /// Scan modules to see if there is submodule instatiation in the
+    /// Adds the given module in a clock domain to package.
+    pub fn add_clocked<I: Interface, O: Interface, D: ClockDomain>(&mut self, module: ClockedModule<I, O, D>) {
+        self.modules.push(module.inner.inner);
+    }
+
+    /// Adds the given parameterized module to package.
+    pub fn add_param(&mut self, module: ParamModule) { self.param_modules.push(module.inner); }
+
+    /// Sets the reset style of state registers.
+    pub fn set_reset_style(&mut self, reset_style: lir::ResetStyle) { self.reset_style = reset_style; }
+
+    /// Scans submodule instantiations of the modules in the package in topological order, i.e., each
+    /// module comes after the modules it instantiates.
     pub fn scan_submodule_inst(&self) -> Vec<lir::Module> {
-        self.modules.iter().flat_map(|module| module.scan_submodule_inst()).collect()
+        self.modules
+            .iter()
+            .chain(self.param_modules.iter().flat_map(|module| module.specializations.iter().map(|(_, module)| module)))
+            .flat_map(|module| module.scan_submodule_inst())
+            .collect()
+    }
+
+    /// Returns an error if the construction of a module in the package failed, e.g., because it is
+    /// stopped at an instantiation cycle, i.e., the module instantiates itself directly or indirectly.
+    pub(crate) fn check_construction(&self) -> Result<(), PackageError> {
+        let specializations =
+            self.param_modules.iter().flat_map(|module| module.specializations.iter().map(|(_, module)| module));
+        let error = self.modules.iter().chain(specializations).find_map(|module| {
+            module.find_composite(&|module| match (&module.instantiation_cycle, &module.error) {
+                (Some(cycle), _) => Some(PackageError::InstantiationCycle { cycle: cycle.clone() }),
+                (None, Some(error)) => Some(PackageError::Module { error: error.clone() }),
+                (None, None) => None,
+            })
+        });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
     }
 
     /// Walk the module structure and return a vec of mutable refs to names of all inner `ModuleInst`s.
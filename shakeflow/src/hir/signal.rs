@@ -1,8 +1,10 @@
+use std::any::TypeId;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 
 use tuple_utils::*;
 
+use crate::lir::bits::{get_elts, join_elts};
 use crate::*;
 
 /// Bit-representable values.
@@ -39,6 +41,17 @@ pub trait Signal: 'static + Debug + Clone {
     /// ```
     fn port_decls() -> lir::PortDecls;
 
+    /// Type of the signal with the names of its Rust types, which has the layout of `port_decls()`.
+    fn signal_typ() -> lir::SignalTyp { Self::port_decls().into() }
+
+    /// Decodes bits in the layout of `port_decls()` into a host-side value.
+    ///
+    /// By default, it is decoded by the structure of `port_decls()`.
+    fn decode(bits: &[bool]) -> Value { Value::decode(&Self::port_decls(), bits) }
+
+    /// Converts into a host-side value.
+    fn to_value(self) -> Value { Self::decode(&self.transl()) }
+
     /// Generates a LIR value.
     fn to_lir(self) -> lir::Expr {
         lir::Expr::Constant { bits: self.transl().into_iter().collect::<Vec<_>>(), typ: Self::port_decls() }
@@ -78,17 +91,45 @@ fn expand_dim_typ<N: Num>(typ: lir::PortDecls) -> lir::PortDecls {
 /// TODO: Support dimension greater than 2
 #[derive(Debug, Clone)]
 pub struct VarArray<V: Signal, N: Num> {
+    /// Bits of the elements, in the layout of `port_decls()`.
+    inner: Vec<bool>,
     _marker: PhantomData<(V, N)>,
 }
 
-impl<V: Signal, N: Num> Copy for VarArray<V, N> {}
+impl<V: Signal, N: Num> VarArray<V, N> {
+    /// Creates new array.
+    pub fn new(inner: Vec<V>) -> Self {
+        assert_eq!(inner.len(), N::WIDTH);
+        Self { inner: transl_elts(inner), _marker: PhantomData }
+    }
+}
+
+impl<V: Default + Signal, N: Num> Default for VarArray<V, N> {
+    fn default() -> Self { Self::new(vec![V::default(); N::WIDTH]) }
+}
 
 impl<V: Signal, N: Num> Signal for VarArray<V, N> {
     const WIDTH: usize = V::WIDTH * N::WIDTH;
 
-    fn transl(self) -> Vec<bool> { todo!() }
+    fn transl(self) -> Vec<bool> { self.inner }
 
     fn port_decls() -> lir::PortDecls { expand_dim_typ::<N>(V::port_decls()) }
+
+    fn decode(bits: &[bool]) -> Value { decode_elts::<V>(bits, N::WIDTH) }
+}
+
+/// Translates elements of an array, distributing them over the leaves of `V::port_decls()`.
+fn transl_elts<V: Signal>(inner: Vec<V>) -> Vec<bool> {
+    join_elts(&inner.into_iter().map(|v| v.transl()).collect::<Vec<_>>(), &V::port_decls())
+}
+
+/// Decodes elements of an array of `count` elements. Arrays of `bool` are decoded as bits.
+fn decode_elts<V: Signal>(bits: &[bool], count: usize) -> Value {
+    assert_eq!(bits.len(), V::WIDTH * count);
+    if TypeId::of::<V>() == TypeId::of::<bool>() {
+        return Value::Bits(bits.to_vec());
+    }
+    Value::Array((0..count).map(|i| V::decode(&get_elts(bits, &V::port_decls(), i, 1))).collect())
 }
 
 #[allow(missing_docs)]
@@ -107,6 +148,17 @@ macro_rules! impl_signal_tuple {
             fn port_decls() -> lir::PortDecls {
                 lir::PortDecls::Struct(vec![(Some("0".to_string()), <$a as Signal>::port_decls())])
             }
+
+            fn signal_typ() -> lir::SignalTyp {
+                lir::SignalTyp::Struct {
+                    name: None,
+                    members: vec![("0".to_string(), Some("0".to_string()), <$a as Signal>::signal_typ())],
+                }
+            }
+
+            fn decode(bits: &[bool]) -> Value {
+                Value::Struct(vec![("0".to_string(), <$a as Signal>::decode(bits))])
+            }
         }
     };
     ($($a:ident)+) => {
@@ -134,6 +186,33 @@ macro_rules! impl_signal_tuple {
                     _ => panic!("internal compiler error"),
                 }
             }
+
+            fn signal_typ() -> lir::SignalTyp {
+                let members = vec![$(<$a as Signal>::signal_typ(),)+];
+                lir::SignalTyp::Struct {
+                    name: None,
+                    members: members
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, typ)| (i.to_string(), Some(i.to_string()), typ))
+                        .collect(),
+                }
+            }
+
+            fn decode(bits: &[bool]) -> Value {
+                let decoders: Vec<(usize, fn(&[bool]) -> Value)> = vec![$((<$a as Signal>::WIDTH, <$a as Signal>::decode),)+];
+                let mut offset = 0;
+                Value::Struct(
+                    decoders
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (width, decode))| {
+                            offset += width;
+                            (i.to_string(), decode(&bits[(offset - width)..offset]))
+                        })
+                        .collect(),
+                )
+            }
         }
     };
 }
@@ -174,9 +253,14 @@ impl_signal!(u128);
 impl_signal!(usize);
 
 /// Array type
+///
+/// The bits of an array are laid out by the leaves of `port_decls()`, as in `lir::Expr::Constant`:
+/// the `i`-th element of each leaf of the element type is stored at the `i`-th slice of the leaf.
+/// Hence for arrays of structs, the first members of all elements come before the second members,
+/// instead of the elements being concatenated as a whole.
 #[derive(Debug, Clone)]
 pub struct Array<V: Signal, N: Num> {
-    inner: Vec<V>,
+    pub(crate) inner: Vec<V>,
     _marker: PhantomData<N>,
 }
 
@@ -200,12 +284,66 @@ impl<V: Signal, N: Num> Signal for Array<V, N> {
 
     fn transl(self) -> Vec<bool> {
         assert_eq!(N::WIDTH, self.inner.len());
-        self.inner.into_iter().flat_map(|v| v.transl()).collect()
+        transl_elts(self.inner)
     }
 
     fn port_decls() -> lir::PortDecls { V::port_decls().multiple(N::WIDTH) }
+
+    fn decode(bits: &[bool]) -> Value { decode_elts::<V>(bits, N::WIDTH) }
 }
 
 impl<const N: usize> From<[bool; N]> for Bits<U<N>> {
     fn from(inner: [bool; N]) -> Self { Bits::new(inner.into_iter().collect()) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    type Elt = (Bits<U<2>>, bool);
+
+    fn elt(value: usize, flag: bool) -> Elt { (Bits::new(usize_to_bitvec(2, value)), flag) }
+
+    fn elts() -> Vec<Elt> { vec![elt(1, true), elt(2, false), elt(3, true)] }
+
+    #[test]
+    fn arrays_of_structs_round_trip() {
+        let array = Array::<Elt, U<3>>::new(elts());
+        let expected = Value::Array(elts().into_iter().map(Elt::to_value).collect());
+        assert_eq!(array.clone().to_value(), expected);
+
+        // Elements are laid out leaf-major: the first members of all elements, then the second ones.
+        assert_eq!(array.transl(), [true, false, false, true, true, true, true, false, true]);
+
+        let nested = Array::<Array<Elt, U<3>>, U<2>>::new(vec![Array::new(elts()), Array::new(elts())]);
+        assert_eq!(nested.to_value(), Value::Array(vec![expected.clone(), expected]));
+
+        let var_array = VarArray::<Elt, U<3>>::new(elts());
+        assert_eq!(var_array.clone().transl(), Array::<Elt, U<3>>::new(elts()).transl());
+        assert_eq!(var_array.to_value(), Array::<Elt, U<3>>::new(elts()).to_value());
+    }
+
+    #[test]
+    fn array_constants_match_generated_verilog() {
+        let module = composite::<UniChannel<bool>, UniChannel<Array<Elt, U<3>>>, _>(
+            "array_constant",
+            Some("in"),
+            Some("out"),
+            |input, k| {
+                input.fsm::<(), UniChannel<Array<Elt, U<3>>>, _>(k, None, ().into(), |_, _, state| {
+                    (Array::<Elt, U<3>>::new(elts()).into(), ().into(), state)
+                })
+            },
+        )
+        .build();
+        let files = generate(package(module), |package, dir| package.gen_vir(dir)).unwrap();
+        let verilog = &files["array_constant_inner.v"];
+
+        // Each member of the elements is a port with the `i`-th element at the `i`-th slice.
+        assert!(verilog.contains("output wire [6-1:0] out_0,"));
+        assert!(verilog.contains("output wire [3-1:0] out_1"));
+        assert!(verilog.contains("assign fsm_0_out_0 = 6'b111001;"));
+        assert!(verilog.contains("assign fsm_0_out_1 = 3'b101;"));
+    }
+}
@@ -0,0 +1,161 @@
+//! Host-side values of signals.
+
+use std::fmt;
+
+use crate::lir::bits::{join_elts, to_usize};
+use crate::*;
+
+/// Host-side value of a signal, decoded from its bit representation.
+///
+/// Values follow the layout of `lir::PortDecls`, and are decoded by `Signal::decode` and encoded
+/// back by `Value::encode`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    /// Bits, LSB first.
+    Bits(Vec<bool>),
+
+    /// Variant of an enum derived by `#[derive(Signal)]`.
+    Enum {
+        /// Name of the variant.
+        variant: String,
+
+        /// Encoding of the variant.
+        bits: Vec<bool>,
+    },
+
+    /// Struct with named members, in declaration order.
+    Struct(Vec<(String, Value)>),
+
+    /// Array of elements.
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// Creates a bits value of the given width from an integer.
+    pub fn from_usize(width: usize, value: usize) -> Self { Self::Bits(usize_to_bitvec(width, value)) }
+
+    /// Decodes bits by the structure of the type. Members of structs are decoded by their types, and
+    /// the members of unnamed members are inlined; bits of the other types are decoded as they are.
+    pub fn decode(typ: &lir::PortDecls, bits: &[bool]) -> Self {
+        assert_eq!(bits.len(), typ.width());
+        match typ {
+            lir::PortDecls::Bits(_) => Self::Bits(bits.to_vec()),
+            lir::PortDecls::Struct(inner) => {
+                let mut offset = 0;
+                let mut members = Vec::new();
+                for (name, member) in inner {
+                    let value = Self::decode(member, &bits[offset..(offset + member.width())]);
+                    offset += member.width();
+                    match (name, value) {
+                        (Some(name), value) => members.push((name.clone(), value)),
+                        (None, Self::Struct(inner)) => members.extend(inner),
+                        (None, value) => members.push((String::new(), value)),
+                    }
+                }
+                Self::Struct(members)
+            }
+        }
+    }
+
+    /// Returns the bit width.
+    pub fn width(&self) -> usize {
+        match self {
+            Value::Bits(bits) | Value::Enum { bits, .. } => bits.len(),
+            Value::Struct(inner) => inner.iter().map(|(_, member)| member.width()).sum(),
+            Value::Array(elts) => elts.iter().map(|elt| elt.width()).sum(),
+        }
+    }
+
+    /// Returns the LIR value type of the value.
+    ///
+    /// Names of members are those of the value, which may differ from the names of the ports.
+    pub fn port_decls(&self) -> lir::PortDecls {
+        match self {
+            Value::Bits(bits) | Value::Enum { bits, .. } => lir::PortDecls::Bits(lir::Shape::new([bits.len()])),
+            Value::Struct(inner) => lir::PortDecls::Struct(
+                inner.iter().map(|(name, member)| (Some(name.clone()), member.port_decls())).collect(),
+            ),
+            Value::Array(elts) => match elts.first() {
+                Some(elt) => elt.port_decls().multiple(elts.len()),
+                None => lir::PortDecls::Bits(lir::Shape::new([0])),
+            },
+        }
+    }
+
+    /// Encodes the value into bits, in the same layout as `lir::Expr::Constant`.
+    pub fn encode(&self) -> Vec<bool> {
+        match self {
+            Value::Bits(bits) | Value::Enum { bits, .. } => bits.clone(),
+            Value::Struct(inner) => inner.iter().flat_map(|(_, member)| member.encode()).collect(),
+            Value::Array(elts) => match elts.first() {
+                Some(elt) => join_elts(&elts.iter().map(Value::encode).collect::<Vec<_>>(), &elt.port_decls()),
+                None => vec![],
+            },
+        }
+    }
+
+    /// Interprets the bits or the encoding of the enum variant as an unsigned integer.
+    ///
+    /// Returns `None` if the value is not bits, or it does not fit in `usize`.
+    pub fn to_usize(&self) -> Option<usize> {
+        match self {
+            Value::Bits(bits) | Value::Enum { bits, .. } => to_usize(bits),
+            _ => None,
+        }
+    }
+
+    /// Returns the member of the struct with the given name.
+    pub fn member(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(inner) => inner.iter().find(|(member_name, _)| member_name == name).map(|(_, member)| member),
+            _ => None,
+        }
+    }
+
+    /// Returns the element of the array at the given index.
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        match self {
+            Value::Array(elts) => elts.get(index),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bits(bits) => {
+                write!(f, "{}'h", bits.len())?;
+                if bits.is_empty() {
+                    return write!(f, "0");
+                }
+                for nibble in bits.chunks(4).rev() {
+                    let digit = nibble.iter().rev().fold(0, |acc, b| (acc << 1) | u32::from(*b));
+                    write!(f, "{}", char::from_digit(digit, 16).unwrap())?;
+                }
+                Ok(())
+            }
+            Value::Enum { variant, .. } => write!(f, "{}", variant),
+            Value::Struct(inner) => {
+                write!(f, "{{ ")?;
+                for (i, (name, member)) in inner.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, member)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Array(elts) => {
+                write!(f, "[")?;
+                for (i, elt) in elts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", elt)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
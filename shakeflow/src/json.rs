@@ -0,0 +1,159 @@
+//! Exports packages to JSON.
+//!
+//! # Note
+//!
+//! Exprs are shared in the LIR, so the exprs of FSMs are not inlined. They refer to the exprs in
+//! the table of the package by their ids, and each expr in the table refers to its operands by
+//! their ids in the same way.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::panic::Location;
+use std::path::Path;
+
+use serde::{Serialize, Serializer};
+
+use crate::*;
+
+/// Package with the table of exprs reachable from its modules.
+#[derive(Debug, Serialize)]
+pub struct Design<'a> {
+    /// Modules.
+    pub modules: &'a [lir::Module],
+
+    /// Parameterized modules.
+    pub param_modules: &'a [lir::ParamModule],
+
+    /// Reset style of state registers.
+    pub reset_style: lir::ResetStyle,
+
+    /// Exprs indexed by their ids.
+    pub exprs: BTreeMap<lir::ExprId, lir::Expr>,
+}
+
+impl<'a> Design<'a> {
+    /// Creates a new design of the package.
+    pub fn new(package: &'a Package) -> Self {
+        let mut exprs = BTreeMap::new();
+        for module in package.modules.iter().chain(
+            package.param_modules.iter().flat_map(|module| module.specializations.iter().map(|(_, module)| module)),
+        ) {
+            collect_module_exprs(module, &mut exprs);
+        }
+
+        Self {
+            modules: &package.modules,
+            param_modules: &package.param_modules,
+            reset_style: package.reset_style,
+            exprs,
+        }
+    }
+}
+
+impl Package {
+    /// Returns the package in JSON.
+    pub fn to_json(&self) -> Result<String, PackageError> {
+        self.check_construction()?;
+        serde_json::to_string_pretty(&Design::new(self)).map_err(|error| PackageError::Json { error })
+    }
+
+    /// Generates JSON of the package at the given directory path, in a file named `package.json`.
+    pub fn gen_json<P: AsRef<Path>>(&self, path_dir: P) -> Result<(), PackageError> {
+        fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
+
+        let path = path_dir.as_ref().join("package.json");
+        let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+
+        writeln!(file, "{}", self.to_json()?).map_err(|error| PackageError::Fs { error })
+    }
+}
+
+/// Collects the exprs of the FSMs in the module and its submodules.
+fn collect_module_exprs(module: &lir::Module, exprs: &mut BTreeMap<lir::ExprId, lir::Expr>) {
+    match &*module.inner {
+        lir::ModuleInner::Composite(_, module) => {
+            for submodule in
+                module.submodules.iter().map(|(submodule, _)| submodule).chain(module.registered_modules.iter())
+            {
+                collect_module_exprs(submodule, exprs);
+            }
+        }
+        lir::ModuleInner::Fsm(module) => {
+            for expr in [module.output_fwd, module.input_bwd, module.state, module.init]
+                .into_iter()
+                .chain(module.assertions.iter().map(|assertion| assertion.cond))
+            {
+                collect_exprs(expr, exprs);
+            }
+        }
+        lir::ModuleInner::ModuleInst(module) => {
+            if let Some(module) = &module.module {
+                collect_module_exprs(module, exprs);
+            }
+        }
+        lir::ModuleInner::VirtualModule(_) => {}
+    }
+}
+
+/// Collects the expr and its operands, transitively.
+fn collect_exprs(expr: lir::ExprId, exprs: &mut BTreeMap<lir::ExprId, lir::Expr>) {
+    let mut worklist = vec![expr];
+
+    while let Some(expr) = worklist.pop() {
+        if exprs.contains_key(&expr) {
+            continue;
+        }
+
+        let inner = (*expr.into_expr()).clone();
+        let _ = inner.map_operands(|operand| {
+            worklist.push(operand);
+            operand
+        });
+        let _ = exprs.insert(expr, inner);
+    }
+}
+
+/// Serializes the source location in the form of `file:line`.
+pub(crate) fn serialize_source<S: Serializer>(
+    source: &Option<&'static Location<'static>>, serializer: S,
+) -> Result<S::Ok, S::Error> {
+    source.map(|source| format!("{}:{}", source.file(), source.line())).serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    /// Returns an FSM whose output and next state share the same sum.
+    fn accumulate() -> Module<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>> {
+        hir::Fsm::<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>, Bits<U<4>>, _>::new(
+            "accumulate",
+            |fwd, bwd, state| {
+                let sum = (state + fwd).resize();
+                (sum, bwd, sum)
+            },
+            0.into(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn shared_exprs_appear_once() {
+        let package = package(accumulate());
+        let design = json::Design::new(&package);
+
+        let adds = design
+            .exprs
+            .values()
+            .filter(|expr| matches!(expr, lir::Expr::BinaryOp { op: lir::BinaryOp::Add, .. }))
+            .count();
+        assert_eq!(adds, 1);
+
+        let json = package.to_json().unwrap();
+        assert_eq!(json.matches("\"Add\"").count(), 1);
+    }
+}
@@ -41,21 +41,43 @@
 //
 #![feature(generic_const_exprs)]
 
+// Derived impls and `channel!` refer to the crate by name.
+#[cfg(test)]
+extern crate self as shakeflow;
+
 #[macro_use]
 pub mod hir;
+pub mod btorgen;
+pub mod circt;
+pub mod circtgen;
 pub mod codegen;
+pub mod dot;
+pub mod dotgen;
 pub mod fir;
 pub mod firgen;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod lir;
+pub mod rtlil;
+pub mod rtlilgen;
+pub mod sim;
+pub mod sv;
+pub mod svgen;
+#[cfg(test)]
+mod testing;
 pub mod utils;
 pub mod vir;
 pub mod virgen;
 
+pub use circtgen::Circtgen;
 pub use firgen::Firgen;
 pub use hir::*;
 #[doc(hidden)]
 pub use linked_hash_map;
 pub use lir::PrimitiveModule;
+pub use rtlilgen::Rtlilgen;
 pub use shakeflow_macro::{Interface, Signal};
+pub use sim::Simulator;
+pub use svgen::Svgen;
 pub use utils::*;
 pub use virgen::Virgen;
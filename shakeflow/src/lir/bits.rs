@@ -0,0 +1,194 @@
+//! Bit vector operations.
+//!
+//! Values are represented as `Vec<bool>` in the same layout as `lir::Expr::Constant`: leaves of the
+//! `PortDecls` are concatenated in order, and each leaf is stored LSB first. An array of `N` elements
+//! stores the `i`-th element of each leaf at `i * width_of_leaf_element`.
+//!
+//! Functions that only rearrange bits are generic over the type of bits, with `T::default()` as zero.
+
+use std::cmp::Ordering;
+
+use crate::lir::*;
+
+/// Returns widths of the leaves of the given type, including zero-width leaves.
+pub(crate) fn leaf_widths(typ: &PortDecls) -> Vec<usize> {
+    match typ {
+        PortDecls::Struct(inner) => inner.iter().flat_map(|(_, member)| leaf_widths(member)).collect(),
+        PortDecls::Bits(shape) => vec![shape.width()],
+    }
+}
+
+/// Splits the bits into leaves with the given widths.
+pub(crate) fn split_leaves<'a, T>(bits: &'a [T], widths: &[usize]) -> Vec<&'a [T]> {
+    assert_eq!(bits.len(), widths.iter().sum::<usize>());
+
+    let mut offset = 0;
+    widths
+        .iter()
+        .map(|width| {
+            let leaf = &bits[offset..(offset + width)];
+            offset += width;
+            leaf
+        })
+        .collect()
+}
+
+/// Returns the number of elements in the array of `typ_elt` represented by `bits`.
+pub(crate) fn array_len<T>(bits: &[T], typ_elt: &PortDecls) -> usize {
+    let width_elt = typ_elt.width();
+    if width_elt == 0 {
+        0
+    } else {
+        assert_eq!(bits.len() % width_elt, 0);
+        bits.len() / width_elt
+    }
+}
+
+/// Returns `count` elements starting from `from` of the array of `typ_elt` represented by `bits`.
+///
+/// Elements out of range are filled with zeros.
+pub(crate) fn get_elts<T: Clone + Default>(bits: &[T], typ_elt: &PortDecls, from: usize, count: usize) -> Vec<T> {
+    let len = array_len(bits, typ_elt);
+    let widths = leaf_widths(typ_elt);
+    let leaves = split_leaves(bits, &widths.iter().map(|width| width * len).collect::<Vec<_>>());
+
+    widths
+        .iter()
+        .zip(leaves)
+        .flat_map(|(width, leaf)| {
+            (from..(from + count)).flat_map(move |index| {
+                if index < len {
+                    leaf[(index * width)..((index + 1) * width)].to_vec()
+                } else {
+                    vec![T::default(); *width]
+                }
+            })
+        })
+        .collect()
+}
+
+/// Overwrites elements starting from `from` of the array of `typ_elt` represented by `bits` with `elts`.
+///
+/// Elements out of range are ignored.
+pub(crate) fn set_elts<T: Clone>(bits: &[T], typ_elt: &PortDecls, from: usize, elts: &[T]) -> Vec<T> {
+    let len = array_len(bits, typ_elt);
+    let count = array_len(elts, typ_elt);
+    let widths = leaf_widths(typ_elt);
+    let leaves = split_leaves(bits, &widths.iter().map(|width| width * len).collect::<Vec<_>>());
+    let elts_leaves = split_leaves(elts, &widths.iter().map(|width| width * count).collect::<Vec<_>>());
+
+    itertools::izip!(widths, leaves, elts_leaves)
+        .flat_map(|(width, leaf, elts_leaf)| {
+            let mut leaf = leaf.to_vec();
+            for index in 0..count {
+                if from + index < len {
+                    leaf[((from + index) * width)..((from + index + 1) * width)]
+                        .clone_from_slice(&elts_leaf[(index * width)..((index + 1) * width)]);
+                }
+            }
+            leaf
+        })
+        .collect()
+}
+
+/// Builds an array of `typ_elt` from its elements.
+pub(crate) fn join_elts<T: Clone>(elts: &[Vec<T>], typ_elt: &PortDecls) -> Vec<T> {
+    let widths = leaf_widths(typ_elt);
+    let elts_leaves = elts.iter().map(|elt| split_leaves(elt, &widths)).collect::<Vec<_>>();
+
+    (0..widths.len()).flat_map(|leaf| elts_leaves.iter().flat_map(move |elt| elt[leaf].iter().cloned())).collect()
+}
+
+/// Concatenates leaves of `lhs` and `rhs` pairwise, `lhs` in the lower bits.
+pub(crate) fn append_leaves<T: Clone>(lhs: &[T], typ_lhs: &PortDecls, rhs: &[T], typ_rhs: &PortDecls) -> Vec<T> {
+    let lhs_leaves = split_leaves(lhs, &leaf_widths(typ_lhs));
+    let rhs_leaves = split_leaves(rhs, &leaf_widths(typ_rhs));
+    assert_eq!(lhs_leaves.len(), rhs_leaves.len());
+
+    lhs_leaves.into_iter().zip(rhs_leaves).flat_map(|(lhs, rhs)| lhs.iter().chain(rhs.iter()).cloned()).collect()
+}
+
+/// Zero-extends or truncates the bits to the given width.
+pub(crate) fn resize<T: Clone + Default>(bits: &[T], width: usize) -> Vec<T> {
+    (0..width).map(|i| bits.get(i).cloned().unwrap_or_default()).collect()
+}
+
+/// Interprets the bits as an unsigned integer. Returns `None` if it does not fit in `usize`.
+pub(crate) fn to_usize(bits: &[bool]) -> Option<usize> {
+    let size_of_usize = ::std::mem::size_of::<usize>() * 8;
+    if bits.iter().skip(size_of_usize).any(|b| *b) {
+        return None;
+    }
+    Some(bits.iter().take(size_of_usize).enumerate().fold(0, |acc, (i, b)| if *b { acc | (1 << i) } else { acc }))
+}
+
+/// Compares two bits as unsigned integers.
+pub(crate) fn cmp(lhs: &[bool], rhs: &[bool]) -> Ordering {
+    let width = lhs.len().max(rhs.len());
+    (0..width)
+        .rev()
+        .map(|i| lhs.get(i).copied().unwrap_or(false).cmp(&rhs.get(i).copied().unwrap_or(false)))
+        .find(|ord| ord.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Adds two bits, wrapping around at the given width.
+pub(crate) fn add(lhs: &[bool], rhs: &[bool], width: usize) -> Vec<bool> {
+    let mut carry = false;
+    (0..width)
+        .map(|i| {
+            let (l, r) = (lhs.get(i).copied().unwrap_or(false), rhs.get(i).copied().unwrap_or(false));
+            let sum = l ^ r ^ carry;
+            carry = (l & r) | (l & carry) | (r & carry);
+            sum
+        })
+        .collect()
+}
+
+/// Subtracts `rhs` from `lhs`, wrapping around at the given width.
+pub(crate) fn sub(lhs: &[bool], rhs: &[bool], width: usize) -> Vec<bool> {
+    let rhs_neg = add(&resize(rhs, width).into_iter().map(|b| !b).collect::<Vec<_>>(), &[true], width);
+    add(lhs, &rhs_neg, width)
+}
+
+/// Multiplies two bits, wrapping around at the given width.
+pub(crate) fn mul(lhs: &[bool], rhs: &[bool], width: usize) -> Vec<bool> {
+    let mut acc = vec![false; width];
+    for (i, b) in rhs.iter().enumerate().take(width) {
+        if *b {
+            let shifted = shift_left(&resize(lhs, width), i);
+            acc = add(&acc, &shifted, width);
+        }
+    }
+    acc
+}
+
+/// Divides `lhs` by `rhs`, returning the quotient and the remainder. Returns `None` if `rhs` is zero.
+pub(crate) fn div_rem(lhs: &[bool], rhs: &[bool]) -> Option<(Vec<bool>, Vec<bool>)> {
+    if rhs.iter().all(|b| !*b) {
+        return None;
+    }
+
+    let width = lhs.len().max(rhs.len());
+    let mut quot = vec![false; width];
+    let mut rem = vec![false; width];
+    for i in (0..lhs.len()).rev() {
+        rem = shift_left(&rem, 1);
+        rem[0] = lhs[i];
+        if cmp(&rem, rhs).is_ge() {
+            rem = sub(&rem, rhs, width);
+            quot[i] = true;
+        }
+    }
+    Some((quot, rem))
+}
+
+/// Shifts the bits left, keeping the width.
+pub(crate) fn shift_left(bits: &[bool], amount: usize) -> Vec<bool> {
+    (0..bits.len()).map(|i| if i >= amount { bits[i - amount] } else { false }).collect()
+}
+
+/// Shifts the bits right, keeping the width.
+pub(crate) fn shift_right(bits: &[bool], amount: usize) -> Vec<bool> {
+    (0..bits.len()).map(|i| bits.get(i.saturating_add(amount)).copied().unwrap_or(false)).collect()
+}
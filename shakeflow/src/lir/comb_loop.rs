@@ -0,0 +1,716 @@
+//! Detection of combinational loops.
+//!
+//! Each module is summarized by its combinational paths, from the forward ports of its input
+//! channels and the backward ports of its output channels to the forward ports of its output
+//! channels and the backward ports of its input channels. Summaries of FSMs are computed from the
+//! bit-level dependencies of their `output_fwd` and `input_bwd` exprs, and summaries of composite
+//! modules from the graphs formed by the summaries of their submodules. A cycle in such a graph is a
+//! combinational loop between Verilog wires.
+//!
+//! Input and output channels of a composite module connected by wires only are not paths but the
+//! same channel. Hence a loop of such connections, e.g. a feedback channel passed through unchanged,
+//! is an undriven channel rather than a combinational loop.
+//!
+//! External modules without a ShakeFlow implementation are assumed to have no combinational paths.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+use linked_hash_map::LinkedHashMap;
+
+use crate::lir::bits::*;
+use crate::lir::*;
+use crate::sim::netlist::{join_interface, split_interface, Dir, Ports};
+use crate::some_or;
+
+/// Ports a bit depends on, as indices of the ports.
+type Deps = BTreeSet<usize>;
+
+/// Port of a channel, identified by the channel's endpoint path in the interface, the direction, and
+/// the index of the port among the leaves of the value type in the direction.
+type Signal = (EndpointPath, Dir, usize);
+
+/// Summary of a module.
+#[derive(Debug, Default)]
+struct Summary {
+    /// Combinational paths, from each forward port of output channels and backward port of input
+    /// channels to the forward ports of input channels and backward ports of output channels it
+    /// depends on.
+    paths: LinkedHashMap<Signal, HashSet<Signal>>,
+
+    /// Pairs of the endpoint paths of input and output channels connected by wires only.
+    aliases: Vec<(EndpointPath, EndpointPath)>,
+}
+
+/// Port of a channel in a composite module, identified by the channel's index, the direction, and the
+/// index of the port.
+type Node = (usize, Dir, usize);
+
+/// Ports each port in a composite module is connected to, and the names of submodules connecting them.
+type Adjacency<'a> = HashMap<Node, Vec<(Node, &'a str)>>;
+
+impl Module {
+    /// Checks that the module has no combinational loops.
+    pub fn check_comb_loops(&self) -> Result<(), ModuleError> {
+        Checker::default().summary(self, self.get_module_name()).map(|_| ())
+    }
+}
+
+/// Combinational loop checker, caching the summaries of modules.
+#[derive(Debug, Default)]
+struct Checker {
+    summaries: HashMap<*const ModuleInner, Rc<Summary>>,
+}
+
+impl Checker {
+    /// Returns the summary of the module instantiated with the given name.
+    fn summary(&mut self, module: &Module, name: String) -> Result<Rc<Summary>, ModuleError> {
+        let key = Rc::as_ptr(&module.inner);
+        if let Some(summary) = self.summaries.get(&key) {
+            return Ok(summary.clone());
+        }
+
+        let summary = match &*module.inner {
+            ModuleInner::Composite(_, module) => match module.module_typ {
+                CompositeModuleTyp::OneToOne => self.summary_composite(module, name)?,
+                CompositeModuleTyp::NToN(n) => {
+                    let summary = self.summary_composite(module, name)?;
+                    let paths = (0..n)
+                        .flat_map(|i| {
+                            summary.paths.iter().map(move |(sink, sources)| {
+                                (with_index(sink, i), sources.iter().map(|source| with_index(source, i)).collect())
+                            })
+                        })
+                        .collect();
+                    let aliases = (0..n)
+                        .flat_map(|i| {
+                            summary
+                                .aliases
+                                .iter()
+                                .map(move |(input, output)| (with_index_path(input, i), with_index_path(output, i)))
+                        })
+                        .collect();
+                    Summary { paths, aliases }
+                }
+            },
+            ModuleInner::Fsm(module) => Summary { paths: summary_fsm(module)?, aliases: Vec::new() },
+            ModuleInner::ModuleInst(module_inst) => match &module_inst.module {
+                Some(inner) => return self.summary(inner, name),
+                None => Summary::default(),
+            },
+            ModuleInner::VirtualModule(_) => Summary::default(),
+        };
+
+        let summary = Rc::new(summary);
+        self.summaries.insert(key, summary.clone());
+        Ok(summary)
+    }
+
+    fn summary_composite(&mut self, module: &CompositeModule, name: String) -> Result<Summary, ModuleError> {
+        let mut graph = Graph::default();
+
+        // Adds registered modules.
+        let mut registered_ports = Vec::new();
+        for (index, registered_module) in module.registered_modules.iter().enumerate() {
+            let registered_inputs = graph.alloc_ports(&registered_module.inner.input_interface_typ());
+            let registered_outputs = graph.alloc_ports(&registered_module.inner.output_interface_typ());
+            let inst_name = format!("{}_registered_{}_{}", name, registered_module.get_module_name(), index);
+            let summary = self.summary(registered_module, inst_name.clone())?;
+            graph.add_summary(&summary, registered_module, &registered_inputs, &registered_outputs, inst_name)?;
+            registered_ports.push((registered_inputs, registered_outputs));
+        }
+
+        let inputs = graph.alloc_ports(&module.input_interface.typ());
+        let submodule_outputs = module
+            .submodules
+            .iter()
+            .map(|(submodule, _)| graph.alloc_ports(&submodule.inner.output_interface_typ()))
+            .collect::<Vec<_>>();
+
+        let endpoint_path = |path: &EndpointPath, typ: &InterfaceTyp| ModuleError::EndpointPath {
+            module: name.clone(),
+            path: path.to_string(),
+            typ: typ.describe(),
+        };
+        let resolve = |endpoint: &Endpoint| -> Result<usize, ModuleError> {
+            match endpoint {
+                Endpoint::Input { path } => {
+                    inputs.get(path).copied().ok_or_else(|| endpoint_path(path, &module.input_interface_typ()))
+                }
+                Endpoint::Submodule { submodule_index, path } => {
+                    { submodule_outputs.get(*submodule_index).and_then(|outputs| outputs.get(path)).copied() }
+                        .ok_or_else(|| ModuleError::Endpoint {
+                            module: name.clone(),
+                            path: path.to_string(),
+                            endpoint: format!("{:?}", endpoint),
+                        })
+                }
+                Endpoint::Temp { path } => Err(ModuleError::Endpoint {
+                    module: name.clone(),
+                    path: path.to_string(),
+                    endpoint: format!("{:?}", endpoint),
+                }),
+            }
+        };
+
+        // Adds submodules.
+        for (index, ((submodule, interface), submodule_outputs)) in
+            module.submodules.iter().zip(submodule_outputs.iter()).enumerate()
+        {
+            let submodule_inputs = interface
+                .into_primitives()
+                .into_iter()
+                .filter_map(|(interface, path)| interface.get_channel().map(|channel| (path, channel.endpoint)))
+                .map(|(path, endpoint)| Ok((path, resolve(&endpoint)?)))
+                .collect::<Result<Ports, ModuleError>>()?;
+
+            match &*submodule.inner {
+                ModuleInner::VirtualModule(virtual_module) => {
+                    let (registered_inputs, registered_outputs) = some_or!(
+                        registered_ports.get(virtual_module.registered_index),
+                        return Err(ModuleError::RegisteredModule {
+                            module: name,
+                            submodule: virtual_module.get_module_name(),
+                        })
+                    );
+                    let registered_module = &module.registered_modules[virtual_module.registered_index];
+                    for (path, id) in submodule_inputs {
+                        let path = virtual_module.input_endpoint().inner.into_iter().chain(path.inner).collect();
+                        let registered_input = registered_inputs
+                            .get(&path)
+                            .ok_or_else(|| endpoint_path(&path, &registered_module.inner.input_interface_typ()))?;
+                        graph.merge(*registered_input, id);
+                    }
+                    for (path, id) in submodule_outputs {
+                        let path =
+                            virtual_module.output_endpoint().inner.into_iter().chain(path.inner.clone()).collect();
+                        let registered_output = registered_outputs
+                            .get(&path)
+                            .ok_or_else(|| endpoint_path(&path, &registered_module.inner.output_interface_typ()))?;
+                        graph.merge(*registered_output, *id);
+                    }
+                }
+                _ => {
+                    let inst_name = format!("{}_{}_{}", name, submodule.get_module_name(), index);
+                    let summary = self.summary(submodule, inst_name.clone())?;
+                    graph.add_summary(&summary, submodule, &submodule_inputs, submodule_outputs, inst_name)?;
+                }
+            }
+        }
+
+        let outputs = module
+            .output_interface
+            .into_primitives()
+            .into_iter()
+            .filter_map(|(interface, path)| interface.get_channel().map(|channel| (path, channel.endpoint)))
+            .map(|(path, endpoint)| Ok((path, resolve(&endpoint)?)))
+            .collect::<Result<Ports, ModuleError>>()?;
+
+        if let Some(chain) = graph.find_cycle() {
+            return Err(ModuleError::CombinationalLoop { module: name, chain });
+        }
+
+        // Computes the combinational paths from the ports of the module.
+        let graph = &graph;
+        let ports = |ports: &Ports, dir: Dir| {
+            ports
+                .iter()
+                .flat_map(|(path, id)| {
+                    (0..graph.leaves(*id, dir))
+                        .map(move |leaf| ((path.clone(), dir, leaf), (graph.find(*id), dir, leaf)))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut sources = HashMap::<Node, Vec<Signal>>::new();
+        for (source, node) in ports(&inputs, Dir::Fwd).into_iter().chain(ports(&outputs, Dir::Bwd)) {
+            sources.entry(node).or_default().push(source);
+        }
+
+        // Ports of the same channel are connected by wires, which are recorded as aliases instead.
+        let reversed = graph.adjacency(true);
+        let paths = ports(&outputs, Dir::Fwd)
+            .into_iter()
+            .chain(ports(&inputs, Dir::Bwd))
+            .map(|(sink, node)| {
+                let sources = reachable(&reversed, node)
+                    .iter()
+                    .filter(|source| **source != node)
+                    .filter_map(|node| sources.get(node))
+                    .flatten()
+                    .cloned()
+                    .collect();
+                (sink, sources)
+            })
+            .collect();
+
+        let aliases = inputs
+            .iter()
+            .flat_map(|(input, input_id)| {
+                outputs
+                    .iter()
+                    .filter(|(_, output_id)| graph.find(*input_id) == graph.find(**output_id))
+                    .map(|(output, _)| (input.clone(), output.clone()))
+            })
+            .collect();
+
+        Ok(Summary { paths, aliases })
+    }
+}
+
+/// Combinational dependency graph of the ports of channels in a composite module.
+#[derive(Debug, Default)]
+struct Graph {
+    /// Types of channels.
+    typs: Vec<ChannelTyp>,
+
+    /// Union-find parents of channels.
+    parents: Vec<usize>,
+
+    /// Ports, and the submodules through which they depend on other ports.
+    edges: Vec<(Node, Node, String)>,
+}
+
+impl Graph {
+    /// Allocates channels of the given interface type.
+    fn alloc_ports(&mut self, typ: &InterfaceTyp) -> Ports {
+        typ.into_primitives()
+            .into_iter()
+            .filter_map(|(typ, path)| {
+                typ.get_channel_typ().map(|channel_typ| {
+                    let id = self.parents.len();
+                    self.typs.push(channel_typ);
+                    self.parents.push(id);
+                    (path, id)
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the number of ports of the channel in the direction.
+    fn leaves(&self, id: usize, dir: Dir) -> usize { leaf_widths(decls(&self.typs[id], dir)).len() }
+
+    fn find(&self, mut id: usize) -> usize {
+        while self.parents[id] != id {
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn merge(&mut self, lhs: usize, rhs: usize) {
+        let (lhs, rhs) = (self.find(lhs), self.find(rhs));
+        self.parents[rhs] = lhs;
+    }
+
+    /// Adds the combinational paths of a submodule connected to the given channels.
+    fn add_summary(
+        &mut self, summary: &Summary, module: &Module, inputs: &Ports, outputs: &Ports, name: String,
+    ) -> Result<(), ModuleError> {
+        let input = |path: &EndpointPath| {
+            inputs.get(path).copied().ok_or_else(|| ModuleError::EndpointPath {
+                module: name.clone(),
+                path: path.to_string(),
+                typ: module.inner.input_interface_typ().describe(),
+            })
+        };
+        let output = |path: &EndpointPath| {
+            outputs.get(path).copied().ok_or_else(|| ModuleError::EndpointPath {
+                module: name.clone(),
+                path: path.to_string(),
+                typ: module.inner.output_interface_typ().describe(),
+            })
+        };
+        let channel = |(path, dir, leaf): &Signal, is_sink: bool| {
+            // Forward ports of output channels and backward ports of input channels are sinks.
+            let id = if (*dir == Dir::Fwd) == is_sink { output(path)? } else { input(path)? };
+            Ok((id, *dir, *leaf))
+        };
+
+        for (sink, sources) in summary.paths.iter() {
+            let sink = channel(sink, true)?;
+            for source in sources {
+                self.edges.push((channel(source, false)?, sink, name.clone()));
+            }
+        }
+
+        for (input_path, output_path) in summary.aliases.iter() {
+            self.merge(input(input_path)?, output(output_path)?);
+        }
+
+        Ok(())
+    }
+
+    /// Returns adjacency lists of ports, with the names of submodules on the edges.
+    fn adjacency(&self, reversed: bool) -> Adjacency<'_> {
+        let mut adjacency = HashMap::<_, Vec<_>>::new();
+        for ((from, from_dir, from_leaf), (to, to_dir, to_leaf), name) in self.edges.iter() {
+            let (from, to) = ((self.find(*from), *from_dir, *from_leaf), (self.find(*to), *to_dir, *to_leaf));
+            let (from, to) = if reversed { (to, from) } else { (from, to) };
+            adjacency.entry(from).or_default().push((to, name.as_str()));
+        }
+        adjacency
+    }
+
+    /// Returns the names of submodules on a cycle, if any.
+    fn find_cycle(&self) -> Option<Vec<String>> {
+        let adjacency = self.adjacency(false);
+        let mut visited = HashSet::new();
+        let mut nodes = adjacency.keys().copied().collect::<Vec<_>>();
+        nodes.sort_by_key(|(id, dir, leaf)| (*id, *dir == Dir::Bwd, *leaf));
+
+        for node in nodes {
+            if !visited.insert(node) {
+                continue;
+            }
+
+            // Depth-first search, with the ports on the current path, the indices of their next
+            // edges, and the names of submodules on the edges between them.
+            let mut path = vec![(node, 0)];
+            let mut names = Vec::new();
+            while let Some((current, index)) = path.last_mut() {
+                match adjacency.get(&*current).and_then(|edges| edges.get(*index)) {
+                    Some(&(next, name)) => {
+                        *index += 1;
+                        if let Some(position) = path.iter().position(|(node, _)| *node == next) {
+                            let chain = names[position..].iter().chain([&name]).map(|name| name.to_string()).collect();
+                            return Some(chain);
+                        }
+                        if visited.insert(next) {
+                            path.push((next, 0));
+                            names.push(name);
+                        }
+                    }
+                    None => {
+                        path.pop();
+                        names.pop();
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns the ports reachable from the given port in the graph, including itself.
+fn reachable(adjacency: &Adjacency<'_>, node: Node) -> HashSet<Node> {
+    let mut reachable = HashSet::new();
+    let mut worklist = vec![node];
+    while let Some(node) = worklist.pop() {
+        if reachable.insert(node) {
+            worklist.extend(adjacency.get(&node).into_iter().flatten().map(|(next, _)| *next));
+        }
+    }
+    reachable
+}
+
+/// Returns the port of the `index`-th element of an array interface.
+fn with_index((path, dir, leaf): &Signal, index: usize) -> Signal { (with_index_path(path, index), *dir, *leaf) }
+
+/// Returns the endpoint path of the `index`-th element of an array interface.
+fn with_index_path(path: &EndpointPath, index: usize) -> EndpointPath {
+    let mut path = path.clone();
+    path.inner.push_front(EndpointNode::Index(index));
+    path
+}
+
+/// Returns the value type of the channel in the direction.
+fn decls(typ: &ChannelTyp, dir: Dir) -> &PortDecls {
+    match dir {
+        Dir::Fwd => &typ.fwd,
+        Dir::Bwd => &typ.bwd,
+    }
+}
+
+/// Returns the combinational paths of the FSM.
+fn summary_fsm(module: &Fsm) -> Result<LinkedHashMap<Signal, HashSet<Signal>>, ModuleError> {
+    let channels = |typ: &InterfaceTyp| {
+        typ.into_primitives()
+            .into_iter()
+            .filter_map(|(typ, path)| typ.get_channel_typ().map(|typ| (typ, path)))
+            .collect::<Vec<_>>()
+    };
+    let inputs = channels(&module.input_interface_typ);
+    let outputs = channels(&module.output_interface_typ);
+
+    // Forward ports of input channels and backward ports of output channels, numbered in order.
+    let mut sources = Vec::new();
+    let mut values = |channels: &[(ChannelTyp, EndpointPath)], dir: Dir| {
+        let mut values = Vec::new();
+        for (typ, path) in channels {
+            let mut value = Vec::new();
+            for (leaf, width) in leaf_widths(decls(typ, dir)).into_iter().enumerate() {
+                value.extend(vec![Deps::from([sources.len()]); width]);
+                sources.push((path.clone(), dir, leaf));
+            }
+            values.push(value);
+        }
+        values
+    };
+    let input_fwd = values(&inputs, Dir::Fwd);
+    let output_bwd = values(&outputs, Dir::Bwd);
+    let interface_error = |error| ModuleError::Interface { module: module.module_name.clone(), error };
+    let input_fwd =
+        join_interface(&module.input_interface_typ, Dir::Fwd, &mut input_fwd.iter().map(|value| &value[..]))
+            .map_err(interface_error)?;
+    let output_bwd =
+        join_interface(&module.output_interface_typ, Dir::Bwd, &mut output_bwd.iter().map(|value| &value[..]))
+            .map_err(interface_error)?;
+
+    let env = [(Some("in"), &input_fwd[..]), (Some("out"), &output_bwd[..])];
+    let mut deps = DepsEvaluator::new(&env);
+    let output_fwd = split_interface(&module.output_interface_typ, Dir::Fwd, &deps.eval(module.output_fwd));
+    let input_bwd = split_interface(&module.input_interface_typ, Dir::Bwd, &deps.eval(module.input_bwd));
+
+    Ok(::std::iter::empty()
+        .chain(outputs.iter().zip(output_fwd).map(|(channel, value)| (channel, Dir::Fwd, value)))
+        .chain(inputs.iter().zip(input_bwd).map(|(channel, value)| (channel, Dir::Bwd, value)))
+        .flat_map(|((typ, path), dir, value)| {
+            split_leaves(&value, &leaf_widths(decls(typ, dir)))
+                .into_iter()
+                .enumerate()
+                .map(|(leaf, value)| {
+                    ((path.clone(), dir, leaf), union(value).into_iter().map(|id| sources[id].clone()).collect())
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// Computes the dependencies of bits of exprs on the bits of inputs.
+///
+/// The analysis is conservative: a bit may be reported to depend on an input bit it does not
+/// actually depend on, but not vice versa. Inputs without bindings, such as states, have no
+/// dependencies.
+#[derive(Debug)]
+struct DepsEvaluator<'a> {
+    /// Dependencies of bits of `Expr::Input`s, by name.
+    env: &'a [(Option<&'a str>, &'a [Deps])],
+
+    /// Dependencies of already evaluated exprs.
+    cache: HashMap<ExprId, Vec<Deps>>,
+}
+
+impl<'a> DepsEvaluator<'a> {
+    fn new(env: &'a [(Option<&'a str>, &'a [Deps])]) -> Self { Self { env, cache: HashMap::new() } }
+
+    fn eval(&mut self, id: ExprId) -> Vec<Deps> {
+        if let Some(value) = self.cache.get(&id) {
+            return value.clone();
+        }
+
+        let value = self.eval_inner(&id.into_expr());
+        self.cache.insert(id, value.clone());
+        value
+    }
+
+    fn eval_inner(&mut self, expr: &Expr) -> Vec<Deps> {
+        match expr {
+            Expr::X { typ } | Expr::Constant { typ, .. } => vec![Deps::new(); typ.width()],
+            Expr::Repeat { inner, count } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.eval(*inner);
+                split_leaves(&value, &leaf_widths(&typ))
+                    .into_iter()
+                    .flat_map(|leaf| ::std::iter::repeat(leaf).take(*count).flatten().cloned())
+                    .collect()
+            }
+            Expr::Input { name, typ } => self
+                .env
+                .iter()
+                .find(|(input, _)| *input == name.as_deref())
+                .map(|(_, value)| value.to_vec())
+                .unwrap_or_else(|| vec![Deps::new(); typ.width()]),
+            Expr::Member { inner, index } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.eval(*inner);
+                match typ {
+                    PortDecls::Struct(members) => {
+                        let offset = members.iter().take(*index).map(|(_, member)| member.width()).sum::<usize>();
+                        value[offset..(offset + members[*index].1.width())].to_vec()
+                    }
+                    PortDecls::Bits(_) => panic!("Cannot index a `PortDecls::Bits`."),
+                }
+            }
+            Expr::Struct { inner } => inner.iter().flat_map(|(_, member)| self.eval(*member)).collect(),
+            Expr::Resize { inner, typ_elt, count } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.eval(*inner);
+                let widths = leaf_widths(&typ_elt.multiple(*count));
+                split_leaves(&value, &leaf_widths(&typ))
+                    .into_iter()
+                    .zip(widths)
+                    .flat_map(|(leaf, width)| resize(leaf, width))
+                    .collect()
+            }
+            Expr::LeftShift { inner, rhs } => self.eval_binary_op(BinaryOp::ShiftLeft, *inner, *rhs),
+            Expr::RightShift { inner, rhs } => self.eval_binary_op(BinaryOp::ShiftRight, *inner, *rhs),
+            Expr::Not { inner } => self.eval(*inner),
+            Expr::BinaryOp { op, lhs, rhs } => self.eval_binary_op(*op, *lhs, *rhs),
+            Expr::Fold { inner, typ_elt, func, init, .. } => {
+                let value = self.eval(*inner);
+                let mut acc = self.eval(*init);
+                for index in 0..array_len(&value, typ_elt) {
+                    let inner_slice = get_elts(&value, typ_elt, index, 1);
+                    acc = DepsEvaluator::new(&[(Some("acc"), &acc), (Some("inner_slice"), &inner_slice)]).eval(*func);
+                }
+                acc
+            }
+            Expr::TreeFold { inner, op, lhs, .. } => {
+                let typ_elt = lhs.into_expr().port_decls();
+                let value = self.eval(*inner);
+                let mut elts = (0..array_len(&value, &typ_elt))
+                    .map(|index| get_elts(&value, &typ_elt, index, 1))
+                    .collect::<Vec<_>>();
+                while elts.len() > 1 {
+                    elts = elts
+                        .chunks(2)
+                        .map(|pair| match pair {
+                            [lhs, rhs] => DepsEvaluator::new(&[(Some("lhs"), lhs), (Some("rhs"), rhs)]).eval(*op),
+                            [elt] => elt.clone(),
+                            _ => unreachable!(),
+                        })
+                        .collect();
+                }
+                elts.pop().unwrap_or_else(|| vec![Deps::new(); typ_elt.width()])
+            }
+            Expr::Map { inner, typ_elt, func } => {
+                let typ_func = func.into_expr().port_decls();
+                let value = self.eval(*inner);
+                let elts = (0..array_len(&value, typ_elt))
+                    .map(|index| DepsEvaluator::new(&[(None, &get_elts(&value, typ_elt, index, 1))]).eval(*func))
+                    .collect::<Vec<_>>();
+                join_elts(&elts, &typ_func)
+            }
+            Expr::Get { inner, typ_elt, index } | Expr::GetVarArray { inner, typ_elt, index } => {
+                let value = self.eval(*inner);
+                let count = array_len(&value, typ_elt);
+                self.select(*index, count, vec![Deps::new(); typ_elt.width()], |index| {
+                    get_elts(&value, typ_elt, index, 1)
+                })
+            }
+            Expr::Clip { inner, typ_elt, from, size } => {
+                let value = self.eval(*inner);
+                let count = array_len(&value, typ_elt);
+                self.select(*from, count, vec![Deps::new(); typ_elt.width() * size], |from| {
+                    get_elts(&value, typ_elt, from, *size)
+                })
+            }
+            Expr::Append { lhs, rhs, .. } => {
+                let (typ_lhs, typ_rhs) = (lhs.into_expr().port_decls(), rhs.into_expr().port_decls());
+                append_leaves(&self.eval(*lhs), &typ_lhs, &self.eval(*rhs), &typ_rhs)
+            }
+            Expr::Zip { inner, .. } => inner.iter().flat_map(|member| self.eval(*member)).collect(),
+            Expr::Concat { inner, .. } | Expr::Chunk { inner, .. } | Expr::Repr { inner } => self.eval(*inner),
+            Expr::Sum { inner, width_elt } => vec![union(&self.eval(*inner)); *width_elt],
+            Expr::Cond { cond, lhs, rhs } => {
+                let cond = union(&self.eval(*cond));
+                with_deps(&merge(&self.eval(*lhs), &self.eval(*rhs)), &cond)
+            }
+            Expr::Set { inner, index, elt } | Expr::SetVarArray { inner, index, elt } => {
+                let typ_elt = elt.into_expr().port_decls();
+                let value = self.eval(*inner);
+                let elt = self.eval(*elt);
+                let count = array_len(&value, &typ_elt);
+                self.select(*index, count, value.clone(), |index| set_elts(&value, &typ_elt, index, &elt))
+            }
+            Expr::SetRange { inner, typ_elt, index, elts } => {
+                let value = self.eval(*inner);
+                let elts = self.eval(*elts);
+                let count = array_len(&value, typ_elt);
+                self.select(*index, count, value.clone(), |index| set_elts(&value, typ_elt, index, &elts))
+            }
+            Expr::Case { case_expr, case_items, default } => {
+                let mut cond = union(&self.eval(*case_expr));
+                let mut output = match default {
+                    Some(default) => self.eval(*default),
+                    None => vec![Deps::new(); expr.width()],
+                };
+                for (case, item) in case_items {
+                    cond.extend(union(&self.eval(*case)));
+                    output = merge(&output, &self.eval(*item));
+                }
+                with_deps(&output, &cond)
+            }
+            Expr::Call { args, typ, .. } => {
+                let deps = args.iter().flat_map(|arg| union(&self.eval(*arg))).collect();
+                vec![deps; typ.width()]
+            }
+            Expr::ConcatArray { inner, elt_typ } => {
+                let elts = inner.iter().map(|elt| self.eval(*elt)).collect::<Vec<_>>();
+                join_elts(&elts, elt_typ)
+            }
+        }
+    }
+
+    fn eval_binary_op(&mut self, op: BinaryOp, lhs: ExprId, rhs: ExprId) -> Vec<Deps> {
+        let amount = constant(rhs);
+        let lhs = self.eval(lhs);
+        let rhs = self.eval(rhs);
+        let deps = union(&lhs).union(&union(&rhs)).copied().collect::<Deps>();
+
+        match op {
+            BinaryOp::Add => vec![deps; lhs.len() + 1],
+            BinaryOp::Sub | BinaryOp::Div => vec![deps; lhs.len()],
+            BinaryOp::Mul => vec![deps; lhs.len() + rhs.len()],
+            BinaryOp::Mod => vec![deps; rhs.len()],
+            BinaryOp::Or | BinaryOp::And | BinaryOp::Xor | BinaryOp::Eq => {
+                lhs.iter().zip(rhs).map(|(l, r)| l.union(&r).copied().collect()).collect()
+            }
+            BinaryOp::EqArithmetic | BinaryOp::Less | BinaryOp::Greater | BinaryOp::LessEq | BinaryOp::GreaterEq => {
+                vec![deps]
+            }
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => match amount {
+                Some(amount) => {
+                    let left = op == BinaryOp::ShiftLeft;
+                    (0..lhs.len())
+                        .map(|i| {
+                            if left {
+                                i.checked_sub(amount).map_or_else(Deps::new, |i| lhs[i].clone())
+                            } else {
+                                lhs.get(i.saturating_add(amount)).cloned().unwrap_or_default()
+                            }
+                        })
+                        .collect()
+                }
+                None => vec![deps; lhs.len()],
+            },
+        }
+    }
+
+    /// Returns `f(index)` if `index` is a constant less than `count`, `default` if it is another
+    /// constant, and the merge of all of them depending on `index` otherwise.
+    fn select<F: FnMut(usize) -> Vec<Deps>>(
+        &mut self, index: ExprId, count: usize, default: Vec<Deps>, mut f: F,
+    ) -> Vec<Deps> {
+        if let Some(index) = constant(index) {
+            return if index < count { f(index) } else { default };
+        }
+
+        let deps = union(&self.eval(index));
+        let value = (0..count).fold(default, |acc, index| merge(&acc, &f(index)));
+        with_deps(&value, &deps)
+    }
+}
+
+/// Returns the value of the expr if it is a constant that fits in `usize`.
+fn constant(id: ExprId) -> Option<usize> {
+    match &*id.into_expr() {
+        Expr::Constant { bits, .. } => to_usize(bits),
+        _ => None,
+    }
+}
+
+/// Returns the dependencies of all bits.
+fn union(value: &[Deps]) -> Deps { value.iter().flatten().copied().collect() }
+
+/// Returns the bitwise union of dependencies, zero-extending the shorter one.
+fn merge(lhs: &[Deps], rhs: &[Deps]) -> Vec<Deps> {
+    let width = lhs.len().max(rhs.len());
+    resize(lhs, width).into_iter().zip(resize(rhs, width)).map(|(l, r)| l.union(&r).copied().collect()).collect()
+}
+
+/// Adds the dependencies to all bits.
+fn with_deps(value: &[Deps], deps: &Deps) -> Vec<Deps> {
+    value.iter().map(|bits| bits.union(deps).copied().collect()).collect()
+}
@@ -1,13 +1,16 @@
 //! LIR Expr.
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 
 use hashcons::merkle::Merkle;
 
 use super::*;
 
 /// Expr Id
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct ExprId(usize);
 
 impl ExprId {
@@ -16,6 +19,23 @@ impl ExprId {
 
     /// Returns expr corresponding to given id
     pub fn into_expr(self) -> Merkle<Expr> { TABLE.with(|table| table.get(self)) }
+
+    /// Returns the structural id of the expr, which is the same for exprs with the same structure
+    /// regardless of the ids of their operands in the table. `ids` memoizes the ids of visited exprs,
+    /// and `structures` interns the exprs whose operands are replaced by their structural ids.
+    pub(crate) fn structural_id(
+        self, ids: &mut HashMap<ExprId, usize>, structures: &mut HashMap<Expr, usize>,
+    ) -> usize {
+        if let Some(id) = ids.get(&self) {
+            return *id;
+        }
+
+        let structure = self.into_expr().map_operands(|operand| ExprId(operand.structural_id(ids, structures)));
+        let len = structures.len();
+        let id = *structures.entry(structure).or_insert(len);
+        let _ = ids.insert(self, id);
+        id
+    }
 }
 
 /// Expr Table
@@ -67,6 +87,7 @@ pub trait TableStorageElement<'id> {}
 
 /// Exprs.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr {
     /// Don't-care value
     X {
@@ -567,4 +588,75 @@ impl Expr {
             Self::ConcatArray { inner, elt_typ } => elt_typ.width() * inner.len(),
         }
     }
+
+    /// Returns the expr whose operands are replaced by `f`.
+    pub(crate) fn map_operands<F: FnMut(ExprId) -> ExprId>(&self, mut f: F) -> Self {
+        match self {
+            Self::X { .. } | Self::Constant { .. } | Self::Input { .. } => self.clone(),
+            Self::Repeat { inner, count } => Self::Repeat { inner: f(*inner), count: *count },
+            Self::Member { inner, index } => Self::Member { inner: f(*inner), index: *index },
+            Self::Struct { inner } => {
+                Self::Struct { inner: inner.iter().map(|(name, member)| (name.clone(), f(*member))).collect() }
+            }
+            Self::Resize { inner, typ_elt, count } => {
+                Self::Resize { inner: f(*inner), typ_elt: typ_elt.clone(), count: *count }
+            }
+            Self::LeftShift { inner, rhs } => Self::LeftShift { inner: f(*inner), rhs: f(*rhs) },
+            Self::RightShift { inner, rhs } => Self::RightShift { inner: f(*inner), rhs: f(*rhs) },
+            Self::Not { inner } => Self::Not { inner: f(*inner) },
+            Self::BinaryOp { op, lhs, rhs } => Self::BinaryOp { op: *op, lhs: f(*lhs), rhs: f(*rhs) },
+            Self::Fold { inner, typ_elt, func, init, acc, inner_slice } => Self::Fold {
+                inner: f(*inner),
+                typ_elt: typ_elt.clone(),
+                func: f(*func),
+                init: f(*init),
+                acc: f(*acc),
+                inner_slice: f(*inner_slice),
+            },
+            Self::TreeFold { inner, acc, op, lhs, rhs } => {
+                Self::TreeFold { inner: f(*inner), acc: f(*acc), op: f(*op), lhs: f(*lhs), rhs: f(*rhs) }
+            }
+            Self::Map { inner, typ_elt, func } => {
+                Self::Map { inner: f(*inner), typ_elt: typ_elt.clone(), func: f(*func) }
+            }
+            Self::Get { inner, typ_elt, index } => {
+                Self::Get { inner: f(*inner), typ_elt: typ_elt.clone(), index: f(*index) }
+            }
+            Self::Clip { inner, typ_elt, from, size } => {
+                Self::Clip { inner: f(*inner), typ_elt: typ_elt.clone(), from: f(*from), size: *size }
+            }
+            Self::Append { lhs, rhs, typ_elt } => Self::Append { lhs: f(*lhs), rhs: f(*rhs), typ_elt: typ_elt.clone() },
+            Self::Zip { inner, typ_inner } => {
+                Self::Zip { inner: inner.iter().map(|inner| f(*inner)).collect(), typ_inner: typ_inner.clone() }
+            }
+            Self::Concat { inner, typ_elt } => Self::Concat { inner: f(*inner), typ_elt: typ_elt.clone() },
+            Self::Chunk { inner, chunk_size } => Self::Chunk { inner: f(*inner), chunk_size: *chunk_size },
+            Self::Repr { inner } => Self::Repr { inner: f(*inner) },
+            Self::Sum { inner, width_elt } => Self::Sum { inner: f(*inner), width_elt: *width_elt },
+            Self::Cond { cond, lhs, rhs } => Self::Cond { cond: f(*cond), lhs: f(*lhs), rhs: f(*rhs) },
+            Self::Set { inner, index, elt } => Self::Set { inner: f(*inner), index: f(*index), elt: f(*elt) },
+            Self::SetRange { inner, typ_elt, index, elts } => {
+                Self::SetRange { inner: f(*inner), typ_elt: typ_elt.clone(), index: f(*index), elts: f(*elts) }
+            }
+            Self::GetVarArray { inner, typ_elt, index } => {
+                Self::GetVarArray { inner: f(*inner), typ_elt: typ_elt.clone(), index: f(*index) }
+            }
+            Self::SetVarArray { inner, index, elt } => {
+                Self::SetVarArray { inner: f(*inner), index: f(*index), elt: f(*elt) }
+            }
+            Self::Case { case_expr, case_items, default } => Self::Case {
+                case_expr: f(*case_expr),
+                case_items: case_items.iter().map(|(case, assignment)| (f(*case), f(*assignment))).collect(),
+                default: default.map(&mut f),
+            },
+            Self::Call { func_name, args, typ } => Self::Call {
+                func_name: func_name.clone(),
+                args: args.iter().map(|arg| f(*arg)).collect(),
+                typ: typ.clone(),
+            },
+            Self::ConcatArray { inner, elt_typ } => {
+                Self::ConcatArray { inner: inner.iter().map(|inner| f(*inner)).collect(), elt_typ: elt_typ.clone() }
+            }
+        }
+    }
 }
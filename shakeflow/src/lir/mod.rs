@@ -1,17 +1,25 @@
 //! Low-level IR.
 
+pub(crate) mod bits;
+mod comb_loop;
 mod expr;
 mod module;
 mod module_composite;
 mod module_fsm;
 mod module_inst;
+mod module_param;
+mod module_structure;
 mod module_virtual;
 mod prelude;
+mod signal_typ;
 
 pub use expr::*;
 pub use module::*;
 pub use module_composite::*;
 pub use module_fsm::*;
 pub use module_inst::*;
+pub use module_param::*;
+pub(crate) use module_structure::*;
 pub use module_virtual::*;
 pub use prelude::*;
+pub use signal_typ::*;
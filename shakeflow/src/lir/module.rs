@@ -1,5 +1,6 @@
 //! Low-level IR's module.
 
+use std::collections::{BTreeSet, HashSet};
 use std::fmt;
 use std::rc::Rc;
 
@@ -21,6 +22,7 @@ pub trait PrimitiveModule: 'static + fmt::Debug {
 
 /// Module's inner data.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[allow(clippy::large_enum_variant)]
 pub enum ModuleInner {
     /// Composite module comprising submodules.
@@ -38,6 +40,8 @@ pub enum ModuleInner {
 
 /// Module.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Module {
     pub(crate) inner: Rc<ModuleInner>,
 }
@@ -53,18 +57,95 @@ impl Module {
         }
     }
 
-    /// Scan submodule instantiation for current module
-    // TODO: Cycle detection
+    /// Scans submodule instantiations of the module in topological order, i.e., each module comes
+    /// after the modules it instantiates.
     pub fn scan_submodule_inst(&self) -> Vec<Module> {
-        // scan for registered modules
+        self.scan_module_insts().into_iter().filter_map(|module_inst| module_inst.module).collect()
+    }
+
+    /// Scans module instantiations in the module in topological order, i.e., each module instantiation
+    /// comes after those in the module it instantiates. Instantiations of external modules are
+    /// included, and the modules instantiated several times are scanned once.
+    pub fn scan_module_insts(&self) -> Vec<ModuleInst> {
+        let mut module_insts = Vec::new();
+        self.scan_module_insts_inner(&mut HashSet::new(), &mut module_insts);
+        module_insts
+    }
+
+    /// Scans module instantiations in the module into `module_insts`. `scanned` is the modules whose
+    /// instantiations are already scanned, identified by their pointers.
+    ///
+    /// Modules are immutable once built, so a module cannot instantiate itself and the scan terminates.
+    pub(crate) fn scan_module_insts_inner(
+        &self, scanned: &mut HashSet<*const ModuleInner>, module_insts: &mut Vec<ModuleInst>,
+    ) {
         match &*self.inner {
-            ModuleInner::Composite(_, composite_module) => composite_module.scan_submodule_inst(),
-            ModuleInner::Fsm(_) | ModuleInner::VirtualModule(_) => vec![],
+            ModuleInner::Composite(_, composite_module) => composite_module.scan_module_insts(scanned, module_insts),
+            ModuleInner::Fsm(_) | ModuleInner::VirtualModule(_) => {}
+            ModuleInner::ModuleInst(module_inst) => {
+                if let Some(module) = &module_inst.module {
+                    if scanned.insert(Rc::as_ptr(&module.inner)) {
+                        module.scan_module_insts_inner(scanned, module_insts);
+                    }
+                }
+                module_insts.push(module_inst.clone());
+            }
+        }
+    }
+
+    /// Returns the first `Some` value of `f` on the composite modules in the module, from the module
+    /// itself to the modules it instantiates.
+    pub fn find_composite<T>(&self, f: &impl Fn(&CompositeModule) -> Option<T>) -> Option<T> {
+        self.find_composite_inner(f, &mut HashSet::new())
+    }
+
+    fn find_composite_inner<T>(
+        &self, f: &impl Fn(&CompositeModule) -> Option<T>, scanned: &mut HashSet<*const ModuleInner>,
+    ) -> Option<T> {
+        if !scanned.insert(Rc::as_ptr(&self.inner)) {
+            return None;
+        }
+        match &*self.inner {
+            ModuleInner::Composite(_, module) => f(module).or_else(|| {
+                ::std::iter::empty()
+                    .chain(module.submodules.iter().map(|(module, _)| module))
+                    .chain(module.registered_modules.iter())
+                    .find_map(|module| module.find_composite_inner(f, scanned))
+            }),
+            ModuleInner::ModuleInst(module_inst) => {
+                module_inst.module.as_ref().and_then(|module| module.find_composite_inner(f, scanned))
+            }
+            ModuleInner::Fsm(_) | ModuleInner::VirtualModule(_) => None,
+        }
+    }
+
+    /// Returns the names of clock domains other than the default one used in the module, in
+    /// lexicographic order.
+    pub fn clock_domains(&self) -> Vec<String> {
+        let mut clock_domains = BTreeSet::new();
+        self.collect_clock_domains(&mut clock_domains);
+        clock_domains.into_iter().collect()
+    }
+
+    fn collect_clock_domains(&self, clock_domains: &mut BTreeSet<String>) {
+        match &*self.inner {
+            ModuleInner::Composite(_, composite_module) => {
+                clock_domains.extend(composite_module.clock_domain.clone());
+                for module in composite_module
+                    .submodules
+                    .iter()
+                    .map(|(module, _)| module)
+                    .chain(composite_module.registered_modules.iter())
+                {
+                    module.collect_clock_domains(clock_domains);
+                }
+            }
+            ModuleInner::Fsm(_) | ModuleInner::VirtualModule(_) => {}
             ModuleInner::ModuleInst(module_inst) => {
+                clock_domains
+                    .extend(module_inst.clock_ports.iter().filter_map(|(_, clock_domain)| clock_domain.clone()));
                 if let Some(module) = &module_inst.module {
-                    [module.scan_submodule_inst(), vec![module.clone()]].concat()
-                } else {
-                    vec![]
+                    module.collect_clock_domains(clock_domains);
                 }
             }
         }
@@ -92,14 +173,30 @@ impl Module {
 }
 
 #[allow(missing_docs)]
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum ModuleError {
-    #[error("file error")]
-    FileError(#[from] std::io::Error),
     #[error("the types are mismatched: {0}")]
     TypMismatch(String),
     #[error("misc error: {0}")]
     Misc(String),
+    #[error("combinational loop in module {module} through {}", .chain.join(" -> "))]
+    CombinationalLoop { module: String, chain: Vec<String> },
+    #[error("expression of type {typ} in {module} does not have the structure of its type")]
+    ExprStructure { module: String, typ: String },
+    #[error("endpoint path {path} in {module} does not match the interface type {typ}")]
+    EndpointPath { module: String, path: String, typ: String },
+    #[error("channel at {path} in {module} is connected to the unexpected endpoint {endpoint}")]
+    Endpoint { module: String, path: String, endpoint: String },
+    #[error("{submodule} cannot be a registered module of {module}")]
+    RegisteredModule { module: String, submodule: String },
+    #[error("{what} in {module} is not supported by the backend")]
+    Unsupported { module: String, what: String },
+    #[error("specializations of {module} differ in {what}, which is not a number")]
+    ParamMismatch { module: String, what: String },
+    #[error("output channel at {path} of {module} is assigned from itself")]
+    CyclicAssignment { module: String, path: String },
+    #[error("interface of {module} does not match its type: {error}")]
+    Interface { module: String, error: InterfaceError },
 }
 
 impl ModuleInner {
@@ -113,11 +210,14 @@ impl ModuleInner {
         }
     }
 
-    /// Returns input prefix of the module.
+    /// Returns input prefix of the module. FSMs are prefixed with `in`, and module instantiations with
+    /// the input prefix of the instance.
     pub(crate) fn input_prefix(&self) -> Option<String> {
         match self {
             Self::Composite(_, builder) => builder.input_prefix.clone(),
-            Self::Fsm(_) | Self::ModuleInst(_) | Self::VirtualModule(_) => None,
+            Self::Fsm(_) => Some("in".to_string()),
+            Self::ModuleInst(module_inst) => module_inst.input_prefix.clone(),
+            Self::VirtualModule(_) => None,
         }
     }
 
@@ -131,11 +231,14 @@ impl ModuleInner {
         }
     }
 
-    /// Returns output prefix of the module.
+    /// Returns output prefix of the module. FSMs are prefixed with `out`, and module instantiations with
+    /// the output prefix of the instance.
     pub(crate) fn output_prefix(&self) -> Option<String> {
         match self {
             Self::Composite(_, builder) => builder.output_prefix.clone(),
-            Self::Fsm(_) | Self::ModuleInst(_) | Self::VirtualModule(_) => None,
+            Self::Fsm(_) => Some("out".to_string()),
+            Self::ModuleInst(module_inst) => module_inst.output_prefix.clone(),
+            Self::VirtualModule(_) => None,
         }
     }
 }
@@ -151,3 +254,163 @@ impl From<ModuleInst> for Module {
 impl From<VirtualModule> for Module {
     fn from(module: VirtualModule) -> Module { Module { inner: Rc::new(ModuleInner::VirtualModule(module)) } }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::sim::SimError;
+    use crate::testing::*;
+    use crate::*;
+
+    type Byte = Bits<U<8>>;
+
+    fn stage() -> Module<UniChannel<Byte>, UniChannel<Byte>> {
+        hir::Fsm::<UniChannel<Byte>, UniChannel<Byte>, Byte, _>::new(
+            "stage",
+            |fwd, bwd, state| (state, bwd, fwd),
+            0.into(),
+        )
+        .into()
+    }
+
+    /// Returns a composite module whose output channel is connected to `endpoint`.
+    fn malformed(
+        endpoint: lir::Endpoint, registered_modules: Vec<lir::Module>,
+    ) -> Module<UniChannel<Byte>, UniChannel<Byte>> {
+        let mut module =
+            lir::CompositeModule::new("malformed".to_string(), Some("in".to_string()), Some("out".to_string()));
+        module.input_interface = input_interface::<UniChannel<Byte>>();
+        module.output_interface = lir::Interface::Channel(lir::Channel {
+            typ: UniChannel::<Byte>::interface_typ().get_channel_typ().unwrap(),
+            endpoint,
+        });
+        module.registered_modules = registered_modules;
+        Module::new(module.build("malformed"))
+    }
+
+    /// Duplicates the interface through its conversions, which is the only way to use a channel twice.
+    fn duplicate<I: Interface>(interface: I) -> (I, I) {
+        let inner = interface.try_into_inner().unwrap();
+        (I::try_from_inner(inner.clone()).unwrap(), I::try_from_inner(inner).unwrap())
+    }
+
+    /// Returns the module error of generating the module in Verilog.
+    fn gen_vir_error<I: Interface, O: Interface>(module: Module<I, O>) -> lir::ModuleError {
+        match generate(package(module), |package, dir| package.gen_vir(dir)) {
+            Err(PackageError::Module { error }) => error,
+            result => panic!("{:?}", result.map(|files| files.into_keys().collect::<Vec<_>>())),
+        }
+    }
+
+    #[test]
+    fn cyclic_assignment_is_a_module_error() {
+        let module =
+            composite::<UniChannel<Byte>, UniChannel<Byte>, _>("wire", Some("in"), Some("out"), |input, _| input)
+                .wrap(|_, (), output: UniChannel<Byte>| {
+                    let (output, feedback) = duplicate(output);
+                    (feedback, output)
+                })
+                .build();
+        let error = gen_vir_error(module.clone());
+        assert!(matches!(&error, lir::ModuleError::CyclicAssignment { module, .. } if module == "wire"), "{:?}", error);
+
+        let error = Simulator::new(&module).unwrap_err();
+        assert!(
+            matches!(&error, SimError::Module(error) if matches!(**error, lir::ModuleError::CyclicAssignment { .. })),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn interface_type_mismatch_is_a_module_error() {
+        let stage = Module::<UniChannel<Byte>, UniChannel<Bits<U<4>>>>::new(stage().inner);
+        let module = composite::<UniChannel<Byte>, UniChannel<Bits<U<4>>>, _>(
+            "mismatch",
+            Some("in"),
+            Some("out"),
+            |input, k| input.comb_inline(k, stage),
+        )
+        .build();
+        let error = gen_vir_error(module);
+        assert!(
+            matches!(
+                &error,
+                lir::ModuleError::Interface { module, error: lir::InterfaceError::TypMismatch { .. } } if module == "stage"
+            ),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn endpoint_path_out_of_the_interface_is_a_module_error() {
+        let path = [lir::EndpointNode::Index(0)].into_iter().collect();
+        let error = gen_vir_error(malformed(lir::Endpoint::input(path), Vec::new()));
+        assert!(
+            matches!(&error, lir::ModuleError::EndpointPath { module, .. } if module == "malformed"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn temporary_endpoint_is_a_module_error() {
+        let module = || malformed(lir::Endpoint::temp(lir::EndpointPath::default()), Vec::new());
+        let error = gen_vir_error(module());
+        assert!(matches!(&error, lir::ModuleError::Endpoint { module, .. } if module == "malformed"), "{:?}", error);
+
+        let error = Simulator::new(&module()).unwrap_err();
+        assert!(
+            matches!(&error, SimError::Module(error) if matches!(**error, lir::ModuleError::Endpoint { .. })),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn registered_fsm_is_a_module_error() {
+        let module = malformed(lir::Endpoint::input(lir::EndpointPath::default()), vec![stage().inner]);
+        let error = gen_vir_error(module);
+        assert!(
+            matches!(&error, lir::ModuleError::RegisteredModule { submodule, .. } if submodule == "stage"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn non_constant_initial_value_is_a_module_error() {
+        let init: Expr<'static, Byte> = 0.into();
+        let module: Module<UniChannel<Byte>, UniChannel<Byte>> =
+            hir::Fsm::<UniChannel<Byte>, UniChannel<Byte>, Byte, _>::new(
+                "inverted",
+                |fwd, bwd, state| (state, bwd, fwd),
+                !init,
+            )
+            .into();
+        let error = gen_vir_error(module);
+        assert!(matches!(&error, lir::ModuleError::Unsupported { .. }), "{:?}", error);
+    }
+
+    #[test]
+    fn struct_argument_of_call_is_a_module_error() {
+        let zero: Expr<'static, Byte> = 0.into();
+        let module: Module<UniChannel<Byte>, UniChannel<Byte>> =
+            hir::Fsm::<UniChannel<Byte>, UniChannel<Byte>, (Byte, Byte), _>::new(
+                "pack",
+                |fwd, bwd, state| {
+                    let packed = lir::Expr::Call {
+                        func_name: "$pack".to_string(),
+                        args: vec![state.into_inner()],
+                        typ: Byte::port_decls(),
+                    };
+                    (packed.into(), bwd, (fwd, fwd).into())
+                },
+                (zero, zero).into(),
+            )
+            .into();
+        let error = gen_vir_error(module);
+        assert!(matches!(&error, lir::ModuleError::ExprStructure { .. }), "{:?}", error);
+    }
+}
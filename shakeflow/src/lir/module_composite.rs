@@ -1,5 +1,7 @@
 //! Composite module.
 
+use std::collections::HashSet;
+use std::panic::Location;
 use std::rc::Rc;
 
 use thiserror::Error;
@@ -22,7 +24,8 @@ pub enum CompositeModuleError {
 }
 
 /// Composite module type.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CompositeModuleTyp {
     /// `I` -> `O`
     OneToOne,
@@ -37,6 +40,7 @@ impl Default for CompositeModuleTyp {
 
 /// Composite module.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CompositeModule {
     /// Name of module.
     pub name: String,
@@ -61,6 +65,22 @@ pub struct CompositeModule {
 
     /// Output interface's prefix. For example, 'm_axis' is output prefix for cmac_pad.
     pub output_prefix: Option<String>,
+
+    /// Clock domain of the module. If it is `None`, the module is in the clock domain of the
+    /// enclosing module.
+    pub clock_domain: Option<String>,
+
+    /// Source location where the module is created.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::json::serialize_source"))]
+    pub source: Option<&'static Location<'static>>,
+
+    /// Names of the modules in the instantiation cycle through the module, if its construction is
+    /// stopped at the cycle. Such a module cannot be generated.
+    pub instantiation_cycle: Option<Vec<String>>,
+
+    /// Error in the construction of the module, which is reported when the package is generated.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub error: Option<ModuleError>,
 }
 
 impl CompositeModule {
@@ -75,6 +95,10 @@ impl CompositeModule {
             input_prefix,
             output_interface: Interface::default(),
             output_prefix,
+            clock_domain: None,
+            source: None,
+            instantiation_cycle: None,
+            error: None,
         }
     }
 
@@ -134,13 +158,15 @@ impl CompositeModule {
         Module { inner: Rc::new(ModuleInner::Composite(String::from(name), self)) }
     }
 
-    /// Scan submodule instantiation of composite module
-    pub fn scan_submodule_inst(&self) -> Vec<Module> {
+    /// Scans module instantiations in the composite module into `module_insts`. See
+    /// `Module::scan_module_insts_inner` for `scanned`.
+    pub(crate) fn scan_module_insts(
+        &self, scanned: &mut HashSet<*const ModuleInner>, module_insts: &mut Vec<ModuleInst>,
+    ) {
         ::std::iter::empty()
             .chain(self.submodules.iter().map(|(module, _)| module))
             .chain(self.registered_modules.iter())
-            .flat_map(|module| module.scan_submodule_inst())
-            .collect()
+            .for_each(|module| module.scan_module_insts_inner(scanned, module_insts))
     }
 
     /// Walk the module structure and return a vec of mutable refs to names of all inner `ModuleInst`s.
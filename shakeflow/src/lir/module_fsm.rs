@@ -1,9 +1,13 @@
 //! Finite state machine (Mealy machine).
 
+use std::fmt;
+use std::panic::Location;
+
 use crate::lir::*;
 
 /// Finite state machine (Mealy machine).
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Fsm {
     /// Input interface type.
     pub(crate) input_interface_typ: InterfaceTyp,
@@ -19,6 +23,110 @@ pub struct Fsm {
     pub(crate) state: ExprId,
     /// Initial value of registers in the FSM.
     pub(crate) init: ExprId,
+    /// Assertions on the signals of the FSM.
+    pub(crate) assertions: Vec<Assertion>,
+    /// Requirements that the output backward value does not depend combinationally on bits of the output forward value.
+    pub(crate) independences: Vec<Independence>,
+    /// Reset of the state registers, overriding the reset style of the package.
+    pub(crate) reset: Option<ResetKind>,
+    /// Source location where the FSM is created.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::json::serialize_source"))]
+    pub(crate) source: Option<&'static Location<'static>>,
+}
+
+/// Kind of reset of state registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ResetKind {
+    /// Synchronous reset.
+    Sync,
+
+    /// Asynchronous reset.
+    Async,
+
+    /// No reset. Registers only have initial values.
+    None,
+}
+
+impl Default for ResetKind {
+    fn default() -> Self { Self::Sync }
+}
+
+/// Polarity of reset signals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ResetPolarity {
+    /// Active-high reset signals, e.g., `rst`.
+    ActiveHigh,
+
+    /// Active-low reset signals, e.g., `rst_n`.
+    ActiveLow,
+}
+
+impl Default for ResetPolarity {
+    fn default() -> Self { Self::ActiveHigh }
+}
+
+/// Reset style of state registers.
+///
+/// The polarity is shared by all FSMs since it determines the reset ports of modules, but the kind
+/// of reset can be overridden by each FSM. Reset ports of instantiated external modules have their
+/// own polarity, which is active-high by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResetStyle {
+    /// Kind of reset.
+    pub kind: ResetKind,
+
+    /// Polarity of reset signals.
+    pub polarity: ResetPolarity,
+}
+
+/// Kind of assertion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum AssertionKind {
+    /// The condition should hold.
+    Assert,
+    /// The condition is assumed to hold.
+    Assume,
+    /// The condition should be reachable.
+    Cover,
+}
+
+impl fmt::Display for AssertionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssertionKind::Assert => write!(f, "assert"),
+            AssertionKind::Assume => write!(f, "assume"),
+            AssertionKind::Cover => write!(f, "cover"),
+        }
+    }
+}
+
+/// Assertion on the signals of an FSM, checked at each rising edge of the clock out of reset.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Assertion {
+    /// Kind.
+    pub(crate) kind: AssertionKind,
+    /// Condition. It may depend on the same inputs as the output exprs of the FSM.
+    pub(crate) cond: ExprId,
+    /// Message.
+    pub(crate) message: String,
+}
+
+/// Requirement that the backward value of the output interface of an FSM does not depend
+/// combinationally on a bit of its forward value.
+///
+/// It can only be checked in the simulator.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Independence {
+    /// Index of the bit in the output forward value.
+    pub(crate) fwd_bit: usize,
+    /// Message.
+    pub(crate) message: String,
 }
 
 impl PrimitiveModule for Fsm {
@@ -1,9 +1,12 @@
 //! Module instantiation.
 
+use std::panic::Location;
+
 use crate::lir::*;
 
 /// Module Instantiation.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ModuleInst {
     /// Input interface type.
     pub(crate) input_interface_typ: InterfaceTyp,
@@ -23,6 +26,15 @@ pub struct ModuleInst {
     pub(crate) output_prefix: Option<String>,
     /// Shakeflow module.
     pub(crate) module: Option<Module>,
+    /// Additional clock and reset ports. `{prefix}_clk` and `{prefix}_rst` are driven by the clock
+    /// domain, or the clock domain of the enclosing module if it is `None`.
+    pub(crate) clock_ports: Vec<(String, Option<String>)>,
+    /// Polarity of the reset ports of the external module. Reset ports of shakeflow modules have the
+    /// polarity of the package.
+    pub(crate) reset_polarity: ResetPolarity,
+    /// Source location where the module instantiation is created.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::json::serialize_source"))]
+    pub(crate) source: Option<&'static Location<'static>>,
 }
 
 impl PrimitiveModule for ModuleInst {
@@ -0,0 +1,42 @@
+//! Parameterized module.
+
+use itertools::Itertools;
+
+use super::Module;
+
+/// Module parameterized by Verilog parameters.
+///
+/// Modules are specialized by const generics in Rust, so it consists of a module for each set of
+/// parameter values. The first specialization gives the default values of the parameters.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParamModule {
+    /// Module name.
+    pub(crate) name: String,
+    /// Parameter names.
+    pub(crate) params: Vec<String>,
+    /// Parameter values and modules of the specializations.
+    pub(crate) specializations: Vec<(Vec<usize>, Module)>,
+}
+
+impl ParamModule {
+    /// Returns module name.
+    pub fn get_module_name(&self) -> String { self.name.clone() }
+
+    /// Returns the parameters with their default values.
+    pub fn default_params(&self) -> Vec<(String, usize)> {
+        match self.specializations.first() {
+            Some((values, _)) => self.params.iter().cloned().zip(values.iter().copied()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the specializations named after their parameter values, e.g., `rx_hash_512_64`, for
+    /// the backends without module parameters.
+    pub(crate) fn named_specializations(&self) -> impl Iterator<Item = (String, &Module)> {
+        self.specializations.iter().map(|(values, module)| {
+            let name = std::iter::once(self.name.clone()).chain(values.iter().map(usize::to_string)).join("_");
+            (name, module)
+        })
+    }
+}
@@ -0,0 +1,152 @@
+//! Structural identity of modules.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::*;
+
+/// Structure of a module, where submodules and exprs are replaced by their structural ids.
+///
+/// Instance names of module instantiations and source locations are omitted, since they do not
+/// affect the logic.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Structure {
+    Composite {
+        name: String,
+        module_typ: CompositeModuleTyp,
+        submodules: Vec<(usize, Interface)>,
+        registered_modules: Vec<usize>,
+        input_interface: Interface,
+        input_prefix: Option<String>,
+        output_interface: Interface,
+        output_prefix: Option<String>,
+        clock_domain: Option<String>,
+    },
+    Fsm {
+        module_name: String,
+        input_interface_typ: InterfaceTyp,
+        output_interface_typ: InterfaceTyp,
+        exprs: [usize; 4],
+        assertions: Vec<(AssertionKind, usize, String)>,
+        independences: Vec<(usize, String)>,
+        reset: Option<ResetKind>,
+    },
+    ModuleInst {
+        module_name: String,
+        input_interface_typ: InterfaceTyp,
+        output_interface_typ: InterfaceTyp,
+        params: Vec<(String, usize)>,
+        has_clkrst: bool,
+        input_prefix: Option<String>,
+        output_prefix: Option<String>,
+        module: Option<usize>,
+        clock_ports: Vec<(String, Option<String>)>,
+        reset_polarity: ResetPolarity,
+    },
+    VirtualModule {
+        module_name: String,
+        registered_index: usize,
+        input_prefix: String,
+        output_prefix: String,
+        input_interface_typ: InterfaceTyp,
+        input_endpoint_path: EndpointPath,
+        output_interface_typ: InterfaceTyp,
+        output_endpoint_path: EndpointPath,
+    },
+}
+
+/// Memoized structural ids of modules.
+///
+/// Modules have the same structural id if and only if they have the same logic, even if their exprs
+/// are allocated separately in the expr table. Structures are interned by equality, so different
+/// modules never share an id.
+#[derive(Debug, Default)]
+pub(crate) struct StructuralIds {
+    /// Ids of modules, by the addresses of their inner data. The modules are kept alive so that the
+    /// addresses are not reused.
+    modules: HashMap<*const ModuleInner, (Module, usize)>,
+
+    /// Ids of module structures.
+    structures: HashMap<Structure, usize>,
+
+    /// Ids of exprs.
+    exprs: HashMap<ExprId, usize>,
+
+    /// Ids of exprs whose operands are replaced by their ids.
+    expr_structures: HashMap<Expr, usize>,
+}
+
+impl StructuralIds {
+    /// Returns the structural id of the module.
+    pub(crate) fn module(&mut self, module: &Module) -> usize {
+        let key = Rc::as_ptr(&module.inner);
+        if let Some((_, id)) = self.modules.get(&key) {
+            return *id;
+        }
+
+        let structure = match &*module.inner {
+            ModuleInner::Composite(name, composite) => Structure::Composite {
+                name: name.clone(),
+                module_typ: composite.module_typ,
+                submodules: composite
+                    .submodules
+                    .iter()
+                    .map(|(submodule, interface)| (self.module(submodule), interface.clone()))
+                    .collect(),
+                registered_modules: composite.registered_modules.iter().map(|module| self.module(module)).collect(),
+                input_interface: composite.input_interface.clone(),
+                input_prefix: composite.input_prefix.clone(),
+                output_interface: composite.output_interface.clone(),
+                output_prefix: composite.output_prefix.clone(),
+                clock_domain: composite.clock_domain.clone(),
+            },
+            ModuleInner::Fsm(fsm) => Structure::Fsm {
+                module_name: fsm.module_name.clone(),
+                input_interface_typ: fsm.input_interface_typ.clone(),
+                output_interface_typ: fsm.output_interface_typ.clone(),
+                exprs: [fsm.output_fwd, fsm.input_bwd, fsm.state, fsm.init].map(|expr| self.expr_id(expr)),
+                assertions: fsm
+                    .assertions
+                    .iter()
+                    .map(|assertion| (assertion.kind, self.expr_id(assertion.cond), assertion.message.clone()))
+                    .collect(),
+                independences: fsm
+                    .independences
+                    .iter()
+                    .map(|independence| (independence.fwd_bit, independence.message.clone()))
+                    .collect(),
+                reset: fsm.reset,
+            },
+            ModuleInner::ModuleInst(module_inst) => Structure::ModuleInst {
+                module_name: module_inst.module_name.clone(),
+                input_interface_typ: module_inst.input_interface_typ.clone(),
+                output_interface_typ: module_inst.output_interface_typ.clone(),
+                params: module_inst.params.clone(),
+                has_clkrst: module_inst.has_clkrst,
+                input_prefix: module_inst.input_prefix.clone(),
+                output_prefix: module_inst.output_prefix.clone(),
+                module: module_inst.module.as_ref().map(|module| self.module(module)),
+                clock_ports: module_inst.clock_ports.clone(),
+                reset_polarity: module_inst.reset_polarity,
+            },
+            ModuleInner::VirtualModule(virtual_module) => Structure::VirtualModule {
+                module_name: virtual_module.module_name.clone(),
+                registered_index: virtual_module.registered_index,
+                input_prefix: virtual_module.input_prefix.clone(),
+                output_prefix: virtual_module.output_prefix.clone(),
+                input_interface_typ: virtual_module.input_interface_typ.clone(),
+                input_endpoint_path: virtual_module.input_endpoint_path.clone(),
+                output_interface_typ: virtual_module.output_interface_typ.clone(),
+                output_endpoint_path: virtual_module.output_endpoint_path.clone(),
+            },
+        };
+
+        let len = self.structures.len();
+        let id = *self.structures.entry(structure).or_insert(len);
+        let _ = self.modules.insert(key, (module.clone(), id));
+        id
+    }
+
+    /// Returns the structural id of the expr.
+    fn expr_id(&mut self, expr: ExprId) -> usize { expr.structural_id(&mut self.exprs, &mut self.expr_structures) }
+}
@@ -5,6 +5,7 @@ use crate::PrimitiveModule;
 
 /// Virtual Module.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct VirtualModule {
     /// Module name
     pub(crate) module_name: String,
@@ -1,15 +1,20 @@
 //! Low-level IR's prelude.
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
 use std::ops::*;
 
 use linked_hash_map::LinkedHashMap;
+use thiserror::Error;
 
+use crate::lir::ChannelTypDecl;
 use crate::utils::join_options;
 
 /// Shape of an array.
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Shape {
     inner: VecDeque<usize>,
 }
@@ -54,6 +59,7 @@ impl Shape {
 
 /// LIR value type.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PortDecls {
     /// Collection of channels.
     Struct(Vec<(Option<String>, PortDecls)>),
@@ -124,6 +130,26 @@ impl PortDecls {
     }
 }
 
+impl ToString for PortDecls {
+    /// Returns the type in the form of `{name: [8], [2][4]}`, where unnamed fields are not prefixed.
+    fn to_string(&self) -> String {
+        match self {
+            PortDecls::Bits(shape) => shape.inner.iter().map(|dim| format!("[{}]", dim)).collect(),
+            PortDecls::Struct(inner) => format!(
+                "{{{}}}",
+                inner
+                    .iter()
+                    .map(|(name, typ)| match name {
+                        Some(name) => format!("{}: {}", name, typ.to_string()),
+                        None => typ.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
 impl IntoIterator for &PortDecls {
     type IntoIter = ValueTypIterator;
     type Item = (Option<String>, Shape);
@@ -144,23 +170,57 @@ impl Iterator for ValueTypIterator {
 }
 
 /// Channel's type.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ChannelTyp {
     /// Forward value.
     pub fwd: PortDecls,
 
     /// Backward value.
     pub bwd: PortDecls,
+
+    /// Declaration with the types of the signals, if known. It is not compared or hashed.
+    pub decl: Option<Box<ChannelTypDecl>>,
 }
 
 impl ChannelTyp {
     /// Creates a new channel type.
-    pub const fn new(fwd: PortDecls, bwd: PortDecls) -> Self { Self { fwd, bwd } }
+    pub const fn new(fwd: PortDecls, bwd: PortDecls) -> Self { Self { fwd, bwd, decl: None } }
+
+    /// Sets the declaration with the types of the signals.
+    #[must_use]
+    pub fn with_decl(self, decl: ChannelTypDecl) -> Self { Self { decl: Some(Box::new(decl)), ..self } }
+
+    /// Returns the declaration with the types of the signals. If unknown, it is anonymous.
+    pub fn decl(&self) -> ChannelTypDecl {
+        match &self.decl {
+            Some(decl) => *decl.clone(),
+            None => ChannelTypDecl {
+                name: "Channel".to_string(),
+                fwd: self.fwd.clone().into(),
+                bwd: self.bwd.clone().into(),
+            },
+        }
+    }
+}
+
+impl PartialEq for ChannelTyp {
+    fn eq(&self, other: &Self) -> bool { self.fwd == other.fwd && self.bwd == other.bwd }
+}
+
+impl Eq for ChannelTyp {}
+
+impl Hash for ChannelTyp {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fwd.hash(state);
+        self.bwd.hash(state);
+    }
 }
 
 /// Interface's type.
 #[allow(variant_size_differences)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum InterfaceTyp {
     /// Unit type
     Unit,
@@ -179,6 +239,22 @@ pub enum InterfaceTyp {
 }
 
 impl InterfaceTyp {
+    /// Returns a short description of the interface type for error messages, e.g. `[VrChannel; 4]`.
+    /// Structs are described by their field names.
+    pub fn describe(&self) -> String {
+        match self {
+            InterfaceTyp::Unit => "()".to_string(),
+            InterfaceTyp::Channel(channel_typ) => {
+                channel_typ.decl.as_ref().map_or_else(|| "channel".to_string(), |decl| decl.name.clone())
+            }
+            InterfaceTyp::Array(interface_typ, count) => format!("[{}; {}]", interface_typ.describe(), count),
+            InterfaceTyp::ExpansiveArray(interface_typ, count) => {
+                format!("ExpansiveArray<{}, {}>", interface_typ.describe(), count)
+            }
+            InterfaceTyp::Struct(inner) => format!("{{ {} }}", inner.keys().cloned().collect::<Vec<_>>().join(", ")),
+        }
+    }
+
     /// TODO: Documentation
     pub fn get_channel_typ(self) -> Option<ChannelTyp> {
         if let InterfaceTyp::Channel(channel_typ) = self {
@@ -249,7 +325,8 @@ impl InterfaceTyp {
 }
 
 /// Input/output channel.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Channel {
     /// Channel's typ.
     pub typ: ChannelTyp,
@@ -268,7 +345,8 @@ impl Channel {
 
 /// Input/output interface.
 #[allow(variant_size_differences)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Interface {
     /// Unit
     Unit,
@@ -351,78 +429,117 @@ impl Interface {
     }
 }
 
-impl FromIterator<(Interface, EndpointPath)> for Interface {
-    /// Constructs interface from primitive interfaces.
-    fn from_iter<I: IntoIterator<Item = (Interface, EndpointPath)>>(iter: I) -> Self {
-        let mut primitives = iter.into_iter().collect::<Vec<_>>();
-        assert!(!primitives.is_empty());
-
-        let is_primitive = primitives[0].1.inner.front().is_none();
-        if is_primitive {
-            assert_eq!(primitives.len(), 1);
-            let (primitive, _) = primitives.pop().unwrap();
-            assert!(matches!(primitive, Interface::Unit | Interface::Channel(_)));
-            primitive
-        } else {
-            match primitives[0].1.inner.front().unwrap() {
-                EndpointNode::Index(_) => {
-                    let mut interfaces = HashMap::<usize, Vec<(Interface, EndpointPath)>>::new();
-                    for (interface, mut path) in primitives {
-                        let node = path.inner.pop_front().unwrap();
-                        match node {
-                            EndpointNode::Index(i) => {
-                                interfaces.entry(i).or_default();
-                                let primitives = interfaces.get_mut(&i).unwrap();
-                                primitives.push((interface, path));
-                            }
-                            _ => panic!("internal compiler error"),
-                        }
-                    }
-                    let len = interfaces.len();
-                    Interface::Array(
-                        (0..len).map(|i| interfaces.get(&i).unwrap().clone().into_iter().collect()).collect(),
-                    )
+impl Interface {
+    /// Constructs interface from primitive interfaces, which should have the endpoint paths of the
+    /// primitives of an interface type.
+    pub fn try_from_primitives(mut primitives: Vec<(Interface, EndpointPath)>) -> Result<Self, InterfaceError> {
+        let front = primitives.first().ok_or(InterfaceError::NoChannels)?.1.inner.front().cloned();
+        let node = match front {
+            Some(node) => node,
+            None => {
+                if primitives.len() > 1 {
+                    return Err(InterfaceError::TooManyChannels);
                 }
-                EndpointNode::ExpansiveIndex(_) => {
-                    let mut interfaces = HashMap::<usize, Vec<(Interface, EndpointPath)>>::new();
-                    for (interface, mut path) in primitives {
-                        let node = path.inner.pop_front().unwrap();
-                        match node {
-                            EndpointNode::ExpansiveIndex(i) => {
-                                interfaces.entry(i).or_default();
-                                let primitives = interfaces.get_mut(&i).unwrap();
-                                primitives.push((interface, path));
-                            }
-                            _ => panic!("internal compiler error"),
-                        }
-                    }
-                    let len = interfaces.len();
-                    Interface::ExpansiveArray(
-                        (0..len).map(|i| interfaces.get(&i).unwrap().clone().into_iter().collect()).collect(),
-                    )
+                let (primitive, path) = primitives.pop().unwrap();
+                return match primitive {
+                    Interface::Unit | Interface::Channel(_) => Ok(primitive),
+                    _ => Err(InterfaceError::PrimitivePath { path }),
+                };
+            }
+        };
+
+        // Groups the primitives by their first endpoint nodes, which should be of the same kind.
+        let mut groups = LinkedHashMap::<EndpointNode, Vec<(Interface, EndpointPath)>>::new();
+        for (interface, mut path) in primitives {
+            let path_front = path.inner.pop_front();
+            let group = match path_front {
+                Some(EndpointNode::Index(i)) if matches!(node, EndpointNode::Index(_)) => EndpointNode::Index(i),
+                Some(EndpointNode::ExpansiveIndex(i)) if matches!(node, EndpointNode::ExpansiveIndex(_)) => {
+                    EndpointNode::ExpansiveIndex(i)
+                }
+                Some(EndpointNode::Field(name, sep)) if matches!(node, EndpointNode::Field(..)) => {
+                    EndpointNode::Field(name, sep)
                 }
-                EndpointNode::Field(..) => {
-                    let mut inner = LinkedHashMap::<String, (Option<String>, Vec<(Interface, EndpointPath)>)>::new();
-                    for (interface, mut path) in primitives {
-                        let node = path.inner.pop_front().unwrap();
-                        match node {
-                            EndpointNode::Field(name, sep) => {
-                                inner.entry(name.clone()).or_insert((sep, Vec::new()));
-                                let primitives = inner.get_mut(&name).unwrap();
-                                primitives.1.push((interface, path));
-                            }
-                            _ => panic!("internal compiler error"),
-                        }
+                _ => {
+                    path.inner.extend(path_front);
+                    return Err(InterfaceError::PrimitivePath { path });
+                }
+            };
+            groups.entry(group).or_insert_with(Vec::new).push((interface, path));
+        }
+
+        let mut interfaces = groups.into_iter().map(|(node, primitives)| {
+            Interface::try_from_primitives(primitives)
+                .map(|interface| (node.clone(), interface))
+                .map_err(|error| error.nested(node))
+        });
+        match node {
+            EndpointNode::Index(_) | EndpointNode::ExpansiveIndex(_) => {
+                let mut elements = interfaces.collect::<Result<Vec<_>, _>>()?;
+                elements.sort_by_key(|(node, _)| match node {
+                    EndpointNode::Index(i) | EndpointNode::ExpansiveIndex(i) => *i,
+                    EndpointNode::Field(..) => unreachable!("elements are grouped by indices"),
+                });
+                for (i, (node, _)) in elements.iter().enumerate() {
+                    if !matches!(node, EndpointNode::Index(j) | EndpointNode::ExpansiveIndex(j) if *j == i) {
+                        return Err(InterfaceError::PrimitivePath { path: [node.clone()].into_iter().collect() });
                     }
-                    Interface::Struct(
-                        inner
-                            .into_iter()
-                            .map(|(name, (sep, primitives))| (name, (sep, primitives.into_iter().collect())))
-                            .collect(),
-                    )
                 }
+                let elements = elements.into_iter().map(|(_, interface)| interface).collect();
+                Ok(if matches!(node, EndpointNode::Index(_)) {
+                    Interface::Array(elements)
+                } else {
+                    Interface::ExpansiveArray(elements)
+                })
+            }
+            EndpointNode::Field(..) => interfaces
+                .try_fold(LinkedHashMap::new(), |mut inner, field| {
+                    let (node, interface) = field?;
+                    if let EndpointNode::Field(name, sep) = node {
+                        let _ = inner.insert(name, (sep, interface));
+                    }
+                    Ok(inner)
+                })
+                .map(Interface::Struct),
+        }
+    }
+}
+
+impl FromIterator<(Interface, EndpointPath)> for Interface {
+    /// Constructs interface from primitive interfaces.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the endpoint paths are not those of the primitives of an interface type. Use
+    /// `Interface::try_from_primitives` to handle the error.
+    fn from_iter<I: IntoIterator<Item = (Interface, EndpointPath)>>(iter: I) -> Self {
+        Interface::try_from_primitives(iter.into_iter().collect()).unwrap_or_else(|error| panic!("{}", error))
+    }
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Error)]
+pub enum InterfaceError {
+    #[error("more channels are required")]
+    NoChannels,
+    #[error("there are too many channels")]
+    TooManyChannels,
+    #[error("interface at {} has type {found}, but {expected} is expected", .path.to_string())]
+    TypMismatch { path: EndpointPath, expected: String, found: String },
+    #[error("primitive interface at {} does not match the other primitives", .path.to_string())]
+    PrimitivePath { path: EndpointPath },
+}
+
+impl InterfaceError {
+    /// Returns the error of the subinterface at `node`, by prepending `node` to the path of the error.
+    pub fn nested(mut self, node: EndpointNode) -> Self {
+        match &mut self {
+            InterfaceError::TypMismatch { path, .. } | InterfaceError::PrimitivePath { path } => {
+                path.inner.push_front(node)
             }
+            InterfaceError::NoChannels | InterfaceError::TooManyChannels => {}
         }
+        self
     }
 }
 
@@ -430,6 +547,7 @@ impl FromIterator<(Interface, EndpointPath)> for Interface {
 // TODO: Add array range types
 #[allow(variant_size_differences)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum EndpointNode {
     /// Element of array.
     Index(usize),
@@ -444,6 +562,8 @@ pub enum EndpointNode {
 
 /// Endpoint's path.
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct EndpointPath {
     /// List of endpoint nodes.
     pub inner: VecDeque<EndpointNode>,
@@ -463,8 +583,28 @@ impl DerefMut for EndpointPath {
     fn deref_mut(&mut self) -> &mut Self::Target { &mut self.inner }
 }
 
+impl ToString for EndpointPath {
+    /// Returns the path in the form of `field[index].field`, or `.` for the empty path.
+    fn to_string(&self) -> String {
+        if self.inner.is_empty() {
+            return ".".to_string();
+        }
+
+        self.inner
+            .iter()
+            .enumerate()
+            .map(|(i, node)| match node {
+                EndpointNode::Index(index) | EndpointNode::ExpansiveIndex(index) => format!("[{}]", index),
+                EndpointNode::Field(name, _) if i == 0 => name.clone(),
+                EndpointNode::Field(name, _) => format!(".{}", name),
+            })
+            .collect()
+    }
+}
+
 /// Wire's endpoint.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Endpoint {
     /// Input interface.
     Input {
@@ -517,6 +657,7 @@ impl Endpoint {
 /// Unary operators.
 // TODO: Add more cases
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum UnaryOp {
     /// Negation
     Negation,
@@ -533,6 +674,7 @@ impl ToString for UnaryOp {
 
 /// Binary operators.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BinaryOp {
     /// Addition
     Add,
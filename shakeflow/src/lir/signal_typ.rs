@@ -0,0 +1,91 @@
+//! Types of signals with the names of their Rust types.
+
+use crate::lir::*;
+
+/// Type of a signal with the names of its Rust types.
+///
+/// It has the same layout as `PortDecls`, and is used by the backends that declare types, e.g.,
+/// packed structs and enums in SystemVerilog.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SignalTyp {
+    /// Bits.
+    Bits(Shape),
+
+    /// Struct derived by `#[derive(Signal)]`.
+    Struct {
+        /// Name of the struct, or `None` for anonymous structs, e.g., tuples
+        name: Option<String>,
+
+        /// Members, which are (field name, name in `PortDecls`, type)
+        members: Vec<(String, Option<String>, SignalTyp)>,
+    },
+
+    /// C-like enum derived by `#[derive(Signal)]`.
+    Enum {
+        /// Name of the enum
+        name: String,
+
+        /// Width of the encodings
+        width: usize,
+
+        /// Variants, which are (variant name, encoding)
+        variants: Vec<(String, usize)>,
+    },
+}
+
+impl SignalTyp {
+    /// Width of the signal.
+    pub fn width(&self) -> usize {
+        match self {
+            SignalTyp::Bits(shape) => shape.width(),
+            SignalTyp::Struct { members, .. } => members.iter().map(|(_, _, member)| member.width()).sum(),
+            SignalTyp::Enum { width, .. } => *width,
+        }
+    }
+
+    /// Returns `PortDecls` of the signal, forgetting the names of types.
+    pub fn port_decls(&self) -> PortDecls {
+        match self {
+            SignalTyp::Bits(shape) => PortDecls::Bits(shape.clone()),
+            SignalTyp::Struct { members, .. } => PortDecls::Struct(
+                members.iter().map(|(_, symbol, member)| (symbol.clone(), member.port_decls())).collect(),
+            ),
+            SignalTyp::Enum { width, .. } => PortDecls::Bits(Shape::new([*width])),
+        }
+    }
+}
+
+impl From<PortDecls> for SignalTyp {
+    fn from(port_decls: PortDecls) -> Self {
+        match port_decls {
+            PortDecls::Bits(shape) => SignalTyp::Bits(shape),
+            PortDecls::Struct(inner) => SignalTyp::Struct {
+                name: None,
+                members: inner
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (symbol, member))| {
+                        (symbol.clone().unwrap_or_else(|| index.to_string()), symbol, member.into())
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Declaration of a channel type with the types of its signals.
+///
+/// It does not affect the structure of the channel type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChannelTypDecl {
+    /// Name of the channel type, e.g., `VrChannel`.
+    pub name: String,
+
+    /// Type of the forward signal.
+    pub fwd: SignalTyp,
+
+    /// Type of the backward signal.
+    pub bwd: SignalTyp,
+}
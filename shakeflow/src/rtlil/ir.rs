@@ -0,0 +1,305 @@
+//! RTLIL IR.
+//!
+//! Textual RTLIL, the internal representation of Yosys. Names are stored with their escape
+//! character: `\` for public names and `$` for names introduced by the generator.
+
+use crate::codegen::*;
+use crate::utils::indent;
+
+const INDENT: usize = 2;
+
+/// Parameter value of cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Const {
+    /// Integer
+    Int(usize),
+    /// Bits, whose first element is the most significant bit
+    Bits(LogicValues),
+}
+
+impl ToString for Const {
+    fn to_string(&self) -> String {
+        match self {
+            Const::Int(value) => value.to_string(),
+            Const::Bits(value) => format!("{}'{}", value.len(), value.to_string()),
+        }
+    }
+}
+
+/// Chunk of signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigChunk {
+    /// Bits of wire.
+    Wire {
+        /// Name of the wire
+        name: String,
+        /// Width of the wire
+        width_wire: usize,
+        /// Offset of the least significant bit
+        offset: usize,
+        /// Width of the chunk
+        width: usize,
+    },
+    /// Constant bits, whose first element is the most significant bit.
+    Const(LogicValues),
+}
+
+impl ToString for SigChunk {
+    fn to_string(&self) -> String {
+        match self {
+            SigChunk::Wire { name, width_wire, offset, width } => {
+                if *offset == 0 && width == width_wire {
+                    name.clone()
+                } else if *width == 1 {
+                    format!("{} [{}]", name, offset)
+                } else {
+                    format!("{} [{}:{}]", name, offset + width - 1, offset)
+                }
+            }
+            SigChunk::Const(value) => format!("{}'{}", value.len(), value.to_string()),
+        }
+    }
+}
+
+impl SigChunk {
+    /// Returns the width of the chunk.
+    pub fn width(&self) -> usize {
+        match self {
+            SigChunk::Wire { width, .. } => *width,
+            SigChunk::Const(value) => value.len(),
+        }
+    }
+
+    /// Returns `width` bits of the chunk starting from `offset`.
+    fn slice(&self, offset: usize, width: usize) -> Self {
+        assert!(offset + width <= self.width());
+        match self {
+            SigChunk::Wire { name, width_wire, offset: base, .. } => {
+                SigChunk::Wire { name: name.clone(), width_wire: *width_wire, offset: base + offset, width }
+            }
+            SigChunk::Const(value) => {
+                let end = value.len() - offset;
+                SigChunk::Const(LogicValues::new(value[end - width..end].to_vec()))
+            }
+        }
+    }
+}
+
+/// Signal, which is the concatenation of chunks. The first chunk is placed at the least
+/// significant bits.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SigSpec {
+    /// Chunks
+    pub chunks: Vec<SigChunk>,
+}
+
+impl ToString for SigSpec {
+    fn to_string(&self) -> String {
+        match self.chunks.as_slice() {
+            [chunk] => chunk.to_string(),
+            chunks => format!(
+                "{{ {}}}",
+                chunks.iter().rev().map(|chunk| format!("{} ", chunk.to_string())).collect::<String>()
+            ),
+        }
+    }
+}
+
+impl SigSpec {
+    /// Creates new signal referring to the whole wire.
+    pub fn wire(name: String, width: usize) -> Self {
+        if width == 0 {
+            return Self::default();
+        }
+        Self { chunks: vec![SigChunk::Wire { name, width_wire: width, offset: 0, width }] }
+    }
+
+    /// Creates new constant signal.
+    pub fn constant(value: LogicValues) -> Self {
+        if value.is_empty() {
+            return Self::default();
+        }
+        Self { chunks: vec![SigChunk::Const(value)] }
+    }
+
+    /// Creates new signal of zeros.
+    pub fn zero(width: usize) -> Self { Self::constant(LogicValues::new(vec![LogicValue::False; width])) }
+
+    /// Concatenates the signals. The first signal is placed at the least significant bits.
+    pub fn concat(sigs: Vec<SigSpec>) -> Self { Self { chunks: sigs.into_iter().flat_map(|sig| sig.chunks).collect() } }
+
+    /// Returns the width of the signal.
+    pub fn width(&self) -> usize { self.chunks.iter().map(|chunk| chunk.width()).sum() }
+
+    /// Returns `width` bits of the signal starting from `offset`.
+    pub fn slice(&self, offset: usize, width: usize) -> Self {
+        assert!(offset + width <= self.width());
+        let mut chunks = Vec::new();
+        let mut base = 0;
+        for chunk in &self.chunks {
+            let start = ::std::cmp::max(base, offset);
+            let end = ::std::cmp::min(base + chunk.width(), offset + width);
+            if start < end {
+                chunks.push(chunk.slice(start - base, end - start));
+            }
+            base += chunk.width();
+        }
+        Self { chunks }
+    }
+
+    /// Repeats the signal `count` times.
+    pub fn repeat(&self, count: usize) -> Self { Self::concat(vec![self.clone(); count]) }
+}
+
+/// Wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wire {
+    /// Name of the wire
+    pub name: String,
+    /// Width of the wire
+    pub width: usize,
+    /// Direction and index of the port, or `None` for internal wires
+    pub port: Option<(Direction, usize)>,
+    /// Initial value, which is given to registers without reset
+    pub init: Option<LogicValues>,
+}
+
+impl ToString for Wire {
+    fn to_string(&self) -> String {
+        let init = match &self.init {
+            Some(init) => format!("attribute \\init {}\n", Const::Bits(init.clone()).to_string()),
+            None => "".to_string(),
+        };
+        let port = match self.port {
+            Some((Direction::Input, index)) => format!(" input {}", index),
+            Some((Direction::Output, index)) => format!(" output {}", index),
+            None => "".to_string(),
+        };
+        format!("{}wire width {}{} {}", init, self.width, port, self.name)
+    }
+}
+
+impl Wire {
+    /// Creates new internal wire.
+    #[inline]
+    pub fn new(name: String, width: usize) -> Self { Wire { name, width, port: None, init: None } }
+}
+
+/// Cell, which is an instance of a builtin cell type (e.g., `$add`) or a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    /// Type of the cell
+    pub typ: String,
+    /// Name of the cell
+    pub name: String,
+    /// Parameters
+    pub params: Vec<(String, Const)>,
+    /// Ports and their signals
+    pub connections: Vec<(String, SigSpec)>,
+}
+
+impl ToString for Cell {
+    fn to_string(&self) -> String {
+        let body = ::std::iter::empty()
+            .chain(self.params.iter().map(|(name, value)| format!("parameter {} {}", name, value.to_string())))
+            .chain(self.connections.iter().map(|(port, sig)| format!("connect {} {}", port, sig.to_string())))
+            .collect::<Vec<_>>();
+
+        if body.is_empty() {
+            format!("cell {} {}\nend", self.typ, self.name)
+        } else {
+            format!("cell {} {}\n{}\nend", self.typ, self.name, indent(body.join("\n"), INDENT))
+        }
+    }
+}
+
+/// Statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// Cell.
+    Cell(Cell),
+    /// Connection of signals of the same width.
+    Connect {
+        /// Driven signal
+        lhs: SigSpec,
+        /// Driving signal
+        rhs: SigSpec,
+    },
+    /// Block of statements.
+    Block {
+        /// Statements
+        stmts: Vec<Statement>,
+    },
+    /// Statement with comment.
+    Commented {
+        /// Comment
+        comment: String,
+        /// Statement
+        stmt: Box<Statement>,
+    },
+}
+
+impl ToString for Statement {
+    fn to_string(&self) -> String {
+        match self {
+            Statement::Cell(cell) => cell.to_string(),
+            Statement::Connect { lhs, rhs } => format!("connect {} {}", lhs.to_string(), rhs.to_string()),
+            Statement::Block { stmts } => {
+                stmts.iter().map(|stmt| stmt.to_string()).filter(|stmt| !stmt.is_empty()).collect::<Vec<_>>().join("\n")
+            }
+            Statement::Commented { comment, stmt } => {
+                let comment = comment.lines().map(|line| format!("# {}", line)).collect::<Vec<_>>().join("\n");
+                format!("{}\n{}", comment, stmt.to_string())
+            }
+        }
+    }
+}
+
+impl Statement {
+    /// Creates new block statement.
+    #[inline]
+    pub fn block(stmts: Vec<Statement>) -> Self { Statement::Block { stmts } }
+}
+
+/// Module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    /// Name of the module
+    pub name: String,
+    /// Wires of the module, including the ports
+    pub wires: Vec<Wire>,
+    /// Body of the module
+    pub body: Statement,
+}
+
+impl ToString for Module {
+    fn to_string(&self) -> String {
+        let body = ::std::iter::empty()
+            .chain(self.wires.iter().map(|wire| wire.to_string()))
+            .chain(::std::iter::once(self.body.to_string()))
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+
+        if body.is_empty() {
+            format!("module {}\nend", self.name)
+        } else {
+            format!("module {}\n{}\nend", self.name, indent(body.join("\n"), INDENT))
+        }
+    }
+}
+
+/// Design, which consists of modules.
+///
+/// Modules defined outside of the design (e.g., Verilog modules) are referred by the cells as
+/// they are, and should be provided to Yosys along with the design.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Design {
+    /// Modules, where instantiated modules precede the modules instantiating them
+    pub modules: Vec<Module>,
+}
+
+impl ToString for Design {
+    fn to_string(&self) -> String {
+        self.modules.iter().map(|module| module.to_string()).collect::<Vec<_>>().join("\n\n")
+    }
+}
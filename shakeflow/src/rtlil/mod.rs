@@ -0,0 +1,5 @@
+//! Yosys RTLIL.
+
+mod ir;
+
+pub use ir::*;
@@ -0,0 +1,1141 @@
+//! Generates Yosys RTLIL code.
+//!
+//! # Note
+//!
+//! Signals are flattened into wires, and loops in expressions and arrays of modules are unrolled as
+//! in FIRRTL. Operators are `$`-cells of Yosys (e.g., `$add`, `$mux`), and state registers are
+//! `$dff`, `$sdff` or `$adff` cells by the reset kind. Names of the cells and wires introduced by
+//! the generator are prefixed by `$`, so they do not conflict with the names of signals.
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::panic::Location;
+use std::path::Path;
+
+use hashcons::merkle::Merkle;
+use itertools::izip;
+
+use crate::codegen::*;
+use crate::rtlil::*;
+use crate::*;
+
+impl Package {
+    /// Generates RTLIL design of the top-level module, which contains the modules it instantiates.
+    /// Modules instantiated by FFIs are referred by their names and parameters.
+    fn gen_rtlil_design(&self, name: String, module: &lir::Module) -> Result<Design, PackageError> {
+        let module_insts = scan_module_insts(module);
+
+        let mut design = Design { modules: Vec::new() };
+
+        for (name, module_inst) in module_insts {
+            if let Some(submodule) = &module_inst.module {
+                design.modules.push(
+                    gen_module::<Rtlilgen>(name, submodule, self.reset_style)
+                        .map_err(|error| PackageError::Module { error })?
+                        .into(),
+                );
+            }
+        }
+
+        design.modules.push(
+            gen_module::<Rtlilgen>(name, module, self.reset_style)
+                .map_err(|error| PackageError::Module { error })?
+                .into(),
+        );
+
+        Ok(design)
+    }
+
+    /// Generates RTLIL code at the given directory path.
+    ///
+    /// Each top-level module is generated in a file with the modules it instantiates, as is each
+    /// specialization of a parameterized module, named after its parameter values.
+    pub fn gen_rtlil<P: AsRef<Path>>(mut self, path_dir: P) -> Result<(), PackageError> {
+        fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
+
+        let (_, top_modules) = self.scan_modules()?;
+
+        let top_modules = top_modules.iter().map(|module| (module.get_module_name(), module));
+        let specializations = self.param_modules.iter().flat_map(|module| module.named_specializations());
+
+        for (name, module) in top_modules.chain(specializations) {
+            let path = path_dir.as_ref().join(format!("{}_inner.il", name));
+            let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+
+            let design = self.gen_rtlil_design(name, module)?;
+
+            writeln!(file, "{}", design.to_string()).map_err(|error| PackageError::Fs { error })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<codegen::Module<Rtlilgen>> for rtlil::Module {
+    fn from(module: codegen::Module<Rtlilgen>) -> Self {
+        let mut wires = module.ports;
+        collect_wires(&module.body, &mut wires);
+
+        let widths = wires.iter().map(|wire| (wire.name.clone(), wire.width)).collect();
+        let body = lower_body(module.body, &widths);
+
+        rtlil::Module { name: gen_public(module.name), wires, body }
+    }
+}
+
+/// Module body, whose wires are declared ahead of the statements by `collect_wires`.
+#[derive(Debug, Clone)]
+pub enum Body {
+    /// Statement.
+    Stmt(Statement),
+
+    /// Wire declaration.
+    Wire(Wire),
+
+    /// Connection of wires or output ports, where ranges are the offset and width of the sliced
+    /// signals. Widths of the signals are resolved by `lower_body`.
+    Connect {
+        /// L-value
+        lvalue: String,
+        /// Range of l-value
+        lvalue_range: Option<(usize, usize)>,
+        /// R-value
+        rvalue: String,
+        /// Range of r-value
+        rvalue_range: Option<(usize, usize)>,
+    },
+
+    /// Block of bodies.
+    Block(Vec<Body>),
+
+    /// Body with comment.
+    Commented {
+        /// Comment
+        comment: String,
+        /// Body
+        body: Box<Body>,
+    },
+}
+
+/// RTLIL Generator
+#[derive(Default, Debug)]
+pub struct Rtlilgen;
+
+impl Codegen for Rtlilgen {
+    type Body = Body;
+    type Ports = Vec<Wire>;
+
+    fn gen_port_decls(&self, module: &lir::Module, ctx: &Context) -> Result<Vec<Wire>, lir::ModuleError> {
+        Ok(gen_port_decls(module, ctx)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, (direction, width, name))| Wire {
+                name: gen_public(name),
+                width,
+                port: Some((direction, index + 1)),
+                init: None,
+            })
+            .collect())
+    }
+
+    fn gen_module_composite(&self, module: &lir::CompositeModule, ctx: &mut Context) -> Result<Body, lir::ModuleError> {
+        let clock_domain = ctx.enter_clock_domain(module.clock_domain.clone());
+        let mut bodies = vec![];
+
+        match module.module_typ {
+            lir::CompositeModuleTyp::OneToOne => {
+                for (name, shape) in gen_submodule_wires(module, ctx)? {
+                    bodies.push(Body::Wire(Wire::new(gen_public(name), shape.width())));
+                }
+
+                bodies.append(&mut self.gen_module_wiring(module, ctx.get_prefix())?);
+                bodies.append(&mut self.gen_submodules(module, ctx)?);
+            }
+            lir::CompositeModuleTyp::NToN(n) => {
+                let genvar_id = ctx.alloc_genvar_id();
+
+                let mut module = module.clone();
+                module.module_typ = lir::CompositeModuleTyp::OneToOne;
+
+                // Arrays of modules are unrolled. Signals in each iteration are prefixed by the
+                // genvar and the iteration index.
+                for i in 0..n {
+                    let prefix = ctx.get_prefix();
+                    ctx.enter_scope(format!("{}_{}", genvar_id, i));
+
+                    for (name, shape) in gen_submodule_wires(&module, ctx)? {
+                        bodies.push(Body::Wire(Wire::new(gen_public(name), shape.width())));
+                    }
+
+                    bodies.append(&mut self.gen_module_wiring_array(&module, prefix, ctx.get_prefix(), i)?);
+                    bodies.append(&mut self.gen_submodules(&module, ctx)?);
+
+                    ctx.leave_scope();
+                }
+            }
+        }
+
+        ctx.leave_clock_domain(clock_domain);
+        Ok(gen_commented(module.source, Body::Block(bodies)))
+    }
+
+    /// Generates target code for FSM.
+    ///
+    /// # Note
+    ///
+    /// Each field of the state is the output of a register cell named after the field with suffix
+    /// `_reg`. Registers without reset have the initial values as `init` attributes.
+    fn gen_module_fsm(&self, module: &lir::Fsm, ctx: &mut Context) -> Result<Body, lir::ModuleError> {
+        let mut bodies = Vec::new();
+
+        let state_init = gen_module_fsm_state_init(&module.state.into_expr(), &module.init.into_expr(), ctx)?;
+        let reset_kind = ctx.reset_kind(module);
+
+        // (1) state registers
+        let mut inits = HashMap::new();
+        // Zero-width fields of the state are not declared.
+        for (shape, net_name, init_value) in state_init.into_iter().filter(|(shape, ..)| shape.width() > 0) {
+            bodies.push(Body::Wire(Wire {
+                name: gen_public(net_name.clone()),
+                width: shape.width(),
+                port: None,
+                init: (reset_kind == lir::ResetKind::None).then(|| init_value.clone()),
+            }));
+            let _ = inits.insert(net_name, init_value);
+        }
+
+        // (2) input, output logic
+        bodies.push(self.gen_module_fsm_output("out".to_string(), module.output_fwd.into_expr(), ctx)?);
+        bodies.push(self.gen_module_fsm_output("in".to_string(), module.input_bwd.into_expr(), ctx)?);
+
+        // (3) state update logic
+        bodies.push(self.gen_module_fsm_state("st".to_string(), module.state.into_expr(), inits, reset_kind, ctx)?);
+
+        Ok(gen_commented(module.source, Body::Block(bodies)))
+    }
+
+    fn gen_module_inst(&self, module: &lir::ModuleInst, ctx: &mut Context) -> Result<Body, lir::ModuleError> {
+        let inst_name = join_options("_", [ctx.get_prefix(), Some(module.inst_name.clone())]).unwrap();
+
+        let mut bodies = Vec::new();
+        let connections = gen_connections(module, ctx)?
+            .into_iter()
+            .map(|(_, port, width, expr, inverted)| {
+                let sig = SigSpec::wire(gen_public(expr), width);
+                let sig = if inverted {
+                    let params = vec![
+                        ("A_SIGNED", Const::Int(0)),
+                        ("A_WIDTH", Const::Int(width)),
+                        ("Y_WIDTH", Const::Int(width)),
+                    ];
+                    gen_cell(&mut bodies, ctx, "$not", params, vec![("A", sig)], width)
+                } else {
+                    sig
+                };
+                (gen_public(port), sig)
+            })
+            .collect();
+
+        // Parameterized modules are specialized, except for the external modules.
+        let params = if module.module.is_some() {
+            Vec::new()
+        } else {
+            module.params.iter().map(|(name, value)| (gen_public(name.clone()), Const::Int(*value))).collect()
+        };
+
+        let cell =
+            Cell { typ: gen_public(gen_module_inst_name(module)), name: gen_public(inst_name), params, connections };
+
+        bodies.push(Body::Stmt(Statement::Cell(cell)));
+        Ok(gen_commented(module.source, Body::Block(bodies)))
+    }
+
+    fn gen_module_virtual(
+        &self, module: &lir::VirtualModule, composite_context_prefix: Option<String>, ctx: &mut Context,
+    ) -> Result<Body, lir::ModuleError> {
+        let conts = gen_virtual_wirings(module, composite_context_prefix, ctx.get_prefix())?
+            .into_iter()
+            .map(|(lvalue, lvalue_range, rvalue, rvalue_range)| {
+                gen_connect(
+                    lvalue,
+                    lvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                    rvalue,
+                    rvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                )
+            })
+            .collect();
+
+        Ok(Body::Block(conts))
+    }
+}
+
+impl Rtlilgen {
+    /// Generates RTLIL code for registered modules and submodules in the module.
+    fn gen_submodules(&self, module: &lir::CompositeModule, ctx: &mut Context) -> Result<Vec<Body>, lir::ModuleError> {
+        let mut bodies = vec![];
+
+        for (index, submodule) in module.registered_modules.iter().enumerate() {
+            let comp_name = submodule.get_module_name();
+            ctx.enter_scope(format!("registered_{}_{}", comp_name, index));
+            match &*submodule.inner {
+                lir::ModuleInner::ModuleInst(module) => {
+                    bodies.push(self.gen_module_inst(module, ctx)?);
+                }
+                lir::ModuleInner::Composite(_, module) => {
+                    bodies.push(self.gen_module_composite(module, ctx)?);
+                }
+                _ => {
+                    return Err(lir::ModuleError::RegisteredModule {
+                        module: module.name.clone(),
+                        submodule: comp_name,
+                    })
+                }
+            }
+            ctx.leave_scope();
+        }
+
+        let composite_context_prefix = ctx.get_prefix();
+
+        for (index, (submodule, _)) in module.submodules.iter().enumerate() {
+            let comp_name = submodule.get_module_name();
+            ctx.enter_scope(format!("{}_{}", comp_name, index));
+            match &*submodule.inner {
+                lir::ModuleInner::Composite(_, module) => {
+                    bodies.push(self.gen_module_composite(module, ctx)?);
+                }
+                lir::ModuleInner::Fsm(module) => {
+                    bodies.push(self.gen_module_fsm(module, ctx)?);
+                }
+                lir::ModuleInner::ModuleInst(module) => {
+                    bodies.push(self.gen_module_inst(module, ctx)?);
+                }
+                lir::ModuleInner::VirtualModule(module) => {
+                    bodies.push(self.gen_module_virtual(module, composite_context_prefix.clone(), ctx)?);
+                }
+            }
+            ctx.leave_scope();
+        }
+
+        Ok(bodies)
+    }
+
+    /// Generates RTLIL code for wirings in the module.
+    fn gen_module_wiring(
+        &self, module: &lir::CompositeModule, prefix: Option<String>,
+    ) -> Result<Vec<Body>, lir::ModuleError> {
+        Ok(gen_wiring(module, prefix)?
+            .into_iter()
+            .map(|(lvalue, lvalue_range, rvalue, rvalue_range)| {
+                gen_connect(
+                    lvalue,
+                    lvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                    rvalue,
+                    rvalue_range.map(|(index, elt_size)| (index * elt_size, elt_size)),
+                )
+            })
+            .collect())
+    }
+
+    /// Generates RTLIL code for wirings in the `iteration`-th element of the array module.
+    ///
+    /// Signals of the array module are prefixed by `prefix`, and signals in the iteration are
+    /// prefixed by `iteration_prefix`.
+    fn gen_module_wiring_array(
+        &self, module: &lir::CompositeModule, prefix: Option<String>, iteration_prefix: Option<String>,
+        iteration: usize,
+    ) -> Result<Vec<Body>, lir::ModuleError> {
+        let range = |generate: Option<usize>, range: Option<(usize, usize)>| match (generate, range) {
+            (Some(gen_size), Some((index, elt_size))) => Some((iteration * gen_size + index * elt_size, elt_size)),
+            (Some(gen_size), None) => Some((iteration * gen_size, gen_size)),
+            (None, Some((index, elt_size))) => Some((index * elt_size, elt_size)),
+            (None, None) => None,
+        };
+
+        Ok(izip!(gen_wiring_array(module, prefix)?, gen_wiring_array(module, iteration_prefix)?)
+            .map(
+                |(
+                    (lvalue, lvalue_generate, lvalue_range, rvalue, rvalue_generate, rvalue_range),
+                    (lvalue_inner, _, _, rvalue_inner, ..),
+                )| {
+                    gen_connect(
+                        if lvalue_generate.is_some() { lvalue } else { lvalue_inner },
+                        range(lvalue_generate, lvalue_range),
+                        if rvalue_generate.is_some() { rvalue } else { rvalue_inner },
+                        range(rvalue_generate, rvalue_range),
+                    )
+                },
+            )
+            .collect())
+    }
+
+    /// Generates FSM output.
+    ///
+    /// Returns `Err` if types of `typ` and `output` are mismatched.
+    fn gen_module_fsm_output(
+        &self, target: String, output: Merkle<lir::Expr>, ctx: &mut Context,
+    ) -> Result<Body, lir::ModuleError> {
+        let mut stmts = Vec::new();
+        let exprs = self.gen_expr(&output, ctx, &mut HashMap::new(), &mut stmts)?;
+
+        let assignments = match_value_typ_exprs(
+            join_options("_", [ctx.get_prefix(), Some(target)]),
+            flatten_typ(output.port_decls()),
+            exprs,
+        )
+        .ok_or_else(|| ctx.expr_structure_error(&output.port_decls()))?;
+
+        for (shape, var_name, sig) in assignments {
+            stmts.push(gen_assign(SigSpec::wire(gen_public(var_name), shape.width()), sig));
+        }
+
+        Ok(Body::Block(stmts))
+    }
+
+    /// Generates FSM state, whose fields are the outputs of the registers. `inits` are the initial
+    /// values of the fields.
+    ///
+    /// Returns `Err` if types of `typ` and `state` are mismatched.
+    fn gen_module_fsm_state(
+        &self, target: String, state: Merkle<lir::Expr>, mut inits: HashMap<String, LogicValues>,
+        reset_kind: lir::ResetKind, ctx: &mut Context,
+    ) -> Result<Body, lir::ModuleError> {
+        let mut stmts = Vec::new();
+        let exprs = self.gen_expr(&state, ctx, &mut HashMap::new(), &mut stmts)?;
+
+        let assignments = match_value_typ_exprs(
+            join_options("_", [ctx.get_prefix(), Some(target)]),
+            flatten_typ(state.port_decls()),
+            exprs,
+        )
+        .ok_or_else(|| ctx.expr_structure_error(&state.port_decls()))?;
+
+        for (_, var_name, sig) in assignments {
+            if let Some(init) = inits.remove(&var_name) {
+                stmts.push(Body::Stmt(Statement::Cell(gen_register(var_name, sig, init, reset_kind, ctx))));
+            }
+        }
+
+        Ok(Body::Block(stmts))
+    }
+
+    /// Generates cells for Expr, which are appended to `stmts`. Returns the signals of the fields of
+    /// the expr.
+    ///
+    /// Signals are cached, so each expr is generated only once.
+    fn gen_expr(
+        &self, expr: &lir::Expr, ctx: &mut Context, cache: &mut HashMap<lir::Expr, CompositeExpr<SigSpec>>,
+        stmts: &mut Vec<Body>,
+    ) -> Result<CompositeExpr<SigSpec>, lir::ModuleError> {
+        if let Some(sigs) = cache.get(expr) {
+            return Ok(sigs.clone());
+        }
+
+        let sigs = match expr {
+            lir::Expr::X { .. } | lir::Expr::Constant { .. } => gen_expr_literal(expr, ctx)?.map(SigSpec::constant),
+            lir::Expr::Repeat { inner, count } => {
+                self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?.map(|sig| sig.repeat(*count))
+            }
+            lir::Expr::Input { name, .. } => {
+                CompositeExpr::from_typ(expr.port_decls(), join_options("_", [ctx.get_prefix(), name.clone()]).unwrap())
+                    .map(|(ident, shape)| SigSpec::wire(gen_public(ident), shape.width()))
+            }
+            lir::Expr::Member { inner, index } => match self.gen_expr(&inner.into_expr(), ctx, cache, stmts)? {
+                CompositeExpr::Struct(inner) => inner[*index].clone(),
+                CompositeExpr::Bits(_) => return Err(ctx.expr_structure_error(&inner.into_expr().port_decls())),
+            },
+            lir::Expr::Struct { inner } => CompositeExpr::Struct(
+                inner
+                    .iter()
+                    .map(|(_, inner)| self.gen_expr(&inner.into_expr(), ctx, cache, stmts))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            lir::Expr::Resize { inner, .. } => {
+                let typ_inner = inner.into_expr().port_decls();
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let shapes = CompositeExpr::from(typ_inner.clone()).try_zip(
+                    CompositeExpr::from(expr.port_decls()),
+                    &typ_inner,
+                    ctx,
+                )?;
+                sigs_for_inner.try_zip(shapes, &typ_inner, ctx)?.map(|(sig, (_, (_, to)))| gen_resize(sig, to.width()))
+            }
+            lir::Expr::LeftShift { inner, rhs } => self.gen_expr_binary_op(
+                lir::BinaryOp::ShiftLeft,
+                &inner.into_expr(),
+                &rhs.into_expr(),
+                ctx,
+                cache,
+                stmts,
+            )?,
+            lir::Expr::RightShift { inner, rhs } => self.gen_expr_binary_op(
+                lir::BinaryOp::ShiftRight,
+                &inner.into_expr(),
+                &rhs.into_expr(),
+                ctx,
+                cache,
+                stmts,
+            )?,
+            lir::Expr::Not { inner } => {
+                let sig = self
+                    .gen_expr(&inner.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&inner.into_expr().port_decls(), ctx)?;
+                let width = sig.width();
+                CompositeExpr::Bits(gen_cell(
+                    stmts,
+                    ctx,
+                    "$not",
+                    vec![("A_SIGNED", Const::Int(0)), ("A_WIDTH", Const::Int(width)), ("Y_WIDTH", Const::Int(width))],
+                    vec![("A", sig)],
+                    width,
+                ))
+            }
+            lir::Expr::BinaryOp { op, lhs, rhs } => {
+                self.gen_expr_binary_op(*op, &lhs.into_expr(), &rhs.into_expr(), ctx, cache, stmts)?
+            }
+            lir::Expr::Fold { inner, typ_elt, func, init, acc, inner_slice } => {
+                let sigs_for_init = self.gen_expr(&init.into_expr(), ctx, cache, stmts)?;
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let loop_count = if typ_elt.width() == 0 { 0 } else { inner.into_expr().width() / typ_elt.width() };
+                let mut sigs_for_acc = sigs_for_init;
+
+                // The loop is unrolled. Each iteration binds the accumulator and the element to the
+                // inputs of `func`.
+                for i in 0..loop_count {
+                    let fold_body_input_prefix = ctx.alloc_temp_id();
+
+                    let mut ctx = Context::new();
+                    ctx.enter_scope(fold_body_input_prefix);
+                    let mut cache = HashMap::new();
+
+                    let idents_for_acc = self.gen_expr(&acc.into_expr(), &mut ctx, &mut cache, stmts)?;
+                    let idents_for_inner_slice =
+                        self.gen_expr(&inner_slice.into_expr(), &mut ctx, &mut cache, stmts)?;
+
+                    let sigs_for_elt = element_sigs(sigs_for_inner.clone(), i, typ_elt, &ctx)?;
+                    bind_sigs(idents_for_acc, sigs_for_acc, &acc.into_expr().port_decls(), &ctx, stmts)?;
+                    bind_sigs(idents_for_inner_slice, sigs_for_elt, typ_elt, &ctx, stmts)?;
+
+                    sigs_for_acc = self.gen_expr(&func.into_expr(), &mut ctx, &mut cache, stmts)?;
+                }
+
+                sigs_for_acc
+            }
+            lir::Expr::TreeFold { inner, op, lhs, rhs, .. } => {
+                let typ_elt = lhs.into_expr().port_decls();
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let num_elts = if typ_elt.width() == 0 { 0 } else { inner.into_expr().width() / typ_elt.width() };
+                let mut sigs_for_elts = (0..num_elts)
+                    .map(|i| element_sigs(sigs_for_inner.clone(), i, &typ_elt, ctx))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Levels of the tree are unrolled. An element without its pair is carried to the
+                // next level.
+                while sigs_for_elts.len() > 1 {
+                    let mut sigs_for_level = Vec::new();
+
+                    for pair in sigs_for_elts.chunks(2) {
+                        let (sigs_for_lhs, sigs_for_rhs) = match pair {
+                            [sigs_for_lhs, sigs_for_rhs] => (sigs_for_lhs.clone(), sigs_for_rhs.clone()),
+                            _ => {
+                                sigs_for_level.push(pair[0].clone());
+                                continue;
+                            }
+                        };
+
+                        let tree_fold_prefix = ctx.alloc_temp_id();
+
+                        let mut ctx = Context::new();
+                        ctx.enter_scope(tree_fold_prefix);
+                        let mut cache = HashMap::new();
+
+                        let idents_for_lhs = self.gen_expr(&lhs.into_expr(), &mut ctx, &mut cache, stmts)?;
+                        let idents_for_rhs = self.gen_expr(&rhs.into_expr(), &mut ctx, &mut cache, stmts)?;
+
+                        bind_sigs(idents_for_lhs, sigs_for_lhs, &typ_elt, &ctx, stmts)?;
+                        bind_sigs(idents_for_rhs, sigs_for_rhs, &typ_elt, &ctx, stmts)?;
+
+                        sigs_for_level.push(self.gen_expr(&op.into_expr(), &mut ctx, &mut cache, stmts)?);
+                    }
+
+                    sigs_for_elts = sigs_for_level;
+                }
+
+                match sigs_for_elts.pop() {
+                    Some(sigs) => sigs,
+                    None => CompositeExpr::from(typ_elt).map(|(_, shape)| SigSpec::zero(shape.width())),
+                }
+            }
+            lir::Expr::Map { inner, typ_elt, func } => {
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+
+                let loop_count = if typ_elt.width() == 0 { 0 } else { inner.into_expr().width() / typ_elt.width() };
+                let mut sigs_for_output = Vec::new();
+
+                for i in 0..loop_count {
+                    let loop_body_input_prefix = ctx.alloc_temp_id();
+
+                    let idents = CompositeExpr::from_typ(typ_elt.clone(), loop_body_input_prefix.clone())
+                        .map(|(ident, shape)| SigSpec::wire(gen_public(ident), shape.width()));
+                    let sigs_for_elt = element_sigs(sigs_for_inner.clone(), i, typ_elt, ctx)?;
+                    bind_sigs(idents, sigs_for_elt, typ_elt, ctx, stmts)?;
+
+                    let mut ctx = Context::new();
+                    ctx.enter_scope(loop_body_input_prefix);
+
+                    sigs_for_output.push(self.gen_expr(&func.into_expr(), &mut ctx, &mut HashMap::new(), stmts)?);
+                }
+
+                concat_sigs(sigs_for_output, &func.into_expr().port_decls(), ctx)?
+            }
+            lir::Expr::Get { inner, typ_elt, index } | lir::Expr::GetVarArray { inner, typ_elt, index } => {
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let sig_for_index = self
+                    .gen_expr(&index.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&index.into_expr().port_decls(), ctx)?;
+
+                range_indexing_sigs(sigs_for_inner, sig_for_index, 1, typ_elt, ctx, stmts)?
+            }
+            lir::Expr::Clip { inner, typ_elt, from, size } => {
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let sig_for_from = self
+                    .gen_expr(&from.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&from.into_expr().port_decls(), ctx)?;
+
+                range_indexing_sigs(sigs_for_inner, sig_for_from, *size, typ_elt, ctx, stmts)?
+            }
+            lir::Expr::Append { lhs, rhs, .. } => {
+                let sigs_for_lhs = self.gen_expr(&lhs.into_expr(), ctx, cache, stmts)?;
+                let sigs_for_rhs = self.gen_expr(&rhs.into_expr(), ctx, cache, stmts)?;
+
+                sigs_for_lhs
+                    .try_zip(sigs_for_rhs, &lhs.into_expr().port_decls(), ctx)?
+                    .map(|(lhs, rhs)| SigSpec::concat(vec![lhs, rhs]))
+            }
+            lir::Expr::Zip { inner, .. } => CompositeExpr::Struct(
+                inner
+                    .iter()
+                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache, stmts))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            lir::Expr::Concat { inner, .. } => self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?,
+            lir::Expr::Chunk { inner, .. } => self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?,
+            lir::Expr::Repr { inner } => self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?,
+            lir::Expr::Sum { inner, width_elt } => {
+                let sig_for_inner = self
+                    .gen_expr(&inner.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&inner.into_expr().port_decls(), ctx)?;
+
+                let mut sig_for_acc = SigSpec::zero(*width_elt);
+
+                if *width_elt > 0 {
+                    for i in 0..(inner.into_expr().width() / width_elt) {
+                        let sig_for_elt = sig_for_inner.slice(i * width_elt, *width_elt);
+                        sig_for_acc = gen_binary(stmts, ctx, "$add", sig_for_acc, sig_for_elt, *width_elt);
+                    }
+                }
+
+                CompositeExpr::Bits(sig_for_acc)
+            }
+            lir::Expr::Cond { cond, lhs, rhs } => {
+                let sig_for_cond = self
+                    .gen_expr(&cond.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&cond.into_expr().port_decls(), ctx)?;
+                let sigs_for_lhs = self.gen_expr(&lhs.into_expr(), ctx, cache, stmts)?;
+                let sigs_for_rhs = self.gen_expr(&rhs.into_expr(), ctx, cache, stmts)?;
+
+                sigs_for_lhs
+                    .try_zip(sigs_for_rhs, &lhs.into_expr().port_decls(), ctx)?
+                    .map(|(lhs, rhs)| gen_mux(stmts, ctx, sig_for_cond.clone(), lhs, rhs))
+            }
+            lir::Expr::Set { inner, index, elt } | lir::Expr::SetVarArray { inner, index, elt } => {
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let sig_for_index = self
+                    .gen_expr(&index.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&index.into_expr().port_decls(), ctx)?;
+                let sigs_for_elt = self.gen_expr(&elt.into_expr(), ctx, cache, stmts)?;
+
+                set_range_sigs(
+                    sigs_for_inner,
+                    inner.into_expr().width(),
+                    sig_for_index,
+                    sigs_for_elt,
+                    1,
+                    &elt.into_expr().port_decls(),
+                    ctx,
+                    stmts,
+                )?
+            }
+            lir::Expr::SetRange { inner, typ_elt, index, elts } => {
+                let sigs_for_inner = self.gen_expr(&inner.into_expr(), ctx, cache, stmts)?;
+                let sig_for_index = self
+                    .gen_expr(&index.into_expr(), ctx, cache, stmts)?
+                    .try_into_expr(&index.into_expr().port_decls(), ctx)?;
+                let sigs_for_elts = self.gen_expr(&elts.into_expr(), ctx, cache, stmts)?;
+
+                set_range_sigs(
+                    sigs_for_inner,
+                    inner.into_expr().width(),
+                    sig_for_index,
+                    sigs_for_elts,
+                    if typ_elt.width() == 0 { 0 } else { elts.into_expr().width() / typ_elt.width() },
+                    typ_elt,
+                    ctx,
+                    stmts,
+                )?
+            }
+            lir::Expr::Case { case_expr, case_items, default } => {
+                let typ = expr.port_decls();
+                let typ_case_expr = case_expr.into_expr().port_decls();
+
+                let sig_for_case_expr =
+                    self.gen_expr(&case_expr.into_expr(), ctx, cache, stmts)?.try_into_expr(&typ_case_expr, ctx)?;
+
+                // Case items are prioritized in order, so the mux chain is built from the last one.
+                let mut sigs_for_output = match default {
+                    Some(default) => self.gen_expr(&default.into_expr(), ctx, cache, stmts)?,
+                    None => CompositeExpr::from(typ.clone()).map(|(_, shape)| SigSpec::zero(shape.width())),
+                };
+
+                for (cond, item) in case_items.iter().rev() {
+                    let sig_for_cond =
+                        self.gen_expr(&cond.into_expr(), ctx, cache, stmts)?.try_into_expr(&typ_case_expr, ctx)?;
+                    let sigs_for_item = self.gen_expr(&item.into_expr(), ctx, cache, stmts)?;
+
+                    let sig_for_cond = gen_binary(stmts, ctx, "$eq", sig_for_case_expr.clone(), sig_for_cond, 1);
+                    sigs_for_output = sigs_for_item
+                        .try_zip(sigs_for_output, &typ, ctx)?
+                        .map(|(item, acc)| gen_mux(stmts, ctx, sig_for_cond.clone(), item, acc));
+                }
+
+                sigs_for_output
+            }
+            // Signedness is not modeled, so `$signed` is not supported either.
+            lir::Expr::Call { func_name, .. } => {
+                return Err(lir::ModuleError::Unsupported {
+                    module: ctx.scope_name(),
+                    what: format!("function call `{}`", func_name),
+                })
+            }
+            lir::Expr::ConcatArray { inner, elt_typ } => {
+                let sigs = inner
+                    .iter()
+                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache, stmts))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                concat_sigs(sigs, elt_typ, ctx)?
+            }
+        };
+
+        cache.insert(expr.clone(), sigs.clone());
+
+        Ok(sigs)
+    }
+
+    /// Generates binary operation.
+    ///
+    /// # Note
+    ///
+    /// Operands of Yosys cells are zero-extended to the width of the result, so the cells have the
+    /// widths of the operations in ShakeFlow.
+    fn gen_expr_binary_op(
+        &self, op: lir::BinaryOp, lhs: &lir::Expr, rhs: &lir::Expr, ctx: &mut Context,
+        cache: &mut HashMap<lir::Expr, CompositeExpr<SigSpec>>, stmts: &mut Vec<Body>,
+    ) -> Result<CompositeExpr<SigSpec>, lir::ModuleError> {
+        let sig_for_lhs = self.gen_expr(lhs, ctx, cache, stmts)?.try_into_expr(&lhs.port_decls(), ctx)?;
+        let sig_for_rhs = self.gen_expr(rhs, ctx, cache, stmts)?.try_into_expr(&rhs.port_decls(), ctx)?;
+
+        let (width_lhs, width_rhs) = (lhs.width(), rhs.width());
+        let (typ, width) = match op {
+            lir::BinaryOp::Add => ("$add", width_lhs + 1),
+            lir::BinaryOp::Sub => ("$sub", width_lhs),
+            lir::BinaryOp::Mul => ("$mul", width_lhs + width_rhs),
+            lir::BinaryOp::Div => ("$div", width_lhs),
+            lir::BinaryOp::Mod => ("$mod", width_rhs),
+            lir::BinaryOp::And => ("$and", width_lhs),
+            lir::BinaryOp::Or => ("$or", width_lhs),
+            lir::BinaryOp::Xor => ("$xor", width_lhs),
+            lir::BinaryOp::Eq => ("$xnor", width_lhs),
+            lir::BinaryOp::EqArithmetic => ("$eq", 1),
+            lir::BinaryOp::Less => ("$lt", 1),
+            lir::BinaryOp::Greater => ("$gt", 1),
+            lir::BinaryOp::LessEq => ("$le", 1),
+            lir::BinaryOp::GreaterEq => ("$ge", 1),
+            lir::BinaryOp::ShiftLeft => ("$shl", width_lhs),
+            lir::BinaryOp::ShiftRight => ("$shr", width_lhs),
+        };
+
+        Ok(CompositeExpr::Bits(gen_binary(stmts, ctx, typ, sig_for_lhs, sig_for_rhs, width)))
+    }
+}
+
+/// Returns the `index`-th element of `sigs`, whose elements are of type `typ_elt`.
+fn element_sigs(
+    sigs: CompositeExpr<SigSpec>, index: usize, typ_elt: &lir::PortDecls, ctx: &Context,
+) -> Result<CompositeExpr<SigSpec>, lir::ModuleError> {
+    let sigs = sigs.try_zip(typ_elt.clone().into(), typ_elt, ctx)?;
+    Ok(sigs.map(|(sig, (_, shape))| sig.slice(index * shape.width(), shape.width())))
+}
+
+/// Returns `count` elements of `sigs` from `index`, whose elements are of type `typ_elt`.
+///
+/// Elements are selected by `$shiftx` cells, whose bits out of range are undefined.
+fn range_indexing_sigs(
+    sigs: CompositeExpr<SigSpec>, index: SigSpec, count: usize, typ_elt: &lir::PortDecls, ctx: &mut Context,
+    stmts: &mut Vec<Body>,
+) -> Result<CompositeExpr<SigSpec>, lir::ModuleError> {
+    let sigs = sigs.try_zip(typ_elt.clone().into(), typ_elt, ctx)?;
+    Ok(sigs.map(|(sig, (_, shape))| {
+        let width = shape.width();
+        if width == 0 {
+            return SigSpec::default();
+        }
+
+        // The shift amount is wide enough not to overflow.
+        let amount = if width == 1 {
+            index.clone()
+        } else {
+            let width_amount = index.width() + (usize::BITS - width.leading_zeros()) as usize;
+            gen_binary(stmts, ctx, "$mul", index.clone(), gen_uint(width, width_amount), width_amount)
+        };
+
+        gen_binary(stmts, ctx, "$shiftx", sig, amount, width * count)
+    }))
+}
+
+/// Returns `sigs` whose `count` elements from `index` are replaced by `elts`. The elements are of
+/// type `typ_elt`, and `width` is the width of `sigs`.
+#[allow(clippy::too_many_arguments)]
+fn set_range_sigs(
+    sigs: CompositeExpr<SigSpec>, width: usize, index: SigSpec, elts: CompositeExpr<SigSpec>, count: usize,
+    typ_elt: &lir::PortDecls, ctx: &mut Context, stmts: &mut Vec<Body>,
+) -> Result<CompositeExpr<SigSpec>, lir::ModuleError> {
+    if typ_elt.width() == 0 {
+        return Ok(sigs);
+    }
+
+    let num_elts = width / typ_elt.width();
+
+    // Conditions that the index is equal to each position. Positions that the index cannot
+    // represent are never selected.
+    let conds = (0..num_elts)
+        .map(|i| {
+            (index.width() >= usize::BITS as usize || i >> index.width() == 0)
+                .then(|| gen_binary(stmts, ctx, "$eq", index.clone(), gen_uint(i, index.width()), 1))
+        })
+        .collect::<Vec<_>>();
+
+    let sigs = sigs.try_zip(elts, typ_elt, ctx)?.try_zip(typ_elt.clone().into(), typ_elt, ctx)?;
+    Ok(sigs.map(|((sig, elts), (_, shape))| {
+        let width = shape.width();
+        let sigs_for_elts = (0..num_elts)
+            .map(|i| {
+                (0..count.min(i + 1)).fold(sig.slice(i * width, width), |acc, j| match &conds[i - j] {
+                    Some(cond) => gen_mux(stmts, ctx, cond.clone(), elts.slice(j * width, width), acc),
+                    None => acc,
+                })
+            })
+            .collect();
+
+        SigSpec::concat(sigs_for_elts)
+    }))
+}
+
+/// Concatenates `sigs`, whose elements are of type `typ_elt`.
+fn concat_sigs(
+    sigs: Vec<CompositeExpr<SigSpec>>, typ_elt: &lir::PortDecls, ctx: &Context,
+) -> Result<CompositeExpr<SigSpec>, lir::ModuleError> {
+    let mut sigs = sigs.into_iter();
+
+    let sigs_for_fields = match sigs.next() {
+        Some(first) => sigs.try_fold(first.map(|sig| vec![sig]), |acc, sigs| {
+            Ok::<_, lir::ModuleError>(acc.try_zip(sigs, typ_elt, ctx)?.map(|(mut acc, sig)| {
+                acc.push(sig);
+                acc
+            }))
+        })?,
+        None => CompositeExpr::from(typ_elt.clone()).map(|_| Vec::new()),
+    };
+
+    Ok(sigs_for_fields.map(SigSpec::concat))
+}
+
+/// Binds `sigs` to the wires `idents`, e.g., inputs of the loop body, which are declared here.
+fn bind_sigs(
+    idents: CompositeExpr<SigSpec>, sigs: CompositeExpr<SigSpec>, typ: &lir::PortDecls, ctx: &Context,
+    stmts: &mut Vec<Body>,
+) -> Result<(), lir::ModuleError> {
+    for (ident, sig) in idents.try_zip(sigs, typ, ctx)?.iter() {
+        for chunk in &ident.chunks {
+            if let SigChunk::Wire { name, width_wire, .. } = chunk {
+                stmts.push(Body::Wire(Wire::new(name.clone(), *width_wire)));
+            }
+        }
+        stmts.push(gen_assign(ident, sig));
+    }
+
+    Ok(())
+}
+
+/// Appends the cell to `stmts`. Returns its output `Y` of the given width, which is named after
+/// the cell.
+fn gen_cell(
+    stmts: &mut Vec<Body>, ctx: &mut Context, typ: &str, params: Vec<(&str, Const)>, inputs: Vec<(&str, SigSpec)>,
+    width: usize,
+) -> SigSpec {
+    if width == 0 {
+        return SigSpec::default();
+    }
+
+    let name = format!("${}", ctx.alloc_temp_id());
+    let output = SigSpec::wire(format!("{}_Y", name), width);
+    stmts.push(Body::Wire(Wire::new(format!("{}_Y", name), width)));
+
+    let cell = Cell {
+        typ: typ.to_string(),
+        name,
+        params: params.into_iter().map(|(name, value)| (gen_public(name.to_string()), value)).collect(),
+        connections: inputs
+            .into_iter()
+            .chain(::std::iter::once(("Y", output.clone())))
+            .map(|(port, sig)| (gen_public(port.to_string()), sig))
+            .collect(),
+    };
+    stmts.push(Body::Stmt(Statement::Cell(cell)));
+
+    output
+}
+
+/// Generates binary cell of type `typ` whose operands are unsigned.
+fn gen_binary(
+    stmts: &mut Vec<Body>, ctx: &mut Context, typ: &str, lhs: SigSpec, rhs: SigSpec, width: usize,
+) -> SigSpec {
+    let params = vec![
+        ("A_SIGNED", Const::Int(0)),
+        ("B_SIGNED", Const::Int(0)),
+        ("A_WIDTH", Const::Int(lhs.width())),
+        ("B_WIDTH", Const::Int(rhs.width())),
+        ("Y_WIDTH", Const::Int(width)),
+    ];
+    gen_cell(stmts, ctx, typ, params, vec![("A", lhs), ("B", rhs)], width)
+}
+
+/// Generates multiplexer.
+fn gen_mux(stmts: &mut Vec<Body>, ctx: &mut Context, cond: SigSpec, tval: SigSpec, fval: SigSpec) -> SigSpec {
+    let width = tval.width();
+    gen_cell(stmts, ctx, "$mux", vec![("WIDTH", Const::Int(width))], vec![("A", fval), ("B", tval), ("S", cond)], width)
+}
+
+/// Generates register cell whose output is the wire `name`.
+fn gen_register(name: String, next: SigSpec, init: LogicValues, reset_kind: lir::ResetKind, ctx: &Context) -> Cell {
+    let (clk, rst) = ctx.clock_signals();
+    let width = next.width();
+    let polarity = match ctx.reset_style().polarity {
+        lir::ResetPolarity::ActiveHigh => 1,
+        lir::ResetPolarity::ActiveLow => 0,
+    };
+
+    let mut params = vec![("CLK_POLARITY", Const::Int(1))];
+    let mut connections = vec![("CLK", SigSpec::wire(gen_public(clk), 1))];
+    let typ = match reset_kind {
+        lir::ResetKind::None => "$dff",
+        lir::ResetKind::Sync => {
+            params.push(("SRST_POLARITY", Const::Int(polarity)));
+            params.push(("SRST_VALUE", Const::Bits(init)));
+            connections.push(("SRST", SigSpec::wire(gen_public(rst), 1)));
+            "$sdff"
+        }
+        lir::ResetKind::Async => {
+            params.push(("ARST_POLARITY", Const::Int(polarity)));
+            params.push(("ARST_VALUE", Const::Bits(init)));
+            connections.push(("ARST", SigSpec::wire(gen_public(rst), 1)));
+            "$adff"
+        }
+    };
+    params.push(("WIDTH", Const::Int(width)));
+    connections.push(("D", next));
+    connections.push(("Q", SigSpec::wire(gen_public(name.clone()), width)));
+
+    Cell {
+        typ: typ.to_string(),
+        name: gen_public(format!("{}_reg", name)),
+        params: params.into_iter().map(|(name, value)| (gen_public(name.to_string()), value)).collect(),
+        connections: connections.into_iter().map(|(port, sig)| (gen_public(port.to_string()), sig)).collect(),
+    }
+}
+
+/// Generates unsigned integer constant of the given width.
+fn gen_uint(value: usize, width: usize) -> SigSpec {
+    SigSpec::constant(LogicValues::new(
+        (0..width)
+            .rev()
+            .map(
+                |i| {
+                    if i < usize::BITS as usize && (value >> i) & 1 == 1 {
+                        LogicValue::True
+                    } else {
+                        LogicValue::False
+                    }
+                },
+            )
+            .collect(),
+    ))
+}
+
+/// Zero-extends or truncates `sig` to width `to`.
+fn gen_resize(sig: SigSpec, to: usize) -> SigSpec {
+    let from = sig.width();
+    if from < to {
+        SigSpec::concat(vec![sig, SigSpec::zero(to - from)])
+    } else {
+        sig.slice(0, to)
+    }
+}
+
+/// Generates connection of signals. Zero-width signals are not connected.
+fn gen_assign(lhs: SigSpec, rhs: SigSpec) -> Body {
+    if lhs.width() == 0 {
+        return Body::Block(Vec::new());
+    }
+
+    Body::Stmt(Statement::Connect { lhs, rhs })
+}
+
+/// Generates connection, where ranges are the offset and width of the sliced signals.
+fn gen_connect(
+    lvalue: String, lvalue_range: Option<(usize, usize)>, rvalue: String, rvalue_range: Option<(usize, usize)>,
+) -> Body {
+    Body::Connect { lvalue: gen_public(lvalue), lvalue_range, rvalue: gen_public(rvalue), rvalue_range }
+}
+
+/// Returns the public name, which is escaped by `\`.
+fn gen_public(name: String) -> String { format!("\\{}", name) }
+
+/// Collects wires declared in the body.
+fn collect_wires(body: &Body, wires: &mut Vec<Wire>) {
+    match body {
+        Body::Wire(wire) => wires.push(wire.clone()),
+        Body::Block(bodies) => bodies.iter().for_each(|body| collect_wires(body, wires)),
+        Body::Commented { body, .. } => collect_wires(body, wires),
+        Body::Stmt(_) | Body::Connect { .. } => {}
+    }
+}
+
+/// Lowers the body into statements, where `widths` are the widths of the wires. Connections
+/// whose signals are zero-width are dropped.
+fn lower_body(body: Body, widths: &HashMap<String, usize>) -> Statement {
+    match body {
+        Body::Stmt(stmt) => stmt,
+        Body::Wire(_) => Statement::block(Vec::new()),
+        Body::Connect { lvalue, lvalue_range, rvalue, rvalue_range } => {
+            let sig = |name: String, range: Option<(usize, usize)>| {
+                let sig = SigSpec::wire(name.clone(), widths.get(&name).copied().unwrap_or_default());
+                match range {
+                    Some((lo, width)) if lo + width <= sig.width() => sig.slice(lo, width),
+                    Some(_) => SigSpec::default(),
+                    None => sig,
+                }
+            };
+
+            let (lhs, rhs) = (sig(lvalue, lvalue_range), sig(rvalue, rvalue_range));
+            if lhs.width() == 0 || lhs.width() != rhs.width() {
+                return Statement::block(Vec::new());
+            }
+            Statement::Connect { lhs, rhs }
+        }
+        Body::Block(bodies) => Statement::block(bodies.into_iter().map(|body| lower_body(body, widths)).collect()),
+        Body::Commented { comment, body } => {
+            Statement::Commented { comment, stmt: Box::new(lower_body(*body, widths)) }
+        }
+    }
+}
+
+/// Annotates the body with the source location.
+fn gen_commented(source: Option<&Location<'_>>, body: Body) -> Body {
+    match source {
+        Some(source) => Body::Commented { comment: gen_source_annotation(source), body: Box::new(body) },
+        None => body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    /// Returns an FSM counting the cycles in which its input is set.
+    fn counter() -> Module<UniChannel<bool>, UniChannel<Bits<U<2>>>> {
+        hir::Fsm::<UniChannel<bool>, UniChannel<Bits<U<2>>>, Bits<U<2>>, _>::new(
+            "counter",
+            |fwd, bwd, state| (state, bwd, fwd.cond((state + 1.into()).resize(), state)),
+            0.into(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn fsm() {
+        let files = generate(package(counter()), |package, dir| package.gen_rtlil(dir)).unwrap();
+        let rtlil = files["counter_inner.il"]
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("# src = "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            rtlil,
+            r#"module \counter
+  wire width 1 input 1 \clk
+  wire width 1 input 2 \rst
+  wire width 1 input 3 \in
+  wire width 2 output 4 \out
+  wire width 2 \st
+  wire width 3 $t0_Y
+  wire width 2 $t1_Y
+  wire width 2 $t2_Y
+  connect \out \st
+  cell $add $t0
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 2
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 3
+    connect \A \st
+    connect \B 2'01
+    connect \Y $t0_Y
+  end
+  cell $shiftx $t1
+    parameter \A_SIGNED 0
+    parameter \B_SIGNED 0
+    parameter \A_WIDTH 3
+    parameter \B_WIDTH 2
+    parameter \Y_WIDTH 2
+    connect \A $t0_Y
+    connect \B 2'00
+    connect \Y $t1_Y
+  end
+  cell $mux $t2
+    parameter \WIDTH 2
+    connect \A \st
+    connect \B $t1_Y
+    connect \S \in
+    connect \Y $t2_Y
+  end
+  cell $sdff \st_reg
+    parameter \CLK_POLARITY 1
+    parameter \SRST_POLARITY 1
+    parameter \SRST_VALUE 2'00
+    parameter \WIDTH 2
+    connect \CLK \clk
+    connect \SRST \rst
+    connect \D $t2_Y
+    connect \Q \st
+  end
+end"#
+        );
+    }
+}
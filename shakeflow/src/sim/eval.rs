@@ -0,0 +1,245 @@
+//! Expression evaluator.
+
+use std::collections::HashMap;
+
+use super::SimError;
+use crate::lir::bits::*;
+use crate::lir::*;
+
+/// Evaluates exprs under given input bindings.
+///
+/// The evaluator is two-valued: don't-care values (`Expr::X`) evaluate to zeros.
+#[derive(Debug)]
+pub(super) struct Evaluator<'a> {
+    /// Values bound to `Expr::Input`s, by name.
+    env: &'a [(Option<&'a str>, &'a [bool])],
+
+    /// Values of already evaluated exprs.
+    cache: HashMap<ExprId, Vec<bool>>,
+}
+
+impl<'a> Evaluator<'a> {
+    /// Creates a new evaluator with the given input bindings.
+    pub(super) fn new(env: &'a [(Option<&'a str>, &'a [bool])]) -> Self { Self { env, cache: HashMap::new() } }
+
+    /// Evaluates the expr.
+    pub(super) fn eval(&mut self, id: ExprId) -> Result<Vec<bool>, SimError> {
+        if let Some(value) = self.cache.get(&id) {
+            return Ok(value.clone());
+        }
+
+        let value = self.eval_inner(&id.into_expr())?;
+        self.cache.insert(id, value.clone());
+        Ok(value)
+    }
+
+    fn eval_inner(&mut self, expr: &Expr) -> Result<Vec<bool>, SimError> {
+        let value = match expr {
+            Expr::X { typ } => vec![false; typ.width()],
+            Expr::Constant { bits, .. } => bits.clone(),
+            Expr::Repeat { inner, count } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.eval(*inner)?;
+                split_leaves(&value, &leaf_widths(&typ)).into_iter().flat_map(|leaf| leaf.repeat(*count)).collect()
+            }
+            Expr::Input { name, typ } => {
+                let (_, value) = self
+                    .env
+                    .iter()
+                    .find(|(input, _)| *input == name.as_deref())
+                    .ok_or_else(|| SimError::UnboundInput(name.clone()))?;
+                if value.len() != typ.width() {
+                    return Err(SimError::WidthMismatch(format!(
+                        "input {:?} has width {}, but {} is given",
+                        name,
+                        typ.width(),
+                        value.len()
+                    )));
+                }
+                value.to_vec()
+            }
+            Expr::Member { inner, index } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.eval(*inner)?;
+                match typ {
+                    PortDecls::Struct(members) => {
+                        let offset = members.iter().take(*index).map(|(_, member)| member.width()).sum::<usize>();
+                        value[offset..(offset + members[*index].1.width())].to_vec()
+                    }
+                    PortDecls::Bits(_) => panic!("Cannot index a `PortDecls::Bits`."),
+                }
+            }
+            Expr::Struct { inner } => {
+                let mut value = Vec::new();
+                for (_, member) in inner {
+                    value.extend(self.eval(*member)?);
+                }
+                value
+            }
+            Expr::Resize { inner, typ_elt, count } => {
+                let typ = inner.into_expr().port_decls();
+                let value = self.eval(*inner)?;
+                let widths = leaf_widths(&typ_elt.multiple(*count));
+                split_leaves(&value, &leaf_widths(&typ))
+                    .into_iter()
+                    .zip(widths)
+                    .flat_map(|(leaf, width)| resize(leaf, width))
+                    .collect()
+            }
+            Expr::LeftShift { inner, rhs } => self.eval_binary_op(BinaryOp::ShiftLeft, *inner, *rhs)?,
+            Expr::RightShift { inner, rhs } => self.eval_binary_op(BinaryOp::ShiftRight, *inner, *rhs)?,
+            Expr::Not { inner } => self.eval(*inner)?.into_iter().map(|b| !b).collect(),
+            Expr::BinaryOp { op, lhs, rhs } => self.eval_binary_op(*op, *lhs, *rhs)?,
+            Expr::Fold { inner, typ_elt, func, init, .. } => {
+                let value = self.eval(*inner)?;
+                let mut acc = self.eval(*init)?;
+                for index in 0..array_len(&value, typ_elt) {
+                    let inner_slice = get_elts(&value, typ_elt, index, 1);
+                    let next =
+                        Evaluator::new(&[(Some("acc"), &acc), (Some("inner_slice"), &inner_slice)]).eval(*func)?;
+                    acc = next;
+                }
+                acc
+            }
+            Expr::TreeFold { inner, op, lhs, .. } => {
+                let typ_elt = lhs.into_expr().port_decls();
+                let value = self.eval(*inner)?;
+                let mut elts = (0..array_len(&value, &typ_elt))
+                    .map(|index| get_elts(&value, &typ_elt, index, 1))
+                    .collect::<Vec<_>>();
+                while elts.len() > 1 {
+                    elts = elts
+                        .chunks(2)
+                        .map(|pair| match pair {
+                            [lhs, rhs] => Evaluator::new(&[(Some("lhs"), lhs), (Some("rhs"), rhs)]).eval(*op),
+                            [elt] => Ok(elt.clone()),
+                            _ => unreachable!(),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                elts.pop().unwrap_or_else(|| vec![false; typ_elt.width()])
+            }
+            Expr::Map { inner, typ_elt, func } => {
+                let typ_func = func.into_expr().port_decls();
+                let value = self.eval(*inner)?;
+                let elts = (0..array_len(&value, typ_elt))
+                    .map(|index| {
+                        let elt = get_elts(&value, typ_elt, index, 1);
+                        Evaluator::new(&[(None, &elt)]).eval(*func)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                join_elts(&elts, &typ_func)
+            }
+            Expr::Get { inner, typ_elt, index } | Expr::GetVarArray { inner, typ_elt, index } => {
+                let value = self.eval(*inner)?;
+                match to_usize(&self.eval(*index)?) {
+                    Some(index) => get_elts(&value, typ_elt, index, 1),
+                    None => vec![false; typ_elt.width()],
+                }
+            }
+            Expr::Clip { inner, typ_elt, from, size } => {
+                let value = self.eval(*inner)?;
+                match to_usize(&self.eval(*from)?) {
+                    Some(from) => get_elts(&value, typ_elt, from, *size),
+                    None => vec![false; typ_elt.width() * size],
+                }
+            }
+            Expr::Append { lhs, rhs, .. } => {
+                let (typ_lhs, typ_rhs) = (lhs.into_expr().port_decls(), rhs.into_expr().port_decls());
+                append_leaves(&self.eval(*lhs)?, &typ_lhs, &self.eval(*rhs)?, &typ_rhs)
+            }
+            Expr::Zip { inner, .. } => {
+                let mut value = Vec::new();
+                for member in inner {
+                    value.extend(self.eval(*member)?);
+                }
+                value
+            }
+            Expr::Concat { inner, .. } | Expr::Chunk { inner, .. } | Expr::Repr { inner } => self.eval(*inner)?,
+            Expr::Sum { inner, width_elt } => {
+                let value = self.eval(*inner)?;
+                let typ_elt = PortDecls::Bits(Shape::new([*width_elt]));
+                (0..array_len(&value, &typ_elt)).fold(vec![false; *width_elt], |acc, index| {
+                    add(&acc, &get_elts(&value, &typ_elt, index, 1), *width_elt)
+                })
+            }
+            Expr::Cond { cond, lhs, rhs } => {
+                if self.eval(*cond)?[0] {
+                    self.eval(*lhs)?
+                } else {
+                    self.eval(*rhs)?
+                }
+            }
+            Expr::Set { inner, index, elt } | Expr::SetVarArray { inner, index, elt } => {
+                let typ_elt = elt.into_expr().port_decls();
+                let value = self.eval(*inner)?;
+                match to_usize(&self.eval(*index)?) {
+                    Some(index) => set_elts(&value, &typ_elt, index, &self.eval(*elt)?),
+                    None => value,
+                }
+            }
+            Expr::SetRange { inner, typ_elt, index, elts } => {
+                let value = self.eval(*inner)?;
+                match to_usize(&self.eval(*index)?) {
+                    Some(index) => set_elts(&value, typ_elt, index, &self.eval(*elts)?),
+                    None => value,
+                }
+            }
+            Expr::Case { case_expr, case_items, default } => {
+                let value = self.eval(*case_expr)?;
+                let mut output = None;
+                for (case, item) in case_items {
+                    if cmp(&value, &self.eval(*case)?).is_eq() {
+                        output = Some(self.eval(*item)?);
+                        break;
+                    }
+                }
+                match (output, default) {
+                    (Some(output), _) => output,
+                    (None, Some(default)) => self.eval(*default)?,
+                    (None, None) => vec![false; expr.width()],
+                }
+            }
+            // Signedness is not modeled, so `$signed` is not supported either.
+            Expr::Call { func_name, .. } => {
+                return Err(SimError::Unsupported(format!("function call `{}`", func_name)));
+            }
+            Expr::ConcatArray { inner, elt_typ } => {
+                let elts = inner.iter().map(|elt| self.eval(*elt)).collect::<Result<Vec<_>, _>>()?;
+                join_elts(&elts, elt_typ)
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn eval_binary_op(&mut self, op: BinaryOp, lhs: ExprId, rhs: ExprId) -> Result<Vec<bool>, SimError> {
+        let lhs = self.eval(lhs)?;
+        let rhs = self.eval(rhs)?;
+
+        let value = match op {
+            BinaryOp::Add => add(&lhs, &rhs, lhs.len() + 1),
+            BinaryOp::Sub => sub(&lhs, &rhs, lhs.len()),
+            BinaryOp::Mul => mul(&lhs, &rhs, lhs.len() + rhs.len()),
+            BinaryOp::Div => {
+                div_rem(&lhs, &rhs).map_or_else(|| vec![false; lhs.len()], |(quot, _)| resize(&quot, lhs.len()))
+            }
+            BinaryOp::Mod => {
+                div_rem(&lhs, &rhs).map_or_else(|| vec![false; rhs.len()], |(_, rem)| resize(&rem, rhs.len()))
+            }
+            BinaryOp::Or => lhs.iter().zip(rhs).map(|(l, r)| *l | r).collect(),
+            BinaryOp::And => lhs.iter().zip(rhs).map(|(l, r)| *l & r).collect(),
+            BinaryOp::Xor => lhs.iter().zip(rhs).map(|(l, r)| *l ^ r).collect(),
+            BinaryOp::Eq => lhs.iter().zip(rhs).map(|(l, r)| *l == r).collect(),
+            BinaryOp::EqArithmetic => vec![cmp(&lhs, &rhs).is_eq()],
+            BinaryOp::Less => vec![cmp(&lhs, &rhs).is_lt()],
+            BinaryOp::Greater => vec![cmp(&lhs, &rhs).is_gt()],
+            BinaryOp::LessEq => vec![cmp(&lhs, &rhs).is_le()],
+            BinaryOp::GreaterEq => vec![cmp(&lhs, &rhs).is_ge()],
+            BinaryOp::ShiftLeft => shift_left(&lhs, to_usize(&rhs).unwrap_or(usize::MAX)),
+            BinaryOp::ShiftRight => shift_right(&lhs, to_usize(&rhs).unwrap_or(usize::MAX)),
+        };
+
+        Ok(value)
+    }
+}
@@ -0,0 +1,191 @@
+//! Cycle-accurate simulator.
+//!
+//! The simulator flattens a module into FSMs connected by nets, one net per channel. On each
+//! evaluation, exprs of FSMs are evaluated until the values of nets settle, and on each clock edge
+//! states of FSMs are updated.
+
+mod eval;
+pub(crate) mod netlist;
+mod vcd;
+
+use std::collections::HashMap;
+
+use netlist::{Netlist, Ports};
+use thiserror::Error;
+pub use vcd::VcdWriter;
+
+use crate::hir::{Interface, Module};
+use crate::lir;
+
+#[allow(missing_docs)]
+#[allow(variant_size_differences)]
+#[derive(Debug, Error)]
+pub enum SimError {
+    #[error("input {0:?} is not bound")]
+    UnboundInput(Option<String>),
+    #[error("the widths are mismatched: {0}")]
+    WidthMismatch(String),
+    #[error("cannot simulate {0}")]
+    Unsupported(String),
+    #[error("the model of `{0}` has different interfaces from its instances")]
+    ModelMismatch(String),
+    #[error("combinational logic does not settle: {0:?}")]
+    CombinationalLoop(Vec<String>),
+    #[error("{kind} failed in {fsm}: {message}")]
+    AssertionFailed { fsm: String, kind: lir::AssertionKind, message: String },
+    #[error("backward value depends combinationally on forward value in {fsm}: {message}")]
+    CombinationalDependency { fsm: String, message: String },
+    #[error(transparent)]
+    Module(Box<lir::ModuleError>),
+}
+
+impl From<lir::ModuleError> for SimError {
+    fn from(error: lir::ModuleError) -> Self { Self::Module(Box::new(error)) }
+}
+
+/// Models of external modules, simulated in place of their instances.
+#[derive(Debug, Default, Clone)]
+pub struct Models {
+    /// Models indexed by the names of the external modules.
+    inner: HashMap<String, lir::Module>,
+}
+
+impl Models {
+    /// Adds the model of the external module named `name`.
+    ///
+    /// The model should have the same interfaces as the external module, and is simulated regardless
+    /// of the parameters of the instances.
+    pub fn add<I: Interface, O: Interface>(&mut self, name: &str, model: Module<I, O>) {
+        let _ = self.inner.insert(name.to_string(), model.inner);
+    }
+}
+
+/// Cycle-accurate simulator.
+#[derive(Debug)]
+pub struct Simulator {
+    /// Simulated module.
+    module: lir::Module,
+
+    netlist: Netlist,
+
+    /// Nets connected to the input channels of the module.
+    inputs: Ports,
+
+    /// Nets connected to the output channels of the module.
+    outputs: Ports,
+
+    /// Number of elapsed clock cycles.
+    cycle: usize,
+}
+
+impl Simulator {
+    /// Creates a new simulator for the module.
+    pub fn new<I: Interface, O: Interface>(module: &Module<I, O>) -> Result<Self, SimError> {
+        Self::from_lir(&module.inner)
+    }
+
+    /// Creates a new simulator for the module, simulating the instances of external modules by `models`.
+    pub fn with_models<I: Interface, O: Interface>(module: &Module<I, O>, models: &Models) -> Result<Self, SimError> {
+        Self::build(&module.inner, models.inner.clone())
+    }
+
+    /// Creates a new simulator for the LIR module.
+    pub fn from_lir(module: &lir::Module) -> Result<Self, SimError> { Self::build(module, HashMap::new()) }
+
+    fn build(module: &lir::Module, models: HashMap<String, lir::Module>) -> Result<Self, SimError> {
+        let mut netlist = Netlist::new(models);
+        let inputs = netlist.alloc_ports(&module.inner.input_interface_typ());
+        let outputs = netlist.alloc_ports(&module.inner.output_interface_typ());
+        netlist.add_module(module, &[], &inputs, &outputs)?;
+        netlist.finish();
+
+        let inputs = inputs.into_iter().map(|(path, id)| (path, netlist.find(id))).collect();
+        let outputs = outputs.into_iter().map(|(path, id)| (path, netlist.find(id))).collect();
+
+        let mut sim = Self { module: module.clone(), netlist, inputs, outputs, cycle: 0 };
+        sim.eval()?;
+        Ok(sim)
+    }
+
+    /// Returns endpoint paths and types of the input channels.
+    pub fn input_channels(&self) -> Vec<(lir::EndpointPath, lir::ChannelTyp)> {
+        self.inputs.iter().map(|(path, id)| (path.clone(), self.netlist.nets[*id].typ.clone())).collect()
+    }
+
+    /// Returns endpoint paths and types of the output channels.
+    pub fn output_channels(&self) -> Vec<(lir::EndpointPath, lir::ChannelTyp)> {
+        self.outputs.iter().map(|(path, id)| (path.clone(), self.netlist.nets[*id].typ.clone())).collect()
+    }
+
+    /// Sets the forward value of the input channel.
+    pub fn set_input_fwd(&mut self, path: &lir::EndpointPath, bits: Vec<bool>) {
+        let net = &mut self.netlist.nets[*self.inputs.get(path).expect("no such input channel")];
+        assert_eq!(net.fwd.len(), bits.len(), "width of the forward value is mismatched");
+        net.fwd = bits;
+    }
+
+    /// Returns the backward value of the input channel.
+    pub fn input_bwd(&self, path: &lir::EndpointPath) -> &[bool] {
+        &self.netlist.nets[*self.inputs.get(path).expect("no such input channel")].bwd
+    }
+
+    /// Returns the forward value of the output channel.
+    pub fn output_fwd(&self, path: &lir::EndpointPath) -> &[bool] {
+        &self.netlist.nets[*self.outputs.get(path).expect("no such output channel")].fwd
+    }
+
+    /// Sets the backward value of the output channel.
+    pub fn set_output_bwd(&mut self, path: &lir::EndpointPath, bits: Vec<bool>) {
+        let net = &mut self.netlist.nets[*self.outputs.get(path).expect("no such output channel")];
+        assert_eq!(net.bwd.len(), bits.len(), "width of the backward value is mismatched");
+        net.bwd = bits;
+    }
+
+    /// Propagates the current inputs through combinational logic.
+    ///
+    /// Should be called after setting inputs and before reading outputs.
+    pub fn eval(&mut self) -> Result<(), SimError> { self.settle() }
+
+    /// Advances the clock by one cycle.
+    ///
+    /// States are updated from the settled values of the current cycle, and then the values are
+    /// propagated again with the new states.
+    pub fn step(&mut self) -> Result<(), SimError> {
+        self.settle()?;
+        self.netlist.tick()?;
+        self.cycle += 1;
+        self.settle()
+    }
+
+    /// Returns the number of clock edges each cover assertion held, with the name of its FSM and its message.
+    ///
+    /// Counts are cleared by [`Simulator::reset`].
+    pub fn cover_counts(&self) -> Vec<(String, String, usize)> {
+        self.netlist
+            .fsms
+            .iter()
+            .flat_map(|fsm| {
+                fsm.assertions
+                    .iter()
+                    .zip(fsm.covered.iter())
+                    .filter(|(assertion, _)| matches!(assertion.kind, lir::AssertionKind::Cover))
+                    .map(move |(assertion, count)| (fsm.name.clone(), assertion.message.clone(), *count))
+            })
+            .collect()
+    }
+
+    /// Resets the states of all FSMs to their initial values.
+    pub fn reset(&mut self) -> Result<(), SimError> {
+        self.netlist.reset();
+        self.settle()
+    }
+
+    /// Propagates values until they settle, and checks the independences of FSMs.
+    fn settle(&mut self) -> Result<(), SimError> {
+        self.netlist.settle()?;
+        self.netlist.check_independences()
+    }
+
+    /// Returns the number of elapsed clock cycles.
+    pub fn cycle(&self) -> usize { self.cycle }
+}
@@ -0,0 +1,587 @@
+//! Flattened netlist of a module.
+
+use std::collections::HashMap;
+
+use linked_hash_map::LinkedHashMap;
+
+use super::eval::Evaluator;
+use super::SimError;
+use crate::lir::bits::*;
+use crate::lir::*;
+use crate::some_or;
+
+/// Channels of an interface, indexed by their endpoint paths.
+pub(crate) type Ports = LinkedHashMap<EndpointPath, usize>;
+
+/// Direction of a channel's signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Dir {
+    /// Forward.
+    Fwd,
+
+    /// Backward.
+    Bwd,
+}
+
+/// A wire connecting the producer and the consumer of a channel.
+#[derive(Debug, Clone)]
+pub(crate) struct Net {
+    /// Channel type.
+    pub(crate) typ: ChannelTyp,
+
+    /// Forward value.
+    pub(crate) fwd: Vec<bool>,
+
+    /// Backward value.
+    pub(crate) bwd: Vec<bool>,
+}
+
+impl Net {
+    fn new(typ: ChannelTyp) -> Self {
+        let fwd = vec![false; typ.fwd.width()];
+        let bwd = vec![false; typ.bwd.width()];
+        Self { typ, fwd, bwd }
+    }
+}
+
+/// An FSM instantiated in the netlist.
+#[derive(Debug)]
+pub(crate) struct FsmInst {
+    /// Hierarchical name of the instance, i.e., the names in `scope` joined by `_`.
+    pub(crate) name: String,
+
+    /// Names of the instances containing the FSM from the top module, ending with the FSM itself.
+    pub(crate) scope: Vec<String>,
+
+    /// Input interface type.
+    pub(crate) input_interface_typ: InterfaceTyp,
+
+    /// Output interface type.
+    pub(crate) output_interface_typ: InterfaceTyp,
+
+    /// Output foreward expr.
+    pub(crate) output_fwd: ExprId,
+
+    /// Input backward expr.
+    pub(crate) input_bwd: ExprId,
+
+    /// Next state expr.
+    pub(crate) state: ExprId,
+
+    /// Initial value of the state.
+    pub(crate) init: Vec<bool>,
+
+    /// Assertions checked on each clock edge.
+    pub(crate) assertions: Vec<Assertion>,
+
+    /// Number of clock edges each cover assertion held, in the order of `assertions`.
+    pub(crate) covered: Vec<usize>,
+
+    /// Bits of the output forward value the output backward value should not depend on.
+    independences: Vec<Independence>,
+
+    /// Current value of the state.
+    pub(crate) st: Vec<bool>,
+
+    /// Nets connected to the input channels, in the order of `InterfaceTyp::into_primitives`.
+    pub(crate) inputs: Vec<usize>,
+
+    /// Nets connected to the output channels, in the order of `InterfaceTyp::into_primitives`.
+    pub(crate) outputs: Vec<usize>,
+}
+
+/// Flattened netlist of a module, consisting of FSMs and nets connecting them.
+#[derive(Debug, Default)]
+pub(crate) struct Netlist {
+    /// Nets. Only the representatives of merged nets are used after the netlist is built.
+    pub(crate) nets: Vec<Net>,
+
+    /// Union-find parents of nets.
+    parents: Vec<usize>,
+
+    /// FSMs.
+    pub(crate) fsms: Vec<FsmInst>,
+
+    /// Models of external modules, indexed by their names.
+    models: HashMap<String, Module>,
+}
+
+impl Netlist {
+    /// Creates a new netlist, simulating the instances of external modules by `models`.
+    pub(crate) fn new(models: HashMap<String, Module>) -> Self { Self { models, ..Self::default() } }
+
+    /// Allocates nets for the channels of the given interface type.
+    pub(crate) fn alloc_ports(&mut self, typ: &InterfaceTyp) -> Ports {
+        typ.into_primitives()
+            .into_iter()
+            .filter_map(|(typ, path)| {
+                typ.get_channel_typ().map(|channel_typ| {
+                    let id = self.nets.len();
+                    self.nets.push(Net::new(channel_typ));
+                    self.parents.push(id);
+                    (path, id)
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the representative of the net.
+    pub(crate) fn find(&self, mut id: usize) -> usize {
+        while self.parents[id] != id {
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn merge(&mut self, lhs: usize, rhs: usize) -> Result<(), SimError> {
+        let (lhs, rhs) = (self.find(lhs), self.find(rhs));
+        if self.nets[lhs].typ != self.nets[rhs].typ {
+            return Err(SimError::WidthMismatch(format!(
+                "cannot connect channels of types {:?} and {:?}",
+                self.nets[lhs].typ, self.nets[rhs].typ
+            )));
+        }
+        self.parents[rhs] = lhs;
+        Ok(())
+    }
+
+    /// Adds the module instantiated at `scope`, connecting its input and output channels to the given nets.
+    pub(crate) fn add_module(
+        &mut self, module: &Module, scope: &[String], inputs: &Ports, outputs: &Ports,
+    ) -> Result<(), SimError> {
+        match &*module.inner {
+            ModuleInner::Composite(_, module) => match module.module_typ {
+                CompositeModuleTyp::OneToOne => self.add_composite(module, scope, inputs, outputs),
+                CompositeModuleTyp::NToN(n) => {
+                    for i in 0..n {
+                        self.add_composite(
+                            module,
+                            &push(scope, i.to_string()),
+                            &strip_index(inputs, i),
+                            &strip_index(outputs, i),
+                        )?;
+                    }
+                    Ok(())
+                }
+            },
+            ModuleInner::Fsm(fsm) => {
+                let scope = if scope.is_empty() { vec![module.get_module_name()] } else { scope.to_vec() };
+                self.add_fsm(fsm, scope, inputs, outputs)
+            }
+            ModuleInner::ModuleInst(module) => match &module.module {
+                Some(inner) => self.add_module(inner, scope, inputs, outputs),
+                None => {
+                    let name = module.get_module_name();
+                    let model = some_or!(
+                        self.models.get(&name).cloned(),
+                        return Err(SimError::Unsupported(format!("external module `{}`", name)))
+                    );
+                    if model.inner.input_interface_typ() != module.input_interface_typ()
+                        || model.inner.output_interface_typ() != module.output_interface_typ()
+                    {
+                        return Err(SimError::ModelMismatch(name));
+                    }
+                    self.add_module(&model, scope, inputs, outputs)
+                }
+            },
+            ModuleInner::VirtualModule(module) => Err(SimError::Unsupported(format!(
+                "virtual module `{}` outside of its composite module",
+                module.get_module_name()
+            ))),
+        }
+    }
+
+    fn add_composite(
+        &mut self, module: &CompositeModule, scope: &[String], inputs: &Ports, outputs: &Ports,
+    ) -> Result<(), SimError> {
+        if let Some(error) = &module.error {
+            return Err(error.clone().into());
+        }
+
+        // Adds registered modules.
+        let mut registered_ports = Vec::new();
+        for (index, registered_module) in module.registered_modules.iter().enumerate() {
+            let registered_inputs = self.alloc_ports(&registered_module.inner.input_interface_typ());
+            let registered_outputs = self.alloc_ports(&registered_module.inner.output_interface_typ());
+            self.add_module(
+                registered_module,
+                &push(scope, format!("registered_{}_{}", registered_module.get_module_name(), index)),
+                &registered_inputs,
+                &registered_outputs,
+            )?;
+            registered_ports.push((registered_inputs, registered_outputs));
+        }
+
+        // Allocates output channels of submodules first, since they can be used before defined.
+        let submodule_outputs = module
+            .submodules
+            .iter()
+            .map(|(submodule, _)| self.alloc_ports(&submodule.inner.output_interface_typ()))
+            .collect::<Vec<_>>();
+
+        let endpoint_path = |path: &EndpointPath, typ: &InterfaceTyp| ModuleError::EndpointPath {
+            module: module.name.clone(),
+            path: path.to_string(),
+            typ: typ.describe(),
+        };
+        let resolve = |endpoint: &Endpoint| -> Result<usize, ModuleError> {
+            match endpoint {
+                Endpoint::Input { path } => {
+                    inputs.get(path).copied().ok_or_else(|| endpoint_path(path, &module.input_interface_typ()))
+                }
+                Endpoint::Submodule { submodule_index, path } => submodule_outputs
+                    .get(*submodule_index)
+                    .and_then(|outputs| outputs.get(path).copied())
+                    .ok_or_else(|| ModuleError::Endpoint {
+                        module: module.name.clone(),
+                        path: path.to_string(),
+                        endpoint: format!("{:?}", endpoint),
+                    }),
+                Endpoint::Temp { path } => Err(ModuleError::Endpoint {
+                    module: module.name.clone(),
+                    path: path.to_string(),
+                    endpoint: format!("{:?}", endpoint),
+                }),
+            }
+        };
+
+        // Adds submodules.
+        for (index, ((submodule, interface), submodule_outputs)) in
+            module.submodules.iter().zip(submodule_outputs.iter()).enumerate()
+        {
+            let submodule_inputs = interface
+                .into_primitives()
+                .into_iter()
+                .filter_map(|(interface, path)| {
+                    interface.get_channel().map(|channel| Ok((path, resolve(&channel.endpoint)?)))
+                })
+                .collect::<Result<Ports, ModuleError>>()?;
+
+            match &*submodule.inner {
+                ModuleInner::VirtualModule(virtual_module) => {
+                    let (registered_inputs, registered_outputs) = some_or!(
+                        registered_ports.get(virtual_module.registered_index),
+                        return Err(ModuleError::RegisteredModule {
+                            module: module.name.clone(),
+                            submodule: virtual_module.get_module_name(),
+                        }
+                        .into())
+                    );
+                    let registered_module = &module.registered_modules[virtual_module.registered_index];
+                    for (path, id) in submodule_inputs {
+                        let path = virtual_module.input_endpoint().inner.into_iter().chain(path.inner).collect();
+                        let registered_input = registered_inputs
+                            .get(&path)
+                            .ok_or_else(|| endpoint_path(&path, &registered_module.inner.input_interface_typ()))?;
+                        self.merge(*registered_input, id)?;
+                    }
+                    for (path, id) in submodule_outputs {
+                        let path =
+                            virtual_module.output_endpoint().inner.into_iter().chain(path.inner.clone()).collect();
+                        let registered_output = registered_outputs
+                            .get(&path)
+                            .ok_or_else(|| endpoint_path(&path, &registered_module.inner.output_interface_typ()))?;
+                        self.merge(*registered_output, *id)?;
+                    }
+                }
+                _ => self.add_module(
+                    submodule,
+                    &push(scope, format!("{}_{}", submodule.get_module_name(), index)),
+                    &submodule_inputs,
+                    submodule_outputs,
+                )?,
+            }
+        }
+
+        // Connects output channels.
+        for (interface, path) in module.output_interface.into_primitives() {
+            if let Some(channel) = interface.get_channel() {
+                let output = outputs.get(&path).ok_or_else(|| endpoint_path(&path, &module.output_interface_typ()))?;
+                self.merge(*output, resolve(&channel.endpoint)?)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_fsm(&mut self, module: &Fsm, scope: Vec<String>, inputs: &Ports, outputs: &Ports) -> Result<(), SimError> {
+        let init = Evaluator::new(&[]).eval(module.init)?;
+        let channels = |typ: &InterfaceTyp, ports: &Ports| {
+            typ.into_primitives()
+                .into_iter()
+                .filter(|(typ, _)| matches!(typ, InterfaceTyp::Channel(_)))
+                .map(|(_, path)| {
+                    ports.get(&path).copied().ok_or_else(|| ModuleError::EndpointPath {
+                        module: module.module_name.clone(),
+                        path: path.to_string(),
+                        typ: typ.describe(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        self.fsms.push(FsmInst {
+            name: scope.join("_"),
+            scope,
+            input_interface_typ: module.input_interface_typ.clone(),
+            output_interface_typ: module.output_interface_typ.clone(),
+            output_fwd: module.output_fwd,
+            input_bwd: module.input_bwd,
+            state: module.state,
+            st: init.clone(),
+            init,
+            assertions: module.assertions.clone(),
+            covered: vec![0; module.assertions.len()],
+            independences: module.independences.clone(),
+            inputs: channels(&module.input_interface_typ, inputs)?,
+            outputs: channels(&module.output_interface_typ, outputs)?,
+        });
+
+        Ok(())
+    }
+
+    /// Replaces nets used by FSMs with their representatives. Should be called after all modules are added.
+    pub(crate) fn finish(&mut self) {
+        for i in 0..self.fsms.len() {
+            let inputs = self.fsms[i].inputs.iter().map(|id| self.find(*id)).collect();
+            let outputs = self.fsms[i].outputs.iter().map(|id| self.find(*id)).collect();
+            self.fsms[i].inputs = inputs;
+            self.fsms[i].outputs = outputs;
+        }
+    }
+
+    /// Returns the values of "in", "out" and "st" inputs of the FSM.
+    fn fsm_inputs(&self, fsm: &FsmInst) -> Result<(Vec<bool>, Vec<bool>), SimError> {
+        let interface_error = |error| ModuleError::Interface { module: fsm.name.clone(), error };
+        let input_fwd = join_interface(
+            &fsm.input_interface_typ,
+            Dir::Fwd,
+            &mut fsm.inputs.iter().map(|id| &self.nets[*id].fwd[..]),
+        )
+        .map_err(interface_error)?;
+        let output_bwd = join_interface(
+            &fsm.output_interface_typ,
+            Dir::Bwd,
+            &mut fsm.outputs.iter().map(|id| &self.nets[*id].bwd[..]),
+        )
+        .map_err(interface_error)?;
+        Ok((input_fwd, output_bwd))
+    }
+
+    /// Propagates values through combinational logic until they settle.
+    ///
+    /// Returns `Err` with the FSMs whose outputs are still changing if the values do not settle,
+    /// which indicates a combinational loop.
+    pub(crate) fn settle(&mut self) -> Result<(), SimError> { self.settle_flipped(None) }
+
+    /// Propagates values as `settle`, flipping the given bit of the output forward value of the given FSM.
+    fn settle_flipped(&mut self, flipped: Option<(usize, usize)>) -> Result<(), SimError> {
+        let mut changed = Vec::new();
+
+        for _ in 0..(2 * self.fsms.len() + 2) {
+            changed.clear();
+
+            for (index, fsm) in self.fsms.iter().enumerate() {
+                let mut fsm_changed = false;
+                let (input_fwd, output_bwd) = self.fsm_inputs(fsm)?;
+                let env = [(Some("in"), &input_fwd[..]), (Some("out"), &output_bwd[..]), (Some("st"), &fsm.st[..])];
+                let mut evaluator = Evaluator::new(&env);
+                let mut output_fwd = evaluator.eval(fsm.output_fwd)?;
+                if let Some((flipped_index, fwd_bit)) = flipped {
+                    if flipped_index == index {
+                        output_fwd[fwd_bit] = !output_fwd[fwd_bit];
+                    }
+                }
+                let input_bwd = evaluator.eval(fsm.input_bwd)?;
+
+                for (id, value) in
+                    fsm.outputs.iter().zip(split_interface(&fsm.output_interface_typ, Dir::Fwd, &output_fwd))
+                {
+                    if self.nets[*id].fwd != value {
+                        self.nets[*id].fwd = value;
+                        fsm_changed = true;
+                    }
+                }
+                for (id, value) in
+                    fsm.inputs.iter().zip(split_interface(&fsm.input_interface_typ, Dir::Bwd, &input_bwd))
+                {
+                    if self.nets[*id].bwd != value {
+                        self.nets[*id].bwd = value;
+                        fsm_changed = true;
+                    }
+                }
+
+                if fsm_changed {
+                    changed.push(fsm.name.clone());
+                }
+            }
+
+            if changed.is_empty() {
+                return Ok(());
+            }
+        }
+
+        Err(SimError::CombinationalLoop(changed))
+    }
+
+    /// Checks that the output backward values of FSMs do not depend combinationally on the bits of
+    /// their output forward values they are required to be independent of.
+    ///
+    /// Should be called after the values are settled, and the values are left unchanged.
+    pub(crate) fn check_independences(&mut self) -> Result<(), SimError> {
+        for index in 0..self.fsms.len() {
+            for independence in self.fsms[index].independences.clone() {
+                let nets = self.nets.clone();
+                let (_, output_bwd) = self.fsm_inputs(&self.fsms[index])?;
+                let result = self.settle_flipped(Some((index, independence.fwd_bit)));
+                let (_, flipped_output_bwd) = self.fsm_inputs(&self.fsms[index])?;
+                self.nets = nets;
+                result?;
+
+                if output_bwd != flipped_output_bwd {
+                    return Err(SimError::CombinationalDependency {
+                        fsm: self.fsms[index].name.clone(),
+                        message: independence.message,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Updates the states of FSMs, as on the rising edge of the clock.
+    ///
+    /// Values should be settled before calling this function.
+    pub(crate) fn tick(&mut self) -> Result<(), SimError> {
+        let states = self
+            .fsms
+            .iter()
+            .map(|fsm| {
+                let (input_fwd, output_bwd) = self.fsm_inputs(fsm)?;
+                let env = [(Some("in"), &input_fwd[..]), (Some("out"), &output_bwd[..]), (Some("st"), &fsm.st[..])];
+                let mut evaluator = Evaluator::new(&env);
+                let conds = fsm
+                    .assertions
+                    .iter()
+                    .map(|assertion| Ok(evaluator.eval(assertion.cond)?[0]))
+                    .collect::<Result<Vec<_>, SimError>>()?;
+                Ok((evaluator.eval(fsm.state)?, conds))
+            })
+            .collect::<Result<Vec<_>, SimError>>()?;
+
+        for (fsm, (_, conds)) in self.fsms.iter_mut().zip(states.iter()) {
+            for (index, (assertion, cond)) in fsm.assertions.iter().zip(conds).enumerate() {
+                match assertion.kind {
+                    AssertionKind::Assert | AssertionKind::Assume if !cond => {
+                        return Err(SimError::AssertionFailed {
+                            fsm: fsm.name.clone(),
+                            kind: assertion.kind,
+                            message: assertion.message.clone(),
+                        });
+                    }
+                    AssertionKind::Cover if *cond => fsm.covered[index] += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        for (fsm, (st, _)) in self.fsms.iter_mut().zip(states) {
+            fsm.st = st;
+        }
+
+        Ok(())
+    }
+
+    /// Resets the states of FSMs to their initial values.
+    pub(crate) fn reset(&mut self) {
+        for fsm in &mut self.fsms {
+            fsm.st = fsm.init.clone();
+            fsm.covered.iter_mut().for_each(|count| *count = 0);
+        }
+    }
+}
+
+/// Returns the scope of the instance `name` in `scope`.
+fn push(scope: &[String], name: String) -> Vec<String> { scope.iter().cloned().chain([name]).collect() }
+
+/// Returns ports of the `index`-th element of an array interface.
+fn strip_index(ports: &Ports, index: usize) -> Ports {
+    ports
+        .iter()
+        .filter_map(|(path, id)| {
+            let mut path = path.clone();
+            match path.pop_front() {
+                Some(EndpointNode::Index(i)) if i == index => Some((path, *id)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Returns the value type of the forward or backward signal of the interface.
+fn interface_decls(typ: &InterfaceTyp, dir: Dir) -> PortDecls {
+    match typ {
+        InterfaceTyp::Unit => PortDecls::Bits(Shape::new([0])),
+        InterfaceTyp::Channel(channel_typ) => match dir {
+            Dir::Fwd => channel_typ.fwd.clone(),
+            Dir::Bwd => channel_typ.bwd.clone(),
+        },
+        InterfaceTyp::Array(typ_elt, count) => interface_decls(typ_elt, dir).multiple(*count),
+        InterfaceTyp::ExpansiveArray(typ_elt, count) => {
+            PortDecls::Struct((0..*count).map(|i| (Some(i.to_string()), interface_decls(typ_elt, dir))).collect())
+        }
+        InterfaceTyp::Struct(inner) => PortDecls::Struct(
+            inner.iter().map(|(name, (_, typ))| (Some(name.clone()), interface_decls(typ, dir))).collect(),
+        ),
+    }
+}
+
+/// Splits the value of an interface into the values of its channels.
+pub(crate) fn split_interface<T: Clone + Default>(typ: &InterfaceTyp, dir: Dir, bits: &[T]) -> Vec<Vec<T>> {
+    match typ {
+        InterfaceTyp::Unit => Vec::new(),
+        InterfaceTyp::Channel(_) => vec![bits.to_vec()],
+        InterfaceTyp::Array(typ_elt, count) => {
+            let decls_elt = interface_decls(typ_elt, dir);
+            (0..*count).flat_map(|i| split_interface(typ_elt, dir, &get_elts(bits, &decls_elt, i, 1))).collect()
+        }
+        InterfaceTyp::ExpansiveArray(typ_elt, count) => {
+            let width = interface_decls(typ_elt, dir).width();
+            (0..*count).flat_map(|i| split_interface(typ_elt, dir, &bits[(i * width)..((i + 1) * width)])).collect()
+        }
+        InterfaceTyp::Struct(inner) => {
+            let mut offset = 0;
+            inner
+                .iter()
+                .flat_map(|(_, (_, typ))| {
+                    let width = interface_decls(typ, dir).width();
+                    offset += width;
+                    split_interface(typ, dir, &bits[(offset - width)..offset])
+                })
+                .collect()
+        }
+    }
+}
+
+/// Joins the values of channels into the value of the interface.
+///
+/// Fails if there are fewer values than the channels of the interface.
+pub(crate) fn join_interface<'a, T: 'a + Clone, I: Iterator<Item = &'a [T]>>(
+    typ: &InterfaceTyp, dir: Dir, values: &mut I,
+) -> Result<Vec<T>, InterfaceError> {
+    Ok(match typ {
+        InterfaceTyp::Unit => Vec::new(),
+        InterfaceTyp::Channel(_) => values.next().ok_or(InterfaceError::NoChannels)?.to_vec(),
+        InterfaceTyp::Array(typ_elt, count) => {
+            let elts = (0..*count).map(|_| join_interface(typ_elt, dir, values)).collect::<Result<Vec<_>, _>>()?;
+            join_elts(&elts, &interface_decls(typ_elt, dir))
+        }
+        InterfaceTyp::ExpansiveArray(typ_elt, count) => {
+            (0..*count).map(|_| join_interface(typ_elt, dir, values)).collect::<Result<Vec<_>, _>>()?.concat()
+        }
+        InterfaceTyp::Struct(inner) => {
+            inner.iter().map(|(_, (_, typ))| join_interface(typ, dir, values)).collect::<Result<Vec<_>, _>>()?.concat()
+        }
+    })
+}
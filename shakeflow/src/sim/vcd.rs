@@ -0,0 +1,390 @@
+//! VCD waveform dump.
+//!
+//! Signals are named after the ports of the Verilog code generated by `Virgen`: ports of the top
+//! module are named as in its port declarations. Channels of each FSM are in the scopes of the
+//! instances containing it, e.g., `fifo_0` and then `fsm_1`, and are named `in_...` and `out_...`
+//! as the wires `fifo_0_fsm_1_in_...` and `fifo_0_fsm_1_out_...` of `Virgen`.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use super::netlist::Dir;
+use super::Simulator;
+use crate::lir::*;
+use crate::utils::join_options;
+
+/// Clock period in the timescale of the dump.
+const PERIOD: usize = 10;
+
+/// Verilog port made of channels of an interface.
+///
+/// Channels in an array are packed into a port, the first element in the lowest bits.
+#[derive(Debug)]
+struct PortGroup {
+    /// Prefix of the port names.
+    prefix: Option<String>,
+
+    /// Separator between the prefix and the names of members.
+    sep: Option<String>,
+
+    /// Channel type.
+    channel_typ: ChannelTyp,
+
+    /// Endpoint paths of the packed channels.
+    paths: Vec<EndpointPath>,
+}
+
+/// Returns the Verilog ports of the interface, in the same order as the Verilog backend.
+fn port_groups(interface_typ: &InterfaceTyp) -> Vec<PortGroup> {
+    match interface_typ {
+        InterfaceTyp::Unit => Vec::new(),
+        InterfaceTyp::Channel(channel_typ) => vec![PortGroup {
+            prefix: None,
+            sep: None,
+            channel_typ: channel_typ.clone(),
+            paths: vec![EndpointPath::default()],
+        }],
+        InterfaceTyp::Array(interface_typ, count) => port_groups(interface_typ)
+            .into_iter()
+            .map(|mut group| {
+                group.paths = (0..*count)
+                    .flat_map(|i| {
+                        group.paths.iter().map(move |path| {
+                            let mut path = path.clone();
+                            path.inner.push_front(EndpointNode::Index(i));
+                            path
+                        })
+                    })
+                    .collect();
+                group
+            })
+            .collect(),
+        InterfaceTyp::ExpansiveArray(interface_typ, count) => (0..*count)
+            .flat_map(|i| {
+                port_groups(interface_typ).into_iter().map(move |mut group| {
+                    match group.prefix {
+                        Some(prefix) => group.prefix = join_options("_", [Some(i.to_string()), Some(prefix)]),
+                        None => {
+                            group.prefix = Some(i.to_string());
+                            group.sep = None;
+                        }
+                    }
+                    for path in group.paths.iter_mut() {
+                        path.inner.push_front(EndpointNode::ExpansiveIndex(i));
+                    }
+                    group
+                })
+            })
+            .collect(),
+        InterfaceTyp::Struct(inner) => inner
+            .iter()
+            .flat_map(|(name, (sep, interface_typ))| {
+                port_groups(interface_typ).into_iter().map(|mut group| {
+                    match group.prefix {
+                        Some(prefix) => {
+                            let sep = sep.clone().unwrap_or_else(|| "_".to_string());
+                            group.prefix = join_options(&sep, [Some(name.clone()), Some(prefix)]);
+                        }
+                        None => {
+                            group.prefix = Some(name.clone());
+                            group.sep = sep.clone();
+                        }
+                    }
+                    for path in group.paths.iter_mut() {
+                        path.inner.push_front(EndpointNode::Field(name.clone(), sep.clone()));
+                    }
+                    group
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Variable in the dump.
+#[derive(Debug)]
+struct Var {
+    /// Identifier code.
+    code: String,
+
+    /// Slices of nets concatenated into the variable, the first one in the lowest bits.
+    slices: Vec<(usize, Dir, usize, usize)>,
+
+    /// Last dumped value.
+    last: Option<Vec<bool>>,
+}
+
+/// VCD waveform writer.
+///
+/// Records the forward and backward signals of every channel in the simulator. Call
+/// [`VcdWriter::sample`] after [`Simulator::eval`] or [`Simulator::step`] to record the values of
+/// the current cycle.
+#[derive(Debug)]
+pub struct VcdWriter<W: Write> {
+    /// Output.
+    out: W,
+
+    /// Variables.
+    vars: Vec<Var>,
+
+    /// Identifier code of the clock.
+    clk: String,
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Creates a new writer for the simulator, writing the header to `out`.
+    pub fn new(sim: &Simulator, mut out: W) -> io::Result<Self> {
+        let mut codes = (0..).map(id_code);
+        let clk = codes.next().unwrap();
+        let mut decls = vec![(Vec::new(), clk.clone(), 1, "clk".to_string())];
+        let mut vars = Vec::new();
+
+        let mut add_group =
+            |scope: &[String], prefix: Option<String>, group: PortGroup, nets: &dyn Fn(&EndpointPath) -> usize| {
+                let sep = group.sep.unwrap_or_else(|| "_".to_string());
+                for (dir, decls_dir) in [(Dir::Fwd, &group.channel_typ.fwd), (Dir::Bwd, &group.channel_typ.bwd)] {
+                    let mut offset = 0;
+                    for (name, shape) in decls_dir.iter() {
+                        let slices = group.paths.iter().map(|path| (nets(path), dir, offset, shape.width())).collect();
+                        offset += shape.width();
+
+                        let code = codes.next().unwrap();
+                        let name =
+                            join_options(&sep, [join_options("_", [prefix.clone(), group.prefix.clone()]), name])
+                                .unwrap_or_else(|| "_".to_string());
+                        decls.push((scope.to_vec(), code.clone(), shape.width() * group.paths.len(), name));
+                        vars.push(Var { code, slices, last: None });
+                    }
+                }
+            };
+
+        // Ports of the top module.
+        let module = &sim.module.inner;
+        for group in port_groups(&module.input_interface_typ()) {
+            add_group(&[], module.input_prefix(), group, &|path| sim.inputs[path]);
+        }
+        for group in port_groups(&module.output_interface_typ()) {
+            add_group(&[], module.output_prefix(), group, &|path| sim.outputs[path]);
+        }
+
+        // Channels of FSMs.
+        for fsm in &sim.netlist.fsms {
+            for (interface_typ, nets, suffix) in
+                [(&fsm.input_interface_typ, &fsm.inputs, "in"), (&fsm.output_interface_typ, &fsm.outputs, "out")]
+            {
+                let channels =
+                    channel_paths(interface_typ).into_iter().zip(nets.iter().copied()).collect::<HashMap<_, _>>();
+                for group in port_groups(interface_typ) {
+                    add_group(&fsm.scope, Some(suffix.to_string()), group, &|path| channels[path]);
+                }
+            }
+        }
+
+        writeln!(out, "$version shakeflow $end")?;
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module {} $end", sim.module.get_module_name())?;
+
+        // FSMs are added in the order of the instance hierarchy, so each scope is opened once.
+        let mut current: &[String] = &[];
+        for (scope, code, width, name) in &decls {
+            let common = current.iter().zip(scope.iter()).take_while(|(lhs, rhs)| lhs == rhs).count();
+            for _ in common..current.len() {
+                writeln!(out, "$upscope $end")?;
+            }
+            for instance in &scope[common..] {
+                writeln!(out, "$scope module {} $end", instance)?;
+            }
+            current = scope;
+            writeln!(out, "$var wire {} {} {} $end", width, code, name)?;
+        }
+        for _ in 0..=current.len() {
+            writeln!(out, "$upscope $end")?;
+        }
+        writeln!(out, "$enddefinitions $end")?;
+
+        Ok(Self { out, vars, clk })
+    }
+
+    /// Records the values of the current cycle.
+    ///
+    /// The rising edge of the clock is placed at the start of each cycle, and only the changed
+    /// values are written.
+    pub fn sample(&mut self, sim: &Simulator) -> io::Result<()> {
+        let time = sim.cycle() * PERIOD;
+        writeln!(self.out, "#{}", time)?;
+        writeln!(self.out, "1{}", self.clk)?;
+
+        for var in self.vars.iter_mut() {
+            let value = var
+                .slices
+                .iter()
+                .flat_map(|(net, dir, offset, width)| {
+                    let net = &sim.netlist.nets[*net];
+                    let bits = match dir {
+                        Dir::Fwd => &net.fwd,
+                        Dir::Bwd => &net.bwd,
+                    };
+                    bits[*offset..(offset + width)].iter().copied()
+                })
+                .collect::<Vec<_>>();
+
+            if var.last.as_ref() != Some(&value) {
+                if value.len() == 1 {
+                    writeln!(self.out, "{}{}", u8::from(value[0]), var.code)?;
+                } else {
+                    let bits = value.iter().rev().map(|b| if *b { '1' } else { '0' }).collect::<String>();
+                    writeln!(self.out, "b{} {}", bits, var.code)?;
+                }
+                var.last = Some(value);
+            }
+        }
+
+        writeln!(self.out, "#{}", time + PERIOD / 2)?;
+        writeln!(self.out, "0{}", self.clk)?;
+        Ok(())
+    }
+
+    /// Flushes the output and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Returns endpoint paths of the channels of the interface, in the order of `InterfaceTyp::into_primitives`.
+fn channel_paths(interface_typ: &InterfaceTyp) -> Vec<EndpointPath> {
+    interface_typ
+        .into_primitives()
+        .into_iter()
+        .filter(|(typ, _)| matches!(typ, InterfaceTyp::Channel(_)))
+        .map(|(_, path)| path)
+        .collect()
+}
+
+/// Returns the `n`-th identifier code, consisting of printable ASCII characters.
+fn id_code(mut n: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push(char::from(b'!' + (n % 94) as u8));
+        n /= 94;
+        if n == 0 {
+            return code;
+        }
+        n -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    type Byte = Bits<U<8>>;
+
+    /// Returns a stage passing values through an FSM.
+    fn stage() -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        composite::<VrChannel<Byte>, VrChannel<Byte>, _>("stage", Some("in"), Some("out"), |input, k| {
+            input.fsm::<(), VrChannel<Byte>, _>(k, None, ().into(), |fwd, bwd, state| (fwd, bwd, state))
+        })
+        .build()
+    }
+
+    fn top() -> Module<VrChannel<Byte>, VrChannel<Byte>> {
+        composite::<VrChannel<Byte>, VrChannel<Byte>, _>("top", Some("s_axis"), Some("m_axis"), |input, k| {
+            input.comb_inline(k, stage()).comb_inline(k, stage())
+        })
+        .build()
+    }
+
+    /// Returns the lines of the VCD header of the module, after simulating it for two cycles.
+    fn header(module: &Module<VrChannel<Byte>, VrChannel<Byte>>) -> Vec<String> {
+        let mut sim = Simulator::new(module).unwrap();
+        let mut vcd = VcdWriter::new(&sim, Vec::new()).unwrap();
+        for _ in 0..2 {
+            vcd.sample(&sim).unwrap();
+            sim.step().unwrap();
+        }
+        let vcd = String::from_utf8(vcd.finish().unwrap()).unwrap();
+        vcd.lines().take_while(|line| *line != "$enddefinitions $end").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn top_vars_are_named_as_verilog_ports() {
+        let header = header(&top());
+
+        // Variables in the scope of the top module.
+        let mut depth = 0;
+        let mut vars = Vec::new();
+        for line in &header {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words[0] {
+                "$scope" => depth += 1,
+                "$upscope" => depth -= 1,
+                "$var" if depth == 1 => vars.push((words[4].to_string(), words[2].parse::<usize>().unwrap())),
+                _ => {}
+            }
+        }
+
+        let files = generate(package(top()), |package, dir| package.gen_vir(dir)).unwrap();
+        let ports = files["top_inner.v"]
+            .lines()
+            .filter_map(|line| {
+                let words = line.trim().trim_end_matches(',').split_whitespace().collect::<Vec<_>>();
+                match words[..] {
+                    ["input" | "output", "wire", name] => Some((name.to_string(), 1)),
+                    ["input" | "output", "wire", width, name] => {
+                        let width = width.trim_start_matches('[').split('-').next().unwrap();
+                        Some((name.to_string(), width.parse().unwrap()))
+                    }
+                    _ => None,
+                }
+            })
+            .filter(|(name, _)| name != "rst")
+            .collect::<Vec<_>>();
+
+        assert_eq!(vars, ports);
+        assert_eq!(vars.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), [
+            "clk",
+            "s_axis",
+            "s_axis_valid",
+            "s_axis_ready",
+            "m_axis",
+            "m_axis_valid",
+            "m_axis_ready"
+        ]);
+    }
+
+    #[test]
+    fn fsm_vars_are_in_instance_scopes() {
+        let header = header(&top());
+
+        // Variables with the scopes they are declared in.
+        let mut scope = Vec::new();
+        let mut vars = Vec::new();
+        for line in &header {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words[0] {
+                "$scope" => scope.push(words[2].to_string()),
+                "$upscope" => assert!(scope.pop().is_some()),
+                "$var" if scope.len() > 1 => vars.push(format!("{}.{}", scope.join("."), words[4])),
+                _ => {}
+            }
+        }
+        assert!(scope.is_empty());
+
+        let expected = ["stage_0", "stage_1"]
+            .iter()
+            .flat_map(|stage| {
+                ["in", "in_valid", "in_ready", "out", "out_valid", "out_ready"]
+                    .iter()
+                    .map(move |name| format!("top.{}.fsm_0.{}", stage, name))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vars, expected);
+
+        // Each instance is opened once.
+        assert_eq!(header.iter().filter(|line| line.starts_with("$scope module stage_0 ")).count(), 1);
+        assert_eq!(header.iter().filter(|line| line.starts_with("$scope module fsm_0 ")).count(), 2);
+    }
+}
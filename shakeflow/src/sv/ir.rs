@@ -0,0 +1,234 @@
+//! SystemVerilog IR.
+//!
+//! Module bodies are written in Verilog IR, since SystemVerilog is a superset of Verilog.
+
+use crate::codegen::Direction;
+use crate::utils::indent;
+use crate::{lir, vir};
+
+const INDENT: usize = 4;
+
+/// Data type.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DataType {
+    /// Packed array of logic.
+    Logic(lir::Shape),
+
+    /// Type declared by `typedef`.
+    Named(String),
+
+    /// Packed struct with (name, type) of members. The first member is the most significant.
+    Struct(Vec<(String, DataType)>),
+
+    /// Enum with width and (name, encoding) of variants.
+    Enum(usize, Vec<(String, usize)>),
+}
+
+impl DataType {
+    /// Declares `ident` of the data type.
+    pub fn declare(&self, ident: &str) -> String {
+        match self {
+            DataType::Logic(shape) => {
+                let dims = (0..shape.dim())
+                    .map(|i| shape.get(i))
+                    .filter(|width| *width > 1)
+                    .map(|width| format!("[{}:0]", width - 1))
+                    .collect::<String>();
+                if dims.is_empty() {
+                    format!("logic {}", ident)
+                } else {
+                    format!("logic {} {}", dims, ident)
+                }
+            }
+            DataType::Named(name) => format!("{} {}", name, ident),
+            DataType::Struct(members) => format!(
+                "struct packed {{\n{}\n}} {}",
+                indent(
+                    members.iter().map(|(name, typ)| format!("{};", typ.declare(name))).collect::<Vec<_>>().join("\n"),
+                    INDENT
+                ),
+                ident
+            ),
+            DataType::Enum(width, variants) => format!(
+                "enum {} {{\n{}\n}} {}",
+                DataType::Logic(lir::Shape::new([*width])).declare("").trim_end(),
+                indent(
+                    variants
+                        .iter()
+                        .map(|(name, encoding)| format!("{} = {}'d{}", name, width, encoding))
+                        .collect::<Vec<_>>()
+                        .join(",\n"),
+                    INDENT
+                ),
+                ident
+            ),
+        }
+    }
+}
+
+/// Type declaration.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Typedef {
+    /// Type name.
+    pub name: String,
+
+    /// Data type.
+    pub typ: DataType,
+}
+
+impl ToString for Typedef {
+    fn to_string(&self) -> String { format!("typedef {};", self.typ.declare(&self.name)) }
+}
+
+/// Package of type declarations.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Package {
+    /// Package name.
+    pub name: String,
+
+    /// Type declarations.
+    pub typedefs: Vec<Typedef>,
+}
+
+impl ToString for Package {
+    fn to_string(&self) -> String {
+        format!(
+            "package {};\n\n{}\n\nendpackage",
+            self.name,
+            self.typedefs.iter().map(|typedef| typedef.to_string()).collect::<Vec<_>>().join("\n\n")
+        )
+    }
+}
+
+/// Modport of an interface.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Modport {
+    /// Modport name.
+    pub name: String,
+
+    /// Directions and names of the signals.
+    pub ports: Vec<(Direction, String)>,
+}
+
+impl ToString for Modport {
+    fn to_string(&self) -> String {
+        format!(
+            "modport {} ({});",
+            self.name,
+            self.ports.iter().map(|(dir, name)| format!("{} {}", dir.to_string(), name)).collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+/// Interface.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Interface {
+    /// Interface name.
+    pub name: String,
+
+    /// Package imported by the interface.
+    pub package: String,
+
+    /// Names and types of the signals.
+    pub signals: Vec<(String, DataType)>,
+
+    /// Modports.
+    pub modports: Vec<Modport>,
+}
+
+impl ToString for Interface {
+    fn to_string(&self) -> String {
+        let signals = self.signals.iter().map(|(name, typ)| format!("{};", typ.declare(name))).collect::<Vec<_>>();
+        let modports = self.modports.iter().map(|modport| modport.to_string()).collect::<Vec<_>>();
+
+        format!(
+            "interface {};\n{}\nendinterface",
+            self.name,
+            [vec![format!("import {}::*;", self.package)], signals, modports]
+                .into_iter()
+                .filter(|lines| !lines.is_empty())
+                .map(|lines| indent(lines.join("\n"), INDENT))
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        )
+    }
+}
+
+/// Port declaration.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PortDeclaration {
+    /// Input or output of a data type.
+    Data(Direction, DataType, String),
+
+    /// Interface port. (Interface name, modport name, identifier, array size)
+    Interface(String, String, String, usize),
+}
+
+impl ToString for PortDeclaration {
+    fn to_string(&self) -> String {
+        match self {
+            PortDeclaration::Data(dir, typ, ident) => format!("{} {}", dir.to_string(), typ.declare(ident)),
+            PortDeclaration::Interface(interface, modport, ident, 1) => format!("{}.{} {}", interface, modport, ident),
+            PortDeclaration::Interface(interface, modport, ident, size) => {
+                format!("{}.{} {} [{}]", interface, modport, ident, size)
+            }
+        }
+    }
+}
+
+/// Module item.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ModuleItem {
+    /// Continuous assignments, whose right-hand sides are cast to the types if any. (Lvalue, type, expr)
+    Assigns(Vec<(vir::Expression, Option<String>, vir::Expression)>),
+
+    /// Verilog module item.
+    Verilog(vir::ModuleItem),
+}
+
+impl ToString for ModuleItem {
+    fn to_string(&self) -> String {
+        match self {
+            ModuleItem::Assigns(assigns) => assigns
+                .iter()
+                .map(|(lvalue, typ, expr)| match typ {
+                    Some(typ) => format!("assign {} = {}'({});", lvalue.to_string(), typ, expr.to_string()),
+                    None => format!("assign {} = {};", lvalue.to_string(), expr.to_string()),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ModuleItem::Verilog(item) => item.to_string(),
+        }
+    }
+}
+
+/// Module.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Module {
+    /// Module name.
+    pub name: String,
+
+    /// Package imported by the module.
+    pub package: String,
+
+    /// Port declarations.
+    pub port_decls: Vec<PortDeclaration>,
+
+    /// Module items.
+    pub module_items: Vec<ModuleItem>,
+}
+
+impl ToString for Module {
+    fn to_string(&self) -> String {
+        format!(
+            "`timescale 1ns / 1ps\n\nmodule {}\n    import {}::*;\n(\n{}\n);\n\ngenerate\n{}\nendgenerate\nendmodule",
+            self.name,
+            self.package,
+            indent(
+                self.port_decls.iter().map(|port_decl| port_decl.to_string()).collect::<Vec<_>>().join(",\n"),
+                INDENT
+            ),
+            self.module_items.iter().map(|item| item.to_string()).collect::<Vec<_>>().join("\n\n")
+        )
+    }
+}
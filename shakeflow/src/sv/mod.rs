@@ -0,0 +1,5 @@
+//! SystemVerilog.
+
+mod ir;
+
+pub use ir::*;
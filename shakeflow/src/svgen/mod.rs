@@ -0,0 +1,530 @@
+//! Generates SystemVerilog code.
+//!
+//! Signal types derived by `#[derive(Signal)]` are declared as packed structs and enums, and
+//! channel types are declared as interfaces with `source` and `sink` modports. The module body
+//! is generated by `Virgen` with the flattened ports, which are connected to the interface ports.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::codegen::*;
+use crate::*;
+
+/// Name of the package of type declarations.
+const TYPES_PACKAGE: &str = "shakeflow_types";
+
+impl Package {
+    /// Generates SystemVerilog code at the given directory path.
+    ///
+    /// Top-level modules are generated in SystemVerilog with interface ports, and the type
+    /// declarations and interfaces are generated in `shakeflow_types.sv`. Submodules and
+    /// parameterized modules are generated in Verilog.
+    pub fn gen_sv<P: AsRef<Path>>(mut self, path_dir: P) -> Result<(), PackageError> {
+        fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
+
+        let (submodules, top_modules) = self.scan_modules()?;
+
+        for submodule in submodules.iter() {
+            self.gen_vir_module(submodule, &path_dir)?;
+        }
+
+        let mut decls = Decls::default();
+        for module in top_modules.iter() {
+            let path = path_dir.as_ref().join(format!("{}.sv", module.get_module_name()));
+            let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+
+            let module = gen_module::<Svgen>(module.get_module_name(), module, self.reset_style)
+                .map_err(|error| PackageError::Module { error })?;
+            let module = gen_sv_module(module, &mut decls);
+
+            writeln!(file, "{}", module.to_string()).map_err(|error| PackageError::Fs { error })?;
+        }
+
+        for module in self.param_modules.iter() {
+            self.gen_vir_param_module(module, &path_dir)?;
+        }
+
+        let path = path_dir.as_ref().join(format!("{}.sv", TYPES_PACKAGE));
+        let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+        let package = sv::Package { name: TYPES_PACKAGE.to_string(), typedefs: decls.typedefs };
+        writeln!(file, "{}", package.to_string()).map_err(|error| PackageError::Fs { error })?;
+        for interface in decls.interfaces.iter() {
+            writeln!(file, "\n{}", interface.to_string()).map_err(|error| PackageError::Fs { error })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Ports of module.
+#[derive(Debug, Clone)]
+pub struct Ports {
+    /// Clock and reset signals.
+    pub clocks: Vec<String>,
+
+    /// Channel ports, which are (direction, channel type, array size, prefix, separator).
+    pub channels: Vec<(Direction, lir::ChannelTyp, usize, Option<String>, String)>,
+
+    /// Flattened ports referred to by the module body, which are (direction, width, name).
+    pub flattened: Vec<(Direction, usize, String)>,
+}
+
+/// SystemVerilog IR Generator
+///
+/// The module body is generated by `Virgen`.
+#[derive(Default, Debug)]
+pub struct Svgen;
+
+impl Codegen for Svgen {
+    type Body = Vec<vir::ModuleItem>;
+    type Ports = Ports;
+
+    fn gen_port_decls(&self, module: &lir::Module, ctx: &Context) -> Result<Ports, lir::ModuleError> {
+        let clocks = [None]
+            .into_iter()
+            .chain(module.clock_domains().into_iter().map(Some))
+            .flat_map(|clock_domain| {
+                let (clk, rst) = clock_signals(clock_domain.as_deref(), ctx.reset_style().polarity);
+                [clk, rst]
+            })
+            .collect();
+
+        Ok(Ports { clocks, channels: gen_channel_ports(module), flattened: gen_port_decls(module, ctx)? })
+    }
+
+    fn gen_module_composite(
+        &self, module: &lir::CompositeModule, ctx: &mut Context,
+    ) -> Result<Vec<vir::ModuleItem>, lir::ModuleError> {
+        Virgen.gen_module_composite(module, ctx)
+    }
+
+    fn gen_module_fsm(&self, module: &lir::Fsm, ctx: &mut Context) -> Result<Vec<vir::ModuleItem>, lir::ModuleError> {
+        Virgen.gen_module_fsm(module, ctx)
+    }
+
+    fn gen_module_inst(
+        &self, module: &lir::ModuleInst, ctx: &mut Context,
+    ) -> Result<Vec<vir::ModuleItem>, lir::ModuleError> {
+        Virgen.gen_module_inst(module, ctx)
+    }
+
+    fn gen_module_virtual(
+        &self, module: &lir::VirtualModule, composite_context_prefix: Option<String>, ctx: &mut Context,
+    ) -> Result<Vec<vir::ModuleItem>, lir::ModuleError> {
+        Virgen.gen_module_virtual(module, composite_context_prefix, ctx)
+    }
+}
+
+/// Type declarations and interfaces shared by the modules in a package.
+#[derive(Debug, Default)]
+struct Decls {
+    /// Type declarations, in the order of dependency.
+    typedefs: Vec<sv::Typedef>,
+
+    /// Interfaces.
+    interfaces: Vec<sv::Interface>,
+
+    /// Names of the declared signal types.
+    typ_names: HashMap<lir::SignalTyp, String>,
+
+    /// Names of the declared channel types.
+    channel_names: HashMap<lir::ChannelTypDecl, String>,
+
+    /// Allocated names.
+    names: HashSet<String>,
+}
+
+impl Decls {
+    /// Allocates a name with the given base and suffix, e.g., `Valid_t` and then `Valid_1_t`.
+    fn alloc_name(&mut self, base: &str, suffix: &str) -> String {
+        let mut name = format!("{}_{}", base, suffix);
+        let mut index = 1;
+        while self.names.contains(&name) {
+            name = format!("{}_{}_{}", base, index, suffix);
+            index += 1;
+        }
+        self.names.insert(name.clone());
+        name
+    }
+
+    /// Returns the data type of the signal type, declaring the named types in it.
+    ///
+    /// Zero-width members are omitted, so the signal type should have nonzero width.
+    fn data_type(&mut self, typ: &lir::SignalTyp) -> sv::DataType {
+        match typ {
+            lir::SignalTyp::Bits(shape) => sv::DataType::Logic(lir::Shape::new([shape.width()])),
+            lir::SignalTyp::Struct { name: None, members } => sv::DataType::Struct(self.members(members)),
+            lir::SignalTyp::Struct { name: Some(name), .. } | lir::SignalTyp::Enum { name, .. } => {
+                sv::DataType::Named(self.typedef(typ, name))
+            }
+        }
+    }
+
+    /// Declares the named signal type, and returns the type name.
+    fn typedef(&mut self, typ: &lir::SignalTyp, name: &str) -> String {
+        if let Some(type_name) = self.typ_names.get(typ) {
+            return type_name.clone();
+        }
+
+        let type_name = self.alloc_name(name, "t");
+        let data_type = match typ {
+            lir::SignalTyp::Struct { members, .. } => sv::DataType::Struct(self.members(members)),
+            lir::SignalTyp::Enum { width, variants, .. } => {
+                // Enum constants share the namespace of the package, so they are prefixed by the type name.
+                let prefix = type_name.strip_suffix("_t").unwrap();
+                sv::DataType::Enum(
+                    *width,
+                    variants.iter().map(|(variant, encoding)| (format!("{}_{}", prefix, variant), *encoding)).collect(),
+                )
+            }
+            lir::SignalTyp::Bits(_) => self.data_type(typ),
+        };
+
+        self.typ_names.insert(typ.clone(), type_name.clone());
+        self.typedefs.push(sv::Typedef { name: type_name.clone(), typ: data_type });
+        type_name
+    }
+
+    /// Returns the members of packed struct.
+    ///
+    /// The members are reversed, since the first member of packed struct is the most significant
+    /// while the first field of signal is the least significant.
+    fn members(&mut self, members: &[(String, Option<String>, lir::SignalTyp)]) -> Vec<(String, sv::DataType)> {
+        members
+            .iter()
+            .rev()
+            .filter(|(_, _, member)| member.width() > 0)
+            .map(|(field, _, member)| (member_name(field), self.data_type(member)))
+            .collect()
+    }
+
+    /// Declares the interface of the channel type, and returns the interface name.
+    fn interface(&mut self, channel_typ: &lir::ChannelTyp) -> String {
+        let decl = channel_typ.decl();
+        if let Some(name) = self.channel_names.get(&decl) {
+            return name.clone();
+        }
+
+        let name = self.alloc_name(&decl.name, "if");
+        let signals = [("fwd", &decl.fwd), ("bwd", &decl.bwd)]
+            .into_iter()
+            .filter(|(_, typ)| typ.width() > 0)
+            .map(|(signal, typ)| (signal.to_string(), self.data_type(typ)))
+            .collect::<Vec<_>>();
+        let modport = |name: &str, fwd_dir: Direction, bwd_dir: Direction| sv::Modport {
+            name: name.to_string(),
+            ports: signals
+                .iter()
+                .map(|(signal, _)| (if signal == "fwd" { fwd_dir.clone() } else { bwd_dir.clone() }, signal.clone()))
+                .collect(),
+        };
+        let modports = vec![
+            modport("source", Direction::Output, Direction::Input),
+            modport("sink", Direction::Input, Direction::Output),
+        ];
+
+        self.channel_names.insert(decl, name.clone());
+        self.interfaces.push(sv::Interface {
+            name: name.clone(),
+            package: TYPES_PACKAGE.to_string(),
+            signals,
+            modports,
+        });
+        name
+    }
+
+    /// Returns the leaves of the signal type with nonzero width, in the order of `PortDecls::iter`.
+    ///
+    /// # Returns
+    ///
+    /// - `String`: Path to the member, e.g., `.payload.data`
+    /// - `Option<String>`: Name in `PortDecls`, e.g., `payload_data`
+    /// - `usize`: Width of the leaf
+    /// - `Option<String>`: Type name of the leaf if it is an enum
+    fn leaves(
+        &mut self, typ: &lir::SignalTyp, path: String, symbol: Option<String>,
+    ) -> Vec<(String, Option<String>, usize, Option<String>)> {
+        match typ {
+            lir::SignalTyp::Struct { members, .. } => members
+                .iter()
+                .flat_map(|(field, member_symbol, member)| {
+                    self.leaves(
+                        member,
+                        format!("{}.{}", path, member_name(field)),
+                        join_options("_", [symbol.clone(), member_symbol.clone()]),
+                    )
+                })
+                .collect(),
+            _ if typ.width() == 0 => vec![],
+            lir::SignalTyp::Enum { name, .. } => vec![(path, symbol, typ.width(), Some(self.typedef(typ, name)))],
+            lir::SignalTyp::Bits(_) => vec![(path, symbol, typ.width(), None)],
+        }
+    }
+}
+
+/// Returns the member name of the field, prefixing `_` to the index of tuples.
+fn member_name(field: &str) -> String {
+    if field.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", field)
+    } else {
+        field.to_string()
+    }
+}
+
+/// Generates SystemVerilog module, declaring the types and interfaces of its ports.
+fn gen_sv_module(module: codegen::Module<Svgen>, decls: &mut Decls) -> sv::Module {
+    let Ports { clocks, channels, flattened } = module.ports;
+
+    let mut port_decls = clocks
+        .iter()
+        .map(|clock| {
+            sv::PortDeclaration::Data(Direction::Input, sv::DataType::Logic(lir::Shape::new([1])), clock.clone())
+        })
+        .collect::<Vec<_>>();
+    let mut assigns = vec![];
+
+    for (dir, channel_typ, size, prefix, sep) in channels {
+        let interface = decls.interface(&channel_typ);
+        let (modport, default_ident) = match dir {
+            Direction::Input => ("sink", "in"),
+            Direction::Output => ("source", "out"),
+        };
+
+        // The flattened port of a `Bits` signal is named by the prefix itself.
+        let mut ident = prefix.clone().unwrap_or_else(|| default_ident.to_string());
+        if flattened.iter().any(|(_, _, name)| *name == ident) {
+            ident = format!("{}_if", ident);
+        }
+
+        let decl = channel_typ.decl();
+        for (signal, typ, driven) in
+            [("fwd", &decl.fwd, dir == Direction::Output), ("bwd", &decl.bwd, dir == Direction::Input)]
+        {
+            for (path, symbol, width, enum_typ) in decls.leaves(typ, "".to_string(), None) {
+                let name = join_options(&sep, [prefix.clone(), symbol]).unwrap();
+                for index in 0..size {
+                    let (element, flat) = if size == 1 {
+                        (ident.clone(), vir::Expression::ident(name.clone()))
+                    } else {
+                        (
+                            format!("{}[{}]", ident, index),
+                            vir::Expression::ident(name.clone()).with_range(vir::Range::new_range(
+                                vir::Expression::number((index * width).to_string()),
+                                vir::Expression::number(width.to_string()),
+                            )),
+                        )
+                    };
+                    let member = vir::Expression::ident(format!("{}.{}{}", element, signal, path));
+
+                    if driven {
+                        assigns.push((member, enum_typ.clone(), flat));
+                    } else {
+                        assigns.push((flat, None, member));
+                    }
+                }
+            }
+        }
+
+        port_decls.push(sv::PortDeclaration::Interface(interface, modport.to_string(), ident, size));
+    }
+
+    // Optimizes the module body with the flattened ports.
+    let body = vir::Module {
+        name: module.name.clone(),
+        params: vec![],
+        port_decls: flattened
+            .iter()
+            .map(|(dir, width, name)| match dir {
+                Direction::Input => vir::PortDeclaration::input(*width, name.clone()),
+                Direction::Output => vir::PortDeclaration::output(*width, name.clone()),
+            })
+            .collect(),
+        module_items: module.body,
+    };
+    let body = vir::opt::wire_cache_opt(body);
+    let body = vir::opt::dead_code_opt(body);
+
+    let flattened_decls = flattened
+        .into_iter()
+        .filter(|(_, _, name)| !clocks.contains(name))
+        .map(|(_, width, name)| vir::Declaration::net(lir::Shape::new([width]), name))
+        .collect::<Vec<_>>();
+
+    let mut module_items = vec![];
+    if !flattened_decls.is_empty() {
+        module_items.push(sv::ModuleItem::Verilog(vir::ModuleItem::Commented(
+            "Flattened ports".to_string(),
+            None,
+            vec![vir::ModuleItem::Declarations(flattened_decls)],
+        )));
+    }
+    if !assigns.is_empty() {
+        module_items.push(sv::ModuleItem::Assigns(assigns));
+    }
+    module_items.extend(body.module_items.into_iter().map(sv::ModuleItem::Verilog));
+
+    sv::Module { name: module.name, package: TYPES_PACKAGE.to_string(), port_decls, module_items }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    /// Operation of a packet.
+    #[derive(Debug, Clone, Signal)]
+    #[width(2)]
+    enum Op {
+        Load,
+        Store,
+        #[encode(3)]
+        Flush,
+    }
+
+    /// Packet with an operation and data.
+    #[derive(Debug, Clone, Signal)]
+    struct Packet {
+        op: Op,
+        data: Bits<U<8>>,
+    }
+
+    /// Returns an FSM delaying packets by a cycle, storing nonzero data and flushing otherwise.
+    fn delay() -> Module<VrChannel<Packet>, VrChannel<Packet>> {
+        hir::Fsm::<VrChannel<Packet>, VrChannel<Packet>, Valid<Packet>, _>::new(
+            "delay",
+            |fwd, _, state| {
+                let op = fwd.inner.data.is_eq(0.into()).cond(Op::Flush.into(), Op::Store.into());
+                let packet = PacketProj { op, data: fwd.inner.data }.into();
+                (state, ReadyProj { ready: true.into() }.into(), ValidProj { inner: packet, valid: fwd.valid }.into())
+            },
+            ValidProj { inner: PacketProj { op: Op::Load.into(), data: 0.into() }.into(), valid: false.into() }.into(),
+        )
+        .into()
+    }
+
+    /// Returns the code without the source locations of modules.
+    fn without_sources(code: &str) -> String {
+        code.lines().filter(|line| !line.trim_start().starts_with("src = ")).map(|line| format!("{}\n", line)).collect()
+    }
+
+    #[test]
+    fn types_are_declared_in_the_package() {
+        let files = generate(package(delay()), |package, dir| package.gen_sv(dir)).unwrap();
+        assert_eq!(
+            files["shakeflow_types.sv"],
+            r#"package shakeflow_types;
+
+typedef enum logic [1:0] {
+    Op_Load = 2'd0,
+    Op_Store = 2'd1,
+    Op_Flush = 2'd3
+} Op_t;
+
+typedef struct packed {
+    logic [7:0] data;
+    Op_t op;
+} Packet_t;
+
+typedef struct packed {
+    logic valid;
+    Packet_t inner;
+} Valid_t;
+
+typedef struct packed {
+    logic ready;
+} Ready_t;
+
+endpackage
+
+interface VrChannel_if;
+    import shakeflow_types::*;
+
+    Valid_t fwd;
+    Ready_t bwd;
+
+    modport source (output fwd, input bwd);
+    modport sink (input fwd, output bwd);
+endinterface
+"#
+        );
+    }
+
+    #[test]
+    fn ports_are_interfaces() {
+        let files = generate(package(delay()), |package, dir| package.gen_sv(dir)).unwrap();
+        assert_eq!(
+            without_sources(&files["delay.sv"]),
+            r#"`timescale 1ns / 1ps
+
+module delay
+    import shakeflow_types::*;
+(
+    input logic clk,
+    input logic rst,
+    VrChannel_if.sink in,
+    VrChannel_if.source out
+);
+
+generate
+/*
+    Flattened ports
+*/
+wire [2-1:0] in_op;
+wire [8-1:0] in_data;
+wire in_valid;
+wire in_ready;
+wire [2-1:0] out_op;
+wire [8-1:0] out_data;
+wire out_valid;
+wire out_ready;
+
+assign in_op = in.fwd.inner.op;
+assign in_data = in.fwd.inner.data;
+assign in_valid = in.fwd.valid;
+assign in.bwd.ready = in_ready;
+assign out.fwd.inner.op = Op_t'(out_op);
+assign out.fwd.inner.data = out_data;
+assign out.fwd.valid = out_valid;
+assign out_ready = out.bwd.ready;
+
+/*
+    Begin FSM delay
+*/
+reg [2-1:0] st_op_reg = 2'b0;
+reg [8-1:0] st_data_reg = 8'b0;
+reg st_valid_reg = 1'b0;
+
+assign out_op = st_op_reg;
+assign out_data = st_data_reg;
+assign out_valid = st_valid_reg;
+
+assign in_ready = 1'b1;
+
+reg [2-1:0] t0;
+
+always @(posedge clk) begin
+    if (in_data == 8'b0) begin
+        t0 = 2'b11;
+    end else begin
+        t0 = 2'b01;
+    end
+    st_op_reg <= t0;
+    st_data_reg <= in_data;
+    st_valid_reg <= in_valid;
+    if (rst) begin
+        st_op_reg <= 2'b0;
+        st_data_reg <= 8'b0;
+        st_valid_reg <= 1'b0;
+    end
+end
+/* End FSM delay */
+endgenerate
+endmodule
+"#
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Utilities for the tests of the crate.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::*;
+
+#[allow(unreachable_pub)]
+mod uni {
+    use crate::*;
+
+    channel! {UniChannel<V: Signal>, V, ()}
+}
+
+pub(crate) use uni::UniChannel;
+
+/// Valid-ready channel, whose interface type is named as that of `shakeflow-std`.
+#[derive(Debug)]
+pub(crate) struct VrChannel<V: Signal> {
+    endpoint: lir::Endpoint,
+    _marker: std::marker::PhantomData<V>,
+}
+
+impl<V: Signal> Interface for VrChannel<V> {
+    type Bwd = Ready;
+    type Fwd = Valid<V>;
+
+    fn interface_typ() -> lir::InterfaceTyp {
+        lir::InterfaceTyp::Channel(channel_typ::<Self::Fwd, Self::Bwd>("VrChannel"))
+    }
+
+    fn try_from_inner(interface: lir::Interface) -> Result<Self, InterfaceError> {
+        let channel = channel_of::<Self>(interface)?;
+        Ok(Self { endpoint: channel.endpoint(), _marker: std::marker::PhantomData })
+    }
+
+    fn try_into_inner(self) -> Result<lir::Interface, InterfaceError> {
+        Ok(lir::Interface::Channel(lir::Channel {
+            typ: channel_typ::<Self::Fwd, Self::Bwd>("VrChannel"),
+            endpoint: self.endpoint,
+        }))
+    }
+}
+
+/// Forward signals of `VrChannel`.
+#[derive(Debug, Clone, Signal)]
+pub(crate) struct Valid<V: Signal> {
+    #[member(name = "")]
+    pub(crate) inner: V,
+    pub(crate) valid: bool,
+}
+
+/// Backward signals of `VrChannel`.
+#[derive(Debug, Clone, Signal)]
+pub(crate) struct Ready {
+    pub(crate) ready: bool,
+}
+
+/// Generates the package with `gen` into a new directory, and returns the generated files by name.
+pub(crate) fn generate<F: FnOnce(Package, &Path) -> Result<(), PackageError>>(
+    package: Package, gen: F,
+) -> Result<BTreeMap<String, String>, PackageError> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "shakeflow-test-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = gen(package, &dir).map(|_| {
+        fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                (path.file_name().unwrap().to_string_lossy().to_string(), fs::read_to_string(&path).unwrap())
+            })
+            .collect()
+    });
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+/// Returns a package of the module.
+pub(crate) fn package<I: Interface, O: Interface>(module: Module<I, O>) -> Package {
+    let mut package = Package::default();
+    package.add(module);
+    package
+}
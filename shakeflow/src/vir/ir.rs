@@ -11,6 +11,9 @@ pub struct Module {
     /// Module name.
     pub name: String,
 
+    /// Parameters with default values.
+    pub params: Vec<(String, usize)>,
+
     /// Port declarations.
     pub port_decls: Vec<PortDeclaration>,
 
@@ -20,9 +23,26 @@ pub struct Module {
 
 impl ToString for Module {
     fn to_string(&self) -> String {
+        let params = if self.params.is_empty() {
+            "".to_string()
+        } else {
+            format!(
+                " #\n(\n{}\n)",
+                indent(
+                    self.params
+                        .iter()
+                        .map(|(name, value)| format!("parameter {} = {}", name, value))
+                        .collect::<Vec<_>>()
+                        .join(",\n"),
+                    INDENT
+                )
+            )
+        };
+
         format!(
-            "`timescale 1ns / 1ps\n\nmodule {}\n(\n{}\n);\n\ngenerate\n{}\nendgenerate\nendmodule",
+            "`timescale 1ns / 1ps\n\nmodule {}{}\n(\n{}\n);\n\ngenerate\n{}\nendgenerate\nendmodule",
             self.name,
+            params,
             indent(
                 self.port_decls.iter().map(|port_decl| port_decl.to_string()).collect::<Vec<_>>().join(",\n"),
                 INDENT
@@ -47,11 +67,17 @@ pub enum ModuleItem {
     /// Generate instantiation.
     GeneratedInstantiation(GeneratedInstantiation),
 
+    /// Elaboration-time check of parameters. (Condition, error message)
+    ParamCheck(Expression, String),
+
     /// Always construct.
     AlwaysConstruct(String, Vec<Statement>),
 
     /// Comment. (Comment before modules, comment after modules, modules)
     Commented(String, Option<String>, Vec<ModuleItem>),
+
+    /// Concurrent assertion, enabled only if `FORMAL` is defined. (Kind, clocking event, condition, message)
+    Assertion(lir::AssertionKind, String, Expression, String),
 }
 
 impl ToString for ModuleItem {
@@ -61,6 +87,14 @@ impl ToString for ModuleItem {
             ModuleItem::ContinuousAssigns(conts) => gen_verilog_conts(conts),
             ModuleItem::ModuleInstantiation(module_inst) => module_inst.to_string(),
             ModuleItem::GeneratedInstantiation(generated_inst) => generated_inst.to_string(),
+            ModuleItem::ParamCheck(cond, message) => {
+                let message = message.replace('\\', "\\\\").replace('"', "\\\"");
+                format!(
+                    "if (!{}) begin\n{}\nend",
+                    cond.clone().into_primary().to_string(),
+                    indent(format!("initial begin\n    $error(\"{}\");\n    $finish;\nend", message), INDENT)
+                )
+            }
             ModuleItem::AlwaysConstruct(event, stmts) => {
                 format!(
                     "{} begin\n{}\nend",
@@ -76,6 +110,18 @@ impl ToString for ModuleItem {
                     comment_after.as_ref().map_or("".to_string(), |c| format!("\n/* {} */", c))
                 )
             }
+            ModuleItem::Assertion(kind, event, expr, message) => {
+                let property = format!("{} property ({} {})", kind, event, expr.to_string());
+                let message = message.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+                let assertion = match kind {
+                    lir::AssertionKind::Assert | lir::AssertionKind::Assume => {
+                        format!("{}\nelse $error(\"{}\");", property, message)
+                    }
+                    lir::AssertionKind::Cover => format!("{}; // {}", property, message),
+                };
+                // Concurrent assertions are SystemVerilog, so they are only enabled for formal verification.
+                format!("`ifdef FORMAL\n{}\n`endif", assertion)
+            }
         }
     }
 }
@@ -93,6 +139,12 @@ pub enum PortDeclaration {
 
     /// Output declaration.
     Output(usize, String),
+
+    /// Input declaration whose width is a parameter expression.
+    ParamInput(Expression, String),
+
+    /// Output declaration whose width is a parameter expression.
+    ParamOutput(Expression, String),
 }
 
 impl ToString for PortDeclaration {
@@ -112,6 +164,8 @@ impl ToString for PortDeclaration {
                     format!("output wire {}", ident)
                 }
             }
+            Self::ParamInput(width, ident) => format!("input wire [{}-1:0] {}", width.to_string(), ident),
+            Self::ParamOutput(width, ident) => format!("output wire [{}-1:0] {}", width.to_string(), ident),
         }
     }
 }
@@ -122,6 +176,16 @@ impl PortDeclaration {
 
     /// Creates new output port declaration.
     pub fn output(width: usize, ident: String) -> Self { Self::Output(width, ident) }
+
+    /// Returns the identifier of the port.
+    pub fn ident(&self) -> &str {
+        match self {
+            Self::Input(_, ident)
+            | Self::Output(_, ident)
+            | Self::ParamInput(_, ident)
+            | Self::ParamOutput(_, ident) => ident,
+        }
+    }
 }
 
 /// Declaration.
@@ -135,6 +199,15 @@ pub enum Declaration {
 
     /// Integer declaration.
     Integer(String),
+
+    /// Net declaration whose width is a parameter expression.
+    ParamNet(Expression, String),
+
+    /// Reg declaration whose width is a parameter expression.
+    ParamReg(Expression, String, Option<Expression>),
+
+    /// Local parameter declaration. (Width, identifier, value)
+    LocalParam(Expression, String, Expression),
 }
 
 impl Declaration {
@@ -208,6 +281,14 @@ impl ToString for Declaration {
                 _ => unimplemented!(),
             },
             Self::Integer(ident) => format!("integer {};", ident),
+            Self::ParamNet(width, ident) => format!("wire [{}-1:0] {};", width.to_string(), ident),
+            Self::ParamReg(width, ident, Some(expr)) => {
+                format!("reg [{}-1:0] {} = {};", width.to_string(), ident, expr.to_string())
+            }
+            Self::ParamReg(width, ident, None) => format!("reg [{}-1:0] {};", width.to_string(), ident),
+            Self::LocalParam(width, ident, value) => {
+                format!("localparam [{}-1:0] {} = {};", width.to_string(), ident, value.to_string())
+            }
         }
     }
 }
@@ -457,9 +538,8 @@ pub enum Primary {
     /// Concatenation.
     Concatenation(Concatenation),
 
-    /// Multiple concatenation.
-    // TODO: Add constant expression
-    MultipleConcatenation(usize, Concatenation),
+    /// Multiple concatenation. The count is a constant expression.
+    MultipleConcatenation(Box<Expression>, Concatenation),
 
     /// Function call.
     FunctionCall(FunctionCall),
@@ -531,7 +611,7 @@ impl Expression {
     /// Multiple concatenation.
     pub fn multiple_concat(self, count: usize) -> Self {
         Self::Primary(Primary::MultipleConcatenation(
-            count,
+            Box::new(Expression::number(count.to_string())),
             if let Self::Primary(Primary::Concatenation(concat)) = self {
                 concat
             } else {
@@ -634,7 +714,7 @@ impl ToString for Primary {
             Self::HierarchicalIdentifier(ident, None) => ident.clone(),
             Self::Concatenation(concat) => concat.to_string(),
             Self::MultipleConcatenation(count, concat) => {
-                format!("{{{}{}}}", count, concat.to_string())
+                format!("{{{}{}}}", count.to_string(), concat.to_string())
             }
             Self::FunctionCall(function_call) => function_call.to_string(),
             Self::MintypmaxExpression(expr) => format!("({})", expr.to_string()),
@@ -2,5 +2,7 @@
 
 mod ir;
 pub mod opt;
+mod param;
 
 pub use ir::*;
+pub use param::*;
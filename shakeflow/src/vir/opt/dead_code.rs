@@ -39,7 +39,7 @@ impl OptimizeDeadcodeWalk for ModuleItem {
         match self {
             ModuleItem::Declarations(decls) => {
                 decls.iter().for_each(|decl| {
-                    if let Declaration::Reg(_, _, Some(init)) = decl {
+                    if let Declaration::Reg(_, _, Some(init)) | Declaration::ParamReg(_, _, Some(init)) = decl {
                         init.walk(used);
                     }
                 });
@@ -47,8 +47,10 @@ impl OptimizeDeadcodeWalk for ModuleItem {
             ModuleItem::ContinuousAssigns(conts) => conts.walk(used),
             ModuleItem::ModuleInstantiation(module_inst) => module_inst.walk(used),
             ModuleItem::GeneratedInstantiation(generated_inst) => generated_inst.walk(used),
+            ModuleItem::ParamCheck(cond, _) => cond.walk(used),
             ModuleItem::AlwaysConstruct(_, stmts) => stmts.walk(used),
             ModuleItem::Commented(_, _, items) => items.walk(used),
+            ModuleItem::Assertion(_, _, expr, _) => expr.walk(used),
         }
     }
 }
@@ -206,23 +208,14 @@ impl OptimizeDeadcode for Vec<ModuleItem> {
                     let decls = decls
                         .iter()
                         .filter_map(|decl| match decl {
-                            Declaration::Net(shape, ident) => {
+                            Declaration::Net(_, ident)
+                            | Declaration::Reg(_, ident, _)
+                            | Declaration::Integer(ident)
+                            | Declaration::ParamNet(_, ident)
+                            | Declaration::ParamReg(_, ident, _)
+                            | Declaration::LocalParam(_, ident, _) => {
                                 if used.get(&Expression::ident(ident.clone())).is_some() {
-                                    Some(Declaration::Net(shape.clone(), ident.clone()))
-                                } else {
-                                    None
-                                }
-                            }
-                            Declaration::Reg(shape, ident, init) => {
-                                if used.get(&Expression::ident(ident.clone())).is_some() {
-                                    Some(Declaration::Reg(shape.clone(), ident.clone(), init.clone()))
-                                } else {
-                                    None
-                                }
-                            }
-                            Declaration::Integer(ident) => {
-                                if used.get(&Expression::ident(ident.clone())).is_some() {
-                                    Some(Declaration::Integer(ident.clone()))
+                                    Some(decl.clone())
                                 } else {
                                     None
                                 }
@@ -261,6 +254,7 @@ impl OptimizeDeadcode for Vec<ModuleItem> {
                         Some(ModuleItem::Commented(comment_before.clone(), comment_after.clone(), items))
                     }
                 }
+                ModuleItem::ParamCheck(..) | ModuleItem::Assertion(..) => Some(module_item.clone()),
             })
             .collect()
     }
@@ -339,12 +333,8 @@ pub fn dead_code_opt(module: Module) -> Module {
     while relaxation {
         let mut used = HashSet::new();
 
-        for port_decl in port_decls.clone() {
-            let ident = match port_decl {
-                PortDeclaration::Input(_, ident) => Expression::ident(ident),
-                PortDeclaration::Output(_, ident) => Expression::ident(ident),
-            };
-            used.insert(ident);
+        for port_decl in port_decls.iter() {
+            used.insert(Expression::ident(port_decl.ident().to_string()));
         }
         module_items.walk(&mut used);
 
@@ -353,5 +343,5 @@ pub fn dead_code_opt(module: Module) -> Module {
         module_items = new_module_items;
     }
 
-    Module { name: module.name, port_decls, module_items }
+    Module { name: module.name, params: module.params, port_decls, module_items }
 }
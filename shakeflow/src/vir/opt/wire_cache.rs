@@ -75,10 +75,10 @@ impl OptimizeWireCache for Vec<ModuleItem> {
                     let decls = decls
                         .iter()
                         .filter_map(|decl| match decl {
-                            Declaration::Net(shape, ident) => {
+                            Declaration::Net(_, ident) | Declaration::ParamNet(_, ident) => {
                                 let expr = Expression::ident(ident.clone());
                                 if wire_cache.get(&expr) == expr {
-                                    Some(Declaration::Net(shape.clone(), ident.clone()))
+                                    Some(decl.clone())
                                 } else {
                                     None
                                 }
@@ -89,7 +89,12 @@ impl OptimizeWireCache for Vec<ModuleItem> {
                             Declaration::Reg(shape, ident, None) => {
                                 Some(Declaration::Reg(shape.clone(), ident.clone(), None))
                             }
-                            Declaration::Integer(ident) => Some(Declaration::Integer(ident.clone())),
+                            Declaration::ParamReg(width, ident, init) => Some(Declaration::ParamReg(
+                                width.clone(),
+                                ident.clone(),
+                                init.as_ref().map(|init| init.optimize(wire_cache)),
+                            )),
+                            Declaration::Integer(_) | Declaration::LocalParam(..) => Some(decl.clone()),
                         })
                         .collect::<Vec<_>>();
 
@@ -113,6 +118,7 @@ impl OptimizeWireCache for Vec<ModuleItem> {
                 ModuleItem::GeneratedInstantiation(generated_inst) => {
                     Some(ModuleItem::GeneratedInstantiation(generated_inst.optimize(wire_cache)))
                 }
+                ModuleItem::ParamCheck(cond, message) => Some(ModuleItem::ParamCheck(cond.clone(), message.clone())),
                 ModuleItem::AlwaysConstruct(event, stmts) => {
                     Some(ModuleItem::AlwaysConstruct(event.clone(), stmts.optimize(wire_cache)))
                 }
@@ -124,6 +130,9 @@ impl OptimizeWireCache for Vec<ModuleItem> {
                         Some(ModuleItem::Commented(comment_before.clone(), comment_after.clone(), items))
                     }
                 }
+                ModuleItem::Assertion(kind, event, expr, message) => {
+                    Some(ModuleItem::Assertion(*kind, event.clone(), expr.optimize(wire_cache), message.clone()))
+                }
             })
             .collect()
     }
@@ -251,7 +260,7 @@ impl OptimizeWireCache for Primary {
             }
             Self::Concatenation(concat) => Self::Concatenation(concat.optimize(wire_cache)),
             Self::MultipleConcatenation(count, concat) => {
-                Self::MultipleConcatenation(*count, concat.optimize(wire_cache))
+                Self::MultipleConcatenation(count.clone(), concat.optimize(wire_cache))
             }
             Self::FunctionCall(function_call) => Self::FunctionCall(function_call.optimize(wire_cache)),
             Self::MintypmaxExpression(expr) => Self::MintypmaxExpression(Box::new(expr.optimize(wire_cache))),
@@ -283,15 +292,12 @@ pub fn wire_cache_opt(module: Module) -> Module {
 
     let port_idents = port_decls
         .iter()
-        .map(|port_decl| match port_decl {
-            PortDeclaration::Input(_, ident) => Expression::ident(ident.clone()),
-            PortDeclaration::Output(_, ident) => Expression::ident(ident.clone()),
-        })
+        .map(|port_decl| Expression::ident(port_decl.ident().to_string()))
         .collect::<HashSet<Expression>>();
 
     let mut wire_cache = WireCache::default();
     wire_cache.preprocess(&module_items, &port_idents);
 
     let module_items = module_items.optimize(&mut wire_cache);
-    Module { name: module.name, port_decls, module_items }
+    Module { name: module.name, params: module.params, port_decls, module_items }
 }
@@ -0,0 +1,627 @@
+//! Parameterized modules.
+//!
+//! A parameterized module is generated once for each set of parameter values, and the specializations
+//! are unified into one module. They should have the same structure except for numbers and widths,
+//! which are written as expressions of the parameters.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::*;
+use crate::lir;
+
+/// Unifies the specializations of a module into one module with the parameters.
+///
+/// Each specialization is given with its parameter values, and the first one gives the default
+/// values. Numbers and widths which differ between the specializations are written as linear
+/// expressions of a parameter or its `$clog2` if one of them matches; otherwise they are selected by
+/// the parameter values. Sized numbers are declared as local
+/// parameters, so that their widths also follow the parameters. Since the expressions are only
+/// checked against the specializations, elaboration with other parameter values fails.
+pub fn unify_specializations(
+    name: String, params: &[String], specializations: &[(Vec<usize>, Module)],
+) -> Result<Module, lir::ModuleError> {
+    if specializations.is_empty() {
+        return Err(lir::ModuleError::Misc(format!("parameterized module {} has no specializations", name)));
+    }
+
+    let mut unifier = Unifier {
+        module: name.clone(),
+        params,
+        values: specializations.iter().map(|(values, _)| values.as_slice()).collect(),
+        literals: HashMap::new(),
+        local_params: Vec::new(),
+    };
+
+    let modules = specializations.iter().map(|(_, module)| module).collect::<Vec<_>>();
+    let port_decls = unifier.each(
+        &modules.iter().map(|module| &module.port_decls).collect::<Vec<_>>(),
+        "ports",
+        Unifier::port_decl,
+    )?;
+    let module_items = unifier.module_items(&modules.iter().map(|module| &module.module_items).collect::<Vec<_>>())?;
+
+    let specialized = unifier
+        .values
+        .iter()
+        .map(|values| unifier.cond(values))
+        .reduce(|lhs, rhs| Expression::binary(lir::BinaryOp::Or, lhs, rhs))
+        .unwrap();
+    let mut items =
+        vec![ModuleItem::ParamCheck(specialized, format!("{}: parameter values are not specialized", name))];
+    if !unifier.local_params.is_empty() {
+        items.push(ModuleItem::Declarations(unifier.local_params));
+    }
+    items.extend(module_items);
+
+    Ok(Module {
+        name,
+        params: params.iter().cloned().zip(specializations[0].0.iter().copied()).collect(),
+        port_decls,
+        module_items: items,
+    })
+}
+
+/// Unifier of specializations.
+#[derive(Debug)]
+struct Unifier<'a> {
+    /// Module name.
+    module: String,
+
+    /// Parameter names.
+    params: &'a [String],
+
+    /// Parameter values of the specializations.
+    values: Vec<&'a [usize]>,
+
+    /// Local parameters by the sized numbers of the specializations.
+    literals: HashMap<Vec<String>, String>,
+
+    /// Local parameter declarations.
+    local_params: Vec<Declaration>,
+}
+
+/// Returns `true` if all items are the same.
+fn all_eq<T: PartialEq>(items: &[&T]) -> bool { items.iter().all(|item| *item == items[0]) }
+
+impl<'a> Unifier<'a> {
+    fn mismatch(&self, what: &str) -> lir::ModuleError {
+        lir::ModuleError::ParamMismatch { module: self.module.clone(), what: what.to_string() }
+    }
+
+    /// Projects the items with `f`, which should succeed for all of them.
+    fn project<'b, T, U>(
+        &self, items: &[&'b T], what: &str, f: impl Fn(&'b T) -> Option<U>,
+    ) -> Result<Vec<U>, lir::ModuleError> {
+        items.iter().map(|item| f(item)).collect::<Option<Vec<_>>>().ok_or_else(|| self.mismatch(what))
+    }
+
+    /// Unifies the elements of vectors of the same length.
+    fn each<T, U>(
+        &mut self, items: &[&Vec<T>], what: &str, mut f: impl FnMut(&mut Self, &[&T]) -> Result<U, lir::ModuleError>,
+    ) -> Result<Vec<U>, lir::ModuleError> {
+        if items.iter().any(|item| item.len() != items[0].len()) {
+            return Err(self.mismatch(&format!("the number of {}", what)));
+        }
+        (0..items[0].len()).map(|index| f(self, &items.iter().map(|item| &item[index]).collect::<Vec<_>>())).collect()
+    }
+
+    /// Returns the condition that the parameters have the values.
+    fn cond(&self, values: &[usize]) -> Expression {
+        self.params
+            .iter()
+            .zip(values.iter())
+            .map(|(param, value)| {
+                Expression::binary(
+                    lir::BinaryOp::EqArithmetic,
+                    Expression::ident(param.clone()),
+                    Expression::number(value.to_string()),
+                )
+            })
+            .reduce(|lhs, rhs| Expression::binary(lir::BinaryOp::And, lhs, rhs))
+            .unwrap_or_else(|| Expression::number("1".to_string()))
+    }
+
+    /// Selects the expressions by the parameter values of the specializations.
+    fn select(&self, exprs: Vec<Expression>) -> Expression {
+        let mut branches = self.values.iter().zip(exprs).rev();
+        let (_, last) = branches.next().unwrap();
+        branches
+            .fold(last, |acc, (values, expr)| Expression::conditional(self.cond(values), expr, acc).into_primary())
+            .into_primary()
+    }
+
+    /// Returns the parameter expression for the numbers of the specializations.
+    fn param_expr(&self, numbers: &[usize]) -> Expression {
+        if numbers.iter().all(|number| *number == numbers[0]) {
+            return Expression::number(numbers[0].to_string());
+        }
+
+        for (index, param) in self.params.iter().enumerate() {
+            let values = self.values.iter().map(|values| values[index]).collect::<Vec<_>>();
+            let clog2_values = values.iter().map(|value| clog2(*value)).collect::<Vec<_>>();
+
+            if let Some(expr) = linear_expr(Expression::ident(param.clone()), &values, numbers) {
+                return expr;
+            }
+            let clog2_param = Expression::function_call("$clog2", vec![Expression::ident(param.clone())]);
+            if let Some(expr) = linear_expr(clog2_param, &clog2_values, numbers) {
+                return expr;
+            }
+        }
+
+        self.select(numbers.iter().map(|number| Expression::number(number.to_string())).collect())
+    }
+
+    /// Unifies numbers. Sized numbers which differ are replaced by local parameters.
+    fn number(&mut self, numbers: &[&String]) -> Result<Expression, lir::ModuleError> {
+        if all_eq(numbers) {
+            return Ok(Expression::number(numbers[0].clone()));
+        }
+
+        if let Some(values) = numbers.iter().map(|number| number.parse::<usize>().ok()).collect::<Option<Vec<_>>>() {
+            return Ok(self.param_expr(&values));
+        }
+
+        let widths = self.project(numbers, "numbers", |number| {
+            number.split_once('\'').and_then(|(width, _)| width.parse::<usize>().ok())
+        })?;
+
+        let key = numbers.iter().map(|number| number.to_string()).collect::<Vec<_>>();
+        if let Some(ident) = self.literals.get(&key) {
+            return Ok(Expression::ident(ident.clone()));
+        }
+
+        // Values are written as unsized numbers only if they fit in 32 bits.
+        let value = match numbers.iter().map(|number| literal_value(number)).collect::<Option<Vec<_>>>() {
+            Some(values) => self.param_expr(&values),
+            None => self.select(numbers.iter().map(|number| Expression::number(number.to_string())).collect()),
+        };
+
+        let ident = format!("literal_{}", self.local_params.len());
+        self.local_params.push(Declaration::LocalParam(self.param_expr(&widths), ident.clone(), value));
+        let _ = self.literals.insert(key, ident.clone());
+        Ok(Expression::ident(ident))
+    }
+
+    fn port_decl(&mut self, port_decls: &[&PortDeclaration]) -> Result<PortDeclaration, lir::ModuleError> {
+        let ports = self.project(port_decls, "ports", |port_decl| match port_decl {
+            PortDeclaration::Input(width, ident) => Some((true, *width, ident)),
+            PortDeclaration::Output(width, ident) => Some((false, *width, ident)),
+            _ => None,
+        })?;
+        let (is_input, width, ident) = ports[0];
+        if ports.iter().any(|(is_input_spec, _, ident_spec)| *is_input_spec != is_input || *ident_spec != ident) {
+            return Err(self.mismatch("ports"));
+        }
+
+        let widths = ports.iter().map(|(_, width, _)| *width).collect::<Vec<_>>();
+        Ok(match (is_input, all_eq(&widths.iter().collect::<Vec<_>>())) {
+            (true, true) => PortDeclaration::input(width, ident.clone()),
+            (false, true) => PortDeclaration::output(width, ident.clone()),
+            (true, false) => PortDeclaration::ParamInput(self.param_expr(&widths), ident.clone()),
+            (false, false) => PortDeclaration::ParamOutput(self.param_expr(&widths), ident.clone()),
+        })
+    }
+
+    fn module_items(&mut self, items: &[&Vec<ModuleItem>]) -> Result<Vec<ModuleItem>, lir::ModuleError> {
+        self.each(items, "module items", Self::module_item)
+    }
+
+    fn module_item(&mut self, items: &[&ModuleItem]) -> Result<ModuleItem, lir::ModuleError> {
+        if all_eq(items) {
+            return Ok(items[0].clone());
+        }
+
+        match items[0] {
+            ModuleItem::Declarations(_) => {
+                let decls = self.project(items, "declarations", |item| match item {
+                    ModuleItem::Declarations(decls) => Some(decls),
+                    _ => None,
+                })?;
+                Ok(ModuleItem::Declarations(self.each(&decls, "declarations", Self::declaration)?))
+            }
+            ModuleItem::ContinuousAssigns(_) => {
+                let conts = self.project(items, "continuous assignments", |item| match item {
+                    ModuleItem::ContinuousAssigns(conts) => Some(conts),
+                    _ => None,
+                })?;
+                let conts = self.each(&conts, "continuous assignments", |this, conts| {
+                    let lvalue = this.expr(&conts.iter().map(|cont| &cont.0).collect::<Vec<_>>())?;
+                    let expr = this.expr(&conts.iter().map(|cont| &cont.1).collect::<Vec<_>>())?;
+                    Ok(ContinuousAssign(lvalue, expr))
+                })?;
+                Ok(ModuleItem::ContinuousAssigns(conts))
+            }
+            ModuleItem::ModuleInstantiation(module_inst) => {
+                let module_insts = self.project(items, "module instantiations", |item| match item {
+                    ModuleItem::ModuleInstantiation(module_inst) => Some(module_inst),
+                    _ => None,
+                })?;
+                if module_insts.iter().any(|inst| {
+                    inst.module_name != module_inst.module_name
+                        || inst.inst_name != module_inst.inst_name
+                        || inst.params != module_inst.params
+                }) {
+                    return Err(self.mismatch(&format!("the instantiation {}", module_inst.inst_name)));
+                }
+                let port_connections = self.each(
+                    &module_insts.iter().map(|inst| &inst.port_connections).collect::<Vec<_>>(),
+                    "port connections",
+                    |this, conns| {
+                        if !all_eq(&conns.iter().map(|(port, _)| port).collect::<Vec<_>>()) {
+                            return Err(this.mismatch("port connections"));
+                        }
+                        Ok((conns[0].0.clone(), this.expr(&conns.iter().map(|(_, expr)| expr).collect::<Vec<_>>())?))
+                    },
+                )?;
+                Ok(ModuleItem::ModuleInstantiation(ModuleInstantiation { port_connections, ..module_inst.clone() }))
+            }
+            ModuleItem::GeneratedInstantiation(generated_inst) => {
+                let generated_insts = self.project(items, "generate loops", |item| match item {
+                    ModuleItem::GeneratedInstantiation(generated_inst) => Some(generated_inst),
+                    _ => None,
+                })?;
+                if generated_insts.iter().any(|inst| {
+                    inst.genvar_identifier != generated_inst.genvar_identifier
+                        || inst.loop_count != generated_inst.loop_count
+                }) {
+                    return Err(self.mismatch(&format!("the generate loop of {}", generated_inst.genvar_identifier)));
+                }
+                let loop_body =
+                    self.module_items(&generated_insts.iter().map(|inst| &inst.loop_body).collect::<Vec<_>>())?;
+                Ok(ModuleItem::GeneratedInstantiation(GeneratedInstantiation { loop_body, ..generated_inst.clone() }))
+            }
+            ModuleItem::AlwaysConstruct(event, _) => {
+                let stmts = self.project(items, "always constructs", |item| match item {
+                    ModuleItem::AlwaysConstruct(event_spec, stmts) if event_spec == event => Some(stmts),
+                    _ => None,
+                })?;
+                Ok(ModuleItem::AlwaysConstruct(event.clone(), self.stmts(&stmts)?))
+            }
+            ModuleItem::Commented(comment_before, comment_after, _) => {
+                let module_items = self.project(items, "comments", |item| match item {
+                    ModuleItem::Commented(before, after, module_items)
+                        if before == comment_before && after == comment_after =>
+                    {
+                        Some(module_items)
+                    }
+                    _ => None,
+                })?;
+                Ok(ModuleItem::Commented(
+                    comment_before.clone(),
+                    comment_after.clone(),
+                    self.module_items(&module_items)?,
+                ))
+            }
+            ModuleItem::Assertion(kind, event, _, message) => {
+                let exprs = self.project(items, "assertions", |item| match item {
+                    ModuleItem::Assertion(kind_spec, event_spec, expr, message_spec)
+                        if kind_spec == kind && event_spec == event && message_spec == message =>
+                    {
+                        Some(expr)
+                    }
+                    _ => None,
+                })?;
+                Ok(ModuleItem::Assertion(*kind, event.clone(), self.expr(&exprs)?, message.clone()))
+            }
+            ModuleItem::ParamCheck(..) => Err(self.mismatch("parameter checks")),
+        }
+    }
+
+    fn declaration(&mut self, decls: &[&Declaration]) -> Result<Declaration, lir::ModuleError> {
+        if all_eq(decls) {
+            return Ok(decls[0].clone());
+        }
+
+        let decls = self.project(decls, "declarations", |decl| match decl {
+            Declaration::Net(shape, ident) => Some((false, shape, ident, None)),
+            Declaration::Reg(shape, ident, init) => Some((true, shape, ident, init.as_ref())),
+            _ => None,
+        })?;
+        let (is_reg, shape, ident, init) = decls[0];
+        if decls.iter().any(|(is_reg_spec, shape_spec, ident_spec, init_spec)| {
+            *is_reg_spec != is_reg
+                || shape_spec.dim() != 1
+                || *ident_spec != ident
+                || init_spec.is_some() != init.is_some()
+        }) || shape.dim() != 1
+        {
+            return Err(self.mismatch(&format!("the declaration of {}", ident)));
+        }
+
+        let widths = decls.iter().map(|(_, shape, ..)| shape.width()).collect::<Vec<_>>();
+        let width = self.param_expr(&widths);
+        if !is_reg {
+            return Ok(Declaration::ParamNet(width, ident.clone()));
+        }
+        let init = match init {
+            Some(_) => Some(self.expr(&decls.iter().map(|(.., init)| init.unwrap()).collect::<Vec<_>>())?),
+            None => None,
+        };
+        Ok(Declaration::ParamReg(width, ident.clone(), init))
+    }
+
+    fn stmts(&mut self, stmts: &[&Vec<Statement>]) -> Result<Vec<Statement>, lir::ModuleError> {
+        self.each(stmts, "statements", Self::stmt)
+    }
+
+    fn stmt(&mut self, stmts: &[&Statement]) -> Result<Statement, lir::ModuleError> {
+        if all_eq(stmts) {
+            return Ok(stmts[0].clone());
+        }
+
+        match stmts[0] {
+            Statement::BlockingAssignment(..) | Statement::NonblockingAssignment(..) => {
+                let is_blocking = matches!(stmts[0], Statement::BlockingAssignment(..));
+                let assigns = self.project(stmts, "assignments", |stmt| match stmt {
+                    Statement::BlockingAssignment(lvalue, expr) if is_blocking => Some((lvalue, expr)),
+                    Statement::NonblockingAssignment(lvalue, expr) if !is_blocking => Some((lvalue, expr)),
+                    _ => None,
+                })?;
+                let lvalue = self.expr(&assigns.iter().map(|(lvalue, _)| *lvalue).collect::<Vec<_>>())?;
+                let expr = self.expr(&assigns.iter().map(|(_, expr)| *expr).collect::<Vec<_>>())?;
+                Ok(if is_blocking {
+                    Statement::BlockingAssignment(lvalue, expr)
+                } else {
+                    Statement::NonblockingAssignment(lvalue, expr)
+                })
+            }
+            Statement::Conditional(..) => {
+                let conds = self.project(stmts, "conditional statements", |stmt| match stmt {
+                    Statement::Conditional(cond, then_stmts, else_stmts) => Some((cond, then_stmts, else_stmts)),
+                    _ => None,
+                })?;
+                Ok(Statement::Conditional(
+                    self.expr(&conds.iter().map(|(cond, ..)| *cond).collect::<Vec<_>>())?,
+                    self.stmts(&conds.iter().map(|(_, then_stmts, _)| *then_stmts).collect::<Vec<_>>())?,
+                    self.stmts(&conds.iter().map(|(.., else_stmts)| *else_stmts).collect::<Vec<_>>())?,
+                ))
+            }
+            Statement::Loop(ident, ..) => {
+                let loops = self.project(stmts, "loop statements", |stmt| match stmt {
+                    Statement::Loop(ident_spec, count, body) if ident_spec == ident => Some((count, body)),
+                    _ => None,
+                })?;
+                Ok(Statement::Loop(
+                    ident.clone(),
+                    self.expr(&loops.iter().map(|(count, _)| *count).collect::<Vec<_>>())?,
+                    self.stmts(&loops.iter().map(|(_, body)| *body).collect::<Vec<_>>())?,
+                ))
+            }
+            Statement::Case(..) => {
+                let cases = self.project(stmts, "case statements", |stmt| match stmt {
+                    Statement::Case(case_expr, case_items, default) => Some((case_expr, case_items, default)),
+                    _ => None,
+                })?;
+                let case_items = self.each(
+                    &cases.iter().map(|(_, case_items, _)| *case_items).collect::<Vec<_>>(),
+                    "case items",
+                    |this, case_items| {
+                        Ok((
+                            this.expr(&case_items.iter().map(|(cond, _)| cond).collect::<Vec<_>>())?,
+                            this.stmts(&case_items.iter().map(|(_, stmts)| stmts).collect::<Vec<_>>())?,
+                        ))
+                    },
+                )?;
+                Ok(Statement::Case(
+                    self.expr(&cases.iter().map(|(case_expr, ..)| *case_expr).collect::<Vec<_>>())?,
+                    case_items,
+                    self.stmts(&cases.iter().map(|(.., default)| *default).collect::<Vec<_>>())?,
+                ))
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[&Vec<Expression>]) -> Result<Vec<Expression>, lir::ModuleError> {
+        self.each(exprs, "operands", Self::expr)
+    }
+
+    fn expr(&mut self, exprs: &[&Expression]) -> Result<Expression, lir::ModuleError> {
+        if all_eq(exprs) {
+            return Ok(exprs[0].clone());
+        }
+
+        match exprs[0] {
+            Expression::Primary(_) => {
+                let prims = self.project(exprs, "expressions", |expr| match expr {
+                    Expression::Primary(prim) => Some(prim),
+                    _ => None,
+                })?;
+                self.primary(&prims)
+            }
+            Expression::Unary(op, _) => {
+                let prims = self.project(exprs, "expressions", |expr| match expr {
+                    Expression::Unary(op_spec, prim) if op_spec == op => Some(prim),
+                    _ => None,
+                })?;
+                Ok(Expression::unary(*op, self.primary(&prims)?))
+            }
+            Expression::Binary(_, op, _) => {
+                let operands = self.project(exprs, "expressions", |expr| match expr {
+                    Expression::Binary(lhs, op_spec, rhs) if op_spec == op => Some((lhs.as_ref(), rhs.as_ref())),
+                    _ => None,
+                })?;
+                Ok(Expression::binary(
+                    *op,
+                    self.expr(&operands.iter().map(|(lhs, _)| *lhs).collect::<Vec<_>>())?,
+                    self.expr(&operands.iter().map(|(_, rhs)| *rhs).collect::<Vec<_>>())?,
+                ))
+            }
+            Expression::Conditional(..) => {
+                let operands = self.project(exprs, "expressions", |expr| match expr {
+                    Expression::Conditional(cond, then_expr, else_expr) => {
+                        Some((cond.as_ref(), then_expr.as_ref(), else_expr.as_ref()))
+                    }
+                    _ => None,
+                })?;
+                Ok(Expression::conditional(
+                    self.expr(&operands.iter().map(|(cond, ..)| *cond).collect::<Vec<_>>())?,
+                    self.expr(&operands.iter().map(|(_, then_expr, _)| *then_expr).collect::<Vec<_>>())?,
+                    self.expr(&operands.iter().map(|(.., else_expr)| *else_expr).collect::<Vec<_>>())?,
+                ))
+            }
+        }
+    }
+
+    fn range(&mut self, ranges: &[&Range]) -> Result<Range, lir::ModuleError> {
+        match ranges[0] {
+            Range::Index(_) => {
+                let indices = self.project(ranges, "ranges", |range| match range {
+                    Range::Index(index) => Some(index.as_ref()),
+                    _ => None,
+                })?;
+                Ok(Range::new_index(self.expr(&indices)?))
+            }
+            Range::Range(..) => {
+                let ranges = self.project(ranges, "ranges", |range| match range {
+                    Range::Range(base, offset) => Some((base.as_ref(), offset.as_ref())),
+                    _ => None,
+                })?;
+                Ok(Range::new_range(
+                    self.expr(&ranges.iter().map(|(base, _)| *base).collect::<Vec<_>>())?,
+                    self.expr(&ranges.iter().map(|(_, offset)| *offset).collect::<Vec<_>>())?,
+                ))
+            }
+        }
+    }
+
+    /// Unifies primaries. The result is an expression, since a number may be replaced by a parameter
+    /// expression.
+    fn primary(&mut self, prims: &[&Primary]) -> Result<Expression, lir::ModuleError> {
+        if all_eq(prims) {
+            return Ok(Expression::Primary(prims[0].clone()));
+        }
+
+        match prims[0] {
+            Primary::Number(_) => {
+                let numbers = self.project(prims, "numbers", |prim| match prim {
+                    Primary::Number(number) => Some(number),
+                    _ => None,
+                })?;
+                self.number(&numbers)
+            }
+            Primary::HierarchicalIdentifier(ident, range) => {
+                let ranges = self.project(prims, "identifiers", |prim| match prim {
+                    Primary::HierarchicalIdentifier(ident_spec, range_spec)
+                        if ident_spec == ident && range_spec.is_some() == range.is_some() =>
+                    {
+                        Some(range_spec.as_ref())
+                    }
+                    _ => None,
+                })?;
+                let range = match range {
+                    Some(_) => Some(self.range(&ranges.iter().map(|range| range.unwrap()).collect::<Vec<_>>())?),
+                    None => None,
+                };
+                Ok(Expression::Primary(Primary::HierarchicalIdentifier(ident.clone(), range)))
+            }
+            Primary::Concatenation(_) => {
+                let concats = self.project(prims, "concatenations", |prim| match prim {
+                    Primary::Concatenation(concat) => Some(&concat.exprs),
+                    _ => None,
+                })?;
+                Ok(Expression::Primary(Primary::Concatenation(Concatenation { exprs: self.exprs(&concats)? })))
+            }
+            Primary::MultipleConcatenation(..) => {
+                let concats = self.project(prims, "multiple concatenations", |prim| match prim {
+                    Primary::MultipleConcatenation(count, concat) => Some((count.as_ref(), &concat.exprs)),
+                    _ => None,
+                })?;
+                Ok(Expression::Primary(Primary::MultipleConcatenation(
+                    Box::new(self.expr(&concats.iter().map(|(count, _)| *count).collect::<Vec<_>>())?),
+                    Concatenation { exprs: self.exprs(&concats.iter().map(|(_, exprs)| *exprs).collect::<Vec<_>>())? },
+                )))
+            }
+            Primary::FunctionCall(function_call) => {
+                let args = self.project(prims, "function calls", |prim| match prim {
+                    Primary::FunctionCall(function_call_spec)
+                        if function_call_spec.func_name == function_call.func_name =>
+                    {
+                        Some(&function_call_spec.args)
+                    }
+                    _ => None,
+                })?;
+                Ok(Expression::function_call(&function_call.func_name, self.exprs(&args)?))
+            }
+            Primary::MintypmaxExpression(_) => {
+                let exprs = self.project(prims, "expressions", |prim| match prim {
+                    Primary::MintypmaxExpression(expr) => Some(expr.as_ref()),
+                    _ => None,
+                })?;
+                Ok(self.expr(&exprs)?.into_primary())
+            }
+        }
+    }
+}
+
+/// Returns the value of a sized number if it has no unknown bits and fits in 32 bits.
+fn literal_value(number: &str) -> Option<usize> {
+    let (_, value) = number.split_once('\'')?;
+    let radix = match value.chars().next()? {
+        'b' => 2,
+        'd' => 10,
+        'h' => 16,
+        _ => return None,
+    };
+    u32::from_str_radix(&value[1..].replace('_', ""), radix).ok().map(|value| value as usize)
+}
+
+/// Returns `base * mul / div + offset` if it gives `numbers` for `base` of `values`, where `mul / div`
+/// is positive and the division is exact.
+fn linear_expr(base: Expression, values: &[usize], numbers: &[usize]) -> Option<Expression> {
+    let points =
+        values.iter().zip(numbers).map(|(value, number)| (*value as i128, *number as i128)).collect::<Vec<_>>();
+    let (value0, number0) = points[0];
+    let (value1, number1) = points.iter().find(|(value, _)| *value != value0)?;
+    let (dnumber, dvalue) = (number1 - number0, value1 - value0);
+    if dnumber * dvalue <= 0 {
+        return None;
+    }
+
+    let gcd = gcd(dnumber.abs(), dvalue.abs());
+    let (mul, div) = (dnumber.abs() / gcd, dvalue.abs() / gcd);
+    if (value0 * mul) % div != 0 {
+        return None;
+    }
+    let offset = number0 - value0 * mul / div;
+    if points.iter().any(|(value, number)| (value * mul) % div != 0 || value * mul / div + offset != *number) {
+        return None;
+    }
+
+    let mut expr = base;
+    if mul != 1 {
+        expr = Expression::binary(lir::BinaryOp::Mul, expr, Expression::number(mul.to_string()));
+    }
+    if div != 1 {
+        expr = Expression::binary(lir::BinaryOp::Div, expr, Expression::number(div.to_string()));
+    }
+    match offset.cmp(&0) {
+        Ordering::Greater => {
+            expr = Expression::binary(lir::BinaryOp::Add, expr, Expression::number(offset.to_string()));
+        }
+        Ordering::Less => {
+            expr = Expression::binary(lir::BinaryOp::Sub, expr, Expression::number((-offset).to_string()));
+        }
+        Ordering::Equal => {}
+    }
+    Some(expr.into_primary())
+}
+
+/// Returns the greatest common divisor.
+fn gcd(lhs: i128, rhs: i128) -> i128 {
+    if rhs == 0 {
+        lhs
+    } else {
+        gcd(rhs, lhs % rhs)
+    }
+}
+
+/// Returns the ceiling of the binary logarithm, as `$clog2` does.
+fn clog2(value: usize) -> usize {
+    if value <= 1 {
+        0
+    } else {
+        (usize::BITS - (value - 1).leading_zeros()) as usize
+    }
+}
@@ -9,19 +9,21 @@ use std::io::Write;
 use std::path::Path;
 
 use hashcons::merkle::Merkle;
+use linked_hash_map::LinkedHashMap;
 
 use crate::codegen::*;
 use crate::vir::*;
 use crate::*;
 
 impl Package {
-    fn gen_vir_module<P: AsRef<Path>>(&self, module: &lir::Module, path_dir: P) -> Result<(), PackageError> {
+    pub(crate) fn gen_vir_module<P: AsRef<Path>>(&self, module: &lir::Module, path_dir: P) -> Result<(), PackageError> {
         let path = path_dir.as_ref().join(format!("{}_inner.v", module.get_module_name()));
         let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
 
         let name = format!("{}_inner", module.get_module_name());
-        let module: vir::Module =
-            gen_module::<Virgen>(name, module).map_err(|error| PackageError::Module { error })?.into();
+        let module: vir::Module = gen_module::<Virgen>(name, module, self.reset_style)
+            .map_err(|error| PackageError::Module { error })?
+            .into();
         let module = vir::opt::wire_cache_opt(module);
         let module = vir::opt::dead_code_opt(module);
 
@@ -30,11 +32,51 @@ impl Package {
         Ok(())
     }
 
+    /// Generates Verilog code of the parameterized module.
+    pub(crate) fn gen_vir_param_module<P: AsRef<Path>>(
+        &self, module: &lir::ParamModule, path_dir: P,
+    ) -> Result<(), PackageError> {
+        let path = path_dir.as_ref().join(format!("{}_inner.v", module.get_module_name()));
+        let mut file = File::create(path).map_err(|error| PackageError::Fs { error })?;
+
+        let module = gen_param_module(module, self.reset_style).map_err(|error| PackageError::Module { error })?;
+
+        writeln!(file, "{}", module.to_string()).map_err(|error| PackageError::Fs { error })?;
+
+        Ok(())
+    }
+
     /// Generates Verilog code at the given directory path.
     pub fn gen_vir<P: AsRef<Path>>(mut self, path_dir: P) -> Result<(), PackageError> {
         fs::create_dir_all(path_dir.as_ref()).map_err(|error| PackageError::Fs { error })?;
 
-        let mut submodule_map = HashMap::<String, lir::Module>::new();
+        let (submodules, top_modules) = self.scan_modules()?;
+
+        for submodule in submodules.iter() {
+            self.gen_vir_module(submodule, &path_dir)?;
+        }
+
+        for module in top_modules.iter() {
+            self.gen_vir_module(module, &path_dir)?;
+        }
+
+        for module in self.param_modules.iter() {
+            self.gen_vir_param_module(module, &path_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Scans the modules to be generated, after renaming module instantiations to have unique names.
+    ///
+    /// Returns submodules in topological order and top-level modules, each of which has a unique name.
+    /// Specializations of parameterized modules are not included, since they are generated with the
+    /// parameterized modules.
+    pub(crate) fn scan_modules(&mut self) -> Result<(Vec<lir::Module>, Vec<lir::Module>), PackageError> {
+        self.check_construction()?;
+
+        // Submodules by name, in topological order.
+        let mut submodule_map = LinkedHashMap::<String, lir::Module>::new();
 
         // If the module contains multiple `module_inst`s with the same `inst_name`,
         // append index until name collision no longer occurs.
@@ -55,21 +97,31 @@ impl Package {
             module_inst_names.insert(final_name);
         }
 
+        // Specializations of parameterized modules are generated with the parameterized modules.
+        let param_module_names =
+            self.param_modules.iter().map(|module| module.get_module_name()).collect::<HashSet<_>>();
+
+        // Modules with the same name are compiled once, so they should have the same logic.
+        let mut structural_ids = lir::StructuralIds::default();
+
         // submodules aggregation, to prevent duplicate compilation for same module
         for submodule in self.scan_submodule_inst().into_iter() {
             let name = submodule.get_module_name();
+            if param_module_names.contains(&name) {
+                continue;
+            }
             if let Some(module) = submodule_map.get(&name) {
-                // TODO: Currently we only check the interface type for modules with same name.
-                // But there can be modules with same name, same interface, but different logic.
-                // We should check this.
-                if !module.is_interface_eq(submodule) {
-                    Err(PackageError::Module {
-                        error: lir::ModuleError::Misc(
-                                   format!(
-                            "Module {}, which is instantiated as a submodule, is instantiated multiple times with different interfaces"
-                                , name)
-                        ),
-                    })?
+                if structural_ids.module(module) != structural_ids.module(&submodule) {
+                    if !module.is_interface_eq(submodule) {
+                        Err(PackageError::Module {
+                            error: lir::ModuleError::Misc(
+                                       format!(
+                                "Module {}, which is instantiated as a submodule, is instantiated multiple times with different interfaces"
+                                    , name)
+                            ),
+                        })?
+                    }
+                    Err(PackageError::NameConflict { name })?
                 }
             } else {
                 let _ = submodule_map.insert(name, submodule);
@@ -77,6 +129,7 @@ impl Package {
         }
 
         // Check if top level modules are instantiated as a submodule
+        let mut top_modules = HashMap::<String, usize>::new();
         for module in self.modules.iter() {
             let name = module.get_module_name();
             if submodule_map.get(&name).is_some() {
@@ -86,23 +139,61 @@ impl Package {
                     ),
                 })?
             }
+            let id = structural_ids.module(module);
+            if *top_modules.entry(name.clone()).or_insert(id) != id {
+                Err(PackageError::NameConflict { name })?
+            }
         }
 
-        for submodule in submodule_map.values() {
-            self.gen_vir_module(submodule, &path_dir)?;
+        // Check if modules have combinational loops
+        let specializations =
+            self.param_modules.iter().flat_map(|module| module.specializations.iter().map(|(_, module)| module));
+        for module in self.modules.iter().chain(specializations) {
+            module.check_comb_loops().map_err(|error| PackageError::Module { error })?;
         }
 
-        for module in self.modules.iter() {
-            self.gen_vir_module(module, &path_dir)?;
-        }
+        let top_modules = self
+            .modules
+            .iter()
+            .filter(|module| top_modules.remove(&module.get_module_name()).is_some())
+            .cloned()
+            .collect();
 
-        Ok(())
+        Ok((submodule_map.into_iter().map(|(_, module)| module).collect(), top_modules))
     }
 }
 
 impl From<codegen::Module<Virgen>> for vir::Module {
     fn from(module: codegen::Module<Virgen>) -> Self {
-        vir::Module { name: module.name, port_decls: module.ports, module_items: module.body }
+        vir::Module { name: module.name, params: Vec::new(), port_decls: module.ports, module_items: module.body }
+    }
+}
+
+/// Generates Verilog IR of the parameterized module.
+///
+/// Each specialization is generated and optimized as a module, and then they are unified into one
+/// module whose numbers and widths are written in terms of the parameters.
+fn gen_param_module(module: &lir::ParamModule, reset_style: lir::ResetStyle) -> Result<vir::Module, lir::ModuleError> {
+    let name = format!("{}_inner", module.get_module_name());
+    let specializations = module
+        .specializations
+        .iter()
+        .map(|(values, specialization)| {
+            let specialization: vir::Module = gen_module::<Virgen>(name.clone(), specialization, reset_style)?.into();
+            let specialization = vir::opt::wire_cache_opt(specialization);
+            let specialization = vir::opt::dead_code_opt(specialization);
+            Ok((values.clone(), specialization))
+        })
+        .collect::<Result<Vec<_>, lir::ModuleError>>()?;
+
+    unify_specializations(name, &module.params, &specializations)
+}
+
+/// Returns the condition that the reset signal is asserted.
+fn gen_reset_cond(rst: String, polarity: lir::ResetPolarity) -> Expression {
+    match polarity {
+        lir::ResetPolarity::ActiveHigh => Expression::ident(rst),
+        lir::ResetPolarity::ActiveLow => Expression::unary(lir::UnaryOp::Negation, Expression::ident(rst)),
     }
 }
 
@@ -114,8 +205,8 @@ impl Codegen for Virgen {
     type Body = Vec<ModuleItem>;
     type Ports = Vec<PortDeclaration>;
 
-    fn gen_port_decls(&self, module: &lir::Module) -> Result<Vec<PortDeclaration>, lir::ModuleError> {
-        Ok(gen_port_decls(module)?
+    fn gen_port_decls(&self, module: &lir::Module, ctx: &Context) -> Result<Vec<PortDeclaration>, lir::ModuleError> {
+        Ok(gen_port_decls(module, ctx)?
             .into_iter()
             .map(|(dir, width, name)| match dir {
                 Direction::Input => PortDeclaration::input(width, name),
@@ -127,6 +218,8 @@ impl Codegen for Virgen {
     fn gen_module_composite(
         &self, module: &lir::CompositeModule, ctx: &mut Context,
     ) -> Result<Vec<ModuleItem>, lir::ModuleError> {
+        let clock_domain = ctx.enter_clock_domain(module.clock_domain.clone());
+
         let composite_module = match module.module_typ {
             lir::CompositeModuleTyp::OneToOne => {
                 let mut module_items = vec![];
@@ -166,7 +259,12 @@ impl Codegen for Virgen {
                         lir::ModuleInner::Composite(_, module) => {
                             module_items.append(&mut self.gen_module_composite(module, ctx)?);
                         }
-                        _ => panic!("internal compiler error"),
+                        _ => {
+                            return Err(lir::ModuleError::RegisteredModule {
+                                module: module.name.clone(),
+                                submodule: comp_name,
+                            })
+                        }
                     }
                     ctx.leave_scope();
                 }
@@ -248,7 +346,12 @@ impl Codegen for Virgen {
                             lir::ModuleInner::ModuleInst(module) => {
                                 module_items.append(&mut self.gen_module_inst(module, ctx)?);
                             }
-                            _ => panic!("internal compiler error"),
+                            _ => {
+                                return Err(lir::ModuleError::RegisteredModule {
+                                    module: module.name.clone(),
+                                    submodule: comp_name,
+                                })
+                            }
                         }
                         ctx.leave_scope();
                     }
@@ -293,8 +396,14 @@ impl Codegen for Virgen {
             }
         };
 
+        ctx.leave_clock_domain(clock_domain);
+
         Ok(vec![ModuleItem::Commented(
-            format!("Begin module {}", module.name),
+            join_options("\n", [
+                Some(format!("Begin module {}", module.name)),
+                module.source.map(gen_source_annotation),
+            ])
+            .unwrap(),
             Some(format!("End module {}", module.name)),
             composite_module,
         )])
@@ -335,6 +444,9 @@ impl Codegen for Virgen {
     ///     end
     /// end
     /// ```
+    ///
+    /// With asynchronous reset, the always block is also triggered by the reset signal and the state
+    /// update logic is in the else branch of the reset. Without reset, the reset branch is omitted.
     fn gen_module_fsm(&self, module: &lir::Fsm, ctx: &mut Context) -> Result<Vec<ModuleItem>, lir::ModuleError> {
         let mut module_items = Vec::new();
 
@@ -452,7 +564,10 @@ impl Codegen for Virgen {
                 self.gen_module_fsm_state("st".to_string(), state.into_expr(), ctx, &mut HashMap::new())?;
 
             // state reset
+            let (clk, rst) = ctx.clock_signals();
+            let reset_kind = ctx.reset_kind(module);
             let mut stmts_rst = Vec::new();
+            let mut stmts_init = Vec::new();
 
             state_init
                 .iter()
@@ -465,23 +580,85 @@ impl Codegen for Virgen {
                     }
                 })
                 .for_each(|(reg_name, expr)| {
-                    stmts_rst.push(Statement::nonblocking_assignment(vir::Expression::ident(reg_name), expr.clone()));
+                    // Registers without reset only have initial values.
+                    if reset_kind == lir::ResetKind::None {
+                        stmts_init.push(Statement::blocking_assignment(vir::Expression::ident(reg_name), expr.clone()));
+                    } else {
+                        stmts_rst
+                            .push(Statement::nonblocking_assignment(vir::Expression::ident(reg_name), expr.clone()));
+                    }
                 });
 
+            let event = match (reset_kind, ctx.reset_style().polarity) {
+                (lir::ResetKind::Async, lir::ResetPolarity::ActiveHigh) if !stmts_rst.is_empty() => {
+                    format!("always @(posedge {} or posedge {})", clk, rst)
+                }
+                (lir::ResetKind::Async, lir::ResetPolarity::ActiveLow) if !stmts_rst.is_empty() => {
+                    format!("always @(posedge {} or negedge {})", clk, rst)
+                }
+                _ => format!("always @(posedge {})", clk),
+            };
+
             if !stmts_rst.is_empty() {
-                stmts.push(Statement::Conditional(vir::Expression::ident("rst".to_string()), stmts_rst, Vec::new()));
+                let cond = gen_reset_cond(rst, ctx.reset_style().polarity);
+                match reset_kind {
+                    lir::ResetKind::Sync => stmts.push(Statement::Conditional(cond, stmts_rst, Vec::new())),
+                    lir::ResetKind::Async => stmts = vec![Statement::Conditional(cond, stmts_rst, stmts)],
+                    lir::ResetKind::None => unreachable!(),
+                }
             }
 
             if !decls.is_empty() {
                 module_items.push(vir::ModuleItem::Declarations(decls));
             }
 
+            if !stmts_init.is_empty() {
+                module_items.push(vir::ModuleItem::AlwaysConstruct("initial".to_string(), stmts_init));
+            }
+
             if !stmts.is_empty() {
-                module_items.push(vir::ModuleItem::AlwaysConstruct("always @(posedge clk)".to_string(), stmts));
+                module_items.push(vir::ModuleItem::AlwaysConstruct(event, stmts));
             }
         }
 
-        Ok(module_items)
+        // (5) assertions
+        for (index, assertion) in module.assertions.iter().enumerate() {
+            let target = format!("assert_{}", index);
+            let net_name = join_options("_", [ctx.get_prefix(), Some(target.clone())]).unwrap();
+
+            module_items
+                .push(vir::ModuleItem::Declarations(vec![Declaration::net(lir::Shape::new([1]), net_name.clone())]));
+            module_items.append(&mut self.gen_module_fsm_output(
+                target,
+                assertion.cond.into_expr(),
+                ctx,
+                &mut HashMap::new(),
+            )?);
+            let (clk, rst) = ctx.clock_signals();
+            module_items.push(vir::ModuleItem::Assertion(
+                assertion.kind,
+                format!(
+                    "@(posedge {}) disable iff ({})",
+                    clk,
+                    gen_reset_cond(rst, ctx.reset_style().polarity).to_string()
+                ),
+                vir::Expression::ident(net_name),
+                join_options(": ", [ctx.get_prefix(), Some(assertion.message.clone())]).unwrap(),
+            ));
+        }
+
+        Ok(match module.source {
+            Some(source) => vec![ModuleItem::Commented(
+                join_options("\n", [
+                    Some(format!("Begin FSM {}", module.module_name)),
+                    Some(gen_source_annotation(source)),
+                ])
+                .unwrap(),
+                Some(format!("End FSM {}", module.module_name)),
+                module_items,
+            )],
+            None => module_items,
+        })
     }
 
     /// Generates module instantiation.
@@ -490,7 +667,10 @@ impl Codegen for Virgen {
     ) -> Result<Vec<ModuleItem>, lir::ModuleError> {
         let connections = gen_connections(module, ctx)?
             .into_iter()
-            .map(|(_, port, expr)| (port, vir::Expression::ident(expr)))
+            .map(|(_, port, _, expr, inverted)| {
+                let expr = vir::Expression::ident(expr);
+                (port, if inverted { vir::Expression::unary(lir::UnaryOp::Negation, expr) } else { expr })
+            })
             .collect();
 
         let module_inst = vir::ModuleInstantiation::new(
@@ -499,8 +679,12 @@ impl Codegen for Virgen {
             module.params.clone(),
             connections,
         );
+        let module_item = vir::ModuleItem::ModuleInstantiation(module_inst);
 
-        Ok(vec![vir::ModuleItem::ModuleInstantiation(module_inst)])
+        Ok(vec![match module.source {
+            Some(source) => vir::ModuleItem::Commented(gen_source_annotation(source), None, vec![module_item]),
+            None => module_item,
+        }])
     }
 
     /// Generated connections for virtual module
@@ -674,7 +858,8 @@ impl Virgen {
         let (decls, stmts, expr) = self.gen_expr(&output, ctx, cache)?;
 
         let assignments =
-            match_value_typ_exprs(Some(format!("{}_{}", ctx.get_prefix().unwrap(), target)), output.port_decls(), expr);
+            match_value_typ_exprs(join_options("_", [ctx.get_prefix(), Some(target)]), output.port_decls(), expr)
+                .ok_or_else(|| ctx.expr_structure_error(&output.port_decls()))?;
 
         let mut conts = vec![];
 
@@ -710,7 +895,8 @@ impl Virgen {
         let (decls, mut stmts, expr) = self.gen_expr(&state, ctx, cache)?;
 
         let assignments =
-            match_value_typ_exprs(Some(format!("{}_{}", ctx.get_prefix().unwrap(), target)), state.port_decls(), expr);
+            match_value_typ_exprs(join_options("_", [ctx.get_prefix(), Some(target)]), state.port_decls(), expr)
+                .ok_or_else(|| ctx.expr_structure_error(&state.port_decls()))?;
 
         for (_, var_name, expr) in assignments {
             let reg_name = format!("{}_reg", var_name);
@@ -737,7 +923,7 @@ impl Virgen {
 
         match expr {
             lir::Expr::X { .. } | lir::Expr::Constant { .. } => {
-                let literal = gen_expr_literal(expr).map(|s| {
+                let literal = gen_expr_literal(expr, ctx)?.map(|s| {
                     if s.is_empty() {
                         Expression::number("0".to_string())
                     } else if s.iter().all(|x| *x == LogicValue::False) {
@@ -762,7 +948,7 @@ impl Virgen {
                     CompositeExpr::Struct(mut fields) => {
                         Ok((decls_for_inner, stmts_for_inner, fields.swap_remove(*index)))
                     }
-                    _ => panic!("gen_expr: cannot index bits"),
+                    CompositeExpr::Bits(_) => Err(ctx.expr_structure_error(&inner.into_expr().port_decls())),
                 }
             }
             lir::Expr::Concat { inner, .. } => self.gen_expr(&inner.into_expr(), ctx, cache),
@@ -807,9 +993,9 @@ impl Virgen {
                 let (decls_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), ctx, cache)?;
 
                 let stmt_for_conditional = Statement::Conditional(
-                    exprs_for_cond.into_expr(),
-                    self.assign_exprs(exprs_for_output.clone(), exprs_for_lhs)?,
-                    self.assign_exprs(exprs_for_output.clone(), exprs_for_rhs)?,
+                    exprs_for_cond.try_into_expr(&cond.into_expr().port_decls(), ctx)?,
+                    self.assign_exprs(exprs_for_output.clone(), exprs_for_lhs, &expr.port_decls(), ctx)?,
+                    self.assign_exprs(exprs_for_output.clone(), exprs_for_rhs, &expr.port_decls(), ctx)?,
                 );
 
                 let decls = [decls_for_cond, decls_for_lhs, decls_for_rhs, decls_for_output].concat();
@@ -834,12 +1020,13 @@ impl Virgen {
 
                 let exprs_for_rhs = self.indexing_exprs(
                     exprs_for_inner,
-                    exprs_for_index.into_expr(),
+                    exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?,
                     typ_elt.clone(),
                     inner.into_expr().port_decls(),
+                    ctx,
                 )?;
 
-                let stmts_for_assign = self.assign_exprs(exprs_for_output.clone(), exprs_for_rhs)?;
+                let stmts_for_assign = self.assign_exprs(exprs_for_output.clone(), exprs_for_rhs, typ_elt, ctx)?;
 
                 let decls = [decls_for_inner, decls_for_index, decls_for_output].concat();
                 let stmts = [stmts_for_inner, stmts_for_index, stmts_for_assign].concat();
@@ -855,11 +1042,13 @@ impl Virgen {
 
                 let exprs_for_elts = self.range_indexing_exprs(
                     exprs_for_inner,
-                    exprs_for_from.into_expr(),
+                    exprs_for_from.try_into_expr(&from.into_expr().port_decls(), ctx)?,
                     Expression::number(size.to_string()),
                     typ_elt.clone(),
+                    ctx,
                 )?;
-                let stmts_for_assign = self.assign_exprs(exprs_for_output.clone(), exprs_for_elts)?;
+                let stmts_for_assign =
+                    self.assign_exprs(exprs_for_output.clone(), exprs_for_elts, &expr.port_decls(), ctx)?;
 
                 let decls = [decls_for_inner, decls_for_from, decls_for_output].concat();
                 let stmts = [stmts_for_inner, stmts_for_from, stmts_for_assign].concat();
@@ -872,14 +1061,17 @@ impl Virgen {
 
                 let decls = [decls_for_lhs, decls_for_rhs].concat();
                 let stmts = [stmts_for_lhs, stmts_for_rhs].concat();
-                let exprs = exprs_for_lhs.zip(exprs_for_rhs).map(|(lhs, rhs)| rhs.concat(lhs));
+                let exprs =
+                    exprs_for_lhs.try_zip(exprs_for_rhs, &expr.port_decls(), ctx)?.map(|(lhs, rhs)| rhs.concat(lhs));
 
                 Ok((decls, stmts, exprs))
             }
             lir::Expr::Zip { inner, .. } => {
                 let (decls_for_inner, stmts_for_inner, exprs_for_inner) = inner
                     .iter()
-                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache).expect("gen_expr: zip"))
+                    .map(|expr_id| self.gen_expr(&expr_id.into_expr(), ctx, cache))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
                     .fold(
                         (Vec::new(), Vec::new(), Vec::new()),
                         |(mut acc_decls, mut acc_stmts, mut acc_exprs), (decls, stmts, exprs)| {
@@ -892,7 +1084,8 @@ impl Virgen {
                 let (decls_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), ctx, cache)?;
 
                 let exprs_for_zipped = CompositeExpr::Struct(exprs_for_inner);
-                let stmts_for_assign = self.assign_exprs(exprs_for_output.clone(), exprs_for_zipped)?;
+                let stmts_for_assign =
+                    self.assign_exprs(exprs_for_output.clone(), exprs_for_zipped, &expr.port_decls(), ctx)?;
 
                 let decls = [decls_for_inner.concat(), decls_for_output].concat();
                 let stmts = [stmts_for_inner.concat(), stmts_for_assign].concat();
@@ -902,7 +1095,9 @@ impl Virgen {
             lir::Expr::Struct { inner } => {
                 let (decls, stmts, exprs) = inner
                     .iter()
-                    .map(|(_, inner)| self.gen_expr(&inner.into_expr(), ctx, cache).unwrap())
+                    .map(|(_, inner)| self.gen_expr(&inner.into_expr(), ctx, cache))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
                     .fold((Vec::new(), Vec::new(), Vec::new()), |mut acc, mut x| {
                         acc.0.append(&mut x.0);
                         acc.1.append(&mut x.1);
@@ -921,15 +1116,18 @@ impl Virgen {
                 let (decls_for_elt, stmts_for_elt, exprs_for_elt) = self.gen_expr(&elt.into_expr(), ctx, cache)?;
 
                 let (decls_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), ctx, cache)?;
-                let stmts_for_assign = self.assign_exprs(exprs_for_output.clone(), exprs_for_inner)?;
+                let stmts_for_assign =
+                    self.assign_exprs(exprs_for_output.clone(), exprs_for_inner, &expr.port_decls(), ctx)?;
 
                 let exprs_for_output_elt = self.indexing_exprs(
                     exprs_for_output.clone(),
-                    exprs_for_index.into_expr(),
+                    exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?,
                     elt.into_expr().port_decls(),
                     expr.port_decls(),
+                    ctx,
                 )?;
-                let stmts_for_assign_elt = self.assign_exprs(exprs_for_output_elt, exprs_for_elt)?;
+                let stmts_for_assign_elt =
+                    self.assign_exprs(exprs_for_output_elt, exprs_for_elt, &elt.into_expr().port_decls(), ctx)?;
 
                 let decls = [decls_for_inner, decls_for_index, decls_for_elt, decls_for_output].concat();
                 let stmts =
@@ -945,17 +1143,20 @@ impl Virgen {
                 let (decls_for_elts, stmts_for_elts, exprs_for_elts) = self.gen_expr(&elts.into_expr(), ctx, cache)?;
 
                 let (decls_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), ctx, cache)?;
-                let stmts_for_assign = self.assign_exprs(exprs_for_output.clone(), exprs_for_inner)?;
+                let stmts_for_assign =
+                    self.assign_exprs(exprs_for_output.clone(), exprs_for_inner, &expr.port_decls(), ctx)?;
 
                 let elts_count = elts.into_expr().width() / typ_elt.width();
 
                 let exprs_for_output_elts = self.range_indexing_exprs(
                     exprs_for_output.clone(),
-                    exprs_for_index.into_expr(),
+                    exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?,
                     Expression::number(elts_count.to_string()),
                     typ_elt.clone(),
+                    ctx,
                 )?;
-                let stmts_for_assign_elts = self.assign_exprs(exprs_for_output_elts, exprs_for_elts)?;
+                let stmts_for_assign_elts =
+                    self.assign_exprs(exprs_for_output_elts, exprs_for_elts, &elts.into_expr().port_decls(), ctx)?;
 
                 let decls = [decls_for_inner, decls_for_index, decls_for_elts, decls_for_output].concat();
                 let stmts = [stmts_for_inner, stmts_for_index, stmts_for_elts, stmts_for_assign, stmts_for_assign_elts]
@@ -971,10 +1172,12 @@ impl Virgen {
                     self.gen_expr(&index.into_expr(), ctx, cache)?;
 
                 let (decls_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), ctx, cache)?;
-                let expr_for_index = exprs_for_index.into_expr();
+                let expr_for_index = exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?;
                 let stmts_for_output = self.assign_exprs(
                     exprs_for_output.clone(),
                     exprs_for_inner.map(|expr| expr.with_range(Range::new_index(expr_for_index.clone()))),
+                    &expr.port_decls(),
+                    ctx,
                 )?;
 
                 let decls = [decls_for_inner, decls_for_index, decls_for_output].concat();
@@ -989,10 +1192,10 @@ impl Virgen {
                     self.gen_expr(&index.into_expr(), ctx, cache)?;
                 let (decls_for_elt, stmts_for_elt, exprs_for_elt) = self.gen_expr(&elt.into_expr(), ctx, cache)?;
 
-                let expr_for_index = exprs_for_index.into_expr();
+                let expr_for_index = exprs_for_index.try_into_expr(&index.into_expr().port_decls(), ctx)?;
                 let stmts_for_assign = exprs_for_inner
                     .clone()
-                    .zip(exprs_for_elt)
+                    .try_zip(exprs_for_elt, &elt.into_expr().port_decls(), ctx)?
                     .iter()
                     .map(|(expr_for_inner, expr_for_elt)| {
                         Statement::nonblocking_assignment(
@@ -1022,11 +1225,13 @@ impl Virgen {
                 ) = case_items
                     .iter()
                     .map(|(cond, expr)| {
-                        (
-                            self.gen_expr(&cond.into_expr(), ctx, cache).unwrap(),
-                            self.gen_expr(&expr.into_expr(), ctx, cache).unwrap(),
-                        )
+                        Ok((
+                            self.gen_expr(&cond.into_expr(), ctx, cache)?,
+                            self.gen_expr(&expr.into_expr(), ctx, cache)?,
+                        ))
                     })
+                    .collect::<Result<Vec<_>, lir::ModuleError>>()?
+                    .into_iter()
                     .fold((Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()), |mut acc, x| {
                         acc.0.push(x.0 .0.clone());
                         acc.1.push(x.0 .1.clone());
@@ -1037,27 +1242,34 @@ impl Virgen {
                         acc
                     });
 
-                let (decls_for_default, stmts_for_default, exprs_for_default) =
-                    (*default).map_or((None, None, None), |d| {
-                        let (decls, stmts, exprs) = self.gen_expr(&d.into_expr(), ctx, cache).unwrap();
+                let (decls_for_default, stmts_for_default, exprs_for_default) = match default {
+                    Some(d) => {
+                        let (decls, stmts, exprs) = self.gen_expr(&d.into_expr(), ctx, cache)?;
                         (Some(decls), Some(stmts), Some(exprs))
-                    });
+                    }
+                    None => (None, None, None),
+                };
 
                 let decls_for_default = decls_for_default.unwrap_or_default();
                 let stmts_for_default = stmts_for_default.unwrap_or_default();
 
                 let (decls_for_output, exprs_for_output) = self.alloc_exprs(expr.clone(), ctx, cache)?;
 
+                let typ_case_expr = case_expr.into_expr().port_decls();
                 let stmt_for_case = Statement::Case(
-                    exprs_for_case_expr.into_expr(),
+                    exprs_for_case_expr.try_into_expr(&typ_case_expr, ctx)?,
                     itertools::izip!(exprs_for_case_conds, exprs_for_case_stmts)
                         .map(|(expr_cond, expr_stmt)| {
-                            (expr_cond.into_expr(), self.assign_exprs(exprs_for_output.clone(), expr_stmt).unwrap())
+                            Ok((
+                                expr_cond.try_into_expr(&typ_case_expr, ctx)?,
+                                self.assign_exprs(exprs_for_output.clone(), expr_stmt, &expr.port_decls(), ctx)?,
+                            ))
                         })
-                        .collect::<Vec<_>>(),
-                    exprs_for_default
-                        .map(|exprs| self.assign_exprs(exprs_for_output.clone(), exprs).unwrap())
-                        .unwrap_or_default(),
+                        .collect::<Result<Vec<_>, lir::ModuleError>>()?,
+                    match exprs_for_default {
+                        Some(exprs) => self.assign_exprs(exprs_for_output.clone(), exprs, &expr.port_decls(), ctx)?,
+                        None => Vec::new(),
+                    },
                 );
 
                 let decls = [
@@ -1083,7 +1295,9 @@ impl Virgen {
             lir::Expr::Call { func_name, args, .. } => {
                 let (decls_for_args, stmts_for_args, exprs_for_args) = args
                     .iter()
-                    .map(|arg_expr| self.gen_expr(&arg_expr.into_expr(), ctx, cache).unwrap())
+                    .map(|arg_expr| self.gen_expr(&arg_expr.into_expr(), ctx, cache))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
                     .fold((Vec::new(), Vec::new(), Vec::new()), |mut acc, x| {
                         acc.0.push(x.0);
                         acc.1.push(x.1);
@@ -1097,7 +1311,9 @@ impl Virgen {
 
                 let expr = Expression::function_call(
                     func_name,
-                    exprs_for_args.into_iter().map(|expr| expr.into_expr()).collect::<Vec<_>>(),
+                    itertools::izip!(args, exprs_for_args)
+                        .map(|(arg_expr, exprs)| exprs.try_into_expr(&arg_expr.into_expr().port_decls(), ctx))
+                        .collect::<Result<Vec<_>, _>>()?,
                 );
                 let exprs = CompositeExpr::Bits(expr);
 
@@ -1127,8 +1343,11 @@ impl Virgen {
                             Expression::number(i.to_string()),
                             elt_typ.clone(),
                             expr.port_decls(),
+                            ctx,
                         )?,
                         exprs_for_elt,
+                        elt_typ,
+                        ctx,
                     )?;
 
                     assign_decls.extend(decls_for_elt);
@@ -1166,7 +1385,8 @@ impl Virgen {
         ctx.enter_scope(tree_fold_prefix.clone());
 
         let (decls_for_acc, stmts_for_acc, exprs_for_acc) = self.gen_expr(acc, &mut ctx, cache)?;
-        let stmts_for_acc_init = self.assign_exprs(exprs_for_acc.clone(), exprs_for_inner)?;
+        let stmts_for_acc_init =
+            self.assign_exprs(exprs_for_acc.clone(), exprs_for_inner, &inner.port_decls(), &ctx)?;
 
         let inner_loop_int = ctx.alloc_int_id();
         let decl_for_inner_loop_count = Declaration::integer(inner_loop_int.clone());
@@ -1185,7 +1405,10 @@ impl Virgen {
                 ),
                 lhs.port_decls(),
                 inner.port_decls(),
+                &ctx,
             )?,
+            &lhs.port_decls(),
+            &ctx,
         )?;
 
         let decl_rhs_reg = Declaration::reg_with_typ(rhs.port_decls(), Some(format!("{}_rhs", tree_fold_prefix)));
@@ -1206,7 +1429,10 @@ impl Virgen {
                 ),
                 rhs.port_decls(),
                 inner.port_decls(),
+                &ctx,
             )?,
+            &rhs.port_decls(),
+            &ctx,
         )?;
 
         let (decls_for_loop_op, stmts_for_loop_op, exprs_for_loop_op) = self.gen_expr(op, &mut ctx, cache)?;
@@ -1216,8 +1442,11 @@ impl Virgen {
                 Expression::ident(inner_loop_int.clone()),
                 lhs.port_decls(),
                 inner.port_decls(),
+                &ctx,
             )?,
             exprs_for_loop_op,
+            &lhs.port_decls(),
+            &ctx,
         )?;
 
         let stmt_for_inner_loop = Statement::Loop(
@@ -1272,7 +1501,10 @@ impl Virgen {
                 Expression::number(0.to_string()),
                 lhs.port_decls(),
                 inner.port_decls(),
+                &ctx,
             )?,
+            &lhs.port_decls(),
+            &ctx,
         )?;
 
         let decls = vec![decls_for_inner, decls_for_loop, decl_for_fold_output].concat();
@@ -1321,7 +1553,7 @@ impl Virgen {
         let (decls_for_inner_slice, stmts_for_inner_slice, exprs_for_inner_slice) =
             self.gen_expr(inner_slice, &mut ctx, cache)?;
 
-        let stmt_acc_initialization = self.assign_exprs(exprs_for_acc.clone(), init_expr)?;
+        let stmt_acc_initialization = self.assign_exprs(exprs_for_acc.clone(), init_expr, &acc.port_decls(), &ctx)?;
 
         let decl_inner_slice_reg = Declaration::reg_with_typ(
             inner_slice.port_decls(),
@@ -1335,14 +1567,18 @@ impl Virgen {
                 Expression::ident(loop_int.clone()),
                 typ_elt.clone(),
                 inner.port_decls(),
+                &ctx,
             )?,
+            &inner_slice.port_decls(),
+            &ctx,
         )?;
 
         let (decls_for_loop_body, stmts_for_loop_body, exprs_for_loop_body) =
             self.gen_expr(func, &mut ctx, &mut HashMap::new())?;
 
         // assign output of closure
-        let stmt_for_loop_body_output = self.assign_exprs(exprs_for_acc.clone(), exprs_for_loop_body)?;
+        let stmt_for_loop_body_output =
+            self.assign_exprs(exprs_for_acc.clone(), exprs_for_loop_body, &acc.port_decls(), &ctx)?;
 
         let stmt_for_loop = Statement::Loop(
             loop_int,
@@ -1358,7 +1594,7 @@ impl Virgen {
         let expr_for_fold_output = codegen::CompositeExpr::from_typ(acc.port_decls(), fold_prefix.clone())
             .map(|(ident, _)| Expression::ident(ident));
         let decl_epilogue_reg = Declaration::reg_with_typ(acc.port_decls(), Some(fold_prefix.clone()));
-        let stmt_epilogue = self.assign_exprs(expr_for_fold_output.clone(), exprs_for_acc)?;
+        let stmt_epilogue = self.assign_exprs(expr_for_fold_output.clone(), exprs_for_acc, &acc.port_decls(), &ctx)?;
 
         let decls =
             [decl_acc_reg, decl_inner_slice_reg, decl_epilogue_reg, decls_for_inner, decls_for_init, decls_for_loop]
@@ -1384,7 +1620,7 @@ impl Virgen {
     ) -> Result<(Vec<Declaration>, Vec<Statement>, CompositeExpr<Expression>), lir::ModuleError> {
         let (decls_for_inner, stmts_for_inner, exprs_for_inner) = self.gen_expr(inner, ctx, cache)?;
 
-        let expr = Expression::unary(op, exprs_for_inner.into_expr());
+        let expr = Expression::unary(op, exprs_for_inner.try_into_expr(&inner.port_decls(), ctx)?);
         let exprs = CompositeExpr::Bits(expr);
 
         Ok((decls_for_inner, stmts_for_inner, exprs))
@@ -1397,7 +1633,11 @@ impl Virgen {
         let (decls_for_lhs, stmts_for_lhs, exprs_for_lhs) = self.gen_expr(lhs, ctx, cache)?;
         let (decls_for_rhs, stmts_for_rhs, exprs_for_rhs) = self.gen_expr(rhs, ctx, cache)?;
 
-        let expr = Expression::binary(op, exprs_for_lhs.into_expr(), exprs_for_rhs.into_expr());
+        let expr = Expression::binary(
+            op,
+            exprs_for_lhs.try_into_expr(&lhs.port_decls(), ctx)?,
+            exprs_for_rhs.try_into_expr(&rhs.port_decls(), ctx)?,
+        );
 
         let decls = [decls_for_lhs, decls_for_rhs].concat();
         let stmts = [stmts_for_lhs, stmts_for_rhs].concat();
@@ -1436,7 +1676,10 @@ impl Virgen {
                 Expression::ident(loop_int.clone()),
                 typ_elt.clone(),
                 inner.port_decls(),
+                ctx,
             )?,
+            typ_elt,
+            ctx,
         )?;
 
         let mut ctx = Context::new();
@@ -1451,8 +1694,11 @@ impl Virgen {
                 Expression::ident(loop_int.clone()),
                 func.port_decls(),
                 expr.port_decls(),
+                &ctx,
             )?,
             exprs_for_loop_body,
+            &func.port_decls(),
+            &ctx,
         )?;
 
         let decls_for_loop =
@@ -1498,8 +1744,9 @@ impl Virgen {
                     Expression::ident(loop_int.clone()),
                     expr.port_decls(),
                     inner.port_decls(),
+                    ctx,
                 )?
-                .into_expr(),
+                .try_into_expr(&expr.port_decls(), ctx)?,
             ),
         );
 
@@ -1526,7 +1773,7 @@ impl Virgen {
         }
 
         let (mut decls_for_alloc, new_exprs) = self.alloc_exprs(expr.clone(), ctx, &mut HashMap::new())?;
-        let mut stmts_for_assign = self.assign_exprs(new_exprs.clone(), exprs)?;
+        let mut stmts_for_assign = self.assign_exprs(new_exprs.clone(), exprs, &expr.port_decls(), ctx)?;
 
         decls.append(&mut decls_for_alloc);
         stmts.append(&mut stmts_for_assign);
@@ -1536,30 +1783,35 @@ impl Virgen {
 
     fn indexing_exprs(
         &self, exprs: CompositeExpr<Expression>, index: Expression, typ_elt: lir::PortDecls, typ: lir::PortDecls,
+        ctx: &Context,
     ) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
-        let exprs_for_elt = exprs.zip(typ_elt.into()).zip(typ.into()).map(|((expr, (_, shape_elt)), (_, shape))| {
-            // `gen_expr()` considers all `lir::lir::Expr`s with width 1 as single bit, not an array.
-            if shape.width() > 1 {
-                expr.with_range(Range::new_range(
-                    Expression::binary(
-                        lir::BinaryOp::Mul,
-                        index.clone(),
+        let exprs_for_elt = exprs
+            .try_zip(typ_elt.clone().into(), &typ_elt, ctx)?
+            .try_zip(typ.clone().into(), &typ, ctx)?
+            .map(|((expr, (_, shape_elt)), (_, shape))| {
+                // `gen_expr()` considers all `lir::lir::Expr`s with width 1 as single bit, not an array.
+                if shape.width() > 1 {
+                    expr.with_range(Range::new_range(
+                        Expression::binary(
+                            lir::BinaryOp::Mul,
+                            index.clone(),
+                            Expression::number(shape_elt.width().to_string()),
+                        ),
                         Expression::number(shape_elt.width().to_string()),
-                    ),
-                    Expression::number(shape_elt.width().to_string()),
-                ))
-            } else {
-                expr
-            }
-        });
+                    ))
+                } else {
+                    expr
+                }
+            });
 
         Ok(exprs_for_elt)
     }
 
     fn range_indexing_exprs(
         &self, exprs: CompositeExpr<Expression>, base: Expression, offset: Expression, typ_elt: lir::PortDecls,
+        ctx: &Context,
     ) -> Result<CompositeExpr<Expression>, lir::ModuleError> {
-        let exprs = exprs.zip(typ_elt.into()).map(|(expr, (_, shape))| {
+        let exprs = exprs.try_zip(typ_elt.clone().into(), &typ_elt, ctx)?.map(|(expr, (_, shape))| {
             expr.with_range(Range::new_range(
                 Expression::binary(lir::BinaryOp::Mul, base.clone(), Expression::number(shape.width().to_string())),
                 Expression::binary(lir::BinaryOp::Mul, offset.clone(), Expression::number(shape.width().to_string())),
@@ -1584,12 +1836,71 @@ impl Virgen {
         Ok((decls, exprs))
     }
 
+    /// Assigns `rhs` to `lhs`, which are of type `typ`.
     fn assign_exprs(
-        &self, lhs: CompositeExpr<Expression>, rhs: CompositeExpr<Expression>,
+        &self, lhs: CompositeExpr<Expression>, rhs: CompositeExpr<Expression>, typ: &lir::PortDecls, ctx: &Context,
     ) -> Result<Vec<Statement>, lir::ModuleError> {
-        let stmts =
-            lhs.zip(rhs).iter().map(|(lvalue, expr)| Statement::blocking_assignment(lvalue, expr)).collect::<Vec<_>>();
+        let stmts = lhs
+            .try_zip(rhs, typ, ctx)?
+            .iter()
+            .map(|(lvalue, expr)| Statement::blocking_assignment(lvalue, expr))
+            .collect::<Vec<_>>();
 
         Ok(stmts)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::Module;
+    use crate::testing::*;
+    use crate::*;
+
+    /// Returns a module named `stage`, delaying values by a cycle with the given initial value.
+    fn stage(init: usize) -> Module<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>> {
+        hir::Fsm::<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>, Bits<U<4>>, _>::new(
+            "stage",
+            |fwd, bwd, state| (state, bwd, fwd),
+            init.into(),
+        )
+        .into()
+    }
+
+    /// Returns a module instantiating two stages with the given initial values.
+    fn pipeline(inits: (usize, usize)) -> Module<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>> {
+        composite::<UniChannel<Bits<U<4>>>, UniChannel<Bits<U<4>>>, _>(
+            "pipeline",
+            Some("in"),
+            Some("out"),
+            |input, k| input.comb(k, Some("first"), stage(inits.0)).comb(k, Some("second"), stage(inits.1)),
+        )
+        .build()
+    }
+
+    #[test]
+    fn identical_modules_are_generated_once() {
+        let files = generate(package(pipeline((0, 0))), |package, dir| package.gen_vir(dir)).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["pipeline_inner.v", "stage_inner.v"]);
+        assert!(files["pipeline_inner.v"].contains("stage_inner_inst_first"));
+        assert!(files["pipeline_inner.v"].contains("stage_inner_inst_second"));
+
+        let mut package = Package::default();
+        package.add(pipeline((0, 0)));
+        package.add(pipeline((0, 0)));
+        let files = generate(package, |package, dir| package.gen_vir(dir)).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["pipeline_inner.v", "stage_inner.v"]);
+        assert_eq!(files["pipeline_inner.v"].matches("module pipeline_inner").count(), 1);
+    }
+
+    #[test]
+    fn different_modules_with_the_same_name_are_rejected() {
+        let error = generate(package(pipeline((0, 1))), |package, dir| package.gen_vir(dir)).unwrap_err();
+        assert!(matches!(&error, PackageError::NameConflict { name } if name == "stage"), "{:?}", error);
+
+        let mut package = Package::default();
+        package.add(stage(0));
+        package.add(stage(1));
+        let error = generate(package, |package, dir| package.gen_vir(dir)).unwrap_err();
+        assert!(matches!(&error, PackageError::NameConflict { name } if name == "stage"), "{:?}", error);
+    }
+}